@@ -127,6 +127,7 @@ pub static FPS_NUMBERS_PNG: &[u8] = include_bytes!("../resources/numbers.png");
 pub struct FpsElement<T: Texture> {
     id: Id,
     value: u32,
+    location: Point<i32, Logical>,
     texture: T,
     commit_counter: CommitCounter,
 }
@@ -138,10 +139,16 @@ impl<T: Texture> FpsElement<T> {
             id: Id::new(),
             texture,
             value: 0,
+            location: (0, 0).into(),
             commit_counter: CommitCounter::default(),
         }
     }
 
+    pub fn with_location(mut self, location: Point<i32, Logical>) -> Self {
+        self.location = location;
+        self
+    }
+
     pub fn update_fps(&mut self, fps: u32) {
         if self.value != fps {
             self.value = fps;
@@ -159,8 +166,8 @@ where
         &self.id
     }
 
-    fn location(&self, _scale: Scale<f64>) -> Point<i32, Physical> {
-        (0, 0).into()
+    fn location(&self, scale: Scale<f64>) -> Point<i32, Physical> {
+        self.location.to_physical_precise_round(scale)
     }
 
     fn src(&self) -> Rectangle<f64, Buffer> {
@@ -182,7 +189,7 @@ where
         } else {
             3
         };
-        Rectangle::from_size((24 * digits, 35).into()).to_physical_precise_round(scale)
+        Rectangle::new(self.location, (24 * digits, 35).into()).to_physical_precise_round(scale)
     }
 
     fn current_commit(&self) -> CommitCounter {
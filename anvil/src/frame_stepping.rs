@@ -0,0 +1,134 @@
+//! Manually driven frame production for integration tests
+//!
+//! If `ANVIL_FRAME_STEPPING` is set to a path, all backends only render a frame when requested
+//! through a unix socket created at that path, using deterministic timestamps for frame
+//! callbacks and presentation feedback. The socket accepts one command per line:
+//!
+//! ```text
+//! step [<frames>]   request the given number of frames, one if omitted
+//! status            reply with `frame <sequence> pending <frames>`
+//! ```
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use smithay::{
+    reexports::calloop::{generic::Generic, ping::make_ping, Interest, LoopHandle, Mode, PostAction},
+    utils::FrameStepper,
+};
+use tracing::{info, warn};
+
+/// Environment variable containing the path of the control socket
+pub const FRAME_STEPPING_ENV: &str = "ANVIL_FRAME_STEPPING";
+
+/// Refresh rate in millihertz used for the deterministic timestamps
+const STEPPING_REFRESH: i32 = 60_000;
+
+/// Create a [`FrameStepper`] controlled through the socket referenced by [`FRAME_STEPPING_ENV`], if any
+pub fn init_from_env<D: 'static>(handle: &LoopHandle<'static, D>) -> Option<FrameStepper> {
+    let path = PathBuf::from(std::env::var_os(FRAME_STEPPING_ENV)?);
+
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path).and_then(|listener| {
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }) {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!(path = %path.display(), ?err, "Failed to create frame stepping socket");
+            return None;
+        }
+    };
+
+    let stepper = FrameStepper::new(STEPPING_REFRESH);
+
+    // wake up the event loop, once frames are requested
+    let (ping, ping_source) = make_ping().expect("Failed to create ping");
+    stepper.set_waker(ping);
+    handle
+        .insert_source(ping_source, |_, _, _| {})
+        .expect("Failed to init frame stepping ping source");
+
+    let loop_handle = handle.clone();
+    let listener_stepper = stepper.clone();
+    handle
+        .insert_source(
+            Generic::new(listener, Interest::READ, Mode::Level),
+            move |_, listener, _| {
+                loop {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(err) = stream.set_nonblocking(true) {
+                                warn!(?err, "Failed to set frame stepping client non-blocking");
+                                continue;
+                            }
+                            let stepper = listener_stepper.clone();
+                            let mut buffer = Vec::new();
+                            let res = loop_handle.insert_source(
+                                Generic::new(stream, Interest::READ, Mode::Level),
+                                move |_, stream, _| Ok(handle_client(&stepper, stream, &mut buffer)),
+                            );
+                            if let Err(err) = res {
+                                warn!(?err, "Failed to insert frame stepping client");
+                            }
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                        Err(err) => return Err(err),
+                    }
+                }
+                Ok(PostAction::Continue)
+            },
+        )
+        .expect("Failed to init frame stepping socket source");
+
+    info!(path = %path.display(), "Frame stepping enabled");
+    Some(stepper)
+}
+
+fn handle_client(stepper: &FrameStepper, mut stream: &UnixStream, buffer: &mut Vec<u8>) -> PostAction {
+    let mut chunk = [0u8; 256];
+    loop {
+        match stream.read(&mut chunk) {
+            Ok(0) => return PostAction::Remove,
+            Ok(len) => buffer.extend_from_slice(&chunk[..len]),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!(?err, "Failed to read from frame stepping client");
+                return PostAction::Remove;
+            }
+        }
+    }
+
+    while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+        let line = buffer.drain(..=pos).collect::<Vec<_>>();
+        let line = String::from_utf8_lossy(&line);
+        let mut args = line.split_whitespace();
+        let reply = match (args.next(), args.next()) {
+            (None, _) => continue,
+            (Some("step"), frames) => match frames.map(str::parse::<u64>).unwrap_or(Ok(1)) {
+                Ok(frames) => {
+                    stepper.step(frames);
+                    None
+                }
+                Err(_) => Some("error invalid frame count".to_string()),
+            },
+            (Some("status"), None) => Some(format!(
+                "frame {} pending {}",
+                stepper.sequence(),
+                stepper.pending()
+            )),
+            (Some(cmd), _) => Some(format!("error unknown command {}", cmd)),
+        };
+        if let Some(reply) = reply {
+            if let Err(err) = writeln!(stream, "{}", reply) {
+                warn!(?err, "Failed to reply to frame stepping client");
+                return PostAction::Remove;
+            }
+        }
+    }
+
+    PostAction::Continue
+}
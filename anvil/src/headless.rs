@@ -0,0 +1,182 @@
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            pixman::{PixmanRenderBuffer, PixmanRenderer},
+            Bind, ImportMemWl, Offscreen,
+        },
+    },
+    desktop::space::OutputRenderElementsBuilder,
+    input::keyboard::LedState,
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{
+        calloop::EventLoop,
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+        wayland_server::{protocol::wl_surface, Display},
+    },
+    wayland::presentation::Refresh,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    render::*,
+    state::{take_presentation_feedback, AnvilState, Backend},
+};
+
+pub const OUTPUT_NAME: &str = "headless";
+
+const OUTPUT_SIZE: (i32, i32) = (1280, 800);
+const OUTPUT_REFRESH: i32 = 60_000;
+
+pub struct HeadlessData {
+    renderer: PixmanRenderer,
+    damage_tracker: OutputDamageTracker,
+    full_redraw: bool,
+}
+
+impl Backend for HeadlessData {
+    fn seat_name(&self) -> String {
+        String::from("headless")
+    }
+    fn reset_buffers(&mut self, _output: &Output) {
+        self.full_redraw = true;
+    }
+    fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
+    fn update_led_state(&mut self, _led_state: LedState) {}
+}
+
+pub fn run_headless() {
+    let mut event_loop = EventLoop::try_new().unwrap();
+    let display = Display::new().unwrap();
+    let mut display_handle = display.handle();
+
+    let mut renderer = match PixmanRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(err) => {
+            error!("Failed to initialize pixman renderer: {}", err);
+            return;
+        }
+    };
+    // The renderer stays bound to this buffer for the whole session
+    let bound =
+        Offscreen::<PixmanRenderBuffer>::create_buffer(&mut renderer, Fourcc::Argb8888, OUTPUT_SIZE.into())
+            .and_then(|buffer| renderer.bind(buffer));
+    if let Err(err) = bound {
+        error!("Failed to create offscreen buffer: {}", err);
+        return;
+    }
+
+    let mode = Mode {
+        size: OUTPUT_SIZE.into(),
+        refresh: OUTPUT_REFRESH,
+    };
+
+    let output = Output::new(
+        OUTPUT_NAME.to_string(),
+        PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "Smithay".into(),
+            model: "Headless".into(),
+        },
+    );
+    let _global = output.create_global::<AnvilState<HeadlessData>>(&display.handle());
+    output.change_current_state(Some(mode), None, None, Some((0, 0).into()));
+    output.set_preferred(mode);
+
+    let data = HeadlessData {
+        renderer,
+        damage_tracker: OutputDamageTracker::from_output(&output),
+        full_redraw: false,
+    };
+    let mut state = AnvilState::init(display, event_loop.handle(), data, true);
+    state
+        .shm_state
+        .update_formats(state.backend_data.renderer.shm_formats());
+    state.space.map_output(&output, (0, 0));
+
+    #[cfg(feature = "xwayland")]
+    state.start_xwayland();
+
+    info!("Initialization completed, starting the main loop.");
+
+    let refresh = Duration::from_millis(1_000_000 / OUTPUT_REFRESH as u64);
+    let mut next_frame = Instant::now();
+    let mut sequence = 0;
+
+    while state.running.load(Ordering::SeqCst) {
+        // Without frame stepping frames are produced at the refresh rate of the output
+        let frame_due = if state.frame_stepper.is_some() {
+            state.frame_pending()
+        } else {
+            Instant::now() >= next_frame
+        };
+
+        if frame_due {
+            next_frame = Instant::now() + refresh;
+            let now = state.next_frame_time();
+            let frame_target = now + refresh;
+            state.pre_repaint(&output, frame_target);
+
+            let backend = &mut state.backend_data;
+            let age = if std::mem::take(&mut backend.full_redraw) {
+                0
+            } else {
+                // we always render into the same buffer
+                1
+            };
+            let render_res = render_output(
+                &output,
+                &state.space,
+                OutputRenderElementsBuilder::default(),
+                &mut backend.renderer,
+                &mut backend.damage_tracker,
+                age,
+                state.show_window_preview,
+            );
+
+            match render_res {
+                Ok(render_output_result) => {
+                    let states = render_output_result.states;
+                    if render_output_result.damage.is_some() {
+                        // the frame is "presented" as soon as it is rendered
+                        sequence += 1;
+                        let (time, seq) = state.presentation_time(now, sequence);
+                        let mut feedback = take_presentation_feedback(&output, &state.space, &states);
+                        feedback.presented(
+                            time,
+                            Refresh::fixed(refresh),
+                            seq,
+                            wp_presentation_feedback::Kind::empty(),
+                        );
+                    }
+
+                    // Send frame events so that client start drawing their next frame
+                    state.post_repaint(&output, frame_target, None, &states);
+                }
+                Err(err) => warn!("Rendering error: {:?}", err),
+            }
+        }
+
+        let timeout = if state.frame_stepper.is_some() {
+            // woken up once frames are requested
+            None
+        } else {
+            Some(next_frame.saturating_duration_since(Instant::now()))
+        };
+        let result = event_loop.dispatch(timeout, &mut state);
+        if result.is_err() {
+            state.running.store(false, Ordering::SeqCst);
+        } else {
+            state.space.refresh();
+            state.popups.cleanup();
+            display_handle.flush_clients().unwrap();
+        }
+    }
+}
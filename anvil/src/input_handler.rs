@@ -9,13 +9,18 @@ use smithay::backend::renderer::DebugFlags;
 
 use smithay::{
     backend::input::{
-        self, Axis, AxisSource, Event, InputBackend, InputEvent, KeyState, KeyboardKeyEvent,
-        PointerAxisEvent, PointerButtonEvent,
+        self, Axis, AxisSource, Event, GestureBeginEvent, GestureEndEvent, GesturePinchUpdateEvent as _,
+        GestureSwipeUpdateEvent as _, InputBackend, InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent,
+        PointerButtonEvent,
     },
     desktop::{layer_map_for_output, WindowSurfaceType},
     input::{
         keyboard::{keysyms as xkb, FilterResult, Keysym, ModifiersState},
-        pointer::{AxisFrame, ButtonEvent, MotionEvent},
+        pointer::{
+            AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
+            GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
+            GestureSwipeUpdateEvent, MotionEvent,
+        },
     },
     output::Scale,
     reexports::{
@@ -24,10 +29,8 @@ use smithay::{
     },
     utils::{Logical, Point, Serial, Transform, SERIAL_COUNTER as SCOUNTER},
     wayland::{
-        compositor::with_states,
-        input_method::InputMethodSeat,
-        keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitorSeat,
-        shell::wlr_layer::{KeyboardInteractivity, Layer as WlrLayer, LayerSurfaceCachedState},
+        input_method::InputMethodSeat, keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitorSeat,
+        seat::WaylandFocus, shell::wlr_layer::Layer as WlrLayer,
     },
 };
 
@@ -43,24 +46,18 @@ use crate::state::Backend;
 use smithay::{
     backend::{
         input::{
-            Device, DeviceCapability, GestureBeginEvent, GestureEndEvent, GesturePinchUpdateEvent as _,
-            GestureSwipeUpdateEvent as _, PointerMotionEvent, ProximityState, TabletToolButtonEvent,
+            Device, DeviceCapability, PointerMotionEvent, ProximityState, TabletToolButtonEvent,
             TabletToolEvent, TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState, TouchEvent,
         },
         session::Session,
     },
     input::{
-        pointer::{
-            GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent, GesturePinchEndEvent,
-            GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent,
-            RelativeMotionEvent,
-        },
+        pointer::RelativeMotionEvent,
         touch::{DownEvent, UpEvent},
     },
     reexports::wayland_server::DisplayHandle,
     wayland::{
         pointer_constraints::{with_pointer_constraint, PointerConstraint},
-        seat::WaylandFocus,
         tablet_manager::{TabletDescriptor, TabletSeatTrait},
     },
 };
@@ -127,6 +124,18 @@ impl<BackendData: Backend> AnvilState<BackendData> {
                 }
             }
 
+            KeyAction::ToggleWindowScale => {
+                // shows the window under the pointer at twice its size
+                let location = self.pointer.current_location();
+                if let Some((window, _)) = self.space.element_under(location) {
+                    let scale = match window.0.scale_override() {
+                        Some(_) => None,
+                        None => Some(2.0),
+                    };
+                    window.0.set_scale_override(scale);
+                }
+            }
+
             _ => unreachable!(
                 "Common key action handler encountered backend specific action {:?}",
                 action
@@ -143,26 +152,19 @@ impl<BackendData: Backend> AnvilState<BackendData> {
         let mut suppressed_keys = self.suppressed_keys.clone();
         let keyboard = self.seat.get_keyboard().unwrap();
 
-        for layer in self.layer_shell_state.layer_surfaces().rev() {
-            let data = with_states(layer.wl_surface(), |states| {
-                *states.cached_state.get::<LayerSurfaceCachedState>().current()
-            });
-            if data.keyboard_interactivity == KeyboardInteractivity::Exclusive
-                && (data.layer == WlrLayer::Top || data.layer == WlrLayer::Overlay)
-            {
-                let surface = self.space.outputs().find_map(|o| {
-                    let map = layer_map_for_output(o);
-                    let cloned = map.layers().find(|l| l.layer_surface() == &layer).cloned();
-                    cloned
-                });
-                if let Some(surface) = surface {
-                    keyboard.set_focus(self, Some(surface.into()), serial);
-                    keyboard.input::<(), _>(self, keycode, state, serial, time, |_, _, _| {
-                        FilterResult::Forward
-                    });
-                    return KeyAction::None;
-                };
+        // layer surfaces requiring exclusive focus receive all keys, even if the focus got moved
+        // elsewhere since the last refresh, e.g. by a client activating its window
+        if let Some(layer) = self.exclusive_layer_focus.current().cloned() {
+            let focused = keyboard
+                .current_focus()
+                .and_then(|focus| focus.wl_surface().map(|surface| surface.into_owned()));
+            if !focused.is_some_and(|surface| self.exclusive_layer_focus.allows_focus(&surface)) {
+                keyboard.set_focus(self, Some(layer.into()), serial);
             }
+            keyboard.input::<(), _>(self, keycode, state, serial, time, |_, _, _| {
+                FilterResult::Forward
+            });
+            return KeyAction::None;
         }
 
         let inhibited = self
@@ -260,6 +262,24 @@ impl<BackendData: Backend> AnvilState<BackendData> {
             && (!keyboard.is_grabbed() || input_method.keyboard_grabbed())
             && !touch.map(|touch| touch.is_grabbed()).unwrap_or(false)
         {
+            if self.exclusive_layer_focus.current().is_some() {
+                // only layer surfaces requiring exclusive focus may take it
+                let layer = self.space.output_under(location).next().and_then(|output| {
+                    let point = location - self.space.output_geometry(output).unwrap().loc.to_f64();
+                    let layers = layer_map_for_output(output);
+                    layers
+                        .surface_under(WlrLayer::Overlay, point, WindowSurfaceType::ALL)
+                        .or_else(|| layers.surface_under(WlrLayer::Top, point, WindowSurfaceType::ALL))
+                        .map(|(layer, surface, _)| (layer.clone(), surface))
+                });
+                if let Some((layer, surface)) = layer {
+                    if self.exclusive_layer_focus.allows_focus(&surface) {
+                        keyboard.set_focus(self, Some(layer.into()), serial);
+                    }
+                }
+                return;
+            }
+
             let output = self.space.output_under(location).next().cloned();
             if let Some(output) = output.as_ref() {
                 let output_geo = self.space.output_geometry(output).unwrap();
@@ -303,7 +323,21 @@ impl<BackendData: Backend> AnvilState<BackendData> {
                 self.space.raise_element(&window, true);
                 #[cfg(feature = "xwayland")]
                 if let Some(surface) = window.0.x11_surface() {
-                    self.xwm.as_mut().unwrap().raise_window(surface).unwrap();
+                    let xwm = self.xwm.as_mut().unwrap();
+                    xwm.raise_window(surface).unwrap();
+                    if surface.is_override_redirect() {
+                        // Menus keep the focus on the window they belong to
+                        let target = xwm.keyboard_focus_for(surface).and_then(|target| {
+                            self.space
+                                .elements()
+                                .find(|e| e.0.x11_surface() == Some(&target))
+                                .cloned()
+                        });
+                        if let Some(target) = target {
+                            keyboard.set_focus(self, Some(target.into()), serial);
+                        }
+                        return;
+                    }
                 }
                 keyboard.set_focus(self, Some(window.into()), serial);
                 return;
@@ -349,7 +383,7 @@ impl<BackendData: Backend> AnvilState<BackendData> {
             .and_then(|f| f.get())
             .and_then(|w| w.surface_under(pos - output_geo.loc.to_f64(), WindowSurfaceType::ALL))
         {
-            under = Some((surface, loc + output_geo.loc));
+            under = Some((surface, loc + output_geo.loc.to_f64()));
         } else if let Some(focus) = layers
             .layer_under(WlrLayer::Overlay, pos - output_geo.loc.to_f64())
             .or_else(|| layers.layer_under(WlrLayer::Top, pos - output_geo.loc.to_f64()))
@@ -363,7 +397,7 @@ impl<BackendData: Backend> AnvilState<BackendData> {
                     .map(|(surface, loc)| {
                         (
                             PointerFocusTarget::from(surface),
-                            loc + layer_loc + output_geo.loc,
+                            (loc + layer_loc + output_geo.loc).to_f64(),
                         )
                     })
             })
@@ -372,7 +406,7 @@ impl<BackendData: Backend> AnvilState<BackendData> {
         } else if let Some(focus) = self.space.element_under(pos).and_then(|(window, loc)| {
             window
                 .surface_under(pos - loc.to_f64(), WindowSurfaceType::ALL)
-                .map(|(surface, surf_loc)| (surface, surf_loc + loc))
+                .map(|(surface, surf_loc)| (surface, surf_loc + loc.to_f64()))
         }) {
             under = Some(focus);
         } else if let Some(focus) = layers
@@ -388,14 +422,14 @@ impl<BackendData: Backend> AnvilState<BackendData> {
                     .map(|(surface, loc)| {
                         (
                             PointerFocusTarget::from(surface),
-                            loc + layer_loc + output_geo.loc,
+                            (loc + layer_loc + output_geo.loc).to_f64(),
                         )
                     })
             })
         {
             under = Some(focus)
         };
-        under.map(|(s, l)| (s, l.to_f64()))
+        under
     }
 
     fn on_pointer_axis<B: InputBackend>(&mut self, evt: B::PointerAxisEvent) {
@@ -437,6 +471,108 @@ impl<BackendData: Backend> AnvilState<BackendData> {
             pointer.frame(self);
         }
     }
+
+    fn on_gesture_swipe_begin<B: InputBackend>(&mut self, evt: B::GestureSwipeBeginEvent) {
+        let serial = SCOUNTER.next_serial();
+        let pointer = self.pointer.clone();
+        pointer.gesture_swipe_begin(
+            self,
+            &GestureSwipeBeginEvent {
+                serial,
+                time: evt.time_msec(),
+                fingers: evt.fingers(),
+            },
+        );
+    }
+
+    fn on_gesture_swipe_update<B: InputBackend>(&mut self, evt: B::GestureSwipeUpdateEvent) {
+        let pointer = self.pointer.clone();
+        pointer.gesture_swipe_update(
+            self,
+            &GestureSwipeUpdateEvent {
+                time: evt.time_msec(),
+                delta: evt.delta(),
+            },
+        );
+    }
+
+    fn on_gesture_swipe_end<B: InputBackend>(&mut self, evt: B::GestureSwipeEndEvent) {
+        let serial = SCOUNTER.next_serial();
+        let pointer = self.pointer.clone();
+        pointer.gesture_swipe_end(
+            self,
+            &GestureSwipeEndEvent {
+                serial,
+                time: evt.time_msec(),
+                cancelled: evt.cancelled(),
+            },
+        );
+    }
+
+    fn on_gesture_pinch_begin<B: InputBackend>(&mut self, evt: B::GesturePinchBeginEvent) {
+        let serial = SCOUNTER.next_serial();
+        let pointer = self.pointer.clone();
+        pointer.gesture_pinch_begin(
+            self,
+            &GesturePinchBeginEvent {
+                serial,
+                time: evt.time_msec(),
+                fingers: evt.fingers(),
+            },
+        );
+    }
+
+    fn on_gesture_pinch_update<B: InputBackend>(&mut self, evt: B::GesturePinchUpdateEvent) {
+        let pointer = self.pointer.clone();
+        pointer.gesture_pinch_update(
+            self,
+            &GesturePinchUpdateEvent {
+                time: evt.time_msec(),
+                delta: evt.delta(),
+                scale: evt.scale(),
+                rotation: evt.rotation(),
+            },
+        );
+    }
+
+    fn on_gesture_pinch_end<B: InputBackend>(&mut self, evt: B::GesturePinchEndEvent) {
+        let serial = SCOUNTER.next_serial();
+        let pointer = self.pointer.clone();
+        pointer.gesture_pinch_end(
+            self,
+            &GesturePinchEndEvent {
+                serial,
+                time: evt.time_msec(),
+                cancelled: evt.cancelled(),
+            },
+        );
+    }
+
+    fn on_gesture_hold_begin<B: InputBackend>(&mut self, evt: B::GestureHoldBeginEvent) {
+        let serial = SCOUNTER.next_serial();
+        let pointer = self.pointer.clone();
+        pointer.gesture_hold_begin(
+            self,
+            &GestureHoldBeginEvent {
+                serial,
+                time: evt.time_msec(),
+                fingers: evt.fingers(),
+            },
+        );
+    }
+
+    fn on_gesture_hold_end<B: InputBackend>(&mut self, evt: B::GestureHoldEndEvent) {
+        let serial = SCOUNTER.next_serial();
+        let pointer = self.pointer.clone();
+        pointer.gesture_hold_end(
+            self,
+            &GestureHoldEndEvent {
+                serial,
+                time: evt.time_msec(),
+                cancelled: evt.cancelled(),
+            },
+        );
+    }
 }
 
 #[cfg(any(feature = "winit", feature = "x11"))]
@@ -506,7 +642,8 @@ impl<BackendData: Backend> AnvilState<BackendData> {
                     | KeyAction::Quit
                     | KeyAction::Run(_)
                     | KeyAction::TogglePreview
-                    | KeyAction::ToggleDecorations => self.process_common_key_action(action),
+                    | KeyAction::ToggleDecorations
+                    | KeyAction::ToggleWindowScale => self.process_common_key_action(action),
 
                     _ => tracing::warn!(
                         ?action,
@@ -527,6 +664,14 @@ impl<BackendData: Backend> AnvilState<BackendData> {
             }
             InputEvent::PointerButton { event } => self.on_pointer_button::<B>(event),
             InputEvent::PointerAxis { event } => self.on_pointer_axis::<B>(event),
+            InputEvent::GestureSwipeBegin { event, .. } => self.on_gesture_swipe_begin::<B>(event),
+            InputEvent::GestureSwipeUpdate { event, .. } => self.on_gesture_swipe_update::<B>(event),
+            InputEvent::GestureSwipeEnd { event, .. } => self.on_gesture_swipe_end::<B>(event),
+            InputEvent::GesturePinchBegin { event, .. } => self.on_gesture_pinch_begin::<B>(event),
+            InputEvent::GesturePinchUpdate { event, .. } => self.on_gesture_pinch_update::<B>(event),
+            InputEvent::GesturePinchEnd { event, .. } => self.on_gesture_pinch_end::<B>(event),
+            InputEvent::GestureHoldBegin { event, .. } => self.on_gesture_hold_begin::<B>(event),
+            InputEvent::GestureHoldEnd { event, .. } => self.on_gesture_hold_end::<B>(event),
             _ => (), // other events are not handled in anvil (yet)
         }
     }
@@ -715,13 +860,15 @@ impl AnvilState<UdevData> {
                     debug_flags.toggle(DebugFlags::TINT);
                     self.backend_data.set_debug_flags(debug_flags);
                 }
+                KeyAction::ReloadOutputConfig => self.reload_output_config(),
 
                 action => match action {
                     KeyAction::None
                     | KeyAction::Quit
                     | KeyAction::Run(_)
                     | KeyAction::TogglePreview
-                    | KeyAction::ToggleDecorations => self.process_common_key_action(action),
+                    | KeyAction::ToggleDecorations
+                    | KeyAction::ToggleWindowScale => self.process_common_key_action(action),
 
                     _ => unreachable!(),
                 },
@@ -1051,108 +1198,6 @@ impl AnvilState<UdevData> {
         }
     }
 
-    fn on_gesture_swipe_begin<B: InputBackend>(&mut self, evt: B::GestureSwipeBeginEvent) {
-        let serial = SCOUNTER.next_serial();
-        let pointer = self.pointer.clone();
-        pointer.gesture_swipe_begin(
-            self,
-            &GestureSwipeBeginEvent {
-                serial,
-                time: evt.time_msec(),
-                fingers: evt.fingers(),
-            },
-        );
-    }
-
-    fn on_gesture_swipe_update<B: InputBackend>(&mut self, evt: B::GestureSwipeUpdateEvent) {
-        let pointer = self.pointer.clone();
-        pointer.gesture_swipe_update(
-            self,
-            &GestureSwipeUpdateEvent {
-                time: evt.time_msec(),
-                delta: evt.delta(),
-            },
-        );
-    }
-
-    fn on_gesture_swipe_end<B: InputBackend>(&mut self, evt: B::GestureSwipeEndEvent) {
-        let serial = SCOUNTER.next_serial();
-        let pointer = self.pointer.clone();
-        pointer.gesture_swipe_end(
-            self,
-            &GestureSwipeEndEvent {
-                serial,
-                time: evt.time_msec(),
-                cancelled: evt.cancelled(),
-            },
-        );
-    }
-
-    fn on_gesture_pinch_begin<B: InputBackend>(&mut self, evt: B::GesturePinchBeginEvent) {
-        let serial = SCOUNTER.next_serial();
-        let pointer = self.pointer.clone();
-        pointer.gesture_pinch_begin(
-            self,
-            &GesturePinchBeginEvent {
-                serial,
-                time: evt.time_msec(),
-                fingers: evt.fingers(),
-            },
-        );
-    }
-
-    fn on_gesture_pinch_update<B: InputBackend>(&mut self, evt: B::GesturePinchUpdateEvent) {
-        let pointer = self.pointer.clone();
-        pointer.gesture_pinch_update(
-            self,
-            &GesturePinchUpdateEvent {
-                time: evt.time_msec(),
-                delta: evt.delta(),
-                scale: evt.scale(),
-                rotation: evt.rotation(),
-            },
-        );
-    }
-
-    fn on_gesture_pinch_end<B: InputBackend>(&mut self, evt: B::GesturePinchEndEvent) {
-        let serial = SCOUNTER.next_serial();
-        let pointer = self.pointer.clone();
-        pointer.gesture_pinch_end(
-            self,
-            &GesturePinchEndEvent {
-                serial,
-                time: evt.time_msec(),
-                cancelled: evt.cancelled(),
-            },
-        );
-    }
-
-    fn on_gesture_hold_begin<B: InputBackend>(&mut self, evt: B::GestureHoldBeginEvent) {
-        let serial = SCOUNTER.next_serial();
-        let pointer = self.pointer.clone();
-        pointer.gesture_hold_begin(
-            self,
-            &GestureHoldBeginEvent {
-                serial,
-                time: evt.time_msec(),
-                fingers: evt.fingers(),
-            },
-        );
-    }
-
-    fn on_gesture_hold_end<B: InputBackend>(&mut self, evt: B::GestureHoldEndEvent) {
-        let serial = SCOUNTER.next_serial();
-        let pointer = self.pointer.clone();
-        pointer.gesture_hold_end(
-            self,
-            &GestureHoldEndEvent {
-                serial,
-                time: evt.time_msec(),
-                cancelled: evt.cancelled(),
-            },
-        );
-    }
-
     fn touch_location_transformed<B: InputBackend, E: AbsolutePositionEvent<B>>(
         &self,
         evt: &E,
@@ -1291,6 +1336,10 @@ enum KeyAction {
     RotateOutput,
     ToggleTint,
     ToggleDecorations,
+    /// Toggle the scale override of the window under the pointer
+    ToggleWindowScale,
+    /// Re-read the output layout configuration
+    ReloadOutputConfig,
     /// Do nothing more
     None,
 }
@@ -1323,6 +1372,10 @@ fn process_keyboard_shortcut(modifiers: ModifiersState, keysym: Keysym) -> Optio
         Some(KeyAction::ToggleTint)
     } else if modifiers.logo && modifiers.shift && keysym == Keysym::D {
         Some(KeyAction::ToggleDecorations)
+    } else if modifiers.logo && modifiers.shift && keysym == Keysym::S {
+        Some(KeyAction::ToggleWindowScale)
+    } else if modifiers.logo && modifiers.shift && keysym == Keysym::O {
+        Some(KeyAction::ReloadOutputConfig)
     } else {
         None
     }
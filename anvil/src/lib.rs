@@ -2,7 +2,7 @@
 // If no backend is enabled, a large portion of the codebase is unused.
 // So silence this useless warning for the CI.
 #![cfg_attr(
-    not(any(feature = "winit", feature = "x11", feature = "udev")),
+    not(any(feature = "winit", feature = "x11", feature = "udev", feature = "headless")),
     allow(dead_code, unused_imports)
 )]
 
@@ -10,7 +10,12 @@
 pub mod cursor;
 pub mod drawing;
 pub mod focus;
+pub mod frame_stepping;
+#[cfg(feature = "headless")]
+pub mod headless;
 pub mod input_handler;
+#[cfg(feature = "udev")]
+pub mod output_config;
 pub mod render;
 pub mod shell;
 pub mod state;
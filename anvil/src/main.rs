@@ -5,6 +5,8 @@ static POSSIBLE_BACKENDS: &[&str] = &[
     "--tty-udev : Run anvil as a tty udev client (requires root if without logind).",
     #[cfg(feature = "x11")]
     "--x11 : Run anvil as an X11 client.",
+    #[cfg(feature = "headless")]
+    "--headless : Run anvil without any display, rendering offscreen.",
 ];
 
 #[cfg(feature = "profile-with-tracy-mem")]
@@ -49,6 +51,11 @@ fn main() {
             tracing::info!("Starting anvil with x11 backend");
             anvil::x11::run_x11();
         }
+        #[cfg(feature = "headless")]
+        Some("--headless") => {
+            tracing::info!("Starting anvil with headless backend");
+            anvil::headless::run_headless();
+        }
         Some(other) => {
             tracing::error!("Unknown backend: {}", other);
         }
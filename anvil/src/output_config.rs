@@ -0,0 +1,232 @@
+//! Output layout configuration
+//!
+//! The file referenced by `ANVIL_OUTPUT_CONFIG` contains one line per output:
+//!
+//! ```text
+//! # name   options
+//! DP-1     position=0,0 scale=1.5
+//! HDMI-A-1 position=2560,0 transform=90
+//! ```
+//!
+//! Supported options are `position=<x>,<y>` in logical coordinates, `scale=<factor>` and
+//! `transform=<normal|90|180|270|flipped|flipped-90|flipped-180|flipped-270>`.
+//! Outputs without a configured position are placed side by side to the right of all
+//! positioned outputs.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use smithay::{
+    output::{Output, Scale},
+    utils::{Logical, Point, Transform},
+};
+use tracing::{info, warn};
+
+use crate::shell::ConfiguredPosition;
+
+/// Environment variable containing the path of the configuration file
+pub const OUTPUT_CONFIG_ENV: &str = "ANVIL_OUTPUT_CONFIG";
+
+/// Configuration of a single output
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OutputConfig {
+    pub position: Option<Point<i32, Logical>>,
+    pub scale: Option<f64>,
+    pub transform: Option<Transform>,
+}
+
+/// Configuration of all outputs, indexed by connector name
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OutputLayout {
+    path: Option<PathBuf>,
+    outputs: HashMap<String, OutputConfig>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("line {line}: {message}")]
+pub struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl OutputLayout {
+    /// Load the configuration file referenced by [`OUTPUT_CONFIG_ENV`], if any
+    pub fn from_env() -> OutputLayout {
+        let Some(path) = std::env::var_os(OUTPUT_CONFIG_ENV).map(PathBuf::from) else {
+            return OutputLayout::default();
+        };
+        let mut layout = OutputLayout {
+            path: Some(path),
+            outputs: HashMap::new(),
+        };
+        layout.reload();
+        layout
+    }
+
+    /// Re-read the configuration file
+    ///
+    /// Keeps the previous configuration, if the file can't be read or parsed.
+    pub fn reload(&mut self) {
+        let Some(path) = self.path.as_ref() else {
+            return;
+        };
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => {
+                warn!(?path, ?err, "Failed to read output configuration");
+                return;
+            }
+        };
+        match OutputLayout::parse(&content) {
+            Ok(outputs) => {
+                info!(?path, "Loaded output configuration for {} outputs", outputs.len());
+                self.outputs = outputs;
+            }
+            Err(err) => warn!(?path, "Invalid output configuration: {}", err),
+        }
+    }
+
+    fn parse(content: &str) -> Result<HashMap<String, OutputConfig>, ParseError> {
+        let mut outputs = HashMap::new();
+        for (idx, line) in content.lines().enumerate() {
+            let error = |message: String| ParseError {
+                line: idx + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap().trim();
+            let mut words = line.split_whitespace();
+            let Some(name) = words.next() else {
+                continue;
+            };
+
+            let mut config = OutputConfig::default();
+            for option in words {
+                let (key, value) = option
+                    .split_once('=')
+                    .ok_or_else(|| error(format!("expected <option>=<value>, got `{option}`")))?;
+                match key {
+                    "position" => {
+                        let position = value
+                            .split_once(',')
+                            .and_then(|(x, y)| Some((x.parse::<i32>().ok()?, y.parse::<i32>().ok()?)))
+                            .ok_or_else(|| error(format!("invalid position `{value}`")))?;
+                        config.position = Some(position.into());
+                    }
+                    "scale" => {
+                        let scale = value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|scale| *scale > 0.0)
+                            .ok_or_else(|| error(format!("invalid scale `{value}`")))?;
+                        config.scale = Some(scale);
+                    }
+                    "transform" => {
+                        let transform = match value {
+                            "normal" => Transform::Normal,
+                            "90" => Transform::_90,
+                            "180" => Transform::_180,
+                            "270" => Transform::_270,
+                            "flipped" => Transform::Flipped,
+                            "flipped-90" => Transform::Flipped90,
+                            "flipped-180" => Transform::Flipped180,
+                            "flipped-270" => Transform::Flipped270,
+                            _ => return Err(error(format!("invalid transform `{value}`"))),
+                        };
+                        config.transform = Some(transform);
+                    }
+                    _ => return Err(error(format!("unknown option `{key}`"))),
+                }
+            }
+            outputs.insert(name.to_string(), config);
+        }
+        Ok(outputs)
+    }
+
+    /// Returns the configuration of an output
+    pub fn get(&self, name: &str) -> Option<&OutputConfig> {
+        self.outputs.get(name)
+    }
+
+    /// Apply the configured scale and transform to an output and record its configured position
+    ///
+    /// Returns true, if the mode of the output changed and its buffers should be reset.
+    /// The position itself is applied by [`fixup_positions`](crate::shell::fixup_positions).
+    pub fn apply(&self, output: &Output) -> bool {
+        let config = self.get(&output.name()).copied().unwrap_or_default();
+
+        output.user_data().insert_if_missing(ConfiguredPosition::default);
+        *output
+            .user_data()
+            .get::<ConfiguredPosition>()
+            .unwrap()
+            .0
+            .lock()
+            .unwrap() = config.position;
+
+        let scale = config
+            .scale
+            .map(Scale::Fractional)
+            .filter(|scale| scale.fractional_scale() != output.current_scale().fractional_scale());
+        let transform = config
+            .transform
+            .filter(|transform| *transform != output.current_transform());
+        if scale.is_none() && transform.is_none() {
+            return false;
+        }
+
+        output.change_current_state(None, transform, scale, None);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid() {
+        let outputs = OutputLayout::parse(
+            "# name   options\n\
+             DP-1     position=0,0 scale=1.5\n\
+             \n\
+             HDMI-A-1 position=2560,-200 transform=flipped-90 # rotated\n\
+             eDP-1\n",
+        )
+        .unwrap();
+
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(
+            outputs["DP-1"],
+            OutputConfig {
+                position: Some((0, 0).into()),
+                scale: Some(1.5),
+                transform: None,
+            }
+        );
+        assert_eq!(
+            outputs["HDMI-A-1"],
+            OutputConfig {
+                position: Some((2560, -200).into()),
+                scale: None,
+                transform: Some(Transform::Flipped90),
+            }
+        );
+        assert_eq!(outputs["eDP-1"], OutputConfig::default());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let error = |content: &str| OutputLayout::parse(content).unwrap_err().to_string();
+
+        assert_eq!(
+            error("DP-1 position=0,0\nDP-2 scale"),
+            "line 2: expected <option>=<value>, got `scale`"
+        );
+        assert_eq!(error("DP-1 position=10"), "line 1: invalid position `10`");
+        assert_eq!(error("DP-1 position=a,0"), "line 1: invalid position `a,0`");
+        assert_eq!(error("DP-1 scale=0"), "line 1: invalid scale `0`");
+        assert_eq!(error("DP-1 scale=-1"), "line 1: invalid scale `-1`");
+        assert_eq!(error("DP-1 transform=45"), "line 1: invalid transform `45`");
+        assert_eq!(error("DP-1 mode=1920x1080"), "line 1: unknown option `mode`");
+    }
+}
@@ -12,7 +12,8 @@ use smithay::{
         Color32F, ImportAll, ImportMem, Renderer,
     },
     desktop::space::{
-        constrain_space_element, ConstrainBehavior, ConstrainReference, Space, SpaceRenderElements,
+        constrain_space_element, ConstrainBehavior, ConstrainReference, OutputRenderElementsBuilder, Space,
+        SpaceRenderElements,
     },
     output::Output,
     utils::{Point, Rectangle, Size},
@@ -139,7 +140,7 @@ where
 pub fn output_elements<R>(
     output: &Output,
     space: &Space<WindowElement>,
-    custom_elements: impl IntoIterator<Item = CustomRenderElements<R>>,
+    mut builder: OutputRenderElementsBuilder<OutputRenderElements<R, WindowRenderElement<R>>>,
     renderer: &mut R,
     show_window_preview: bool,
 ) -> (Vec<OutputRenderElements<R, WindowRenderElement<R>>>, Color32F)
@@ -156,36 +157,23 @@ where
         let window_render_elements: Vec<WindowRenderElement<R>> =
             AsRenderElements::<R>::render_elements(&window, renderer, (0, 0).into(), scale, 1.0);
 
-        let elements = custom_elements
-            .into_iter()
-            .map(OutputRenderElements::from)
-            .chain(
-                window_render_elements
-                    .into_iter()
-                    .map(|e| OutputRenderElements::Window(Wrap::from(e))),
-            )
-            .collect::<Vec<_>>();
-        (elements, CLEAR_COLOR_FULLSCREEN)
+        builder.underlay(
+            window_render_elements
+                .into_iter()
+                .map(|e| OutputRenderElements::Window(Wrap::from(e))),
+        );
+        (builder.build(), CLEAR_COLOR_FULLSCREEN)
     } else {
-        let mut output_render_elements = custom_elements
-            .into_iter()
-            .map(OutputRenderElements::from)
-            .collect::<Vec<_>>();
-
         if show_window_preview && space.elements_for_output(output).count() > 0 {
-            output_render_elements.extend(space_preview_elements(renderer, space, output));
+            builder.custom(space_preview_elements::<
+                _,
+                OutputRenderElements<R, WindowRenderElement<R>>,
+            >(renderer, space, output));
         }
 
-        let space_elements = smithay::desktop::space::space_render_elements::<_, WindowElement, _>(
-            renderer,
-            [space],
-            output,
-            1.0,
-        )
-        .expect("output without mode?");
-        output_render_elements.extend(space_elements.into_iter().map(OutputRenderElements::Space));
+        builder.spaces(renderer, [space], output, 1.0);
 
-        (output_render_elements, CLEAR_COLOR)
+        (builder.build(), CLEAR_COLOR)
     }
 }
 
@@ -193,7 +181,7 @@ where
 pub fn render_output<'a, 'd, R>(
     output: &'a Output,
     space: &'a Space<WindowElement>,
-    custom_elements: impl IntoIterator<Item = CustomRenderElements<R>>,
+    builder: OutputRenderElementsBuilder<OutputRenderElements<R, WindowRenderElement<R>>>,
     renderer: &'a mut R,
     damage_tracker: &'d mut OutputDamageTracker,
     age: usize,
@@ -203,7 +191,6 @@ where
     R: Renderer + ImportAll + ImportMem,
     R::TextureId: Clone + 'static,
 {
-    let (elements, clear_color) =
-        output_elements(output, space, custom_elements, renderer, show_window_preview);
+    let (elements, clear_color) = output_elements(output, space, builder, renderer, show_window_preview);
     damage_tracker.render_output(renderer, age, &elements, clear_color)
 }
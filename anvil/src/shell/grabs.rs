@@ -67,7 +67,8 @@ impl<BackendData: Backend> PointerGrab<AnvilState<BackendData>> for PointerMoveS
         handle.button(data, event);
         if handle.current_pressed().is_empty() {
             // No more buttons are pressed, release the grab.
-            handle.unset_grab(self, data, event.serial, event.time, true);
+            handle.unset_grab(self, data, event.serial, event.time, false);
+            refresh_pointer_focus(data, handle, event);
         }
     }
 
@@ -526,6 +527,9 @@ impl<BackendData: Backend> PointerGrab<AnvilState<BackendData>> for PointerResiz
                     });
                 }
             }
+
+            // Resizing by the top or left edge moved the window, the focus of the grab start is outdated
+            refresh_pointer_focus(data, handle, event);
         }
     }
 
@@ -867,3 +871,22 @@ impl<BackendData: Backend> TouchGrab<AnvilState<BackendData>> for TouchResizeSur
 
     fn unset(&mut self, _data: &mut AnvilState<BackendData>) {}
 }
+
+/// Focus the surface under the pointer after a grab moved windows underneath it
+fn refresh_pointer_focus<BackendData: Backend>(
+    data: &mut AnvilState<BackendData>,
+    handle: &mut PointerInnerHandle<'_, AnvilState<BackendData>>,
+    event: &ButtonEvent,
+) {
+    let location = handle.current_location();
+    let under = data.surface_under(location);
+    handle.motion(
+        data,
+        under,
+        &MotionEvent {
+            location,
+            serial: event.serial,
+            time: event.time,
+        },
+    );
+}
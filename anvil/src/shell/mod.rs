@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, sync::Mutex};
 
 #[cfg(feature = "xwayland")]
 use smithay::xwayland::XWaylandClientData;
@@ -9,10 +9,10 @@ use smithay::wayland::drm_syncobj::DrmSyncobjCachedState;
 use smithay::{
     backend::renderer::utils::on_commit_buffer_handler,
     desktop::{
-        layer_map_for_output, space::SpaceElement, LayerSurface, PopupKind, PopupManager, Space,
-        WindowSurfaceType,
+        layer_map_for_output, space::SpaceElement, ExclusiveFocusChange, LayerSurface, PopupKind,
+        PopupManager, Space, WindowSurfaceType,
     },
-    input::pointer::{CursorImageStatus, CursorImageSurfaceData},
+    input::pointer::{CursorImageStatus, CursorImageSurfaceData, MotionEvent},
     output::Output,
     reexports::{
         calloop::Interest,
@@ -21,11 +21,11 @@ use smithay::{
             Client, Resource,
         },
     },
-    utils::{IsAlive, Logical, Point, Rectangle, Size},
+    utils::{IsAlive, Logical, Point, Rectangle, Size, SERIAL_COUNTER},
     wayland::{
         buffer::BufferHandler,
         compositor::{
-            add_blocker, add_pre_commit_hook, get_parent, is_sync_subsurface, with_states,
+            add_blocker, add_pre_commit_hook, get_parent, get_role, is_sync_subsurface, with_states,
             with_surface_tree_upward, BufferAssignment, CompositorClientState, CompositorHandler,
             CompositorState, SurfaceAttributes, TraversalAction,
         },
@@ -33,7 +33,7 @@ use smithay::{
         shell::{
             wlr_layer::{
                 Layer, LayerSurface as WlrLayerSurface, LayerSurfaceData, WlrLayerShellHandler,
-                WlrLayerShellState,
+                WlrLayerShellState, LAYER_SURFACE_ROLE,
             },
             xdg::XdgToplevelSurfaceData,
         },
@@ -141,7 +141,12 @@ impl<BackendData: Backend> CompositorHandler for AnvilState<BackendData> {
             if let Some(dmabuf) = maybe_dmabuf {
                 #[cfg(feature = "udev")]
                 if let Some(acquire_point) = acquire_point {
-                    if let Ok((blocker, source)) = acquire_point.generate_blocker() {
+                    let blocker = if state.backend_data.gpu_acquire_waits() {
+                        acquire_point.generate_submission_blocker()
+                    } else {
+                        acquire_point.generate_blocker()
+                    };
+                    if let Ok((blocker, source)) = blocker {
                         let client = surface.client().unwrap();
                         let res = state.handle.insert_source(source, move |_, _, data| {
                             let dh = data.display_handle.clone();
@@ -171,6 +176,18 @@ impl<BackendData: Backend> CompositorHandler for AnvilState<BackendData> {
     }
 
     fn commit(&mut self, surface: &WlSurface) {
+        #[cfg(feature = "debug")]
+        {
+            let new_buffer = with_states(surface, |states| {
+                matches!(
+                    states.cached_state.get::<SurfaceAttributes>().current().buffer,
+                    Some(BufferAssignment::NewBuffer(_))
+                )
+            });
+            if new_buffer {
+                smithay::desktop::utils::record_frame_commit(surface, self.clock.now());
+            }
+        }
         on_commit_buffer_handler::<Self>(surface);
         self.backend_data.early_import(surface);
 
@@ -195,6 +212,24 @@ impl<BackendData: Backend> CompositorHandler for AnvilState<BackendData> {
                     if let Some(buffer_offset) = buffer_offset {
                         let current_loc = self.space.element_location(&window).unwrap();
                         self.space.map_element(window, current_loc + buffer_offset, false);
+
+                        // the surface moved underneath the pointer, update the surface-local position.
+                        // Grabs decide the focus themselves, the move and resize grabs refresh it on release.
+                        if !self.pointer.is_grabbed() {
+                            let pointer = self.pointer.clone();
+                            let location = pointer.current_location();
+                            let under = self.surface_under(location);
+                            pointer.motion(
+                                self,
+                                under,
+                                &MotionEvent {
+                                    location,
+                                    serial: SERIAL_COUNTER.next_serial(),
+                                    time: self.clock.now().as_millis(),
+                                },
+                            );
+                            pointer.frame(self);
+                        }
                     }
                 }
             }
@@ -238,7 +273,11 @@ impl<BackendData: Backend> CompositorHandler for AnvilState<BackendData> {
             });
         }
 
-        ensure_initial_configure(surface, &self.space, &mut self.popups)
+        ensure_initial_configure(surface, &self.space, &mut self.popups);
+
+        if get_role(surface) == Some(LAYER_SURFACE_ROLE) {
+            self.refresh_exclusive_layer_focus();
+        }
     }
 }
 
@@ -273,10 +312,28 @@ impl<BackendData: Backend> WlrLayerShellHandler for AnvilState<BackendData> {
         }) {
             map.unmap_layer(&layer);
         }
+        self.schedule_texture_cleanup();
+        self.refresh_exclusive_layer_focus();
     }
 }
 
 impl<BackendData: Backend> AnvilState<BackendData> {
+    /// Move the keyboard focus to or away from layer surfaces requiring exclusive focus
+    pub fn refresh_exclusive_layer_focus(&mut self) {
+        let Some(change) = self.exclusive_layer_focus.refresh(self.space.outputs()) else {
+            return;
+        };
+        let keyboard = self.seat.get_keyboard().unwrap();
+        let serial = SERIAL_COUNTER.next_serial();
+        match change {
+            ExclusiveFocusChange::Focus(layer) => keyboard.set_focus(self, Some(layer.into()), serial),
+            ExclusiveFocusChange::Release => {
+                let window = self.space.elements().next_back().cloned();
+                keyboard.set_focus(self, window.map(Into::into), serial);
+            }
+        }
+    }
+
     pub fn window_for_surface(&self, surface: &WlSurface) -> Option<WindowElement> {
         self.space
             .elements()
@@ -434,17 +491,36 @@ fn place_new_window(
     space.map_element(window.clone(), (x, y), activate);
 }
 
+/// Position of an output requested by the user, stored in the outputs user data
+///
+/// Outputs without a configured position are placed side by side by [`fixup_positions`].
+#[derive(Debug, Default)]
+pub struct ConfiguredPosition(pub Mutex<Option<Point<i32, Logical>>>);
+
 pub fn fixup_positions(space: &mut Space<WindowElement>, pointer_location: Point<f64, Logical>) {
-    // fixup outputs
+    // fixup outputs, starting with the ones with a configured position
+    let (configured, unconfigured): (Vec<_>, Vec<_>) = space
+        .outputs()
+        .cloned()
+        .map(|output| {
+            let position = output
+                .user_data()
+                .get::<ConfiguredPosition>()
+                .and_then(|position| *position.0.lock().unwrap());
+            (output, position)
+        })
+        .partition(|(_, position)| position.is_some());
+
     let mut offset = Point::<i32, Logical>::from((0, 0));
-    for output in space.outputs().cloned().collect::<Vec<_>>().into_iter() {
+    for (output, position) in configured.into_iter().chain(unconfigured) {
         let size = space
             .output_geometry(&output)
             .map(|geo| geo.size)
             .unwrap_or_else(|| Size::from((0, 0)));
-        space.map_output(&output, offset);
+        let location = position.unwrap_or(offset);
+        space.map_output(&output, location);
         layer_map_for_output(&output).arrange();
-        offset.x += size.w;
+        offset.x = offset.x.max(location.x + size.w);
     }
 
     // fixup windows
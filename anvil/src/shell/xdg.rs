@@ -47,6 +47,8 @@ impl<BackendData: Backend> XdgShellHandler for AnvilState<BackendData> {
         // Do not send a configure here, the initial configure
         // of a xdg_surface has to be sent during the commit if
         // the surface is not already configured
+        #[cfg(feature = "debug")]
+        smithay::desktop::utils::enable_frame_stats(surface.wl_surface(), std::time::Duration::from_secs(1));
         let window = WindowElement(Window::new_wayland_window(surface.clone()));
         place_new_window(&mut self.space, self.pointer.current_location(), &window, true);
 
@@ -55,6 +57,10 @@ impl<BackendData: Backend> XdgShellHandler for AnvilState<BackendData> {
         });
     }
 
+    fn toplevel_destroyed(&mut self, _surface: ToplevelSurface) {
+        self.schedule_texture_cleanup();
+    }
+
     fn new_popup(&mut self, surface: PopupSurface, _positioner: PositionerState) {
         // Do not send a configure here, the initial configure
         // of a xdg_surface has to be sent during the commit if
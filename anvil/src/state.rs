@@ -1,7 +1,7 @@
 use std::{
     collections::HashMap,
     os::unix::io::OwnedFd,
-    sync::{atomic::AtomicBool, Arc},
+    sync::{atomic::AtomicBool, Arc, OnceLock},
     time::Duration,
 };
 
@@ -26,7 +26,7 @@ use smithay::{
             surface_presentation_feedback_flags_from_states, surface_primary_scanout_output,
             update_surface_primary_scanout_output, with_surfaces_surface_tree, OutputPresentationFeedback,
         },
-        PopupKind, PopupManager, Space,
+        ExclusiveLayerFocus, PopupKind, PopupManager, Space,
     },
     input::{
         keyboard::{Keysym, LedState, XkbConfig},
@@ -45,7 +45,10 @@ use smithay::{
             Client, Display, DisplayHandle, Resource,
         },
     },
-    utils::{Clock, Logical, Monotonic, Point, Rectangle, Time},
+    utils::{
+        Clock, FrameStepper, IdleWorkQueue, IdleWorkToken, Logical, LoopMetrics, Monotonic, Point, Rectangle,
+        Time,
+    },
     wayland::{
         commit_timing::{CommitTimerBarrierStateUserData, CommitTimingManagerState},
         compositor::{get_parent, with_states, CompositorClientState, CompositorHandler, CompositorState},
@@ -60,6 +63,7 @@ use smithay::{
         pointer_constraints::{with_pointer_constraint, PointerConstraintsHandler, PointerConstraintsState},
         pointer_gestures::PointerGesturesState,
         presentation::PresentationState,
+        protocol_error::{ClientProcess, ProtocolErrorReport},
         relative_pointer::RelativePointerManagerState,
         seat::WaylandFocus,
         security_context::{
@@ -115,12 +119,25 @@ use smithay::{
 pub struct ClientState {
     pub compositor_state: CompositorClientState,
     pub security_context: Option<SecurityContext>,
+    pub process: OnceLock<ClientProcess>,
+}
+impl ClientState {
+    /// Remember the process of a newly inserted client for diagnostics
+    fn track_process(client: &Client, dh: &DisplayHandle) {
+        if let Some(process) = ClientProcess::new(client, dh) {
+            let _ = client.get_data::<ClientState>().unwrap().process.set(process);
+        }
+    }
 }
 impl ClientData for ClientState {
     /// Notification that a client was initialized
     fn initialized(&self, _client_id: ClientId) {}
     /// Notification that a client is disconnected
-    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
+    fn disconnected(&self, client_id: ClientId, reason: DisconnectReason) {
+        if let Some(report) = ProtocolErrorReport::new(client_id, &reason, self.process.get().cloned()) {
+            warn!("{}", report);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -134,6 +151,7 @@ pub struct AnvilState<BackendData: Backend + 'static> {
     // desktop
     pub space: Space<WindowElement>,
     pub popups: PopupManager,
+    pub exclusive_layer_focus: ExclusiveLayerFocus,
 
     // smithay state
     pub compositor_state: CompositorState,
@@ -166,6 +184,10 @@ pub struct AnvilState<BackendData: Backend + 'static> {
     pub seat_name: String,
     pub seat: Seat<AnvilState<BackendData>>,
     pub clock: Clock<Monotonic>,
+    pub frame_stepper: Option<FrameStepper>,
+    pub loop_metrics: LoopMetrics,
+    pub idle_work: IdleWorkQueue<AnvilState<BackendData>>,
+    texture_cleanup: Option<IdleWorkToken>,
     pub pointer: PointerHandle<AnvilState<BackendData>>,
 
     #[cfg(feature = "xwayland")]
@@ -533,11 +555,12 @@ impl<BackendData: Backend + 'static> SecurityContextHandler for AnvilState<Backe
                     security_context: Some(security_context.clone()),
                     ..ClientState::default()
                 };
-                if let Err(err) = data
+                match data
                     .display_handle
                     .insert_client(client_stream, Arc::new(client_state))
                 {
-                    warn!("Error adding wayland client: {}", err);
+                    Ok(client) => ClientState::track_process(&client, &data.display_handle),
+                    Err(err) => warn!("Error adding wayland client: {}", err),
                 };
             })
             .expect("Failed to init wayland socket source");
@@ -584,6 +607,15 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
         let dh = display.handle();
 
         let clock = Clock::new();
+        let frame_stepper = crate::frame_stepping::init_from_env(&handle);
+        let loop_metrics = LoopMetrics::new();
+        loop_metrics
+            .register(&handle)
+            .expect("Failed to init event loop metrics");
+        let idle_work = IdleWorkQueue::new();
+        idle_work
+            .register(&handle)
+            .expect("Failed to init idle work queue");
 
         // init wayland clients
         let socket_name = if listen_on_socket {
@@ -591,11 +623,12 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             let socket_name = source.socket_name().to_string_lossy().into_owned();
             handle
                 .insert_source(source, |client_stream, _, data| {
-                    if let Err(err) = data
+                    match data
                         .display_handle
                         .insert_client(client_stream, Arc::new(ClientState::default()))
                     {
-                        warn!("Error adding wayland client: {}", err);
+                        Ok(client) => ClientState::track_process(&client, &data.display_handle),
+                        Err(err) => warn!("Error adding wayland client: {}", err),
                     };
                 })
                 .expect("Failed to init wayland socket source");
@@ -672,7 +705,7 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
         #[cfg(feature = "xwayland")]
         XWaylandKeyboardGrabState::new::<Self>(&dh.clone());
 
-        AnvilState {
+        let mut state = AnvilState {
             backend_data,
             display_handle: dh,
             socket_name,
@@ -680,6 +713,7 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             handle,
             space: Space::default(),
             popups: PopupManager::default(),
+            exclusive_layer_focus: ExclusiveLayerFocus::new(),
             compositor_state,
             data_device_state,
             layer_shell_state,
@@ -706,6 +740,10 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             seat,
             pointer,
             clock,
+            frame_stepper,
+            loop_metrics,
+            idle_work,
+            texture_cleanup: None,
 
             #[cfg(feature = "xwayland")]
             xwayland_shell_state,
@@ -716,7 +754,16 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             #[cfg(feature = "debug")]
             renderdoc: renderdoc::RenderDoc::new().ok(),
             show_window_preview: false,
+        };
+
+        if std::env::var("ANVIL_NUMLOCK").is_ok() {
+            let keyboard = state.seat.get_keyboard().unwrap();
+            keyboard.with_xkb_state(&mut state, |mut context| {
+                context.set_num_lock(true);
+            });
         }
+
+        state
     }
 
     #[cfg(feature = "xwayland")]
@@ -774,6 +821,56 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
 }
 
 impl<BackendData: Backend + 'static> AnvilState<BackendData> {
+    /// Presentation rate of the keyboard focused window, shown in the debug overlay
+    #[cfg(feature = "debug")]
+    pub fn focused_presentation_rate(&self) -> Option<u32> {
+        let focus = self.seat.get_keyboard()?.current_focus()?;
+        let surface = focus.wl_surface()?;
+        let stats = smithay::desktop::utils::surface_frame_stats(&surface, self.clock.now())?;
+        Some(stats.presentation_rate.round() as u32)
+    }
+
+    /// Returns if a frame should be rendered, which is always the case unless frame stepping is enabled
+    pub fn frame_pending(&self) -> bool {
+        self.frame_stepper
+            .as_ref()
+            .map(|stepper| stepper.pending() > 0)
+            .unwrap_or(true)
+    }
+
+    /// Returns the time of the frame about to be rendered
+    ///
+    /// While frame stepping this consumes one of the requested frames and returns its deterministic timestamp.
+    pub fn next_frame_time(&mut self) -> Time<Monotonic> {
+        self.frame_stepper
+            .as_ref()
+            .and_then(|stepper| stepper.next_frame())
+            .map(|frame| frame.time)
+            .unwrap_or_else(|| self.clock.now())
+    }
+
+    /// Free the resources of destroyed textures once the event loop is idle
+    ///
+    /// Destroying the surfaces of a heavy client frees many textures at once, doing so
+    /// outside of rendering avoids stalling the next frame. Repeated calls are coalesced.
+    pub fn schedule_texture_cleanup(&mut self) {
+        if let Some(token) = self.texture_cleanup.take() {
+            self.idle_work.cancel(token);
+        }
+        self.texture_cleanup = Some(self.idle_work.schedule(|state: &mut Self| {
+            state.texture_cleanup = None;
+            state.backend_data.cleanup_textures();
+        }));
+    }
+
+    /// Replaces the presentation time and sequence reported by the backend with deterministic ones while frame stepping
+    pub fn presentation_time(&self, time: Time<Monotonic>, seq: u64) -> (Time<Monotonic>, u64) {
+        match self.frame_stepper.as_ref() {
+            Some(stepper) => (stepper.now(), stepper.sequence()),
+            None => (time, seq),
+        }
+    }
+
     pub fn pre_repaint(&mut self, output: &Output, frame_target: impl Into<Time<Monotonic>>) {
         let frame_target = frame_target.into();
 
@@ -1107,4 +1204,10 @@ pub trait Backend {
     fn reset_buffers(&mut self, output: &Output);
     fn early_import(&mut self, surface: &WlSurface);
     fn update_led_state(&mut self, led_state: LedState);
+    /// Whether the renderer waits for explicit sync acquire points on the gpu
+    fn gpu_acquire_waits(&self) -> bool {
+        false
+    }
+    /// Free the renderer resources of destroyed textures and buffers
+    fn cleanup_textures(&mut self) {}
 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::hash_map::HashMap,
+    collections::{hash_map::HashMap, HashSet},
     io,
     path::Path,
     sync::{atomic::Ordering, Mutex},
@@ -8,6 +8,7 @@ use std::{
 
 use crate::{
     drawing::*,
+    output_config::OutputLayout,
     render::*,
     shell::WindowElement,
     state::{take_presentation_feedback, AnvilState, Backend},
@@ -31,20 +32,21 @@ use smithay::{
             Fourcc,
         },
         drm::{
-            compositor::{DrmCompositor, FrameFlags},
+            compositor::{ColorDepth, DrmCompositor, FrameFlags, LinkRecovery},
+            modes::{ModeFilter, ModePolicy},
             output::{DrmOutput, DrmOutputManager, DrmOutputRenderElements},
             CreateDrmNodeError, DrmAccessError, DrmDevice, DrmDeviceFd, DrmError, DrmEvent, DrmEventMetadata,
             DrmNode, DrmSurface, GbmBufferedSurface, NodeType,
         },
-        egl::{self, context::ContextPriority, EGLDevice, EGLDisplay},
-        input::InputEvent,
+        egl::{self, context::ContextPriority, fence::EGLFence, EGLDevice, EGLDisplay},
+        input::{Device as _, Event as _, InputEvent, KeyState, KeyboardKeyEvent, Keycode},
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{
             damage::Error as OutputDamageTrackerError,
             element::{memory::MemoryRenderBuffer, AsRenderElements, RenderElementStates},
             gles::GlesRenderer,
             multigpu::{gbm::GbmGlesBackend, GpuManager, MultiRenderer},
-            DebugFlags, ImportDma, ImportMemWl,
+            DebugFlags, ImportDma, ImportMemWl, Renderer,
         },
         session::{
             libseat::{self, LibSeatSession},
@@ -55,12 +57,12 @@ use smithay::{
     },
     delegate_dmabuf, delegate_drm_lease,
     desktop::{
-        space::{Space, SurfaceTree},
+        space::{OutputRenderElementsBuilder, Space, SurfaceTree},
         utils::OutputPresentationFeedback,
     },
     input::{
         keyboard::LedState,
-        pointer::{CursorImageAttributes, CursorImageStatus},
+        pointer::{CursorImageAttributes, CursorImageStatus, CursorScale},
     },
     output::{Mode as WlMode, Output, PhysicalProperties},
     reexports::{
@@ -69,21 +71,24 @@ use smithay::{
             EventLoop, RegistrationToken,
         },
         drm::{
-            control::{connector, crtc, Device, ModeTypeFlags},
+            control::{connector, crtc, Device},
             Device as _,
         },
         input::{DeviceCapability, Libinput},
-        rustix::fs::OFlags,
+        rustix::fs::{Dev, OFlags},
         wayland_protocols::wp::{
             linux_dmabuf::zv1::server::zwp_linux_dmabuf_feedback_v1,
             presentation_time::server::wp_presentation_feedback,
         },
         wayland_server::{backend::GlobalId, protocol::wl_surface, Display, DisplayHandle},
     },
-    utils::{DeviceFd, IsAlive, Logical, Monotonic, Point, Scale, Time, Transform},
+    utils::{DeviceFd, IsAlive, Logical, Monotonic, Point, Scale, Time, Transform, SERIAL_COUNTER},
     wayland::{
         compositor,
-        dmabuf::{DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier},
+        dmabuf::{
+            DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState, ImportFailureReason,
+            ImportNotifier,
+        },
         drm_lease::{
             DrmLease, DrmLeaseBuilder, DrmLeaseHandler, DrmLeaseRequest, DrmLeaseState, LeaseRejected,
         },
@@ -103,13 +108,14 @@ use tracing::{debug, error, info, trace, warn};
 // - we might need some work-arounds, if one supports modifiers, but the other does not
 //
 // So lets just pick `ARGB2101010` (10-bit) or `ARGB8888` (8-bit) for now, they are widely supported.
-const SUPPORTED_FORMATS: &[Fourcc] = &[
-    Fourcc::Abgr2101010,
-    Fourcc::Argb2101010,
-    Fourcc::Abgr8888,
-    Fourcc::Argb8888,
-];
-const SUPPORTED_FORMATS_8BIT_ONLY: &[Fourcc] = &[Fourcc::Abgr8888, Fourcc::Argb8888];
+// `ColorDepth::color_formats` falls back to 8-bit, if 10-bit isn't supported.
+fn color_depth() -> ColorDepth {
+    if std::env::var("ANVIL_DISABLE_10BIT").is_ok() {
+        ColorDepth::Bits8
+    } else {
+        ColorDepth::Bits10
+    }
+}
 
 type UdevRenderer<'a> = MultiRenderer<
     'a,
@@ -129,16 +135,21 @@ pub struct UdevData {
     dh: DisplayHandle,
     dmabuf_state: Option<(DmabufState, DmabufGlobal)>,
     syncobj_state: Option<DrmSyncobjState>,
+    gpu_acquire_waits: bool,
     primary_gpu: DrmNode,
     gpus: GpuManager<GbmGlesBackend<GlesRenderer, DrmDeviceFd>>,
     backends: HashMap<DrmNode, BackendData>,
-    pointer_images: Vec<(xcursor::parser::Image, MemoryRenderBuffer)>,
+    pointer_images: Vec<(xcursor::parser::Image, i32, MemoryRenderBuffer)>,
     pointer_element: PointerElement,
     #[cfg(feature = "debug")]
     fps_texture: Option<MultiTexture>,
     pointer_image: crate::cursor::Cursor,
+    cursor_scale: CursorScale,
     debug_flags: DebugFlags,
     keyboards: Vec<smithay::reexports::input::Device>,
+    // keys held down on each keyboard, to restore the keyboard state if one goes away
+    held_keys: HashMap<String, HashSet<Keycode>>,
+    output_layout: OutputLayout,
 }
 
 impl UdevData {
@@ -165,17 +176,17 @@ impl DmabufHandler for AnvilState<UdevData> {
     }
 
     fn dmabuf_imported(&mut self, _global: &DmabufGlobal, dmabuf: Dmabuf, notifier: ImportNotifier) {
-        if self
+        match self
             .backend_data
             .gpus
             .single_renderer(&self.backend_data.primary_gpu)
             .and_then(|mut renderer| renderer.import_dmabuf(&dmabuf, None))
-            .is_ok()
         {
-            dmabuf.set_node(self.backend_data.primary_gpu);
-            let _ = notifier.successful::<AnvilState<UdevData>>();
-        } else {
-            notifier.failed();
+            Ok(_) => {
+                dmabuf.set_node(self.backend_data.primary_gpu);
+                let _ = notifier.successful::<AnvilState<UdevData>>();
+            }
+            Err(err) => notifier.failed_with_reason(ImportFailureReason::Renderer(err.to_string())),
         }
     }
 }
@@ -210,6 +221,28 @@ impl Backend for UdevData {
             keyboard.led_update(led_state.into());
         }
     }
+
+    fn gpu_acquire_waits(&self) -> bool {
+        self.gpu_acquire_waits
+    }
+
+    fn cleanup_textures(&mut self) {
+        let mut nodes = self
+            .backends
+            .values()
+            .map(|backend| backend.render_node)
+            .collect::<HashSet<_>>();
+        nodes.insert(self.primary_gpu);
+        for node in nodes {
+            let res = self
+                .gpus
+                .single_renderer(&node)
+                .and_then(|mut renderer| renderer.cleanup_texture_cache());
+            if let Err(err) = res {
+                warn!("Failed to clean up textures on {}: {}", node, err);
+            }
+        }
+    }
 }
 
 pub fn run_udev() {
@@ -253,17 +286,21 @@ pub fn run_udev() {
         dh: display_handle.clone(),
         dmabuf_state: None,
         syncobj_state: None,
+        gpu_acquire_waits: false,
         session,
         primary_gpu,
         gpus,
         backends: HashMap::new(),
         pointer_image: crate::cursor::Cursor::load(),
         pointer_images: Vec::new(),
+        cursor_scale: CursorScale::new(),
         pointer_element: PointerElement::default(),
         #[cfg(feature = "debug")]
         fps_texture: None,
         debug_flags: DebugFlags::empty(),
         keyboards: Vec::new(),
+        held_keys: HashMap::new(),
+        output_layout: OutputLayout::from_env(),
     };
     let mut state = AnvilState::init(display, event_loop.handle(), data, true);
 
@@ -294,20 +331,41 @@ pub fn run_udev() {
         .handle()
         .insert_source(libinput_backend, move |mut event, _, data| {
             let dh = data.backend_data.dh.clone();
-            if let InputEvent::DeviceAdded { device } = &mut event {
-                if device.has_capability(DeviceCapability::Keyboard) {
+            let mut keyboards_changed = false;
+            match &mut event {
+                InputEvent::DeviceAdded { device } if device.has_capability(DeviceCapability::Keyboard) => {
                     if let Some(led_state) = data.seat.get_keyboard().map(|keyboard| keyboard.led_state()) {
                         device.led_update(led_state.into());
                     }
                     data.backend_data.keyboards.push(device.clone());
+                    keyboards_changed = true;
                 }
-            } else if let InputEvent::DeviceRemoved { ref device } = event {
-                if device.has_capability(DeviceCapability::Keyboard) {
+                InputEvent::DeviceRemoved { device } if device.has_capability(DeviceCapability::Keyboard) => {
                     data.backend_data.keyboards.retain(|item| item != device);
+                    data.backend_data.held_keys.remove(&device.id());
+                    keyboards_changed = true;
+                }
+                InputEvent::Keyboard { event } => {
+                    let keys = data
+                        .backend_data
+                        .held_keys
+                        .entry(event.device().id())
+                        .or_default();
+                    match event.state() {
+                        KeyState::Pressed => keys.insert(event.key_code()),
+                        KeyState::Released => keys.remove(&event.key_code()),
+                    };
                 }
+                _ => {}
             }
 
-            data.process_input_event(&dh, event)
+            data.process_input_event(&dh, event);
+
+            // releases of a removed keyboard and modifiers latched on it might have been lost,
+            // a new keyboard might be a reconnected one with a different state
+            if keyboards_changed {
+                data.resync_keyboard();
+            }
         })
         .unwrap();
 
@@ -332,6 +390,9 @@ pub fn run_udev() {
                 if let Err(err) = libinput_context.resume() {
                     error!("Failed to resume libinput context: {:?}", err);
                 }
+                // key releases are lost while the session is paused, so make sure no key is stuck
+                data.backend_data.held_keys.clear();
+                data.resync_keyboard();
                 for (node, backend) in data
                     .backend_data
                     .backends
@@ -366,6 +427,13 @@ pub fn run_udev() {
             error!("Skipping device {device_id}: {err}");
         }
     }
+    // gpus without display hardware are not reported by the udev backend,
+    // but may be used as the primary gpu rendering for all other devices
+    if state.backend_data.gpus.single_renderer(&primary_gpu).is_err() {
+        if let Err(err) = state.render_node_added(primary_gpu) {
+            error!("Failed to initialize render node {primary_gpu}: {err}");
+        }
+    }
     state.shm_state.update_formats(
         state
             .backend_data
@@ -397,6 +465,8 @@ pub fn run_udev() {
         for backend in state.backend_data.backends.values_mut() {
             for surface in backend.surfaces.values_mut() {
                 surface.fps_element = Some(FpsElement::new(fps_texture.clone()));
+                surface.window_fps_element =
+                    Some(FpsElement::new(fps_texture.clone()).with_location((0, 35).into()));
             }
         }
         state.backend_data.fps_texture = Some(fps_texture);
@@ -432,6 +502,7 @@ pub fn run_udev() {
                         surface_data.render_node,
                         gpus,
                         compositor.surface(),
+                        compositor.color_depth(),
                     )
                 })
             });
@@ -451,6 +522,15 @@ pub fn run_udev() {
                 let syncobj_state =
                     DrmSyncobjState::new::<AnvilState<UdevData>>(&display_handle, import_device);
                 state.backend_data.syncobj_state = Some(syncobj_state);
+                // let the renderer wait for acquire points on the gpu, if it can import native fences
+                state.backend_data.gpu_acquire_waits = state
+                    .backend_data
+                    .gpus
+                    .single_renderer(&primary_gpu)
+                    .map(|mut renderer| {
+                        EGLFence::supports_importing(renderer.as_mut().egl_context().display())
+                    })
+                    .unwrap_or(false);
             }
         }
     }
@@ -476,6 +556,15 @@ pub fn run_udev() {
                     data.device_removed(node)
                 }
             }
+            UdevEvent::FirmwareReplaced {
+                firmware_device_id,
+                device_id,
+            } => {
+                if let Ok(node) = DrmNode::from_dev_id(device_id) {
+                    data.firmware_replaced(firmware_device_id, node)
+                }
+            }
+            _ => {}
         })
         .unwrap();
 
@@ -605,6 +694,8 @@ struct SurfaceData {
     fps: fps_ticker::Fps,
     #[cfg(feature = "debug")]
     fps_element: Option<FpsElement<MultiTexture>>,
+    #[cfg(feature = "debug")]
+    window_fps_element: Option<FpsElement<MultiTexture>>,
     dmabuf_feedback: Option<SurfaceDmabufFeedback>,
 }
 
@@ -644,6 +735,8 @@ enum DeviceAddError {
     DrmNode(CreateDrmNodeError),
     #[error("Failed to add device to GpuManager: {0}")]
     AddNode(egl::Error),
+    #[error("Failed to find the device path of the drm node")]
+    NoDevicePath,
 }
 
 fn get_surface_dmabuf_feedback(
@@ -651,6 +744,7 @@ fn get_surface_dmabuf_feedback(
     render_node: DrmNode,
     gpus: &mut GpuManager<GbmGlesBackend<GlesRenderer, DrmDeviceFd>>,
     surface: &DrmSurface,
+    color_depth: Option<ColorDepth>,
 ) -> Option<SurfaceDmabufFeedback> {
     let primary_formats = gpus.single_renderer(&primary_gpu).ok()?.dmabuf_formats();
     let render_formats = gpus.single_renderer(&render_node).ok()?.dmabuf_formats();
@@ -665,7 +759,8 @@ fn get_surface_dmabuf_feedback(
 
     // We limit the scan-out tranche to formats we can also render from
     // so that there is always a fallback render path available in case
-    // the supplied buffer can not be scanned out directly
+    // the supplied buffer can not be scanned out directly.
+    // Buffers deeper than the output provide no benefit, so skip those as well.
     let planes_formats = surface
         .plane_info()
         .formats
@@ -675,6 +770,7 @@ fn get_surface_dmabuf_feedback(
         .collect::<FormatSet>()
         .intersection(&all_render_formats)
         .copied()
+        .filter(|format| color_depth.map_or(true, |depth| depth.supports(format.code)))
         .collect::<FormatSet>();
 
     let builder = DmabufFeedbackBuilder::new(primary_gpu.dev_id(), primary_formats);
@@ -701,6 +797,22 @@ fn get_surface_dmabuf_feedback(
 }
 
 impl AnvilState<UdevData> {
+    /// Rebuild the keyboard state from the keys held down on the connected keyboards
+    fn resync_keyboard(&mut self) {
+        let Some(keyboard) = self.seat.get_keyboard() else {
+            return;
+        };
+        let held_keys = self
+            .backend_data
+            .held_keys
+            .values()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let time = self.clock.now().as_millis();
+        keyboard.resync_pressed_keys(self, held_keys, SERIAL_COUNTER.next_serial(), time);
+    }
+
     fn device_added(&mut self, node: DrmNode, path: &Path) -> Result<(), DeviceAddError> {
         // Try to open the device
         let fd = self
@@ -745,11 +857,7 @@ impl AnvilState<UdevData> {
             .map_err(DeviceAddError::AddNode)?;
 
         let allocator = GbmAllocator::new(gbm.clone(), GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT);
-        let color_formats = if std::env::var("ANVIL_DISABLE_10BIT").is_ok() {
-            SUPPORTED_FORMATS_8BIT_ONLY
-        } else {
-            SUPPORTED_FORMATS
-        };
+        let color_formats = color_depth().color_formats();
         let mut renderer = self.backend_data.gpus.single_renderer(&render_node).unwrap();
         let render_formats = renderer.as_mut().egl_context().dmabuf_render_formats().clone();
 
@@ -785,6 +893,29 @@ impl AnvilState<UdevData> {
         Ok(())
     }
 
+    fn render_node_added(&mut self, node: DrmNode) -> Result<(), DeviceAddError> {
+        let path = node.dev_path().ok_or(DeviceAddError::NoDevicePath)?;
+        let fd = self
+            .backend_data
+            .session
+            .open(
+                &path,
+                OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK,
+            )
+            .map_err(DeviceAddError::DeviceOpen)?;
+
+        let fd = DrmDeviceFd::new(DeviceFd::from(fd));
+        let gbm = GbmDevice::new(fd).map_err(DeviceAddError::GbmDevice)?;
+        self.backend_data
+            .gpus
+            .as_mut()
+            .add_node(node, gbm)
+            .map_err(DeviceAddError::AddNode)?;
+
+        info!("Using render-only device {} for rendering", node);
+        Ok(())
+    }
+
     fn connector_connected(&mut self, node: DrmNode, connector: connector::Info, crtc: crtc::Handle) {
         let device = if let Some(device) = self.backend_data.backends.get_mut(&node) {
             device
@@ -843,13 +974,11 @@ impl AnvilState<UdevData> {
                 );
             }
         } else {
-            let mode_id = connector
-                .modes()
-                .iter()
-                .position(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
-                .unwrap_or(0);
-
-            let drm_mode = connector.modes()[mode_id];
+            let modes = ModeFilter::new().apply(connector.modes());
+            let Some(drm_mode) = ModePolicy::Preferred.select(&modes) else {
+                warn!("Connector {} has no usable mode", output_name);
+                return;
+            };
             let wl_mode = WlMode::from(drm_mode);
 
             let (phys_w, phys_h) = connector.size().unwrap_or((0, 0));
@@ -868,10 +997,16 @@ impl AnvilState<UdevData> {
                 .space
                 .outputs()
                 .fold(0, |acc, o| acc + self.space.output_geometry(o).unwrap().size.w);
-            let position = (x, 0).into();
+            let position = self
+                .backend_data
+                .output_layout
+                .get(&output.name())
+                .and_then(|config| config.position)
+                .unwrap_or_else(|| (x, 0).into());
 
             output.set_preferred(wl_mode);
             output.change_current_state(Some(wl_mode), None, None, Some(position));
+            self.backend_data.output_layout.apply(&output);
             self.space.map_output(&output, position);
 
             output.user_data().insert_if_missing(|| UdevOutputId {
@@ -881,6 +1016,12 @@ impl AnvilState<UdevData> {
 
             #[cfg(feature = "debug")]
             let fps_element = self.backend_data.fps_texture.clone().map(FpsElement::new);
+            #[cfg(feature = "debug")]
+            let window_fps_element = self
+                .backend_data
+                .fps_texture
+                .clone()
+                .map(|texture| FpsElement::new(texture).with_location((0, 35).into()));
 
             let driver = match drm_device.get_driver() {
                 Ok(driver) => driver,
@@ -937,6 +1078,7 @@ impl AnvilState<UdevData> {
                     device.render_node,
                     &mut self.backend_data.gpus,
                     compositor.surface(),
+                    compositor.color_depth(),
                 )
             });
 
@@ -951,6 +1093,8 @@ impl AnvilState<UdevData> {
                 fps: fps_ticker::Fps::default(),
                 #[cfg(feature = "debug")]
                 fps_element,
+                #[cfg(feature = "debug")]
+                window_fps_element,
                 dmabuf_feedback,
             };
 
@@ -1045,10 +1189,50 @@ impl AnvilState<UdevData> {
             }
         }
 
+        self.recover_links(node);
+
         // fixup window coordinates
         crate::shell::fixup_positions(&mut self.space, self.pointer.current_location());
     }
 
+    fn recover_links(&mut self, node: DrmNode) {
+        let Some(device) = self.backend_data.backends.get_mut(&node) else {
+            return;
+        };
+
+        let mut recovered = Vec::new();
+        for (connector, crtc) in device.drm_scanner.crtcs() {
+            let Some(surface) = device.surfaces.get(&crtc) else {
+                continue;
+            };
+            let modes = ModeFilter::new().apply(connector.modes());
+            match surface
+                .drm_output
+                .with_compositor(|compositor| compositor.recover_link(&modes))
+            {
+                Ok(LinkRecovery::Good) => {}
+                Ok(recovery) => recovered.push((crtc, recovery)),
+                Err(err) => warn!(?err, "Failed to recover link of {:?}", crtc),
+            }
+        }
+
+        for (crtc, recovery) in recovered {
+            if let LinkRecovery::ModeChanged(mode) = recovery {
+                let output = self.space.outputs().find(|o| {
+                    o.user_data().get::<UdevOutputId>()
+                        == Some(&UdevOutputId {
+                            device_id: node,
+                            crtc,
+                        })
+                });
+                if let Some(output) = output {
+                    output.change_current_state(Some(WlMode::from(mode)), None, None, None);
+                }
+            }
+            self.render(node, Some(crtc), self.clock.now());
+        }
+    }
+
     fn device_removed(&mut self, node: DrmNode) {
         let device = if let Some(device) = self.backend_data.backends.get_mut(&node) {
             device
@@ -1087,6 +1271,82 @@ impl AnvilState<UdevData> {
         crate::shell::fixup_positions(&mut self.space, self.pointer.current_location());
     }
 
+    fn firmware_replaced(&mut self, firmware_device_id: Dev, node: DrmNode) {
+        let primary_gpu = self.backend_data.primary_gpu;
+        let uses_firmware = primary_gpu.dev_id() == firmware_device_id
+            || primary_gpu
+                .node_with_type(NodeType::Primary)
+                .and_then(|x| x.ok())
+                .is_some_and(|primary| primary.dev_id() == firmware_device_id);
+        if !uses_firmware {
+            return;
+        }
+
+        let primary_gpu = node
+            .node_with_type(NodeType::Render)
+            .and_then(|x| x.ok())
+            .unwrap_or(node);
+        if self.backend_data.gpus.single_renderer(&primary_gpu).is_err() {
+            if let Err(err) = self.render_node_added(primary_gpu) {
+                error!("Failed to initialize render node {primary_gpu}: {err}");
+                return;
+            }
+        }
+        info!(
+            "Firmware framebuffer replaced, using {} as primary gpu.",
+            primary_gpu
+        );
+        self.backend_data.primary_gpu = primary_gpu;
+
+        let Ok(renderer) = self.backend_data.gpus.single_renderer(&primary_gpu) else {
+            return;
+        };
+        self.shm_state.update_formats(renderer.shm_formats());
+
+        // recreate the dmabuf global with the formats of the new primary gpu
+        let default_feedback = DmabufFeedbackBuilder::new(primary_gpu.dev_id(), renderer.dmabuf_formats())
+            .build()
+            .unwrap();
+        if let Some((dmabuf_state, global)) = self.backend_data.dmabuf_state.as_mut() {
+            let new_global = dmabuf_state.create_global_with_default_feedback::<AnvilState<UdevData>>(
+                &self.display_handle,
+                &default_feedback,
+            );
+            let old_global = std::mem::replace(global, new_global);
+            dmabuf_state.disable_global::<AnvilState<UdevData>>(&self.display_handle, &old_global);
+            dmabuf_state.destroy_global::<AnvilState<UdevData>>(&self.display_handle, old_global);
+        }
+
+        let gpus = &mut self.backend_data.gpus;
+        for backend_data in self.backend_data.backends.values_mut() {
+            for surface_data in backend_data.surfaces.values_mut() {
+                surface_data.dmabuf_feedback = surface_data.drm_output.with_compositor(|compositor| {
+                    get_surface_dmabuf_feedback(
+                        primary_gpu,
+                        surface_data.render_node,
+                        gpus,
+                        compositor.surface(),
+                        compositor.color_depth(),
+                    )
+                });
+            }
+        }
+    }
+
+    /// Re-read the output configuration and apply it to all connected outputs
+    pub fn reload_output_config(&mut self) {
+        self.backend_data.output_layout.reload();
+
+        let outputs = self.space.outputs().cloned().collect::<Vec<_>>();
+        for output in outputs {
+            if self.backend_data.output_layout.apply(&output) {
+                self.backend_data.reset_buffers(&output);
+            }
+        }
+
+        crate::shell::fixup_positions(&mut self.space, self.pointer.current_location());
+    }
+
     fn frame_finish(&mut self, dev_id: DrmNode, crtc: crtc::Handle, metadata: &mut Option<DrmEventMetadata>) {
         profiling::scope!("frame_finish", &format!("{crtc:?}"));
 
@@ -1119,12 +1379,16 @@ impl AnvilState<UdevData> {
             return;
         };
 
-        let tp = metadata.as_ref().and_then(|metadata| match metadata.time {
-            smithay::backend::drm::DrmEventTime::Monotonic(tp) => Some(tp),
-            smithay::backend::drm::DrmEventTime::Realtime(_) => None,
-        });
+        let submit_result = surface
+            .drm_output
+            .frame_submitted_with_metadata(*metadata)
+            .map_err(Into::<SwapBuffersError>::into);
 
-        let seq = metadata.as_ref().map(|metadata| metadata.sequence).unwrap_or(0);
+        let tp = submit_result
+            .as_ref()
+            .ok()
+            .and_then(|submitted| submitted.as_ref())
+            .and_then(|submitted| submitted.monotonic_time());
 
         let (clock, flags) = if let Some(tp) = tp {
             (
@@ -1137,23 +1401,27 @@ impl AnvilState<UdevData> {
             (self.clock.now(), wp_presentation_feedback::Kind::Vsync)
         };
 
-        let submit_result = surface
-            .drm_output
-            .frame_submitted()
-            .map_err(Into::<SwapBuffersError>::into);
-
         let schedule_render = match submit_result {
-            Ok(user_data) => {
-                if let Some(mut feedback) = user_data.flatten() {
+            Ok(submitted) => {
+                let seq = submitted
+                    .as_ref()
+                    .and_then(|submitted| submitted.sequence)
+                    .unwrap_or(0);
+                // deterministic presentation times while frame stepping
+                let (time, seq) = match self.frame_stepper.as_ref() {
+                    Some(stepper) => (stepper.now(), stepper.sequence()),
+                    None => (clock, seq as u64),
+                };
+                if let Some(mut feedback) = submitted.and_then(|submitted| submitted.user_data) {
                     feedback.presented(
-                        clock,
+                        time,
                         output
                             .current_mode()
                             .map(|mode| {
                                 Refresh::fixed(Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
                             })
                             .unwrap_or(Refresh::Unknown),
-                        seq as u64,
+                        seq,
                         flags,
                     );
                 }
@@ -1222,6 +1490,10 @@ impl AnvilState<UdevData> {
             let repaint_delay =
                 Duration::from_millis(((1_000_000f32 / output_refresh as f32) * 0.6f32) as u64);
 
+            // keep deferred cleanup work out of the way of the repaint
+            self.idle_work
+                .set_deadline(Some(self.clock.now() + repaint_delay));
+
             let timer = if self.backend_data.primary_gpu != surface.render_node {
                 // However, if we need to do a copy, that might not be enough.
                 // (And without actual comparision to previous frames we cannot really know.)
@@ -1282,8 +1554,34 @@ impl AnvilState<UdevData> {
             return;
         };
 
+        // While frame stepping only requested frames are rendered, using their deterministic timestamps
+        let frame_target = if self.frame_stepper.is_some() {
+            let Some(refresh) = output
+                .current_mode()
+                .map(|mode| Duration::from_millis(1_000_000 / mode.refresh as u64))
+            else {
+                return;
+            };
+            if !self.frame_pending() {
+                // check again after approx. one frame
+                self.handle
+                    .insert_source(Timer::from_duration(refresh), move |_, _, data| {
+                        data.render(node, Some(crtc), frame_target + refresh);
+                        TimeoutAction::Drop
+                    })
+                    .expect("failed to schedule frame timer");
+                return;
+            }
+            self.next_frame_time() + refresh
+        } else {
+            frame_target
+        };
+
         self.pre_repaint(&output, frame_target);
 
+        #[cfg(feature = "debug")]
+        let window_fps = self.focused_presentation_rate();
+
         let device = if let Some(device) = self.backend_data.backends.get_mut(&node) {
             device
         } else {
@@ -1298,11 +1596,15 @@ impl AnvilState<UdevData> {
 
         let start = Instant::now();
 
-        // TODO get scale from the rendersurface when supporting HiDPI
+        // load the cursor matching the scale of the output the pointer is on
+        self.backend_data
+            .cursor_scale
+            .update(self.space.output_under(self.pointer.current_location()));
+        let cursor_scale = self.backend_data.cursor_scale.buffer_scale();
         let frame = self
             .backend_data
             .pointer_image
-            .get_image(1 /*scale*/, self.clock.now().into());
+            .get_image(cursor_scale as u32, self.clock.now().into());
 
         let render_node = surface.render_node;
         let primary_gpu = self.backend_data.primary_gpu;
@@ -1319,8 +1621,8 @@ impl AnvilState<UdevData> {
         let pointer_images = &mut self.backend_data.pointer_images;
         let pointer_image = pointer_images
             .iter()
-            .find_map(|(image, texture)| {
-                if image == &frame {
+            .find_map(|(image, scale, texture)| {
+                if image == &frame && *scale == cursor_scale {
                     Some(texture.clone())
                 } else {
                     None
@@ -1331,11 +1633,11 @@ impl AnvilState<UdevData> {
                     &frame.pixels_rgba,
                     Fourcc::Argb8888,
                     (frame.width as i32, frame.height as i32),
-                    1,
+                    cursor_scale,
                     Transform::Normal,
                     None,
                 );
-                pointer_images.push((frame, buffer.clone()));
+                pointer_images.push((frame, cursor_scale, buffer.clone()));
                 buffer
             });
 
@@ -1350,6 +1652,8 @@ impl AnvilState<UdevData> {
             &self.dnd_icon,
             &mut self.cursor_status,
             self.show_window_preview,
+            #[cfg(feature = "debug")]
+            window_fps,
         );
         let reschedule = match result {
             Ok((has_rendered, states)) => {
@@ -1432,11 +1736,12 @@ fn render_surface<'a>(
     dnd_icon: &Option<DndIcon>,
     cursor_status: &mut CursorImageStatus,
     show_window_preview: bool,
+    #[cfg(feature = "debug")] window_fps: Option<u32>,
 ) -> Result<(bool, RenderElementStates), SwapBuffersError> {
     let output_geometry = space.output_geometry(output).unwrap();
     let scale = Scale::from(output.current_scale().fractional_scale());
 
-    let mut custom_elements: Vec<CustomRenderElements<_>> = Vec::new();
+    let mut builder = OutputRenderElementsBuilder::default();
 
     if output_geometry.to_f64().contains(pointer_location) {
         let cursor_hotspot = if let CursorImageStatus::Surface(ref surface) = cursor_status {
@@ -1471,8 +1776,8 @@ fn render_surface<'a>(
             pointer_element.set_status(cursor_status.clone());
         }
 
-        custom_elements.extend(
-            pointer_element.render_elements(
+        builder.cursor(
+            pointer_element.render_elements::<CustomRenderElements<_>>(
                 renderer,
                 (cursor_pos - cursor_hotspot.to_f64())
                     .to_physical(scale)
@@ -1489,7 +1794,9 @@ fn render_surface<'a>(
                     .to_physical(scale)
                     .to_i32_round();
                 if icon.surface.alive() {
-                    custom_elements.extend(AsRenderElements::<UdevRenderer<'a>>::render_elements(
+                    builder.dnd_icon(AsRenderElements::<UdevRenderer<'a>>::render_elements::<
+                        CustomRenderElements<_>,
+                    >(
                         &SurfaceTree::from_surface(&icon.surface),
                         renderer,
                         dnd_icon_pos,
@@ -1505,11 +1812,15 @@ fn render_surface<'a>(
     if let Some(element) = surface.fps_element.as_mut() {
         element.update_fps(surface.fps.avg().round() as u32);
         surface.fps.tick();
-        custom_elements.push(CustomRenderElements::Fps(element.clone()));
+        builder.custom([CustomRenderElements::Fps(element.clone())]);
+    }
+    #[cfg(feature = "debug")]
+    if let (Some(element), Some(fps)) = (surface.window_fps_element.as_mut(), window_fps) {
+        element.update_fps(fps);
+        builder.custom([CustomRenderElements::Fps(element.clone())]);
     }
 
-    let (elements, clear_color) =
-        output_elements(output, space, custom_elements, renderer, show_window_preview);
+    let (elements, clear_color) = output_elements(output, space, builder, renderer, show_window_preview);
 
     let frame_mode = if surface.disable_direct_scanout {
         FrameFlags::empty()
@@ -19,12 +19,13 @@ use smithay::{
             damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
             element::AsRenderElements,
             gles::GlesRenderer,
-            ImportDma, ImportMemWl,
+            ImportDma, ImportMemWl, Renderer,
         },
         winit::{self, WinitEvent, WinitGraphicsBackend},
         SwapBuffersError,
     },
     delegate_dmabuf,
+    desktop::{space::OutputRenderElementsBuilder, utils::OutputPresentationFeedback},
     input::{
         keyboard::LedState,
         pointer::{CursorImageAttributes, CursorImageStatus},
@@ -40,7 +41,8 @@ use smithay::{
     wayland::{
         compositor,
         dmabuf::{
-            DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier,
+            DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState,
+            ImportFailureReason, ImportNotifier,
         },
         presentation::Refresh,
     },
@@ -57,6 +59,7 @@ pub struct WinitData {
     damage_tracker: OutputDamageTracker,
     dmabuf_state: (DmabufState, DmabufGlobal, Option<DmabufFeedback>),
     full_redraw: u8,
+    pending_presentation: Option<OutputPresentationFeedback>,
     #[cfg(feature = "debug")]
     pub fps: fps_ticker::Fps,
 }
@@ -67,16 +70,11 @@ impl DmabufHandler for AnvilState<WinitData> {
     }
 
     fn dmabuf_imported(&mut self, _global: &DmabufGlobal, dmabuf: Dmabuf, notifier: ImportNotifier) {
-        if self
-            .backend_data
-            .backend
-            .renderer()
-            .import_dmabuf(&dmabuf, None)
-            .is_ok()
-        {
-            let _ = notifier.successful::<AnvilState<WinitData>>();
-        } else {
-            notifier.failed();
+        match self.backend_data.backend.renderer().import_dmabuf(&dmabuf, None) {
+            Ok(_) => {
+                let _ = notifier.successful::<AnvilState<WinitData>>();
+            }
+            Err(err) => notifier.failed_with_reason(ImportFailureReason::Renderer(err.to_string())),
         }
     }
 }
@@ -91,6 +89,11 @@ impl Backend for WinitData {
     }
     fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
     fn update_led_state(&mut self, _led_state: LedState) {}
+    fn cleanup_textures(&mut self) {
+        if let Err(err) = self.backend.renderer().cleanup_texture_cache() {
+            warn!("Failed to clean up textures: {}", err);
+        }
+    }
 }
 
 pub fn run_winit() {
@@ -142,7 +145,9 @@ pub fn run_winit() {
         )
         .expect("Unable to upload FPS texture");
     #[cfg(feature = "debug")]
-    let mut fps_element = FpsElement::new(fps_texture);
+    let mut fps_element = FpsElement::new(fps_texture.clone());
+    #[cfg(feature = "debug")]
+    let mut window_fps_element = FpsElement::new(fps_texture).with_location((0, 35).into());
 
     let render_node = EGLDevice::device_for_display(backend.renderer().egl_context().display())
         .and_then(|device| device.try_get_render_node());
@@ -195,6 +200,7 @@ pub fn run_winit() {
             damage_tracker,
             dmabuf_state,
             full_redraw: 0,
+            pending_presentation: None,
             #[cfg(feature = "debug")]
             fps: fps_ticker::Fps::default(),
         }
@@ -227,6 +233,17 @@ pub fn run_winit() {
                 crate::shell::fixup_positions(&mut state.space, state.pointer.current_location());
             }
             WinitEvent::Input(event) => state.process_input_event_windowed(event, OUTPUT_NAME),
+            WinitEvent::Presented { time } => {
+                let (time, seq) = state.presentation_time(time, 0);
+                if let Some(mut feedback) = state.backend_data.pending_presentation.take() {
+                    let refresh = output
+                        .current_mode()
+                        .map(|mode| Refresh::fixed(Duration::from_secs_f64(1_000f64 / mode.refresh as f64)))
+                        .unwrap_or(Refresh::Unknown);
+                    // the host compositor does not tell us how the frame was presented
+                    feedback.presented(time, refresh, seq, wp_presentation_feedback::Kind::empty());
+                }
+            }
             _ => (),
         });
 
@@ -236,8 +253,8 @@ pub fn run_winit() {
         }
 
         // drawing logic
-        {
-            let now = state.clock.now();
+        if state.frame_pending() {
+            let now = state.next_frame_time();
             let frame_target = now
                 + output
                     .current_mode()
@@ -245,6 +262,13 @@ pub fn run_winit() {
                     .unwrap_or_default();
             state.pre_repaint(&output, frame_target);
 
+            #[cfg(feature = "debug")]
+            let window_fps = state.focused_presentation_rate();
+            #[cfg(feature = "debug")]
+            if let Some(fps) = window_fps {
+                window_fps_element.update_fps(fps);
+            }
+
             let backend = &mut state.backend_data.backend;
 
             // draw the cursor as relevant
@@ -317,10 +341,10 @@ pub fn run_winit() {
 
                 let renderer = backend.renderer();
 
-                let mut elements = Vec::<CustomRenderElements<GlesRenderer>>::new();
+                let mut elements = OutputRenderElementsBuilder::default();
 
-                elements.extend(
-                    pointer_element.render_elements(
+                elements.cursor(
+                    pointer_element.render_elements::<CustomRenderElements<GlesRenderer>>(
                         renderer,
                         (cursor_pos - cursor_hotspot.to_f64())
                             .to_physical(scale)
@@ -336,7 +360,9 @@ pub fn run_winit() {
                         .to_physical(scale)
                         .to_i32_round();
                     if icon.surface.alive() {
-                        elements.extend(AsRenderElements::<GlesRenderer>::render_elements(
+                        elements.dnd_icon(AsRenderElements::<GlesRenderer>::render_elements::<
+                            CustomRenderElements<GlesRenderer>,
+                        >(
                             &smithay::desktop::space::SurfaceTree::from_surface(&icon.surface),
                             renderer,
                             dnd_icon_pos,
@@ -347,7 +373,11 @@ pub fn run_winit() {
                 }
 
                 #[cfg(feature = "debug")]
-                elements.push(CustomRenderElements::Fps(fps_element.clone()));
+                elements.custom([CustomRenderElements::Fps(fps_element.clone())]);
+                #[cfg(feature = "debug")]
+                if window_fps.is_some() {
+                    elements.custom([CustomRenderElements::Fps(window_fps_element.clone())]);
+                }
 
                 render_output(
                     &output,
@@ -395,19 +425,9 @@ pub fn run_winit() {
 
                     let states = render_output_result.states;
                     if has_rendered {
-                        let mut output_presentation_feedback =
-                            take_presentation_feedback(&output, &state.space, &states);
-                        output_presentation_feedback.presented(
-                            frame_target,
-                            output
-                                .current_mode()
-                                .map(|mode| {
-                                    Refresh::fixed(Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
-                                })
-                                .unwrap_or(Refresh::Unknown),
-                            0,
-                            wp_presentation_feedback::Kind::Vsync,
-                        )
+                        // feedback is sent once the host reports the presentation
+                        state.backend_data.pending_presentation =
+                            Some(take_presentation_feedback(&output, &state.space, &states));
                     }
 
                     // Send frame events so that client start drawing their next frame
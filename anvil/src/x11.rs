@@ -23,12 +23,13 @@ use smithay::{
         egl::{EGLContext, EGLDisplay},
         renderer::{
             damage::OutputDamageTracker, element::AsRenderElements, gles::GlesRenderer, Bind, ImportDma,
-            ImportMemWl,
+            ImportMemWl, Renderer,
         },
         vulkan::{version::Version, Instance, PhysicalDevice},
         x11::{WindowBuilder, X11Backend, X11Event, X11Surface},
     },
     delegate_dmabuf,
+    desktop::{space::OutputRenderElementsBuilder, utils::OutputPresentationFeedback},
     input::{
         keyboard::LedState,
         pointer::{CursorImageAttributes, CursorImageStatus},
@@ -45,7 +46,8 @@ use smithay::{
     wayland::{
         compositor,
         dmabuf::{
-            DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier,
+            DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState,
+            ImportFailureReason, ImportNotifier,
         },
         presentation::Refresh,
     },
@@ -63,6 +65,7 @@ pub struct X11Data {
     renderer: GlesRenderer,
     damage_tracker: OutputDamageTracker,
     surface: X11Surface,
+    pending_presentation: Option<OutputPresentationFeedback>,
     dmabuf_state: DmabufState,
     _dmabuf_global: DmabufGlobal,
     _dmabuf_default_feedback: DmabufFeedback,
@@ -76,10 +79,11 @@ impl DmabufHandler for AnvilState<X11Data> {
     }
 
     fn dmabuf_imported(&mut self, _global: &DmabufGlobal, dmabuf: Dmabuf, notifier: ImportNotifier) {
-        if self.backend_data.renderer.import_dmabuf(&dmabuf, None).is_ok() {
-            let _ = notifier.successful::<AnvilState<X11Data>>();
-        } else {
-            notifier.failed();
+        match self.backend_data.renderer.import_dmabuf(&dmabuf, None) {
+            Ok(_) => {
+                let _ = notifier.successful::<AnvilState<X11Data>>();
+            }
+            Err(err) => notifier.failed_with_reason(ImportFailureReason::Renderer(err.to_string())),
         }
     }
 }
@@ -94,6 +98,11 @@ impl Backend for X11Data {
     }
     fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
     fn update_led_state(&mut self, _led_state: LedState) {}
+    fn cleanup_textures(&mut self) {
+        if let Err(err) = self.renderer.cleanup_texture_cache() {
+            warn!("Failed to clean up textures: {}", err);
+        }
+    }
 }
 
 pub fn run_x11() {
@@ -220,7 +229,9 @@ pub fn run_x11() {
         )
         .expect("Unable to upload FPS texture");
     #[cfg(feature = "debug")]
-    let mut fps_element = FpsElement::new(fps_texture);
+    let mut fps_element = FpsElement::new(fps_texture.clone());
+    #[cfg(feature = "debug")]
+    let mut window_fps_element = FpsElement::new(fps_texture).with_location((0, 35).into());
     let output = Output::new(
         OUTPUT_NAME.to_string(),
         PhysicalProperties {
@@ -242,6 +253,7 @@ pub fn run_x11() {
         surface,
         renderer,
         damage_tracker,
+        pending_presentation: None,
         dmabuf_state,
         _dmabuf_global: dmabuf_global,
         _dmabuf_default_feedback: dmabuf_default_feedback,
@@ -277,7 +289,18 @@ pub fn run_x11() {
 
                 data.backend_data.render = true;
             }
-            X11Event::PresentCompleted { .. } | X11Event::Refresh { .. } => {
+            X11Event::PresentCompleted { time, msc, .. } => {
+                let (time, msc) = data.presentation_time(time, msc);
+                if let Some(mut feedback) = data.backend_data.pending_presentation.take() {
+                    let refresh = output_clone
+                        .current_mode()
+                        .map(|mode| Refresh::fixed(Duration::from_secs_f64(1_000f64 / mode.refresh as f64)))
+                        .unwrap_or(Refresh::Unknown);
+                    feedback.presented(time, refresh, msc, wp_presentation_feedback::Kind::Vsync);
+                }
+                data.backend_data.render = true;
+            }
+            X11Event::Refresh { .. } => {
                 data.backend_data.render = true;
             }
             X11Event::Input { event, .. } => data.process_input_event_windowed(event, OUTPUT_NAME),
@@ -296,10 +319,10 @@ pub fn run_x11() {
     let mut pointer_element = PointerElement::default();
 
     while state.running.load(Ordering::SeqCst) {
-        if state.backend_data.render {
+        if state.backend_data.render && state.frame_pending() {
             profiling::scope!("render_frame");
 
-            let now = state.clock.now();
+            let now = state.next_frame_time();
             let frame_target = now
                 + output
                     .current_mode()
@@ -307,6 +330,13 @@ pub fn run_x11() {
                     .unwrap_or_default();
             state.pre_repaint(&output, frame_target);
 
+            #[cfg(feature = "debug")]
+            let window_fps = state.focused_presentation_rate();
+            #[cfg(feature = "debug")]
+            if let Some(fps) = window_fps {
+                window_fps_element.update_fps(fps);
+            }
+
             let backend_data = &mut state.backend_data;
             // We need to borrow everything we want to refer to inside the renderer callback otherwise rustc is unhappy.
             #[cfg(feature = "debug")]
@@ -329,7 +359,7 @@ pub fn run_x11() {
                 );
             }
 
-            let mut elements: Vec<CustomRenderElements<GlesRenderer>> = Vec::new();
+            let mut elements = OutputRenderElementsBuilder::default();
 
             // draw the cursor as relevant
             // reset the cursor if the surface is no longer alive
@@ -359,8 +389,8 @@ pub fn run_x11() {
             let cursor_pos = state.pointer.current_location();
 
             pointer_element.set_status(state.cursor_status.clone());
-            elements.extend(
-                pointer_element.render_elements(
+            elements.cursor(
+                pointer_element.render_elements::<CustomRenderElements<GlesRenderer>>(
                     &mut backend_data.renderer,
                     (cursor_pos - cursor_hotspot.to_f64())
                         .to_physical(scale)
@@ -376,7 +406,9 @@ pub fn run_x11() {
                     .to_physical(scale)
                     .to_i32_round();
                 if icon.surface.alive() {
-                    elements.extend(AsRenderElements::<GlesRenderer>::render_elements(
+                    elements.dnd_icon(AsRenderElements::<GlesRenderer>::render_elements::<
+                        CustomRenderElements<GlesRenderer>,
+                    >(
                         &smithay::desktop::space::SurfaceTree::from_surface(&icon.surface),
                         &mut backend_data.renderer,
                         dnd_icon_pos,
@@ -387,7 +419,11 @@ pub fn run_x11() {
             }
 
             #[cfg(feature = "debug")]
-            elements.push(CustomRenderElements::Fps(fps_element.clone()));
+            elements.custom([CustomRenderElements::Fps(fps_element.clone())]);
+            #[cfg(feature = "debug")]
+            if window_fps.is_some() {
+                elements.custom([CustomRenderElements::Fps(window_fps_element.clone())]);
+            }
 
             let render_res = render_output(
                 &output,
@@ -413,20 +449,10 @@ pub fn run_x11() {
                     let states = render_output_result.states;
                     #[cfg(feature = "debug")]
                     let rendered = render_output_result.damage.is_some();
-                    if render_output_result.damage.is_some() {
-                        let mut output_presentation_feedback =
-                            take_presentation_feedback(&output, &state.space, &states);
-                        output_presentation_feedback.presented(
-                            frame_target,
-                            output
-                                .current_mode()
-                                .map(|mode| {
-                                    Refresh::fixed(Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
-                                })
-                                .unwrap_or(Refresh::Unknown),
-                            0,
-                            wp_presentation_feedback::Kind::Vsync,
-                        )
+                    if submitted && render_output_result.damage.is_some() {
+                        // feedback is sent once the x server reports the presentation
+                        state.backend_data.pending_presentation =
+                            Some(take_presentation_feedback(&output, &state.space, &states));
                     }
 
                     #[cfg(feature = "debug")]
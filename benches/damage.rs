@@ -0,0 +1,172 @@
+//! Element processing cost of [`OutputDamageTracker::render_output`]
+//!
+//! The scenes are rendered with the [`DummyRenderer`], so the measurements only contain
+//! damage and opaque region calculations, not actual drawing.
+//!
+//! Plane assignment of the `DrmCompositor` requires a drm device and is benchmarked separately
+//! in `drm_compositor.rs`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use smithay::{
+    backend::renderer::{
+        damage::OutputDamageTracker,
+        element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            Kind,
+        },
+        test::DummyRenderer,
+    },
+    utils::{Physical, Point, Size, Transform},
+};
+
+const OUTPUT_SIZE: (i32, i32) = (3840, 2160);
+const CURSOR_SIZE: i32 = 64;
+
+struct Scene {
+    buffers: Vec<(SolidColorBuffer, Point<i32, Physical>, f64)>,
+    rng: StdRng,
+}
+
+impl Scene {
+    /// Many small surfaces spread over the output, like a busy desktop with notifications and clocks
+    fn small_surfaces(count: usize) -> Scene {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let buffers = (0..count)
+            .map(|_| {
+                let size = Size::from((rng.gen_range(16..128), rng.gen_range(16..128)));
+                let location = Point::from((
+                    rng.gen_range(0..OUTPUT_SIZE.0 - size.w),
+                    rng.gen_range(0..OUTPUT_SIZE.1 - size.h),
+                ));
+                (SolidColorBuffer::new(size, random_color(&mut rng)), location, 1.0)
+            })
+            .collect();
+        Scene { buffers, rng }
+    }
+
+    /// A fullscreen video with a cursor on top
+    fn fullscreen_video() -> Scene {
+        Scene {
+            buffers: vec![
+                (
+                    SolidColorBuffer::new((CURSOR_SIZE, CURSOR_SIZE), [1.0, 1.0, 1.0, 1.0]),
+                    Point::from((100, 100)),
+                    1.0,
+                ),
+                (
+                    SolidColorBuffer::new(OUTPUT_SIZE, [0.0, 0.0, 0.0, 1.0]),
+                    Point::from((0, 0)),
+                    1.0,
+                ),
+            ],
+            rng: StdRng::seed_from_u64(0x5eed),
+        }
+    }
+
+    /// Windows scaled down into a grid, like an overview of all workspaces
+    fn overview(count: usize) -> Scene {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let columns = (count as f64).sqrt().ceil() as i32;
+        let cell = Size::<i32, Physical>::from((OUTPUT_SIZE.0 / columns, OUTPUT_SIZE.1 / columns));
+        let buffers = (0..count as i32)
+            .map(|idx| {
+                let location = Point::from(((idx % columns) * cell.w, (idx / columns) * cell.h));
+                let scale = cell.w as f64 / 1920.0;
+                (
+                    SolidColorBuffer::new((1920, 1080), random_color(&mut rng)),
+                    location,
+                    scale,
+                )
+            })
+            .collect();
+        Scene { buffers, rng }
+    }
+
+    /// Damage a fraction of the buffers by changing their color
+    fn damage(&mut self, fraction: f64) {
+        for (buffer, _, _) in self.buffers.iter_mut() {
+            if self.rng.gen_bool(fraction) {
+                buffer.set_color(random_color(&mut self.rng));
+            }
+        }
+    }
+
+    /// Move the cursor of the scene
+    fn move_cursor(&mut self) {
+        let (_, location, _) = &mut self.buffers[0];
+        location.x = (location.x + 7) % (OUTPUT_SIZE.0 - CURSOR_SIZE);
+        location.y = (location.y + 3) % (OUTPUT_SIZE.1 - CURSOR_SIZE);
+    }
+
+    fn elements(&self) -> Vec<SolidColorRenderElement> {
+        self.buffers
+            .iter()
+            .map(|(buffer, location, scale)| {
+                SolidColorRenderElement::from_buffer(buffer, *location, *scale, 1.0, Kind::Unspecified)
+            })
+            .collect()
+    }
+}
+
+fn random_color(rng: &mut StdRng) -> [f32; 4] {
+    [rng.gen(), rng.gen(), rng.gen(), 1.0]
+}
+
+fn render(renderer: &mut DummyRenderer, damage_tracker: &mut OutputDamageTracker, scene: &Scene) {
+    let elements = scene.elements();
+    damage_tracker
+        .render_output(renderer, 1, &elements, [0.0, 0.0, 0.0, 1.0])
+        .unwrap();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut renderer = DummyRenderer::new();
+
+    let mut group = c.benchmark_group("render_output/small_surfaces");
+    for count in [50, 200, 1000] {
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| {
+            let mut scene = Scene::small_surfaces(count);
+            let mut damage_tracker = OutputDamageTracker::new(OUTPUT_SIZE, 1.0, Transform::Normal);
+            render(&mut renderer, &mut damage_tracker, &scene);
+            b.iter(|| {
+                scene.damage(0.25);
+                render(&mut renderer, &mut damage_tracker, &scene);
+            });
+        });
+    }
+    group.finish();
+
+    c.bench_function("render_output/fullscreen_video", |b| {
+        let mut scene = Scene::fullscreen_video();
+        let mut damage_tracker = OutputDamageTracker::new(OUTPUT_SIZE, 1.0, Transform::Normal);
+        render(&mut renderer, &mut damage_tracker, &scene);
+        b.iter(|| {
+            scene.move_cursor();
+            let (video, _, _) = &mut scene.buffers[1];
+            video.set_color([0.0, 0.0, 0.0, 1.0]);
+            render(&mut renderer, &mut damage_tracker, &scene);
+        });
+    });
+
+    let mut group = c.benchmark_group("render_output/overview_500");
+    group.bench_function("idle", |b| {
+        let scene = Scene::overview(500);
+        let mut damage_tracker = OutputDamageTracker::new(OUTPUT_SIZE, 1.0, Transform::Normal);
+        render(&mut renderer, &mut damage_tracker, &scene);
+        b.iter(|| render(&mut renderer, &mut damage_tracker, &scene));
+    });
+    group.bench_function("damaged", |b| {
+        let mut scene = Scene::overview(500);
+        let mut damage_tracker = OutputDamageTracker::new(OUTPUT_SIZE, 1.0, Transform::Normal);
+        render(&mut renderer, &mut damage_tracker, &scene);
+        b.iter(|| {
+            scene.damage(0.1);
+            render(&mut renderer, &mut damage_tracker, &scene);
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,279 @@
+//! Plane assignment cost of [`DrmCompositor::render_frame`]
+//!
+//! The `DrmCompositor` tests every plane assignment with an atomic test commit, so this benchmark
+//! needs real hardware: a drm device with a connected output and drm master. Run it from a free VT
+//! without another compositor running, the device can be selected with `SMITHAY_BENCH_DRM_DEVICE`
+//! (defaults to `/dev/dri/card0`). The benchmark is skipped, if the device can not be used.
+//!
+//! Frames are only prepared, never committed, so the measurements contain the element processing
+//! and plane assignment of the compositor including the test commits, but no page flips.
+
+use std::{fs::OpenOptions, os::unix::io::OwnedFd};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use smithay::{
+    backend::{
+        allocator::{
+            dmabuf::{AsDmabuf, Dmabuf},
+            gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+            Allocator, Buffer as _, Fourcc, Modifier,
+        },
+        drm::{
+            compositor::{DrmCompositor, FrameFlags},
+            DrmDevice, DrmDeviceFd, DrmSurface,
+        },
+        egl::{EGLContext, EGLDisplay},
+        renderer::{
+            element::{Element, Id, Kind, RenderElement, UnderlyingStorage},
+            gles::{GlesRenderer, GlesTexture},
+            utils::CommitCounter,
+            Frame, ImportDma, Renderer,
+        },
+    },
+    output::OutputModeSource,
+    reexports::drm::control::{connector, Device as ControlDevice},
+    utils::{Buffer, DeviceFd, Physical, Point, Rectangle, Scale, Transform},
+};
+use tracing::warn;
+
+const CURSOR_SIZE: i32 = 64;
+
+type Compositor = DrmCompositor<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>;
+
+/// A client buffer backed by a dmabuf, which the compositor may scan out
+struct DmabufElement {
+    id: Id,
+    commit: CommitCounter,
+    location: Point<i32, Physical>,
+    dmabuf: Dmabuf,
+    texture: GlesTexture,
+    kind: Kind,
+}
+
+impl Element for DmabufElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.dmabuf.size().to_f64())
+    }
+
+    fn geometry(&self, _scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        Rectangle::new(self.location, (self.dmabuf.size().w, self.dmabuf.size().h).into())
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl RenderElement<GlesRenderer> for DmabufElement {
+    fn draw(
+        &self,
+        frame: &mut <GlesRenderer as Renderer>::Frame<'_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), <GlesRenderer as Renderer>::Error> {
+        Frame::render_texture_from_to(
+            frame,
+            &self.texture,
+            src,
+            dst,
+            damage,
+            opaque_regions,
+            Transform::Normal,
+            1.0,
+        )
+    }
+
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage<'_>> {
+        Some(UnderlyingStorage::External(&self.dmabuf))
+    }
+}
+
+struct Bench {
+    // drop order matters, the compositor has to go before the device
+    compositor: Compositor,
+    renderer: GlesRenderer,
+    allocator: GbmAllocator<DrmDeviceFd>,
+    output_size: (i32, i32),
+    _device: DrmDevice,
+}
+
+impl Bench {
+    fn new() -> Option<Bench> {
+        let path = std::env::var("SMITHAY_BENCH_DRM_DEVICE").unwrap_or_else(|_| "/dev/dri/card0".into());
+        match Bench::open(&path) {
+            Ok(bench) => Some(bench),
+            Err(err) => {
+                warn!("Skipping drm compositor benchmarks, {path} is not usable: {err}");
+                None
+            }
+        }
+    }
+
+    fn open(path: &str) -> Result<Bench, Box<dyn std::error::Error>> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let fd = DrmDeviceFd::new(DeviceFd::from(OwnedFd::from(file)));
+        let (mut device, _notifier) = DrmDevice::new(fd.clone(), true)?;
+        let gbm = GbmDevice::new(fd)?;
+        let display = unsafe { EGLDisplay::new(gbm.clone())? };
+        let renderer = unsafe { GlesRenderer::new(EGLContext::new(&display)?)? };
+
+        let resources = device.resource_handles()?;
+        let (connector, mode, crtc) = resources
+            .connectors()
+            .iter()
+            .filter_map(|handle| device.get_connector(*handle, false).ok())
+            .filter(|info| info.state() == connector::State::Connected)
+            .find_map(|info| {
+                let mode = *info.modes().first()?;
+                let crtc = info
+                    .encoders()
+                    .iter()
+                    .filter_map(|encoder| device.get_encoder(*encoder).ok())
+                    .find_map(|encoder| resources.filter_crtcs(encoder.possible_crtcs()).first().copied())?;
+                Some((info.handle(), mode, crtc))
+            })
+            .ok_or("no connected connector")?;
+        let surface: DrmSurface = device.create_surface(crtc, mode, &[connector])?;
+
+        let (w, h) = mode.size();
+        let output_size = (w as i32, h as i32);
+        let allocator = GbmAllocator::new(gbm.clone(), GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT);
+        let compositor = DrmCompositor::new(
+            OutputModeSource::Static {
+                size: output_size.into(),
+                scale: Scale::from(1.0),
+                transform: Transform::Normal,
+            },
+            surface,
+            None,
+            allocator.clone(),
+            gbm.clone(),
+            [Fourcc::Argb8888, Fourcc::Xrgb8888],
+            renderer.dmabuf_formats(),
+            device.cursor_size(),
+            Some(gbm),
+        )?;
+
+        Ok(Bench {
+            compositor,
+            renderer,
+            allocator,
+            output_size,
+            _device: device,
+        })
+    }
+
+    fn element(&mut self, size: (i32, i32), location: (i32, i32), kind: Kind) -> DmabufElement {
+        let buffer = self
+            .allocator
+            .create_buffer(
+                size.0 as u32,
+                size.1 as u32,
+                Fourcc::Argb8888,
+                &[Modifier::Linear],
+            )
+            .expect("Failed to allocate buffer");
+        let dmabuf = buffer.export().expect("Failed to export buffer");
+        let texture = self
+            .renderer
+            .import_dmabuf(&dmabuf, None)
+            .expect("Failed to import buffer");
+        DmabufElement {
+            id: Id::new(),
+            commit: CommitCounter::default(),
+            location: location.into(),
+            dmabuf,
+            texture,
+            kind,
+        }
+    }
+
+    fn render(&mut self, elements: &[DmabufElement]) {
+        self.compositor
+            .render_frame(
+                &mut self.renderer,
+                elements,
+                [0.0, 0.0, 0.0, 1.0],
+                FrameFlags::DEFAULT,
+            )
+            .expect("Failed to render frame");
+    }
+}
+
+/// Many small surfaces spread over the output, like a busy desktop with notifications and clocks
+fn small_surfaces(bench: &mut Bench, count: usize) -> Vec<DmabufElement> {
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+    let (w, h) = bench.output_size;
+    (0..count)
+        .map(|_| {
+            let size = (rng.gen_range(16..128), rng.gen_range(16..128));
+            let location = (rng.gen_range(0..w - size.0), rng.gen_range(0..h - size.1));
+            bench.element(size, location, Kind::Unspecified)
+        })
+        .collect()
+}
+
+/// A fullscreen video with a cursor on top
+fn fullscreen_video(bench: &mut Bench) -> Vec<DmabufElement> {
+    vec![
+        bench.element((CURSOR_SIZE, CURSOR_SIZE), (100, 100), Kind::Cursor),
+        bench.element(bench.output_size, (0, 0), Kind::Video),
+    ]
+}
+
+/// Damage a fraction of the elements by committing a new frame
+fn damage(elements: &mut [DmabufElement], rng: &mut StdRng, fraction: f64) {
+    for element in elements.iter_mut() {
+        if rng.gen_bool(fraction) {
+            element.commit.increment();
+        }
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let _ = tracing_subscriber::fmt().try_init();
+    let Some(mut bench) = Bench::new() else {
+        return;
+    };
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+
+    let mut group = c.benchmark_group("drm_compositor/small_surfaces");
+    for count in [10, 50, 200] {
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| {
+            let mut elements = small_surfaces(&mut bench, count);
+            bench.compositor.reset_state().expect("Failed to reset state");
+            b.iter(|| {
+                damage(&mut elements, &mut rng, 0.25);
+                bench.render(&elements);
+            });
+        });
+    }
+    group.finish();
+
+    c.bench_function("drm_compositor/fullscreen_video", |b| {
+        let mut elements = fullscreen_video(&mut bench);
+        bench.compositor.reset_state().expect("Failed to reset state");
+        let (w, h) = bench.output_size;
+        b.iter(|| {
+            let cursor = &mut elements[0];
+            cursor.location.x = (cursor.location.x + 7) % (w - CURSOR_SIZE);
+            cursor.location.y = (cursor.location.y + 3) % (h - CURSOR_SIZE);
+            elements[1].commit.increment();
+            bench.render(&elements);
+        });
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
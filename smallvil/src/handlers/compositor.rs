@@ -1,4 +1,6 @@
-use crate::{grabs::resize_grab, state::ClientState, Smallvil};
+#[cfg(feature = "floating")]
+use crate::grabs::resize_grab;
+use crate::{state::ClientState, Smallvil};
 use smithay::{
     backend::renderer::utils::on_commit_buffer_handler,
     delegate_compositor, delegate_shm,
@@ -43,7 +45,10 @@ impl CompositorHandler for Smallvil {
         };
 
         xdg_shell::handle_commit(&mut self.popups, &self.space, surface);
+        #[cfg(feature = "floating")]
         resize_grab::handle_commit(&mut self.space, surface);
+        #[cfg(feature = "layer_shell")]
+        super::layer_shell::handle_commit(&self.space, surface);
     }
 }
 
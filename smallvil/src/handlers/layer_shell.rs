@@ -0,0 +1,96 @@
+use smithay::{
+    delegate_layer_shell,
+    desktop::{layer_map_for_output, LayerSurface, Space, Window, WindowSurfaceType},
+    output::Output,
+    reexports::wayland_server::protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    wayland::{
+        compositor::with_states,
+        shell::wlr_layer::{
+            Layer, LayerSurface as WlrLayerSurface, LayerSurfaceData, WlrLayerShellHandler,
+            WlrLayerShellState,
+        },
+    },
+};
+
+use crate::Smallvil;
+
+impl WlrLayerShellHandler for Smallvil {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.layer_shell_state
+    }
+
+    fn new_layer_surface(
+        &mut self,
+        surface: WlrLayerSurface,
+        output: Option<WlOutput>,
+        _layer: Layer,
+        namespace: String,
+    ) {
+        // Layer surfaces are placed on the requested output, or on the first one if the client doesn't care.
+        let output = output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(|| self.space.outputs().next().cloned());
+        let Some(output) = output else {
+            surface.send_close();
+            return;
+        };
+
+        // The layer map of the output arranges all layer surfaces according to their anchors and margins.
+        let mut map = layer_map_for_output(&output);
+        map.map_layer(&LayerSurface::new(surface, namespace)).unwrap();
+    }
+
+    fn layer_destroyed(&mut self, surface: WlrLayerSurface) {
+        let output = self.space.outputs().find(|o| {
+            let map = layer_map_for_output(o);
+            map.layer_for_surface(surface.wl_surface(), WindowSurfaceType::TOPLEVEL)
+                .is_some()
+        });
+        if let Some(output) = output.cloned() {
+            let mut map = layer_map_for_output(&output);
+            let layer = map
+                .layers()
+                .find(|&layer| layer.layer_surface() == &surface)
+                .cloned();
+            if let Some(layer) = layer {
+                map.unmap_layer(&layer);
+            }
+        }
+    }
+}
+delegate_layer_shell!(Smallvil);
+
+/// Should be called on `WlSurface::commit`
+pub fn handle_commit(space: &Space<Window>, surface: &WlSurface) {
+    let Some(output) = space.outputs().find(|o| {
+        let map = layer_map_for_output(o);
+        map.layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+            .is_some()
+    }) else {
+        return;
+    };
+
+    let initial_configure_sent = with_states(surface, |states| {
+        states
+            .data_map
+            .get::<LayerSurfaceData>()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .initial_configure_sent
+    });
+
+    let mut map = layer_map_for_output(output);
+
+    // Arrange the layers before sending the initial configure,
+    // so the client knows the size it was assigned.
+    map.arrange();
+
+    if !initial_configure_sent {
+        let layer = map
+            .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+            .unwrap();
+        layer.layer_surface().send_configure();
+    }
+}
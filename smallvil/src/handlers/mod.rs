@@ -1,4 +1,8 @@
 mod compositor;
+#[cfg(feature = "layer_shell")]
+mod layer_shell;
+#[cfg(feature = "screencopy")]
+pub mod screencopy;
 mod xdg_shell;
 
 use crate::Smallvil;
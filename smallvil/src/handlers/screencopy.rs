@@ -0,0 +1,275 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker, element::surface::WaylandSurfaceRenderElement,
+            gles::GlesRenderbuffer, gles::GlesRenderer, Bind, ExportMem, Offscreen, TextureMapping, Unbind,
+        },
+    },
+    desktop::{space::render_output, Space, Window},
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            protocol::{wl_buffer::WlBuffer, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Buffer, Clock, Logical, Monotonic, Rectangle, Transform},
+    wayland::shm::with_buffer_contents_mut,
+};
+use tracing::warn;
+
+use crate::Smallvil;
+
+/// A screencopy request waiting for the next frame of its output
+pub struct PendingScreencopy {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    output: Output,
+    region: Rectangle<i32, Buffer>,
+}
+
+/// User data of a `zwlr_screencopy_frame_v1`
+pub struct ScreencopyFrameData {
+    output: Option<Output>,
+    region: Rectangle<i32, Buffer>,
+    used: AtomicBool,
+}
+
+impl GlobalDispatch<ZwlrScreencopyManagerV1, ()> for Smallvil {
+    fn bind(
+        _state: &mut Self,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for Smallvil {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _manager: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let (frame, output, region) = match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput { frame, output, .. } => (frame, output, None),
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                output,
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => (
+                frame,
+                output,
+                Some(Rectangle::<i32, Logical>::new(
+                    (x, y).into(),
+                    (width, height).into(),
+                )),
+            ),
+            zwlr_screencopy_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        // The region is given in logical coordinates of the output,
+        // but the client receives the pixels of the output's framebuffer.
+        let mode = Output::from_resource(&output).and_then(|output| Some((output.current_mode()?, output)));
+        let Some((mode, output)) = mode else {
+            let frame = data_init.init(
+                frame,
+                ScreencopyFrameData {
+                    output: None,
+                    region: Rectangle::default(),
+                    used: AtomicBool::new(true),
+                },
+            );
+            frame.failed();
+            return;
+        };
+        let transform = output.current_transform();
+        let scale = output.current_scale().fractional_scale();
+        let output_size = transform.transform_size(mode.size).to_f64().to_logical(scale);
+        let full = Rectangle::from_size(mode.size.to_logical(1).to_buffer(1, Transform::Normal));
+        let region = match region {
+            Some(region) => region
+                .to_f64()
+                .to_buffer(scale, transform.invert(), &output_size)
+                .to_i32_round()
+                .intersection(full)
+                .unwrap_or_default(),
+            None => full,
+        };
+
+        let frame = data_init.init(
+            frame,
+            ScreencopyFrameData {
+                output: Some(output),
+                region,
+                used: AtomicBool::new(false),
+            },
+        );
+
+        // Smallvil only supports copies into shm buffers.
+        frame.buffer(
+            wl_shm::Format::Argb8888,
+            region.size.w as u32,
+            region.size.h as u32,
+            region.size.w as u32 * 4,
+        );
+        if frame.version() >= 3 {
+            frame.buffer_done();
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData> for Smallvil {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        frame: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &ScreencopyFrameData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let buffer = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer }
+            | zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => buffer,
+            zwlr_screencopy_frame_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if data.used.swap(true, Ordering::SeqCst) {
+            frame.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "frame was already copied",
+            );
+            return;
+        }
+        let Some(output) = data.output.clone() else {
+            return;
+        };
+
+        // The contents are copied once the output renders its next frame, see `render_screencopies`.
+        state.pending_screencopies.push(PendingScreencopy {
+            frame: frame.clone(),
+            buffer,
+            output,
+            region: data.region,
+        });
+    }
+}
+
+/// Copy the contents of `output` into the buffers of all pending screencopy frames
+///
+/// Should be called after rendering the output. The output is rendered again into an offscreen
+/// buffer, because the framebuffer of the backend might already be queued for scanout.
+pub fn render_screencopies(state: &mut Smallvil, renderer: &mut GlesRenderer, output: &Output) {
+    let (pending, rest) = std::mem::take(&mut state.pending_screencopies)
+        .into_iter()
+        .partition::<Vec<_>, _>(|pending| &pending.output == output);
+    state.pending_screencopies = rest;
+    if pending.is_empty() {
+        return;
+    }
+
+    let time = Duration::from(Clock::<Monotonic>::new().now());
+    for pending in pending {
+        match copy_output(renderer, output, &state.space, &pending) {
+            Ok(()) => {
+                pending.frame.flags(zwlr_screencopy_frame_v1::Flags::empty());
+                pending.frame.ready(
+                    (time.as_secs() >> 32) as u32,
+                    time.as_secs() as u32,
+                    time.subsec_nanos(),
+                );
+            }
+            Err(err) => {
+                warn!("Screencopy failed: {}", err);
+                pending.frame.failed();
+            }
+        }
+    }
+}
+
+fn copy_output(
+    renderer: &mut GlesRenderer,
+    output: &Output,
+    space: &Space<Window>,
+    pending: &PendingScreencopy,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mode = output.current_mode().ok_or("output has no mode")?;
+    let size = mode.size.to_logical(1).to_buffer(1, Transform::Normal);
+
+    // A fresh damage tracker redraws the whole output.
+    let mut damage_tracker = OutputDamageTracker::from_output(output);
+    let framebuffer: GlesRenderbuffer = renderer.create_buffer(Fourcc::Argb8888, size)?;
+    renderer.bind(framebuffer)?;
+    render_output::<_, WaylandSurfaceRenderElement<GlesRenderer>, _, _>(
+        output,
+        renderer,
+        1.0,
+        0,
+        [space],
+        &[],
+        &mut damage_tracker,
+        [0.1, 0.1, 0.1, 1.0],
+    )?;
+    let mapping = renderer.copy_framebuffer(Rectangle::from_size(size), Fourcc::Argb8888)?;
+    let pixels = renderer.map_texture(&mapping)?;
+    renderer.unbind()?;
+
+    let region = pending.region;
+    with_buffer_contents_mut(&pending.buffer, |ptr, len, data| {
+        if data.format != wl_shm::Format::Argb8888 && data.format != wl_shm::Format::Xrgb8888
+            || data.width != region.size.w
+            || data.height != region.size.h
+            || data.stride < region.size.w * 4
+            || data.offset as usize + (data.stride * data.height) as usize > len
+        {
+            pending.frame.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "buffer does not match the advertised parameters",
+            );
+            return Err("invalid buffer");
+        }
+
+        let offset = data.offset as usize;
+        let dst = unsafe { std::slice::from_raw_parts_mut(ptr.add(offset), len - offset) };
+        let src_stride = size.w as usize * 4;
+        for row in 0..region.size.h as usize {
+            // The renderer reads the framebuffer bottom-up, so the rows are flipped while copying.
+            let src_row = if mapping.flipped() {
+                size.h as usize - 1 - (region.loc.y as usize + row)
+            } else {
+                region.loc.y as usize + row
+            };
+            let src = src_row * src_stride + region.loc.x as usize * 4;
+            let dst_start = row * data.stride as usize;
+            dst[dst_start..dst_start + region.size.w as usize * 4]
+                .copy_from_slice(&pixels[src..src + region.size.w as usize * 4]);
+        }
+        Ok(())
+    })??;
+
+    Ok(())
+}
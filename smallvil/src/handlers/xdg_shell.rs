@@ -1,18 +1,8 @@
 use smithay::{
     delegate_xdg_shell,
     desktop::{find_popup_root_surface, get_popup_toplevel_coords, PopupKind, PopupManager, Space, Window},
-    input::{
-        pointer::{Focus, GrabStartData as PointerGrabStartData},
-        Seat,
-    },
-    reexports::{
-        wayland_protocols::xdg::shell::server::xdg_toplevel,
-        wayland_server::{
-            protocol::{wl_seat, wl_surface::WlSurface},
-            Resource,
-        },
-    },
-    utils::{Rectangle, Serial},
+    reexports::wayland_server::protocol::{wl_seat, wl_surface::WlSurface},
+    utils::Serial,
     wayland::{
         compositor::with_states,
         shell::xdg::{
@@ -22,11 +12,20 @@ use smithay::{
     },
 };
 
-use crate::{
-    grabs::{MoveSurfaceGrab, ResizeSurfaceGrab},
-    Smallvil,
+#[cfg(feature = "floating")]
+use smithay::{
+    input::{
+        pointer::{Focus, GrabStartData as PointerGrabStartData},
+        Seat,
+    },
+    reexports::{wayland_protocols::xdg::shell::server::xdg_toplevel, wayland_server::Resource},
+    utils::Rectangle,
 };
 
+#[cfg(feature = "floating")]
+use crate::grabs::{MoveSurfaceGrab, ResizeSurfaceGrab};
+use crate::Smallvil;
+
 impl XdgShellHandler for Smallvil {
     fn xdg_shell_state(&mut self) -> &mut XdgShellState {
         &mut self.xdg_shell_state
@@ -52,6 +51,7 @@ impl XdgShellHandler for Smallvil {
         surface.send_repositioned(token);
     }
 
+    #[cfg(feature = "floating")]
     fn move_request(&mut self, surface: ToplevelSurface, seat: wl_seat::WlSeat, serial: Serial) {
         let seat = Seat::from_resource(&seat).unwrap();
 
@@ -78,6 +78,7 @@ impl XdgShellHandler for Smallvil {
         }
     }
 
+    #[cfg(feature = "floating")]
     fn resize_request(
         &mut self,
         surface: ToplevelSurface,
@@ -126,6 +127,7 @@ impl XdgShellHandler for Smallvil {
 // Xdg Shell
 delegate_xdg_shell!(Smallvil);
 
+#[cfg(feature = "floating")]
 fn check_grab(
     seat: &Seat<Smallvil>,
     surface: &WlSurface,
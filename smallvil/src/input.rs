@@ -1,10 +1,10 @@
 use smithay::{
     backend::input::{
         AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
-        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
+        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionEvent,
     },
     input::{
-        keyboard::FilterResult,
+        keyboard::{FilterResult, Keysym},
         pointer::{AxisFrame, ButtonEvent, MotionEvent},
     },
     reexports::wayland_server::protocol::wl_surface::WlSurface,
@@ -26,10 +26,49 @@ impl Smallvil {
                     event.state(),
                     serial,
                     time,
-                    |_, _, _| FilterResult::Forward,
+                    |state, modifiers, handle| {
+                        // There is no window to close when running on a tty, so offer a way out.
+                        if modifiers.ctrl && modifiers.alt && handle.modified_sym() == Keysym::BackSpace {
+                            state.loop_signal.stop();
+                            return FilterResult::Intercept(());
+                        }
+                        FilterResult::Forward
+                    },
                 );
             }
-            InputEvent::PointerMotion { .. } => {}
+            InputEvent::PointerMotion { event, .. } => {
+                let pointer = self.seat.get_pointer().unwrap();
+
+                // Relative motion, e.g. from libinput, moves the pointer within the bounds of all outputs.
+                let mut pos = pointer.current_location() + event.delta();
+                let bounds = self
+                    .space
+                    .outputs()
+                    .filter_map(|output| self.space.output_geometry(output))
+                    .reduce(|acc, geo| acc.merge(geo));
+                if let Some(bounds) = bounds {
+                    pos.x = pos
+                        .x
+                        .clamp(bounds.loc.x as f64, (bounds.loc.x + bounds.size.w - 1) as f64);
+                    pos.y = pos
+                        .y
+                        .clamp(bounds.loc.y as f64, (bounds.loc.y + bounds.size.h - 1) as f64);
+                }
+
+                let serial = SERIAL_COUNTER.next_serial();
+                let under = self.surface_under(pos);
+
+                pointer.motion(
+                    self,
+                    under,
+                    &MotionEvent {
+                        location: pos,
+                        serial,
+                        time: event.time_msec(),
+                    },
+                );
+                pointer.frame(self);
+            }
             InputEvent::PointerMotionAbsolute { event, .. } => {
                 let output = self.space.outputs().next().unwrap();
 
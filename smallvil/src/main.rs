@@ -2,9 +2,12 @@
 
 mod handlers;
 
+#[cfg(feature = "floating")]
 mod grabs;
 mod input;
 mod state;
+#[cfg(feature = "drm")]
+mod udev;
 mod winit;
 
 use smithay::reexports::{
@@ -36,7 +39,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         display_handle,
     };
 
-    crate::winit::init_winit(&mut event_loop, &mut data)?;
+    // Without a parent compositor smallvil drives the displays itself.
+    #[cfg(feature = "drm")]
+    let on_tty = std::env::var_os("WAYLAND_DISPLAY").is_none() && std::env::var_os("DISPLAY").is_none();
+    #[cfg(not(feature = "drm"))]
+    let on_tty = false;
+
+    if on_tty {
+        #[cfg(feature = "drm")]
+        crate::udev::init_udev(&mut event_loop, &mut data)?;
+    } else {
+        crate::winit::init_winit(&mut event_loop, &mut data)?;
+    }
 
     let mut args = std::env::args().skip(1);
     let flag = args.next();
@@ -1,5 +1,12 @@
 use std::{ffi::OsString, sync::Arc};
 
+#[cfg(feature = "screencopy")]
+use smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1;
+#[cfg(feature = "layer_shell")]
+use smithay::{
+    desktop::{layer_map_for_output, WindowSurfaceType as LayerSurfaceType},
+    wayland::shell::wlr_layer::{Layer, WlrLayerShellState},
+};
 use smithay::{
     desktop::{PopupManager, Space, Window, WindowSurfaceType},
     input::{Seat, SeatState},
@@ -22,6 +29,8 @@ use smithay::{
     },
 };
 
+#[cfg(feature = "screencopy")]
+use crate::handlers::screencopy::PendingScreencopy;
 use crate::CalloopData;
 
 pub struct Smallvil {
@@ -39,6 +48,10 @@ pub struct Smallvil {
     pub output_manager_state: OutputManagerState,
     pub seat_state: SeatState<Smallvil>,
     pub data_device_state: DataDeviceState,
+    #[cfg(feature = "layer_shell")]
+    pub layer_shell_state: WlrLayerShellState,
+    #[cfg(feature = "screencopy")]
+    pub pending_screencopies: Vec<PendingScreencopy>,
     pub popups: PopupManager,
 
     pub seat: Seat<Self>,
@@ -56,6 +69,11 @@ impl Smallvil {
         let output_manager_state = OutputManagerState::new_with_xdg_output::<Self>(&dh);
         let mut seat_state = SeatState::new();
         let data_device_state = DataDeviceState::new::<Self>(&dh);
+        #[cfg(feature = "layer_shell")]
+        let layer_shell_state = WlrLayerShellState::new::<Self>(&dh);
+        // Smithay has no abstraction for wlr-screencopy, so the global is created directly.
+        #[cfg(feature = "screencopy")]
+        dh.create_global::<Self, ZwlrScreencopyManagerV1, _>(3, ());
         let popups = PopupManager::default();
 
         // A seat is a group of keyboards, pointer and touch devices.
@@ -95,6 +113,10 @@ impl Smallvil {
             output_manager_state,
             seat_state,
             data_device_state,
+            #[cfg(feature = "layer_shell")]
+            layer_shell_state,
+            #[cfg(feature = "screencopy")]
+            pending_screencopies: Vec::new(),
             popups,
             seat,
         }
@@ -143,10 +165,40 @@ impl Smallvil {
     }
 
     pub fn surface_under(&self, pos: Point<f64, Logical>) -> Option<(WlSurface, Point<f64, Logical>)> {
-        self.space.element_under(pos).and_then(|(window, location)| {
+        #[cfg(feature = "layer_shell")]
+        if let Some(under) = self.layer_surface_under(pos, &[Layer::Overlay, Layer::Top]) {
+            return Some(under);
+        }
+
+        let under = self.space.element_under(pos).and_then(|(window, location)| {
             window
                 .surface_under(pos - location.to_f64(), WindowSurfaceType::ALL)
                 .map(|(s, p)| (s, (p + location).to_f64()))
+        });
+
+        #[cfg(feature = "layer_shell")]
+        let under = under.or_else(|| self.layer_surface_under(pos, &[Layer::Bottom, Layer::Background]));
+
+        under
+    }
+
+    /// Find the surface under the pointer on the given layers of the output below it
+    #[cfg(feature = "layer_shell")]
+    fn layer_surface_under(
+        &self,
+        pos: Point<f64, Logical>,
+        layers: &[Layer],
+    ) -> Option<(WlSurface, Point<f64, Logical>)> {
+        let output = self.space.output_under(pos).next()?;
+        let output_geo = self.space.output_geometry(output)?;
+        let map = layer_map_for_output(output);
+
+        layers.iter().find_map(|layer| {
+            let layer_surface = map.layer_under(*layer, pos - output_geo.loc.to_f64())?;
+            let layer_loc = map.layer_geometry(layer_surface)?.loc + output_geo.loc;
+            layer_surface
+                .surface_under(pos - layer_loc.to_f64(), LayerSurfaceType::ALL)
+                .map(|(s, p)| (s, (p + layer_loc).to_f64()))
         })
     }
 }
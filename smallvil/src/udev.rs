@@ -0,0 +1,362 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use smithay::{
+    backend::{
+        allocator::{
+            gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+            Fourcc,
+        },
+        drm::{
+            compositor::FrameFlags,
+            output::{DrmOutput, DrmOutputManager, DrmOutputRenderElements},
+            DrmDevice, DrmDeviceFd, DrmEvent, DrmNode,
+        },
+        egl::{EGLContext, EGLDisplay},
+        libinput::{LibinputInputBackend, LibinputSessionInterface},
+        renderer::{
+            element::{
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                surface::WaylandSurfaceRenderElement,
+                Kind,
+            },
+            gles::GlesRenderer,
+            ImportDma,
+        },
+        session::{libseat::LibSeatSession, Event as SessionEvent, Session},
+        udev::{primary_gpu, UdevBackend, UdevEvent},
+    },
+    desktop::space::{space_render_elements, SpaceRenderElements},
+    output::{Mode, Output, PhysicalProperties},
+    reexports::{
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            EventLoop, LoopHandle,
+        },
+        drm::control::{connector, crtc, ModeTypeFlags},
+        input::Libinput,
+        rustix::fs::OFlags,
+    },
+    render_elements,
+    utils::{DeviceFd, Scale},
+};
+use smithay_drm_extras::drm_scanner::{DrmScanEvent, DrmScanner};
+use tracing::{error, info, warn};
+
+use crate::{CalloopData, Smallvil};
+
+type GbmDrmOutputManager =
+    DrmOutputManager<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>;
+type GbmDrmOutput = DrmOutput<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>;
+
+render_elements! {
+    UdevRenderElements<=GlesRenderer>;
+    Space=SpaceRenderElements<GlesRenderer, WaylandSurfaceRenderElement<GlesRenderer>>,
+    Cursor=SolidColorRenderElement,
+}
+
+struct UdevData {
+    handle: LoopHandle<'static, CalloopData>,
+    node: DrmNode,
+    renderer: GlesRenderer,
+    // the outputs have to be dropped before the drm device of the output manager
+    outputs: HashMap<crtc::Handle, (Output, GbmDrmOutput)>,
+    drm_output_manager: GbmDrmOutputManager,
+    drm_scanner: DrmScanner,
+    // smallvil draws a plain square as its cursor
+    cursor: SolidColorBuffer,
+}
+
+pub fn init_udev(
+    event_loop: &mut EventLoop<'static, CalloopData>,
+    data: &mut CalloopData,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The session gives access to input and drm devices without running as root,
+    // and notifies us when switching to another virtual terminal.
+    let (mut session, notifier) = LibSeatSession::new()?;
+
+    let path = primary_gpu(session.seat())?.ok_or("No gpu found")?;
+    let node = DrmNode::from_path(&path)?;
+    info!("Using {} as primary gpu.", node);
+
+    let fd = session.open(
+        &path,
+        OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK,
+    )?;
+    let fd = DrmDeviceFd::new(DeviceFd::from(fd));
+
+    // The drm device drives the display hardware, gbm allocates the buffers we render into
+    // and EGL creates the OpenGL context rendering into them.
+    let (drm, drm_notifier) = DrmDevice::new(fd.clone(), true)?;
+    let gbm = GbmDevice::new(fd)?;
+    let egl = unsafe { EGLDisplay::new(gbm.clone())? };
+    let context = EGLContext::new(&egl)?;
+    let renderer = unsafe { GlesRenderer::new(context)? };
+
+    let allocator = GbmAllocator::new(gbm.clone(), GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT);
+    let render_formats = renderer.dmabuf_formats();
+    let drm_output_manager = DrmOutputManager::new(
+        drm,
+        allocator,
+        gbm.clone(),
+        Some(gbm),
+        [Fourcc::Argb8888, Fourcc::Xrgb8888],
+        render_formats,
+    );
+
+    let udev = Rc::new(RefCell::new(UdevData {
+        handle: event_loop.handle(),
+        node,
+        renderer,
+        drm_output_manager,
+        drm_scanner: DrmScanner::new(),
+        outputs: HashMap::new(),
+        cursor: SolidColorBuffer::new((8, 8), [1.0, 1.0, 1.0, 1.0]),
+    }));
+
+    // Libinput reads keyboards, mice and touchpads of our seat.
+    let mut libinput =
+        Libinput::new_with_udev::<LibinputSessionInterface<LibSeatSession>>(session.clone().into());
+    libinput.udev_assign_seat(&session.seat()).unwrap();
+    event_loop
+        .handle()
+        .insert_source(LibinputInputBackend::new(libinput.clone()), |event, _, data| {
+            data.state.process_input_event(event)
+        })?;
+
+    // Every vblank the previous frame has been presented and the next one can be rendered.
+    let udev_clone = udev.clone();
+    event_loop
+        .handle()
+        .insert_source(drm_notifier, move |event, _, data| match event {
+            DrmEvent::VBlank(crtc) => {
+                if let Some((_, drm_output)) = udev_clone.borrow_mut().outputs.get_mut(&crtc) {
+                    if let Err(err) = drm_output.frame_submitted() {
+                        warn!("Failed to submit frame: {}", err);
+                    }
+                }
+                render(&udev_clone, crtc, &mut data.state);
+            }
+            DrmEvent::Error(err) => error!("{:?}", err),
+        })?;
+
+    // Outputs change, when monitors are plugged in or out.
+    let udev_clone = udev.clone();
+    let udev_backend = UdevBackend::new(session.seat())?;
+    event_loop
+        .handle()
+        .insert_source(udev_backend, move |event, _, data| {
+            if let UdevEvent::Changed { device_id } = event {
+                if device_id == udev_clone.borrow().node.dev_id() {
+                    let connected = scan_connectors(&mut udev_clone.borrow_mut(), &mut data.state);
+                    for crtc in connected {
+                        render(&udev_clone, crtc, &mut data.state);
+                    }
+                }
+            }
+        })?;
+
+    // While another virtual terminal is active, we may not access the devices.
+    let udev_clone = udev.clone();
+    event_loop
+        .handle()
+        .insert_source(notifier, move |event, _, data| match event {
+            SessionEvent::PauseSession => {
+                libinput.suspend();
+                udev_clone.borrow_mut().drm_output_manager.pause();
+            }
+            SessionEvent::ActivateSession => {
+                if let Err(err) = libinput.resume() {
+                    error!("Failed to resume libinput context: {:?}", err);
+                }
+                if let Err(err) = udev_clone.borrow_mut().drm_output_manager.activate(false) {
+                    error!("Failed to activate drm device: {}", err);
+                }
+                render_all(&udev_clone, &mut data.state);
+            }
+        })?;
+
+    scan_connectors(&mut udev.borrow_mut(), &mut data.state);
+    render_all(&udev, &mut data.state);
+
+    std::env::set_var("WAYLAND_DISPLAY", &data.state.socket_name);
+
+    Ok(())
+}
+
+/// Returns the crtcs of newly connected outputs
+fn scan_connectors(udev: &mut UdevData, state: &mut Smallvil) -> Vec<crtc::Handle> {
+    let scan_result = match udev.drm_scanner.scan_connectors(udev.drm_output_manager.device()) {
+        Ok(scan_result) => scan_result,
+        Err(err) => {
+            warn!(?err, "Failed to scan connectors");
+            return Vec::new();
+        }
+    };
+
+    let mut connected = Vec::new();
+    for event in scan_result {
+        match event {
+            DrmScanEvent::Connected {
+                connector,
+                crtc: Some(crtc),
+            } => connected.extend(connector_connected(udev, state, connector, crtc).then_some(crtc)),
+            DrmScanEvent::Disconnected { crtc: Some(crtc), .. } => {
+                if let Some((output, _)) = udev.outputs.remove(&crtc) {
+                    state.space.unmap_output(&output);
+                }
+            }
+            _ => {}
+        }
+    }
+    connected
+}
+
+fn connector_connected(
+    udev: &mut UdevData,
+    state: &mut Smallvil,
+    connector: connector::Info,
+    crtc: crtc::Handle,
+) -> bool {
+    let name = format!("{}-{}", connector.interface().as_str(), connector.interface_id());
+    let Some(drm_mode) = connector
+        .modes()
+        .iter()
+        .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+        .or_else(|| connector.modes().first())
+        .copied()
+    else {
+        warn!("Connector {} has no modes", name);
+        return false;
+    };
+    let mode = Mode::from(drm_mode);
+
+    let (phys_w, phys_h) = connector.size().unwrap_or((0, 0));
+    let output = Output::new(
+        name,
+        PhysicalProperties {
+            size: (phys_w as i32, phys_h as i32).into(),
+            subpixel: connector.subpixel().into(),
+            make: "Smithay".into(),
+            model: "Generic DRM".into(),
+        },
+    );
+    let _global = output.create_global::<Smallvil>(&state.display_handle);
+
+    // New outputs are placed to the right of the existing ones.
+    let x = state
+        .space
+        .outputs()
+        .filter_map(|output| state.space.output_geometry(output))
+        .map(|geo| geo.loc.x + geo.size.w)
+        .max()
+        .unwrap_or(0);
+    output.set_preferred(mode);
+    output.change_current_state(Some(mode), None, None, Some((x, 0).into()));
+    state.space.map_output(&output, (x, 0));
+
+    // The drm output drives the crtc with a compositor deciding which elements are scanned out directly.
+    let drm_output = match udev
+        .drm_output_manager
+        .initialize_output::<_, UdevRenderElements>(
+            crtc,
+            drm_mode,
+            &[connector.handle()],
+            &output,
+            None,
+            &mut udev.renderer,
+            &DrmOutputRenderElements::default(),
+        ) {
+        Ok(drm_output) => drm_output,
+        Err(err) => {
+            warn!("Failed to initialize drm output: {}", err);
+            state.space.unmap_output(&output);
+            return false;
+        }
+    };
+
+    udev.outputs.insert(crtc, (output, drm_output));
+    true
+}
+
+fn render_all(udev: &Rc<RefCell<UdevData>>, state: &mut Smallvil) {
+    let crtcs: Vec<_> = udev.borrow().outputs.keys().copied().collect();
+    for crtc in crtcs {
+        render(udev, crtc, state);
+    }
+}
+
+fn render(udev: &Rc<RefCell<UdevData>>, crtc: crtc::Handle, state: &mut Smallvil) {
+    let mut guard = udev.borrow_mut();
+    let UdevData {
+        handle,
+        renderer,
+        outputs,
+        cursor,
+        ..
+    } = &mut *guard;
+    let Some((output, drm_output)) = outputs.get_mut(&crtc) else {
+        return;
+    };
+
+    let mut elements = Vec::new();
+    let output_geo = state.space.output_geometry(output).unwrap();
+    let pointer = state.seat.get_pointer().unwrap().current_location();
+    if output_geo.to_f64().contains(pointer) {
+        let scale = Scale::from(output.current_scale().fractional_scale());
+        let location = (pointer - output_geo.loc.to_f64())
+            .to_physical(scale)
+            .to_i32_round();
+        elements.push(UdevRenderElements::Cursor(SolidColorRenderElement::from_buffer(
+            cursor,
+            location,
+            scale,
+            1.0,
+            Kind::Cursor,
+        )));
+    }
+    elements.extend(
+        space_render_elements(renderer, [&state.space], output, 1.0)
+            .unwrap()
+            .into_iter()
+            .map(UdevRenderElements::Space),
+    );
+
+    let rendered =
+        match drm_output.render_frame(renderer, &elements, [0.1, 0.1, 0.1, 1.0], FrameFlags::DEFAULT) {
+            Ok(result) => !result.is_empty,
+            Err(err) => {
+                warn!("Failed to render frame: {}", err);
+                false
+            }
+        };
+    let queued = rendered
+        && drm_output
+            .queue_frame(())
+            .inspect_err(|err| warn!("Failed to queue frame: {}", err))
+            .is_ok();
+
+    crate::handlers::screencopy::render_screencopies(state, renderer, output);
+
+    state.space.elements().for_each(|window| {
+        window.send_frame(
+            output,
+            state.start_time.elapsed(),
+            Some(Duration::ZERO),
+            |_, _| Some(output.clone()),
+        )
+    });
+    state.space.refresh();
+    state.popups.cleanup();
+    let _ = state.display_handle.flush_clients();
+
+    // Without a queued frame there will be no vblank, so we try again after one refresh cycle.
+    if !queued {
+        let refresh = output.current_mode().map(|mode| mode.refresh).unwrap_or(60_000);
+        let timer = Timer::from_duration(Duration::from_micros(1_000_000_000 / refresh as u64));
+        let udev = udev.clone();
+        let _ = handle.insert_source(timer, move |_, _, data| {
+            render(&udev, crtc, &mut data.state);
+            TimeoutAction::Drop
+        });
+    }
+}
@@ -62,6 +62,8 @@ pub fn init_winit(
                     None,
                     None,
                 );
+                #[cfg(feature = "layer_shell")]
+                smithay::desktop::layer_map_for_output(&output).arrange();
             }
             WinitEvent::Input(event) => state.process_input_event(event),
             WinitEvent::Redraw => {
@@ -80,6 +82,8 @@ pub fn init_winit(
                     [0.1, 0.1, 0.1, 1.0],
                 )
                 .unwrap();
+                #[cfg(feature = "screencopy")]
+                crate::handlers::screencopy::render_screencopies(state, backend.renderer(), &output);
                 backend.submit(Some(&[damage])).unwrap();
 
                 state.space.elements().for_each(|window| {
@@ -26,9 +26,10 @@ struct State {
 }
 
 struct Device {
+    // surfaces have to be dropped before the device
+    surfaces: HashMap<crtc::Handle, Surface>,
     drm: drm::DrmDevice,
     drm_scanner: drm_scanner::DrmScanner,
-    surfaces: HashMap<crtc::Handle, Surface>,
 }
 
 #[derive(Clone)]
@@ -96,6 +97,7 @@ impl State {
                     self.device_removed(node);
                 }
             }
+            _ => {}
         }
     }
 
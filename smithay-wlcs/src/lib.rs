@@ -0,0 +1,414 @@
+//! # Smithay WLCS
+//!
+//! This crate provides a [WLCS](https://github.com/canonical/wlcs) integration for any
+//! smithay-based compositor.
+//!
+//! Implement [`WlcsCompositor`] for the state of your compositor and expose the resulting
+//! [`WlcsServerHandle`] to WLCS using the `wlcs_server_integration!` macro from a `cdylib` crate:
+//!
+//! ```ignore
+//! type MyServerHandle = smithay_wlcs::WlcsServerHandle<MyState>;
+//! wlcs::wlcs_server_integration!(MyServerHandle);
+//! ```
+//!
+//! The harness runs the compositor on its own thread, inserts the clients created by WLCS
+//! and forwards window placement and input requests to the [`WlcsCompositor`] implementation.
+
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    marker::PhantomData,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    sync::atomic::{AtomicU32, Ordering},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use smithay::{
+    backend::input::ButtonState,
+    reexports::{
+        calloop::{
+            channel::{channel, Channel, Event as ChannelEvent, Sender},
+            EventLoop, LoopHandle,
+        },
+        wayland_server::{protocol::wl_surface::WlSurface, Client, DisplayHandle},
+    },
+    utils::{Logical, Point},
+};
+
+use wayland_sys::{
+    client::{wl_display, wl_display_get_fd, wl_proxy, wl_proxy_get_id},
+    common::{wl_fixed_t, wl_fixed_to_double},
+    ffi_dispatch,
+};
+use wlcs::{ffi_display_server_api::WlcsIntegrationDescriptor, Wlcs};
+
+static DEVICE_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Event sent by WLCS to control the compositor
+#[derive(Debug)]
+pub enum WlcsEvent {
+    /// Stop the running server
+    Exit,
+    /// Create a new client from given RawFd
+    NewClient {
+        stream: UnixStream,
+        client_id: i32,
+    },
+    /// Position this window from the client associated with this Fd on the global space
+    PositionWindow {
+        client_id: i32,
+        surface_id: u32,
+        location: Point<i32, Logical>,
+    },
+    /* Pointer related events */
+    /// A new pointer device is available
+    NewPointer {
+        device_id: u32,
+    },
+    /// Move the pointer in absolute coordinate space
+    PointerMoveAbsolute {
+        device_id: u32,
+        location: Point<f64, Logical>,
+    },
+    /// Move the pointer in relative coordinate space
+    PointerMoveRelative {
+        device_id: u32,
+        delta: Point<f64, Logical>,
+    },
+    /// Press a pointer button
+    PointerButtonDown {
+        device_id: u32,
+        button_id: i32,
+    },
+    /// Release a pointer button
+    PointerButtonUp {
+        device_id: u32,
+        button_id: i32,
+    },
+    /// A pointer device is removed
+    PointerRemoved {
+        device_id: u32,
+    },
+    /* Touch related events */
+    /// A new touch device is available
+    NewTouch {
+        device_id: u32,
+    },
+    /// A touch point is down
+    TouchDown {
+        device_id: u32,
+        location: Point<f64, Logical>,
+    },
+    /// A touch point moved
+    TouchMove {
+        device_id: u32,
+        location: Point<f64, Logical>,
+    },
+    /// A touch point is up
+    TouchUp {
+        device_id: u32,
+    },
+    TouchRemoved {
+        device_id: u32,
+    },
+}
+
+/// A compositor driven by WLCS
+///
+/// The compositor state is the data of the event loop run by the harness, so this trait is
+/// usually implemented for a marker type. All functions are called on the compositor thread.
+pub trait WlcsCompositor: 'static {
+    /// State of the compositor
+    type State: 'static;
+
+    /// Extensions supported by the compositor
+    fn descriptor() -> &'static WlcsIntegrationDescriptor;
+
+    /// Create the compositor state
+    ///
+    /// The compositor should advertise at least one output.
+    fn spawn(handle: LoopHandle<'static, Self::State>) -> Self::State;
+
+    /// Returns the display handle of the compositor
+    fn display_handle(state: &Self::State) -> DisplayHandle;
+
+    /// Returns false once the compositor should stop
+    fn running(state: &Self::State) -> bool;
+
+    /// Request the compositor to stop
+    fn stop(state: &mut Self::State);
+
+    /// Insert a new client connected through the given stream
+    fn new_client(state: &mut Self::State, stream: UnixStream) -> Client;
+
+    /// Move the window of the given toplevel surface to an absolute location
+    fn position_window(state: &mut Self::State, surface: &WlSurface, location: Point<i32, Logical>);
+
+    /// Move the pointer to an absolute location
+    fn pointer_move_absolute(state: &mut Self::State, device_id: u32, location: Point<f64, Logical>);
+
+    /// Move the pointer relative to its current location
+    fn pointer_move_relative(state: &mut Self::State, device_id: u32, delta: Point<f64, Logical>);
+
+    /// Press or release a pointer button
+    fn pointer_button(state: &mut Self::State, device_id: u32, button: u32, button_state: ButtonState);
+
+    /// Put a touch point down
+    fn touch_down(state: &mut Self::State, device_id: u32, location: Point<f64, Logical>) {
+        let _ = (state, device_id, location);
+    }
+
+    /// Move a touch point
+    fn touch_move(state: &mut Self::State, device_id: u32, location: Point<f64, Logical>) {
+        let _ = (state, device_id, location);
+    }
+
+    /// Lift a touch point
+    fn touch_up(state: &mut Self::State, device_id: u32) {
+        let _ = (state, device_id);
+    }
+
+    /// Called before every iteration of the event loop
+    ///
+    /// This is the place to render and to send frame callbacks to clients.
+    fn frame(state: &mut Self::State) {
+        let _ = state;
+    }
+
+    /// Called after every iteration of the event loop
+    ///
+    /// This is the place to refresh state and to flush clients.
+    fn refresh(state: &mut Self::State) {
+        let _ = state;
+    }
+}
+
+/// Server handle for WLCS running a [`WlcsCompositor`]
+pub struct WlcsServerHandle<C> {
+    server: Option<(Sender<WlcsEvent>, JoinHandle<()>)>,
+    _compositor: PhantomData<fn() -> C>,
+}
+
+impl<C: WlcsCompositor> Wlcs for WlcsServerHandle<C> {
+    type Pointer = PointerHandle;
+    type Touch = TouchHandle;
+
+    fn new() -> Self {
+        WlcsServerHandle {
+            server: None,
+            _compositor: PhantomData,
+        }
+    }
+
+    fn start(&mut self) {
+        let (tx, rx) = channel();
+        let join = std::thread::spawn(move || run::<C>(rx));
+        self.server = Some((tx, join));
+    }
+
+    fn stop(&mut self) {
+        if let Some((sender, join)) = self.server.take() {
+            let _ = sender.send(WlcsEvent::Exit);
+            let _ = join.join();
+        }
+    }
+
+    fn create_client_socket(&self) -> std::io::Result<OwnedFd> {
+        if let Some((ref sender, _)) = self.server {
+            if let Ok((client_side, server_side)) = UnixStream::pair() {
+                if let Err(e) = sender.send(WlcsEvent::NewClient {
+                    stream: server_side,
+                    client_id: client_side.as_raw_fd(),
+                }) {
+                    return Err(Error::new(ErrorKind::ConnectionReset, e));
+                }
+                return Ok(client_side.into());
+            }
+        }
+        Err(Error::from(ErrorKind::NotFound))
+    }
+
+    // the signature is given by the `Wlcs` trait, WLCS passes valid pointers
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn position_window_absolute(&self, display: *mut wl_display, surface: *mut wl_proxy, x: i32, y: i32) {
+        let client_id = unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_display_get_fd, display) };
+        let surface_id = unsafe { ffi_dispatch!(WAYLAND_CLIENT_HANDLE, wl_proxy_get_id, surface) };
+        if let Some((ref sender, _)) = self.server {
+            let _ = sender.send(WlcsEvent::PositionWindow {
+                client_id,
+                surface_id,
+                location: (x, y).into(),
+            });
+        }
+    }
+
+    fn create_pointer(&mut self) -> Option<Self::Pointer> {
+        let server = self.server.as_ref()?;
+        Some(PointerHandle {
+            device_id: DEVICE_ID.fetch_add(1, Ordering::Relaxed),
+            sender: server.0.clone(),
+        })
+    }
+
+    fn create_touch(&mut self) -> Option<Self::Touch> {
+        let server = self.server.as_ref()?;
+        Some(TouchHandle {
+            device_id: DEVICE_ID.fetch_add(1, Ordering::Relaxed),
+            sender: server.0.clone(),
+        })
+    }
+
+    fn get_descriptor(&self) -> &WlcsIntegrationDescriptor {
+        C::descriptor()
+    }
+}
+
+/// Pointer device created by WLCS
+pub struct PointerHandle {
+    device_id: u32,
+    sender: Sender<WlcsEvent>,
+}
+
+impl wlcs::Pointer for PointerHandle {
+    fn move_absolute(&mut self, x: wl_fixed_t, y: wl_fixed_t) {
+        let _ = self.sender.send(WlcsEvent::PointerMoveAbsolute {
+            device_id: self.device_id,
+            location: (wl_fixed_to_double(x), wl_fixed_to_double(y)).into(),
+        });
+    }
+
+    fn move_relative(&mut self, dx: wl_fixed_t, dy: wl_fixed_t) {
+        let _ = self.sender.send(WlcsEvent::PointerMoveRelative {
+            device_id: self.device_id,
+            delta: (wl_fixed_to_double(dx), wl_fixed_to_double(dy)).into(),
+        });
+    }
+
+    fn button_up(&mut self, button: i32) {
+        let _ = self.sender.send(WlcsEvent::PointerButtonUp {
+            device_id: self.device_id,
+            button_id: button,
+        });
+    }
+
+    fn button_down(&mut self, button: i32) {
+        let _ = self.sender.send(WlcsEvent::PointerButtonDown {
+            device_id: self.device_id,
+            button_id: button,
+        });
+    }
+
+    fn destroy(&mut self) {}
+}
+
+/// Touch device created by WLCS
+pub struct TouchHandle {
+    device_id: u32,
+    sender: Sender<WlcsEvent>,
+}
+
+impl wlcs::Touch for TouchHandle {
+    fn touch_down(&mut self, x: wl_fixed_t, y: wl_fixed_t) {
+        let _ = self.sender.send(WlcsEvent::TouchDown {
+            device_id: self.device_id,
+            location: (wl_fixed_to_double(x), wl_fixed_to_double(y)).into(),
+        });
+    }
+
+    fn touch_move(&mut self, x: wl_fixed_t, y: wl_fixed_t) {
+        let _ = self.sender.send(WlcsEvent::TouchMove {
+            device_id: self.device_id,
+            location: (wl_fixed_to_double(x), wl_fixed_to_double(y)).into(),
+        });
+    }
+
+    fn touch_up(&mut self) {
+        let _ = self.sender.send(WlcsEvent::TouchUp {
+            device_id: self.device_id,
+        });
+    }
+
+    fn destroy(&mut self) {}
+}
+
+/// Run a [`WlcsCompositor`] until it is stopped, processing the events of the channel
+pub fn run<C: WlcsCompositor>(channel: Channel<WlcsEvent>) {
+    let mut event_loop = EventLoop::<C::State>::try_new().expect("Failed to init the event loop.");
+    let mut state = C::spawn(event_loop.handle());
+
+    let mut clients = HashMap::new();
+    event_loop
+        .handle()
+        .insert_source(channel, move |event, &mut (), state| match event {
+            ChannelEvent::Msg(evt) => handle_event::<C>(evt, state, &mut clients),
+            ChannelEvent::Closed => handle_event::<C>(WlcsEvent::Exit, state, &mut clients),
+        })
+        .unwrap();
+
+    while C::running(&state) {
+        C::frame(&mut state);
+
+        if event_loop
+            .dispatch(Some(Duration::from_millis(16)), &mut state)
+            .is_err()
+        {
+            C::stop(&mut state);
+        } else {
+            C::refresh(&mut state);
+        }
+    }
+}
+
+fn handle_event<C: WlcsCompositor>(
+    event: WlcsEvent,
+    state: &mut C::State,
+    clients: &mut HashMap<i32, Client>,
+) {
+    match event {
+        WlcsEvent::Exit => C::stop(state),
+        WlcsEvent::NewClient { stream, client_id } => {
+            let client = C::new_client(state, stream);
+            clients.insert(client_id, client);
+        }
+        WlcsEvent::PositionWindow {
+            client_id,
+            surface_id,
+            location,
+        } => {
+            // find the surface
+            let Some(client) = clients.get(&client_id) else {
+                return;
+            };
+            if let Ok(surface) =
+                client.object_from_protocol_id::<WlSurface>(&C::display_handle(state), surface_id)
+            {
+                C::position_window(state, &surface, location);
+            }
+        }
+        // pointer inputs
+        WlcsEvent::NewPointer { .. } => {}
+        WlcsEvent::PointerMoveAbsolute { device_id, location } => {
+            C::pointer_move_absolute(state, device_id, location)
+        }
+        WlcsEvent::PointerMoveRelative { device_id, delta } => {
+            C::pointer_move_relative(state, device_id, delta)
+        }
+        WlcsEvent::PointerButtonDown { device_id, button_id } => {
+            C::pointer_button(state, device_id, button_id as u32, ButtonState::Pressed)
+        }
+        WlcsEvent::PointerButtonUp { device_id, button_id } => {
+            C::pointer_button(state, device_id, button_id as u32, ButtonState::Released)
+        }
+        WlcsEvent::PointerRemoved { .. } => {}
+        // touch inputs
+        WlcsEvent::NewTouch { .. } => {}
+        WlcsEvent::TouchDown { device_id, location } => C::touch_down(state, device_id, location),
+        WlcsEvent::TouchMove { device_id, location } => C::touch_move(state, device_id, location),
+        WlcsEvent::TouchUp { device_id } => C::touch_up(state, device_id),
+        WlcsEvent::TouchRemoved { .. } => {}
+    }
+}
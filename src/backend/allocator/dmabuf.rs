@@ -292,6 +292,22 @@ impl Dmabuf {
         Ok((blocker, source))
     }
 
+    /// Returns `true` if all implicit fences relevant for reading this [`Dmabuf`] are signaled
+    ///
+    /// This never blocks.
+    #[cfg(all(feature = "backend_drm", feature = "backend_gbm"))]
+    pub(crate) fn is_ready_for_read(&self) -> bool {
+        self.handles().all(|handle| {
+            matches!(
+                rustix::event::poll(
+                    &mut [rustix::event::PollFd::new(&handle, rustix::event::PollFlags::IN)],
+                    0
+                ),
+                Ok(1)
+            )
+        })
+    }
+
     /// Map the plane at specified index with the specified mode
     ///
     /// Returns `Err` if the plane with the specified index does not exist or
@@ -1,10 +1,17 @@
 //! Module for [DumbBuffer](https://docs.kernel.org/gpu/drm-kms.html#dumb-buffer-objects) buffers
+//!
+//! Dumb buffers are always linear and cpu accessible. Besides single-planar rgb formats the
+//! [`DumbAllocator`] supports semi-planar (e.g. [`Fourcc::Nv12`] or [`Fourcc::P010`]) and
+//! planar 4:2:0 yuv formats, by allocating all planes in a single buffer object.
 
 use std::fmt;
 use std::io;
 
 use drm::buffer::Buffer as DrmBuffer;
-use drm::control::{dumbbuffer::DumbBuffer as Handle, Device as ControlDevice};
+use drm::control::{
+    dumbbuffer::{DumbBuffer as Handle, DumbMapping},
+    Device as ControlDevice,
+};
 use tracing::instrument;
 
 use super::dmabuf::{AsDmabuf, Dmabuf, DmabufFlags};
@@ -18,6 +25,8 @@ pub struct DumbBuffer {
     fd: DrmDeviceFd,
     handle: Handle,
     format: Format,
+    size: Size<i32, BufferCoords>,
+    layout: PlaneLayout,
 }
 
 impl fmt::Debug for DumbBuffer {
@@ -25,10 +34,88 @@ impl fmt::Debug for DumbBuffer {
         f.debug_struct("DumbBuffer")
             .field("handle", &self.handle)
             .field("format", &self.format)
+            .field("size", &self.size)
+            .field("layout", &self.layout)
             .finish()
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PlaneLayout {
+    num_planes: usize,
+    offsets: [u32; 4],
+    pitches: [u32; 4],
+}
+
+/// Chroma subsampling of a yuv format, as divisor of the width and height
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Subsampling {
+    /// Two planes, luma followed by interleaved chroma
+    SemiPlanar { vertical: u32 },
+    /// Three planes, luma followed by both chroma planes each with half the pitch
+    Planar,
+}
+
+/// Returns the bits per pixel of the first plane and the subsampling of the format
+const fn format_layout(fourcc: Fourcc) -> Option<(u32, Option<Subsampling>)> {
+    match fourcc {
+        Fourcc::Nv12 | Fourcc::Nv21 => Some((8, Some(Subsampling::SemiPlanar { vertical: 2 }))),
+        Fourcc::Nv16 | Fourcc::Nv61 => Some((8, Some(Subsampling::SemiPlanar { vertical: 1 }))),
+        Fourcc::P010 | Fourcc::P012 | Fourcc::P016 => {
+            Some((16, Some(Subsampling::SemiPlanar { vertical: 2 })))
+        }
+        Fourcc::P210 => Some((16, Some(Subsampling::SemiPlanar { vertical: 1 }))),
+        Fourcc::Yuv420 | Fourcc::Yvu420 => Some((8, Some(Subsampling::Planar))),
+        fourcc => match get_bpp(fourcc) {
+            Some(bpp) => Some((bpp as u32, None)),
+            None => None,
+        },
+    }
+}
+
+/// Returns the bits per pixel and the size of the dumb buffer to allocate
+fn allocation_size(fourcc: Fourcc, width: u32, height: u32) -> Option<(u32, (u32, u32))> {
+    let (bpp, subsampling) = format_layout(fourcc)?;
+    let chroma_height = match subsampling {
+        None => 0,
+        Some(Subsampling::SemiPlanar { vertical }) => height.div_ceil(vertical),
+        Some(Subsampling::Planar) => height.div_ceil(2),
+    };
+    // Chroma planes are horizontally subsampled, so make sure odd widths are rounded up
+    let width = if subsampling.is_some() {
+        width.next_multiple_of(2)
+    } else {
+        width
+    };
+    Some((bpp, (width, height + chroma_height)))
+}
+
+/// Returns the layout of the planes given the pitch of the allocated dumb buffer
+fn plane_layout(fourcc: Fourcc, height: u32, pitch: u32) -> Option<PlaneLayout> {
+    let (_, subsampling) = format_layout(fourcc)?;
+    let mut layout = PlaneLayout {
+        num_planes: 1,
+        offsets: [0; 4],
+        pitches: [pitch, 0, 0, 0],
+    };
+    match subsampling {
+        None => {}
+        Some(Subsampling::SemiPlanar { .. }) => {
+            layout.num_planes = 2;
+            layout.offsets[1] = pitch * height;
+            layout.pitches[1] = pitch;
+        }
+        Some(Subsampling::Planar) => {
+            layout.num_planes = 3;
+            layout.offsets[1] = pitch * height;
+            layout.offsets[2] = pitch * height + (pitch / 2) * height.div_ceil(2);
+            layout.pitches[1] = pitch / 2;
+            layout.pitches[2] = pitch / 2;
+        }
+    }
+    Some(layout)
+}
+
 /// Light wrapper around an [`DrmDeviceFd`] to implement the [`Allocator`]-trait
 #[derive(Debug)]
 pub struct DumbAllocator {
@@ -40,6 +127,24 @@ impl DumbAllocator {
     pub fn new(fd: DrmDeviceFd) -> Self {
         DumbAllocator { fd }
     }
+
+    /// Create a buffer suitable for the cursor plane
+    ///
+    /// `size` should be the cursor size of the device as reported by
+    /// [`DrmDevice::cursor_size`](crate::backend::drm::DrmDevice::cursor_size),
+    /// as cursor planes usually do not support other sizes.
+    /// Only [`Fourcc::Argb8888`] and [`Fourcc::Xrgb8888`] are accepted, as
+    /// these are the only formats supported by the legacy cursor api.
+    pub fn create_cursor_buffer(
+        &mut self,
+        size: Size<u32, BufferCoords>,
+        fourcc: Fourcc,
+    ) -> Result<DumbBuffer, io::Error> {
+        if !matches!(fourcc, Fourcc::Argb8888 | Fourcc::Xrgb8888) {
+            return Err(rustix::io::Errno::INVAL.into());
+        }
+        self.create_buffer(size.w, size.h, fourcc, &[Modifier::Linear])
+    }
 }
 
 impl Allocator for DumbAllocator {
@@ -63,11 +168,11 @@ impl Allocator for DumbAllocator {
             return Err(rustix::io::Errno::INVAL.into());
         }
 
-        let handle = self.fd.create_dumb_buffer(
-            (width, height),
-            fourcc,
-            get_bpp(fourcc).ok_or(rustix::io::Errno::INVAL)? as u32,
-        )?;
+        let (bpp, allocation_size) =
+            allocation_size(fourcc, width, height).ok_or(rustix::io::Errno::INVAL)?;
+        let handle = self.fd.create_dumb_buffer(allocation_size, fourcc, bpp)?;
+        // Use the pitch returned by the kernel, it already matches the alignment requirements of the device
+        let layout = plane_layout(fourcc, height, handle.pitch()).ok_or(rustix::io::Errno::INVAL)?;
 
         Ok(DumbBuffer {
             fd: self.fd.clone(),
@@ -76,14 +181,15 @@ impl Allocator for DumbAllocator {
                 code: fourcc,
                 modifier: Modifier::Linear,
             },
+            size: (width as i32, height as i32).into(),
+            layout,
         })
     }
 }
 
 impl Buffer for DumbBuffer {
     fn size(&self) -> Size<i32, BufferCoords> {
-        let (w, h) = self.handle.size();
-        (w as i32, h as i32).into()
+        self.size
     }
 
     fn format(&self) -> Format {
@@ -99,6 +205,29 @@ impl DumbBuffer {
     pub fn handle(&self) -> &Handle {
         &self.handle
     }
+
+    /// Returns the number of planes of this buffer
+    pub fn num_planes(&self) -> usize {
+        self.layout.num_planes
+    }
+
+    /// Returns the offsets of the planes of this buffer
+    pub fn offsets(&self) -> impl Iterator<Item = u32> + '_ {
+        self.layout.offsets.into_iter().take(self.layout.num_planes)
+    }
+
+    /// Returns the pitches of the planes of this buffer
+    pub fn pitches(&self) -> impl Iterator<Item = u32> + '_ {
+        self.layout.pitches.into_iter().take(self.layout.num_planes)
+    }
+
+    /// Map the buffer into memory for cpu access
+    ///
+    /// All planes are part of the same mapping, use [`DumbBuffer::offsets`]
+    /// and [`DumbBuffer::pitches`] to access them.
+    pub fn map(&mut self) -> io::Result<DumbMapping<'_>> {
+        self.fd.map_dumb_buffer(&mut self.handle)
+    }
 }
 
 impl AsDmabuf for DumbBuffer {
@@ -115,7 +244,10 @@ impl AsDmabuf for DumbBuffer {
             self.format.modifier,
             DmabufFlags::empty(),
         );
-        builder.add_plane(fd, 0, 0, self.handle.pitch());
+        // all planes share the same buffer object, so every plane gets a duplicate of the same fd
+        for (idx, (offset, pitch)) in self.offsets().zip(self.pitches()).enumerate() {
+            builder.add_plane(fd.try_clone()?, idx as u32, offset, pitch);
+        }
         if let Ok(node) = DrmNode::from_file(&self.fd) {
             builder.set_node(node);
         }
@@ -128,3 +260,54 @@ impl Drop for DumbBuffer {
         let _ = self.fd.destroy_dumb_buffer(self.handle);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{allocation_size, plane_layout, Fourcc, PlaneLayout};
+
+    #[test]
+    fn rgb_layout() {
+        assert_eq!(allocation_size(Fourcc::Argb8888, 63, 64), Some((32, (63, 64))));
+        assert_eq!(
+            plane_layout(Fourcc::Argb8888, 64, 256),
+            Some(PlaneLayout {
+                num_planes: 1,
+                offsets: [0; 4],
+                pitches: [256, 0, 0, 0],
+            })
+        );
+    }
+
+    #[test]
+    fn semi_planar_layout() {
+        assert_eq!(allocation_size(Fourcc::Nv12, 63, 63), Some((8, (64, 95))));
+        assert_eq!(allocation_size(Fourcc::P010, 64, 64), Some((16, (64, 96))));
+        assert_eq!(allocation_size(Fourcc::Nv16, 64, 64), Some((8, (64, 128))));
+        assert_eq!(
+            plane_layout(Fourcc::Nv12, 63, 64),
+            Some(PlaneLayout {
+                num_planes: 2,
+                offsets: [0, 64 * 63, 0, 0],
+                pitches: [64, 64, 0, 0],
+            })
+        );
+    }
+
+    #[test]
+    fn planar_layout() {
+        assert_eq!(allocation_size(Fourcc::Yuv420, 64, 63), Some((8, (64, 95))));
+        assert_eq!(
+            plane_layout(Fourcc::Yuv420, 63, 64),
+            Some(PlaneLayout {
+                num_planes: 3,
+                offsets: [0, 64 * 63, 64 * 63 + 32 * 32, 0],
+                pitches: [64, 32, 32, 0],
+            })
+        );
+    }
+
+    #[test]
+    fn unsupported_format() {
+        assert_eq!(allocation_size(Fourcc::Yuyv, 64, 64), None);
+    }
+}
@@ -1,6 +1,7 @@
 //! Format info tables for DRM formats.
 //!
-//! This module provides three functions, [`get_opaque`], [`has_alpha`] and [`get_bpp`].
+//! This module provides functions to query properties of DRM formats, like [`get_opaque`],
+//! [`has_alpha`], [`get_bpp`] or [`get_plane_count`].
 //!
 //! [`get_opaque`] returns the opaque alternative of a DRM format with an alpha channel.
 //!
@@ -36,6 +37,16 @@
 //! assert_eq!(get_depth(Fourcc::Argb8888), Some(32));
 //! assert_eq!(get_depth(Fourcc::Xrgb8888), Some(24));
 //! ```
+//!
+//! [`get_plane_count`] returns the number of memory planes of a format.
+//!
+//! ```
+//! # use smithay::backend::allocator::Fourcc;
+//! # use smithay::backend::allocator::format::get_plane_count;
+//! assert_eq!(get_plane_count(Fourcc::Argb8888), Some(1));
+//! assert_eq!(get_plane_count(Fourcc::Nv12), Some(2));
+//! assert_eq!(get_plane_count(Fourcc::Yuv420), Some(3));
+//! ```
 
 use std::sync::Arc;
 
@@ -368,6 +379,74 @@ format_tables! {
     // TODO: YUV and other formats
 }
 
+/// Returns the number of memory planes of the specified format.
+///
+/// This does not include auxiliary planes a modifier might add (e.g. for compression).
+///
+/// Unknown formats will always return [`None`].
+pub const fn get_plane_count(fourcc: super::Fourcc) -> Option<usize> {
+    use super::Fourcc;
+
+    match fourcc {
+        // 2 plane YCbCr, Y followed by interleaved CbCr or CrCb
+        Fourcc::Nv12
+        | Fourcc::Nv21
+        | Fourcc::Nv15
+        | Fourcc::Nv16
+        | Fourcc::Nv61
+        | Fourcc::Nv24
+        | Fourcc::Nv42
+        | Fourcc::P010
+        | Fourcc::P012
+        | Fourcc::P016
+        | Fourcc::P210 => Some(2),
+        // 2 plane RGB + A
+        Fourcc::Rgb565_a8
+        | Fourcc::Bgr565_a8
+        | Fourcc::Rgb888_a8
+        | Fourcc::Bgr888_a8
+        | Fourcc::Xrgb8888_a8
+        | Fourcc::Xbgr8888_a8
+        | Fourcc::Rgbx8888_a8
+        | Fourcc::Bgrx8888_a8 => Some(2),
+        // 3 plane YCbCr
+        Fourcc::Yuv410
+        | Fourcc::Yvu410
+        | Fourcc::Yuv411
+        | Fourcc::Yvu411
+        | Fourcc::Yuv420
+        | Fourcc::Yvu420
+        | Fourcc::Yuv422
+        | Fourcc::Yvu422
+        | Fourcc::Yuv444
+        | Fourcc::Yvu444
+        | Fourcc::Q410
+        | Fourcc::Q401 => Some(3),
+        // packed YCbCr
+        Fourcc::Yuyv
+        | Fourcc::Yvyu
+        | Fourcc::Uyvy
+        | Fourcc::Vyuy
+        | Fourcc::Ayuv
+        | Fourcc::Xyuv8888
+        | Fourcc::Vuy888
+        | Fourcc::Vuy101010
+        | Fourcc::Y210
+        | Fourcc::Y212
+        | Fourcc::Y216
+        | Fourcc::Y410
+        | Fourcc::Y412
+        | Fourcc::Y416
+        | Fourcc::Xvyu2101010
+        | Fourcc::Xvyu12_16161616
+        | Fourcc::Xvyu16161616 => Some(1),
+        fourcc => match get_bpp(fourcc) {
+            Some(_) => Some(1),
+            None => None,
+        },
+    }
+}
+
 /// A set of [`Format`]s
 #[derive(Debug, Default, Clone)]
 pub struct FormatSet {
@@ -493,7 +572,8 @@ impl Iterator for FormatSetIntoIter {
 
 #[cfg(test)]
 mod tests {
-    use super::{_impl_formats, get_bpp, get_depth, get_opaque, get_transparent, has_alpha};
+    use super::{_impl_formats, get_bpp, get_depth, get_opaque, get_plane_count, get_transparent, has_alpha};
+    use crate::backend::allocator::Fourcc;
 
     /// Tests that opaque alternatives are not the same as the variant with alpha.
     #[test]
@@ -606,4 +686,25 @@ mod tests {
             );
         }
     }
+
+    /// All formats with a known bpp are single-planar RGB formats
+    #[test]
+    fn rgb_formats_are_single_planar() {
+        for &format in _impl_formats() {
+            assert_eq!(
+                get_plane_count(format),
+                Some(1),
+                "{} is expected to have a single plane",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn yuv_plane_count() {
+        assert_eq!(get_plane_count(Fourcc::Nv12), Some(2));
+        assert_eq!(get_plane_count(Fourcc::P010), Some(2));
+        assert_eq!(get_plane_count(Fourcc::Yuv420), Some(3));
+        assert_eq!(get_plane_count(Fourcc::Yuyv), Some(1));
+    }
 }
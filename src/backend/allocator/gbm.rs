@@ -15,15 +15,24 @@ use crate::utils::{Buffer as BufferCoords, Size};
 use drm::buffer::PlanarBuffer;
 use gbm::BufferObject;
 pub use gbm::{BufferObjectFlags as GbmBufferFlags, Device as GbmDevice};
-use std::os::unix::io::{AsFd, BorrowedFd};
+use std::{
+    any::Any,
+    os::unix::io::{AsFd, BorrowedFd},
+    sync::Arc,
+};
 use tracing::instrument;
 
 /// A GBM buffer object
+///
+/// Buffers created by a [`GbmAllocator`] keep the [`GbmDevice`] and its file descriptor alive,
+/// so they can safely outlive the allocator.
 #[derive(Debug)]
 pub struct GbmBuffer {
+    // declared first, so the buffer object is destroyed before the device
     bo: BufferObject<()>,
     size: Size<i32, BufferCoords>,
     format: Format,
+    _device: Option<Arc<dyn Any + Send + Sync>>,
 }
 
 #[cfg(feature = "backend_drm")]
@@ -93,6 +102,9 @@ impl GbmBuffer {
     ///
     /// Gbm might otherwise give us the underlying or a non-sensical modifier,
     /// which can fail in various other apis.
+    ///
+    /// The file descriptor of the device the object was created with has to stay open,
+    /// until the buffer is dropped.
     pub fn from_bo(bo: BufferObject<()>, implicit: bool) -> Self {
         let size = (bo.width() as i32, bo.height() as i32).into();
         let format = Format {
@@ -103,7 +115,17 @@ impl GbmBuffer {
                 bo.modifier()
             },
         };
-        Self { bo, size, format }
+        Self {
+            bo,
+            size,
+            format,
+            _device: None,
+        }
+    }
+
+    fn with_device(mut self, device: Arc<dyn Any + Send + Sync>) -> Self {
+        self._device = Some(device);
+        self
     }
 }
 
@@ -124,23 +146,29 @@ impl std::ops::DerefMut for GbmBuffer {
 }
 
 /// Light wrapper around an [`GbmDevice`] to implement the [`Allocator`]-trait
-#[derive(Clone, Debug)]
+///
+/// The device is shared with all allocated buffers, it is only closed once the allocator
+/// and all of its buffers are dropped.
+#[derive(Debug)]
 pub struct GbmAllocator<A: AsFd + 'static> {
-    device: GbmDevice<A>,
+    device: Arc<GbmDevice<A>>,
     default_flags: GbmBufferFlags,
 }
 
-impl<A: AsFd + 'static> AsRef<GbmDevice<A>> for GbmAllocator<A> {
+impl<A: AsFd + 'static> Clone for GbmAllocator<A> {
     #[inline]
-    fn as_ref(&self) -> &GbmDevice<A> {
-        &self.device
+    fn clone(&self) -> Self {
+        GbmAllocator {
+            device: self.device.clone(),
+            default_flags: self.default_flags,
+        }
     }
 }
 
-impl<A: AsFd + 'static> AsMut<GbmDevice<A>> for GbmAllocator<A> {
+impl<A: AsFd + 'static> AsRef<GbmDevice<A>> for GbmAllocator<A> {
     #[inline]
-    fn as_mut(&mut self) -> &mut GbmDevice<A> {
-        &mut self.device
+    fn as_ref(&self) -> &GbmDevice<A> {
+        &self.device
     }
 }
 
@@ -156,11 +184,13 @@ impl<A: AsFd + 'static> GbmAllocator<A> {
     /// to be used when [`Allocator::create_buffer`] is invoked.
     pub fn new(device: GbmDevice<A>, default_flags: GbmBufferFlags) -> GbmAllocator<A> {
         GbmAllocator {
-            device,
+            device: Arc::new(device),
             default_flags,
         }
     }
+}
 
+impl<A: AsFd + Send + Sync + 'static> GbmAllocator<A> {
     /// Alternative to [`Allocator::create_buffer`], if you need a one-off buffer with
     /// a different set of usage flags.
     #[instrument(level = "trace", skip(self), fields(self.device = ?self.device, err))]
@@ -188,7 +218,7 @@ impl<A: AsFd + 'static> GbmAllocator<A> {
             return self
                 .device
                 .create_buffer_object(width, height, fourcc, flags)
-                .map(|bo| GbmBuffer::from_bo(bo, true));
+                .map(|bo| GbmBuffer::from_bo(bo, true).with_device(self.device.clone()));
         } else {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
@@ -196,7 +226,7 @@ impl<A: AsFd + 'static> GbmAllocator<A> {
             ));
         };
 
-        match result {
+        let result = match result {
             Ok(bo) => Ok(bo),
             Err(err) => {
                 if modifiers.contains(&Modifier::Invalid) || modifiers.contains(&Modifier::Linear) {
@@ -207,11 +237,12 @@ impl<A: AsFd + 'static> GbmAllocator<A> {
                     Err(err)
                 }
             }
-        }
+        };
+        result.map(|buffer| buffer.with_device(self.device.clone()))
     }
 }
 
-impl<A: AsFd + 'static> Allocator for GbmAllocator<A> {
+impl<A: AsFd + Send + Sync + 'static> Allocator for GbmAllocator<A> {
     type Buffer = GbmBuffer;
     type Error = std::io::Error;
 
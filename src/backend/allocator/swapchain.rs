@@ -241,6 +241,18 @@ where
         }
     }
 
+    /// Approximate memory in bytes used by the buffers currently allocated by the swapchain
+    ///
+    /// Buffers of formats with an unknown number of bits per pixel are skipped. See
+    /// [`estimated_memory_usage`](crate::backend::renderer::utils::estimated_memory_usage) for details.
+    pub fn memory_usage(&self) -> usize {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.buffer.as_ref())
+            .filter_map(crate::backend::renderer::utils::buffer_memory_usage)
+            .sum()
+    }
+
     /// Reset the age for each buffer.
     ///
     /// Resetting the buffer age will discard all damage information and force a
@@ -0,0 +1,115 @@
+use crate::backend::allocator::Fourcc as DrmFourcc;
+
+const FORMATS_10BIT: &[DrmFourcc] = &[
+    DrmFourcc::Abgr2101010,
+    DrmFourcc::Argb2101010,
+    DrmFourcc::Xbgr2101010,
+    DrmFourcc::Xrgb2101010,
+];
+const FORMATS_8BIT: &[DrmFourcc] = &[
+    DrmFourcc::Abgr8888,
+    DrmFourcc::Argb8888,
+    DrmFourcc::Xbgr8888,
+    DrmFourcc::Xrgb8888,
+];
+
+// The opaque variants are matched by the compositor implicitly
+const COLOR_FORMATS_8BIT: &[DrmFourcc] = &[DrmFourcc::Abgr8888, DrmFourcc::Argb8888];
+const COLOR_FORMATS_10BIT: &[DrmFourcc] = &[
+    DrmFourcc::Abgr2101010,
+    DrmFourcc::Argb2101010,
+    DrmFourcc::Abgr8888,
+    DrmFourcc::Argb8888,
+];
+
+/// Color depth of an output
+///
+/// Bundles the format choices required to drive an output at a certain depth, the color formats
+/// for the [`DrmCompositor`](super::DrmCompositor), the format for offscreen rendering and the formats
+/// worth advertising to clients for direct scan-out.
+///
+/// Not every plane or driver supports 10-bit formats, so [`ColorDepth::color_formats`] for
+/// [`ColorDepth::Bits10`] falls back to 8-bit formats. Use [`DrmCompositor::color_depth`](super::DrmCompositor::color_depth)
+/// to query the depth, which was actually selected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// 8 bits per color channel
+    #[default]
+    Bits8,
+    /// 10 bits per color channel
+    Bits10,
+}
+
+impl ColorDepth {
+    /// Returns the number of bits per color channel
+    pub fn bits(&self) -> u32 {
+        match self {
+            ColorDepth::Bits8 => 8,
+            ColorDepth::Bits10 => 10,
+        }
+    }
+
+    /// Returns the depth of a color format
+    ///
+    /// Returns `None` for formats, which are not used for outputs.
+    pub fn from_format(code: DrmFourcc) -> Option<Self> {
+        if FORMATS_10BIT.contains(&code) {
+            Some(ColorDepth::Bits10)
+        } else if FORMATS_8BIT.contains(&code) {
+            Some(ColorDepth::Bits8)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the color formats to test in order of preference
+    ///
+    /// Formats of lower depths are included as a fallback.
+    pub fn color_formats(&self) -> &'static [DrmFourcc] {
+        match self {
+            ColorDepth::Bits8 => COLOR_FORMATS_8BIT,
+            ColorDepth::Bits10 => COLOR_FORMATS_10BIT,
+        }
+    }
+
+    /// Returns the format to use for offscreen rendering at this depth
+    pub fn offscreen_format(&self) -> DrmFourcc {
+        match self {
+            ColorDepth::Bits8 => DrmFourcc::Abgr8888,
+            ColorDepth::Bits10 => DrmFourcc::Abgr2101010,
+        }
+    }
+
+    /// Returns if a format doesn't exceed this depth
+    ///
+    /// Formats not used for outputs are always considered supported. This can be used to filter
+    /// the scan-out formats advertised to clients, as buffers deeper than the output provide no benefit.
+    pub fn supports(&self, code: DrmFourcc) -> bool {
+        ColorDepth::from_format(code).map_or(true, |depth| depth <= *self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColorDepth;
+    use crate::backend::allocator::Fourcc;
+
+    #[test]
+    fn fallback_order() {
+        let formats = ColorDepth::Bits10.color_formats();
+        let depths = formats
+            .iter()
+            .map(|code| ColorDepth::from_format(*code).unwrap())
+            .collect::<Vec<_>>();
+        assert!(depths.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(depths.last(), Some(&ColorDepth::Bits8));
+        assert!(ColorDepth::Bits8
+            .color_formats()
+            .iter()
+            .all(|code| ColorDepth::from_format(*code) == Some(ColorDepth::Bits8)));
+
+        assert!(ColorDepth::Bits8.supports(Fourcc::Nv12));
+        assert!(!ColorDepth::Bits8.supports(Fourcc::Argb2101010));
+        assert!(ColorDepth::Bits10.supports(Fourcc::Argb8888));
+    }
+}
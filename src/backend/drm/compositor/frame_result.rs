@@ -19,7 +19,7 @@ use crate::{
     utils::{Buffer as BufferCoords, Physical, Point, Rectangle, Scale, Size, Transform},
 };
 
-use super::{DrmScanoutBuffer, ScanoutBuffer};
+use super::{DrmScanoutBuffer, FrameScanoutInfo, ScanoutBuffer};
 
 /// Result for [`DrmCompositor::render_frame`]
 ///
@@ -39,6 +39,9 @@ use super::{DrmScanoutBuffer, ScanoutBuffer};
 ///   large enough to hold onto at least one `RenderFrameResult`.
 pub struct RenderFrameResult<'a, B: Buffer, F: Framebuffer, E> {
     /// If this frame contains any changes and should be submitted
+    ///
+    /// Note: Frames only updating the cursor plane may be empty,
+    /// see [`RenderFrameResult::cursor_update`].
     pub is_empty: bool,
     /// The render element states of this frame
     pub states: RenderElementStates,
@@ -50,6 +53,18 @@ pub struct RenderFrameResult<'a, B: Buffer, F: Framebuffer, E> {
     ///
     /// If set always above all other elements
     pub cursor_element: Option<&'a E>,
+    /// Changes of the cursor plane compared to the previously rendered frame, if any
+    ///
+    /// Reported regardless of [`FrameFlags::SKIP_CURSOR_ONLY_UPDATES`](super::FrameFlags::SKIP_CURSOR_ONLY_UPDATES).
+    pub cursor_update: Option<CursorPlaneUpdate>,
+    /// Plane usage of this frame
+    pub scanout_info: FrameScanoutInfo,
+    /// Elements whose buffer fences did not signal in time
+    ///
+    /// These elements are either skipped or continue to show their previous buffer.
+    /// See [`DrmCompositor::set_fence_timeout`](super::DrmCompositor::set_fence_timeout).
+    /// The compositor may use this to warn about or disconnect misbehaving clients.
+    pub fence_timeouts: Vec<Id>,
 
     pub(super) primary_plane_element_id: Id,
     pub(super) supports_fencing: bool,
@@ -66,6 +81,60 @@ impl<B: Buffer, F: Framebuffer, E> RenderFrameResult<'_, B, F, E> {
     }
 }
 
+/// Change of the cursor plane between two rendered frames
+///
+/// Moving the cursor on the cursor plane does not damage the primary plane and, depending on the
+/// [`FrameFlags`](super::FrameFlags) used, might not even result in a frame being submitted.
+/// Capture implementations, like screencopy or a screencast portal, can use this to damage the
+/// affected region of their captured frames or to forward the cursor position as metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorPlaneUpdate {
+    /// Geometry of the element on the cursor plane in the previous frame
+    ///
+    /// `None` if the cursor plane was not used.
+    pub previous: Option<Rectangle<i32, Physical>>,
+    /// Geometry of the element on the cursor plane in this frame
+    ///
+    /// `None` if the cursor plane is not used.
+    pub current: Option<Rectangle<i32, Physical>>,
+    /// If the element on the cursor plane or its contents changed
+    pub image_changed: bool,
+}
+
+impl CursorPlaneUpdate {
+    pub(super) fn new(
+        previous: Option<&(Id, CommitCounter, Rectangle<i32, Physical>)>,
+        current: Option<&(Id, CommitCounter, Rectangle<i32, Physical>)>,
+    ) -> Option<Self> {
+        let image_changed = match (previous, current) {
+            (Some((previous_id, previous_commit, _)), Some((id, commit, _))) => {
+                previous_id != id || previous_commit != commit
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        let update = CursorPlaneUpdate {
+            previous: previous.map(|(_, _, geometry)| *geometry),
+            current: current.map(|(_, _, geometry)| *geometry),
+            image_changed,
+        };
+
+        (update.image_changed || update.moved()).then_some(update)
+    }
+
+    /// Returns if the cursor moved
+    pub fn moved(&self) -> bool {
+        self.previous != self.current
+    }
+
+    /// Returns the damage caused by this update in output coordinates
+    pub fn damage(&self) -> impl Iterator<Item = Rectangle<i32, Physical>> {
+        let previous = self.previous.filter(|previous| Some(*previous) != self.current);
+        let current = self.current.filter(|_| self.image_changed || self.moved());
+        previous.into_iter().chain(current)
+    }
+}
+
 struct SwapchainElement<'a, 'b, B: Buffer> {
     id: Id,
     slot: &'a Slot<B>,
@@ -417,6 +486,8 @@ impl<B: Buffer + std::fmt::Debug, F: Framebuffer + std::fmt::Debug, E: std::fmt:
             .field("primary_element", &self.primary_element)
             .field("overlay_elements", &self.overlay_elements)
             .field("cursor_element", &self.cursor_element)
+            .field("cursor_update", &self.cursor_update)
+            .field("scanout_info", &self.scanout_info)
             .finish()
     }
 }
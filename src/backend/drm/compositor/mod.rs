@@ -19,7 +19,10 @@
 //! ### General
 //!
 //! First the element has to provide a [`UnderlyingStorage`] which can be exported as a drm framebuffer.
-//! Currently this is limited to wayland buffers, but may be extended in the future.
+//! Currently this is limited to wayland buffers and dmabuf backed buffers provided through
+//! [`UnderlyingStorage::External`], but may be extended in the future.
+//! The latter allows direct scan-out without the `wayland_frontend` feature, e.g. for
+//! compositors only rendering their own content.
 //! This module provides a default exporter based on [`gbm`] which should fit most use-cases.
 //!
 //! If a certain combination of elements works can only be determined by asking the driver by submitting
@@ -35,6 +38,23 @@
 //! An underlay plane is only used if it does not overlap with an already assigned plane lower in the stack
 //! and the element is fully opaque.
 //!
+//! ### Element kind
+//!
+//! The [`Kind`] of an element gives additional hints for the plane assignment.
+//! Only elements marked as [`Kind::Cursor`] are considered for the cursor plane.
+//! Elements marked as [`Kind::ForceRender`] are never directly scanned out.
+//!
+//! Overlay planes are scarce, so elements marked as [`Kind::Video`] are prioritized over elements
+//! marked as [`Kind::Overlayable`], which are prioritized over all other elements.
+//! An element will not be assigned to an overlay plane if the remaining free planes are
+//! needed for higher priority elements further down the stack.
+//!
+//! Elements marked as [`Kind::ScanoutCandidate`] try planes lower in the stack first,
+//! so that static content like a wallpaper ends up on an underlay plane while
+//! windows above it can still be assigned to the overlay planes.
+//! Layer surfaces on the background and bottom layer are marked as scan-out
+//! candidates by default, see [`LayerSurface::render_kind`](crate::desktop::LayerSurface::render_kind).
+//!
 //! ### Primary plane
 //!
 //! For an element to be considered to be directly scanned out on the primary plane it has to be the last remaining
@@ -120,12 +140,14 @@
 //! }
 //! ```
 use std::{
+    cell::RefCell,
     collections::HashMap,
     fmt::Debug,
     io::ErrorKind,
     os::unix::io::{AsFd, OwnedFd},
     str::FromStr,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use drm::{
@@ -136,24 +158,31 @@ use drm_fourcc::{DrmFormat, DrmFourcc, DrmModifier};
 use indexmap::{IndexMap, IndexSet};
 use smallvec::SmallVec;
 use tracing::{debug, error, info, info_span, instrument, trace, warn};
+#[cfg(feature = "wayland_frontend")]
 use wayland_server::{protocol::wl_buffer::WlBuffer, Resource};
 
+#[cfg(all(feature = "renderer_pixman", feature = "wayland_frontend"))]
+use crate::backend::renderer::ImportAll;
 #[cfg(feature = "renderer_pixman")]
 use crate::backend::renderer::{
     pixman::{PixmanError, PixmanRenderBuffer, PixmanRenderer, PixmanTexture},
-    Frame as _, ImportAll, Unbind,
+    Frame as _, ImportDma as _, Unbind,
+};
+#[cfg(feature = "wayland_frontend")]
+use crate::{
+    backend::renderer::{buffer_y_inverted, sync::Fence as _},
+    wayland::{shm, single_pixel_buffer},
 };
 use crate::{
     backend::{
         allocator::{
-            dmabuf::{AsDmabuf, Dmabuf},
+            dmabuf::{AsDmabuf, Dmabuf, WeakDmabuf},
             format::{get_opaque, has_alpha},
             gbm::{GbmAllocator, GbmBuffer, GbmBufferFlags, GbmDevice},
             Allocator, Buffer, Slot, Swapchain,
         },
         drm::{plane_has_property, DrmError, PlaneDamageClips},
         renderer::{
-            buffer_y_inverted,
             damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
             element::{
                 Element, Id, Kind, RenderElement, RenderElementPresentationState, RenderElementState,
@@ -167,21 +196,25 @@ use crate::{
     },
     output::OutputModeSource,
     utils::{Buffer as BufferCoords, DevPath, Physical, Point, Rectangle, Scale, Size, Transform},
-    wayland::{shm, single_pixel_buffer},
 };
 
 use super::{
     error::AccessError,
     exporter::{ExportBuffer, ExportFramebuffer},
-    surface::VrrSupport,
-    DrmSurface, Framebuffer, PlaneClaim, PlaneInfo, Planes,
+    surface::{LinkStatus, VrrSupport},
+    DrmEventMetadata, DrmEventTime, DrmSurface, Framebuffer, PlaneClaim, PlaneInfo, Planes,
 };
 
+mod color_depth;
 mod elements;
 mod frame_result;
+mod report;
 
+pub use color_depth::ColorDepth;
 use elements::*;
 pub use frame_result::*;
+use report::{drm_error_errno, ScanoutStatistics};
+pub use report::{FrameScanoutInfo, PlaneScanoutStats, ScanoutFailure, ScanoutReport};
 
 impl RenderElementState {
     pub(crate) fn zero_copy(visible_area: usize) -> Self {
@@ -202,7 +235,9 @@ impl RenderElementState {
 #[allow(dead_code)] // This structs purpose is to keep buffer objects alive, most variants won't be read
 #[derive(Debug)]
 enum ScanoutBuffer<B: Buffer> {
+    #[cfg(feature = "wayland_frontend")]
     Wayland(crate::backend::renderer::utils::Buffer),
+    Dmabuf(Dmabuf),
     Swapchain(Arc<Slot<B>>),
     Cursor(Arc<GbmBuffer>),
 }
@@ -210,7 +245,9 @@ enum ScanoutBuffer<B: Buffer> {
 impl<B: Buffer> Clone for ScanoutBuffer<B> {
     fn clone(&self) -> Self {
         match self {
+            #[cfg(feature = "wayland_frontend")]
             Self::Wayland(arg0) => Self::Wayland(arg0.clone()),
+            Self::Dmabuf(arg0) => Self::Dmabuf(arg0.clone()),
             Self::Swapchain(arg0) => Self::Swapchain(arg0.clone()),
             Self::Cursor(arg0) => Self::Cursor(arg0.clone()),
         }
@@ -222,13 +259,20 @@ impl<B: Buffer> ScanoutBuffer<B> {
         &self,
         signaled_fence: Option<&Arc<OwnedFd>>,
     ) -> Option<(SyncPoint, Option<Arc<OwnedFd>>)> {
+        #[cfg(feature = "wayland_frontend")]
         if let Self::Wayland(buffer) = self {
-            // Assume `DrmSyncobjBlocker` is used, so acquire point has already
-            // been signaled. Instead of converting with `SyncPoint::from`.
-            if buffer.acquire_point().is_some() {
-                return Some((SyncPoint::signaled(), signaled_fence.cloned()));
+            if let Some(acquire_point) = buffer.acquire_point() {
+                // The acquire point is usually already signaled by the time the buffer is scanned out,
+                // so avoid exporting a sync file for it. Otherwise a submission blocker was used
+                // without a fence timeout and the fence is passed to the kernel.
+                if acquire_point.is_signaled() {
+                    return Some((SyncPoint::signaled(), signaled_fence.cloned()));
+                }
+                return Some((SyncPoint::from(acquire_point.clone()), None));
             }
         }
+        #[cfg(not(feature = "wayland_frontend"))]
+        let _ = signaled_fence;
         None
     }
 }
@@ -237,7 +281,9 @@ impl<B: Buffer> ScanoutBuffer<B> {
     #[inline]
     fn from_underlying_storage(storage: UnderlyingStorage<'_>) -> Option<Self> {
         match storage {
+            #[cfg(feature = "wayland_frontend")]
             UnderlyingStorage::Wayland(buffer) => Some(Self::Wayland(buffer.clone())),
+            UnderlyingStorage::External(storage) => storage.dmabuf().cloned().map(Self::Dmabuf),
             UnderlyingStorage::Memory { .. } => None,
         }
     }
@@ -329,14 +375,20 @@ impl<B: Buffer, F: Framebuffer> Framebuffer for DrmScanoutBuffer<B, F> {
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 enum ElementFramebufferCacheBuffer {
+    #[cfg(feature = "wayland_frontend")]
     Wayland(wayland_server::Weak<WlBuffer>),
+    Dmabuf(WeakDmabuf),
 }
 
 impl ElementFramebufferCacheBuffer {
     #[inline]
     fn from_underlying_storage(storage: &UnderlyingStorage<'_>) -> Option<Self> {
         match storage {
+            #[cfg(feature = "wayland_frontend")]
             UnderlyingStorage::Wayland(buffer) => Some(Self::Wayland(buffer.downgrade())),
+            UnderlyingStorage::External(storage) => {
+                storage.dmabuf().map(|dmabuf| Self::Dmabuf(dmabuf.weak()))
+            }
             UnderlyingStorage::Memory { .. } => None,
         }
     }
@@ -363,7 +415,9 @@ impl ElementFramebufferCacheKey {
     #[inline]
     fn is_alive(&self) -> bool {
         match self.buffer {
+            #[cfg(feature = "wayland_frontend")]
             ElementFramebufferCacheBuffer::Wayland(ref buffer) => buffer.is_alive(),
+            ElementFramebufferCacheBuffer::Dmabuf(ref buffer) => !buffer.is_gone(),
         }
     }
 }
@@ -406,6 +460,11 @@ impl<B> ElementFramebufferCache<B>
 where
     B: Framebuffer,
 {
+    #[inline]
+    fn clear(&mut self) {
+        self.fb_cache.clear();
+    }
+
     #[inline]
     fn get(
         &self,
@@ -456,6 +515,18 @@ struct PlaneProperties {
 }
 
 impl PlaneProperties {
+    fn scanout_failure(&self, element: &Id, err: &DrmError) -> ScanoutFailure {
+        ScanoutFailure {
+            element: element.clone(),
+            format: self.format,
+            src: self.src,
+            dst: self.dst,
+            transform: self.transform,
+            alpha: self.alpha,
+            errno: drm_error_errno(err),
+        }
+    }
+
     #[inline]
     fn is_compatible(&self, other: &PlaneProperties) -> bool {
         self.src == other.src
@@ -561,6 +632,8 @@ impl<B: Buffer, F: Framebuffer> Clone for PlaneState<B, F> {
 #[derive(Debug)]
 struct FrameState<B: Buffer, F: Framebuffer> {
     planes: SmallVec<[(plane::Handle, PlaneState<B, F>); 10]>,
+    // color programmed into the crtc background, if supported
+    background_color: Option<Color32F>,
 }
 
 impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
@@ -618,6 +691,35 @@ impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
         self.plane_state(handle)
             .and_then(|state| state.config.as_ref().map(|config| &config.buffer))
     }
+
+    // Keep the overlay or cursor plane an element was scanned out on in the previous frame unchanged,
+    // so the plane continues to show the previous buffer of the element.
+    fn keep_element_plane(
+        &mut self,
+        previous_frame: &Self,
+        primary_plane: plane::Handle,
+        element_id: &Id,
+        allowed: impl Fn(plane::Handle) -> bool,
+    ) -> Option<plane::Handle> {
+        let (handle, previous_state) = previous_frame.planes.iter().find(|(handle, state)| {
+            *handle != primary_plane
+                && allowed(*handle)
+                && state.config.is_some()
+                && state
+                    .element_state
+                    .as_ref()
+                    .is_some_and(|element_state| element_state.id == *element_id)
+        })?;
+        if self.is_assigned(*handle) {
+            return None;
+        }
+
+        let mut state = previous_state.clone();
+        state.skip = true;
+        state.needs_test = false;
+        self.set_state(*handle, state);
+        Some(*handle)
+    }
 }
 
 impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
@@ -637,7 +739,10 @@ impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
                 .map(|info| (info.handle, PlaneState::default())),
         );
 
-        FrameState { planes: tmp }
+        FrameState {
+            planes: tmp,
+            background_color: None,
+        }
     }
 }
 
@@ -668,6 +773,7 @@ impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
         let backup = current_config.clone();
         *current_config = state;
 
+        self.apply_background_color(surface);
         let res = surface.test_state(self.build_planes(surface, supports_fencing, true), allow_modeset);
 
         if res.is_err() {
@@ -707,6 +813,7 @@ impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
             return Ok(());
         }
 
+        self.apply_background_color(surface);
         let res = surface.test_state(
             self.build_planes(surface, supports_fencing, allow_partial_update),
             allow_modeset,
@@ -730,6 +837,7 @@ impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
         event: bool,
     ) -> Result<(), crate::backend::drm::error::Error> {
         debug_assert!(!self.planes.iter().any(|(_, state)| state.needs_test));
+        self.apply_background_color(surface);
         surface.commit(
             self.build_planes(surface, supports_fencing, allow_partial_update),
             event,
@@ -745,12 +853,38 @@ impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
         event: bool,
     ) -> Result<(), crate::backend::drm::error::Error> {
         debug_assert!(!self.planes.iter().any(|(_, state)| state.needs_test));
+        self.apply_background_color(surface);
         surface.page_flip(
             self.build_planes(surface, supports_fencing, allow_partial_update),
             event,
         )
     }
 
+    // the background color is part of the crtc state, so it has to match the frame being tested or committed
+    fn apply_background_color(&self, surface: &DrmSurface) {
+        if let Some(color) = self.background_color {
+            if let Err(err) = surface.set_background_color(Some(color)) {
+                debug!(?err, "failed to set crtc background color");
+            }
+        }
+    }
+
+    #[profiling::function]
+    fn page_flip_async(
+        &mut self,
+        surface: &DrmSurface,
+        supports_fencing: bool,
+        allow_partial_update: bool,
+        event: bool,
+    ) -> Result<(), crate::backend::drm::error::Error> {
+        debug_assert!(!self.planes.iter().any(|(_, state)| state.needs_test));
+        self.apply_background_color(surface);
+        surface.page_flip_async(
+            self.build_planes(surface, supports_fencing, allow_partial_update),
+            event,
+        )
+    }
+
     #[profiling::function]
     fn build_planes<'a>(
         &'a mut self,
@@ -937,6 +1071,7 @@ impl From<&PlaneInfo> for PlaneAssignment {
 struct PendingFrame<A: Allocator, F: ExportFramebuffer<<A as Allocator>::Buffer>, U> {
     frame: CompositorFrameState<A, F>,
     user_data: U,
+    tearing: bool,
 }
 
 impl<A, F, U> std::fmt::Debug for PendingFrame<A, F, U>
@@ -951,10 +1086,39 @@ where
         f.debug_struct("PendingFrame")
             .field("frame", &self.frame)
             .field("user_data", &self.user_data)
+            .field("tearing", &self.tearing)
             .finish()
     }
 }
 
+/// Information about a frame that has been presented on screen
+///
+/// See [`DrmCompositor::frame_submitted_with_metadata`].
+#[derive(Debug, Clone, Copy)]
+pub struct SubmittedFrame<U> {
+    /// User data passed to [`DrmCompositor::queue_frame`]
+    pub user_data: U,
+    /// Time of the vblank the frame has been presented at, if known
+    pub time: Option<DrmEventTime>,
+    /// Vblank sequence number the frame has been presented at, if known
+    pub sequence: Option<u32>,
+    /// Whether the frame was presented using an async page-flip and might
+    /// have caused tearing
+    ///
+    /// See [`DrmCompositor::set_allow_tearing`].
+    pub tearing: bool,
+}
+
+impl<U> SubmittedFrame<U> {
+    /// Returns the presentation time if the device reports monotonic timestamps
+    pub fn monotonic_time(&self) -> Option<Duration> {
+        match self.time {
+            Some(DrmEventTime::Monotonic(tp)) => Some(tp),
+            _ => None,
+        }
+    }
+}
+
 struct QueuedFrame<A: Allocator, F: ExportFramebuffer<<A as Allocator>::Buffer>, U> {
     prepared_frame: PreparedFrame<A, F>,
     user_data: U,
@@ -1034,6 +1198,23 @@ bitflags::bitflags! {
     }
 }
 
+/// Number of times the link is retrained with the same mode, before a lower mode is selected
+const LINK_RETRAIN_ATTEMPTS: usize = 1;
+
+/// Outcome of [`DrmCompositor::recover_link`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkRecovery {
+    /// The links of all connectors are good
+    Good,
+    /// Link training failed, the link will be retrained with the current mode on the next frame
+    Retrain,
+    /// Link training failed repeatedly, the link will be retrained with the contained lower mode on the next frame
+    ModeChanged(Mode),
+    /// Link training failed and no lower mode is available, the link will be retrained with the current mode
+    /// on the next frame
+    NoFallback,
+}
+
 /// Composite an output using a combination of planes and rendering
 ///
 /// see the [`module docs`](crate::backend::drm::compositor) for more information
@@ -1054,6 +1235,7 @@ where
     primary_plane_element_id: Id,
     primary_plane_damage_bag: DamageBag<i32, BufferCoords>,
     supports_fencing: bool,
+    allow_tearing: bool,
     reset_pending: bool,
     signaled_fence: Option<Arc<OwnedFd>>,
 
@@ -1074,6 +1256,12 @@ where
     opaque_regions: Vec<Rectangle<i32, Physical>>,
     element_opaque_regions_workhouse: Vec<Rectangle<i32, Physical>>,
 
+    fence_timeout: Option<Duration>,
+    pending_fences: HashMap<Id, (CommitCounter, Instant)>,
+    scanout_statistics: RefCell<ScanoutStatistics>,
+    last_cursor_plane: Option<(Id, CommitCounter, Rectangle<i32, Physical>)>,
+    link_failures: usize,
+
     debug_flags: DebugFlags,
     span: tracing::Span,
 }
@@ -1087,7 +1275,7 @@ where
     F: ExportFramebuffer<A::Buffer>,
     <F as ExportFramebuffer<A::Buffer>>::Framebuffer: std::fmt::Debug + 'static,
     <F as ExportFramebuffer<A::Buffer>>::Error: std::error::Error + Send + Sync,
-    G: AsFd + Clone,
+    G: AsFd + Clone + Send + Sync,
 {
     /// Initialize a new [`DrmCompositor`].
     ///
@@ -1257,6 +1445,12 @@ where
                         element_states: IndexMap::new(),
                         previous_element_states: IndexMap::new(),
                         opaque_regions: Vec::new(),
+                        fence_timeout: None,
+                        pending_fences: HashMap::new(),
+                        scanout_statistics: RefCell::new(ScanoutStatistics::default()),
+                        last_cursor_plane: None,
+                        link_failures: 0,
+                        allow_tearing: false,
                         element_opaque_regions_workhouse: Vec::new(),
                         supports_fencing,
                         debug_flags: DebugFlags::empty(),
@@ -1438,6 +1632,12 @@ where
             element_states: IndexMap::new(),
             previous_element_states: IndexMap::new(),
             opaque_regions: Vec::new(),
+            fence_timeout: None,
+            pending_fences: HashMap::new(),
+            scanout_statistics: RefCell::new(ScanoutStatistics::default()),
+            last_cursor_plane: None,
+            link_failures: 0,
+            allow_tearing: false,
             element_opaque_regions_workhouse: Vec::new(),
             supports_fencing,
             debug_flags: DebugFlags::empty(),
@@ -1837,9 +2037,17 @@ where
         // This holds all elements that are visible on the output
         // A element is considered visible if it intersects with the output geometry
         // AND is not completely hidden behind opaque regions
-        let mut output_elements: Vec<(&'a E, Rectangle<i32, Physical>, usize, bool)> =
+        let mut output_elements: Vec<(&'a E, Rectangle<i32, Physical>, usize, bool, bool)> =
             Vec::with_capacity(elements.len());
 
+        // This holds all elements whose buffer fences did not signal in time
+        let mut fence_timeouts: Vec<Id> = Vec::new();
+        // This holds the elements waiting for their buffer fences on the plane they were scanned out on
+        let mut kept_plane_elements: Vec<(plane::Handle, &'a E)> = Vec::new();
+        #[allow(clippy::mutable_key_type)]
+        let previous_pending_fences = std::mem::take(&mut self.pending_fences);
+        let now = Instant::now();
+
         let mut element_opaque_regions_workhouse = std::mem::take(&mut self.element_opaque_regions_workhouse);
         for (index, element) in elements.iter().enumerate() {
             let element_id = element.id();
@@ -1853,6 +2061,75 @@ where
                 None => continue,
             };
 
+            // Then test if the buffer fences of the element are signaled, an element
+            // waiting for too long is skipped to not stall the whole output
+            let mut element_fence_pending = false;
+            if let Some(fence_timeout) = self.fence_timeout {
+                let fences_signaled = element
+                    .underlying_storage(renderer)
+                    .map(|storage| underlying_storage_fences_signaled(&storage))
+                    .unwrap_or(true);
+
+                if !fences_signaled {
+                    let commit = element.current_commit();
+                    let pending_since = match previous_pending_fences.get(element_id) {
+                        Some((pending_commit, since)) if *pending_commit == commit => *since,
+                        _ => now,
+                    };
+                    self.pending_fences
+                        .insert(element_id.clone(), (commit, pending_since));
+
+                    let timed_out = now.duration_since(pending_since) >= fence_timeout;
+                    if timed_out {
+                        trace!(
+                            "buffer fences of element {:?} did not signal within {:?}",
+                            element_id,
+                            fence_timeout
+                        );
+                        fence_timeouts.push(element_id.clone());
+                    }
+
+                    // An element previously scanned out on a plane continues to show
+                    // its previous buffer until the fences are signaled
+                    let previous_state = self
+                        .pending_frame
+                        .as_ref()
+                        .map(|pending| &pending.frame)
+                        .unwrap_or(&self.current_frame);
+                    let kept_plane = next_frame_state.keep_element_plane(
+                        previous_state,
+                        self.surface.plane(),
+                        element_id,
+                        |plane| {
+                            if self.planes.cursor.iter().any(|info| info.handle == plane) {
+                                frame_flags.contains(FrameFlags::ALLOW_CURSOR_PLANE_SCANOUT)
+                            } else {
+                                frame_flags.contains(FrameFlags::ALLOW_OVERLAY_PLANE_SCANOUT)
+                            }
+                        },
+                    );
+                    if let Some(plane) = kept_plane {
+                        trace!(
+                            "keeping previous buffer of element {:?} on plane {:?}",
+                            element_id,
+                            plane
+                        );
+                        kept_plane_elements.push((plane, element));
+                    }
+
+                    if kept_plane.is_some() || timed_out {
+                        if !render_element_states.states.contains_key(element_id) {
+                            render_element_states
+                                .states
+                                .insert(element_id.clone(), RenderElementState::skipped());
+                        }
+                        continue;
+                    }
+
+                    element_fence_pending = true;
+                }
+            }
+
             // Then test if the element is completely hidden behind opaque regions
             element_opaque_regions_workhouse.clear();
             element_opaque_regions_workhouse.push(element_output_geometry);
@@ -1907,13 +2184,15 @@ where
             // this element the last element, enabling direct scan-out on the primary plane for it.
             if element_is_opaque && element_output_geometry.contains_rect(output_geometry) {
                 let element_color = element.underlying_storage(renderer).and_then(|storage| {
+                    #[cfg(feature = "wayland_frontend")]
                     if let UnderlyingStorage::Wayland(buffer) = storage {
-                        single_pixel_buffer::get_single_pixel_buffer(buffer)
+                        return single_pixel_buffer::get_single_pixel_buffer(buffer)
                             .ok()
-                            .map(|spb| Color32F::from(spb.rgba32f()))
-                    } else {
-                        None
+                            .map(|spb| Color32F::from(spb.rgba32f()));
                     }
+                    #[cfg(not(feature = "wayland_frontend"))]
+                    let _ = storage;
+                    None::<Color32F>
                 });
 
                 if let Some(color) = element_color {
@@ -1936,6 +2215,7 @@ where
                         element_geometry,
                         element_visible_area,
                         element_is_opaque,
+                        element_fence_pending,
                     ));
                 }
 
@@ -1952,10 +2232,24 @@ where
                 break;
             }
 
-            output_elements.push((element, element_geometry, element_visible_area, element_is_opaque));
+            output_elements.push((
+                element,
+                element_geometry,
+                element_visible_area,
+                element_is_opaque,
+                element_fence_pending,
+            ));
         }
         self.element_opaque_regions_workhouse = element_opaque_regions_workhouse;
 
+        // If the crtc can fill its background with the clear color, the primary plane
+        // does not need to be cleared and an element not covering the whole output can
+        // still be scanned out directly
+        let crtc_background_supported = self.surface.background_color_supported();
+        if crtc_background_supported {
+            next_frame_state.background_color = Some(clear_color);
+        }
+
         // This will hold the element that has been selected for direct scan-out on
         // the primary plane if any
         let mut primary_plane_scanout_element: Option<&'a E> = None;
@@ -1969,15 +2263,38 @@ where
         // This will hold the element assigned on the cursor plane if any
         let mut cursor_plane_element: Option<&'a E> = None;
 
+        for (plane, element) in kept_plane_elements {
+            if self.planes.cursor.iter().any(|info| info.handle == plane) {
+                cursor_plane_element = Some(element);
+            } else {
+                overlay_plane_elements.insert(plane, element);
+            }
+        }
+
+        // This holds the number of not yet assigned elements per overlay priority,
+        // used to reserve overlay planes for higher priority elements further down the stack
+        let mut remaining_overlay_priorities = [0usize; OVERLAY_PRIORITIES];
+        for (element, _, _, _, _) in output_elements.iter() {
+            remaining_overlay_priorities[overlay_priority(element.kind())] += 1;
+        }
+
         let output_elements_len = output_elements.len();
-        for (index, (element, element_geometry, element_visible_area, element_is_opaque)) in
-            output_elements.iter().enumerate()
+        for (
+            index,
+            (element, element_geometry, element_visible_area, element_is_opaque, element_fence_pending),
+        ) in output_elements.iter().enumerate()
         {
             let element_id = element.id();
             let element_geometry = *element_geometry;
             let remaining_elements = output_elements_len - index;
             let element_is_opaque = *element_is_opaque;
 
+            let element_overlay_priority = overlay_priority(element.kind());
+            remaining_overlay_priorities[element_overlay_priority] -= 1;
+            let reserved_overlay_planes: usize = remaining_overlay_priorities[element_overlay_priority + 1..]
+                .iter()
+                .sum();
+
             // Check if we found our last item, we can try to do
             // direct scan-out on the primary plane
             // If we already assigned an element to
@@ -1985,9 +2302,9 @@ where
             // on the primary plane, this will disable direct scan-out
             // on the primary plane.
             let try_assign_primary_plane = if remaining_elements == 1 && primary_plane_elements.is_empty() {
-                let crtc_background_matches_clear_color =
-                    (clear_color.r() == 0f32 && clear_color.g() == 0f32 && clear_color.b() == 0f32)
-                        || clear_color.a() == 0f32;
+                let crtc_background_matches_clear_color = crtc_background_supported
+                    || (clear_color.r() == 0f32 && clear_color.g() == 0f32 && clear_color.b() == 0f32)
+                    || clear_color.a() == 0f32;
                 let element_spans_complete_output = element_geometry.contains_rect(output_geometry);
                 let overlaps_with_underlay = self
                     .planes
@@ -2004,21 +2321,33 @@ where
                 false
             };
 
-            match self.try_assign_element(
-                renderer,
-                *element,
-                index,
-                element_geometry,
-                element_is_opaque,
-                &mut element_states,
-                &primary_plane_elements,
-                output_scale,
-                &mut next_frame_state,
-                output_transform,
-                output_geometry,
-                try_assign_primary_plane,
-                frame_flags,
-            ) {
+            // Directly scanning out an element with pending fences would delay the whole commit
+            let assignment = if *element_fence_pending {
+                trace!(
+                    "skipping direct scan-out for element {:?}, buffer fences not signaled",
+                    element_id
+                );
+                Err(Some(RenderingReason::FenceNotSignaled))
+            } else {
+                self.try_assign_element(
+                    renderer,
+                    *element,
+                    index,
+                    element_geometry,
+                    element_is_opaque,
+                    &mut element_states,
+                    &primary_plane_elements,
+                    output_scale,
+                    &mut next_frame_state,
+                    output_transform,
+                    output_geometry,
+                    try_assign_primary_plane,
+                    reserved_overlay_planes,
+                    frame_flags,
+                )
+            };
+
+            match assignment {
                 Ok(direct_scan_out_plane) => {
                     match direct_scan_out_plane.type_ {
                         drm::control::PlaneType::Overlay => {
@@ -2083,6 +2412,7 @@ where
             .is_err()
         {
             trace!("atomic test failed for frame, resetting frame");
+            self.scanout_statistics.get_mut().frame_test_failed();
 
             let mut removed_overlay_elements: Vec<(usize, &E)> = Vec::with_capacity(
                 next_frame_state
@@ -2111,6 +2441,7 @@ where
                 let Some(element) = element else {
                     continue;
                 };
+                self.scanout_statistics.get_mut().fallback(*plane);
 
                 // Reset the plane config and state
                 state.config = None;
@@ -2159,6 +2490,7 @@ where
             .map(|config| matches!(config.buffer, ScanoutBuffer::Swapchain(_)))
             .unwrap_or(false);
 
+        let mut rendered = false;
         if render {
             trace!(
                 "rendering {} elements on the primary {:?}",
@@ -2230,15 +2562,24 @@ where
                 )
                 .collect::<Vec<_>>();
 
+            // With the clear color programmed into the crtc background an alpha format
+            // only needs to stay transparent where no element is drawn, the background
+            // shows through instead of painting the clear color into the primary plane
+            let primary_clear_color = if crtc_background_supported && has_alpha(self.swapchain.format()) {
+                Color32F::TRANSPARENT
+            } else {
+                clear_color
+            };
             let render_res =
                 self.damage_tracker
-                    .render_output_with(renderer, dmabuf, age, &elements, clear_color);
+                    .render_output_with(renderer, dmabuf, age, &elements, primary_clear_color);
 
             // restore the renderer debug flags
             renderer.set_debug_flags(renderer_debug_flags);
 
             match render_res {
                 Ok(render_output_result) => {
+                    rendered = render_output_result.damage.is_some();
                     if render_output_result.damage.is_none() {
                         // if we receive no damage we can assume no rendering took place
                         // and we should trigger a cleanup of the renderer texture cache
@@ -2376,6 +2717,23 @@ where
             }
         }
 
+        let cursor_plane = cursor_plane_element.map(|element| {
+            (
+                element.id().clone(),
+                element.current_commit(),
+                element.geometry(output_scale),
+            )
+        });
+        let cursor_update = CursorPlaneUpdate::new(self.last_cursor_plane.as_ref(), cursor_plane.as_ref());
+        self.last_cursor_plane = cursor_plane;
+
+        let scanout_info = FrameScanoutInfo {
+            rendered,
+            primary_scanout: !render,
+            overlay_planes: overlay_plane_elements.len(),
+            cursor_plane: cursor_plane_element.is_some(),
+        };
+
         let next_frame = PreparedFrame {
             kind: if allow_partial_update {
                 PreparedFrameKind::Partial
@@ -2389,6 +2747,9 @@ where
             primary_element: primary_plane_element,
             overlay_elements: overlay_plane_elements.into_values().collect(),
             cursor_element: cursor_plane_element,
+            cursor_update,
+            scanout_info,
+            fence_timeouts,
             states: render_element_states,
             primary_plane_element_id: self.primary_plane_element_id.clone(),
             supports_fencing: self.supports_fencing,
@@ -2398,6 +2759,7 @@ where
         // Storing the (empty) frame could keep a reference to wayland buffers which
         // could otherwise be potentially released on `frame_submitted`
         if !next_frame.is_empty() {
+            self.scanout_statistics.get_mut().frame(&scanout_info);
             self.next_frame = Some(next_frame);
         }
 
@@ -2517,6 +2879,27 @@ where
         Ok(())
     }
 
+    /// Re-evaluates the state of the crtc after the session was resumed
+    ///
+    /// Like [`DrmCompositor::reset_state`], but additionally completes a frame, which was
+    /// queued for scan-out right before the session got paused. The vblank event of such a frame
+    /// might never be received, which would otherwise prevent any further frames from being submitted.
+    /// The user data of these frames is dropped.
+    ///
+    /// As long as the drm device fd stays valid, everything else survives pausing the session:
+    /// the swapchain and its buffers, the framebuffers of directly scanned-out elements and
+    /// the textures imported by the renderer. Only the next frame has to be fully composited.
+    pub fn resume(&mut self) -> Result<(), DrmError> {
+        // The pending frame has been committed before the session was paused, so it is the state
+        // the crtc was left in. The queued frame was never committed and is dropped.
+        if let Some(PendingFrame { frame, .. }) = self.pending_frame.take() {
+            debug!("Completing frame pending before the session was paused");
+            self.current_frame = frame;
+        }
+        self.queued_frame = None;
+        self.reset_state()
+    }
+
     #[profiling::function]
     fn submit(&mut self) -> FrameResult<(), A, F> {
         let QueuedFrame {
@@ -2525,23 +2908,40 @@ where
         } = self.queued_frame.take().unwrap();
 
         let allow_partial_update = prepared_frame.kind == PreparedFrameKind::Partial;
+        let mut tearing = false;
         let flip = if self.surface.commit_pending() {
             prepared_frame
                 .frame
                 .commit(&self.surface, self.supports_fencing, allow_partial_update, true)
         } else {
-            prepared_frame
-                .frame
-                .page_flip(&self.surface, self.supports_fencing, allow_partial_update, true)
+            // Drivers are quite restrictive about what an async page-flip may change,
+            // so fall back to a vsynced page-flip if it gets rejected.
+            let async_flip = self.allow_tearing
+                && prepared_frame
+                    .frame
+                    .page_flip_async(&self.surface, self.supports_fencing, allow_partial_update, true)
+                    .inspect_err(|err| trace!(?err, "async page-flip failed, using a vsynced page-flip"))
+                    .is_ok();
+            tearing = async_flip;
+            if async_flip {
+                Ok(())
+            } else {
+                prepared_frame.frame.page_flip(
+                    &self.surface,
+                    self.supports_fencing,
+                    allow_partial_update,
+                    true,
+                )
+            }
         };
 
-        self.handle_flip(prepared_frame, Some(user_data), flip)
+        self.handle_flip(prepared_frame, Some((user_data, tearing)), flip)
     }
 
     fn handle_flip(
         &mut self,
         prepared_frame: PreparedFrame<A, F>,
-        user_data: Option<U>,
+        user_data: Option<(U, bool)>,
         flip: Result<(), crate::backend::drm::error::Error>,
     ) -> FrameResult<(), A, F> {
         match flip {
@@ -2550,9 +2950,10 @@ where
                     self.reset_pending = false;
                 }
 
-                self.pending_frame = user_data.map(|user_data| PendingFrame {
+                self.pending_frame = user_data.map(|(user_data, tearing)| PendingFrame {
                     frame: prepared_frame.frame,
                     user_data,
+                    tearing,
                 });
             }
             Err(crate::backend::drm::error::Error::Access(ref access))
@@ -2593,12 +2994,36 @@ where
     /// Otherwise the underlying swapchain will run out of buffers eventually.
     #[profiling::function]
     pub fn frame_submitted(&mut self) -> FrameResult<Option<U>, A, F> {
-        if let Some(PendingFrame { mut frame, user_data }) = self.pending_frame.take() {
+        self.frame_submitted_with_metadata(None)
+            .map(|submitted| submitted.map(|submitted| submitted.user_data))
+    }
+
+    /// Marks the current frame as submitted and returns the presentation information
+    /// of the frame.
+    ///
+    /// `metadata` is expected to be the [`DrmEventMetadata`] of the vblank event
+    /// received for this surface. See [`DrmCompositor::frame_submitted`] for details.
+    #[profiling::function]
+    pub fn frame_submitted_with_metadata(
+        &mut self,
+        metadata: Option<DrmEventMetadata>,
+    ) -> FrameResult<Option<SubmittedFrame<U>>, A, F> {
+        if let Some(PendingFrame {
+            mut frame,
+            user_data,
+            tearing,
+        }) = self.pending_frame.take()
+        {
             std::mem::swap(&mut frame, &mut self.current_frame);
             if self.queued_frame.is_some() {
                 self.submit()?;
             }
-            Ok(Some(user_data))
+            Ok(Some(SubmittedFrame {
+                user_data,
+                time: metadata.map(|metadata| metadata.time),
+                sequence: metadata.map(|metadata| metadata.sequence),
+                tearing,
+            }))
         } else {
             Ok(None)
         }
@@ -2617,6 +3042,36 @@ where
         self.swapchain.reset_buffer_ages();
     }
 
+    /// Forces the next frame to be fully composited
+    ///
+    /// All elements on the primary plane are drawn again and the plane assignment is
+    /// re-evaluated for every element, while the buffers and cached framebuffers are kept.
+    pub fn damage_all(&mut self) {
+        self.damage_tracker.damage_all();
+        self.reset_pending = true;
+    }
+
+    /// Drops the cached framebuffers of the element with the given [`Id`]
+    ///
+    /// The framebuffers are exported again the next time the element is considered for direct scan-out.
+    /// This can be used if the underlying buffer of an element was modified in a way
+    /// invalidating its framebuffer, e.g. by re-allocating it through external gpu apis.
+    ///
+    /// Returns `false` if no state is known for the element.
+    pub fn invalidate_element(&mut self, id: &Id) -> bool {
+        let mut found = false;
+        for states in [&mut self.element_states, &mut self.previous_element_states] {
+            if let Some(state) = states.get_mut(id) {
+                state.fb_cache.clear();
+                found = true;
+            }
+        }
+        if found {
+            self.reset_pending = true;
+        }
+        found
+    }
+
     /// Returns the underlying [`crtc`] of this surface
     pub fn crtc(&self) -> crtc::Handle {
         self.surface.crtc()
@@ -2694,6 +3149,10 @@ where
     /// Fails if the mode is not compatible with the underlying
     /// [`crtc`] or any of the
     /// pending [`connector`]s.
+    ///
+    /// The swapchain is kept, if the new mode has the same size as the current one. Together with
+    /// drivers applying such mode switches without a full modeset (see [`DrmSurface::use_mode`]) this
+    /// allows switching the refresh rate without visible flicker.
     pub fn use_mode(&mut self, mode: Mode) -> FrameResult<(), A, F> {
         self.surface.use_mode(mode).map_err(FrameError::DrmError)?;
         let (w, h) = mode.size();
@@ -2722,6 +3181,70 @@ where
         self.surface.use_vrr(vrr).map_err(FrameError::DrmError)
     }
 
+    /// Returns if the crtc supports a background color
+    ///
+    /// If supported, the clear color passed to [`DrmCompositor::render_frame`] is programmed as the
+    /// background of the crtc. The clear color then no longer prevents direct scan-out on the primary
+    /// plane of an element not covering the whole output, e.g. letterboxed fullscreen content.
+    /// If the primary plane format has an alpha channel, rendering leaves uncovered areas transparent
+    /// instead of painting the clear color.
+    pub fn crtc_background_supported(&self) -> bool {
+        self.surface.background_color_supported()
+    }
+
+    /// Checks the link status of the connectors and tries to recover failed links
+    ///
+    /// This should be called for every hotplug event of the device, as the kernel reports failed
+    /// link training, e.g. of a DisplayPort connection over a marginal cable, that way. Without recovery
+    /// the output silently stays black.
+    ///
+    /// A failed link is first retrained with the current mode. If it fails again, the next lower mode out of
+    /// `modes` is selected, preferring modes of the same size. Lowering the mode changes the size of the output,
+    /// if no mode of the same size is left, so [`LinkRecovery::ModeChanged`] has to be propagated to the
+    /// output state. In any case but [`LinkRecovery::Good`] a new frame has to be rendered and queued to
+    /// apply the recovery.
+    pub fn recover_link(&mut self, modes: &[Mode]) -> FrameResult<LinkRecovery, A, F> {
+        let mut bad = false;
+        for conn in self.surface.current_connectors() {
+            bad |= self.surface.link_status(conn).map_err(FrameError::DrmError)? == LinkStatus::Bad;
+        }
+        if !bad {
+            self.link_failures = 0;
+            return Ok(LinkRecovery::Good);
+        }
+
+        self.link_failures += 1;
+        if self.link_failures <= LINK_RETRAIN_ATTEMPTS {
+            warn!("Link training failed, retraining link");
+            self.surface.retrain_link();
+            return Ok(LinkRecovery::Retrain);
+        }
+
+        let current = self.surface.pending_mode();
+        let lower = modes
+            .iter()
+            .filter(|mode| mode.clock() < current.clock())
+            .max_by_key(|mode| (mode.size() == current.size(), mode.clock()))
+            .copied();
+        let recovery = match lower {
+            Some(mode) => {
+                warn!(
+                    ?mode,
+                    "Link training failed repeatedly, falling back to lower mode"
+                );
+                self.use_mode(mode)?;
+                self.link_failures = 0;
+                LinkRecovery::ModeChanged(mode)
+            }
+            None => {
+                warn!("Link training failed repeatedly, no lower mode available");
+                LinkRecovery::NoFallback
+            }
+        };
+        self.surface.retrain_link();
+        Ok(recovery)
+    }
+
     /// Set the [`DebugFlags`] to use
     ///
     /// Note: This will reset the primary plane swapchain if
@@ -2738,6 +3261,71 @@ where
         self.debug_flags
     }
 
+    /// Allow frames to be presented using async page-flips
+    ///
+    /// If enabled and supported by the driver, queued frames are flipped without waiting
+    /// for the next vblank, which reduces latency but might cause tearing.
+    /// Frames the driver refuses to flip asynchronously, e.g. because they change more than
+    /// the framebuffer of the primary plane, are presented with a vsynced page-flip instead.
+    /// Whether a frame was presented with tearing is reported by
+    /// [`DrmCompositor::frame_submitted_with_metadata`].
+    ///
+    /// Defaults to `false`.
+    pub fn set_allow_tearing(&mut self, allow: bool) {
+        self.allow_tearing = allow && self.surface.async_page_flip_supported();
+    }
+
+    /// Returns whether frames may be presented using async page-flips,
+    /// see [`DrmCompositor::set_allow_tearing`]
+    pub fn allow_tearing(&self) -> bool {
+        self.allow_tearing
+    }
+
+    /// Set the timeout for waiting on the implicit fences of element buffers
+    ///
+    /// If set, elements with unsignaled buffer fences or explicit sync acquire points will not be
+    /// directly scanned out, as this would delay the commit until the fences are signaled.
+    /// If the element was directly scanned out on an overlay or cursor plane before, the plane
+    /// keeps showing the previous buffer of the element until the fences are signaled.
+    /// If the fences of an element buffer did not signal within the timeout,
+    /// the element will be skipped until a new buffer is attached or the fences
+    /// get signaled, unless it is kept on its plane. Elements exceeding the timeout are
+    /// reported in [`RenderFrameResult::fence_timeouts`].
+    ///
+    /// Defaults to `None`, which disables checking fences.
+    pub fn set_fence_timeout(&mut self, timeout: Option<Duration>) {
+        self.fence_timeout = timeout;
+        if timeout.is_none() {
+            self.pending_fences.clear();
+        }
+    }
+
+    /// Returns the current timeout for waiting on buffer fences
+    pub fn fence_timeout(&self) -> Option<Duration> {
+        self.fence_timeout
+    }
+
+    /// Returns the direct scan-out statistics collected since creation
+    /// or the last call to [`DrmCompositor::reset_scanout_report`]
+    ///
+    /// The report contains the number of successful and failed plane assignments
+    /// per plane together with the most recent configuration rejected by the driver,
+    /// which can help to tune the compositor on problematic drivers.
+    /// Additionally it counts the frames requiring the renderer, which can be polled
+    /// e.g. by power management to verify the output is idling on direct scan-out.
+    pub fn scanout_report(&self) -> ScanoutReport {
+        self.scanout_statistics.borrow().report(
+            std::iter::once(self.surface.plane_info())
+                .chain(self.planes.cursor.iter())
+                .chain(self.planes.overlay.iter()),
+        )
+    }
+
+    /// Reset the direct scan-out statistics
+    pub fn reset_scanout_report(&mut self) {
+        self.scanout_statistics.get_mut().reset();
+    }
+
     /// Returns a reference to the underlying drm surface
     pub fn surface(&self) -> &DrmSurface {
         &self.surface
@@ -2777,6 +3365,56 @@ where
         Ok(())
     }
 
+    /// Returns the color depth of the underlying swapchain
+    ///
+    /// Returns `None`, if the format isn't known to [`ColorDepth`].
+    pub fn color_depth(&self) -> Option<ColorDepth> {
+        ColorDepth::from_format(self.format())
+    }
+
+    /// Reset the underlying swapchain and select a new color format.
+    ///
+    /// `color_formats` are tested in order until a working configuration is found, like in
+    /// [`DrmCompositor::new`]. This allows switching the color depth at runtime, e.g. using
+    /// [`ColorDepth::color_formats`]. The current swapchain is kept, if no format works.
+    ///
+    /// Returns the selected format.
+    pub fn set_color_formats(
+        &mut self,
+        mut allocator: A,
+        color_formats: impl IntoIterator<Item = DrmFourcc>,
+        renderer_formats: impl IntoIterator<Item = DrmFormat>,
+    ) -> Result<DrmFourcc, FrameErrorType<A, F>> {
+        let renderer_formats = renderer_formats.into_iter().collect::<Vec<_>>();
+
+        let mut error = None;
+        for format in color_formats {
+            debug!("Testing color format: {}", format);
+            match Self::find_supported_format(
+                self.surface.clone(),
+                self.supports_fencing,
+                &self.planes,
+                allocator,
+                &self.framebuffer_exporter,
+                renderer_formats.clone(),
+                format,
+            ) {
+                Ok((swapchain, is_opaque)) => {
+                    self.swapchain = swapchain;
+                    self.primary_is_opaque = is_opaque;
+                    return Ok(format);
+                }
+                Err((alloc, err)) => {
+                    warn!("Preferred format {} not available: {:?}", format, err);
+                    allocator = alloc;
+                    error = Some(err);
+                }
+            }
+        }
+
+        Err(error.unwrap_or(FrameError::NoSupportedPlaneFormat))
+    }
+
     /// Change the output mode source.
     pub fn set_output_mode_source(&mut self, output_mode_source: OutputModeSource) {
         // Avoid clearing damage if mode source did not change.
@@ -2805,6 +3443,7 @@ where
         output_transform: Transform,
         output_geometry: Rectangle<i32, Physical>,
         try_assign_primary_plane: bool,
+        reserved_overlay_planes: usize,
         frame_flags: FrameFlags,
     ) -> Result<PlaneAssignment, Option<RenderingReason>>
     where
@@ -2820,6 +3459,14 @@ where
             return Err(None);
         };
 
+        if element.kind() == Kind::ForceRender {
+            trace!(
+                "skipping direct scan-out for element {:?}, element requested rendering",
+                element.id()
+            );
+            return Err(None);
+        }
+
         let mut rendering_reason: Option<RenderingReason> = None;
 
         if try_assign_primary_plane {
@@ -2874,6 +3521,7 @@ where
             frame_state,
             output_transform,
             output_geometry,
+            reserved_overlay_planes,
             frame_flags,
         ) {
             Ok(plane) => {
@@ -3265,11 +3913,17 @@ where
             // Create a pixman image from the source cursor data. This will either be set by the
             // client, or the compositor's choice.
             let cursor_texture = match storage {
+                #[cfg(feature = "wayland_frontend")]
                 UnderlyingStorage::Wayland(buffer) => pixman_renderer
                     .import_buffer(buffer, None, &[element.src().to_i32_up()])
                     .transpose()
                     .ok()
                     .flatten(),
+                UnderlyingStorage::External(storage) => storage.dmabuf().and_then(|dmabuf| {
+                    pixman_renderer
+                        .import_dmabuf(dmabuf, Some(&[element.src().to_i32_up()]))
+                        .ok()
+                }),
                 UnderlyingStorage::Memory(memory) => {
                     let format = memory.format();
                     let size = memory.size();
@@ -3382,6 +4036,7 @@ where
             })
             .unwrap_or(false);
 
+        let properties = config.properties;
         let plane_state = PlaneState {
             skip: false,
             // Note: we assume we only have to test if the plane is
@@ -3402,18 +4057,25 @@ where
             frame_state.set_state(plane_info.handle, plane_state);
             true
         } else {
-            frame_state
-                .test_state(
-                    &self.surface,
-                    self.supports_fencing,
-                    plane_info.handle,
-                    plane_state,
-                    false,
-                )
-                .is_ok()
+            match frame_state.test_state(
+                &self.surface,
+                self.supports_fencing,
+                plane_info.handle,
+                plane_state,
+                false,
+            ) {
+                Ok(()) => true,
+                Err(err) => {
+                    self.scanout_statistics
+                        .borrow_mut()
+                        .test_failed(plane_info.handle, properties.scanout_failure(element.id(), &err));
+                    false
+                }
+            }
         };
 
         if res {
+            self.scanout_statistics.borrow_mut().assigned(plane_info.handle);
             cursor_state.previous_output_scale = Some(scale);
             cursor_state.previous_output_transform = Some(output_transform);
             Some(plane_info.into())
@@ -3696,6 +4358,7 @@ where
         frame_state: &mut CompositorFrameState<A, F>,
         output_transform: Transform,
         output_geometry: Rectangle<i32, Physical>,
+        reserved_overlay_planes: usize,
         frame_flags: FrameFlags,
     ) -> Result<PlaneAssignment, Option<RenderingReason>>
     where
@@ -3709,12 +4372,13 @@ where
         let element_id = element.id();
 
         // Check if we have a free plane, otherwise we can exit early
-        if self
+        let free_planes = self
             .planes
             .overlay
             .iter()
-            .all(|plane| frame_state.is_assigned(plane.handle))
-        {
+            .filter(|plane| !frame_state.is_assigned(plane.handle))
+            .count();
+        if free_planes == 0 {
             trace!(
                 "skipping overlay planes for element {:?}, no free planes",
                 element_id
@@ -3722,6 +4386,16 @@ where
             return Err(None);
         }
 
+        // Leave the remaining planes to elements with a higher priority
+        if free_planes <= reserved_overlay_planes {
+            trace!(
+                "skipping overlay planes for element {:?}, {} free planes reserved for higher priority elements",
+                element_id,
+                free_planes
+            );
+            return Err(None);
+        }
+
         let element_config = self.element_config(
             renderer,
             element,
@@ -3855,9 +4529,20 @@ where
             }
         }
 
-        // If we found no compatible plane fall back to walk all available planes
+        // If we found no compatible plane fall back to walk all available planes.
+        // Scan-out candidates are static content like wallpapers, so we walk the
+        // planes from the bottom to leave planes higher in the stack for more
+        // frequently changing elements.
+        let prefer_lower_planes = element.kind() == Kind::ScanoutCandidate;
+        let plane_count = self.planes.overlay.len();
         let mut rendering_reason: Option<RenderingReason> = None;
-        for (index, plane) in self.planes.overlay.iter().enumerate() {
+        for index in 0..plane_count {
+            let index = if prefer_lower_planes {
+                plane_count - 1 - index
+            } else {
+                index
+            };
+            let plane = &self.planes.overlay[index];
             // if the tested element state already tells us that this failed skip the test
             if element_config.failed_planes.overlay_bitmask & (1 << index) != 0 {
                 trace!(
@@ -4007,6 +4692,7 @@ where
             config: Some(config),
         };
 
+        let properties = element_config.properties;
         let res = if is_compatible {
             trace!(
                 "skipping atomic test for compatible element {:?} on {:?} with zpos {:?}",
@@ -4017,18 +4703,25 @@ where
             frame_state.set_state(plane.handle, plane_state);
             true
         } else {
-            frame_state
-                .test_state(
-                    &self.surface,
-                    self.supports_fencing,
-                    plane.handle,
-                    plane_state,
-                    false,
-                )
-                .is_ok()
+            match frame_state.test_state(
+                &self.surface,
+                self.supports_fencing,
+                plane.handle,
+                plane_state,
+                false,
+            ) {
+                Ok(()) => true,
+                Err(err) => {
+                    self.scanout_statistics
+                        .borrow_mut()
+                        .test_failed(plane.handle, properties.scanout_failure(element_id, &err));
+                    false
+                }
+            }
         };
 
         if res {
+            self.scanout_statistics.borrow_mut().assigned(plane.handle);
             trace!(
                 "successfully assigned element {:?} to {:?} with zpos {:?} for direct scan-out",
                 element_id,
@@ -4068,29 +4761,62 @@ where
     }
 }
 
+/// Number of distinct overlay plane priorities, see [`overlay_priority`]
+const OVERLAY_PRIORITIES: usize = 3;
+
+#[inline]
+fn overlay_priority(kind: Kind) -> usize {
+    match kind {
+        Kind::Video => 2,
+        Kind::Overlayable => 1,
+        Kind::Cursor | Kind::ScanoutCandidate | Kind::ForceRender | Kind::Unspecified => 0,
+    }
+}
+
+#[inline]
+fn underlying_storage_fences_signaled(storage: &UnderlyingStorage<'_>) -> bool {
+    match storage {
+        #[cfg(feature = "wayland_frontend")]
+        UnderlyingStorage::Wayland(buffer) => {
+            // A submission blocker releases the transaction before the acquire point is signaled,
+            // with a fence timeout it is treated like an implicit fence instead of an in-fence
+            match buffer.acquire_point() {
+                Some(acquire_point) => acquire_point.is_signaled(),
+                None => crate::wayland::dmabuf::get_dmabuf(buffer)
+                    .map(|dmabuf| dmabuf.is_ready_for_read())
+                    .unwrap_or(true),
+            }
+        }
+        UnderlyingStorage::External(storage) => storage.is_ready_for_read(),
+        UnderlyingStorage::Memory { .. } => true,
+    }
+}
+
 #[inline]
 fn apply_underlying_storage_transform(
     element_transform: Transform,
     storage: &UnderlyingStorage<'_>,
 ) -> Transform {
-    match storage {
-        UnderlyingStorage::Wayland(buffer) => {
-            if buffer_y_inverted(buffer).unwrap_or(false) {
-                match element_transform {
-                    Transform::Normal => Transform::Flipped,
-                    Transform::_90 => Transform::Flipped90,
-                    Transform::_180 => Transform::Flipped180,
-                    Transform::_270 => Transform::Flipped270,
-                    Transform::Flipped => Transform::Normal,
-                    Transform::Flipped90 => Transform::_90,
-                    Transform::Flipped180 => Transform::_180,
-                    Transform::Flipped270 => Transform::_270,
-                }
-            } else {
-                element_transform
-            }
+    let y_inverted = match storage {
+        #[cfg(feature = "wayland_frontend")]
+        UnderlyingStorage::Wayland(buffer) => buffer_y_inverted(buffer).unwrap_or(false),
+        UnderlyingStorage::External(storage) => storage.y_inverted(),
+        UnderlyingStorage::Memory { .. } => false,
+    };
+
+    if y_inverted {
+        match element_transform {
+            Transform::Normal => Transform::Flipped,
+            Transform::_90 => Transform::Flipped90,
+            Transform::_180 => Transform::Flipped180,
+            Transform::_270 => Transform::Flipped270,
+            Transform::Flipped => Transform::Normal,
+            Transform::Flipped90 => Transform::_90,
+            Transform::Flipped180 => Transform::_180,
+            Transform::Flipped270 => Transform::_270,
         }
-        UnderlyingStorage::Memory { .. } => element_transform,
+    } else {
+        element_transform
     }
 }
 
@@ -4218,6 +4944,7 @@ where
     };
 
     match underlying_storage {
+        #[cfg(feature = "wayland_frontend")]
         UnderlyingStorage::Wayland(buffer) => {
             // Only shm buffers are supported for copy
             shm::with_buffer_contents(buffer, |ptr, len, data| {
@@ -4249,6 +4976,8 @@ where
 
             copy_to_bo(memory, memory.stride(), memory.size().h)
         }
+        // dmabufs would need to be mapped, which is likely slower than rendering them
+        UnderlyingStorage::External(_) => false,
     }
 }
 
@@ -4413,3 +5142,139 @@ fn drm_compositor_is_send() {
 
     is_send::<DrmCompositor<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>>();
 }
+
+#[test]
+fn fence_timeout_keeps_previous_plane_state() {
+    use std::{fs::File, num::NonZeroU32};
+
+    use crate::backend::{
+        allocator::{dmabuf::DmabufFlags, format::FormatSet},
+        drm::device::PlaneClaimStorage,
+    };
+
+    #[derive(Debug)]
+    struct TestFramebuffer(framebuffer::Handle);
+
+    impl AsRef<framebuffer::Handle> for TestFramebuffer {
+        fn as_ref(&self) -> &framebuffer::Handle {
+            &self.0
+        }
+    }
+
+    impl Framebuffer for TestFramebuffer {
+        fn format(&self) -> DrmFormat {
+            DrmFormat {
+                code: DrmFourcc::Argb8888,
+                modifier: DrmModifier::Linear,
+            }
+        }
+    }
+
+    fn handle<T: From<NonZeroU32>>(value: u32) -> T {
+        T::from(NonZeroU32::new(value).unwrap())
+    }
+
+    fn plane_info(handle: plane::Handle, type_: PlaneType) -> PlaneInfo {
+        PlaneInfo {
+            handle,
+            type_,
+            zpos: None,
+            formats: FormatSet::default(),
+            size_hints: None,
+        }
+    }
+
+    let claims = PlaneClaimStorage::default();
+    let crtc: crtc::Handle = handle(1);
+    let plane_state = |plane: plane::Handle, id: &Id, fb: u32| {
+        let mut builder = Dmabuf::builder(
+            (64, 64),
+            DrmFourcc::Argb8888,
+            DrmModifier::Linear,
+            DmabufFlags::empty(),
+        );
+        builder.add_plane(OwnedFd::from(File::open("/dev/null").unwrap()), 0, 0, 256);
+        PlaneState::<Dmabuf, TestFramebuffer> {
+            skip: false,
+            needs_test: false,
+            element_state: Some(PlaneElementState {
+                id: id.clone(),
+                commit: CommitCounter::default(),
+                z_index: 1,
+            }),
+            config: Some(PlaneConfig {
+                properties: PlaneProperties {
+                    src: Rectangle::from_size((64., 64.).into()),
+                    dst: Rectangle::new((10, 10).into(), (64, 64).into()),
+                    transform: Transform::Normal,
+                    alpha: 1.0,
+                    format: DrmFormat {
+                        code: DrmFourcc::Argb8888,
+                        modifier: DrmModifier::Linear,
+                    },
+                },
+                buffer: DrmScanoutBuffer {
+                    buffer: ScanoutBuffer::Dmabuf(builder.build().unwrap()),
+                    fb: CachedDrmFramebuffer::new(DrmFramebuffer::Exporter(TestFramebuffer(handle(fb)))),
+                },
+                damage_clips: None,
+                plane_claim: claims.claim(plane, crtc).unwrap(),
+                sync: None,
+            }),
+        }
+    };
+
+    let (primary, cursor, overlay) = (handle(10), handle(11), handle(12));
+    let planes = Planes {
+        primary: vec![plane_info(primary, PlaneType::Primary)],
+        cursor: vec![plane_info(cursor, PlaneType::Cursor)],
+        overlay: vec![plane_info(overlay, PlaneType::Overlay)],
+    };
+    let element = Id::new();
+    let other = Id::new();
+
+    let mut previous = FrameState::from_planes(primary, &planes);
+    previous.set_state(primary, plane_state(primary, &other, 20));
+    previous.set_state(overlay, plane_state(overlay, &element, 21));
+
+    // the overlay plane keeps showing the previous buffer of the element, if scan-out is allowed
+    let mut next = FrameState::from_planes(primary, &planes);
+    assert_eq!(
+        next.keep_element_plane(&previous, primary, &element, |_| false),
+        None
+    );
+    assert!(!next.is_assigned(overlay));
+    assert_eq!(
+        next.keep_element_plane(&previous, primary, &element, |_| true),
+        Some(overlay)
+    );
+    let state = next.plane_state(overlay).unwrap();
+    assert!(state.skip && !state.needs_test);
+    assert!(state.is_compatible(previous.plane_state(overlay).unwrap()));
+    assert!(next.plane_buffer(overlay).unwrap().fb == previous.plane_buffer(overlay).unwrap().fb);
+    assert_eq!(
+        next.keep_element_plane(&previous, primary, &element, |_| true),
+        None
+    );
+
+    // elements directly scanned out on the primary plane or composited are not kept
+    assert_eq!(
+        next.keep_element_plane(&previous, primary, &other, |_| true),
+        None
+    );
+    assert_eq!(
+        next.keep_element_plane(&previous, primary, &Id::new(), |_| true),
+        None
+    );
+    assert!(!next.is_assigned(primary));
+    assert!(!next.is_assigned(cursor));
+
+    // a plane already used by another element is not replaced
+    let mut next = FrameState::from_planes(primary, &planes);
+    next.set_state(overlay, plane_state(overlay, &other, 22));
+    assert_eq!(
+        next.keep_element_plane(&previous, primary, &element, |_| true),
+        None
+    );
+    assert!(next.plane_buffer(overlay).unwrap().fb != previous.plane_buffer(overlay).unwrap().fb);
+}
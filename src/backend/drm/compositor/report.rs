@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use drm::control::{plane, PlaneType};
+use drm_fourcc::DrmFormat;
+
+use crate::{
+    backend::{
+        drm::{DrmError, PlaneInfo},
+        renderer::element::Id,
+    },
+    utils::{Buffer as BufferCoords, Physical, Rectangle, Transform},
+};
+
+/// Details about a plane configuration rejected by the driver
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanoutFailure {
+    /// Id of the element that failed the test
+    pub element: Id,
+    /// Format of the element buffer
+    pub format: DrmFormat,
+    /// Source rectangle in buffer coordinates
+    pub src: Rectangle<f64, BufferCoords>,
+    /// Destination rectangle on the output
+    pub dst: Rectangle<i32, Physical>,
+    /// Transform of the plane
+    pub transform: Transform,
+    /// Alpha of the plane
+    pub alpha: f32,
+    /// Error code returned by the driver, if available
+    pub errno: Option<i32>,
+}
+
+/// Direct scan-out statistics of a single plane
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaneScanoutStats {
+    /// Handle of the plane
+    pub handle: plane::Handle,
+    /// Type of the plane
+    pub type_: PlaneType,
+    /// z-position of the plane if available
+    pub zpos: Option<i32>,
+    /// Number of elements assigned to the plane
+    pub assigned: u64,
+    /// Number of elements rejected by the atomic test of the plane
+    pub test_failures: u64,
+    /// Number of elements moved back to the primary plane after
+    /// the atomic test of the complete frame failed
+    pub fallbacks: u64,
+    /// The most recently rejected configuration
+    pub last_failure: Option<ScanoutFailure>,
+}
+
+/// Plane usage of a single frame
+///
+/// See [`RenderFrameResult::scanout_info`](super::RenderFrameResult::scanout_info).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameScanoutInfo {
+    /// If the renderer was used to composite the primary plane
+    pub rendered: bool,
+    /// If an element is directly scanned out on the primary plane
+    pub primary_scanout: bool,
+    /// Number of overlay planes used for direct scan-out
+    pub overlay_planes: usize,
+    /// If the cursor plane is used
+    pub cursor_plane: bool,
+}
+
+impl FrameScanoutInfo {
+    /// Returns if the frame was realized without waking up the renderer
+    pub fn is_zero_copy(&self) -> bool {
+        !self.rendered
+    }
+}
+
+/// Direct scan-out statistics of a [`DrmCompositor`](super::DrmCompositor)
+///
+/// See [`DrmCompositor::scanout_report`](super::DrmCompositor::scanout_report).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanoutReport {
+    /// Statistics per plane, primary plane first
+    pub planes: Vec<PlaneScanoutStats>,
+    /// Number of frames whose complete atomic test failed
+    pub frame_test_failures: u64,
+    /// Number of non-empty frames
+    pub frames: u64,
+    /// Number of non-empty frames, which required the renderer
+    pub rendered_frames: u64,
+    /// Number of the most recent consecutive non-empty frames realized without the renderer
+    ///
+    /// Not affected by [`DrmCompositor::reset_scanout_report`](super::DrmCompositor::reset_scanout_report).
+    pub consecutive_zero_copy_frames: u64,
+}
+
+impl ScanoutReport {
+    /// Returns the fraction of non-empty frames, which required the renderer
+    ///
+    /// Returns `None` if no frames were recorded.
+    pub fn renderer_wake_rate(&self) -> Option<f64> {
+        (self.frames > 0).then(|| self.rendered_frames as f64 / self.frames as f64)
+    }
+}
+
+#[derive(Debug, Default)]
+struct PlaneCounters {
+    assigned: u64,
+    test_failures: u64,
+    fallbacks: u64,
+    last_failure: Option<ScanoutFailure>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct ScanoutStatistics {
+    planes: HashMap<plane::Handle, PlaneCounters>,
+    frame_test_failures: u64,
+    frames: u64,
+    rendered_frames: u64,
+    consecutive_zero_copy_frames: u64,
+}
+
+impl ScanoutStatistics {
+    pub(super) fn assigned(&mut self, plane: plane::Handle) {
+        self.planes.entry(plane).or_default().assigned += 1;
+    }
+
+    pub(super) fn test_failed(&mut self, plane: plane::Handle, failure: ScanoutFailure) {
+        let counters = self.planes.entry(plane).or_default();
+        counters.test_failures += 1;
+        counters.last_failure = Some(failure);
+    }
+
+    pub(super) fn fallback(&mut self, plane: plane::Handle) {
+        self.planes.entry(plane).or_default().fallbacks += 1;
+    }
+
+    pub(super) fn frame_test_failed(&mut self) {
+        self.frame_test_failures += 1;
+    }
+
+    pub(super) fn frame(&mut self, info: &FrameScanoutInfo) {
+        self.frames += 1;
+        if info.rendered {
+            self.rendered_frames += 1;
+            self.consecutive_zero_copy_frames = 0;
+        } else {
+            self.consecutive_zero_copy_frames += 1;
+        }
+    }
+
+    // Starts a new report, keeping the counter of consecutive frames
+    pub(super) fn reset(&mut self) {
+        *self = ScanoutStatistics {
+            consecutive_zero_copy_frames: self.consecutive_zero_copy_frames,
+            ..Default::default()
+        };
+    }
+
+    pub(super) fn report<'a>(&self, planes: impl IntoIterator<Item = &'a PlaneInfo>) -> ScanoutReport {
+        let planes = planes
+            .into_iter()
+            .map(|info| {
+                let counters = self.planes.get(&info.handle);
+                PlaneScanoutStats {
+                    handle: info.handle,
+                    type_: info.type_,
+                    zpos: info.zpos,
+                    assigned: counters.map(|c| c.assigned).unwrap_or_default(),
+                    test_failures: counters.map(|c| c.test_failures).unwrap_or_default(),
+                    fallbacks: counters.map(|c| c.fallbacks).unwrap_or_default(),
+                    last_failure: counters.and_then(|c| c.last_failure.clone()),
+                }
+            })
+            .collect();
+
+        ScanoutReport {
+            planes,
+            frame_test_failures: self.frame_test_failures,
+            frames: self.frames,
+            rendered_frames: self.rendered_frames,
+            consecutive_zero_copy_frames: self.consecutive_zero_copy_frames,
+        }
+    }
+}
+
+pub(super) fn drm_error_errno(err: &DrmError) -> Option<i32> {
+    match err {
+        DrmError::Access(err) => err.source.raw_os_error(),
+        _ => None,
+    }
+}
@@ -6,7 +6,9 @@ use std::sync::{atomic::AtomicBool, Arc, Mutex, Weak};
 use std::time::{Duration, SystemTime};
 
 use calloop::{EventSource, Interest, Poll, PostAction, Readiness, Token, TokenFactory};
-use drm::control::{connector, crtc, plane, Device as ControlDevice, Event, Mode, ResourceHandles};
+use drm::control::{
+    connector, crtc, plane, Device as ControlDevice, Event, Mode, ResourceHandle, ResourceHandles,
+};
 use drm::{ClientCapability, Device as BasicDevice, DriverCapability};
 use libc::dev_t;
 
@@ -18,7 +20,7 @@ use crate::utils::{Buffer, DevPath, Size};
 
 use super::error::AccessError;
 use super::surface::{atomic::AtomicDrmSurface, legacy::LegacyDrmSurface, DrmSurface, DrmSurfaceInternal};
-use super::{error::Error, planes, Planes};
+use super::{error::Error, planes, properties, Planes, PropertyInfo};
 use atomic::AtomicDrmDevice;
 use legacy::LegacyDrmDevice;
 
@@ -131,6 +133,17 @@ impl AsFd for DrmDevice {
 impl BasicDevice for DrmDevice {}
 impl ControlDevice for DrmDevice {}
 
+impl Drop for DrmDevice {
+    fn drop(&mut self) {
+        // The surfaces keep the device open, but can't be paused or activated with it anymore
+        // and delay restoring the previous state of the device until they are dropped.
+        debug_assert!(
+            std::thread::panicking() || self.surfaces.iter().all(|surface| surface.strong_count() == 0),
+            "DrmDevice dropped before its surfaces, drop all DrmSurfaces, GbmBufferedSurfaces and DrmCompositors first"
+        );
+    }
+}
+
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum DrmDeviceInternal {
@@ -310,6 +323,9 @@ impl DrmDevice {
     ///     has to be compatible with the provided `connectors`.
     /// - [`connectors`](drm::control::connector) - List of connectors driven by the crtc. At least one(!) connector needs to be \
     ///     attached to a crtc in smithay.
+    ///
+    /// The surface and everything created from it, like a `GbmBufferedSurface` or `DrmCompositor`,
+    /// have to be dropped before the device.
     #[instrument(skip(self), parent = self.internal.span(), err)]
     pub fn create_surface(
         &mut self,
@@ -410,6 +426,18 @@ impl DrmDevice {
         self.internal.device_fd()
     }
 
+    /// Returns all properties of a drm object (e.g. a connector, crtc or plane)
+    /// including their current values
+    pub fn properties(&self, handle: impl ResourceHandle) -> Result<Vec<PropertyInfo>, Error> {
+        properties::properties(self.device_fd(), handle)
+    }
+
+    /// Returns the property named `name` of a drm object (e.g. a connector, crtc or plane)
+    /// including its current value
+    pub fn property(&self, handle: impl ResourceHandle, name: &str) -> Result<Option<PropertyInfo>, Error> {
+        properties::property(self.device_fd(), handle, name)
+    }
+
     /// Pauses the device.
     ///
     /// This will cause the `DrmDevice` to avoid making calls to the file descriptor e.g. on drop.
@@ -605,3 +633,53 @@ impl EventSource for DrmDeviceNotifier {
         poll.unregister(self.internal.as_fd())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use drm::{buffer::DrmFourcc, control::Device as ControlDevice};
+    use rustix::fs::{Mode, OFlags};
+
+    use super::{DrmDevice, DrmDeviceFd};
+    use crate::utils::DeviceFd;
+
+    fn open_card() -> Option<DrmDeviceFd> {
+        let mut cards = std::fs::read_dir("/dev/dri")
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("card"))
+            })
+            .collect::<Vec<_>>();
+        cards.sort();
+        let fd = rustix::fs::open(cards.first()?, OFlags::RDWR | OFlags::CLOEXEC, Mode::empty()).ok()?;
+        Some(DrmDeviceFd::new(DeviceFd::from(fd)))
+    }
+
+    #[test]
+    fn framebuffers_survive_pause() {
+        // Skipped on machines without a usable drm device
+        let Some(fd) = open_card() else {
+            return;
+        };
+        let Ok((mut device, _notifier)) = DrmDevice::new(fd.clone(), false) else {
+            return;
+        };
+        let Ok(buffer) = fd.create_dumb_buffer((64, 64), DrmFourcc::Xrgb8888, 32) else {
+            return;
+        };
+        let fb = fd.add_framebuffer(&buffer, 24, 32).unwrap();
+
+        device.pause();
+        assert!(!device.is_active());
+        device.activate(false).unwrap();
+        assert!(device.is_active());
+
+        // nothing needs to be recreated after resuming
+        assert!(fd.get_framebuffer(fb).is_ok());
+        fd.destroy_framebuffer(fb).unwrap();
+        fd.destroy_dumb_buffer(buffer).unwrap();
+    }
+}
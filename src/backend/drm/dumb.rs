@@ -87,17 +87,30 @@ impl PlanarBuffer for PlanarDumbBuffer<'_> {
 
     #[inline]
     fn pitches(&self) -> [u32; 4] {
-        [self.0.handle().pitch(), 0, 0, 0]
+        let mut pitches = [0; 4];
+        for (pitch, plane_pitch) in pitches.iter_mut().zip(self.0.pitches()) {
+            *pitch = plane_pitch;
+        }
+        pitches
     }
 
     #[inline]
     fn handles(&self) -> [Option<drm::buffer::Handle>; 4] {
-        [Some(self.0.handle().handle()), None, None, None]
+        // all planes are part of the same dumb buffer
+        let mut handles = [None; 4];
+        for handle in handles.iter_mut().take(self.0.num_planes()) {
+            *handle = Some(self.0.handle().handle());
+        }
+        handles
     }
 
     #[inline]
     fn offsets(&self) -> [u32; 4] {
-        [0, 0, 0, 0]
+        let mut offsets = [0; 4];
+        for (offset, plane_offset) in offsets.iter_mut().zip(self.0.offsets()) {
+            *offset = plane_offset;
+        }
+        offsets
     }
 }
 
@@ -36,7 +36,8 @@ impl ExportFramebuffer<DumbBuffer> for DrmDeviceFd {
     ) -> Result<Option<Self::Framebuffer>, Self::Error> {
         match buffer {
             #[cfg(feature = "wayland_frontend")]
-            ExportBuffer::Wayland(_) => return Err(Error::Unsupported),
+            ExportBuffer::Wayland(_) => Err(Error::Unsupported),
+            ExportBuffer::Dmabuf(_) => Err(Error::Unsupported),
             ExportBuffer::Allocator(buffer) => framebuffer_from_dumb_buffer(self, buffer, use_opaque)
                 .map_err(Error::Drm)
                 .map(Some),
@@ -48,6 +49,7 @@ impl ExportFramebuffer<DumbBuffer> for DrmDeviceFd {
         match buffer {
             #[cfg(feature = "wayland_frontend")]
             ExportBuffer::Wayland(_) => false,
+            ExportBuffer::Dmabuf(_) => false,
             ExportBuffer::Allocator(_) => true,
         }
     }
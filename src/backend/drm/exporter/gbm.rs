@@ -2,13 +2,17 @@
 
 use std::os::unix::io::AsFd;
 
+use drm_fourcc::DrmModifier;
+
 use super::{ExportBuffer, ExportFramebuffer};
 #[cfg(feature = "wayland_frontend")]
 use crate::backend::drm::gbm::framebuffer_from_wayland_buffer;
 use crate::backend::{
-    allocator::gbm::GbmBuffer,
+    allocator::{gbm::GbmBuffer, Buffer},
     drm::{
-        gbm::{framebuffer_from_bo, Error, GbmFramebuffer},
+        gbm::{
+            dmabuf_has_required_planes, framebuffer_from_bo, framebuffer_from_dmabuf, Error, GbmFramebuffer,
+        },
         DrmDeviceFd,
     },
 };
@@ -32,33 +36,42 @@ impl<A: AsFd + 'static> ExportFramebuffer<GbmBuffer> for gbm::Device<A> {
             ExportBuffer::Allocator(buffer) => framebuffer_from_bo(drm, buffer, use_opaque)
                 .map_err(Error::Drm)
                 .map(Some),
+            ExportBuffer::Dmabuf(dmabuf) => {
+                // Importing a buffer without explicit modifiers to KMS is not safe,
+                // see `framebuffer_from_wayland_buffer`
+                if dmabuf.format().modifier == DrmModifier::Invalid {
+                    return Ok(None);
+                }
+                framebuffer_from_dmabuf(drm, self, dmabuf, use_opaque, false).map(Some)
+            }
         }
     }
 
     #[inline]
-    #[cfg(feature = "wayland_frontend")]
     fn can_add_framebuffer(&self, buffer: &ExportBuffer<'_, GbmBuffer>) -> bool {
         match buffer {
-            #[cfg(not(all(feature = "backend_egl", feature = "use_system_lib")))]
+            #[cfg(all(
+                feature = "wayland_frontend",
+                not(all(feature = "backend_egl", feature = "use_system_lib"))
+            ))]
             ExportBuffer::Wayland(buffer) => matches!(
                 crate::backend::renderer::buffer_type(buffer),
                 Some(crate::backend::renderer::BufferType::Dma)
             ),
-            #[cfg(all(feature = "backend_egl", feature = "use_system_lib"))]
+            #[cfg(all(
+                feature = "wayland_frontend",
+                feature = "backend_egl",
+                feature = "use_system_lib"
+            ))]
             ExportBuffer::Wayland(buffer) => matches!(
                 crate::backend::renderer::buffer_type(buffer),
                 Some(crate::backend::renderer::BufferType::Dma)
                     | Some(crate::backend::renderer::BufferType::Egl)
             ),
             ExportBuffer::Allocator(_) => true,
-        }
-    }
-
-    #[inline]
-    #[cfg(not(feature = "wayland_frontend"))]
-    fn can_add_framebuffer(&self, buffer: &ExportBuffer<'_, GbmBuffer>) -> bool {
-        match buffer {
-            ExportBuffer::Allocator(_) => true,
+            ExportBuffer::Dmabuf(dmabuf) => {
+                dmabuf.format().modifier != DrmModifier::Invalid && dmabuf_has_required_planes(dmabuf)
+            }
         }
     }
 }
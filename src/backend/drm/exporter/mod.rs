@@ -9,7 +9,10 @@ use std::{
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::protocol::wl_buffer::WlBuffer;
 
-use crate::backend::{allocator::Buffer, renderer::element::UnderlyingStorage};
+use crate::backend::{
+    allocator::{dmabuf::Dmabuf, Buffer},
+    renderer::element::UnderlyingStorage,
+};
 
 use super::{DrmDeviceFd, Framebuffer};
 
@@ -17,6 +20,7 @@ use super::{DrmDeviceFd, Framebuffer};
 pub mod dumb;
 #[cfg(feature = "backend_gbm")]
 pub mod gbm;
+pub mod shared;
 
 /// Possible buffers to export as a framebuffer using [`ExportFramebuffer`]
 #[derive(Debug)]
@@ -26,6 +30,8 @@ pub enum ExportBuffer<'a, B: Buffer> {
     Wayland(&'a WlBuffer),
     /// A [`Allocator`] buffer
     Allocator(&'a B),
+    /// A [`Dmabuf`] backing an [`ExternalStorage`](crate::backend::renderer::element::ExternalStorage)
+    Dmabuf(&'a Dmabuf),
 }
 
 impl<'a, B: Buffer> ExportBuffer<'a, B> {
@@ -35,6 +41,7 @@ impl<'a, B: Buffer> ExportBuffer<'a, B> {
         match storage {
             #[cfg(feature = "wayland_frontend")]
             UnderlyingStorage::Wayland(buffer) => Some(Self::Wayland(buffer)),
+            UnderlyingStorage::External(storage) => storage.dmabuf().map(Self::Dmabuf),
             UnderlyingStorage::Memory { .. } => None,
         }
     }
@@ -0,0 +1,374 @@
+//! Framebuffer exporter sharing framebuffers of client buffers
+//!
+//! Every [`DrmCompositor`](crate::backend::drm::compositor::DrmCompositor) caches the framebuffers
+//! of the elements it scans out, but this cache is local to the compositor and the element.
+//! If the same client buffer is shown on multiple outputs of the same device, or by multiple
+//! elements, every one of them adds and removes its own framebuffer for it.
+//!
+//! [`SharedFramebufferExporter`] wraps another [`ExportFramebuffer`] implementation and keeps
+//! the framebuffers of [`Dmabuf`]s (including dmabuf backed wayland buffers) alive for as long
+//! as the [`Dmabuf`] exists. Clones of the exporter share the same cache, so it can be handed
+//! to multiple compositors, e.g. by passing it to
+//! [`DrmOutputManager`](crate::backend::drm::output::DrmOutputManager).
+//!
+//! ```no_run
+//! # use smithay::backend::{
+//! #     allocator::gbm::GbmDevice,
+//! #     drm::{exporter::shared::SharedFramebufferExporter, DrmDeviceFd},
+//! # };
+//! # let gbm: GbmDevice<DrmDeviceFd> = todo!();
+//! let exporter = SharedFramebufferExporter::new(gbm);
+//! // hand out clones of the exporter to all compositors of the device
+//! let exporter_for_output = exporter.clone();
+//! ```
+//!
+//! Framebuffers are reference counted and only destroyed after the cache and all compositors
+//! released them. Cache entries are dropped once the [`Dmabuf`] is destroyed, or if the cache
+//! grows beyond its [limit](SharedFramebufferExporter::set_limit), in which case the least
+//! recently used framebuffers currently not in use by any compositor are evicted first.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use drm::control::framebuffer;
+use tracing::trace;
+
+use super::{ExportBuffer, ExportFramebuffer};
+use crate::backend::{
+    allocator::{
+        dmabuf::{Dmabuf, WeakDmabuf},
+        Buffer,
+    },
+    drm::{DrmDeviceFd, Framebuffer},
+};
+
+/// Default number of framebuffers kept by a [`SharedFramebufferExporter`]
+pub const DEFAULT_CACHE_LIMIT: usize = 64;
+
+/// A framebuffer exported by a [`SharedFramebufferExporter`]
+#[derive(Debug)]
+pub struct SharedFramebuffer<F>(Arc<F>);
+
+impl<F> Clone for SharedFramebuffer<F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<F: Framebuffer> AsRef<framebuffer::Handle> for SharedFramebuffer<F> {
+    #[inline]
+    fn as_ref(&self) -> &framebuffer::Handle {
+        (*self.0).as_ref()
+    }
+}
+
+impl<F: Framebuffer> Framebuffer for SharedFramebuffer<F> {
+    #[inline]
+    fn format(&self) -> drm_fourcc::DrmFormat {
+        (*self.0).format()
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry<F> {
+    drm: DrmDeviceFd,
+    use_opaque: bool,
+    framebuffer: Arc<F>,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+struct FramebufferCache<F> {
+    entries: HashMap<WeakDmabuf, Vec<CacheEntry<F>>>,
+    limit: usize,
+    clock: u64,
+}
+
+impl<F> Default for FramebufferCache<F> {
+    fn default() -> Self {
+        FramebufferCache {
+            entries: HashMap::new(),
+            limit: DEFAULT_CACHE_LIMIT,
+            clock: 0,
+        }
+    }
+}
+
+impl<F> FramebufferCache<F> {
+    fn len(&self) -> usize {
+        self.entries.values().map(Vec::len).sum()
+    }
+
+    fn get(&mut self, drm: &DrmDeviceFd, dmabuf: &Dmabuf, use_opaque: bool) -> Option<Arc<F>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self
+            .entries
+            .get_mut(&dmabuf.weak())?
+            .iter_mut()
+            .find(|entry| entry.use_opaque == use_opaque && &entry.drm == drm)?;
+        entry.last_used = clock;
+        Some(entry.framebuffer.clone())
+    }
+
+    fn insert(&mut self, drm: &DrmDeviceFd, dmabuf: &Dmabuf, use_opaque: bool, framebuffer: Arc<F>) {
+        self.clock += 1;
+        self.entries.entry(dmabuf.weak()).or_default().push(CacheEntry {
+            drm: drm.clone(),
+            use_opaque,
+            framebuffer,
+            last_used: self.clock,
+        });
+        self.cleanup();
+    }
+
+    fn cleanup(&mut self) {
+        self.entries.retain(|dmabuf, _| !dmabuf.is_gone());
+
+        let mut len = self.len();
+        while len > self.limit {
+            // Only evict framebuffers nobody else holds a reference to,
+            // in-use framebuffers would not be destroyed anyway
+            let lru = self
+                .entries
+                .iter()
+                .flat_map(|(dmabuf, entries)| {
+                    entries
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, entry)| Arc::strong_count(&entry.framebuffer) == 1)
+                        .map(move |(index, entry)| (entry.last_used, dmabuf, index))
+                })
+                .min_by_key(|(last_used, _, _)| *last_used)
+                .map(|(_, dmabuf, index)| (dmabuf.clone(), index));
+
+            let Some((dmabuf, index)) = lru else {
+                break;
+            };
+
+            trace!("evicting cached framebuffer");
+            let entries = self.entries.get_mut(&dmabuf).unwrap();
+            entries.remove(index);
+            if entries.is_empty() {
+                self.entries.remove(&dmabuf);
+            }
+            len -= 1;
+        }
+    }
+}
+
+/// [`ExportFramebuffer`] wrapper sharing the framebuffers of client buffers
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct SharedFramebufferExporter<E, F> {
+    exporter: E,
+    cache: Arc<Mutex<FramebufferCache<F>>>,
+}
+
+impl<E: Clone, F> Clone for SharedFramebufferExporter<E, F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            exporter: self.exporter.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<E, F> SharedFramebufferExporter<E, F> {
+    /// Create a new shared exporter wrapping the provided exporter
+    pub fn new(exporter: E) -> Self {
+        SharedFramebufferExporter {
+            exporter,
+            cache: Default::default(),
+        }
+    }
+
+    /// Returns a reference to the wrapped exporter
+    pub fn exporter(&self) -> &E {
+        &self.exporter
+    }
+
+    /// Returns the maximum number of cached framebuffers
+    pub fn limit(&self) -> usize {
+        self.cache.lock().unwrap().limit
+    }
+
+    /// Set the maximum number of cached framebuffers
+    ///
+    /// Framebuffers currently in use are never evicted, so the cache
+    /// might temporarily exceed the limit.
+    pub fn set_limit(&self, limit: usize) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.limit = limit;
+        cache.cleanup();
+    }
+
+    /// Returns the number of cached framebuffers
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no framebuffers are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the cached framebuffers of destroyed buffers
+    ///
+    /// This also happens automatically whenever a new framebuffer is added.
+    pub fn cleanup(&self) {
+        self.cache.lock().unwrap().cleanup();
+    }
+
+    /// Drop all cached framebuffers
+    ///
+    /// Framebuffers still in use by a compositor are destroyed once they are released.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().entries.clear();
+    }
+}
+
+impl<E, F, B> ExportFramebuffer<B> for SharedFramebufferExporter<E, F>
+where
+    E: ExportFramebuffer<B, Framebuffer = F>,
+    F: Framebuffer,
+    B: Buffer,
+{
+    type Framebuffer = SharedFramebuffer<F>;
+    type Error = E::Error;
+
+    #[profiling::function]
+    fn add_framebuffer(
+        &self,
+        drm: &DrmDeviceFd,
+        buffer: ExportBuffer<'_, B>,
+        use_opaque: bool,
+    ) -> Result<Option<Self::Framebuffer>, Self::Error> {
+        let dmabuf = match buffer {
+            #[cfg(feature = "wayland_frontend")]
+            ExportBuffer::Wayland(buffer) => crate::wayland::dmabuf::get_dmabuf(buffer).ok(),
+            ExportBuffer::Dmabuf(dmabuf) => Some(dmabuf),
+            // Allocator buffers are owned by a single swapchain which already
+            // keeps the framebuffer around for the lifetime of the buffer
+            ExportBuffer::Allocator(_) => None,
+        };
+
+        let Some(dmabuf) = dmabuf else {
+            return self
+                .exporter
+                .add_framebuffer(drm, buffer, use_opaque)
+                .map(|fb| fb.map(|fb| SharedFramebuffer(Arc::new(fb))));
+        };
+
+        if let Some(framebuffer) = self.cache.lock().unwrap().get(drm, dmabuf, use_opaque) {
+            trace!("using shared framebuffer for {:?}", dmabuf);
+            return Ok(Some(SharedFramebuffer(framebuffer)));
+        }
+
+        let Some(framebuffer) = self.exporter.add_framebuffer(drm, buffer, use_opaque)? else {
+            return Ok(None);
+        };
+        let framebuffer = Arc::new(framebuffer);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(drm, dmabuf, use_opaque, framebuffer.clone());
+
+        Ok(Some(SharedFramebuffer(framebuffer)))
+    }
+
+    #[inline]
+    fn can_add_framebuffer(&self, buffer: &ExportBuffer<'_, B>) -> bool {
+        self.exporter.can_add_framebuffer(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, os::unix::io::OwnedFd, sync::Arc};
+
+    use drm_fourcc::{DrmFourcc, DrmModifier};
+
+    use super::FramebufferCache;
+    use crate::{
+        backend::{
+            allocator::dmabuf::{Dmabuf, DmabufFlags},
+            drm::DrmDeviceFd,
+        },
+        utils::DeviceFd,
+    };
+
+    fn test_dmabuf() -> Dmabuf {
+        let mut builder = Dmabuf::builder(
+            (64, 64),
+            DrmFourcc::Argb8888,
+            DrmModifier::Linear,
+            DmabufFlags::empty(),
+        );
+        builder.add_plane(OwnedFd::from(File::open("/dev/null").unwrap()), 0, 0, 256);
+        builder.build().unwrap()
+    }
+
+    fn test_drm() -> DrmDeviceFd {
+        DrmDeviceFd::new(DeviceFd::from(OwnedFd::from(File::open("/dev/null").unwrap())))
+    }
+
+    #[test]
+    fn shared_between_lookups() {
+        let drm = test_drm();
+        let dmabuf = test_dmabuf();
+        let mut cache = FramebufferCache::default();
+
+        assert!(cache.get(&drm, &dmabuf, false).is_none());
+        cache.insert(&drm, &dmabuf, false, Arc::new(1u32));
+
+        let fb = cache.get(&drm, &dmabuf, false).unwrap();
+        assert_eq!(*fb, 1);
+        assert_eq!(Arc::strong_count(&fb), 2);
+        assert!(cache.get(&drm, &dmabuf, true).is_none());
+        assert!(cache.get(&test_drm(), &dmabuf, false).is_none());
+    }
+
+    #[test]
+    fn evict_destroyed_buffers() {
+        let drm = test_drm();
+        let dmabuf = test_dmabuf();
+        let mut cache = FramebufferCache::default();
+
+        cache.insert(&drm, &dmabuf, false, Arc::new(1u32));
+        let fb = cache.get(&drm, &dmabuf, false).unwrap();
+        drop(dmabuf);
+
+        cache.cleanup();
+        assert_eq!(cache.len(), 0);
+        // the framebuffer stays valid as long as it is referenced
+        assert_eq!(Arc::strong_count(&fb), 1);
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let drm = test_drm();
+        let dmabufs = [test_dmabuf(), test_dmabuf(), test_dmabuf()];
+        let mut cache = FramebufferCache {
+            limit: 2,
+            ..Default::default()
+        };
+
+        cache.insert(&drm, &dmabufs[0], false, Arc::new(0u32));
+        cache.insert(&drm, &dmabufs[1], false, Arc::new(1u32));
+        let in_use = cache.get(&drm, &dmabufs[0], false).unwrap();
+        let _ = cache.get(&drm, &dmabufs[1], false);
+        cache.insert(&drm, &dmabufs[2], false, Arc::new(2u32));
+
+        // the first buffer is the least recently used, but still in use
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&drm, &dmabufs[1], false).is_none());
+        assert_eq!(*cache.get(&drm, &dmabufs[0], false).unwrap(), *in_use);
+        assert!(cache.get(&drm, &dmabufs[2], false).is_some());
+    }
+}
@@ -0,0 +1,361 @@
+//! Minimal cpu-side rendering to display a message or logo on a [`DrmSurface`]
+//!
+//! A [`FallbackScreen`] fills a dumb buffer with a solid background and an optional image
+//! without requiring any renderer. This can be used to show an early-boot splash before the
+//! renderer is initialized, or an emergency message like "compositor crashed, restarting".
+//!
+//! Text is not rendered by this module, messages have to be provided as a pre-rendered image.
+//!
+//! ```no_run
+//! # use smithay::backend::{
+//! #     drm::{fallback::{FallbackImage, FallbackScreen}, DrmSurface},
+//! #     renderer::Color32F,
+//! # };
+//! # let surface: DrmSurface = todo!();
+//! # let logo: &[u8] = todo!();
+//! let screen = FallbackScreen::new(
+//!     &surface,
+//!     Color32F::BLACK,
+//!     Some(FallbackImage {
+//!         data: logo,
+//!         size: (128, 128).into(),
+//!         stride: 128 * 4,
+//!         scale: 1,
+//!     }),
+//! )
+//! .expect("failed to create fallback screen");
+//! screen.show(&surface).expect("failed to show fallback screen");
+//! // keep `screen` alive for as long as it should be displayed
+//! ```
+//!
+//! The screen is shown using [`DrmSurface::commit`], so this works with the atomic and
+//! the legacy drm api. Any other user of the surface, like a
+//! [`DrmCompositor`](crate::backend::drm::compositor::DrmCompositor), should reset its
+//! state after the fallback screen was shown.
+
+use std::io;
+
+use drm::buffer::Buffer as _;
+use thiserror::Error;
+
+use crate::{
+    backend::{
+        allocator::{dumb::DumbAllocator, dumb::DumbBuffer, Allocator, Fourcc, Modifier},
+        renderer::Color32F,
+    },
+    utils::{Buffer as BufferCoords, Rectangle, Size, Transform},
+};
+
+use super::{
+    dumb::{framebuffer_from_dumb_buffer, DumbFramebuffer},
+    error::AccessError,
+    DrmError, DrmSurface, PlaneConfig, PlaneState,
+};
+
+/// Image displayed by a [`FallbackScreen`]
+#[derive(Debug, Clone, Copy)]
+pub struct FallbackImage<'a> {
+    /// Pixel data in [`Fourcc::Argb8888`] format with pre-multiplied alpha
+    pub data: &'a [u8],
+    /// Size of the image in pixels
+    pub size: Size<i32, BufferCoords>,
+    /// Stride of the image data in bytes
+    pub stride: usize,
+    /// Integer scale used to display the image
+    pub scale: u32,
+}
+
+/// Errors of a [`FallbackScreen`]
+#[derive(Debug, Error)]
+pub enum FallbackError {
+    /// The image data does not match its size and stride
+    #[error("the image data does not match its size and stride")]
+    InvalidImage,
+    /// Failed to create the dumb buffer
+    #[error("failed to create the dumb buffer")]
+    Buffer(io::Error),
+    /// Failed to map the dumb buffer
+    #[error("failed to map the dumb buffer")]
+    Map(io::Error),
+    /// Failed to add a framebuffer for the dumb buffer
+    #[error("failed to add a framebuffer for the dumb buffer")]
+    Framebuffer(AccessError),
+}
+
+/// Cpu rendered screen content for a [`DrmSurface`]
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct FallbackScreen {
+    framebuffer: DumbFramebuffer,
+    _buffer: DumbBuffer,
+}
+
+impl FallbackScreen {
+    /// Render the background and image into a new buffer matching the pending mode of the surface
+    ///
+    /// The image is centered on the screen and clipped if it exceeds the screen.
+    pub fn new(
+        surface: &DrmSurface,
+        background: Color32F,
+        image: Option<FallbackImage<'_>>,
+    ) -> Result<Self, FallbackError> {
+        if let Some(image) = image.as_ref() {
+            if !image.is_valid() {
+                return Err(FallbackError::InvalidImage);
+            }
+        }
+
+        let (width, height) = surface.pending_mode().size();
+        let mut buffer = DumbAllocator::new(surface.device_fd().clone())
+            .create_buffer(width as u32, height as u32, Fourcc::Xrgb8888, &[Modifier::Linear])
+            .map_err(FallbackError::Buffer)?;
+
+        let stride = buffer.handle().pitch() as usize;
+        let mut mapping = buffer.map().map_err(FallbackError::Map)?;
+        draw(
+            &mut mapping,
+            stride,
+            (width as i32, height as i32).into(),
+            background,
+            image.as_ref(),
+        );
+        drop(mapping);
+
+        let framebuffer = framebuffer_from_dumb_buffer(surface.device_fd(), &buffer, false)
+            .map_err(FallbackError::Framebuffer)?;
+
+        Ok(FallbackScreen {
+            framebuffer,
+            _buffer: buffer,
+        })
+    }
+
+    /// Display the screen on the primary plane of the surface
+    ///
+    /// All other planes of the surface are disabled.
+    pub fn show(&self, surface: &DrmSurface) -> Result<(), DrmError> {
+        let (width, height) = surface.pending_mode().size();
+        let size = Size::from((width as i32, height as i32));
+
+        let planes = surface.planes();
+        let primary = PlaneState {
+            handle: surface.plane(),
+            config: Some(PlaneConfig {
+                src: Rectangle::from_size(size).to_f64(),
+                dst: Rectangle::from_size((size.w, size.h).into()),
+                transform: Transform::Normal,
+                alpha: 1.0,
+                damage_clips: None,
+                fb: *self.framebuffer.as_ref(),
+                fence: None,
+            }),
+        };
+        let others = planes
+            .cursor
+            .iter()
+            .chain(planes.overlay.iter())
+            .map(|plane| PlaneState {
+                handle: plane.handle,
+                config: None,
+            });
+
+        surface.commit(std::iter::once(primary).chain(others), false)
+    }
+}
+
+impl FallbackImage<'_> {
+    fn is_valid(&self) -> bool {
+        if self.size.w < 0 || self.size.h < 0 {
+            return false;
+        }
+        // the scaled size has to fit into the coordinate space of the screen
+        let Some(scale) = i32::try_from(self.scale).ok().filter(|scale| *scale > 0) else {
+            return false;
+        };
+        if self.size.w.checked_mul(scale).is_none() || self.size.h.checked_mul(scale).is_none() {
+            return false;
+        }
+
+        let Some(row_len) = (self.size.w as usize).checked_mul(4) else {
+            return false;
+        };
+        if self.stride < row_len {
+            return false;
+        }
+        if self.size.h == 0 {
+            return true;
+        }
+        (self.size.h as usize - 1)
+            .checked_mul(self.stride)
+            .and_then(|len| len.checked_add(row_len))
+            .is_some_and(|len| self.data.len() >= len)
+    }
+}
+
+fn draw(
+    data: &mut [u8],
+    stride: usize,
+    size: Size<i32, BufferCoords>,
+    background: Color32F,
+    image: Option<&FallbackImage<'_>>,
+) {
+    let to_u8 = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    // Pre-multiplied color on black
+    let background = [
+        to_u8(background.b()),
+        to_u8(background.g()),
+        to_u8(background.r()),
+        0xff,
+    ];
+
+    for row in data.chunks_exact_mut(stride).take(size.h as usize) {
+        for pixel in row[..size.w as usize * 4].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&background);
+        }
+    }
+
+    let Some(image) = image else {
+        return;
+    };
+
+    // `FallbackImage::is_valid` guarantees the scaled size does not overflow
+    let scale = image.scale as i32;
+    let image_size = Size::<i32, BufferCoords>::from((image.size.w * scale, image.size.h * scale));
+    let offset_x = (size.w - image_size.w) / 2;
+    let offset_y = (size.h - image_size.h) / 2;
+
+    for y in offset_y.max(0)..(offset_y + image_size.h).min(size.h) {
+        let src_row = ((y - offset_y) / scale) as usize * image.stride;
+        let dst_row = y as usize * stride;
+        for x in offset_x.max(0)..(offset_x + image_size.w).min(size.w) {
+            let src = src_row + ((x - offset_x) / scale) as usize * 4;
+            let dst = dst_row + x as usize * 4;
+            let src = &image.data[src..src + 4];
+            let dst = &mut data[dst..dst + 4];
+
+            let inv_alpha = 255 - src[3] as u32;
+            for channel in 0..3 {
+                dst[channel] =
+                    (src[channel] as u32 + (dst[channel] as u32 * inv_alpha + 127) / 255).min(255) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{draw, FallbackImage};
+    use crate::backend::renderer::Color32F;
+
+    #[test]
+    fn background_only() {
+        let mut data = vec![0u8; 4 * 4 * 2];
+        draw(
+            &mut data,
+            16,
+            (3, 2).into(),
+            Color32F::new(1.0, 0.0, 0.0, 1.0),
+            None,
+        );
+
+        for row in data.chunks_exact(16) {
+            assert_eq!(&row[..12], &[0, 0, 255, 255, 0, 0, 255, 255, 0, 0, 255, 255]);
+            // padding of the stride is left untouched
+            assert_eq!(&row[12..], &[0, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn centered_scaled_image() {
+        let mut data = vec![0u8; 4 * 4 * 4];
+        let pixel = [10, 20, 30, 255];
+        let image = FallbackImage {
+            data: &pixel,
+            size: (1, 1).into(),
+            stride: 4,
+            scale: 2,
+        };
+        assert!(image.is_valid());
+        draw(&mut data, 16, (4, 4).into(), Color32F::BLACK, Some(&image));
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let offset = y * 16 + x * 4;
+                let expected = if (1..3).contains(&x) && (1..3).contains(&y) {
+                    [10, 20, 30]
+                } else {
+                    [0, 0, 0]
+                };
+                assert_eq!(&data[offset..offset + 3], &expected, "pixel {x}x{y}");
+            }
+        }
+    }
+
+    #[test]
+    fn blend_and_clip() {
+        let mut data = vec![0u8; 4 * 2];
+        // half transparent white, pre-multiplied
+        let pixels = [128u8; 4 * 4];
+        let image = FallbackImage {
+            data: &pixels,
+            size: (4, 1).into(),
+            stride: 16,
+            scale: 1,
+        };
+        draw(
+            &mut data,
+            8,
+            (2, 1).into(),
+            Color32F::new(1.0, 1.0, 1.0, 1.0),
+            Some(&image),
+        );
+        assert_eq!(data, [255, 255, 255, 255, 255, 255, 255, 255]);
+
+        draw(&mut data, 8, (2, 1).into(), Color32F::BLACK, Some(&image));
+        assert_eq!(data, [128, 128, 128, 255, 128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn invalid_image() {
+        let image = FallbackImage {
+            data: &[0; 12],
+            size: (2, 2).into(),
+            stride: 8,
+            scale: 1,
+        };
+        assert!(!image.is_valid());
+
+        let image = FallbackImage {
+            data: &[0; 4],
+            size: (i32::MAX, 1).into(),
+            stride: usize::MAX,
+            scale: 1,
+        };
+        assert!(!image.is_valid());
+
+        let image = FallbackImage {
+            data: &[0; 4],
+            size: (1, i32::MAX).into(),
+            stride: usize::MAX,
+            scale: 1,
+        };
+        assert!(!image.is_valid());
+
+        let image = FallbackImage {
+            data: &[0; 4],
+            size: (1, 1).into(),
+            stride: 4,
+            scale: u32::MAX,
+        };
+        assert!(!image.is_valid());
+
+        let image = FallbackImage {
+            data: &[0; 4],
+            size: (1 << 16, 1).into(),
+            stride: 1 << 18,
+            scale: 1 << 15,
+        };
+        assert!(!image.is_valid());
+    }
+}
@@ -1,6 +1,6 @@
 //! Utilities to attach [`framebuffer::Handle`]s to gbm backed buffers
 
-use std::os::unix::io::AsFd;
+use std::{num::NonZeroU32, os::unix::io::AsFd};
 
 use thiserror::Error;
 
@@ -13,14 +13,12 @@ use tracing::{trace, warn};
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::protocol::wl_buffer::WlBuffer;
 
-#[cfg(feature = "wayland_frontend")]
-use crate::backend::allocator::Buffer;
 use crate::backend::{
     allocator::{
         dmabuf::Dmabuf,
-        format::{get_bpp, get_depth, get_opaque},
+        format::{get_bpp, get_depth, get_opaque, get_plane_count},
         gbm::GbmBuffer,
-        Fourcc,
+        Buffer, Fourcc,
     },
     drm::DrmDeviceFd,
 };
@@ -104,8 +102,7 @@ pub fn framebuffer_from_wayland_buffer<A: AsFd + 'static>(
             drm,
             BufferObjectInternal {
                 bo: &bo,
-                offsets: None,
-                pitches: None,
+                layout: None,
             },
             use_opaque,
             true,
@@ -131,6 +128,28 @@ pub enum Error {
     /// Failed to add a framebuffer for the bo
     #[error("failed to add a framebuffer for the bo")]
     Drm(AccessError),
+    /// The [`Dmabuf`] has less planes than required by its format
+    #[error("the dmabuf has {found} planes, but its format requires at least {required}")]
+    MissingPlanes {
+        /// Number of planes required by the format
+        required: usize,
+        /// Number of planes of the dmabuf
+        found: usize,
+    },
+    /// Failed to query the handle of a plane of the imported bo
+    #[error("failed to get the handle of plane {0} of the imported bo")]
+    PlaneHandle(usize),
+}
+
+/// Test if a [`Dmabuf`] provides all planes required by its format
+///
+/// Modifiers might require additional auxiliary planes, so a [`Dmabuf`] is allowed
+/// to provide more planes than the format itself requires.
+#[inline]
+pub fn dmabuf_has_required_planes(dmabuf: &Dmabuf) -> bool {
+    get_plane_count(dmabuf.format().code)
+        .map(|required| dmabuf.num_planes() >= required)
+        .unwrap_or(true)
 }
 
 /// Attach a framebuffer for a [`Dmabuf`]
@@ -149,26 +168,20 @@ pub fn framebuffer_from_dmabuf<A: AsFd + 'static>(
         .import_to(gbm, gbm::BufferObjectFlags::SCANOUT)
         .map_err(Error::Import)?;
 
-    // We override the offsets and pitches here cause the imported bo
-    // can return the wrong values. bo will only return the correct values
-    // for buffers we have allocated, but not for all client provided buffers.
-    let mut offsets: [u32; 4] = [0; 4];
-    let mut pitches: [u32; 4] = [0; 4];
-
-    for (index, offset) in dmabuf.offsets().enumerate() {
-        offsets[index] = offset;
-    }
-
-    for (index, stride) in dmabuf.strides().enumerate() {
-        pitches[index] = stride;
-    }
+    let layout = PlaneLayout::from_dmabuf(dmabuf, |plane| {
+        // SAFETY: gem handles are 32 bit, so the union is always initialized as `u32_`
+        let handle = unsafe { bo.handle_for_plane(plane as i32).u32_ };
+        // gbm reports -1 for planes it does not know about
+        NonZeroU32::new(handle)
+            .filter(|handle| handle.get() != u32::MAX)
+            .map(drm::buffer::Handle::from)
+    })?;
 
     framebuffer_from_bo_internal(
         drm,
         BufferObjectInternal {
             bo: &bo,
-            offsets: Some(offsets),
-            pitches: Some(pitches),
+            layout: Some(layout),
         },
         use_opaque,
         allow_legacy,
@@ -188,27 +201,73 @@ pub fn framebuffer_from_bo(
     bo: &GbmBuffer,
     use_opaque: bool,
 ) -> Result<GbmFramebuffer, AccessError> {
-    framebuffer_from_bo_internal(
-        drm,
-        BufferObjectInternal {
-            bo,
-            offsets: None,
-            pitches: None,
+    framebuffer_from_bo_internal(drm, BufferObjectInternal { bo, layout: None }, use_opaque, true).map(
+        |(fb, format)| GbmFramebuffer {
+            fb,
+            format,
+            drm: drm.clone(),
         },
-        use_opaque,
-        true,
     )
-    .map(|(fb, format)| GbmFramebuffer {
-        fb,
-        format,
-        drm: drm.clone(),
-    })
+}
+
+/// Plane layout of a framebuffer as described by a [`Dmabuf`]
+///
+/// We override the bo values here cause the imported bo can return
+/// the wrong values. bo will only return the correct values for buffers
+/// we have allocated, but not for all client provided buffers. This is
+/// especially true for multi-planar formats, where gbm might not know
+/// about all planes, and for the modifier, which gbm might not report
+/// for imported buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PlaneLayout {
+    format: drm_fourcc::DrmFormat,
+    handles: [Option<drm::buffer::Handle>; 4],
+    pitches: [u32; 4],
+    offsets: [u32; 4],
+}
+
+impl PlaneLayout {
+    fn from_dmabuf(
+        dmabuf: &Dmabuf,
+        mut handle_for_plane: impl FnMut(usize) -> Option<drm::buffer::Handle>,
+    ) -> Result<Self, Error> {
+        if let Some(required) = get_plane_count(dmabuf.format().code) {
+            if dmabuf.num_planes() < required {
+                return Err(Error::MissingPlanes {
+                    required,
+                    found: dmabuf.num_planes(),
+                });
+            }
+        }
+
+        let mut handles = [None; 4];
+        let mut pitches: [u32; 4] = [0; 4];
+        let mut offsets: [u32; 4] = [0; 4];
+
+        for (index, (offset, stride)) in dmabuf.offsets().zip(dmabuf.strides()).enumerate() {
+            handles[index] = Some(handle_for_plane(index).ok_or(Error::PlaneHandle(index))?);
+            pitches[index] = stride;
+            offsets[index] = offset;
+        }
+
+        Ok(PlaneLayout {
+            format: dmabuf.format(),
+            handles,
+            pitches,
+            offsets,
+        })
+    }
 }
 
 struct BufferObjectInternal<'a> {
     bo: &'a GbmBuffer,
-    pitches: Option<[u32; 4]>,
-    offsets: Option<[u32; 4]>,
+    layout: Option<PlaneLayout>,
+}
+
+impl BufferObjectInternal<'_> {
+    fn plane_count(&self) -> usize {
+        PlanarBuffer::handles(self).iter().flatten().count()
+    }
 }
 
 impl std::ops::Deref for BufferObjectInternal<'_> {
@@ -228,27 +287,45 @@ impl PlanarBuffer for BufferObjectInternal<'_> {
 
     #[inline]
     fn format(&self) -> drm_fourcc::DrmFourcc {
-        PlanarBuffer::format(self.bo)
+        match self.layout {
+            Some(layout) => layout.format.code,
+            None => PlanarBuffer::format(self.bo),
+        }
     }
 
     #[inline]
     fn modifier(&self) -> Option<DrmModifier> {
-        PlanarBuffer::modifier(self.bo)
+        match self.layout {
+            Some(layout) => match layout.format.modifier {
+                DrmModifier::Invalid => None,
+                modifier => Some(modifier),
+            },
+            None => PlanarBuffer::modifier(self.bo),
+        }
     }
 
     #[inline]
     fn pitches(&self) -> [u32; 4] {
-        self.pitches.unwrap_or_else(|| PlanarBuffer::pitches(self.bo))
+        match self.layout {
+            Some(layout) => layout.pitches,
+            None => PlanarBuffer::pitches(self.bo),
+        }
     }
 
     #[inline]
     fn handles(&self) -> [Option<drm::buffer::Handle>; 4] {
-        PlanarBuffer::handles(self.bo)
+        match self.layout {
+            Some(layout) => layout.handles,
+            None => PlanarBuffer::handles(self.bo),
+        }
     }
 
     #[inline]
     fn offsets(&self) -> [u32; 4] {
-        self.offsets.unwrap_or_else(|| PlanarBuffer::offsets(self.bo))
+        match self.layout {
+            Some(layout) => layout.offsets,
+            None => PlanarBuffer::offsets(self.bo),
+        }
     }
 }
 
@@ -300,8 +377,8 @@ fn framebuffer_from_bo_internal<D>(
 where
     D: drm::control::Device + DevPath,
 {
-    let modifier = bo.modifier();
-    let flags = if bo.modifier().is_some() {
+    let modifier = PlanarBuffer::modifier(&bo);
+    let flags = if modifier.is_some() {
         FbCmd2Flags::MODIFIERS
     } else {
         FbCmd2Flags::empty()
@@ -323,7 +400,7 @@ where
             (
                 fb,
                 drm_fourcc::DrmFormat {
-                    code: bo.format(),
+                    code: PlanarBuffer::format(&bo),
                     modifier: modifier.unwrap_or(DrmModifier::Invalid),
                 },
             )
@@ -352,7 +429,7 @@ where
                 });
             }
 
-            let fourcc = bo.format();
+            let fourcc = PlanarBuffer::format(&bo);
             let (depth, bpp) = get_depth(fourcc)
                 .and_then(|d| get_bpp(fourcc).map(|b| (d, b)))
                 .ok_or_else(|| AccessError {
@@ -379,3 +456,81 @@ where
     };
     Ok((fb, format))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, num::NonZeroU32, os::unix::io::OwnedFd};
+
+    use drm_fourcc::{DrmFourcc, DrmModifier};
+
+    use super::{dmabuf_has_required_planes, Error, PlaneLayout};
+    use crate::backend::allocator::{
+        dmabuf::{Dmabuf, DmabufFlags},
+        Buffer,
+    };
+
+    fn test_dmabuf(code: DrmFourcc, modifier: DrmModifier, planes: &[(u32, u32)]) -> Dmabuf {
+        let mut builder = Dmabuf::builder((64, 64), code, modifier, DmabufFlags::empty());
+        for (idx, &(offset, stride)) in planes.iter().enumerate() {
+            let fd = OwnedFd::from(File::open("/dev/null").unwrap());
+            builder.add_plane(fd, idx as u32, offset, stride);
+        }
+        builder.build().unwrap()
+    }
+
+    fn handle(value: u32) -> drm::buffer::Handle {
+        drm::buffer::Handle::from(NonZeroU32::new(value).unwrap())
+    }
+
+    #[test]
+    fn nv12_layout() {
+        let dmabuf = test_dmabuf(DrmFourcc::Nv12, DrmModifier::Linear, &[(0, 64), (4096, 64)]);
+        let layout = PlaneLayout::from_dmabuf(&dmabuf, |plane| Some(handle(plane as u32 + 1))).unwrap();
+
+        assert_eq!(layout.format, dmabuf.format());
+        assert_eq!(layout.handles, [Some(handle(1)), Some(handle(2)), None, None]);
+        assert_eq!(layout.pitches, [64, 64, 0, 0]);
+        assert_eq!(layout.offsets, [0, 4096, 0, 0]);
+    }
+
+    #[test]
+    fn p010_layout_keeps_modifier() {
+        let modifier = DrmModifier::I915_y_tiled_ccs;
+        let dmabuf = test_dmabuf(DrmFourcc::P010, modifier, &[(0, 128), (8192, 128), (12288, 32)]);
+        let layout = PlaneLayout::from_dmabuf(&dmabuf, |_| Some(handle(1))).unwrap();
+
+        assert_eq!(layout.format.code, DrmFourcc::P010);
+        assert_eq!(layout.format.modifier, modifier);
+        assert_eq!(
+            layout.handles,
+            [Some(handle(1)), Some(handle(1)), Some(handle(1)), None]
+        );
+        assert_eq!(layout.pitches, [128, 128, 32, 0]);
+        assert_eq!(layout.offsets, [0, 8192, 12288, 0]);
+    }
+
+    #[test]
+    fn missing_planes() {
+        let dmabuf = test_dmabuf(DrmFourcc::Nv12, DrmModifier::Linear, &[(0, 64)]);
+        assert!(!dmabuf_has_required_planes(&dmabuf));
+        assert!(matches!(
+            PlaneLayout::from_dmabuf(&dmabuf, |_| Some(handle(1))),
+            Err(Error::MissingPlanes {
+                required: 2,
+                found: 1
+            })
+        ));
+
+        let dmabuf = test_dmabuf(DrmFourcc::Argb8888, DrmModifier::Linear, &[(0, 256)]);
+        assert!(dmabuf_has_required_planes(&dmabuf));
+    }
+
+    #[test]
+    fn missing_plane_handle() {
+        let dmabuf = test_dmabuf(DrmFourcc::Nv12, DrmModifier::Linear, &[(0, 64), (4096, 64)]);
+        assert!(matches!(
+            PlaneLayout::from_dmabuf(&dmabuf, |plane| (plane == 0).then(|| handle(1))),
+            Err(Error::PlaneHandle(1))
+        ));
+    }
+}
@@ -49,6 +49,14 @@
 //!
 //! A commit/page_flip may be triggered to apply the pending state.
 //!
+//! ## Session switching
+//!
+//! When the session gets paused, e.g. on a VT switch, the device has to be paused using [`DrmDevice::pause`]
+//! and activated again with [`DrmDevice::activate`] once the session is resumed. The device fd stays valid
+//! in between, so all framebuffers, buffers and imported textures can be kept and do not need to be recreated.
+//! Only the state of the crtcs has to be re-evaluated, as another drm master might have changed it.
+//! [`DrmOutputManager::activate`](output::DrmOutputManager::activate) takes care of that for all of its outputs.
+//!
 //! ## Rendering
 //!
 //! The drm infrastructure makes no assumptions about the used renderer and does not interface with them directly.
@@ -70,18 +78,22 @@
 //! to allocate buffers for use in X11 or Wayland. If you need to do mode setting, you should use
 //! [`DrmDevice`] instead.
 
-#[cfg(all(feature = "wayland_frontend", feature = "backend_gbm"))]
+#[cfg(feature = "backend_gbm")]
 pub mod compositor;
 pub(crate) mod device;
 #[cfg(feature = "backend_drm")]
 pub mod dumb;
 mod error;
 pub mod exporter;
+#[cfg(feature = "backend_drm")]
+pub mod fallback;
 #[cfg(feature = "backend_gbm")]
 pub mod gbm;
-#[cfg(all(feature = "wayland_frontend", feature = "backend_gbm"))]
+#[cfg(feature = "backend_drm")]
+pub mod modes;
+#[cfg(feature = "backend_gbm")]
 pub mod output;
-
+mod properties;
 mod surface;
 
 use std::sync::Once;
@@ -96,9 +108,10 @@ use drm_fourcc::{DrmFormat, DrmFourcc, DrmModifier};
 pub use error::AccessError as DrmAccessError;
 pub use error::Error as DrmError;
 use indexmap::IndexSet;
+pub use properties::{PropertyEnumValue, PropertyInfo, PropertyKind};
 #[cfg(feature = "backend_gbm")]
 pub use surface::gbm::{Error as GbmBufferedSurfaceError, GbmBufferedSurface};
-pub use surface::{DrmSurface, PlaneConfig, PlaneDamageClips, PlaneState, VrrSupport};
+pub use surface::{DrmSurface, LinkStatus, PlaneConfig, PlaneDamageClips, PlaneState, VrrSupport};
 
 use drm::{
     control::{crtc, framebuffer, plane, Device as ControlDevice, PlaneType},
@@ -0,0 +1,286 @@
+//! Helpers to filter and select connector modes
+//!
+//! Connectors usually report a lot of modes, many of them only differing in timings,
+//! being interlaced or exceeding the limits of the link. [`ModeFilter`] reduces them to a list
+//! suitable to be presented to the user, e.g. through an output-management protocol, and [`ModePolicy`]
+//! selects the mode to initially drive a connector with.
+//!
+//! ```no_run
+//! # use drm::control::connector;
+//! use smithay::backend::drm::modes::{ModeFilter, ModePolicy};
+//!
+//! # fn connector_info() -> connector::Info { unimplemented!() }
+//! let connector = connector_info();
+//! let modes = ModeFilter::new().max_pixel_clock(600_000).apply(connector.modes());
+//! let mode = ModePolicy::Preferred.select(&modes).expect("No usable mode");
+//! ```
+
+use std::{fmt, sync::Arc};
+
+use drm::control::{Mode, ModeFlags, ModeTypeFlags};
+
+use crate::utils::{Physical, Size};
+
+/// Returns the refresh rate of a mode in millihertz
+pub fn refresh_rate(mode: &Mode) -> i32 {
+    crate::output::Mode::from(*mode).refresh
+}
+
+fn size(mode: &Mode) -> Size<i32, Physical> {
+    let (w, h) = mode.size();
+    Size::from((w as i32, h as i32))
+}
+
+fn is_preferred(mode: &Mode) -> bool {
+    mode.mode_type().contains(ModeTypeFlags::PREFERRED)
+}
+
+/// Filter for connector modes
+///
+/// By default interlaced and double scan modes are dropped and modes with the same resolution
+/// and refresh rate are deduplicated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeFilter {
+    interlaced: bool,
+    deduplicate: bool,
+    max_pixel_clock: Option<u32>,
+    max_size: Option<Size<i32, Physical>>,
+}
+
+impl Default for ModeFilter {
+    fn default() -> Self {
+        ModeFilter {
+            interlaced: false,
+            deduplicate: true,
+            max_pixel_clock: None,
+            max_size: None,
+        }
+    }
+}
+
+impl ModeFilter {
+    /// Create a new filter with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep interlaced and double scan modes
+    pub fn allow_interlaced(mut self, allow: bool) -> Self {
+        self.interlaced = allow;
+        self
+    }
+
+    /// Deduplicate modes with the same resolution and refresh rate
+    ///
+    /// Of every group of duplicates the first mode in the original order is kept,
+    /// unless another one is marked as preferred.
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// Drop modes exceeding the given pixel clock in kHz
+    ///
+    /// This can be used to apply limits of the link, like a dock or an adapter.
+    pub fn max_pixel_clock(mut self, clock: u32) -> Self {
+        self.max_pixel_clock = Some(clock);
+        self
+    }
+
+    /// Drop modes exceeding the given bandwidth in bits per second at the given bits per pixel
+    ///
+    /// Overrides [`ModeFilter::max_pixel_clock`].
+    pub fn max_bandwidth(self, bits_per_second: u64, bits_per_pixel: u32) -> Self {
+        let clock = bits_per_second / bits_per_pixel.max(1) as u64 / 1000;
+        self.max_pixel_clock(clock.min(u32::MAX as u64) as u32)
+    }
+
+    /// Drop modes exceeding the given size in either dimension
+    pub fn max_size(mut self, size: impl Into<Size<i32, Physical>>) -> Self {
+        self.max_size = Some(size.into());
+        self
+    }
+
+    /// Returns if a mode passes the filter, ignoring deduplication
+    pub fn accepts(&self, mode: &Mode) -> bool {
+        if !self.interlaced && mode.flags().intersects(ModeFlags::INTERLACE | ModeFlags::DBLSCAN) {
+            return false;
+        }
+        if self.max_pixel_clock.is_some_and(|max| mode.clock() > max) {
+            return false;
+        }
+        let size = size(mode);
+        if self.max_size.is_some_and(|max| size.w > max.w || size.h > max.h) {
+            return false;
+        }
+        true
+    }
+
+    /// Filter a list of modes
+    ///
+    /// The modes are returned ordered by resolution and refresh rate, highest first,
+    /// with the preferred mode in front.
+    pub fn apply(&self, modes: &[Mode]) -> Vec<Mode> {
+        let mut filtered: Vec<Mode> = Vec::with_capacity(modes.len());
+        for mode in modes.iter().filter(|mode| self.accepts(mode)) {
+            if self.deduplicate {
+                if let Some(existing) = filtered.iter_mut().find(|existing| {
+                    existing.size() == mode.size() && refresh_rate(existing) == refresh_rate(mode)
+                }) {
+                    if is_preferred(mode) && !is_preferred(existing) {
+                        *existing = *mode;
+                    }
+                    continue;
+                }
+            }
+            filtered.push(*mode);
+        }
+
+        // stable, so equal modes keep the driver order
+        filtered.sort_by_key(|mode| {
+            let size = size(mode);
+            std::cmp::Reverse((
+                is_preferred(mode),
+                size.w as i64 * size.h as i64,
+                refresh_rate(mode),
+            ))
+        });
+        filtered
+    }
+}
+
+/// Policy to select the initial mode of a connector
+#[derive(Clone, Default)]
+pub enum ModePolicy {
+    /// The mode marked as preferred, usually the native mode of the display as reported by its EDID,
+    /// falling back to the highest resolution
+    #[default]
+    Preferred,
+    /// The highest resolution with the highest refresh rate available for it
+    HighestResolution,
+    /// The highest refresh rate with the highest resolution available for it
+    HighestRefreshRate,
+    /// The mode matching the given size and refresh rate in millihertz, falling back to
+    /// [`ModePolicy::Preferred`]
+    ///
+    /// Without a refresh rate the highest refresh rate for the size is selected, otherwise
+    /// the closest one.
+    Exact {
+        /// Size of the mode
+        size: Size<i32, Physical>,
+        /// Refresh rate of the mode in millihertz
+        refresh: Option<i32>,
+    },
+    /// A custom policy, e.g. consulting a configuration file
+    Custom(Arc<dyn Fn(&[Mode]) -> Option<Mode> + Send + Sync>),
+}
+
+impl fmt::Debug for ModePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModePolicy::Preferred => write!(f, "Preferred"),
+            ModePolicy::HighestResolution => write!(f, "HighestResolution"),
+            ModePolicy::HighestRefreshRate => write!(f, "HighestRefreshRate"),
+            ModePolicy::Exact { size, refresh } => f
+                .debug_struct("Exact")
+                .field("size", size)
+                .field("refresh", refresh)
+                .finish(),
+            ModePolicy::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl ModePolicy {
+    /// Select a mode out of `modes`
+    ///
+    /// Returns `None`, if `modes` is empty or a custom policy didn't select any mode.
+    pub fn select(&self, modes: &[Mode]) -> Option<Mode> {
+        let area = |mode: &Mode| {
+            let size = size(mode);
+            size.w as i64 * size.h as i64
+        };
+
+        match self {
+            ModePolicy::Preferred => modes
+                .iter()
+                .find(|mode| is_preferred(mode))
+                .copied()
+                .or_else(|| ModePolicy::HighestResolution.select(modes)),
+            ModePolicy::HighestResolution => modes
+                .iter()
+                .max_by_key(|mode| (area(mode), refresh_rate(mode)))
+                .copied(),
+            ModePolicy::HighestRefreshRate => modes
+                .iter()
+                .max_by_key(|mode| (refresh_rate(mode), area(mode)))
+                .copied(),
+            ModePolicy::Exact {
+                size: target,
+                refresh,
+            } => {
+                let candidates = modes.iter().filter(|mode| size(mode) == *target);
+                let mode = match refresh {
+                    Some(refresh) => {
+                        candidates.min_by_key(|mode| (refresh_rate(mode) - refresh).unsigned_abs())
+                    }
+                    None => candidates.max_by_key(|mode| refresh_rate(mode)),
+                };
+                mode.copied().or_else(|| ModePolicy::Preferred.select(modes))
+            }
+            ModePolicy::Custom(select) => select(modes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use drm::control::{Mode, ModeFlags, ModeTypeFlags};
+
+    use super::{refresh_rate, ModeFilter, ModePolicy};
+
+    fn mode(w: u16, h: u16, refresh: u32, flags: ModeFlags, type_: ModeTypeFlags) -> Mode {
+        let (htotal, vtotal) = (w + 100, h + 50);
+        Mode::from(drm_ffi::drm_mode_modeinfo {
+            clock: htotal as u32 * vtotal as u32 * refresh / 1000,
+            hdisplay: w,
+            htotal,
+            vdisplay: h,
+            vtotal,
+            vrefresh: refresh,
+            flags: flags.bits(),
+            type_: type_.bits(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn filter_and_select() {
+        let modes = [
+            mode(1920, 1080, 60, ModeFlags::empty(), ModeTypeFlags::DRIVER),
+            mode(2560, 1440, 60, ModeFlags::empty(), ModeTypeFlags::PREFERRED),
+            mode(1920, 1080, 60, ModeFlags::empty(), ModeTypeFlags::DRIVER),
+            mode(1920, 1080, 144, ModeFlags::empty(), ModeTypeFlags::DRIVER),
+            mode(1920, 1080, 60, ModeFlags::INTERLACE, ModeTypeFlags::DRIVER),
+            mode(3840, 2160, 60, ModeFlags::empty(), ModeTypeFlags::DRIVER),
+        ];
+
+        let filtered = ModeFilter::new().apply(&modes);
+        assert_eq!(filtered.len(), 4);
+        assert_eq!(filtered[0], modes[1]);
+        assert_eq!(filtered[1], modes[5]);
+        assert_eq!(filtered[2], modes[3]);
+
+        let limited = ModeFilter::new().max_pixel_clock(modes[1].clock()).apply(&modes);
+        assert!(!limited.contains(&modes[5]));
+
+        assert_eq!(ModePolicy::Preferred.select(&filtered), Some(modes[1]));
+        assert_eq!(ModePolicy::HighestResolution.select(&filtered), Some(modes[5]));
+        assert_eq!(ModePolicy::HighestRefreshRate.select(&filtered), Some(modes[3]));
+        let exact = ModePolicy::Exact {
+            size: (1920, 1080).into(),
+            refresh: Some(refresh_rate(&modes[0]) + 10),
+        };
+        assert_eq!(exact.select(&filtered), Some(modes[0]));
+    }
+}
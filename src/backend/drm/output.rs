@@ -18,7 +18,10 @@ use crate::{
             gbm::GbmDevice,
             Allocator,
         },
-        renderer::{element::RenderElement, Bind, Color32F, DebugFlags, Renderer, Texture},
+        renderer::{
+            element::{Id, RenderElement},
+            Bind, Color32F, DebugFlags, Renderer, Texture,
+        },
     },
     output::OutputModeSource,
 };
@@ -26,10 +29,10 @@ use crate::{
 use super::{
     compositor::{
         DrmCompositor, FrameError, FrameFlags, FrameResult, RenderFrameError, RenderFrameErrorType,
-        RenderFrameResult,
+        RenderFrameResult, SubmittedFrame,
     },
     exporter::ExportFramebuffer,
-    DrmDevice, DrmError, Planes,
+    DrmDevice, DrmError, DrmEventMetadata, Planes,
 };
 
 type CompositorList<A, F, U, G> = Arc<RwLock<HashMap<crtc::Handle, Mutex<DrmCompositor<A, F, U, G>>>>>;
@@ -50,11 +53,12 @@ where
     <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Framebuffer: fmt::Debug + 'static,
     G: AsFd + 'static,
 {
+    // declared first, so the compositors are dropped before the device
+    compositor: CompositorList<A, F, U, G>,
     device: DrmDevice,
     allocator: A,
     exporter: F,
     gbm: Option<GbmDevice<G>>,
-    compositor: CompositorList<A, F, U, G>,
     color_formats: Vec<DrmFourcc>,
     renderer_formats: Vec<DrmFormat>,
 }
@@ -97,6 +101,18 @@ where
     pub fn device_mut(&mut self) -> &mut DrmDevice {
         &mut self.device
     }
+
+    /// Returns the color formats tested in order when creating a new [`DrmOutput`]
+    pub fn color_formats(&self) -> &[DrmFourcc] {
+        &self.color_formats
+    }
+
+    /// Set the color formats tested in order when creating a new [`DrmOutput`]
+    ///
+    /// This does not affect already created outputs, see [`DrmOutput::set_color_formats`].
+    pub fn set_color_formats(&mut self, color_formats: impl IntoIterator<Item = DrmFourcc>) {
+        self.color_formats = color_formats.into_iter().collect();
+    }
 }
 
 impl<A, F, U, G> DrmOutputManager<A, F, U, G>
@@ -157,7 +173,7 @@ where
     <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Framebuffer: std::fmt::Debug + 'static,
     <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Error:
         std::marker::Send + std::marker::Sync + 'static,
-    G: AsFd + std::clone::Clone + 'static,
+    G: AsFd + std::clone::Clone + Send + Sync + 'static,
     U: 'static,
 {
     /// Create a new [`DrmOutputManager`] from a [`DrmDevice`].
@@ -501,13 +517,22 @@ where
     /// the device was not active before. Otherwise you need to make sure there are no
     /// conflicting requirements when enabling or creating surfaces or you are prepared
     /// to handle errors caused by those.
+    ///
+    /// The swapchains and framebuffers of all outputs are kept, see [`DrmCompositor::resume`].
     pub fn activate(&mut self, disable_connectors: bool) -> Result<(), DrmError> {
+        let was_active = self.device.is_active();
         self.device.activate(disable_connectors)?;
 
         // We request a write guard here to guarantee unique access
         let mut write_guard = self.compositor.write().unwrap();
         for compositor in write_guard.values_mut() {
-            if let Err(err) = compositor.get_mut().unwrap().reset_state() {
+            let compositor = compositor.get_mut().unwrap();
+            let res = if was_active {
+                compositor.reset_state()
+            } else {
+                compositor.resume()
+            };
+            if let Err(err) = res {
                 tracing::warn!("Failed to reset drm surface state: {}", err);
             }
         }
@@ -560,7 +585,7 @@ where
     <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Framebuffer: std::fmt::Debug + 'static,
     <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Error:
         std::marker::Send + std::marker::Sync + 'static,
-    G: AsFd + std::clone::Clone + 'static,
+    G: AsFd + std::clone::Clone + Send + Sync + 'static,
     U: 'static,
 {
     /// Set the [`DebugFlags`] to use
@@ -576,6 +601,20 @@ where
         self.with_compositor(|compositor| compositor.reset_buffers());
     }
 
+    /// Forces the next frame to be fully composited
+    ///
+    /// See [`DrmCompositor::damage_all`] for more details.
+    pub fn damage_all(&self) {
+        self.with_compositor(|compositor| compositor.damage_all());
+    }
+
+    /// Drops the cached framebuffers of the element with the given [`Id`]
+    ///
+    /// See [`DrmCompositor::invalidate_element`] for more details.
+    pub fn invalidate_element(&self, id: &Id) -> bool {
+        self.with_compositor(|compositor| compositor.invalidate_element(id))
+    }
+
     /// Marks the current frame as submitted.
     ///
     /// *Note*: Needs to be called, after the vblank event of the matching [`DrmDevice`]
@@ -585,6 +624,33 @@ where
         self.with_compositor(|compositor| compositor.frame_submitted())
     }
 
+    /// Marks the current frame as submitted and returns the presentation information
+    /// of the frame.
+    ///
+    /// See [`DrmCompositor::frame_submitted_with_metadata`] for details.
+    pub fn frame_submitted_with_metadata(
+        &self,
+        metadata: Option<DrmEventMetadata>,
+    ) -> FrameResult<Option<SubmittedFrame<U>>, A, F> {
+        self.with_compositor(|compositor| compositor.frame_submitted_with_metadata(metadata))
+    }
+
+    /// Reset the underlying swapchain and select a new color format.
+    ///
+    /// See [`DrmCompositor::set_color_formats`] for details.
+    pub fn set_color_formats(
+        &self,
+        color_formats: impl IntoIterator<Item = DrmFourcc>,
+    ) -> FrameResult<DrmFourcc, A, F> {
+        self.with_compositor(|compositor| {
+            compositor.set_color_formats(
+                self.allocator.clone(),
+                color_formats,
+                self.renderer_formats.iter().copied(),
+            )
+        })
+    }
+
     /// Get the format of the underlying swapchain
     pub fn format(&self) -> DrmFourcc {
         self.with_compositor(|compositor| compositor.format())
@@ -736,7 +802,7 @@ where
     <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Framebuffer: std::fmt::Debug + 'static,
     <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Error:
         std::marker::Send + std::marker::Sync + 'static,
-    G: AsFd + std::clone::Clone + 'static,
+    G: AsFd + std::clone::Clone + Send + Sync + 'static,
     U: 'static,
     E: RenderElement<R>,
     R: Renderer + Bind<Dmabuf>,
@@ -913,7 +979,7 @@ where
         <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Framebuffer: std::fmt::Debug + 'static,
         <F as ExportFramebuffer<<A as Allocator>::Buffer>>::Error:
             std::marker::Send + std::marker::Sync + 'static,
-        G: AsFd + std::clone::Clone + 'static,
+        G: AsFd + std::clone::Clone + Send + Sync + 'static,
         U: 'static,
     {
         let (elements, clear_color) = self
@@ -0,0 +1,294 @@
+use drm::control::{property, Device as ControlDevice, ResourceHandle};
+
+use super::{error::AccessError, DrmError};
+use crate::utils::DevPath;
+
+/// Possible value of an enum property
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyEnumValue {
+    /// Name of the value
+    pub name: String,
+    /// Raw value
+    pub value: u64,
+}
+
+/// Type and valid values of a property
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyKind {
+    /// Unknown property type
+    Unknown,
+    /// Boolean
+    Boolean,
+    /// Unsigned range including `min` and `max`
+    UnsignedRange {
+        /// Minimal value
+        min: u64,
+        /// Maximal value
+        max: u64,
+    },
+    /// Signed range including `min` and `max`
+    SignedRange {
+        /// Minimal value
+        min: i64,
+        /// Maximal value
+        max: i64,
+    },
+    /// Enumeration of the specified values
+    Enum(Vec<PropertyEnumValue>),
+    /// Bitmask
+    Bitmask,
+    /// Blob id
+    Blob,
+    /// Handle of a drm object of an unspecified type
+    Object,
+    /// Handle of a crtc
+    Crtc,
+    /// Handle of a connector
+    Connector,
+    /// Handle of an encoder
+    Encoder,
+    /// Handle of a framebuffer
+    Framebuffer,
+    /// Handle of a plane
+    Plane,
+    /// Handle of a property
+    Property,
+}
+
+impl From<property::ValueType> for PropertyKind {
+    #[inline]
+    fn from(value_type: property::ValueType) -> Self {
+        match value_type {
+            property::ValueType::Unknown => PropertyKind::Unknown,
+            property::ValueType::Boolean => PropertyKind::Boolean,
+            property::ValueType::UnsignedRange(min, max) => PropertyKind::UnsignedRange { min, max },
+            property::ValueType::SignedRange(min, max) => PropertyKind::SignedRange { min, max },
+            property::ValueType::Enum(values) => {
+                let (_, values) = values.values();
+                PropertyKind::Enum(
+                    values
+                        .iter()
+                        .map(|value| PropertyEnumValue {
+                            name: value.name().to_string_lossy().into_owned(),
+                            value: value.value(),
+                        })
+                        .collect(),
+                )
+            }
+            property::ValueType::Bitmask => PropertyKind::Bitmask,
+            property::ValueType::Blob => PropertyKind::Blob,
+            property::ValueType::Object => PropertyKind::Object,
+            property::ValueType::CRTC => PropertyKind::Crtc,
+            property::ValueType::Connector => PropertyKind::Connector,
+            property::ValueType::Encoder => PropertyKind::Encoder,
+            property::ValueType::Framebuffer => PropertyKind::Framebuffer,
+            property::ValueType::Plane => PropertyKind::Plane,
+            property::ValueType::Property => PropertyKind::Property,
+        }
+    }
+}
+
+/// Description and current value of a property of a drm object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyInfo {
+    /// Handle of the property
+    pub handle: property::Handle,
+    /// Name of the property
+    pub name: String,
+    /// Type and valid values of the property
+    pub kind: PropertyKind,
+    /// Whether the property can be modified
+    pub mutable: bool,
+    /// Whether the property is only exposed to atomic clients
+    pub atomic: bool,
+    /// Raw current value of the property
+    pub value: property::RawValue,
+}
+
+impl PropertyInfo {
+    /// Returns the current value of a boolean property
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.kind {
+            PropertyKind::Boolean => Some(self.value != 0),
+            // A range from [0,1] will be interpreted as Boolean in drm-rs
+            PropertyKind::UnsignedRange { min: 0, max: 1 } => Some(self.value != 0),
+            _ => None,
+        }
+    }
+
+    /// Returns the current value of an unsigned or signed range property
+    ///
+    /// Unsigned values not fitting into an `i64` are returned as `None`.
+    pub fn as_range(&self) -> Option<i64> {
+        match self.kind {
+            PropertyKind::UnsignedRange { .. } => i64::try_from(self.value).ok(),
+            PropertyKind::SignedRange { .. } => Some(self.value as i64),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the current value of an enum property
+    pub fn as_enum(&self) -> Option<&str> {
+        match &self.kind {
+            PropertyKind::Enum(values) => values
+                .iter()
+                .find(|value| value.value == self.value)
+                .map(|value| &*value.name),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw value of the enum value named `name`, if the property is an enum
+    /// and supports the value.
+    pub fn enum_value(&self, name: &str) -> Option<property::RawValue> {
+        match &self.kind {
+            PropertyKind::Enum(values) => values
+                .iter()
+                .find(|value| value.name == name)
+                .map(|value| value.value),
+            _ => None,
+        }
+    }
+
+    /// Test if `value` is a valid value for this property
+    pub fn is_valid(&self, value: property::RawValue) -> bool {
+        match &self.kind {
+            PropertyKind::Boolean => value <= 1,
+            PropertyKind::UnsignedRange { min, max } => (*min..=*max).contains(&value),
+            PropertyKind::SignedRange { min, max } => (*min..=*max).contains(&(value as i64)),
+            PropertyKind::Enum(values) => values.iter().any(|v| v.value == value),
+            _ => true,
+        }
+    }
+}
+
+pub(super) fn properties<T: ResourceHandle>(
+    dev: &(impl ControlDevice + DevPath),
+    handle: T,
+) -> Result<Vec<PropertyInfo>, DrmError> {
+    let props = dev.get_properties(handle).map_err(|source| {
+        DrmError::Access(AccessError {
+            errmsg: "Failed to get properties of object",
+            dev: dev.dev_path(),
+            source,
+        })
+    })?;
+    let (ids, vals) = props.as_props_and_values();
+    ids.iter()
+        .zip(vals.iter())
+        .map(|(&id, &value)| {
+            let info = dev.get_property(id).map_err(|source| {
+                DrmError::Access(AccessError {
+                    errmsg: "Failed to get property info",
+                    dev: dev.dev_path(),
+                    source,
+                })
+            })?;
+            Ok(PropertyInfo {
+                handle: id,
+                name: info.name().to_string_lossy().into_owned(),
+                kind: info.value_type().into(),
+                mutable: info.mutable(),
+                atomic: info.atomic(),
+                value,
+            })
+        })
+        .collect()
+}
+
+pub(super) fn property<T: ResourceHandle>(
+    dev: &(impl ControlDevice + DevPath),
+    handle: T,
+    name: &str,
+) -> Result<Option<PropertyInfo>, DrmError> {
+    Ok(properties(dev, handle)?
+        .into_iter()
+        .find(|property| property.name == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use drm::control::property;
+
+    use super::{PropertyEnumValue, PropertyInfo, PropertyKind};
+
+    fn info(kind: PropertyKind, value: property::RawValue) -> PropertyInfo {
+        PropertyInfo {
+            handle: drm::control::from_u32(1).unwrap(),
+            name: String::from("test"),
+            kind,
+            mutable: true,
+            atomic: false,
+            value,
+        }
+    }
+
+    #[test]
+    fn boolean() {
+        let prop = info(PropertyKind::Boolean, 1);
+        assert_eq!(prop.as_bool(), Some(true));
+        assert_eq!(prop.as_range(), None);
+        assert!(prop.is_valid(0));
+        assert!(!prop.is_valid(2));
+
+        let prop = info(PropertyKind::UnsignedRange { min: 0, max: 1 }, 0);
+        assert_eq!(prop.as_bool(), Some(false));
+        assert_eq!(
+            info(PropertyKind::UnsignedRange { min: 0, max: 2 }, 0).as_bool(),
+            None
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        let prop = info(PropertyKind::UnsignedRange { min: 16, max: 32 }, 24);
+        assert_eq!(prop.as_range(), Some(24));
+        assert!(prop.is_valid(16));
+        assert!(prop.is_valid(32));
+        assert!(!prop.is_valid(15));
+        assert!(!prop.is_valid(33));
+        assert_eq!(
+            info(
+                PropertyKind::UnsignedRange {
+                    min: 0,
+                    max: u64::MAX
+                },
+                u64::MAX
+            )
+            .as_range(),
+            None
+        );
+
+        let prop = info(PropertyKind::SignedRange { min: -10, max: 10 }, -5i64 as u64);
+        assert_eq!(prop.as_range(), Some(-5));
+        assert!(prop.is_valid(-10i64 as u64));
+        assert!(!prop.is_valid(-11i64 as u64));
+        assert!(!prop.is_valid(11));
+    }
+
+    #[test]
+    fn enums() {
+        let values = vec![
+            PropertyEnumValue {
+                name: String::from("Off"),
+                value: 0,
+            },
+            PropertyEnumValue {
+                name: String::from("On"),
+                value: 4,
+            },
+        ];
+        let prop = info(PropertyKind::Enum(values), 4);
+        assert_eq!(prop.as_enum(), Some("On"));
+        assert_eq!(prop.enum_value("Off"), Some(0));
+        assert_eq!(prop.enum_value("Auto"), None);
+        assert!(prop.is_valid(0));
+        assert!(!prop.is_valid(1));
+        assert_eq!(prop.as_bool(), None);
+
+        let prop = info(PropertyKind::Blob, 4);
+        assert_eq!(prop.as_enum(), None);
+        assert_eq!(prop.enum_value("On"), None);
+        assert!(prop.is_valid(42));
+    }
+}
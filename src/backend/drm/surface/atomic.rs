@@ -24,6 +24,7 @@ use crate::{
             error::Error,
             plane_type, DrmDeviceFd,
         },
+        renderer::Color32F,
     },
     utils::DevPath,
 };
@@ -32,6 +33,16 @@ use tracing::{debug, info, info_span, instrument, trace, warn};
 
 use super::{PlaneConfig, PlaneState, VrrSupport};
 
+// value of the `link-status` connector property, see `DRM_MODE_LINK_STATUS_GOOD`
+const DRM_MODE_LINK_STATUS_GOOD: u64 = 0;
+
+// encodes a color in the 16 bit per channel format of the `BACKGROUND_COLOR` crtc property
+fn background_color_value(color: Color32F) -> u64 {
+    // the background is not blended with anything, so the premultiplied channels are the result
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u64;
+    (0xffff << 48) | (channel(color.r()) << 32) | (channel(color.g()) << 16) | channel(color.b())
+}
+
 #[derive(Debug, Clone)]
 pub struct State {
     pub active: bool,
@@ -167,6 +178,8 @@ pub struct AtomicDrmSurface {
     prop_mapping: Arc<RwLock<PropMapping>>,
     state: RwLock<State>,
     pending: RwLock<State>,
+    background_color: Mutex<Option<Color32F>>,
+    retrain_link: AtomicBool,
     pub(super) span: tracing::Span,
 }
 
@@ -214,6 +227,8 @@ impl AtomicDrmSurface {
             prop_mapping,
             state: RwLock::new(state),
             pending: RwLock::new(pending),
+            background_color: Mutex::new(None),
+            retrain_link: AtomicBool::new(false),
             span,
         };
 
@@ -648,8 +663,36 @@ impl AtomicDrmSurface {
         Ok(())
     }
 
+    pub fn background_color_supported(&self) -> bool {
+        self.prop_mapping
+            .read()
+            .unwrap()
+            .crtc_prop_handle(self.crtc, "BACKGROUND_COLOR")
+            .is_ok()
+    }
+
+    pub fn background_color(&self) -> Option<Color32F> {
+        *self.background_color.lock().unwrap()
+    }
+
+    pub fn set_background_color(&self, color: Option<Color32F>) -> Result<(), Error> {
+        if color.is_some() && !self.background_color_supported() {
+            return Err(Error::UnknownProperty {
+                handle: self.crtc.into(),
+                name: "BACKGROUND_COLOR",
+            });
+        }
+        *self.background_color.lock().unwrap() = color;
+        Ok(())
+    }
+
+    pub fn retrain_link(&self) {
+        self.retrain_link.store(true, Ordering::SeqCst);
+    }
+
     pub fn commit_pending(&self) -> bool {
         *self.pending.read().unwrap() != *self.state.read().unwrap()
+            || self.retrain_link.load(Ordering::SeqCst)
     }
 
     #[instrument(level = "trace", parent = &self.span, skip(self, planes))]
@@ -752,8 +795,10 @@ impl AtomicDrmSurface {
         trace!("Testing screen config");
 
         // test the new config and return the request if it would be accepted by the driver.
+        let retrain_link = self.retrain_link.load(Ordering::SeqCst);
+        let mut allow_modeset = true;
         let req = {
-            let req = self.build_request(
+            let mut req = self.build_request(
                 &mut added,
                 &mut removed,
                 &*planes,
@@ -761,32 +806,64 @@ impl AtomicDrmSurface {
                 pending.vrr,
             )?;
 
-            if let Err(err) = self.fd.atomic_commit(
+            if retrain_link {
+                // Link training only happens on a full modeset, userspace is expected to reset the status
+                info!("Retraining link");
+                let prop_mapping = self.prop_mapping.read().unwrap();
+                for conn in pending.connectors.iter() {
+                    if let Some(prop) = prop_mapping
+                        .connectors
+                        .get(conn)
+                        .and_then(|props| props.get("link-status"))
+                    {
+                        req.add_property(*conn, *prop, property::Value::Unknown(DRM_MODE_LINK_STATUS_GOOD));
+                    }
+                }
+            }
+
+            // Some changes, like switching between modes only differing in their refresh rate,
+            // can be applied by some drivers without a full modeset, which avoids blanking the output.
+            if !retrain_link
+                && self
+                    .fd
+                    .atomic_commit(AtomicCommitFlags::TEST_ONLY, req.clone())
+                    .is_ok()
+            {
+                if current.mode != pending.mode {
+                    debug!("Switching mode without modeset");
+                }
+                allow_modeset = false;
+            } else if let Err(err) = self.fd.atomic_commit(
                 AtomicCommitFlags::ALLOW_MODESET | AtomicCommitFlags::TEST_ONLY,
                 req.clone(),
             ) {
                 warn!("New screen configuration invalid!:\n\t{:#?}\n\t{}\n", req, err);
 
                 return Err(Error::TestFailed(self.crtc));
-            } else {
-                if current.mode != pending.mode {
-                    if let Err(err) = self.fd.destroy_property_blob(current.blob.into()) {
-                        warn!("Failed to destroy old mode property blob: {}", err);
-                    }
-                }
+            }
 
-                // new config
-                req
+            if current.mode != pending.mode {
+                if let Err(err) = self.fd.destroy_property_blob(current.blob.into()) {
+                    warn!("Failed to destroy old mode property blob: {}", err);
+                }
             }
+
+            // new config
+            req
         };
 
         debug!("Setting screen: {:?}", req);
+        let modeset_flags = if allow_modeset {
+            AtomicCommitFlags::ALLOW_MODESET
+        } else {
+            AtomicCommitFlags::empty()
+        };
         let result = self
             .fd
             .atomic_commit(
                 if event {
                     // on the atomic api we can modeset and trigger a page_flip event on the same call!
-                    AtomicCommitFlags::PAGE_FLIP_EVENT | AtomicCommitFlags::ALLOW_MODESET
+                    AtomicCommitFlags::PAGE_FLIP_EVENT | modeset_flags
                     // we also *should* not need to wait for completion, like with `set_crtc`,
                     // because we have tested this exact commit already, so we do not expect any errors later down the line.
                     //
@@ -796,7 +873,7 @@ impl AtomicDrmSurface {
                     // so we skip this flag:
                     // AtomicCommitFlags::Nonblock,
                 } else {
-                    AtomicCommitFlags::ALLOW_MODESET
+                    modeset_flags
                 },
                 req,
             )
@@ -810,6 +887,7 @@ impl AtomicDrmSurface {
 
         if result.is_ok() {
             *current = pending.clone();
+            self.retrain_link.store(false, Ordering::SeqCst);
             for plane in planes.iter() {
                 if plane.config.is_some() {
                     used_planes.insert(plane.handle);
@@ -828,6 +906,7 @@ impl AtomicDrmSurface {
         &self,
         planes: impl IntoIterator<Item = PlaneState<'a>>,
         event: bool,
+        async_flip: bool,
     ) -> Result<(), Error> {
         if !self.active.load(Ordering::SeqCst) {
             return Err(Error::DeviceInactive);
@@ -849,23 +928,20 @@ impl AtomicDrmSurface {
         // If we would set anything here, that would require a modeset, this would fail,
         // indicating a problem in our assumptions.
         trace!(?planes, "Queueing page flip: {:?}", req);
-        let res = self
-            .fd
-            .atomic_commit(
-                if event {
-                    AtomicCommitFlags::PAGE_FLIP_EVENT | AtomicCommitFlags::NONBLOCK
-                } else {
-                    AtomicCommitFlags::NONBLOCK
-                },
-                req,
-            )
-            .map_err(|source| {
-                Error::Access(AccessError {
-                    errmsg: "Page flip commit failed",
-                    dev: self.fd.dev_path(),
-                    source,
-                })
-            });
+        let mut flags = AtomicCommitFlags::NONBLOCK;
+        if event {
+            flags |= AtomicCommitFlags::PAGE_FLIP_EVENT;
+        }
+        if async_flip {
+            flags |= AtomicCommitFlags::PAGE_FLIP_ASYNC;
+        }
+        let res = self.fd.atomic_commit(flags, req).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Page flip commit failed",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        });
 
         if res.is_ok() {
             for plane in planes.iter() {
@@ -945,6 +1021,14 @@ impl AtomicDrmSurface {
             });
         }
 
+        if let Some(color) = *self.background_color.lock().unwrap() {
+            req.add_property(
+                self.crtc,
+                prop_mapping.crtc_prop_handle(self.crtc, "BACKGROUND_COLOR")?,
+                property::Value::UnsignedRange(background_color_value(color)),
+            );
+        }
+
         for plane_state in planes.into_iter() {
             let handle = &plane_state.handle;
 
@@ -1329,7 +1413,10 @@ impl From<Transform> for DrmRotation {
 #[cfg(test)]
 mod test {
     use crate::{
-        backend::drm::surface::atomic::to_fixed,
+        backend::{
+            drm::surface::atomic::{background_color_value, to_fixed},
+            renderer::Color32F,
+        },
         utils::{Physical, Rectangle},
     };
 
@@ -1355,4 +1442,22 @@ mod test {
         let fixed = to_fixed(geometry.size.w) as u64;
         assert_eq!(125835674, fixed);
     }
+
+    #[test]
+    fn test_background_color_value() {
+        assert_eq!(0xffff_0000_0000_0000, background_color_value(Color32F::BLACK));
+        assert_eq!(
+            u64::MAX,
+            background_color_value(Color32F::new(1.0, 1.0, 1.0, 1.0))
+        );
+        assert_eq!(
+            0xffff_ffff_8000_0000,
+            background_color_value(Color32F::new(1.0, 0.5, 0.0, 1.0))
+        );
+        // the alpha channel is always opaque and out of range values are clamped
+        assert_eq!(
+            0xffff_0000_ffff_0000,
+            background_color_value(Color32F::new(-1.0, 2.0, 0.0, 0.0))
+        );
+    }
 }
@@ -87,6 +87,7 @@ pub struct LegacyDrmSurface {
     state: RwLock<State>,
     pending: RwLock<State>,
     dpms: Mutex<bool>,
+    retrain_link: AtomicBool,
     pub(super) span: tracing::Span,
 }
 
@@ -116,6 +117,7 @@ impl LegacyDrmSurface {
             state: RwLock::new(state),
             pending: RwLock::new(pending),
             dpms: Mutex::new(true),
+            retrain_link: AtomicBool::new(false),
             span,
         };
 
@@ -222,8 +224,13 @@ impl LegacyDrmSurface {
         Ok(())
     }
 
+    pub fn retrain_link(&self) {
+        self.retrain_link.store(true, Ordering::SeqCst);
+    }
+
     pub fn commit_pending(&self) -> bool {
         *self.pending.read().unwrap() != *self.state.read().unwrap()
+            || self.retrain_link.load(Ordering::SeqCst)
     }
 
     #[instrument(level = "trace", parent = &self.span, skip(self))]
@@ -310,6 +317,8 @@ impl LegacyDrmSurface {
             })?;
 
         *current = pending.clone();
+        // `set_crtc` always does a full modeset, which retrains the link
+        self.retrain_link.store(false, Ordering::SeqCst);
 
         if event {
             // set crtc does not trigger page_flip events, so we immediately queue a flip
@@ -333,7 +342,12 @@ impl LegacyDrmSurface {
 
     #[instrument(level = "trace", parent = &self.span, skip(self))]
     #[profiling::function]
-    pub fn page_flip(&self, framebuffer: framebuffer::Handle, event: bool) -> Result<(), Error> {
+    pub fn page_flip(
+        &self,
+        framebuffer: framebuffer::Handle,
+        event: bool,
+        async_flip: bool,
+    ) -> Result<(), Error> {
         trace!("Queueing Page flip");
 
         if !self.active.load(Ordering::SeqCst) {
@@ -347,18 +361,14 @@ impl LegacyDrmSurface {
             *dpms = true;
         }
 
-        ControlDevice::page_flip(
-            &*self.fd,
-            self.crtc,
-            framebuffer,
-            if event {
-                PageFlipFlags::EVENT
-            } else {
-                PageFlipFlags::empty()
-            },
-            None,
-        )
-        .map_err(|source| {
+        let mut flags = PageFlipFlags::empty();
+        if event {
+            flags |= PageFlipFlags::EVENT;
+        }
+        if async_flip {
+            flags |= PageFlipFlags::ASYNC;
+        }
+        ControlDevice::page_flip(&*self.fd, self.crtc, framebuffer, flags, None).map_err(|source| {
             Error::Access(AccessError {
                 errmsg: "Failed to page flip",
                 dev: self.fd.dev_path(),
@@ -3,8 +3,8 @@ use std::os::unix::io::{AsFd, BorrowedFd};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-use drm::control::{connector, crtc, framebuffer, plane, Device as ControlDevice, Mode};
-use drm::Device as BasicDevice;
+use drm::control::{connector, crtc, framebuffer, plane, Device as ControlDevice, Mode, ResourceHandle};
+use drm::{Device as BasicDevice, DriverCapability};
 
 use libc::dev_t;
 
@@ -13,9 +13,10 @@ pub(super) mod atomic;
 pub(super) mod gbm;
 pub(super) mod legacy;
 use super::{
-    device::PlaneClaimStorage, error::Error, plane_type, DrmDeviceFd, PlaneClaim, PlaneInfo, PlaneType,
-    Planes,
+    device::PlaneClaimStorage, error::Error, plane_type, properties, DrmDeviceFd, PlaneClaim, PlaneInfo,
+    PlaneType, Planes, PropertyInfo,
 };
+use crate::backend::renderer::Color32F;
 use crate::utils::DevPath;
 use crate::utils::{Buffer, Physical, Point, Rectangle, Transform};
 use atomic::AtomicDrmSurface;
@@ -157,6 +158,17 @@ pub struct PlaneConfig<'a> {
     pub fence: Option<BorrowedFd<'a>>,
 }
 
+/// Status of the link between a connector and the display
+///
+/// See [`DrmSurface::link_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// The link is working
+    Good,
+    /// Link training failed, the display might not show any content
+    Bad,
+}
+
 /// VRR support state
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VrrSupport {
@@ -215,6 +227,29 @@ impl DrmSurface {
         &self.primary_plane.0
     }
 
+    /// Returns all properties of the underlying [`crtc`](drm::control::crtc) including their current values
+    pub fn crtc_properties(&self) -> Result<Vec<PropertyInfo>, Error> {
+        properties::properties(self.device_fd(), self.crtc)
+    }
+
+    /// Returns all properties of the underlying primary [`plane`](drm::control::plane)
+    /// including their current values
+    pub fn plane_properties(&self) -> Result<Vec<PropertyInfo>, Error> {
+        properties::properties(self.device_fd(), self.plane())
+    }
+
+    /// Returns all properties of a drm object (e.g. one of the connectors or planes of this surface)
+    /// including their current values
+    pub fn properties(&self, handle: impl ResourceHandle) -> Result<Vec<PropertyInfo>, Error> {
+        properties::properties(self.device_fd(), handle)
+    }
+
+    /// Returns the property named `name` of a drm object (e.g. one of the connectors or
+    /// planes of this surface) including its current value
+    pub fn property(&self, handle: impl ResourceHandle, name: &str) -> Result<Option<PropertyInfo>, Error> {
+        properties::property(self.device_fd(), handle, name)
+    }
+
     /// Currently used [`connector`](drm::control::connector)s of this surface
     pub fn current_connectors(&self) -> impl IntoIterator<Item = connector::Handle> {
         match &*self.internal {
@@ -297,6 +332,10 @@ impl DrmSurface {
     /// Fails if the mode is not compatible with the underlying
     /// [`crtc`](drm::control::crtc) or any of the
     /// pending [`connector`](drm::control::connector)s.
+    ///
+    /// On the atomic api the next commit is tested without a modeset first, so switching between
+    /// modes only differing in their refresh rate doesn't blank the output, if the driver supports it.
+    /// The legacy api always triggers a full modeset.
     pub fn use_mode(&self, mode: Mode) -> Result<(), Error> {
         match &*self.internal {
             DrmSurfaceInternal::Atomic(surf) => surf.use_mode(mode),
@@ -304,6 +343,33 @@ impl DrmSurface {
         }
     }
 
+    /// Returns the status of the link of the given connector
+    ///
+    /// The kernel flags the link as [`LinkStatus::Bad`] and sends a hotplug event, if link training
+    /// failed after a modeset or the link degraded while being used, e.g. with a marginal cable.
+    /// The display is likely to stay black until the link was retrained, see [`DrmSurface::retrain_link`].
+    ///
+    /// Connectors without a `link-status` property always report [`LinkStatus::Good`].
+    pub fn link_status(&self, conn: connector::Handle) -> Result<LinkStatus, Error> {
+        let status = self.property(conn, "link-status")?;
+        Ok(match status.as_ref().and_then(|prop| prop.as_enum()) {
+            Some("Bad") => LinkStatus::Bad,
+            _ => LinkStatus::Good,
+        })
+    }
+
+    /// Forces a full modeset with the next commit to retrain the link of all connectors
+    ///
+    /// The modeset uses the pending mode, which might be lowered using [`DrmSurface::use_mode`] beforehand,
+    /// if the link failed to train with the current one. This causes [`DrmSurface::commit_pending`]
+    /// to return `true`.
+    pub fn retrain_link(&self) {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.retrain_link(),
+            DrmSurfaceInternal::Legacy(surf) => surf.retrain_link(),
+        }
+    }
+
     /// Returns if Variable Refresh Rate is advertised as supported by the given connector.
     ///
     /// Note: This will always return [`VrrSupport::NotSupported`] if the underlying
@@ -341,6 +407,41 @@ impl DrmSurface {
         }
     }
 
+    /// Returns if the crtc supports a background color through the `BACKGROUND_COLOR` property.
+    ///
+    /// Note: This will always return `false` if the underlying implementation is using the
+    /// legacy DRM api.
+    pub fn background_color_supported(&self) -> bool {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.background_color_supported(),
+            DrmSurfaceInternal::Legacy(_) => false,
+        }
+    }
+
+    /// Returns the background color set for the next commits, if any.
+    pub fn background_color(&self) -> Option<Color32F> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.background_color(),
+            DrmSurfaceInternal::Legacy(_) => None,
+        }
+    }
+
+    /// Sets the color the crtc shows where no plane covers the output.
+    ///
+    /// The color is included in all following commits, page-flips and test commits.
+    /// `None` leaves the property untouched. Errors if the crtc does not
+    /// support a background color, see [`DrmSurface::background_color_supported`].
+    pub fn set_background_color(&self, color: Option<Color32F>) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.set_background_color(color),
+            DrmSurfaceInternal::Legacy(_) if color.is_none() => Ok(()),
+            DrmSurfaceInternal::Legacy(_) => Err(Error::UnknownProperty {
+                handle: self.crtc.into(),
+                name: "BACKGROUND_COLOR",
+            }),
+        }
+    }
+
     /// Disables the given plane.
     ///
     /// Errors if the plane is not supported by this crtc or if the underlying
@@ -358,6 +459,7 @@ impl DrmSurface {
     /// - [`add_connector`](DrmSurface::add_connector)
     /// - [`remove_connector`](DrmSurface::remove_connector)
     /// - [`use_mode`](DrmSurface::use_mode)
+    /// - [`retrain_link`](DrmSurface::retrain_link)
     pub fn commit_pending(&self) -> bool {
         match &*self.internal {
             DrmSurfaceInternal::Atomic(surf) => surf.commit_pending(),
@@ -431,10 +533,42 @@ impl DrmSurface {
         event: bool,
     ) -> Result<(), Error> {
         match &*self.internal {
-            DrmSurfaceInternal::Atomic(surf) => surf.page_flip(planes, event),
+            DrmSurfaceInternal::Atomic(surf) => surf.page_flip(planes, event, false),
+            DrmSurfaceInternal::Legacy(surf) => {
+                let fb = ensure_legacy_planes(self, planes)?;
+                surf.page_flip(fb, event, false)
+            }
+        }
+    }
+
+    /// Returns `true` if the driver supports asynchronous page-flips for this surface,
+    /// see [`DrmSurface::page_flip_async`]
+    pub fn async_page_flip_supported(&self) -> bool {
+        let cap = if self.is_legacy() {
+            DriverCapability::ASyncPageFlip
+        } else {
+            DriverCapability::AtomicASyncPageFlip
+        };
+        matches!(self.device_fd().get_driver_capability(cap), Ok(1))
+    }
+
+    /// Page-flip the underlying [`crtc`](drm::control::crtc) to a new given set of [`framebuffer`]s
+    /// without waiting for the next vblank.
+    ///
+    /// Like [`DrmSurface::page_flip`], but the flip is applied as soon as possible, which might cause tearing.
+    /// Drivers usually only allow to change the framebuffer of the primary plane
+    /// this way, otherwise the flip fails and has to be retried using [`DrmSurface::page_flip`].
+    #[profiling::function]
+    pub fn page_flip_async<'a>(
+        &self,
+        planes: impl IntoIterator<Item = PlaneState<'a>>,
+        event: bool,
+    ) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.page_flip(planes, event, true),
             DrmSurfaceInternal::Legacy(surf) => {
                 let fb = ensure_legacy_planes(self, planes)?;
-                surf.page_flip(fb, event)
+                surf.page_flip(fb, event, true)
             }
         }
     }
@@ -0,0 +1,222 @@
+//! Input latency measurement
+//!
+//! The [`InputLatencyTracker`] correlates the timestamps of input events with the presentation time
+//! of the first frame reflecting them, providing an estimate of the input-to-photon latency.
+//! This is useful to tune frame scheduling or to compare backends.
+//!
+//! Report input events changing the scene using [`InputLatencyTracker::input`]. When rendering a frame
+//! take an [`InputLatencyMarker`] and attach it to the frame, e.g. as part of the user data of
+//! [`DrmCompositor::queue_frame`](crate::backend::drm::compositor::DrmCompositor::queue_frame).
+//! Once the frame is presented, pass the marker back to [`InputLatencyTracker::presented`].
+//!
+//! The timestamps of input events and the presentation time have to use the same clock.
+//! Libinput and the presentation time of the drm backend both use the monotonic clock,
+//! [`event_time`] converts the timestamp of an event accordingly.
+
+use std::{collections::VecDeque, time::Duration};
+
+use crate::utils::{Monotonic, Time};
+
+use super::{Event, InputBackend};
+
+/// Returns the timestamp of an input event as a point in time of the monotonic clock
+///
+/// This is only meaningful for backends using the monotonic clock for their events, like libinput.
+pub fn event_time<B: InputBackend>(event: &impl Event<B>) -> Time<Monotonic> {
+    Time::from(Duration::from_micros(event.time()))
+}
+
+/// Input events reflected by a frame
+///
+/// Returned by [`InputLatencyTracker::take_marker`] to be attached to the rendered frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputLatencyMarker {
+    earliest: Time<Monotonic>,
+    latest: Time<Monotonic>,
+    events: usize,
+}
+
+impl InputLatencyMarker {
+    /// Returns the time of the earliest input event reflected by the frame
+    pub fn earliest(&self) -> Time<Monotonic> {
+        self.earliest
+    }
+
+    /// Returns the time of the latest input event reflected by the frame
+    pub fn latest(&self) -> Time<Monotonic> {
+        self.latest
+    }
+
+    /// Returns the number of input events reflected by the frame
+    pub fn events(&self) -> usize {
+        self.events
+    }
+
+    fn merge(&mut self, other: InputLatencyMarker) {
+        self.earliest = self.earliest.min(other.earliest);
+        self.latest = self.latest.max(other.latest);
+        self.events += other.events;
+    }
+}
+
+/// Input latency statistics
+///
+/// All values are calculated over the window configured in [`InputLatencyTracker::new`].
+/// The latency of a frame is measured from the earliest input event it reflects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputLatencyStats {
+    /// Number of presented frames reflecting input
+    pub frames: usize,
+    /// Latency of the most recently presented frame
+    pub latest: Option<Duration>,
+    /// Average latency
+    pub average: Option<Duration>,
+    /// Shortest latency
+    pub min: Option<Duration>,
+    /// Longest latency
+    pub max: Option<Duration>,
+}
+
+/// Tracks the latency from input events to the presentation of the frames reflecting them
+#[derive(Debug)]
+pub struct InputLatencyTracker {
+    window: Duration,
+    pending: Option<InputLatencyMarker>,
+    // presentation time and latency
+    samples: VecDeque<(Time<Monotonic>, Duration)>,
+}
+
+impl InputLatencyTracker {
+    /// Create a new tracker calculating statistics over the last `window`
+    pub fn new(window: Duration) -> Self {
+        InputLatencyTracker {
+            window,
+            pending: None,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Returns the window statistics are calculated over
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Set the window statistics are calculated over
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Report an input event at `time`, which will be reflected by the next rendered frame
+    ///
+    /// Events not causing any damage, like pointer motion over an unfocused area with
+    /// a hardware cursor, should not be reported to not distort the measurement.
+    pub fn input(&mut self, time: Time<Monotonic>) {
+        let marker = InputLatencyMarker {
+            earliest: time,
+            latest: time,
+            events: 1,
+        };
+        match self.pending.as_mut() {
+            Some(pending) => pending.merge(marker),
+            None => self.pending = Some(marker),
+        }
+    }
+
+    /// Returns if input events are waiting to be reflected by a frame
+    pub fn has_pending_input(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Take the marker for the frame currently being rendered
+    ///
+    /// Returns `None`, if no input was reported since the last frame.
+    pub fn take_marker(&mut self) -> Option<InputLatencyMarker> {
+        self.pending.take()
+    }
+
+    /// Report the presentation of a frame the marker was attached to
+    ///
+    /// Returns the latency of the frame.
+    pub fn presented(&mut self, marker: InputLatencyMarker, presented: Time<Monotonic>) -> Duration {
+        let latency = Time::elapsed(&marker.earliest, presented);
+        self.samples.push_back((presented, latency));
+        self.prune(presented);
+        latency
+    }
+
+    /// Report that a frame the marker was attached to was never presented
+    ///
+    /// The input events are carried over to the next rendered frame.
+    pub fn discarded(&mut self, marker: InputLatencyMarker) {
+        match self.pending.as_mut() {
+            Some(pending) => pending.merge(marker),
+            None => self.pending = Some(marker),
+        }
+    }
+
+    /// Returns the statistics of the frames presented within the window before `now`
+    pub fn stats(&mut self, now: Time<Monotonic>) -> InputLatencyStats {
+        self.prune(now);
+
+        let latencies = self.samples.iter().map(|(_, latency)| *latency);
+        InputLatencyStats {
+            frames: self.samples.len(),
+            latest: self.samples.back().map(|(_, latency)| *latency),
+            average: (!self.samples.is_empty())
+                .then(|| latencies.clone().sum::<Duration>() / self.samples.len() as u32),
+            min: latencies.clone().min(),
+            max: latencies.max(),
+        }
+    }
+
+    fn prune(&mut self, now: Time<Monotonic>) {
+        let window = self.window;
+        while self.samples.front().is_some_and(|(time, _)| *time + window < now) {
+            self.samples.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::InputLatencyTracker;
+    use crate::utils::{Monotonic, Time};
+
+    fn ms(ms: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn latency_of_earliest_event() {
+        let mut tracker = InputLatencyTracker::new(Duration::from_secs(1));
+        assert_eq!(tracker.take_marker(), None);
+
+        tracker.input(ms(100));
+        tracker.input(ms(105));
+        let marker = tracker.take_marker().unwrap();
+        assert_eq!(marker.events(), 2);
+        assert!(!tracker.has_pending_input());
+
+        // dropped frames carry their input over to the next frame
+        tracker.input(ms(110));
+        tracker.discarded(marker);
+        let marker = tracker.take_marker().unwrap();
+        assert_eq!(marker.earliest(), ms(100));
+        assert_eq!(marker.latest(), ms(110));
+        assert_eq!(tracker.presented(marker, ms(130)), Duration::from_millis(30));
+
+        tracker.input(ms(140));
+        let marker = tracker.take_marker().unwrap();
+        tracker.presented(marker, ms(150));
+
+        let stats = tracker.stats(ms(150));
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.latest, Some(Duration::from_millis(10)));
+        assert_eq!(stats.average, Some(Duration::from_millis(20)));
+        assert_eq!(stats.max, Some(Duration::from_millis(30)));
+
+        assert_eq!(tracker.stats(ms(1140)).frames, 1);
+    }
+}
@@ -4,6 +4,7 @@ use std::path::PathBuf;
 
 pub use xkbcommon::xkb::Keycode;
 
+pub mod latency;
 mod tablet;
 
 pub use tablet::{
@@ -56,14 +57,22 @@ pub trait Event<B: InputBackend> {
         (self.time() / 1000) as u32
     }
 
-    /// Timestamp in microseconds, with an undefined base.
+    /// Timestamp in microseconds of the [`Monotonic`](crate::utils::Monotonic) clock
+    ///
+    /// Timestamps of all backends are comparable with each other and with
+    /// [`Clock<Monotonic>::now`](crate::utils::Clock::now), e.g. to measure input latency.
     ///
     /// Libinput does not guarantee that timestamps always increase monotonically.
-    // # TODO:
-    // - check if events can even arrive out of order.
-    // - Make stronger time guarantees, if possible
     fn time(&self) -> u64;
 
+    /// Timestamp in microseconds as delivered by the backend
+    ///
+    /// The base and the resolution of this timestamp depend on the backend,
+    /// e.g. the X11 backend reports the server time in milliseconds.
+    fn raw_time(&self) -> u64 {
+        self.time()
+    }
+
     /// Returns the device, that generated this event
     fn device(&self) -> B::Device;
 }
@@ -143,22 +152,11 @@ pub enum MouseButton {
     Forward,
 }
 
-/// State of a button on a pointer device, like mouse or tablet tool. Either pressed or released
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-pub enum ButtonState {
-    /// Button is released
-    Released,
-    /// Button is pressed
-    Pressed,
-}
-
-/// Common methods pointer event generated by pressed buttons do implement
-pub trait PointerButtonEvent<B: InputBackend>: Event<B> {
-    /// Pressed button of the event.
+impl MouseButton {
+    /// Convert a button code as defined in `linux/input-event-codes.h` into a [`MouseButton`]
     ///
-    /// This may return [`None`] if the button pressed in the event is not a standard mouse button. You may
-    /// obtain the button code using [`PointerButtonEvent::button_code`].
-    fn button(&self) -> Option<MouseButton> {
+    /// Returns [`None`] if the code does not represent a standard mouse button.
+    pub fn from_button_code(code: u32) -> Option<MouseButton> {
         // These values are coming from <linux/input-event-codes.h>.
         const BTN_LEFT: u32 = 0x110;
         const BTN_RIGHT: u32 = 0x111;
@@ -168,7 +166,7 @@ pub trait PointerButtonEvent<B: InputBackend>: Event<B> {
         const BTN_FORWARD: u32 = 0x115;
         const BTN_BACK: u32 = 0x116;
 
-        match self.button_code() {
+        match code {
             BTN_LEFT => Some(MouseButton::Left),
             BTN_RIGHT => Some(MouseButton::Right),
             BTN_MIDDLE => Some(MouseButton::Middle),
@@ -177,6 +175,26 @@ pub trait PointerButtonEvent<B: InputBackend>: Event<B> {
             _ => None,
         }
     }
+}
+
+/// State of a button on a pointer device, like mouse or tablet tool. Either pressed or released
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ButtonState {
+    /// Button is released
+    Released,
+    /// Button is pressed
+    Pressed,
+}
+
+/// Common methods pointer event generated by pressed buttons do implement
+pub trait PointerButtonEvent<B: InputBackend>: Event<B> {
+    /// Pressed button of the event.
+    ///
+    /// This may return [`None`] if the button pressed in the event is not a standard mouse button. You may
+    /// obtain the button code using [`PointerButtonEvent::button_code`].
+    fn button(&self) -> Option<MouseButton> {
+        MouseButton::from_button_code(self.button_code())
+    }
 
     /// Returns the numerical button code of the mouse button.
     ///
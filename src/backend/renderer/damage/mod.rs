@@ -208,11 +208,14 @@ use tracing::{info_span, instrument, trace};
 use crate::{
     backend::renderer::{element::RenderElementPresentationState, Frame},
     output::{Output, OutputModeSource, OutputNoMode},
-    utils::{Buffer as BufferCoords, Physical, Rectangle, Scale, Size, Transform},
+    utils::{Buffer as BufferCoords, Physical, Point, Rectangle, Scale, Size, Transform},
 };
 
 use super::{
-    element::{Element, Id, RenderElement, RenderElementState, RenderElementStates},
+    element::{
+        utils::{Relocate, RelocateRenderElement},
+        Element, Id, RenderElement, RenderElementState, RenderElementStates,
+    },
     sync::SyncPoint,
     utils::CommitCounter,
     Bind, Color32F,
@@ -289,6 +292,8 @@ struct RendererState {
 #[derive(Debug)]
 pub struct OutputDamageTracker {
     mode: OutputModeSource,
+    // location of the rendered tile on the output, see `OutputDamageTracker::for_tile`
+    tile_offset: Point<i32, Physical>,
     last_state: RendererState,
     damage_shaper: DamageShaper,
     damage: Vec<Rectangle<i32, Physical>>,
@@ -297,6 +302,7 @@ pub struct OutputDamageTracker {
     opaque_regions_index: Vec<Range<usize>>,
     element_opaque_regions: Vec<Rectangle<i32, Physical>>,
     element_visible_area_workhouse: Vec<Rectangle<i32, Physical>>,
+    render_elements: Vec<usize>,
     span: tracing::Span,
 }
 
@@ -354,6 +360,7 @@ impl OutputDamageTracker {
                 scale: scale.into(),
                 transform,
             },
+            tile_offset: Point::default(),
             last_state: Default::default(),
             damage_shaper: Default::default(),
             damage: Default::default(),
@@ -362,6 +369,7 @@ impl OutputDamageTracker {
             opaque_regions_index: Default::default(),
             element_opaque_regions: Default::default(),
             element_visible_area_workhouse: Default::default(),
+            render_elements: Default::default(),
             span: info_span!("renderer_damage"),
         }
     }
@@ -374,6 +382,7 @@ impl OutputDamageTracker {
     pub fn from_output(output: &Output) -> Self {
         Self {
             mode: OutputModeSource::Auto(output.clone()),
+            tile_offset: Point::default(),
             damage_shaper: Default::default(),
             damage: Default::default(),
             element_damage: Default::default(),
@@ -381,6 +390,7 @@ impl OutputDamageTracker {
             opaque_regions_index: Default::default(),
             element_opaque_regions: Default::default(),
             element_visible_area_workhouse: Default::default(),
+            render_elements: Default::default(),
             last_state: Default::default(),
             span: info_span!("renderer_damage", output = output.name()),
         }
@@ -394,6 +404,7 @@ impl OutputDamageTracker {
     pub fn from_mode_source(output_mode_source: impl Into<OutputModeSource>) -> Self {
         Self {
             mode: output_mode_source.into(),
+            tile_offset: Point::default(),
             span: info_span!("render_damage"),
             damage_shaper: Default::default(),
             damage: Default::default(),
@@ -402,15 +413,50 @@ impl OutputDamageTracker {
             opaque_regions: Default::default(),
             opaque_regions_index: Default::default(),
             element_visible_area_workhouse: Default::default(),
+            render_elements: Default::default(),
             last_state: Default::default(),
         }
     }
 
+    /// Initialize a static [`OutputDamageTracker`] for a tile of an output
+    ///
+    /// Renders the part of the output covered by `tile` into a buffer of the size of the tile. This allows
+    /// to drive outputs exceeding the maximum render target size of a renderer with multiple buffers,
+    /// see [`output_tiles`]. Each tile needs its own damage tracker.
+    ///
+    /// - `size`, `scale` and `transform` describe the whole output
+    /// - `tile` is the area of the tile in the buffer of the whole output, i.e. before applying `transform`
+    ///
+    /// Elements are passed with their location on the whole output, the mode of the returned tracker
+    /// describes the tile.
+    pub fn for_tile(
+        size: impl Into<Size<i32, Physical>>,
+        scale: impl Into<Scale<f64>>,
+        transform: Transform,
+        tile: Rectangle<i32, Physical>,
+    ) -> Self {
+        let tile_offset = transform.transform_rect_in(tile, &size.into()).loc;
+        Self {
+            tile_offset,
+            ..Self::new(tile.size, scale, transform)
+        }
+    }
+
     /// Get the [`OutputModeSource`] of the [`OutputDamageTracker`]
     pub fn mode(&self) -> &OutputModeSource {
         &self.mode
     }
 
+    /// Forces the next render to damage the whole output
+    ///
+    /// The tracked element states and the damage history are dropped, so every element
+    /// is drawn again regardless of the buffer age. This can be used to recover from
+    /// rendering artifacts or after the contents of the buffers were modified
+    /// outside of the renderer.
+    pub fn damage_all(&mut self) {
+        self.last_state = Default::default();
+    }
+
     /// Render this output with the provided [`Renderer`] in the provided buffer
     ///
     /// - `elements` for this output in front-to-back order
@@ -452,6 +498,36 @@ impl OutputDamageTracker {
         self.render_output_internal(renderer, age, elements, clear_color.into(), |_| Ok(()))
     }
 
+    /// Render this output with the provided [`Renderer`] and store the damage in `damage`
+    ///
+    /// Works like [`OutputDamageTracker::render_output`], but lets the caller own the damage buffer,
+    /// so its allocation can be re-used across frames. The damage is computed in place, the previous
+    /// contents of `damage` are discarded and it is left empty if rendering was skipped.
+    ///
+    /// - `elements` for this output in front-to-back order
+    #[instrument(level = "trace", parent = &self.span, skip(renderer, elements, clear_color, damage))]
+    #[profiling::function]
+    pub fn render_output_into<E, R>(
+        &mut self,
+        renderer: &mut R,
+        age: usize,
+        elements: &[E],
+        clear_color: impl Into<Color32F>,
+        damage: &mut Vec<Rectangle<i32, Physical>>,
+    ) -> Result<(SyncPoint, RenderElementStates), Error<R::Error>>
+    where
+        E: RenderElement<R>,
+        R: Renderer,
+        <R as Renderer>::TextureId: Texture,
+    {
+        std::mem::swap(&mut self.damage, damage);
+        let result = self
+            .render_output_internal(renderer, age, elements, clear_color.into(), |_| Ok(()))
+            .map(|result| (result.sync, result.states));
+        std::mem::swap(&mut self.damage, damage);
+        result
+    }
+
     /// Damage this output and return the damage without actually rendering the difference
     ///
     /// - `elements` for this output in front-to-back order
@@ -476,16 +552,27 @@ impl OutputDamageTracker {
         // damage with the wrong size
         let output_geo = Rectangle::from_size(output_transform.transform_size(output_size));
 
-        let mut render_elements: Vec<&E> = Vec::with_capacity(elements.len());
-        let states = self.damage_output_internal(
-            age,
-            elements,
-            output_scale,
-            output_transform,
-            output_geo,
-            self.last_state.clear_color,
-            &mut render_elements,
-        );
+        let clear_color = self.last_state.clear_color;
+        let states = if self.tile_offset == Point::default() {
+            self.damage_output_internal(
+                age,
+                elements,
+                output_scale,
+                output_transform,
+                output_geo,
+                clear_color,
+            )
+        } else {
+            let elements = tile_elements(elements, self.tile_offset);
+            self.damage_output_internal(
+                age,
+                &elements,
+                output_scale,
+                output_transform,
+                output_geo,
+                clear_color,
+            )
+        };
 
         if self.damage.is_empty() {
             Ok((None, states))
@@ -494,17 +581,40 @@ impl OutputDamageTracker {
         }
     }
 
+    /// Damage this output and store the damage in `damage` without actually rendering the difference
+    ///
+    /// Works like [`OutputDamageTracker::damage_output`], but lets the caller own the damage buffer,
+    /// so its allocation can be re-used across frames. The damage is computed in place, the previous
+    /// contents of `damage` are discarded and it is left empty if nothing changed.
+    ///
+    /// - `elements` for this output in front-to-back order
+    #[instrument(level = "trace", parent = &self.span, skip(elements, damage))]
+    #[profiling::function]
+    pub fn damage_output_into<E>(
+        &mut self,
+        age: usize,
+        elements: &[E],
+        damage: &mut Vec<Rectangle<i32, Physical>>,
+    ) -> Result<RenderElementStates, OutputNoMode>
+    where
+        E: Element,
+    {
+        std::mem::swap(&mut self.damage, damage);
+        let result = self.damage_output(age, elements).map(|(_, states)| states);
+        std::mem::swap(&mut self.damage, damage);
+        result
+    }
+
     #[allow(clippy::too_many_arguments)]
     #[profiling::function]
-    fn damage_output_internal<'a, E>(
+    fn damage_output_internal<E>(
         &mut self,
         age: usize,
-        elements: &'a [E],
+        elements: &[E],
         output_scale: Scale<f64>,
         output_transform: Transform,
         output_geo: Rectangle<i32, Physical>,
         clear_color: Option<Color32F>,
-        render_elements: &mut Vec<&'a E>,
     ) -> RenderElementStates
     where
         E: Element,
@@ -512,6 +622,7 @@ impl OutputDamageTracker {
         self.damage.clear();
         self.opaque_regions.clear();
         self.opaque_regions_index.clear();
+        self.render_elements.clear();
 
         let mut element_render_states = RenderElementStates {
             states: HashMap::with_capacity(elements.len()),
@@ -521,7 +632,7 @@ impl OutputDamageTracker {
         let mut element_damage = std::mem::take(&mut self.element_damage);
 
         let mut element_visible_area_workhouse = std::mem::take(&mut self.element_visible_area_workhouse);
-        for element in elements.iter() {
+        for (index, element) in elements.iter().enumerate() {
             let element_id = element.id();
             let element_loc = element.geometry(output_scale).loc;
 
@@ -581,7 +692,7 @@ impl OutputDamageTracker {
             let element_opaque_regions_end_index = self.opaque_regions.len();
             self.opaque_regions_index
                 .push(element_opaque_regions_start_index..element_opaque_regions_end_index);
-            render_elements.push(element);
+            self.render_elements.push(index);
 
             if let Some(state) = element_render_states.states.get_mut(element_id) {
                 if matches!(state.presentation_state, RenderElementPresentationState::Skipped) {
@@ -620,7 +731,7 @@ impl OutputDamageTracker {
         }
 
         // if the element has been moved or it's alpha or z index changed, damage it
-        for (z_index, element) in render_elements.iter().enumerate() {
+        for (z_index, element) in self.render_elements.iter().map(|&idx| &elements[idx]).enumerate() {
             let element_src = element.src();
             let element_geometry = element.geometry(output_scale);
             let element_transform = element.transform();
@@ -681,12 +792,21 @@ impl OutputDamageTracker {
             self.damage.push(output_geo);
         }
 
-        // That is all completely new damage, which we need to store for subsequent renders
-        let mut new_damage = self.damage.clone();
-        new_damage.shrink_to_fit();
+        // That is all completely new damage, which we need to store for subsequent renders.
+        // Re-use the allocation of the oldest damage state, if it is truncated below anyway.
+        let use_old_damage = age > 0 && self.last_state.old_damage.len() >= age;
+        let retained_damage_states = if use_old_damage { age } else { MAX_AGE };
+        let mut new_damage = if self.last_state.old_damage.len() > retained_damage_states {
+            let mut damage = self.last_state.old_damage.pop_back().unwrap();
+            damage.clear();
+            damage
+        } else {
+            Vec::new()
+        };
+        new_damage.extend_from_slice(&self.damage);
 
         // We now add old damage states, if we have an age value
-        if age > 0 && self.last_state.old_damage.len() >= age {
+        if use_old_damage {
             trace!("age of {} recent enough, using old damage", age);
             // We do not need even older states anymore
             self.last_state.old_damage.truncate(age);
@@ -728,45 +848,46 @@ impl OutputDamageTracker {
 
         let mut new_elements_state = std::mem::take(&mut self.last_state.elements);
         new_elements_state.clear();
-        new_elements_state.reserve(render_elements.len());
-        let new_elements_state =
-            render_elements
-                .iter()
-                .enumerate()
-                .fold(new_elements_state, |mut map, (z_index, elem)| {
-                    let id = elem.id();
-                    let elem_src = elem.src();
-                    let elem_alpha = elem.alpha();
-                    let elem_geometry = elem.geometry(output_scale);
-                    let elem_transform = elem.transform();
-
-                    if let Some(state) = map.get_mut(id) {
-                        state.last_instances.push(ElementInstanceState {
-                            last_src: elem_src,
-                            last_geometry: elem_geometry,
-                            last_transform: elem_transform,
-                            last_alpha: elem_alpha,
-                            last_z_index: z_index,
-                        });
-                    } else {
-                        let current_commit = elem.current_commit();
-                        map.insert(
-                            id.clone(),
-                            ElementState {
-                                last_commit: current_commit,
-                                last_instances: smallvec![ElementInstanceState {
-                                    last_src: elem_src,
-                                    last_geometry: elem_geometry,
-                                    last_transform: elem_transform,
-                                    last_alpha: elem_alpha,
-                                    last_z_index: z_index,
-                                }],
-                            },
-                        );
-                    }
-
-                    map
-                });
+        new_elements_state.reserve(self.render_elements.len());
+        let new_elements_state = self
+            .render_elements
+            .iter()
+            .map(|&idx| &elements[idx])
+            .enumerate()
+            .fold(new_elements_state, |mut map, (z_index, elem)| {
+                let id = elem.id();
+                let elem_src = elem.src();
+                let elem_alpha = elem.alpha();
+                let elem_geometry = elem.geometry(output_scale);
+                let elem_transform = elem.transform();
+
+                if let Some(state) = map.get_mut(id) {
+                    state.last_instances.push(ElementInstanceState {
+                        last_src: elem_src,
+                        last_geometry: elem_geometry,
+                        last_transform: elem_transform,
+                        last_alpha: elem_alpha,
+                        last_z_index: z_index,
+                    });
+                } else {
+                    let current_commit = elem.current_commit();
+                    map.insert(
+                        id.clone(),
+                        ElementState {
+                            last_commit: current_commit,
+                            last_instances: smallvec![ElementInstanceState {
+                                last_src: elem_src,
+                                last_geometry: elem_geometry,
+                                last_transform: elem_transform,
+                                last_alpha: elem_alpha,
+                                last_z_index: z_index,
+                            }],
+                        },
+                    );
+                }
+
+                map
+            });
 
         self.last_state.size = Some(output_geo.size);
         self.last_state.transform = Some(output_transform);
@@ -776,14 +897,35 @@ impl OutputDamageTracker {
         self.last_state
             .opaque_regions
             .extend(self.opaque_regions.iter().copied());
-        self.last_state.opaque_regions.shrink_to_fit();
         self.last_state.clear_color = clear_color;
 
         element_render_states
     }
 
+    fn render_output_internal<'a, E, R, F>(
+        &'a mut self,
+        renderer: &mut R,
+        age: usize,
+        elements: &[E],
+        clear_color: Color32F,
+        pre_render: F,
+    ) -> Result<RenderOutputResult<'a>, Error<R::Error>>
+    where
+        E: RenderElement<R>,
+        R: Renderer,
+        <R as Renderer>::TextureId: Texture,
+        F: FnOnce(&mut R) -> Result<(), <R as Renderer>::Error>,
+    {
+        if self.tile_offset == Point::default() {
+            self.render_elements_internal(renderer, age, elements, clear_color, pre_render)
+        } else {
+            let elements = tile_elements(elements, self.tile_offset);
+            self.render_elements_internal(renderer, age, &elements, clear_color, pre_render)
+        }
+    }
+
     #[profiling::function]
-    fn render_output_internal<'a, 'e, E, R, F>(
+    fn render_elements_internal<'a, 'e, E, R, F>(
         &'a mut self,
         renderer: &mut R,
         age: usize,
@@ -810,7 +952,6 @@ impl OutputDamageTracker {
         let output_geo = Rectangle::from_size(output_transform.transform_size(output_size));
 
         // This will hold all the damage we need for this rendering step
-        let mut render_elements: Vec<&E> = Vec::with_capacity(elements.len());
         let states = self.damage_output_internal(
             age,
             elements,
@@ -818,7 +959,6 @@ impl OutputDamageTracker {
             output_transform,
             output_geo,
             Some(clear_color),
-            &mut render_elements,
         );
 
         if self.damage.is_empty() {
@@ -848,7 +988,13 @@ impl OutputDamageTracker {
             trace!("clearing damage {:?}", element_damage);
             frame.clear(clear_color, &element_damage)?;
 
-            for (z_index, element) in render_elements.iter().rev().enumerate() {
+            for (z_index, element) in self
+                .render_elements
+                .iter()
+                .rev()
+                .map(|&idx| &elements[idx])
+                .enumerate()
+            {
                 let element_id = element.id();
                 let element_geometry = element.geometry(output_scale);
 
@@ -928,3 +1074,170 @@ impl OutputDamageTracker {
         }
     }
 }
+
+/// Splits an output of `size` into tiles not exceeding `max_size`
+///
+/// The tiles are returned row by row and cover the output without overlapping.
+/// The last tile of every row and column is smaller, if `size` is not a multiple of `max_size`.
+/// Returns a single tile, if the output does not exceed `max_size` or `max_size` is empty.
+///
+/// See [`OutputDamageTracker::for_tile`] to render the tiles.
+pub fn output_tiles(
+    size: Size<i32, Physical>,
+    max_size: Size<i32, Physical>,
+) -> Vec<Rectangle<i32, Physical>> {
+    if max_size.w <= 0 || max_size.h <= 0 {
+        return vec![Rectangle::from_size(size)];
+    }
+
+    let mut tiles = Vec::new();
+    for y in (0..size.h.max(1)).step_by(max_size.h as usize) {
+        for x in (0..size.w.max(1)).step_by(max_size.w as usize) {
+            tiles.push(Rectangle::new(
+                (x, y).into(),
+                (max_size.w.min(size.w - x), max_size.h.min(size.h - y)).into(),
+            ));
+        }
+    }
+    tiles
+}
+
+// Moves the elements from the output into the coordinate space of a tile at `tile_offset`
+fn tile_elements<E: Element>(
+    elements: &[E],
+    tile_offset: Point<i32, Physical>,
+) -> Vec<RelocateRenderElement<&E>> {
+    elements
+        .iter()
+        .map(|element| {
+            RelocateRenderElement::from_element(element, (-tile_offset.x, -tile_offset.y), Relocate::Relative)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{output_tiles, OutputDamageTracker};
+    use crate::{
+        backend::renderer::element::{
+            solid::{SolidColorBuffer, SolidColorRenderElement},
+            Kind,
+        },
+        utils::{Physical, Rectangle, Size, Transform},
+    };
+
+    #[test]
+    fn tiles_cover_output() {
+        let size = Size::<i32, Physical>::from((300, 100));
+        assert_eq!(
+            output_tiles(size, (300, 100).into()),
+            vec![Rectangle::from_size(size)]
+        );
+        assert_eq!(
+            output_tiles(size, (0, 0).into()),
+            vec![Rectangle::from_size(size)]
+        );
+
+        let tiles = output_tiles(size, (128, 64).into());
+        assert_eq!(
+            tiles,
+            vec![
+                Rectangle::new((0, 0).into(), (128, 64).into()),
+                Rectangle::new((128, 0).into(), (128, 64).into()),
+                Rectangle::new((256, 0).into(), (44, 64).into()),
+                Rectangle::new((0, 64).into(), (128, 36).into()),
+                Rectangle::new((128, 64).into(), (128, 36).into()),
+                Rectangle::new((256, 64).into(), (44, 36).into()),
+            ]
+        );
+    }
+
+    fn tile_damage(
+        size: Size<i32, Physical>,
+        transform: Transform,
+        tile: Rectangle<i32, Physical>,
+        element: &SolidColorRenderElement,
+    ) -> Vec<Rectangle<i32, Physical>> {
+        let mut damage_tracker = OutputDamageTracker::for_tile(size, 1.0, transform, tile);
+        damage_tracker
+            .damage_output(1, &[] as &[SolidColorRenderElement])
+            .unwrap();
+        let (damage, _) = damage_tracker
+            .damage_output(1, std::slice::from_ref(element))
+            .unwrap();
+        damage.cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn tile_damage_tracking() {
+        let buffer = SolidColorBuffer::new((50, 20), [1.0, 0.0, 0.0, 1.0]);
+        let element = SolidColorRenderElement::from_buffer(&buffer, (100, 10), 1.0, 1.0, Kind::Unspecified);
+
+        let size = Size::from((200, 100));
+        let tiles = output_tiles(size, (128, 128).into());
+        assert_eq!(
+            tile_damage(size, Transform::Normal, tiles[0], &element),
+            vec![Rectangle::new((100, 10).into(), (28, 20).into())]
+        );
+        assert_eq!(
+            tile_damage(size, Transform::Normal, tiles[1], &element),
+            vec![Rectangle::new((0, 10).into(), (22, 20).into())]
+        );
+
+        // the buffer of a rotated output is split along the other axis of the elements
+        let size = Size::from((100, 200));
+        let tiles = output_tiles(size, (128, 128).into());
+        assert_eq!(
+            tile_damage(size, Transform::_90, tiles[0], &element),
+            vec![Rectangle::new((28, 10).into(), (50, 20).into())]
+        );
+        assert!(tile_damage(size, Transform::_90, tiles[1], &element).is_empty());
+    }
+
+    #[test]
+    fn damage_into_caller_buffer() {
+        let buffer = SolidColorBuffer::new((50, 20), [1.0, 0.0, 0.0, 1.0]);
+        let element = SolidColorRenderElement::from_buffer(&buffer, (10, 10), 1.0, 1.0, Kind::Unspecified);
+        let mut damage_tracker = OutputDamageTracker::new((100, 100), 1.0, Transform::Normal);
+
+        // previous contents are discarded
+        let mut damage = vec![Rectangle::from_size((1, 1).into())];
+        damage_tracker
+            .damage_output_into(1, &[] as &[SolidColorRenderElement], &mut damage)
+            .unwrap();
+        // the first frame damages the whole output
+        assert_eq!(damage, vec![Rectangle::from_size((100, 100).into())]);
+
+        damage_tracker
+            .damage_output_into(1, std::slice::from_ref(&element), &mut damage)
+            .unwrap();
+        assert_eq!(damage, vec![Rectangle::new((10, 10).into(), (50, 20).into())]);
+
+        // nothing changed
+        damage_tracker
+            .damage_output_into(1, std::slice::from_ref(&element), &mut damage)
+            .unwrap();
+        assert!(damage.is_empty());
+    }
+    #[test]
+    fn damage_all() {
+        let buffer = SolidColorBuffer::new((50, 20), [1.0, 0.0, 0.0, 1.0]);
+        let element = SolidColorRenderElement::from_buffer(&buffer, (10, 10), 1.0, 1.0, Kind::Unspecified);
+        let mut damage_tracker = OutputDamageTracker::new((100, 100), 1.0, Transform::Normal);
+        let mut damage = Vec::new();
+
+        damage_tracker
+            .damage_output_into(1, std::slice::from_ref(&element), &mut damage)
+            .unwrap();
+        damage_tracker
+            .damage_output_into(1, std::slice::from_ref(&element), &mut damage)
+            .unwrap();
+        assert!(damage.is_empty());
+
+        damage_tracker.damage_all();
+        damage_tracker
+            .damage_output_into(1, std::slice::from_ref(&element), &mut damage)
+            .unwrap();
+        assert_eq!(damage, vec![Rectangle::from_size((100, 100).into())]);
+    }
+}
@@ -0,0 +1,407 @@
+//! Element caching a group of static elements in a texture
+//!
+//! Elements like wallpapers or panels rarely change, but still have to be drawn every time
+//! a window on top of them damages the output. A [`CompositeLayer`] renders such a group of elements
+//! into an offscreen texture using its own [`OutputDamageTracker`], and provides a single
+//! [`CompositeLayerElement`] to be rendered in their place. The texture is only updated in the regions
+//! damaged by any member of the group, so drawing the group below damage costs a single texture blit
+//! instead of drawing every element again.
+//!
+//! A layer is meant to be kept per output and covers the whole output.
+//!
+//! # Why use this implementation
+//!
+//! Rendering the cached elements in place of the original ones is only worth it, if the group
+//! consists of multiple or expensive elements and changes rarely. Elements changing every frame should
+//! be rendered directly, as they would otherwise be drawn twice.
+//!
+//! # How to use it
+//!
+//! ```no_run
+//! # use smithay::{
+//! #     backend::renderer::{
+//! #         element::{composite::CompositeLayer, solid::SolidColorRenderElement, Id, Kind},
+//! #         gles::{GlesRenderer, GlesTexture},
+//! #         utils::CommitCounter,
+//! #     },
+//! #     utils::{Rectangle, Size},
+//! # };
+//! # let mut renderer: GlesRenderer = todo!();
+//! # let size = Size::from((1920, 1080));
+//! # let wallpaper = SolidColorRenderElement::new(Id::new(), Rectangle::from_size(size), CommitCounter::default(), [0.0, 0.0, 0.0, 1.0], Kind::Unspecified);
+//! let mut layer = CompositeLayer::<GlesTexture>::new();
+//!
+//! // each frame, before binding the target of the output
+//! let background = layer
+//!     .render_element(&mut renderer, size, 1.0, &[wallpaper], Kind::Unspecified)
+//!     .expect("Failed to render the composite layer");
+//! // render `background` below the windows of the output
+//! ```
+
+use tracing::{instrument, warn};
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::{Error as DamageError, OutputDamageTracker},
+            sync::SyncPoint,
+            utils::{DamageBag, DamageSet, DamageSnapshot},
+            Frame, Offscreen, Renderer, Texture,
+        },
+    },
+    utils::{Buffer, Physical, Rectangle, Scale, Size, Transform},
+};
+
+use super::{CommitCounter, Element, Id, Kind, RenderElement, RenderElementStates};
+
+/// Offscreen texture caching the composite of a group of elements
+#[derive(Debug)]
+pub struct CompositeLayer<T> {
+    id: Id,
+    format: Fourcc,
+    target: Option<CompositeTarget<T>>,
+    damage: DamageBag<i32, Physical>,
+    states: RenderElementStates,
+}
+
+#[derive(Debug)]
+struct CompositeTarget<T> {
+    renderer_id: usize,
+    texture: T,
+    scale: Scale<f64>,
+    damage_tracker: OutputDamageTracker,
+}
+
+impl<T> Default for CompositeLayer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> CompositeLayer<T> {
+    /// Create a new layer using a [`Fourcc::Abgr8888`] texture
+    pub fn new() -> Self {
+        CompositeLayer {
+            id: Id::new(),
+            format: Fourcc::Abgr8888,
+            target: None,
+            damage: DamageBag::default(),
+            states: RenderElementStates::default(),
+        }
+    }
+
+    /// Set the format of the texture
+    ///
+    /// The format has to support an alpha channel, if the elements do not cover the whole output.
+    pub fn with_format(mut self, format: Fourcc) -> Self {
+        self.format = format;
+        self.target = None;
+        self
+    }
+
+    /// Drop the texture, e.g. when the output is disabled
+    ///
+    /// The next call to [`CompositeLayer::render_element`] draws all elements again.
+    pub fn reset(&mut self) {
+        self.target = None;
+    }
+
+    /// Returns the states of the elements of the last update
+    ///
+    /// Can be used to update the primary scan-out output of surfaces drawn into the layer.
+    pub fn render_element_states(&self) -> &RenderElementStates {
+        &self.states
+    }
+}
+
+impl<T: Texture + Clone> CompositeLayer<T> {
+    /// Update the texture with the given elements and return an element to render it
+    ///
+    /// - `size` and `scale` have to match the output the returned element is rendered on
+    /// - `elements` in front-to-back order and relative to the output
+    ///
+    /// Only the regions damaged by the elements since the last call are drawn. The texture gets bound
+    /// for rendering, so this has to be called before binding the target of the output.
+    #[instrument(level = "trace", skip(self, renderer, elements))]
+    #[profiling::function]
+    pub fn render_element<R, E>(
+        &mut self,
+        renderer: &mut R,
+        size: impl Into<Size<i32, Physical>> + std::fmt::Debug,
+        scale: impl Into<Scale<f64>> + std::fmt::Debug,
+        elements: &[E],
+        kind: Kind,
+    ) -> Result<CompositeLayerElement<T>, DamageError<R::Error>>
+    where
+        R: Renderer<TextureId = T> + Offscreen<T>,
+        E: RenderElement<R>,
+    {
+        let size = size.into();
+        let scale = scale.into();
+
+        let outdated = self.target.as_ref().map_or(true, |target| {
+            target.renderer_id != renderer.id()
+                || target.texture.size() != Size::<i32, Buffer>::from((size.w, size.h))
+                || target.scale != scale
+        });
+        let age = if outdated {
+            let texture = renderer
+                .create_buffer(self.format, (size.w, size.h).into())
+                .map_err(DamageError::Rendering)?;
+            self.target = Some(CompositeTarget {
+                renderer_id: renderer.id(),
+                texture,
+                scale,
+                damage_tracker: OutputDamageTracker::new(size, scale, Transform::Normal),
+            });
+            // the damage tracker reports the whole texture as damaged
+            0
+        } else {
+            1
+        };
+
+        let target = self.target.as_mut().unwrap();
+        let result = target.damage_tracker.render_output_with(
+            renderer,
+            target.texture.clone(),
+            age,
+            elements,
+            [0.0, 0.0, 0.0, 0.0].into(),
+        )?;
+        if let Some(damage) = result.damage {
+            self.damage.add(damage.iter().copied());
+        }
+        let sync = result.sync;
+        self.states = result.states;
+        if let Err(err) = renderer.unbind() {
+            warn!(?err, "Failed to unbind composite layer texture");
+        }
+
+        Ok(CompositeLayerElement {
+            id: self.id.clone(),
+            renderer_id: target.renderer_id,
+            texture: target.texture.clone(),
+            size,
+            snapshot: self.damage.snapshot(),
+            sync,
+            alpha: 1.0,
+            kind,
+        })
+    }
+}
+
+/// Element rendering the texture of a [`CompositeLayer`]
+#[derive(Debug, Clone)]
+pub struct CompositeLayerElement<T> {
+    id: Id,
+    renderer_id: usize,
+    texture: T,
+    size: Size<i32, Physical>,
+    snapshot: DamageSnapshot<i32, Physical>,
+    sync: SyncPoint,
+    alpha: f32,
+    kind: Kind,
+}
+
+impl<T> CompositeLayerElement<T> {
+    /// Set the alpha the texture is rendered with
+    pub fn with_alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+}
+
+impl<T: Texture> Element for CompositeLayerElement<T> {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.snapshot.current_commit()
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.texture.size()).to_f64()
+    }
+
+    fn geometry(&self, _scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        Rectangle::from_size(self.size)
+    }
+
+    fn damage_since(&self, _scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        self.snapshot
+            .damage_since(commit)
+            .unwrap_or_else(|| DamageSet::from_slice(&[Rectangle::from_size(self.size)]))
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl<R, T> RenderElement<R> for CompositeLayerElement<T>
+where
+    R: Renderer<TextureId = T>,
+    T: Texture,
+{
+    #[instrument(level = "trace", skip(self, frame))]
+    #[profiling::function]
+    fn draw(
+        &self,
+        frame: &mut <R as Renderer>::Frame<'_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), <R as Renderer>::Error> {
+        if frame.id() != self.renderer_id {
+            warn!("trying to render composite layer from different renderer");
+            return Ok(());
+        }
+
+        frame.wait(&self.sync)?;
+        frame.render_texture_from_to(
+            &self.texture,
+            src,
+            dst,
+            damage,
+            opaque_regions,
+            Transform::Normal,
+            self.alpha,
+        )
+    }
+}
+
+#[cfg(all(test, feature = "renderer_test"))]
+mod tests {
+    use super::CompositeLayer;
+    use crate::{
+        backend::renderer::{
+            element::{solid::SolidColorRenderElement, Element, Id, Kind},
+            test::{DummyRenderer, DummyTexture},
+            utils::CommitCounter,
+        },
+        utils::{Physical, Rectangle, Scale},
+    };
+
+    fn solid(id: &Id, commit: CommitCounter) -> SolidColorRenderElement {
+        SolidColorRenderElement::new(
+            id.clone(),
+            Rectangle::new((10, 10).into(), (20, 20).into()),
+            commit,
+            [1.0, 0.0, 0.0, 1.0],
+            Kind::Unspecified,
+        )
+    }
+
+    #[test]
+    fn damage_of_members() {
+        let mut renderer = DummyRenderer::new();
+        let mut layer = CompositeLayer::<DummyTexture>::new();
+        let id = Id::new();
+        let mut commit = CommitCounter::default();
+        let scale = Scale::from(1.0);
+        let full = Rectangle::<i32, Physical>::from_size((200, 100).into());
+
+        let element = layer
+            .render_element(
+                &mut renderer,
+                (200, 100),
+                1.0,
+                &[solid(&id, commit)],
+                Kind::Unspecified,
+            )
+            .unwrap();
+        assert!(layer.render_element_states().element_was_presented(id.clone()));
+        assert_eq!(element.geometry(scale), full);
+        assert_eq!(element.damage_since(scale, None).to_vec(), vec![full]);
+        let first = element.current_commit();
+
+        // unchanged members keep the texture
+        let element = layer
+            .render_element(
+                &mut renderer,
+                (200, 100),
+                1.0,
+                &[solid(&id, commit)],
+                Kind::Unspecified,
+            )
+            .unwrap();
+        assert_eq!(element.current_commit(), first);
+        assert!(element.damage_since(scale, Some(first)).is_empty());
+
+        commit.increment();
+        let element = layer
+            .render_element(
+                &mut renderer,
+                (200, 100),
+                1.0,
+                &[solid(&id, commit)],
+                Kind::Unspecified,
+            )
+            .unwrap()
+            .with_alpha(0.5);
+        assert_ne!(element.current_commit(), first);
+        assert_eq!(
+            element.damage_since(scale, Some(first)).to_vec(),
+            vec![Rectangle::new((10, 10).into(), (20, 20).into())]
+        );
+        assert_eq!(element.alpha(), 0.5);
+    }
+
+    #[test]
+    fn recreated_texture_damages_everything() {
+        let mut renderer = DummyRenderer::new();
+        let mut layer = CompositeLayer::<DummyTexture>::new();
+        let id = Id::new();
+        let commit = CommitCounter::default();
+        let scale = Scale::from(1.0);
+
+        let element = layer
+            .render_element(
+                &mut renderer,
+                (200, 100),
+                1.0,
+                &[solid(&id, commit)],
+                Kind::Unspecified,
+            )
+            .unwrap();
+        let first = element.current_commit();
+
+        // a new output size
+        let element = layer
+            .render_element(
+                &mut renderer,
+                (300, 100),
+                1.0,
+                &[solid(&id, commit)],
+                Kind::Unspecified,
+            )
+            .unwrap();
+        assert_eq!(
+            element.damage_since(scale, Some(first)).to_vec(),
+            vec![Rectangle::from_size((300, 100).into())]
+        );
+        let second = element.current_commit();
+
+        layer.reset();
+        let element = layer
+            .render_element(
+                &mut renderer,
+                (300, 100),
+                1.0,
+                &[solid(&id, commit)],
+                Kind::Unspecified,
+            )
+            .unwrap();
+        assert_eq!(
+            element.damage_since(scale, Some(second)).to_vec(),
+            vec![Rectangle::from_size((300, 100).into())]
+        );
+    }
+}
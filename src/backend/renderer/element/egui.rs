@@ -0,0 +1,757 @@
+//! Integration of the [`egui`] toolkit
+//!
+//! [`EguiWidget`] runs an [`egui::Context`] as a [`PaintWidget`] and [`Widget`], so it can be rendered
+//! with a [`WidgetElement`] and receive input through a [`WidgetFocus`](crate::input::widget::WidgetFocus)
+//! like any other compositor-internal widget. This is meant for settings dialogs, HUDs and similar
+//! user interfaces drawn by the compositor itself.
+//!
+//! The output of egui is rasterized in software into the memory of the element, so it works with every
+//! [`ImportMem`](crate::backend::renderer::ImportMem) renderer. egui only runs, if the widget received
+//! input or requested a repaint, and only the primitives, which changed since the last frame, are drawn
+//! again and reported as damage.
+//!
+//! ```no_run
+//! # use smithay::{
+//! #     backend::renderer::{element::{egui::EguiWidget, widget::WidgetElement, Kind}, ImportMem, Renderer},
+//! # };
+//! # fn render<R: Renderer + ImportMem>(renderer: &mut R) where R::TextureId: Send + Clone + 'static {
+//! let mut counter = 0;
+//! let mut settings = WidgetElement::new(
+//!     EguiWidget::new((300, 200), move |ctx| {
+//!         egui::CentralPanel::default().show(ctx, |ui| {
+//!             if ui.button("Click me").clicked() {
+//!                 counter += 1;
+//!             }
+//!             ui.label(format!("Clicked {} times", counter));
+//!         });
+//!     }),
+//!     1,
+//! );
+//!
+//! // each frame
+//! let element = settings.render_element(renderer, (0.0, 0.0), 1.0, Kind::Unspecified).unwrap();
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use egui::{
+    epaint::{ClippedPrimitive, ImageData, Primitive, Vertex},
+    Color32, Context, Event, Key, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput, Rect, TextureId,
+    Vec2, ViewportId,
+};
+use tracing::warn;
+
+use crate::{
+    backend::input::{AxisSource, ButtonState, KeyState, MouseButton},
+    input::{
+        widget::{NamedKey, Widget, WidgetEvent, WidgetKey},
+        Seat, SeatHandler,
+    },
+    utils::{Buffer, Logical, Rectangle, Size},
+};
+
+use super::widget::{PaintWidget, WidgetCanvas};
+
+#[cfg(doc)]
+use super::widget::WidgetElement;
+
+// damage is reported as a single rectangle, once more primitives than this changed
+const MAX_DAMAGE_RECTS: usize = 16;
+
+/// A [`PaintWidget`] and [`Widget`] running an [`egui::Context`]
+///
+/// `ui` is called every time egui runs, to build the user interface.
+pub struct EguiWidget<F> {
+    ctx: Context,
+    ui: F,
+    size: Size<i32, Logical>,
+    events: Vec<Event>,
+    modifiers: Modifiers,
+    pointer: Option<Pos2>,
+    focused: bool,
+    start: Instant,
+    repaint_at: Option<Instant>,
+    textures: HashMap<TextureId, EguiTexture>,
+    primitives: Vec<ClippedPrimitive>,
+}
+
+impl<F> fmt::Debug for EguiWidget<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EguiWidget")
+            .field("ctx", &self.ctx)
+            .field("size", &self.size)
+            .field("events", &self.events)
+            .field("modifiers", &self.modifiers)
+            .field("pointer", &self.pointer)
+            .field("focused", &self.focused)
+            .field("repaint_at", &self.repaint_at)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FnMut(&Context) + Send> EguiWidget<F> {
+    /// Create a new widget of the given logical size
+    pub fn new(size: impl Into<Size<i32, Logical>>, ui: F) -> Self {
+        Self::with_context(Context::default(), size, ui)
+    }
+
+    /// Create a new widget using an existing context, e.g. with custom fonts or styles
+    pub fn with_context(ctx: Context, size: impl Into<Size<i32, Logical>>, ui: F) -> Self {
+        EguiWidget {
+            ctx,
+            ui,
+            size: size.into(),
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            pointer: None,
+            focused: false,
+            start: Instant::now(),
+            repaint_at: Some(Instant::now()),
+            textures: HashMap::new(),
+            primitives: Vec::new(),
+        }
+    }
+
+    /// Returns the egui context
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// Set the logical size of the widget
+    pub fn set_size(&mut self, size: impl Into<Size<i32, Logical>>) {
+        self.size = size.into();
+    }
+
+    /// Returns true if the widget has to be painted again, e.g. to schedule a new frame
+    ///
+    /// This is the case after receiving input, once an animation requested a repaint or after
+    /// [`Context::request_repaint`] was called from another thread.
+    pub fn needs_repaint(&self) -> bool {
+        !self.events.is_empty()
+            || self.repaint_at.is_some_and(|at| at <= Instant::now())
+            || self.ctx.has_requested_repaint()
+    }
+
+    fn handle(&mut self, event: WidgetEvent) {
+        match event {
+            WidgetEvent::PointerEnter { location } | WidgetEvent::PointerMotion { location, .. } => {
+                let pos = Pos2::new(location.x as f32, location.y as f32);
+                self.pointer = Some(pos);
+                self.events.push(Event::PointerMoved(pos));
+            }
+            WidgetEvent::PointerButton { button, state, .. } => {
+                let button = match button {
+                    Some(MouseButton::Left) => PointerButton::Primary,
+                    Some(MouseButton::Right) => PointerButton::Secondary,
+                    Some(MouseButton::Middle) => PointerButton::Middle,
+                    Some(MouseButton::Back) => PointerButton::Extra1,
+                    Some(MouseButton::Forward) => PointerButton::Extra2,
+                    None => return,
+                };
+                self.events.push(Event::PointerButton {
+                    pos: self.pointer.unwrap_or_default(),
+                    button,
+                    pressed: state == ButtonState::Pressed,
+                    modifiers: self.modifiers,
+                });
+            }
+            WidgetEvent::PointerAxis {
+                source, delta, v120, ..
+            } => {
+                let (unit, delta) = match (v120, source) {
+                    (Some((x, y)), _) => (MouseWheelUnit::Line, Vec2::new(x as f32, y as f32) / 120.0),
+                    (None, Some(AxisSource::Wheel)) => (
+                        MouseWheelUnit::Line,
+                        Vec2::new(delta.0 as f32, delta.1 as f32) / 15.0,
+                    ),
+                    (None, _) => (MouseWheelUnit::Point, Vec2::new(delta.0 as f32, delta.1 as f32)),
+                };
+                // egui moves the content by the delta, which is the opposite of the scroll direction
+                self.events.push(Event::MouseWheel {
+                    unit,
+                    delta: -delta,
+                    modifiers: self.modifiers,
+                });
+            }
+            WidgetEvent::PointerLeave => {
+                self.pointer = None;
+                self.events.push(Event::PointerGone);
+            }
+            WidgetEvent::KeyboardEnter => {
+                self.focused = true;
+                self.events.push(Event::WindowFocused(true));
+            }
+            WidgetEvent::KeyboardLeave => {
+                self.focused = false;
+                self.modifiers = Modifiers::default();
+                self.events.push(Event::WindowFocused(false));
+            }
+            WidgetEvent::Key { key, state, text, .. } => {
+                let pressed = state == KeyState::Pressed;
+                if let Some(key) = egui_key(&key) {
+                    self.events.push(Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed,
+                        repeat: false,
+                        modifiers: self.modifiers,
+                    });
+                }
+                // shortcuts are handled through the key events
+                if pressed && !self.modifiers.ctrl && !self.modifiers.alt {
+                    if let Some(text) = text.filter(|text| !text.chars().any(char::is_control)) {
+                        self.events.push(Event::Text(text));
+                    }
+                }
+            }
+            WidgetEvent::Modifiers(state) => {
+                self.modifiers = Modifiers {
+                    alt: state.alt,
+                    ctrl: state.ctrl,
+                    shift: state.shift,
+                    mac_cmd: false,
+                    command: state.ctrl,
+                };
+            }
+        }
+    }
+
+    fn run(&mut self, scale: i32) -> (Vec<ClippedPrimitive>, bool) {
+        let mut input = RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                Pos2::ZERO,
+                Vec2::new(self.size.w as f32, self.size.h as f32),
+            )),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: self.focused,
+            ..Default::default()
+        };
+        input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(scale as f32);
+
+        let ui = &mut self.ui;
+        let output = self.ctx.run(input, |ctx| ui(ctx));
+
+        self.repaint_at = output
+            .viewport_output
+            .get(&ViewportId::ROOT)
+            .and_then(|viewport| Instant::now().checked_add(viewport.repaint_delay))
+            .filter(|_| {
+                output
+                    .viewport_output
+                    .get(&ViewportId::ROOT)
+                    .is_some_and(|viewport| viewport.repaint_delay < Duration::MAX)
+            });
+
+        let textures_changed = !output.textures_delta.set.is_empty();
+        for (id, delta) in output.textures_delta.set {
+            let texture = self.textures.entry(id).or_insert_with(|| EguiTexture {
+                size: [0, 0],
+                pixels: Vec::new(),
+            });
+            texture.update(delta.pos, &delta.image);
+        }
+        for id in output.textures_delta.free {
+            self.textures.remove(&id);
+        }
+
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        (primitives, textures_changed)
+    }
+
+    fn paint_pixels(
+        &mut self,
+        pixels: &mut [u8],
+        size: Size<i32, Buffer>,
+        scale: i32,
+        cleared: bool,
+    ) -> Vec<Rectangle<i32, Buffer>> {
+        if !cleared && !self.needs_repaint() {
+            return Vec::new();
+        }
+
+        let (primitives, textures_changed) = self.run(scale);
+        let bounds = Rectangle::from_size(size);
+        let damage = if cleared || textures_changed {
+            vec![bounds]
+        } else {
+            changed_regions(&self.primitives, &primitives, scale as f32, bounds)
+        };
+
+        for region in &damage {
+            clear(pixels, size, *region);
+            for primitive in &primitives {
+                match &primitive.primitive {
+                    Primitive::Mesh(mesh) => {
+                        let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                            warn!(id = ?mesh.texture_id, "Egui mesh uses an unknown texture");
+                            continue;
+                        };
+                        let Some(clip) = clip_rect(primitive.clip_rect, scale as f32, *region) else {
+                            continue;
+                        };
+                        for triangle in mesh.indices.chunks_exact(3) {
+                            let vertex = |idx: u32| mesh.vertices.get(idx as usize).copied();
+                            if let (Some(a), Some(b), Some(c)) =
+                                (vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2]))
+                            {
+                                rasterize(pixels, size, clip, scale as f32, [a, b, c], texture);
+                            }
+                        }
+                    }
+                    Primitive::Callback(_) => {
+                        warn!("Egui paint callbacks are not supported");
+                    }
+                }
+            }
+        }
+
+        self.primitives = primitives;
+        damage
+    }
+}
+
+impl<F: FnMut(&Context) + Send> PaintWidget for EguiWidget<F> {
+    fn size(&self) -> Size<i32, Logical> {
+        self.size
+    }
+
+    fn paint(&mut self, canvas: &mut WidgetCanvas<'_>) -> Vec<Rectangle<i32, Buffer>> {
+        let (size, scale, cleared) = (canvas.size(), canvas.scale(), canvas.is_cleared());
+        self.paint_pixels(canvas.pixels(), size, scale, cleared)
+    }
+}
+
+impl<D: SeatHandler, F: FnMut(&Context) + Send> Widget<D> for EguiWidget<F> {
+    fn handle_event(&mut self, _seat: &Seat<D>, _data: &mut D, event: WidgetEvent) {
+        self.handle(event);
+    }
+}
+
+fn egui_key(key: &WidgetKey) -> Option<Key> {
+    match key {
+        WidgetKey::Named(named) => match named {
+            NamedKey::Enter => Some(Key::Enter),
+            NamedKey::Tab => Some(Key::Tab),
+            NamedKey::Space => Some(Key::Space),
+            NamedKey::Backspace => Some(Key::Backspace),
+            NamedKey::Escape => Some(Key::Escape),
+            NamedKey::Delete => Some(Key::Delete),
+            NamedKey::Insert => Some(Key::Insert),
+            NamedKey::Home => Some(Key::Home),
+            NamedKey::End => Some(Key::End),
+            NamedKey::PageUp => Some(Key::PageUp),
+            NamedKey::PageDown => Some(Key::PageDown),
+            NamedKey::ArrowLeft => Some(Key::ArrowLeft),
+            NamedKey::ArrowRight => Some(Key::ArrowRight),
+            NamedKey::ArrowUp => Some(Key::ArrowUp),
+            NamedKey::ArrowDown => Some(Key::ArrowDown),
+            NamedKey::F(n) => Key::from_name(&format!("F{}", n)),
+            _ => None,
+        },
+        WidgetKey::Character(text) => Key::from_name(text),
+        WidgetKey::Unidentified(_) => None,
+    }
+}
+
+// premultiplied rgba pixels of an egui texture
+#[derive(Debug)]
+struct EguiTexture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+}
+
+impl EguiTexture {
+    fn update(&mut self, pos: Option<[usize; 2]>, image: &ImageData) {
+        let pixels: Vec<Color32> = match image {
+            ImageData::Color(image) => image.pixels.clone(),
+            ImageData::Font(image) => image.srgba_pixels(None).collect(),
+        };
+        let [width, height] = image.size();
+
+        match pos {
+            None => {
+                self.size = [width, height];
+                self.pixels = pixels;
+            }
+            Some([x, y]) => {
+                for row in 0..height {
+                    let dst_y = y + row;
+                    if dst_y >= self.size[1] || x >= self.size[0] {
+                        continue;
+                    }
+                    let len = width.min(self.size[0] - x);
+                    let dst = dst_y * self.size[0] + x;
+                    self.pixels[dst..dst + len].copy_from_slice(&pixels[row * width..row * width + len]);
+                }
+            }
+        }
+    }
+
+    fn sample(&self, u: f32, v: f32) -> Color32 {
+        let [width, height] = self.size;
+        if width == 0 || height == 0 {
+            return Color32::TRANSPARENT;
+        }
+        let x = ((u * width as f32) as usize).min(width - 1);
+        let y = ((v * height as f32) as usize).min(height - 1);
+        self.pixels[y * width + x]
+    }
+}
+
+// regions covered by primitives, which differ between two frames
+fn changed_regions(
+    old: &[ClippedPrimitive],
+    new: &[ClippedPrimitive],
+    scale: f32,
+    bounds: Rectangle<i32, Buffer>,
+) -> Vec<Rectangle<i32, Buffer>> {
+    let mut damage = Vec::new();
+    for idx in 0..old.len().max(new.len()) {
+        let (old, new) = (old.get(idx), new.get(idx));
+        let unchanged = match (old, new) {
+            (Some(old), Some(new)) => {
+                old.clip_rect == new.clip_rect
+                    && match (&old.primitive, &new.primitive) {
+                        (Primitive::Mesh(old), Primitive::Mesh(new)) => old == new,
+                        _ => false,
+                    }
+            }
+            _ => false,
+        };
+        if !unchanged {
+            damage.extend(old.and_then(|p| primitive_bounds(p, scale, bounds)));
+            damage.extend(new.and_then(|p| primitive_bounds(p, scale, bounds)));
+        }
+    }
+
+    if damage.len() > MAX_DAMAGE_RECTS {
+        let merged = damage
+            .iter()
+            .skip(1)
+            .fold(damage[0], |acc, rect| acc.merge(*rect));
+        vec![merged]
+    } else {
+        damage
+    }
+}
+
+fn primitive_bounds(
+    primitive: &ClippedPrimitive,
+    scale: f32,
+    bounds: Rectangle<i32, Buffer>,
+) -> Option<Rectangle<i32, Buffer>> {
+    let Primitive::Mesh(mesh) = &primitive.primitive else {
+        return clip_rect(primitive.clip_rect, scale, bounds);
+    };
+    let mesh_rect = mesh.calc_bounds();
+    if mesh_rect.is_negative() {
+        return None;
+    }
+    clip_rect(
+        primitive.clip_rect.intersect(mesh_rect.expand(1.0)),
+        scale,
+        bounds,
+    )
+}
+
+// clip rect of egui in points to pixels, limited to `bounds`
+fn clip_rect(rect: Rect, scale: f32, bounds: Rectangle<i32, Buffer>) -> Option<Rectangle<i32, Buffer>> {
+    let min_x = (rect.min.x * scale).floor().max(i32::MIN as f32) as i32;
+    let min_y = (rect.min.y * scale).floor().max(i32::MIN as f32) as i32;
+    let max_x = (rect.max.x * scale).ceil().min(i32::MAX as f32) as i32;
+    let max_y = (rect.max.y * scale).ceil().min(i32::MAX as f32) as i32;
+    if max_x <= min_x || max_y <= min_y {
+        return None;
+    }
+    Rectangle::new((min_x, min_y).into(), (max_x - min_x, max_y - min_y).into()).intersection(bounds)
+}
+
+fn clear(pixels: &mut [u8], size: Size<i32, Buffer>, region: Rectangle<i32, Buffer>) {
+    let stride = size.w as usize * 4;
+    for y in region.loc.y..region.loc.y + region.size.h {
+        let start = y as usize * stride + region.loc.x as usize * 4;
+        pixels[start..start + region.size.w as usize * 4].fill(0);
+    }
+}
+
+// draws a triangle with premultiplied alpha blending into argb8888 pixels
+fn rasterize(
+    pixels: &mut [u8],
+    size: Size<i32, Buffer>,
+    clip: Rectangle<i32, Buffer>,
+    scale: f32,
+    vertices: [Vertex; 3],
+    texture: &EguiTexture,
+) {
+    let [a, b, c] = vertices.map(|v| (v.pos.x * scale, v.pos.y * scale));
+    let area = edge(a, b, c);
+    if area == 0.0 {
+        return;
+    }
+
+    let min_x = a.0.min(b.0).min(c.0).floor().max(clip.loc.x as f32) as i32;
+    let min_y = a.1.min(b.1).min(c.1).floor().max(clip.loc.y as f32) as i32;
+    let max_x =
+        a.0.max(b.0)
+            .max(c.0)
+            .ceil()
+            .min((clip.loc.x + clip.size.w) as f32) as i32;
+    let max_y =
+        a.1.max(b.1)
+            .max(c.1)
+            .ceil()
+            .min((clip.loc.y + clip.size.h) as f32) as i32;
+
+    let stride = size.w as usize * 4;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let p = (x as f32 + 0.5, y as f32 + 0.5);
+            // barycentric weights, normalized so they are positive inside for both windings
+            let w0 = edge(b, c, p) / area;
+            let w1 = edge(c, a, p) / area;
+            let w2 = edge(a, b, p) / area;
+            if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                continue;
+            }
+            // pixels exactly on a shared edge belong to only one of the triangles
+            if (w0 == 0.0 && !owns_edge(b, c, area))
+                || (w1 == 0.0 && !owns_edge(c, a, area))
+                || (w2 == 0.0 && !owns_edge(a, b, area))
+            {
+                continue;
+            }
+
+            let [va, vb, vc] = vertices;
+            let u = va.uv.x * w0 + vb.uv.x * w1 + vc.uv.x * w2;
+            let v = va.uv.y * w0 + vb.uv.y * w1 + vc.uv.y * w2;
+            let texel = texture.sample(u, v);
+            let color = |channel: fn(&Color32) -> u8| {
+                let vertex = channel(&va.color) as f32 * w0
+                    + channel(&vb.color) as f32 * w1
+                    + channel(&vc.color) as f32 * w2;
+                vertex * channel(&texel) as f32 / 255.0
+            };
+            let src = [
+                color(|c| c.b()),
+                color(|c| c.g()),
+                color(|c| c.r()),
+                color(|c| c.a()),
+            ];
+
+            let offset = y as usize * stride + x as usize * 4;
+            let dst = &mut pixels[offset..offset + 4];
+            let inv_alpha = 1.0 - src[3] / 255.0;
+            for (dst, src) in dst.iter_mut().zip(src) {
+                *dst = (src + *dst as f32 * inv_alpha).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+fn edge(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+// top-left rule, adjusted for the winding of the triangle
+fn owns_edge(a: (f32, f32), b: (f32, f32), area: f32) -> bool {
+    let (dx, dy) = if area > 0.0 {
+        (b.0 - a.0, b.1 - a.1)
+    } else {
+        (a.0 - b.0, a.1 - b.1)
+    };
+    dy < 0.0 || (dy == 0.0 && dx > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Point;
+
+    fn widget() -> EguiWidget<impl FnMut(&Context) + Send> {
+        EguiWidget::new((40, 20), |ctx: &Context| {
+            egui::CentralPanel::default()
+                .frame(egui::Frame::none().fill(Color32::RED))
+                .show(ctx, |_| {});
+        })
+    }
+
+    fn pixel(pixels: &[u8], size: Size<i32, Buffer>, x: i32, y: i32) -> [u8; 4] {
+        let offset = ((y * size.w + x) * 4) as usize;
+        pixels[offset..offset + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn paint_only_when_needed() {
+        let mut widget = widget();
+        let size = Size::from((80, 40));
+        let mut pixels = vec![0; 80 * 40 * 4];
+
+        let damage = widget.paint_pixels(&mut pixels, size, 2, true);
+        assert_eq!(damage, vec![Rectangle::from_size(size)]);
+        // argb8888 in little endian
+        assert_eq!(pixel(&pixels, size, 0, 0), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, size, 79, 39), [0, 0, 255, 255]);
+
+        // egui usually wants a second pass after the first frame, afterwards it is idle
+        widget.paint_pixels(&mut pixels, size, 2, false);
+        assert!(!widget.needs_repaint());
+        assert!(widget.paint_pixels(&mut pixels, size, 2, false).is_empty());
+
+        // unchanged contents after input cause no damage
+        widget.handle(WidgetEvent::PointerMotion {
+            location: Point::from((5.0, 5.0)),
+            time: 0,
+        });
+        assert!(widget.needs_repaint());
+        assert!(widget.paint_pixels(&mut pixels, size, 2, false).is_empty());
+    }
+
+    #[test]
+    fn translate_input() {
+        let mut widget = widget();
+        widget.handle(WidgetEvent::Modifiers(crate::input::keyboard::ModifiersState {
+            shift: true,
+            ..Default::default()
+        }));
+        widget.handle(WidgetEvent::PointerEnter {
+            location: Point::from((1.0, 2.0)),
+        });
+        widget.handle(WidgetEvent::PointerButton {
+            button: Some(MouseButton::Left),
+            code: 0x110,
+            state: ButtonState::Pressed,
+            time: 0,
+        });
+        widget.handle(WidgetEvent::PointerAxis {
+            source: Some(AxisSource::Wheel),
+            delta: (0.0, 15.0),
+            v120: Some((0, 120)),
+            time: 0,
+        });
+        widget.handle(WidgetEvent::Key {
+            key: WidgetKey::Character("A".into()),
+            keycode: 38u32.into(),
+            state: KeyState::Pressed,
+            text: Some("A".into()),
+            time: 0,
+        });
+
+        let shift = Modifiers {
+            shift: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            widget.events,
+            vec![
+                Event::PointerMoved(Pos2::new(1.0, 2.0)),
+                Event::PointerButton {
+                    pos: Pos2::new(1.0, 2.0),
+                    button: PointerButton::Primary,
+                    pressed: true,
+                    modifiers: shift,
+                },
+                Event::MouseWheel {
+                    unit: MouseWheelUnit::Line,
+                    delta: Vec2::new(0.0, -1.0),
+                    modifiers: shift,
+                },
+                Event::Key {
+                    key: Key::A,
+                    physical_key: None,
+                    pressed: true,
+                    repeat: false,
+                    modifiers: shift,
+                },
+                Event::Text("A".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rasterize_triangles() {
+        let size = Size::from((4, 4));
+        let mut pixels = vec![0; 4 * 4 * 4];
+        let texture = EguiTexture {
+            size: [1, 1],
+            pixels: vec![Color32::WHITE],
+        };
+        let vertex = |x: f32, y: f32, color: Color32| Vertex {
+            pos: Pos2::new(x, y),
+            uv: Pos2::ZERO,
+            color,
+        };
+
+        // two triangles of a square sharing an edge, half transparent
+        let color = Color32::from_rgba_premultiplied(0, 0, 128, 128);
+        let clip = Rectangle::from_size(size);
+        let square = [
+            vertex(0.0, 0.0, color),
+            vertex(4.0, 0.0, color),
+            vertex(4.0, 4.0, color),
+            vertex(0.0, 4.0, color),
+        ];
+        rasterize(
+            &mut pixels,
+            size,
+            clip,
+            1.0,
+            [square[0], square[1], square[2]],
+            &texture,
+        );
+        rasterize(
+            &mut pixels,
+            size,
+            clip,
+            1.0,
+            [square[0], square[2], square[3]],
+            &texture,
+        );
+        for y in 0..4 {
+            for x in 0..4 {
+                // no pixel is blended twice
+                assert_eq!(pixel(&pixels, size, x, y), [128, 0, 0, 128], "{} {}", x, y);
+            }
+        }
+
+        // clipping and opaque blending
+        let clip = Rectangle::new((1, 1).into(), (2, 2).into());
+        let triangle = [
+            vertex(0.0, 0.0, Color32::GREEN),
+            vertex(8.0, 0.0, Color32::GREEN),
+            vertex(0.0, 8.0, Color32::GREEN),
+        ];
+        rasterize(&mut pixels, size, clip, 1.0, triangle, &texture);
+        assert_eq!(pixel(&pixels, size, 1, 1), [0, 255, 0, 255]);
+        assert_eq!(pixel(&pixels, size, 0, 0), [128, 0, 0, 128]);
+        assert_eq!(pixel(&pixels, size, 3, 3), [128, 0, 0, 128]);
+    }
+
+    #[test]
+    fn texture_updates() {
+        let mut texture = EguiTexture {
+            size: [0, 0],
+            pixels: Vec::new(),
+        };
+        texture.update(
+            None,
+            &ImageData::Color(egui::ColorImage::new([2, 2], Color32::BLACK).into()),
+        );
+        texture.update(
+            Some([1, 1]),
+            &ImageData::Color(egui::ColorImage::new([1, 1], Color32::WHITE).into()),
+        );
+        assert_eq!(texture.sample(0.0, 0.0), Color32::BLACK);
+        assert_eq!(texture.sample(0.9, 0.9), Color32::WHITE);
+    }
+}
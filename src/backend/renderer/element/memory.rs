@@ -293,15 +293,15 @@ impl MemoryBuffer {
     /// Resize this buffer to the size specified
     pub fn resize(&mut self, size: impl Into<Size<i32, Buffer>>) -> bool {
         let size = size.into();
+        if self.size == size {
+            return false;
+        }
         let stride = size.w * (get_bpp(self.format).expect("Format with unknown bits per pixel") / 8) as i32;
         let mem = Arc::make_mut(&mut self.mem);
-        let mem_size = (stride * size.h) as usize;
-        if mem.len() != mem_size {
-            mem.resize(mem_size, 0);
-            true
-        } else {
-            false
-        }
+        mem.resize((stride * size.h) as usize, 0);
+        self.size = size;
+        self.stride = stride;
+        true
     }
 }
 
@@ -463,6 +463,11 @@ impl Default for MemoryRenderBuffer {
 }
 
 impl MemoryRenderBuffer {
+    /// Returns a snapshot of the current contents of this buffer
+    pub fn memory(&self) -> MemoryBuffer {
+        self.inner.lock().unwrap().mem.clone()
+    }
+
     /// Initialize a empty [`MemoryRenderBuffer`]
     pub fn new(
         format: Fourcc,
@@ -633,7 +638,7 @@ impl<R: Renderer> MemoryRenderBufferRenderElement<R> {
     }
 
     fn scale(&self) -> Scale<f64> {
-        let src = self.src();
+        let src = self.src;
         Scale::from((self.size.w as f64 / src.size.w, self.size.h as f64 / src.size.h))
     }
 }
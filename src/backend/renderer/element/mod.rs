@@ -12,6 +12,7 @@
 //! - [`texture`] - Texture based render element
 //! - [`surface`] - Wayland surface render element
 //! - [`solid`] - Solid color render element
+//! - [`widget`] - Compositor-internal widget render element
 //!
 //! The [`render_elements!`] macro provides an easy way to aggregate multiple different [RenderElement]s
 //! into a single enum.
@@ -19,12 +20,13 @@
 //! See the [`damage`](crate::backend::renderer::damage) module for more information on
 //! damage tracking.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, fmt, sync::Arc};
 
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::{backend::ObjectId, Resource};
 
 use crate::{
+    backend::allocator::dmabuf::Dmabuf,
     output::{Output, WeakOutput},
     utils::{Buffer as BufferCoords, Physical, Point, Rectangle, Scale, Transform},
 };
@@ -36,12 +38,19 @@ use super::{
     Renderer,
 };
 
+pub mod composite;
+#[cfg(feature = "egui")]
+pub mod egui;
 pub mod memory;
+pub mod remote;
+pub mod shadow;
 pub mod solid;
 #[cfg(feature = "wayland_frontend")]
 pub mod surface;
 pub mod texture;
 pub mod utils;
+pub mod video;
+pub mod widget;
 
 crate::utils::ids::id_gen!(external_id);
 
@@ -106,6 +115,36 @@ pub enum UnderlyingStorage<'a> {
     Wayland(&'a Buffer),
     /// A memory backed buffer
     Memory(&'a memory::MemoryBuffer),
+    /// A buffer not associated with any client, see [`ExternalStorage`]
+    External(&'a dyn ExternalStorage),
+}
+
+/// A buffer not associated with any client, which can be provided as [`UnderlyingStorage`]
+///
+/// Implement this for buffers owned by the compositor, e.g. produced by a video decoder,
+/// to make them available for direct scan-out without the `wayland_frontend` feature.
+pub trait ExternalStorage: fmt::Debug {
+    /// The [`Dmabuf`] backing this buffer, if any
+    ///
+    /// Only dmabuf backed buffers can be scanned out directly.
+    fn dmabuf(&self) -> Option<&Dmabuf>;
+
+    /// Returns true if the buffer can be read, e.g. rendering into it has finished
+    fn is_ready_for_read(&self) -> bool {
+        self.dmabuf().map_or(true, Dmabuf::is_ready_for_read)
+    }
+
+    /// Returns true if the contents of the buffer are stored upside down
+    fn y_inverted(&self) -> bool {
+        self.dmabuf().is_some_and(Dmabuf::y_inverted)
+    }
+}
+
+impl ExternalStorage for Dmabuf {
+    #[inline]
+    fn dmabuf(&self) -> Option<&Dmabuf> {
+        Some(self)
+    }
 }
 
 /// Defines the (optional) reason why a [`Element`] was selected for
@@ -116,6 +155,11 @@ pub enum RenderingReason {
     FormatUnsupported,
     /// Element was selected for direct scan-out but failed
     ScanoutFailed,
+    /// The implicit fences of the element buffer are not signaled yet
+    ///
+    /// Directly scanning out the element would delay the commit until
+    /// the fences are signaled.
+    FenceNotSignaled,
 }
 
 /// Defines the presentation state of an element after rendering
@@ -327,6 +371,7 @@ impl RenderElementStates {
 ///
 /// This can give the backend a hint about how to handle the element
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
 pub enum Kind {
     /// The element represents a cursor
     ///
@@ -334,6 +379,29 @@ pub enum Kind {
     /// Not marking a cursor element as `Cursor` may result in lower performance and increased power usage.
     /// In contrast, marking elements that change frequently as `Cursor` can degrade performance significantly.
     Cursor,
+    /// The element is a good candidate for direct scan-out
+    ///
+    /// This hints that the element is backed by a buffer that changes infrequently,
+    /// like a wallpaper or a background surface. Backends may prefer to put such
+    /// elements on planes lower in the stack, like underlay planes, to keep planes
+    /// higher in the stack available for more frequently changing content.
+    ScanoutCandidate,
+    /// The element represents video content
+    ///
+    /// Video content is usually updated on every frame and benefits the most from
+    /// direct scan-out. Backends should prioritize these elements when assigning
+    /// scarce overlay planes.
+    Video,
+    /// The element is suitable for overlay planes
+    ///
+    /// Backends should prioritize these elements over elements of
+    /// unspecified kind when assigning overlay planes, but not over [`Kind::Video`].
+    Overlayable,
+    /// The element should never be directly scanned out
+    ///
+    /// Backends will always render these elements, e.g. because their
+    /// buffers are known to be unsuitable for planes.
+    ForceRender,
     /// The element kind is unspecified
     #[default]
     Unspecified,
@@ -0,0 +1,908 @@
+//! Element to render surfaces of another compositor process
+//!
+//! # Why use this implementation
+//!
+//! Compositors can be split into multiple processes, e.g. to isolate a shell or a panel
+//! written in another toolkit from the compositor handling the hardware. The child process renders
+//! into dmabufs and shares them with the parent through a unix socket using the [`RemoteSurfaceExporter`].
+//! The parent receives them with a [`RemoteSurface`] and renders them as part of its scene
+//! using a [`RemoteSurfaceRenderElement`].
+//!
+//! Buffers are never copied, they are imported by the renderer of the parent and may even be directly
+//! scanned out. Only the buffer metadata and the damage of each commit travel over the socket.
+//! Every buffer is only sent once, later commits only reference it.
+//!
+//! # Why **not** to use this implementation
+//!
+//! Only dmabufs can be shared, buffers backed by system memory are not supported. Synchronization
+//! relies on implicit sync of the dmabufs. Input, surface roles and everything else needed to run a
+//! nested compositor are out of scope and have to be implemented on top, e.g. using a wayland connection
+//! between the processes.
+//!
+//! # How to use it
+//!
+//! Create a connected socket pair using [`socket_pair`] and pass one end to the child process.
+//! The socket is a `SOCK_SEQPACKET` unix socket, so it can be combined with other messages on another socket,
+//! but should not be shared with them.
+//!
+//! In the child, create a [`RemoteSurfaceExporter`] and call [`RemoteSurfaceExporter::export`] for every
+//! rendered frame. A buffer stays in use by the parent until another buffer is committed and
+//! the parent released it, use [`RemoteSurfaceExporter::is_busy`] to select a free buffer of your swapchain.
+//!
+//! In the parent, create a [`RemoteSurface`] and insert its file descriptor into your event loop. Whenever
+//! it becomes readable call [`RemoteSurface::dispatch`] and schedule a redraw, if a new buffer was committed.
+//!
+//! ```no_run
+//! # use smithay::backend::renderer::{ImportDma, Renderer};
+//! use smithay::{
+//!     backend::renderer::element::{
+//!         remote::{socket_pair, RemoteSurface, RemoteSurfaceRenderElement},
+//!         Kind,
+//!     },
+//!     utils::Point,
+//! };
+//!
+//! # fn render<R: Renderer + ImportDma>(renderer: &mut R) where R::TextureId: Clone + Send + 'static {
+//! let (parent, child) = socket_pair().expect("Failed to create socket");
+//! // pass `child` to the child process, e.g. by inheriting it
+//!
+//! let mut surface = RemoteSurface::new(parent);
+//!
+//! // when the socket becomes readable
+//! if surface.dispatch().expect("Connection to child lost") {
+//!     // schedule a redraw
+//! }
+//!
+//! // in the render loop
+//! let element = RemoteSurfaceRenderElement::from_surface(
+//!     renderer,
+//!     Point::from((0.0, 0.0)),
+//!     &surface,
+//!     1.0,
+//!     None,
+//!     Kind::Unspecified,
+//! )
+//! .expect("Failed to import remote buffer");
+//! # }
+//! ```
+
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    io::{self, IoSlice, IoSliceMut},
+    os::unix::io::{AsFd, BorrowedFd, OwnedFd},
+    sync::Mutex,
+};
+
+use rustix::net::{
+    AddressFamily, RecvAncillaryBuffer, RecvAncillaryMessage, RecvFlags, SendAncillaryBuffer,
+    SendAncillaryMessage, SendFlags, SocketFlags, SocketType,
+};
+use tracing::{debug, instrument, trace, warn};
+
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    backend::{
+        allocator::{
+            dmabuf::{Dmabuf, DmabufFlags, WeakDmabuf, MAX_PLANES},
+            format::has_alpha,
+            Buffer as _, Fourcc, Modifier,
+        },
+        renderer::{
+            utils::{CommitCounter, DamageBag, DamageSet, DamageSnapshot, OpaqueRegions},
+            Frame, ImportDma, Renderer,
+        },
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+
+use super::{Element, Id, Kind, RenderElement, UnderlyingStorage};
+
+/// Maximum number of damage rectangles sent per commit, more are merged into their bounding box
+const MAX_DAMAGE: usize = 32;
+/// Size of the largest message, a commit with [`MAX_DAMAGE`] rectangles
+const MAX_MESSAGE_SIZE: usize = 4 + 8 + 4 + 4 + 4 + MAX_DAMAGE * 16;
+/// Number of commits to keep the damage of
+const DAMAGE_LIMIT: usize = 4;
+
+const OP_NEW_BUFFER: u32 = 0;
+const OP_COMMIT: u32 = 1;
+const OP_DESTROY_BUFFER: u32 = 2;
+const OP_RELEASE: u32 = 3;
+
+/// Create a connected pair of sockets suitable for [`RemoteSurfaceExporter`] and [`RemoteSurface`]
+///
+/// The sockets are created with `CLOEXEC`, clear the flag of the socket passed to the child process
+/// if it is inherited on `exec`.
+pub fn socket_pair() -> io::Result<(OwnedFd, OwnedFd)> {
+    Ok(rustix::net::socketpair(
+        AddressFamily::UNIX,
+        SocketType::SEQPACKET,
+        SocketFlags::CLOEXEC,
+        None,
+    )?)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct BufferInfo {
+    size: Size<i32, Buffer>,
+    code: Fourcc,
+    modifier: Modifier,
+    flags: DmabufFlags,
+    // offset and stride of every plane
+    planes: Vec<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Message {
+    NewBuffer {
+        id: u64,
+        info: BufferInfo,
+    },
+    Commit {
+        id: u64,
+        scale: i32,
+        transform: Transform,
+        damage: Vec<Rectangle<i32, Buffer>>,
+    },
+    DestroyBuffer {
+        id: u64,
+    },
+    Release {
+        id: u64,
+    },
+}
+
+fn transform_to_raw(transform: Transform) -> u32 {
+    match transform {
+        Transform::Normal => 0,
+        Transform::_90 => 1,
+        Transform::_180 => 2,
+        Transform::_270 => 3,
+        Transform::Flipped => 4,
+        Transform::Flipped90 => 5,
+        Transform::Flipped180 => 6,
+        Transform::Flipped270 => 7,
+    }
+}
+
+fn transform_from_raw(raw: u32) -> Option<Transform> {
+    Some(match raw {
+        0 => Transform::Normal,
+        1 => Transform::_90,
+        2 => Transform::_180,
+        3 => Transform::_270,
+        4 => Transform::Flipped,
+        5 => Transform::Flipped90,
+        6 => Transform::Flipped180,
+        7 => Transform::Flipped270,
+        _ => return None,
+    })
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.0.len() < N {
+            return Err(invalid_data("truncated message"));
+        }
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.bytes().map(u32::from_ne_bytes)
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        self.bytes().map(i32::from_ne_bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.bytes().map(u64::from_ne_bytes)
+    }
+}
+
+impl Message {
+    // Messages never leave the host, so native endianness is used
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        match self {
+            Message::NewBuffer { id, info } => {
+                buf.extend_from_slice(&OP_NEW_BUFFER.to_ne_bytes());
+                buf.extend_from_slice(&id.to_ne_bytes());
+                buf.extend_from_slice(&info.size.w.to_ne_bytes());
+                buf.extend_from_slice(&info.size.h.to_ne_bytes());
+                buf.extend_from_slice(&(info.code as u32).to_ne_bytes());
+                buf.extend_from_slice(&u64::from(info.modifier).to_ne_bytes());
+                buf.extend_from_slice(&info.flags.bits().to_ne_bytes());
+                buf.extend_from_slice(&(info.planes.len() as u32).to_ne_bytes());
+                for (offset, stride) in &info.planes {
+                    buf.extend_from_slice(&offset.to_ne_bytes());
+                    buf.extend_from_slice(&stride.to_ne_bytes());
+                }
+            }
+            Message::Commit {
+                id,
+                scale,
+                transform,
+                damage,
+            } => {
+                buf.extend_from_slice(&OP_COMMIT.to_ne_bytes());
+                buf.extend_from_slice(&id.to_ne_bytes());
+                buf.extend_from_slice(&scale.to_ne_bytes());
+                buf.extend_from_slice(&transform_to_raw(*transform).to_ne_bytes());
+                buf.extend_from_slice(&(damage.len() as u32).to_ne_bytes());
+                for rect in damage {
+                    buf.extend_from_slice(&rect.loc.x.to_ne_bytes());
+                    buf.extend_from_slice(&rect.loc.y.to_ne_bytes());
+                    buf.extend_from_slice(&rect.size.w.to_ne_bytes());
+                    buf.extend_from_slice(&rect.size.h.to_ne_bytes());
+                }
+            }
+            Message::DestroyBuffer { id } => {
+                buf.extend_from_slice(&OP_DESTROY_BUFFER.to_ne_bytes());
+                buf.extend_from_slice(&id.to_ne_bytes());
+            }
+            Message::Release { id } => {
+                buf.extend_from_slice(&OP_RELEASE.to_ne_bytes());
+                buf.extend_from_slice(&id.to_ne_bytes());
+            }
+        }
+    }
+
+    fn decode(data: &[u8]) -> io::Result<Message> {
+        let mut reader = Reader(data);
+        let msg = match reader.u32()? {
+            OP_NEW_BUFFER => {
+                let id = reader.u64()?;
+                let size = Size::from((reader.i32()?, reader.i32()?));
+                let code = Fourcc::try_from(reader.u32()?).map_err(|_| invalid_data("unknown format"))?;
+                let modifier = Modifier::from(reader.u64()?);
+                let flags = DmabufFlags::from_bits_truncate(reader.u32()?);
+                let num_planes = reader.u32()? as usize;
+                if num_planes == 0 || num_planes > MAX_PLANES {
+                    return Err(invalid_data("invalid number of planes"));
+                }
+                let planes = (0..num_planes)
+                    .map(|_| Ok((reader.u32()?, reader.u32()?)))
+                    .collect::<io::Result<Vec<_>>>()?;
+                Message::NewBuffer {
+                    id,
+                    info: BufferInfo {
+                        size,
+                        code,
+                        modifier,
+                        flags,
+                        planes,
+                    },
+                }
+            }
+            OP_COMMIT => {
+                let id = reader.u64()?;
+                let scale = reader.i32()?;
+                let transform =
+                    transform_from_raw(reader.u32()?).ok_or_else(|| invalid_data("invalid transform"))?;
+                let num_damage = reader.u32()? as usize;
+                if num_damage > MAX_DAMAGE {
+                    return Err(invalid_data("too many damage rectangles"));
+                }
+                let damage = (0..num_damage)
+                    .map(|_| {
+                        Ok(Rectangle::new(
+                            (reader.i32()?, reader.i32()?).into(),
+                            (reader.i32()?, reader.i32()?).into(),
+                        ))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?;
+                Message::Commit {
+                    id,
+                    scale,
+                    transform,
+                    damage,
+                }
+            }
+            OP_DESTROY_BUFFER => Message::DestroyBuffer { id: reader.u64()? },
+            OP_RELEASE => Message::Release { id: reader.u64()? },
+            _ => return Err(invalid_data("unknown opcode")),
+        };
+
+        if !reader.0.is_empty() {
+            return Err(invalid_data("trailing data"));
+        }
+        Ok(msg)
+    }
+}
+
+fn send(socket: BorrowedFd<'_>, msg: &Message, fds: &[BorrowedFd<'_>]) -> io::Result<()> {
+    let mut data = Vec::with_capacity(MAX_MESSAGE_SIZE);
+    msg.encode(&mut data);
+
+    let mut space = [0; rustix::cmsg_space!(ScmRights(MAX_PLANES))];
+    let mut control = SendAncillaryBuffer::new(&mut space);
+    if !fds.is_empty() && !control.push(SendAncillaryMessage::ScmRights(fds)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "too many file descriptors",
+        ));
+    }
+
+    rustix::net::sendmsg(socket, &[IoSlice::new(&data)], &mut control, SendFlags::NOSIGNAL)?;
+    Ok(())
+}
+
+// Returns `None`, if no message is pending
+fn recv(socket: BorrowedFd<'_>, fds: &mut Vec<OwnedFd>) -> io::Result<Option<Message>> {
+    let mut data = [0u8; MAX_MESSAGE_SIZE];
+    let mut space = [0; rustix::cmsg_space!(ScmRights(MAX_PLANES))];
+    let mut control = RecvAncillaryBuffer::new(&mut space);
+
+    let res = match rustix::net::recvmsg(
+        socket,
+        &mut [IoSliceMut::new(&mut data)],
+        &mut control,
+        RecvFlags::DONTWAIT | RecvFlags::CMSG_CLOEXEC,
+    ) {
+        Ok(res) => res,
+        Err(rustix::io::Errno::WOULDBLOCK) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    fds.clear();
+    for msg in control.drain() {
+        if let RecvAncillaryMessage::ScmRights(received) = msg {
+            fds.extend(received);
+        }
+    }
+
+    if res.bytes == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+    }
+    if res.flags.contains(RecvFlags::TRUNC) {
+        return Err(invalid_data("message truncated"));
+    }
+    Message::decode(&data[..res.bytes]).map(Some)
+}
+
+/// Shares buffers with a [`RemoteSurface`] in another process
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct RemoteSurfaceExporter {
+    socket: OwnedFd,
+    next_id: u64,
+    buffers: HashMap<WeakDmabuf, u64>,
+    busy: HashSet<u64>,
+    current: Option<u64>,
+    #[cfg(feature = "wayland_frontend")]
+    surface_commit: Option<CommitCounter>,
+    #[cfg(feature = "wayland_frontend")]
+    surface_buffers: HashMap<u64, crate::backend::renderer::utils::Buffer>,
+}
+
+impl RemoteSurfaceExporter {
+    /// Create a new exporter sending buffers over `socket`
+    ///
+    /// See [`socket_pair`] for the requirements of the socket.
+    pub fn new(socket: OwnedFd) -> Self {
+        RemoteSurfaceExporter {
+            socket,
+            next_id: 0,
+            buffers: HashMap::new(),
+            busy: HashSet::new(),
+            current: None,
+            #[cfg(feature = "wayland_frontend")]
+            surface_commit: None,
+            #[cfg(feature = "wayland_frontend")]
+            surface_buffers: HashMap::new(),
+        }
+    }
+
+    /// Commit a new buffer with the given damage
+    ///
+    /// The damage is in buffer coordinates, an empty slice damages the whole buffer. The buffer
+    /// is interpreted with the given scale and transform, like a wayland buffer.
+    /// Buffers not known to the other side yet are sent along.
+    #[instrument(level = "trace", skip(self, dmabuf, damage))]
+    #[profiling::function]
+    pub fn export(
+        &mut self,
+        dmabuf: &Dmabuf,
+        damage: &[Rectangle<i32, Buffer>],
+        scale: i32,
+        transform: Transform,
+    ) -> io::Result<()> {
+        self.cleanup()?;
+
+        let id = match self.buffers.get(&dmabuf.weak()) {
+            Some(id) => *id,
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+
+                let info = BufferInfo {
+                    size: dmabuf.size(),
+                    code: dmabuf.format().code,
+                    modifier: dmabuf.format().modifier,
+                    flags: if dmabuf.y_inverted() {
+                        DmabufFlags::Y_INVERT
+                    } else {
+                        DmabufFlags::empty()
+                    },
+                    planes: dmabuf.offsets().zip(dmabuf.strides()).collect(),
+                };
+                let fds = dmabuf.handles().collect::<Vec<_>>();
+                debug!(id, ?info, "Sending new buffer");
+                send(self.socket.as_fd(), &Message::NewBuffer { id, info }, &fds)?;
+                self.buffers.insert(dmabuf.weak(), id);
+                id
+            }
+        };
+
+        let damage = if damage.is_empty() {
+            vec![Rectangle::from_size(dmabuf.size())]
+        } else if damage.len() > MAX_DAMAGE {
+            vec![damage[1..].iter().fold(damage[0], |acc, rect| acc.merge(*rect))]
+        } else {
+            damage.to_vec()
+        };
+        send(
+            self.socket.as_fd(),
+            &Message::Commit {
+                id,
+                scale,
+                transform,
+                damage,
+            },
+            &[],
+        )?;
+        self.busy.insert(id);
+        self.current = Some(id);
+        Ok(())
+    }
+
+    /// Commit the current buffer of a wayland surface
+    ///
+    /// The surface has to use [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler)
+    /// and its buffer has to be a dmabuf, otherwise nothing is exported. The wayland buffer is only released
+    /// to the client, once the other side released it.
+    ///
+    /// Returns if a buffer was exported.
+    #[cfg(feature = "wayland_frontend")]
+    pub fn export_surface(&mut self, surface: &WlSurface) -> io::Result<bool> {
+        use crate::backend::renderer::utils::with_renderer_surface_state;
+
+        let Some((buffer, dmabuf, damage, commit, scale, transform)) =
+            with_renderer_surface_state(surface, |state| {
+                let buffer = state.buffer()?.clone();
+                let dmabuf = crate::wayland::dmabuf::get_dmabuf(&buffer).ok()?.clone();
+                let damage = state.damage_since(self.surface_commit).to_vec();
+                Some((
+                    buffer,
+                    dmabuf,
+                    damage,
+                    state.current_commit(),
+                    state.buffer_scale(),
+                    state.buffer_transform(),
+                ))
+            })
+            .flatten()
+        else {
+            return Ok(false);
+        };
+
+        if self.surface_commit == Some(commit) {
+            return Ok(false);
+        }
+        if damage.is_empty() && self.surface_commit.is_some() {
+            // no damage, but a new commit, don't send an empty commit damaging everything
+            self.surface_commit = Some(commit);
+            return Ok(false);
+        }
+
+        self.export(&dmabuf, &damage, scale, transform)?;
+        self.surface_commit = Some(commit);
+        if let Some(id) = self.current {
+            self.surface_buffers.insert(id, buffer);
+        }
+        Ok(true)
+    }
+
+    /// Returns if a buffer is still used by the other side
+    ///
+    /// Busy buffers must not be written to. Call [`RemoteSurfaceExporter::dispatch`] to receive
+    /// releases of buffers.
+    pub fn is_busy(&self, dmabuf: &Dmabuf) -> bool {
+        self.buffers
+            .get(&dmabuf.weak())
+            .is_some_and(|id| self.busy.contains(id))
+    }
+
+    /// Receive pending messages from the other side
+    ///
+    /// This does not block and should be called whenever the socket becomes readable.
+    pub fn dispatch(&mut self) -> io::Result<()> {
+        let mut fds = Vec::new();
+        while let Some(msg) = recv(self.socket.as_fd(), &mut fds)? {
+            match msg {
+                Message::Release { id } => {
+                    trace!(id, "Buffer released");
+                    self.busy.remove(&id);
+                    #[cfg(feature = "wayland_frontend")]
+                    self.surface_buffers.remove(&id);
+                }
+                msg => {
+                    warn!(?msg, "Unexpected message from remote surface");
+                    return Err(invalid_data("unexpected message"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Tell the other side about destroyed buffers
+    fn cleanup(&mut self) -> io::Result<()> {
+        let destroyed = self
+            .buffers
+            .iter()
+            .filter(|(buffer, id)| buffer.is_gone() && Some(**id) != self.current)
+            .map(|(buffer, id)| (buffer.clone(), *id))
+            .collect::<Vec<_>>();
+        for (buffer, id) in destroyed {
+            send(self.socket.as_fd(), &Message::DestroyBuffer { id }, &[])?;
+            self.buffers.remove(&buffer);
+            self.busy.remove(&id);
+        }
+        Ok(())
+    }
+}
+
+impl AsFd for RemoteSurfaceExporter {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RemoteBuffer {
+    id: u64,
+    dmabuf: Dmabuf,
+    scale: i32,
+    transform: Transform,
+}
+
+/// A surface of another process receiving buffers from a [`RemoteSurfaceExporter`]
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct RemoteSurface {
+    id: Id,
+    socket: OwnedFd,
+    buffers: HashMap<u64, Dmabuf>,
+    current: Option<RemoteBuffer>,
+    damage: DamageBag<i32, Buffer>,
+    textures: Mutex<HashMap<(TypeId, usize), Box<dyn Any + Send>>>,
+}
+
+impl RemoteSurface {
+    /// Create a new remote surface receiving buffers over `socket`
+    ///
+    /// See [`socket_pair`] for the requirements of the socket.
+    pub fn new(socket: OwnedFd) -> Self {
+        RemoteSurface {
+            id: Id::new(),
+            socket,
+            buffers: HashMap::new(),
+            current: None,
+            damage: DamageBag::new(DAMAGE_LIMIT),
+            textures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Receive pending messages from the other side
+    ///
+    /// This does not block and should be called whenever the socket becomes readable.
+    /// An error of kind [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) signals the other side
+    /// closed the connection, the last committed buffer can still be rendered.
+    ///
+    /// Returns if a new buffer was committed.
+    #[instrument(level = "trace", skip(self))]
+    #[profiling::function]
+    pub fn dispatch(&mut self) -> io::Result<bool> {
+        let mut committed = false;
+        let mut fds = Vec::new();
+        while let Some(msg) = recv(self.socket.as_fd(), &mut fds)? {
+            match msg {
+                Message::NewBuffer { id, info } => {
+                    if fds.len() != info.planes.len() {
+                        return Err(invalid_data("number of file descriptors doesn't match planes"));
+                    }
+                    let mut builder = Dmabuf::builder(info.size, info.code, info.modifier, info.flags);
+                    for (idx, (fd, (offset, stride))) in fds.drain(..).zip(info.planes).enumerate() {
+                        builder.add_plane(fd, idx as u32, offset, stride);
+                    }
+                    let dmabuf = builder.build().ok_or_else(|| invalid_data("invalid dmabuf"))?;
+                    debug!(id, "Received new buffer");
+                    self.buffers.insert(id, dmabuf);
+                }
+                Message::Commit {
+                    id,
+                    scale,
+                    transform,
+                    damage,
+                } => {
+                    let dmabuf = self
+                        .buffers
+                        .get(&id)
+                        .ok_or_else(|| invalid_data("commit of unknown buffer"))?
+                        .clone();
+
+                    let previous = self.current.replace(RemoteBuffer {
+                        id,
+                        dmabuf,
+                        scale: scale.max(1),
+                        transform,
+                    });
+                    match previous {
+                        Some(previous) if previous.id == id => {
+                            self.damage.add(damage);
+                        }
+                        previous => {
+                            if let Some(previous) = previous {
+                                send(self.socket.as_fd(), &Message::Release { id: previous.id }, &[])?;
+                            }
+                            // the texture changed, so everything is damaged
+                            self.damage
+                                .add([Rectangle::from_size(self.current.as_ref().unwrap().dmabuf.size())]);
+                            self.textures.lock().unwrap().clear();
+                        }
+                    }
+                    committed = true;
+                }
+                Message::DestroyBuffer { id } => {
+                    self.buffers.remove(&id);
+                }
+                msg => {
+                    warn!(?msg, "Unexpected message from remote surface exporter");
+                    return Err(invalid_data("unexpected message"));
+                }
+            }
+        }
+        Ok(committed)
+    }
+
+    /// Returns the currently committed buffer
+    pub fn current_buffer(&self) -> Option<&Dmabuf> {
+        self.current.as_ref().map(|buffer| &buffer.dmabuf)
+    }
+
+    /// Returns the size of the surface, if a buffer was committed
+    pub fn size(&self) -> Option<Size<i32, Logical>> {
+        self.current
+            .as_ref()
+            .map(|buffer| buffer.dmabuf.size().to_logical(buffer.scale, buffer.transform))
+    }
+}
+
+impl AsFd for RemoteSurface {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+/// A render element for the current buffer of a [`RemoteSurface`]
+#[derive(Debug)]
+pub struct RemoteSurfaceRenderElement<R: Renderer> {
+    id: Id,
+    location: Point<f64, Physical>,
+    dmabuf: Dmabuf,
+    buffer_scale: i32,
+    buffer_transform: Transform,
+    damage: DamageSnapshot<i32, Buffer>,
+    size: Size<i32, Logical>,
+    alpha: f32,
+    texture: R::TextureId,
+    kind: Kind,
+}
+
+impl<R: Renderer> RemoteSurfaceRenderElement<R> {
+    /// Create a new [`RemoteSurfaceRenderElement`] for the current buffer of a [`RemoteSurface`]
+    ///
+    /// The surface is shown at its own size, unless a `size` is given.
+    /// Returns `None`, if no buffer was committed yet.
+    #[instrument(level = "trace", skip(renderer, location, surface))]
+    #[profiling::function]
+    pub fn from_surface(
+        renderer: &mut R,
+        location: impl Into<Point<f64, Physical>>,
+        surface: &RemoteSurface,
+        alpha: f32,
+        size: Option<Size<i32, Logical>>,
+        kind: Kind,
+    ) -> Result<Option<Self>, <R as Renderer>::Error>
+    where
+        R: ImportDma,
+        <R as Renderer>::TextureId: Send + Clone + 'static,
+    {
+        let Some(buffer) = surface.current.as_ref() else {
+            return Ok(None);
+        };
+
+        let mut textures = surface.textures.lock().unwrap();
+        let texture_id = (TypeId::of::<<R as Renderer>::TextureId>(), renderer.id());
+        let texture = match textures.get(&texture_id) {
+            Some(texture) => texture.downcast_ref::<R::TextureId>().unwrap().clone(),
+            None => {
+                trace!(id = buffer.id, "importing remote buffer");
+                let texture = renderer.import_dmabuf(&buffer.dmabuf, None)?;
+                textures.insert(texture_id, Box::new(texture.clone()));
+                texture
+            }
+        };
+
+        Ok(Some(RemoteSurfaceRenderElement {
+            id: surface.id.clone(),
+            location: location.into(),
+            dmabuf: buffer.dmabuf.clone(),
+            buffer_scale: buffer.scale,
+            buffer_transform: buffer.transform,
+            damage: surface.damage.snapshot(),
+            size: size.unwrap_or_else(|| buffer.dmabuf.size().to_logical(buffer.scale, buffer.transform)),
+            alpha,
+            texture,
+            kind,
+        }))
+    }
+
+    fn physical_size(&self, scale: Scale<f64>) -> Size<i32, Physical> {
+        ((self.size.to_f64().to_physical(scale).to_point() + self.location).to_i32_round()
+            - self.location.to_i32_round())
+        .to_size()
+    }
+
+    fn logical_size(&self) -> Size<f64, Logical> {
+        self.dmabuf
+            .size()
+            .to_f64()
+            .to_logical(self.buffer_scale as f64, self.buffer_transform)
+    }
+}
+
+impl<R: Renderer> Element for RemoteSurfaceRenderElement<R> {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.damage.current_commit()
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.dmabuf.size().to_f64())
+    }
+
+    fn transform(&self) -> Transform {
+        self.buffer_transform
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        Rectangle::new(self.location.to_i32_round(), self.physical_size(scale))
+    }
+
+    fn damage_since(&self, scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        let physical_size = self.physical_size(scale);
+        let buffer_size = self.dmabuf.size().to_f64();
+        let surface_scale = physical_size.to_f64() / self.logical_size().to_physical(scale);
+
+        self.damage
+            .damage_since(commit)
+            .map(|damage| {
+                damage
+                    .into_iter()
+                    .map(|rect| {
+                        rect.to_f64()
+                            .to_logical(self.buffer_scale as f64, self.buffer_transform, &buffer_size)
+                            .to_physical_precise_up(surface_scale * scale)
+                    })
+                    .filter_map(|rect| rect.intersection(Rectangle::from_size(physical_size)))
+                    .collect::<DamageSet<_, _>>()
+            })
+            .unwrap_or_else(|| DamageSet::from_slice(&[Rectangle::from_size(physical_size)]))
+    }
+
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        if self.alpha < 1.0 || has_alpha(self.dmabuf.format().code) {
+            return OpaqueRegions::default();
+        }
+
+        OpaqueRegions::from_slice(&[Rectangle::from_size(self.physical_size(scale))])
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl<R> RenderElement<R> for RemoteSurfaceRenderElement<R>
+where
+    R: Renderer + ImportDma,
+    <R as Renderer>::TextureId: 'static,
+{
+    #[instrument(level = "trace", skip(self, frame))]
+    #[profiling::function]
+    fn draw<'a>(
+        &self,
+        frame: &mut <R as Renderer>::Frame<'a>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), <R as Renderer>::Error> {
+        frame.render_texture_from_to(
+            &self.texture,
+            src,
+            dst,
+            damage,
+            opaque_regions,
+            self.buffer_transform,
+            self.alpha,
+        )
+    }
+
+    #[inline]
+    fn underlying_storage(&self, _renderer: &mut R) -> Option<UnderlyingStorage<'_>> {
+        Some(UnderlyingStorage::External(&self.dmabuf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferInfo, Message};
+    use crate::{
+        backend::allocator::{dmabuf::DmabufFlags, Fourcc, Modifier},
+        utils::{Rectangle, Size, Transform},
+    };
+
+    #[test]
+    fn message_roundtrip() {
+        let messages = [
+            Message::NewBuffer {
+                id: 7,
+                info: BufferInfo {
+                    size: Size::from((1920, 1080)),
+                    code: Fourcc::Nv12,
+                    modifier: Modifier::Linear,
+                    flags: DmabufFlags::Y_INVERT,
+                    planes: vec![(0, 1920), (1920 * 1080, 1920)],
+                },
+            },
+            Message::Commit {
+                id: 7,
+                scale: 2,
+                transform: Transform::Flipped90,
+                damage: vec![Rectangle::new((10, 20).into(), (30, 40).into())],
+            },
+            Message::DestroyBuffer { id: 3 },
+            Message::Release { id: u64::MAX },
+        ];
+
+        let mut buf = Vec::new();
+        for msg in messages {
+            msg.encode(&mut buf);
+            assert_eq!(Message::decode(&buf).unwrap(), msg);
+            assert!(Message::decode(&buf[..buf.len() - 1]).is_err());
+        }
+    }
+}
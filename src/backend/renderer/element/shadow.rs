@@ -0,0 +1,483 @@
+//! Elements to render drop shadows and borders around windows
+//!
+//! # How to use it
+//!
+//! [`Shadow`] describes a soft drop shadow. The shadow is generated once as a small texture of a blurred
+//! corner, which is stretched to the size of the window using nine-slice scaling, so resizing a window
+//! doesn't require generating a new texture. Create a [`ShadowRenderElement`] for the geometry of the window
+//! in your render loop and render it below the window.
+//!
+//! [`Border`] describes a solid stroke around a window, e.g. to indicate focus. It is rendered
+//! using [`SolidColorRenderElement`]s.
+//!
+//! Both keep their element ids stable, so moving or resizing a window only damages the old and new
+//! area, while changing the parameters damages the whole element.
+//!
+//! ```no_run
+//! # use smithay::backend::renderer::{ImportMem, Renderer};
+//! use smithay::{
+//!     backend::renderer::{
+//!         element::{
+//!             shadow::{Border, Shadow, ShadowRenderElement},
+//!             Kind,
+//!         },
+//!         Color32F,
+//!     },
+//!     utils::{Physical, Rectangle, Scale},
+//! };
+//!
+//! # fn render<R: Renderer + ImportMem>(renderer: &mut R) where R::TextureId: Clone + Send + 'static {
+//! let shadow = Shadow::new(16, Color32F::new(0.0, 0.0, 0.0, 0.5));
+//! let border = Border::new(2, Color32F::new(0.3, 0.5, 0.9, 1.0));
+//!
+//! let geometry = Rectangle::<i32, Physical>::new((100, 100).into(), (800, 600).into());
+//! let scale = Scale::from(1.0);
+//! let borders = border.render_elements(geometry, scale, 1.0, Kind::Unspecified);
+//! let shadow = ShadowRenderElement::new(renderer, &shadow, geometry, scale, 1.0, Kind::Unspecified)
+//!     .expect("Failed to upload shadow");
+//! # }
+//! ```
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{instrument, trace};
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{utils::CommitCounter, Color32F, Frame, ImportMem, Renderer},
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+
+use super::{solid::SolidColorRenderElement, Element, Id, Kind, RenderElement, UnderlyingStorage};
+
+#[derive(Debug, Default)]
+struct ShadowTextures {
+    // pixel data of the nine-slice texture, generated on first use
+    data: Option<Arc<Vec<u8>>>,
+    textures: HashMap<(TypeId, usize), Box<dyn Any + Send>>,
+}
+
+/// A soft drop shadow
+#[derive(Debug, Clone)]
+pub struct Shadow {
+    id: Id,
+    commit: CommitCounter,
+    radius: i32,
+    color: Color32F,
+    offset: Point<i32, Logical>,
+    textures: Arc<Mutex<ShadowTextures>>,
+}
+
+impl Shadow {
+    /// Create a new shadow with the given blur radius and color
+    ///
+    /// The shadow extends `radius` beyond the window and fades in over the same distance.
+    pub fn new(radius: i32, color: impl Into<Color32F>) -> Self {
+        Shadow {
+            id: Id::new(),
+            commit: CommitCounter::default(),
+            radius: radius.max(0),
+            color: color.into(),
+            offset: Point::default(),
+            textures: Arc::default(),
+        }
+    }
+
+    /// Returns the blur radius of the shadow
+    pub fn radius(&self) -> i32 {
+        self.radius
+    }
+
+    /// Set the blur radius of the shadow
+    ///
+    /// Note: If the radius matches the current radius this will do nothing
+    pub fn set_radius(&mut self, radius: i32) {
+        let radius = radius.max(0);
+        if radius != self.radius {
+            self.radius = radius;
+            self.reset_textures();
+        }
+    }
+
+    /// Returns the color of the shadow
+    pub fn color(&self) -> Color32F {
+        self.color
+    }
+
+    /// Set the color of the shadow
+    ///
+    /// Note: If the color matches the current color this will do nothing
+    pub fn set_color(&mut self, color: impl Into<Color32F>) {
+        let color = color.into();
+        if color != self.color {
+            self.color = color;
+            self.reset_textures();
+        }
+    }
+
+    /// Returns the offset of the shadow relative to the window
+    pub fn offset(&self) -> Point<i32, Logical> {
+        self.offset
+    }
+
+    /// Set the offset of the shadow relative to the window
+    pub fn set_offset(&mut self, offset: impl Into<Point<i32, Logical>>) {
+        self.offset = offset.into();
+    }
+
+    fn reset_textures(&mut self) {
+        // clones of this shadow keep the old textures
+        self.textures = Arc::default();
+        self.commit.increment();
+    }
+
+    #[instrument(level = "trace", skip(self, renderer))]
+    #[profiling::function]
+    fn import_texture<R>(
+        &self,
+        renderer: &mut R,
+    ) -> Result<<R as Renderer>::TextureId, <R as Renderer>::Error>
+    where
+        R: Renderer + ImportMem,
+        <R as Renderer>::TextureId: Send + Clone + 'static,
+    {
+        let mut textures = self.textures.lock().unwrap();
+        let texture_id = (TypeId::of::<<R as Renderer>::TextureId>(), renderer.id());
+        if let Some(texture) = textures.textures.get(&texture_id) {
+            return Ok(texture.downcast_ref::<R::TextureId>().unwrap().clone());
+        }
+
+        let data = textures
+            .data
+            .get_or_insert_with(|| Arc::new(shadow_texture(self.radius, self.color)))
+            .clone();
+        let size = shadow_texture_size(self.radius);
+        trace!(size, "importing shadow texture");
+        let texture = renderer.import_memory(&data, Fourcc::Abgr8888, (size, size).into(), false)?;
+        textures.textures.insert(texture_id, Box::new(texture.clone()));
+        Ok(texture)
+    }
+}
+
+// Width of the faded band along the edges of the shadow, half outside and half inside the window
+fn shadow_edge(radius: i32) -> i32 {
+    2 * radius
+}
+
+fn shadow_texture_size(radius: i32) -> i32 {
+    2 * shadow_edge(radius) + 1
+}
+
+// Approximation of the error function (Abramowitz and Stegun 7.1.26)
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly =
+        t * (0.254_829_6 + t * (-0.284_496_7 + t * (1.421_413_8 + t * (-1.453_152 + t * 1.061_405_4))));
+    let y = 1.0 - poly * (-x * x).exp();
+    y.copysign(x)
+}
+
+// Premultiplied RGBA pixels of a blurred rectangle with corners of `shadow_edge(radius)` size
+fn shadow_texture(radius: i32, color: Color32F) -> Vec<u8> {
+    let size = shadow_texture_size(radius);
+    let sigma = radius as f32 / 2.0;
+
+    // coverage of a pixel at the given distance from the outer edge of the shadow
+    let profile = (0..size)
+        .map(|i| {
+            let dist = i.min(size - 1 - i) as f32 + 0.5 - radius as f32;
+            if sigma > 0.0 {
+                0.5 * (1.0 + erf(dist / (sigma * std::f32::consts::SQRT_2)))
+            } else {
+                1.0
+            }
+        })
+        .collect::<Vec<_>>();
+    // make sure the center is fully covered, so the stretched slices match up
+    let max = profile[size as usize / 2];
+
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size as usize {
+        for x in 0..size as usize {
+            let coverage = (profile[x] / max) * (profile[y] / max);
+            for channel in [color.r(), color.g(), color.b(), color.a()] {
+                data.push((channel * coverage * 255.0).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+    }
+    data
+}
+
+/// A render element for a [`Shadow`]
+#[derive(Debug)]
+pub struct ShadowRenderElement<R: Renderer> {
+    id: Id,
+    commit: CommitCounter,
+    geometry: Rectangle<i32, Physical>,
+    corner: i32,
+    edge: i32,
+    alpha: f32,
+    texture: R::TextureId,
+    kind: Kind,
+}
+
+impl<R: Renderer> ShadowRenderElement<R> {
+    /// Create a new [`ShadowRenderElement`] for a window with the given geometry
+    pub fn new(
+        renderer: &mut R,
+        shadow: &Shadow,
+        window_geometry: Rectangle<i32, Physical>,
+        scale: impl Into<Scale<f64>>,
+        alpha: f32,
+        kind: Kind,
+    ) -> Result<Self, <R as Renderer>::Error>
+    where
+        R: ImportMem,
+        <R as Renderer>::TextureId: Send + Clone + 'static,
+    {
+        let scale = scale.into();
+        let texture = shadow.import_texture(renderer)?;
+
+        let radius =
+            Size::<i32, Logical>::from((shadow.radius, shadow.radius)).to_physical_precise_round(scale);
+        let mut geometry = window_geometry;
+        geometry.loc += shadow.offset.to_physical_precise_round(scale);
+        let geometry = Rectangle::new(
+            geometry.loc - Point::from((radius.w, radius.h)),
+            geometry.size + radius + radius,
+        );
+        let corner = (radius.w.max(radius.h) * 2)
+            .min(geometry.size.w / 2)
+            .min(geometry.size.h / 2)
+            .max(0);
+
+        Ok(ShadowRenderElement {
+            id: shadow.id.clone(),
+            commit: shadow.commit,
+            geometry,
+            corner,
+            edge: shadow_edge(shadow.radius),
+            alpha,
+            texture,
+            kind,
+        })
+    }
+}
+
+impl<R: Renderer> Element for ShadowRenderElement<R> {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        let size = 2 * self.edge + 1;
+        Rectangle::from_size((size, size).into()).to_f64()
+    }
+
+    fn geometry(&self, _scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.geometry
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl<R> RenderElement<R> for ShadowRenderElement<R>
+where
+    R: Renderer + ImportMem,
+    <R as Renderer>::TextureId: 'static,
+{
+    #[instrument(level = "trace", skip(self, frame))]
+    #[profiling::function]
+    fn draw(
+        &self,
+        frame: &mut <R as Renderer>::Frame<'_>,
+        _src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), <R as Renderer>::Error> {
+        let corner = self.corner.min(dst.size.w / 2).min(dst.size.h / 2);
+        let dst_x = [0, corner, dst.size.w - corner, dst.size.w];
+        let dst_y = [0, corner, dst.size.h - corner, dst.size.h];
+        let edge = self.edge as f64;
+        let src_steps = [0.0, edge, edge + 1.0, 2.0 * edge + 1.0];
+
+        for y in 0..3 {
+            for x in 0..3 {
+                let slice = Rectangle::<i32, Physical>::from_extemities(
+                    (dst_x[x], dst_y[y]),
+                    (dst_x[x + 1], dst_y[y + 1]),
+                );
+                if slice.is_empty() {
+                    continue;
+                }
+
+                let slice_damage = damage
+                    .iter()
+                    .filter_map(|rect| rect.intersection(slice))
+                    .map(|mut rect| {
+                        rect.loc -= slice.loc;
+                        rect
+                    })
+                    .collect::<Vec<_>>();
+                if slice_damage.is_empty() {
+                    continue;
+                }
+
+                let src = Rectangle::<f64, Buffer>::from_extemities(
+                    (src_steps[x], src_steps[y]),
+                    (src_steps[x + 1], src_steps[y + 1]),
+                );
+                let slice_dst = Rectangle::new(dst.loc + slice.loc, slice.size);
+                frame.render_texture_from_to(
+                    &self.texture,
+                    src,
+                    slice_dst,
+                    &slice_damage,
+                    &[],
+                    Transform::Normal,
+                    self.alpha,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn underlying_storage(&self, _renderer: &mut R) -> Option<UnderlyingStorage<'_>> {
+        None
+    }
+}
+
+/// A solid stroke around a window
+#[derive(Debug, Clone)]
+pub struct Border {
+    ids: [Id; 4],
+    commit: CommitCounter,
+    width: i32,
+    color: Color32F,
+}
+
+impl Border {
+    /// Create a new border with the given width and color
+    pub fn new(width: i32, color: impl Into<Color32F>) -> Self {
+        Border {
+            ids: [Id::new(), Id::new(), Id::new(), Id::new()],
+            commit: CommitCounter::default(),
+            width: width.max(0),
+            color: color.into(),
+        }
+    }
+
+    /// Returns the width of the border
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Set the width of the border
+    pub fn set_width(&mut self, width: i32) {
+        let width = width.max(0);
+        if width != self.width {
+            self.width = width;
+            self.commit.increment();
+        }
+    }
+
+    /// Returns the color of the border
+    pub fn color(&self) -> Color32F {
+        self.color
+    }
+
+    /// Set the color of the border, e.g. to indicate focus
+    ///
+    /// Note: If the color matches the current color this will do nothing
+    pub fn set_color(&mut self, color: impl Into<Color32F>) {
+        let color = color.into();
+        if color != self.color {
+            self.color = color;
+            self.commit.increment();
+        }
+    }
+
+    /// Create the elements of the border around a window with the given geometry
+    ///
+    /// The border is drawn outside of the window geometry.
+    /// Returns no elements, if the width of the border is zero.
+    pub fn render_elements(
+        &self,
+        window_geometry: Rectangle<i32, Physical>,
+        scale: impl Into<Scale<f64>>,
+        alpha: f32,
+        kind: Kind,
+    ) -> Vec<SolidColorRenderElement> {
+        let width = Size::<i32, Logical>::from((self.width, self.width)).to_physical_precise_round(scale);
+        if width.is_empty() {
+            return Vec::new();
+        }
+
+        let Rectangle { loc, size } = window_geometry;
+        let outer_w = size.w + 2 * width.w;
+        let rects = [
+            Rectangle::new(
+                (loc.x - width.w, loc.y - width.h).into(),
+                (outer_w, width.h).into(),
+            ),
+            Rectangle::new(
+                (loc.x - width.w, loc.y + size.h).into(),
+                (outer_w, width.h).into(),
+            ),
+            Rectangle::new((loc.x - width.w, loc.y).into(), (width.w, size.h).into()),
+            Rectangle::new((loc.x + size.w, loc.y).into(), (width.w, size.h).into()),
+        ];
+
+        let color = self.color * alpha;
+        self.ids
+            .iter()
+            .zip(rects)
+            .map(|(id, rect)| SolidColorRenderElement::new(id.clone(), rect, self.commit, color, kind))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shadow_texture, shadow_texture_size};
+    use crate::backend::renderer::Color32F;
+
+    #[test]
+    fn shadow_texture_fades_out() {
+        let radius = 8;
+        let size = shadow_texture_size(radius) as usize;
+        let data = shadow_texture(radius, Color32F::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(data.len(), size * size * 4);
+
+        let alpha = |x: usize, y: usize| data[(y * size + x) * 4 + 3];
+        let center = size / 2;
+        assert_eq!(alpha(center, center), 255);
+        assert!(alpha(0, 0) < 5);
+        assert!(alpha(0, center) < alpha(radius as usize, center));
+        assert!(alpha(radius as usize, center) < alpha(center, center));
+        // symmetric
+        assert_eq!(alpha(1, center), alpha(size - 2, center));
+
+        let hard = shadow_texture(0, Color32F::new(0.0, 0.0, 0.0, 0.5));
+        assert_eq!(hard, vec![0, 0, 0, 128]);
+    }
+}
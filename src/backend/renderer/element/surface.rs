@@ -9,6 +9,8 @@
 //!
 //! [`WaylandSurfaceRenderElement::from_surface`] allows you to obtain a [`WaylandSurfaceRenderElement`] for a single [`WlSurface`](wayland_server::protocol::wl_surface::WlSurface).
 //! To retrieve [`WaylandSurfaceRenderElement`]s for a whole surface tree you can use [`render_elements_from_surface_tree`].
+//! [`WaylandSurfaceRenderElement::with_crop`] and [`WaylandSurfaceRenderElement::with_scale`] show only a part of a surface
+//! or resize it independently of the viewport state of the client, e.g. to show a preview of a window.
 //!
 //! ```no_run
 //! # #[cfg(all(
@@ -219,7 +221,10 @@
 //! }
 //! ```
 
-use std::fmt;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
 
 use tracing::{instrument, warn};
 use wayland_server::protocol::wl_surface;
@@ -310,6 +315,64 @@ where
     surfaces
 }
 
+// Number of scales, e.g. of different outputs, the physical view of a surface is cached for
+const MAX_SCALED_VIEWS: usize = 4;
+// Number of previous commits damage is cached for per scale
+const MAX_CACHED_DAMAGE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScaledViewKey {
+    scale: Scale<f64>,
+    // the rounding of the physical size depends on the sub-pixel location
+    location_fract: Point<f64, Physical>,
+    view: SurfaceView,
+}
+
+#[derive(Debug)]
+struct ScaledView {
+    key: ScaledViewKey,
+    opaque_regions: Option<Vec<Rectangle<i32, Physical>>>,
+    damage: Vec<(Option<CommitCounter>, Vec<Rectangle<i32, Physical>>)>,
+}
+
+/// Cache of the physical damage and opaque regions of a surface per scale
+///
+/// Rendering a surface on multiple outputs with different (fractional) scales would otherwise
+/// recompute these for every output or evict the values of the other outputs.
+#[derive(Debug, Default)]
+pub(crate) struct ScaledViewCache {
+    commit: CommitCounter,
+    views: Vec<ScaledView>,
+}
+
+impl ScaledViewCache {
+    fn get(&mut self, commit: CommitCounter, key: ScaledViewKey) -> &mut ScaledView {
+        if self.commit != commit {
+            self.commit = commit;
+            self.views.clear();
+        }
+
+        match self.views.iter().position(|view| view.key == key) {
+            Some(idx) => {
+                // keep the most recently used view last
+                let view = self.views.remove(idx);
+                self.views.push(view);
+            }
+            None => {
+                if self.views.len() >= MAX_SCALED_VIEWS {
+                    self.views.remove(0);
+                }
+                self.views.push(ScaledView {
+                    key,
+                    opaque_regions: None,
+                    damage: Vec::new(),
+                });
+            }
+        }
+        self.views.last_mut().unwrap()
+    }
+}
+
 /// Texture used for the [`WaylandSurfaceRenderElement`]
 #[derive(Debug)]
 pub enum WaylandSurfaceTexture<R: Renderer> {
@@ -334,6 +397,7 @@ pub struct WaylandSurfaceRenderElement<R: Renderer> {
     damage: DamageSnapshot<i32, BufferCoords>,
     opaque_regions: OpaqueRegions<i32, Logical>,
     texture: WaylandSurfaceTexture<R>,
+    scaled_views: Arc<Mutex<ScaledViewCache>>,
 }
 
 impl<R: Renderer> fmt::Debug for WaylandSurfaceRenderElement<R> {
@@ -413,6 +477,7 @@ impl<R: Renderer + ImportAll> WaylandSurfaceRenderElement<R> {
                 .map(OpaqueRegions::from_slice)
                 .unwrap_or_default(),
             texture,
+            scaled_views: data.scaled_views.clone(),
         })
     }
 
@@ -422,6 +487,16 @@ impl<R: Renderer + ImportAll> WaylandSurfaceRenderElement<R> {
         .to_size()
     }
 
+    fn with_scaled_view<T>(&self, scale: Scale<f64>, f: impl FnOnce(&mut ScaledView) -> T) -> T {
+        let key = ScaledViewKey {
+            scale,
+            location_fract: self.location - self.location.to_i32_floor::<i32>().to_f64(),
+            view: self.view,
+        };
+        let mut cache = self.scaled_views.lock().unwrap();
+        f(cache.get(self.damage.current_commit(), key))
+    }
+
     /// Get the buffer dimensions in logical coordinates
     pub fn buffer_size(&self) -> Size<i32, Logical> {
         self.buffer_dimensions
@@ -437,6 +512,56 @@ impl<R: Renderer + ImportAll> WaylandSurfaceRenderElement<R> {
     pub fn texture(&self) -> &WaylandSurfaceTexture<R> {
         &self.texture
     }
+
+    /// Only show a part of the surface
+    ///
+    /// The crop is given in surface-local logical coordinates, after the viewport of the client
+    /// has been applied, and is clamped to the surface. The top-left corner of the cropped area is
+    /// rendered at the location of the element, e.g. cropping to `(0, 0, width, 20)` only shows the
+    /// top 20 pixels of the surface. Damage and opaque regions are cropped accordingly.
+    pub fn with_crop(mut self, crop: Rectangle<f64, Logical>) -> Self {
+        let Some(crop) = crop.intersection(Rectangle::from_size(self.view.dst.to_f64())) else {
+            self.view.dst = Size::default();
+            self.opaque_regions = OpaqueRegions::default();
+            return self;
+        };
+
+        self.opaque_regions = self
+            .opaque_regions
+            .iter()
+            .filter_map(|region| region.to_f64().intersection(crop))
+            .map(|region| Rectangle::new(region.loc - crop.loc, region.size).to_i32_down())
+            .filter(|region| !region.is_empty())
+            .collect();
+        self.view = SurfaceView {
+            src: self.view.rect_to_local(crop),
+            dst: crop.size.to_i32_round(),
+            offset: self.view.offset,
+        };
+        self
+    }
+
+    /// Scale the surface, independently of the viewport state of the client
+    ///
+    /// The element keeps its location and is resized by `scale`, e.g. for a picture-in-picture
+    /// copy of a window. Damage and opaque regions are scaled accordingly.
+    pub fn with_scale(mut self, scale: impl Into<Scale<f64>>) -> Self {
+        if self.view.dst.is_empty() {
+            return self;
+        }
+
+        let dst = self.view.dst.to_f64().upscale(scale.into()).to_i32_round();
+        // scale by the rounded size, so the regions match the rendered size
+        let scale = dst.to_f64() / self.view.dst.to_f64();
+        self.opaque_regions = self
+            .opaque_regions
+            .iter()
+            .map(|region| region.to_f64().upscale(scale).to_i32_down())
+            .filter(|region| !region.is_empty())
+            .collect();
+        self.view.dst = dst;
+        self
+    }
 }
 
 impl<R: Renderer + ImportAll> Element for WaylandSurfaceRenderElement<R> {
@@ -465,6 +590,52 @@ impl<R: Renderer + ImportAll> Element for WaylandSurfaceRenderElement<R> {
     }
 
     fn damage_since(&self, scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        self.with_scaled_view(scale, |view| {
+            if let Some((_, damage)) = view.damage.iter().find(|(since, _)| *since == commit) {
+                return DamageSet::from_slice(damage);
+            }
+
+            let damage = self.physical_damage_since(scale, commit);
+            if view.damage.len() >= MAX_CACHED_DAMAGE {
+                view.damage.remove(0);
+            }
+            view.damage.push((commit, damage.to_vec()));
+            damage
+        })
+    }
+
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        if self.alpha < 1.0 {
+            return OpaqueRegions::default();
+        }
+
+        self.with_scaled_view(scale, |view| {
+            let regions = view
+                .opaque_regions
+                .get_or_insert_with(|| self.physical_opaque_regions(scale).to_vec());
+            OpaqueRegions::from_slice(regions)
+        })
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl<R: Renderer + ImportAll> WaylandSurfaceRenderElement<R> {
+    fn physical_damage_since(
+        &self,
+        scale: Scale<f64>,
+        commit: Option<CommitCounter>,
+    ) -> DamageSet<i32, Physical> {
+        if self.view.dst.is_empty() {
+            return DamageSet::default();
+        }
+
         let dst_size = self.size(scale);
         self.damage
             .damage_since(commit)
@@ -499,11 +670,7 @@ impl<R: Renderer + ImportAll> Element for WaylandSurfaceRenderElement<R> {
             .collect::<DamageSet<_, _>>()
     }
 
-    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
-        if self.alpha < 1.0 {
-            return OpaqueRegions::default();
-        }
-
+    fn physical_opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
         self.opaque_regions
             .iter()
             .map(|r| {
@@ -515,14 +682,6 @@ impl<R: Renderer + ImportAll> Element for WaylandSurfaceRenderElement<R> {
             })
             .collect::<OpaqueRegions<_, _>>()
     }
-
-    fn alpha(&self) -> f32 {
-        self.alpha
-    }
-
-    fn kind(&self) -> Kind {
-        self.kind
-    }
 }
 
 impl<R> RenderElement<R> for WaylandSurfaceRenderElement<R>
@@ -545,6 +704,13 @@ where
         damage: &[Rectangle<i32, Physical>],
         opaque_regions: &[Rectangle<i32, Physical>],
     ) -> Result<(), R::Error> {
+        // Wait for the client's rendering on the gpu, in case the surface transaction was
+        // released before the acquire point got signaled
+        #[cfg(feature = "backend_drm")]
+        if let Some(acquire_point) = self.buffer.pending_acquire_point() {
+            frame.wait(&acquire_point)?;
+        }
+
         match self.texture {
             WaylandSurfaceTexture::Texture(ref texture) => frame.render_texture_from_to(
                 texture,
@@ -559,3 +725,153 @@ where
         }
     }
 }
+
+#[cfg(all(test, feature = "renderer_test"))]
+mod tests {
+    use super::WaylandSurfaceRenderElement;
+    use crate::{
+        backend::renderer::{
+            element::{Element, Kind},
+            test::DummyRenderer,
+            utils::CommitCounter,
+        },
+        utils::{Rectangle, Scale},
+        wayland::{compositor, test_utils::TestFixture},
+    };
+
+    fn element(
+        renderer: &mut DummyRenderer,
+        surface: &wayland_server::protocol::wl_surface::WlSurface,
+    ) -> WaylandSurfaceRenderElement<DummyRenderer> {
+        compositor::with_states(surface, |states| {
+            WaylandSurfaceRenderElement::from_surface(
+                renderer,
+                surface,
+                states,
+                (0.0, 0.0).into(),
+                1.0,
+                Kind::Unspecified,
+            )
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    // A 100x100 surface with an opaque top half and a following commit damaging `damage`
+    //
+    // Returns the commit of the element before the damage.
+    fn setup(
+        renderer: &mut DummyRenderer,
+        damage: (i32, i32, i32, i32),
+    ) -> (
+        TestFixture,
+        wayland_server::protocol::wl_surface::WlSurface,
+        CommitCounter,
+    ) {
+        let mut fixture = TestFixture::new();
+        let (surface, server_surface) = fixture.create_surface();
+        let region = fixture.create_region(&[(0, 0, 100, 50)]);
+        surface.set_opaque_region(Some(&region));
+        fixture.map(&surface, 100, 100);
+        let previous = element(renderer, &server_surface).current_commit();
+
+        let buffer = fixture.create_buffer(100, 100);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.damage_buffer(damage.0, damage.1, damage.2, damage.3);
+        surface.commit();
+        fixture.roundtrip();
+        (fixture, server_surface, previous)
+    }
+
+    #[test]
+    fn crop_maps_damage_and_opaque_regions() {
+        let mut renderer = DummyRenderer::new();
+        let (_fixture, surface, previous) = setup(&mut renderer, (30, 30, 10, 10));
+        let previous = Some(previous);
+        let element = element(&mut renderer, &surface);
+        let element = element.with_crop(Rectangle::new((10.0, 20.0).into(), (50.0, 40.0).into()));
+
+        let scale = Scale::from(1.0);
+        assert_eq!(element.geometry(scale), Rectangle::from_size((50, 40).into()));
+        assert_eq!(
+            element.opaque_regions(scale).to_vec(),
+            vec![Rectangle::from_size((50, 30).into())]
+        );
+        assert_eq!(
+            element.damage_since(scale, previous).to_vec(),
+            vec![Rectangle::new((20, 10).into(), (10, 10).into())]
+        );
+        assert_eq!(
+            element.damage_since(scale, None).to_vec(),
+            vec![Rectangle::from_size((50, 40).into())]
+        );
+    }
+
+    #[test]
+    fn crop_drops_damage_outside_of_the_crop() {
+        let mut renderer = DummyRenderer::new();
+        let (_fixture, surface, previous) = setup(&mut renderer, (80, 80, 10, 10));
+        let previous = Some(previous);
+        let element = element(&mut renderer, &surface);
+        let element = element.with_crop(Rectangle::new((0.0, 60.0).into(), (50.0, 40.0).into()));
+
+        let scale = Scale::from(1.0);
+        assert!(element.damage_since(scale, previous).is_empty());
+        assert!(element.opaque_regions(scale).is_empty());
+
+        // a crop outside of the surface hides it entirely
+        let element = element.with_crop(Rectangle::new((200.0, 200.0).into(), (10.0, 10.0).into()));
+        assert!(element.geometry(scale).is_empty());
+        assert!(element.damage_since(scale, None).is_empty());
+    }
+
+    #[test]
+    fn scale_maps_damage_and_opaque_regions() {
+        let mut renderer = DummyRenderer::new();
+        let (_fixture, surface, previous) = setup(&mut renderer, (30, 30, 10, 10));
+        let previous = Some(previous);
+        let element = element(&mut renderer, &surface);
+        let element = element.with_scale(2.0);
+
+        let scale = Scale::from(1.0);
+        assert_eq!(element.geometry(scale), Rectangle::from_size((200, 200).into()));
+        assert_eq!(
+            element.opaque_regions(scale).to_vec(),
+            vec![Rectangle::from_size((200, 100).into())]
+        );
+        assert_eq!(
+            element.damage_since(scale, previous).to_vec(),
+            vec![Rectangle::new((60, 60).into(), (20, 20).into())]
+        );
+
+        // the output scale applies on top
+        let scale = Scale::from(1.5);
+        assert_eq!(element.geometry(scale), Rectangle::from_size((300, 300).into()));
+        assert_eq!(
+            element.damage_since(scale, previous).to_vec(),
+            vec![Rectangle::new((90, 90).into(), (30, 30).into())]
+        );
+    }
+
+    #[test]
+    fn crop_and_scale() {
+        let mut renderer = DummyRenderer::new();
+        let (_fixture, surface, previous) = setup(&mut renderer, (30, 30, 10, 10));
+        let previous = Some(previous);
+        let element = element(&mut renderer, &surface);
+        let element = element
+            .with_crop(Rectangle::new((10.0, 20.0).into(), (50.0, 40.0).into()))
+            .with_scale(0.5);
+
+        let scale = Scale::from(1.0);
+        assert_eq!(element.geometry(scale), Rectangle::from_size((25, 20).into()));
+        assert_eq!(
+            element.opaque_regions(scale).to_vec(),
+            vec![Rectangle::from_size((25, 15).into())]
+        );
+        assert_eq!(
+            element.damage_since(scale, previous).to_vec(),
+            vec![Rectangle::new((10, 5).into(), (5, 5).into())]
+        );
+    }
+}
@@ -25,7 +25,7 @@ pub fn select_dmabuf_feedback<'a>(
             Some(RenderingReason::FormatUnsupported) | Some(RenderingReason::ScanoutFailed) => {
                 scanout_feedback
             }
-            None => default_feedback,
+            Some(RenderingReason::FenceNotSignaled) | None => default_feedback,
         },
         RenderElementPresentationState::ZeroCopy => scanout_feedback,
         RenderElementPresentationState::Skipped => default_feedback,
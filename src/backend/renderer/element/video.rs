@@ -0,0 +1,752 @@
+//! Element to render video frames
+//!
+//! # Why use this implementation
+//!
+//! Media frameworks like gstreamer (using an `appsink`) or libmpv (using its render API) can produce
+//! decoded video frames as dmabufs. The [`VideoSink`] receives these frames, possibly from another thread,
+//! and selects the frame to show for a given presentation time. The [`VideoRenderElement`] imports
+//! the selected frame into the renderer, waits for the frame to be ready before sampling from it and
+//! allows the frame to be directly scanned out.
+//!
+//! This makes it possible to play videos as part of the compositor, like boot animations, wallpapers
+//! or kiosk content, without dealing with frame timing and synchronization yourself.
+//!
+//! # Why **not** to use this implementation
+//!
+//! The sink only handles video frames already decoded into dmabufs, decoding and audio playback
+//! are left to the media framework. Frames backed by system memory should use a
+//! [`MemoryRenderBuffer`](super::memory::MemoryRenderBuffer) instead.
+//!
+//! # How to use it
+//!
+//! Create a [`VideoSink`] and push every decoded frame with its presentation timestamp using
+//! [`VideoSink::push`], e.g. from the render callback of libmpv. Timestamps are relative
+//! to the start of the stream, playback starts when the first frame is shown.
+//!
+//! With the `video_gstreamer` feature, [`VideoSink::appsink`] creates a gstreamer `appsink` element
+//! negotiating dmabuf memory and pushing the decoded frames into the sink, so it can be used as
+//! the video sink of a pipeline (e.g. `playbin`).
+//!
+//! In your render loop call [`VideoSink::update`] with the estimated presentation time of the next frame
+//! and create a [`VideoRenderElement`] from the sink. Use [`VideoSink::next_frame_time`] to schedule
+//! rendering of the next video frame.
+//!
+//! ```no_run
+//! # use smithay::backend::{allocator::dmabuf::Dmabuf, renderer::{ImportDma, Renderer}};
+//! use std::time::Duration;
+//!
+//! use smithay::{
+//!     backend::renderer::element::{
+//!         video::{VideoFrame, VideoRenderElement, VideoSink},
+//!         Kind,
+//!     },
+//!     utils::{Monotonic, Point, Time},
+//! };
+//!
+//! # fn decoded_frame() -> (Dmabuf, Duration) { unimplemented!() }
+//! # fn render<R: Renderer + ImportDma>(renderer: &mut R, now: Time<Monotonic>) where R::TextureId: Clone + Send + 'static {
+//! let sink = VideoSink::new();
+//!
+//! // on the decoder thread
+//! let producer = sink.clone();
+//! std::thread::spawn(move || loop {
+//!     let (dmabuf, pts) = decoded_frame();
+//!     producer.push(VideoFrame::new(dmabuf, pts));
+//! });
+//!
+//! // in the render loop
+//! sink.update(now);
+//! let element = VideoRenderElement::from_sink(renderer, Point::from((0.0, 0.0)), &sink, None, None, Kind::Unspecified)
+//!     .expect("Failed to import video frame");
+//! # }
+//! ```
+
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "video_gstreamer")]
+use tracing::warn;
+use tracing::{instrument, trace};
+
+use crate::{
+    backend::{
+        allocator::{dmabuf::Dmabuf, format::has_alpha, Buffer as _},
+        renderer::{
+            sync::SyncPoint,
+            utils::{CommitCounter, DamageSet, OpaqueRegions},
+            Frame, ImportDma, Renderer,
+        },
+    },
+    utils::{Buffer, Logical, Monotonic, Physical, Point, Rectangle, Scale, Size, Time, Transform},
+};
+
+use super::{Element, Id, Kind, RenderElement, UnderlyingStorage};
+
+/// A decoded video frame
+#[derive(Clone)]
+pub struct VideoFrame {
+    dmabuf: Dmabuf,
+    pts: Duration,
+    sync: SyncPoint,
+    _owner: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for VideoFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VideoFrame")
+            .field("dmabuf", &self.dmabuf)
+            .field("pts", &self.pts)
+            .field("sync", &self.sync)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VideoFrame {
+    /// Create a new video frame with its presentation timestamp relative to the start of the stream
+    pub fn new(dmabuf: Dmabuf, pts: Duration) -> Self {
+        VideoFrame {
+            dmabuf,
+            pts,
+            sync: SyncPoint::signaled(),
+            _owner: None,
+        }
+    }
+
+    /// Keep `owner` alive as long as this frame is in use
+    ///
+    /// Media frameworks usually re-use the buffers of their decoders once they are released.
+    /// Attaching the buffer of the framework prevents the frame from being overwritten while it is shown.
+    pub fn with_owner(mut self, owner: impl Any + Send + Sync) -> Self {
+        self._owner = Some(Arc::new(owner));
+        self
+    }
+
+    /// Set a [`SyncPoint`] signaling when the decoder finished writing the frame
+    ///
+    /// Frames are not shown before the sync point is reached.
+    pub fn with_sync(mut self, sync: SyncPoint) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Returns the dmabuf of this frame
+    pub fn dmabuf(&self) -> &Dmabuf {
+        &self.dmabuf
+    }
+
+    /// Returns the presentation timestamp of this frame
+    pub fn pts(&self) -> Duration {
+        self.pts
+    }
+}
+
+const DEFAULT_MAX_QUEUED: usize = 4;
+
+#[derive(Debug)]
+struct VideoSinkInner {
+    queue: VecDeque<VideoFrame>,
+    max_queued: usize,
+    current: Option<VideoFrame>,
+    commit: CommitCounter,
+    // presentation time of the first shown frame and its timestamp
+    start: Option<(Time<Monotonic>, Duration)>,
+    textures: HashMap<(TypeId, usize), Box<dyn Any + Send>>,
+}
+
+impl VideoSinkInner {
+    // presentation time a frame is due at
+    fn due(&self, pts: Duration) -> Option<Time<Monotonic>> {
+        let (start, first_pts) = self.start?;
+        Some(start + pts.saturating_sub(first_pts))
+    }
+}
+
+/// Receives decoded video frames and selects the frame to show
+///
+/// The sink can be cloned and sent to the thread decoding the video.
+#[derive(Debug, Clone)]
+pub struct VideoSink {
+    id: Id,
+    inner: Arc<Mutex<VideoSinkInner>>,
+}
+
+impl Default for VideoSink {
+    fn default() -> Self {
+        VideoSink {
+            id: Id::new(),
+            inner: Arc::new(Mutex::new(VideoSinkInner {
+                queue: VecDeque::new(),
+                max_queued: DEFAULT_MAX_QUEUED,
+                current: None,
+                commit: CommitCounter::default(),
+                start: None,
+                textures: HashMap::new(),
+            })),
+        }
+    }
+}
+
+impl VideoSink {
+    /// Create a new empty video sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a decoded frame
+    ///
+    /// If more than [`VideoSink::max_queued`] frames are waiting to be shown, the oldest frame is dropped.
+    pub fn push(&self, frame: VideoFrame) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.queue.push_back(frame);
+        while inner.queue.len() > inner.max_queued {
+            inner.queue.pop_front();
+        }
+    }
+
+    /// Returns the maximum number of frames waiting to be shown
+    pub fn max_queued(&self) -> usize {
+        self.inner.lock().unwrap().max_queued
+    }
+
+    /// Set the maximum number of frames waiting to be shown
+    ///
+    /// Defaults to 4. Decoders should stop producing frames while the queue is full,
+    /// to not drop frames, see [`VideoSink::queued`].
+    pub fn set_max_queued(&self, max_queued: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.max_queued = max_queued.max(1);
+        while inner.queue.len() > inner.max_queued {
+            inner.queue.pop_front();
+        }
+    }
+
+    /// Returns the number of frames waiting to be shown
+    pub fn queued(&self) -> usize {
+        self.inner.lock().unwrap().queue.len()
+    }
+
+    /// Returns the frame currently shown
+    pub fn current_frame(&self) -> Option<VideoFrame> {
+        self.inner.lock().unwrap().current.clone()
+    }
+
+    /// Drop all queued frames and restart the playback clock
+    ///
+    /// This should be called when seeking or restarting the stream. The current frame is kept
+    /// until a new frame is shown.
+    pub fn reset(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.queue.clear();
+        inner.start = None;
+    }
+
+    /// Advance to the frame to show at `presentation_time`
+    ///
+    /// Selects the latest queued frame, which is due at `presentation_time` and whose
+    /// sync point is reached. Older frames are dropped. Playback starts with the first frame
+    /// shown, if playback didn't start yet.
+    ///
+    /// Returns if the shown frame changed.
+    pub fn update(&self, presentation_time: Time<Monotonic>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.start.is_none() {
+            match inner.queue.front() {
+                Some(frame) if frame.sync.is_reached() => {
+                    inner.start = Some((presentation_time, frame.pts));
+                }
+                _ => return false,
+            }
+        }
+
+        let mut next = None;
+        while let Some(frame) = inner.queue.front() {
+            let due = inner.due(frame.pts).unwrap();
+            if due > presentation_time || !frame.sync.is_reached() {
+                break;
+            }
+            next = inner.queue.pop_front();
+        }
+
+        match next {
+            Some(frame) => {
+                trace!(pts = ?frame.pts, "Showing next video frame");
+                inner.current = Some(frame);
+                inner.commit.increment();
+                inner.textures.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the presentation time the next queued frame is due at
+    ///
+    /// Returns `None`, if no frame is queued or playback didn't start yet.
+    pub fn next_frame_time(&self) -> Option<Time<Monotonic>> {
+        let inner = self.inner.lock().unwrap();
+        inner.queue.front().and_then(|frame| inner.due(frame.pts))
+    }
+
+    /// Create a gstreamer `appsink` element pushing its frames into this sink
+    ///
+    /// The element only accepts linear dmabufs of the formats supported by [`VideoFrame::from_gst_sample`],
+    /// so the decoder or a converter before it has to provide dmabuf memory. Frames without dmabuf memory
+    /// are dropped. The element synchronizes against the pipeline clock, so frames arrive shortly before
+    /// they are due and the queue of the sink only has to cover the latency of the render loop.
+    #[cfg(feature = "video_gstreamer")]
+    pub fn appsink(&self) -> gstreamer_app::AppSink {
+        let caps = gstreamer_video::VideoCapsBuilder::new()
+            .features([gstreamer_allocators::CAPS_FEATURE_MEMORY_DMABUF])
+            .format_list(GST_FORMATS.iter().map(|(format, _)| *format))
+            .build();
+
+        let sink = self.clone();
+        gstreamer_app::AppSink::builder()
+            .caps(&caps)
+            .max_buffers(self.max_queued() as u32)
+            .callbacks(
+                gstreamer_app::AppSinkCallbacks::builder()
+                    .new_sample(move |appsink| {
+                        let sample = appsink.pull_sample().map_err(|_| gstreamer::FlowError::Eos)?;
+                        match VideoFrame::from_gst_sample(&sample) {
+                            Some(frame) => sink.push(frame),
+                            None => warn!("Dropping video frame without dmabuf memory"),
+                        }
+                        Ok(gstreamer::FlowSuccess::Ok)
+                    })
+                    .build(),
+            )
+            .build()
+    }
+}
+
+// gstreamer formats are named by byte order, drm formats by the order of a little-endian word
+#[cfg(feature = "video_gstreamer")]
+const GST_FORMATS: &[(gstreamer_video::VideoFormat, crate::backend::allocator::Fourcc)] = {
+    use crate::backend::allocator::Fourcc;
+    use gstreamer_video::VideoFormat;
+    &[
+        (VideoFormat::Bgrx, Fourcc::Xrgb8888),
+        (VideoFormat::Bgra, Fourcc::Argb8888),
+        (VideoFormat::Rgbx, Fourcc::Xbgr8888),
+        (VideoFormat::Rgba, Fourcc::Abgr8888),
+        (VideoFormat::Xrgb, Fourcc::Bgrx8888),
+        (VideoFormat::Argb, Fourcc::Bgra8888),
+        (VideoFormat::Xbgr, Fourcc::Rgbx8888),
+        (VideoFormat::Abgr, Fourcc::Rgba8888),
+        (VideoFormat::Rgb, Fourcc::Bgr888),
+        (VideoFormat::Bgr, Fourcc::Rgb888),
+        (VideoFormat::Nv12, Fourcc::Nv12),
+        (VideoFormat::Nv21, Fourcc::Nv21),
+        (VideoFormat::I420, Fourcc::Yuv420),
+        (VideoFormat::Yv12, Fourcc::Yvu420),
+    ]
+};
+
+#[cfg(feature = "video_gstreamer")]
+impl VideoFrame {
+    /// Create a frame from a gstreamer sample backed by dmabuf memory
+    ///
+    /// The buffer of the sample is kept alive as long as the frame is in use. The presentation timestamp
+    /// of the buffer is used as the timestamp of the frame.
+    ///
+    /// Returns `None`, if the sample has no timestamp, is not backed by dmabuf memory or uses a
+    /// format without a matching drm fourcc.
+    pub fn from_gst_sample(sample: &gstreamer::Sample) -> Option<VideoFrame> {
+        use std::os::unix::io::BorrowedFd;
+
+        use crate::backend::allocator::{dmabuf::DmabufFlags, Modifier};
+
+        let buffer = sample.buffer_owned()?;
+        let info = gstreamer_video::VideoInfo::from_caps(sample.caps()?).ok()?;
+        let (_, fourcc) = GST_FORMATS.iter().find(|(format, _)| *format == info.format())?;
+        let pts = Duration::from_nanos(buffer.pts()?.nseconds());
+
+        // the video meta describes the actual layout, if the producer padded the planes
+        let meta = buffer.meta::<gstreamer_video::VideoMeta>();
+        let (offsets, strides) = match &meta {
+            Some(meta) => (meta.offset(), meta.stride()),
+            None => (info.offset(), info.stride()),
+        };
+
+        let mut builder = Dmabuf::builder(
+            (info.width() as i32, info.height() as i32),
+            *fourcc,
+            Modifier::Linear,
+            DmabufFlags::empty(),
+        );
+        for (idx, (offset, stride)) in offsets.iter().zip(strides).enumerate() {
+            let (memories, skip) = buffer.find_memory(*offset..*offset + 1)?;
+            let memory = buffer.peek_memory(memories.start);
+            let dmabuf_memory = memory.downcast_memory_ref::<gstreamer_allocators::DmaBufMemory>()?;
+            // SAFETY: the fd is owned by the memory, which is alive while we duplicate it
+            let fd = unsafe { BorrowedFd::borrow_raw(dmabuf_memory.fd()) }
+                .try_clone_to_owned()
+                .ok()?;
+            builder.add_plane(fd, idx as u32, (memory.offset() + skip) as u32, *stride as u32);
+        }
+
+        Some(VideoFrame::new(builder.build()?, pts).with_owner(buffer))
+    }
+}
+
+/// A render element for the current frame of a [`VideoSink`]
+#[derive(Debug)]
+pub struct VideoRenderElement<R: Renderer> {
+    id: Id,
+    location: Point<f64, Physical>,
+    dmabuf: Dmabuf,
+    sync: SyncPoint,
+    commit: CommitCounter,
+    size: Size<i32, Logical>,
+    alpha: f32,
+    texture: R::TextureId,
+    kind: Kind,
+}
+
+impl<R: Renderer> VideoRenderElement<R> {
+    /// Create a new [`VideoRenderElement`] for the current frame of a [`VideoSink`]
+    ///
+    /// The frame is shown at its buffer size, unless a `size` is given.
+    /// Returns `None`, if the sink has no frame to show yet.
+    #[instrument(level = "trace", skip(renderer, location, sink))]
+    #[profiling::function]
+    pub fn from_sink(
+        renderer: &mut R,
+        location: impl Into<Point<f64, Physical>>,
+        sink: &VideoSink,
+        alpha: Option<f32>,
+        size: Option<Size<i32, Logical>>,
+        kind: Kind,
+    ) -> Result<Option<Self>, <R as Renderer>::Error>
+    where
+        R: ImportDma,
+        <R as Renderer>::TextureId: Send + Clone + 'static,
+    {
+        let mut inner = sink.inner.lock().unwrap();
+        let Some(frame) = inner.current.clone() else {
+            return Ok(None);
+        };
+
+        let texture_id = (TypeId::of::<<R as Renderer>::TextureId>(), renderer.id());
+        let texture = match inner.textures.get(&texture_id) {
+            Some(texture) => texture.downcast_ref::<R::TextureId>().unwrap().clone(),
+            None => {
+                trace!("importing video frame");
+                let texture = renderer.import_dmabuf(&frame.dmabuf, None)?;
+                inner.textures.insert(texture_id, Box::new(texture.clone()));
+                texture
+            }
+        };
+
+        let buffer_size = frame.dmabuf.size();
+        Ok(Some(VideoRenderElement {
+            id: sink.id.clone(),
+            location: location.into(),
+            size: size.unwrap_or_else(|| Size::from((buffer_size.w, buffer_size.h))),
+            dmabuf: frame.dmabuf,
+            sync: frame.sync,
+            commit: inner.commit,
+            alpha: alpha.unwrap_or(1.0),
+            texture,
+            kind,
+        }))
+    }
+
+    fn physical_size(&self, scale: Scale<f64>) -> Size<i32, Physical> {
+        ((self.size.to_f64().to_physical(scale).to_point() + self.location).to_i32_round()
+            - self.location.to_i32_round())
+        .to_size()
+    }
+}
+
+impl<R: Renderer> Element for VideoRenderElement<R> {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.dmabuf.size().to_f64())
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        Rectangle::new(self.location.to_i32_round(), self.physical_size(scale))
+    }
+
+    fn damage_since(&self, scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        if commit == Some(self.commit) {
+            DamageSet::default()
+        } else {
+            DamageSet::from_slice(&[Rectangle::from_size(self.physical_size(scale))])
+        }
+    }
+
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        if self.alpha < 1.0 || has_alpha(self.dmabuf.format().code) {
+            return OpaqueRegions::default();
+        }
+
+        OpaqueRegions::from_slice(&[Rectangle::from_size(self.physical_size(scale))])
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl<R> RenderElement<R> for VideoRenderElement<R>
+where
+    R: Renderer + ImportDma,
+    <R as Renderer>::TextureId: 'static,
+{
+    #[instrument(level = "trace", skip(self, frame))]
+    #[profiling::function]
+    fn draw<'a>(
+        &self,
+        frame: &mut <R as Renderer>::Frame<'a>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), <R as Renderer>::Error> {
+        frame.wait(&self.sync)?;
+        frame.render_texture_from_to(
+            &self.texture,
+            src,
+            dst,
+            damage,
+            opaque_regions,
+            Transform::Normal,
+            self.alpha,
+        )
+    }
+
+    #[inline]
+    fn underlying_storage(&self, _renderer: &mut R) -> Option<UnderlyingStorage<'_>> {
+        Some(UnderlyingStorage::External(&self.dmabuf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        os::unix::io::OwnedFd,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::{VideoFrame, VideoSink};
+    use crate::{
+        backend::{
+            allocator::{
+                dmabuf::{Dmabuf, DmabufFlags},
+                Fourcc, Modifier,
+            },
+            renderer::sync::{Fence, Interrupted, SyncPoint},
+        },
+        utils::{Monotonic, Time},
+    };
+
+    #[derive(Debug, Default)]
+    struct TestFence(AtomicBool);
+
+    impl Fence for Arc<TestFence> {
+        fn is_signaled(&self) -> bool {
+            self.0.load(Ordering::SeqCst)
+        }
+
+        fn wait(&self) -> Result<(), Interrupted> {
+            Ok(())
+        }
+
+        fn is_exportable(&self) -> bool {
+            false
+        }
+
+        fn export(&self) -> Option<OwnedFd> {
+            None
+        }
+    }
+
+    fn frame(pts_ms: u64) -> VideoFrame {
+        let mut builder = Dmabuf::builder((64, 64), Fourcc::Xrgb8888, Modifier::Linear, DmabufFlags::empty());
+        builder.add_plane(OwnedFd::from(File::open("/dev/null").unwrap()), 0, 0, 256);
+        VideoFrame::new(builder.build().unwrap(), Duration::from_millis(pts_ms))
+    }
+
+    fn time(ms: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_millis(ms))
+    }
+
+    fn current_pts(sink: &VideoSink) -> Option<Duration> {
+        sink.current_frame().map(|frame| frame.pts())
+    }
+
+    #[test]
+    fn frames_are_shown_when_due() {
+        let sink = VideoSink::new();
+        assert!(!sink.update(time(1000)));
+        assert_eq!(sink.next_frame_time(), None);
+
+        for pts in [500, 516, 533] {
+            sink.push(frame(pts));
+        }
+
+        // playback starts with the first frame
+        assert!(sink.update(time(1000)));
+        assert_eq!(current_pts(&sink), Some(Duration::from_millis(500)));
+        assert_eq!(sink.next_frame_time(), Some(time(1016)));
+
+        assert!(!sink.update(time(1010)));
+        assert_eq!(current_pts(&sink), Some(Duration::from_millis(500)));
+
+        assert!(sink.update(time(1016)));
+        assert_eq!(current_pts(&sink), Some(Duration::from_millis(516)));
+        assert_eq!(sink.queued(), 1);
+    }
+
+    #[test]
+    fn late_frames_are_skipped() {
+        let sink = VideoSink::new();
+        sink.push(frame(0));
+        assert!(sink.update(time(0)));
+
+        for pts in [16, 33, 50] {
+            sink.push(frame(pts));
+        }
+        assert!(sink.update(time(40)));
+        assert_eq!(current_pts(&sink), Some(Duration::from_millis(33)));
+        assert_eq!(sink.queued(), 1);
+        assert_eq!(sink.next_frame_time(), Some(time(50)));
+    }
+
+    #[test]
+    fn queue_is_bounded() {
+        let sink = VideoSink::new();
+        sink.set_max_queued(2);
+        for pts in [0, 16, 33] {
+            sink.push(frame(pts));
+        }
+        assert_eq!(sink.queued(), 2);
+
+        // the oldest frame was dropped, playback starts with the next one
+        assert!(sink.update(time(0)));
+        assert_eq!(current_pts(&sink), Some(Duration::from_millis(16)));
+
+        sink.set_max_queued(0);
+        assert_eq!(sink.max_queued(), 1);
+    }
+
+    #[test]
+    fn frames_wait_for_their_sync_point() {
+        let sink = VideoSink::new();
+        let fence = Arc::new(TestFence::default());
+        sink.push(frame(0).with_sync(SyncPoint::from(fence.clone())));
+
+        assert!(!sink.update(time(0)));
+        assert!(current_pts(&sink).is_none());
+
+        fence.0.store(true, Ordering::SeqCst);
+        assert!(sink.update(time(5)));
+        assert_eq!(current_pts(&sink), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn reset_keeps_current_frame() {
+        let sink = VideoSink::new();
+        sink.push(frame(0));
+        sink.push(frame(16));
+        assert!(sink.update(time(0)));
+
+        sink.reset();
+        assert_eq!(sink.queued(), 0);
+        assert_eq!(current_pts(&sink), Some(Duration::ZERO));
+
+        // the clock restarts with the next frame shown
+        sink.push(frame(5000));
+        assert!(sink.update(time(100)));
+        assert_eq!(current_pts(&sink), Some(Duration::from_millis(5000)));
+    }
+
+    #[cfg(feature = "video_gstreamer")]
+    mod gstreamer {
+        use super::super::{VideoFrame, VideoSink, GST_FORMATS};
+        use crate::backend::allocator::format::{get_bpp, has_alpha};
+
+        #[test]
+        fn formats_match_drm_fourccs() {
+            for (format, fourcc) in GST_FORMATS {
+                let info = gstreamer_video::VideoFormatInfo::from_format(*format);
+                assert_eq!(info.has_alpha(), has_alpha(*fourcc), "{:?}", format);
+                if info.n_planes() == 1 {
+                    assert_eq!(Some(info.pixel_stride()[0] as usize * 8), get_bpp(*fourcc));
+                }
+                assert_eq!(GST_FORMATS.iter().filter(|(_, other)| other == fourcc).count(), 1);
+            }
+        }
+
+        #[test]
+        fn appsink_negotiates_dmabufs() {
+            gstreamer::init().unwrap();
+
+            let sink = VideoSink::new();
+            sink.set_max_queued(3);
+            let appsink = sink.appsink();
+            assert_eq!(appsink.max_buffers(), 3);
+
+            let caps = appsink.caps().unwrap();
+            assert_eq!(caps.size(), 1);
+            assert!(caps
+                .features(0)
+                .unwrap()
+                .contains(gstreamer_allocators::CAPS_FEATURE_MEMORY_DMABUF));
+            let formats = caps
+                .structure(0)
+                .unwrap()
+                .get::<gstreamer::List>("format")
+                .unwrap();
+            assert_eq!(formats.len(), GST_FORMATS.len());
+        }
+
+        #[test]
+        fn samples_without_dmabuf_memory_are_rejected() {
+            gstreamer::init().unwrap();
+
+            let info = gstreamer_video::VideoInfo::builder(gstreamer_video::VideoFormat::Bgrx, 4, 4)
+                .build()
+                .unwrap();
+            let mut buffer = gstreamer::Buffer::with_size(info.size()).unwrap();
+            let caps = info.to_caps().unwrap();
+
+            // no timestamp
+            let sample = gstreamer::Sample::builder().buffer(&buffer).caps(&caps).build();
+            assert!(VideoFrame::from_gst_sample(&sample).is_none());
+
+            // system memory
+            buffer
+                .get_mut()
+                .unwrap()
+                .set_pts(gstreamer::ClockTime::from_mseconds(16));
+            let sample = gstreamer::Sample::builder().buffer(&buffer).caps(&caps).build();
+            assert!(VideoFrame::from_gst_sample(&sample).is_none());
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Element for compositor-internal widgets
+//!
+//! [`WidgetElement`] renders a [`PaintWidget`] into a [`MemoryRenderBuffer`] and provides a
+//! [`WidgetFocus`] delivering the input of a [`Seat`](crate::input::Seat) to the same widget.
+//! This is the building block for integrating UI toolkits like `egui` or `iced` into a compositor,
+//! e.g. for settings dialogs or a HUD: the toolkit paints into the provided [`WidgetCanvas`]
+//! using a software rasterizer and receives its input as [`WidgetEvent`](crate::input::widget::WidgetEvent)s.
+//!
+//! Only the regions reported by [`PaintWidget::paint`] are uploaded and damaged, so a
+//! widget, which did not change, does not cause any rendering.
+//!
+//! ```no_run
+//! # use smithay::{
+//! #     backend::renderer::{element::{widget::*, Kind}, ImportMem, Renderer},
+//! #     utils::{Buffer, Logical, Rectangle, Size},
+//! # };
+//! struct Hud {
+//!     dirty: bool,
+//! }
+//!
+//! impl PaintWidget for Hud {
+//!     fn size(&self) -> Size<i32, Logical> {
+//!         (200, 50).into()
+//!     }
+//!
+//!     fn paint(&mut self, canvas: &mut WidgetCanvas<'_>) -> Vec<Rectangle<i32, Buffer>> {
+//!         if !self.dirty && !canvas.is_cleared() {
+//!             return Vec::new();
+//!         }
+//!         self.dirty = false;
+//!         canvas.pixels().fill(0xff);
+//!         vec![Rectangle::from_size(canvas.size())]
+//!     }
+//! }
+//!
+//! # fn render<R: Renderer + ImportMem>(renderer: &mut R) where R::TextureId: Send + Clone + 'static {
+//! let mut hud = WidgetElement::new(Hud { dirty: true }, 1);
+//! // each frame
+//! let element = hud.render_element(renderer, (0.0, 0.0), 1.0, Kind::Unspecified).unwrap();
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use tracing::warn;
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{ImportMem, Renderer},
+    },
+    input::{
+        widget::{Widget, WidgetFocus},
+        SeatHandler,
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Size, Transform},
+};
+
+use super::{
+    memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+    Kind,
+};
+
+/// A widget drawing itself into memory
+pub trait PaintWidget: Send {
+    /// Size of the widget in logical coordinates
+    fn size(&self) -> Size<i32, Logical>;
+
+    /// Paint the widget into the canvas
+    ///
+    /// Returns the regions of the canvas that were updated. Returning no regions
+    /// keeps the previous contents, unless the canvas [was cleared](WidgetCanvas::is_cleared).
+    fn paint(&mut self, canvas: &mut WidgetCanvas<'_>) -> Vec<Rectangle<i32, Buffer>>;
+}
+
+/// Pixel buffer a [`PaintWidget`] draws into
+///
+/// Pixels are stored as [`Fourcc::Argb8888`] with premultiplied alpha.
+#[derive(Debug)]
+pub struct WidgetCanvas<'a> {
+    pixels: &'a mut [u8],
+    size: Size<i32, Buffer>,
+    scale: i32,
+    cleared: bool,
+}
+
+impl WidgetCanvas<'_> {
+    /// Raw pixels of the canvas
+    pub fn pixels(&mut self) -> &mut [u8] {
+        self.pixels
+    }
+
+    /// Size of the canvas in pixels
+    pub fn size(&self) -> Size<i32, Buffer> {
+        self.size
+    }
+
+    /// Number of bytes per row of pixels
+    pub fn stride(&self) -> i32 {
+        self.size.w * 4
+    }
+
+    /// Scale of the canvas relative to the logical size of the widget
+    pub fn scale(&self) -> i32 {
+        self.scale
+    }
+
+    /// Returns true if the previous contents were lost and the whole canvas has to be painted
+    pub fn is_cleared(&self) -> bool {
+        self.cleared
+    }
+}
+
+/// Element rendering a [`PaintWidget`]
+#[derive(Debug)]
+pub struct WidgetElement<W> {
+    widget: Arc<Mutex<W>>,
+    buffer: MemoryRenderBuffer,
+    buffer_size: Size<i32, Buffer>,
+    scale: i32,
+    cleared: bool,
+}
+
+impl<W: PaintWidget> WidgetElement<W> {
+    /// Create a new element for a widget painted at the given scale
+    pub fn new(widget: W, scale: i32) -> Self {
+        Self::from_shared(Arc::new(Mutex::new(widget)), scale)
+    }
+
+    /// Create a new element for a widget, which is also accessed elsewhere
+    pub fn from_shared(widget: Arc<Mutex<W>>, scale: i32) -> Self {
+        WidgetElement {
+            widget,
+            buffer: MemoryRenderBuffer::new(Fourcc::Argb8888, (0, 0), scale, Transform::Normal, None),
+            buffer_size: Size::default(),
+            scale,
+            cleared: true,
+        }
+    }
+
+    /// Returns the rendered widget
+    pub fn widget(&self) -> &Arc<Mutex<W>> {
+        &self.widget
+    }
+
+    /// Returns a focus target delivering input to the rendered widget
+    pub fn focus<D>(&self) -> WidgetFocus<D>
+    where
+        D: SeatHandler + 'static,
+        W: Widget<D> + 'static,
+    {
+        WidgetFocus::from_shared(self.widget.clone())
+    }
+
+    /// Returns the scale the widget is painted at
+    pub fn scale(&self) -> i32 {
+        self.scale
+    }
+
+    /// Set the scale to paint the widget at, e.g. to match the scale of an output
+    pub fn set_scale(&mut self, scale: i32) {
+        if self.scale != scale {
+            self.scale = scale;
+            self.buffer = MemoryRenderBuffer::new(Fourcc::Argb8888, (0, 0), scale, Transform::Normal, None);
+            self.buffer_size = Size::default();
+            self.cleared = true;
+        }
+    }
+
+    /// Returns the current logical size of the widget
+    pub fn size(&self) -> Size<i32, Logical> {
+        self.widget.lock().unwrap().size()
+    }
+
+    /// Paint the widget, if necessary, and return a render element for it
+    ///
+    /// The element is positioned at `location` and uses the widgets buffer scale for its size.
+    pub fn render_element<R>(
+        &mut self,
+        renderer: &mut R,
+        location: impl Into<Point<f64, Physical>>,
+        alpha: f32,
+        kind: Kind,
+    ) -> Result<MemoryRenderBufferRenderElement<R>, <R as Renderer>::Error>
+    where
+        R: Renderer + ImportMem,
+        <R as Renderer>::TextureId: Send + Clone + 'static,
+    {
+        self.paint();
+        MemoryRenderBufferRenderElement::from_buffer(
+            renderer,
+            location,
+            &self.buffer,
+            Some(alpha),
+            None,
+            None,
+            kind,
+        )
+    }
+
+    fn paint(&mut self) {
+        let mut widget = self.widget.lock().unwrap();
+        let size = widget.size().to_buffer(self.scale, Transform::Normal);
+        let size = Size::from((size.w.max(0), size.h.max(0)));
+
+        let mut context = self.buffer.render();
+        if self.buffer_size != size {
+            context.resize(size);
+            self.buffer_size = size;
+            self.cleared = true;
+        }
+
+        let scale = self.scale;
+        let cleared = std::mem::take(&mut self.cleared);
+        let _ = context.draw(|pixels| {
+            if cleared {
+                pixels.fill(0);
+            }
+            let mut canvas = WidgetCanvas {
+                pixels,
+                size,
+                scale,
+                cleared,
+            };
+            let damage = widget.paint(&mut canvas);
+            let bounds = Rectangle::from_size(size);
+            let damage = if cleared {
+                vec![bounds]
+            } else {
+                damage
+                    .into_iter()
+                    .filter_map(|rect| {
+                        let clamped = rect.intersection(bounds);
+                        if clamped.is_none() {
+                            warn!(?rect, "Widget reported damage outside of the canvas");
+                        }
+                        clamped
+                    })
+                    .collect()
+            };
+            Result::<_, ()>::Ok(damage)
+        });
+    }
+}
+
+#[cfg(all(test, feature = "renderer_pixman"))]
+mod tests {
+    use super::*;
+    use crate::backend::renderer::{element::Element, pixman::PixmanRenderer};
+
+    struct Counter {
+        size: Size<i32, Logical>,
+        dirty: Vec<Rectangle<i32, Buffer>>,
+        painted: usize,
+    }
+
+    impl PaintWidget for Counter {
+        fn size(&self) -> Size<i32, Logical> {
+            self.size
+        }
+
+        fn paint(&mut self, _canvas: &mut WidgetCanvas<'_>) -> Vec<Rectangle<i32, Buffer>> {
+            self.painted += 1;
+            std::mem::take(&mut self.dirty)
+        }
+    }
+
+    #[test]
+    fn damage_follows_paint() {
+        let mut renderer = PixmanRenderer::new().unwrap();
+        let mut element = WidgetElement::new(
+            Counter {
+                size: (10, 10).into(),
+                dirty: Vec::new(),
+                painted: 0,
+            },
+            2,
+        );
+
+        let first = element
+            .render_element(&mut renderer, (0.0, 0.0), 1.0, Kind::Unspecified)
+            .unwrap();
+        assert_eq!(first.geometry(2.0.into()).size, (20, 20).into());
+
+        // nothing changed
+        let second = element
+            .render_element(&mut renderer, (0.0, 0.0), 1.0, Kind::Unspecified)
+            .unwrap();
+        assert_eq!(first.current_commit(), second.current_commit());
+
+        element.widget().lock().unwrap().dirty = vec![Rectangle::new((2, 2).into(), (4, 4).into())];
+        let third = element
+            .render_element(&mut renderer, (0.0, 0.0), 1.0, Kind::Unspecified)
+            .unwrap();
+        let damage = third.damage_since(2.0.into(), Some(second.current_commit()));
+        assert_eq!(
+            damage.iter().copied().collect::<Vec<_>>(),
+            vec![Rectangle::new((2, 2).into(), (4, 4).into())]
+        );
+
+        // resizing repaints everything
+        element.widget().lock().unwrap().size = (12, 10).into();
+        let fourth = element
+            .render_element(&mut renderer, (0.0, 0.0), 1.0, Kind::Unspecified)
+            .unwrap();
+        assert_eq!(fourth.geometry(2.0.into()).size, (24, 20).into());
+        assert_eq!(element.widget().lock().unwrap().painted, 4);
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Statistics of the texture caches of a [`GlesRenderer`](super::GlesRenderer)
+///
+/// Counters accumulate until reset via [`GlesRenderer::reset_texture_cache_stats`](super::GlesRenderer::reset_texture_cache_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextureCacheStats {
+    /// Number of textures currently cached for dmabufs
+    pub dmabuf_entries: usize,
+    /// Number of dmabuf imports re-using a cached texture
+    pub dmabuf_hits: u64,
+    /// Number of dmabuf imports creating a new texture
+    pub dmabuf_misses: u64,
+    /// Number of dmabuf textures evicted because of the cache limit
+    pub dmabuf_evictions: u64,
+    /// Number of surfaces with a texture cached for their shm buffers
+    pub shm_entries: usize,
+    /// Number of shm imports re-using the cached texture of the surface
+    pub shm_hits: u64,
+    /// Number of shm imports creating a new texture
+    pub shm_misses: u64,
+    /// Number of shm textures evicted because of the cache limit
+    pub shm_evictions: u64,
+}
+
+// Cache evicting the least recently used entries once it exceeds its limit
+#[derive(Debug)]
+pub(super) struct LruCache<K, V> {
+    entries: HashMap<K, (V, u64)>,
+    tick: u64,
+    limit: Option<usize>,
+}
+
+impl<K: Hash + Eq, V> LruCache<K, V> {
+    pub(super) fn new(limit: Option<usize>) -> Self {
+        LruCache {
+            entries: HashMap::new(),
+            tick: 0,
+            limit,
+        }
+    }
+
+    pub(super) fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    // Returns the evicted values
+    pub(super) fn set_limit(&mut self, limit: Option<usize>) -> Vec<V> {
+        self.limit = limit;
+        match limit {
+            Some(limit) => self.evict(limit),
+            None => Vec::new(),
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(super) fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        *last_used = self.tick;
+        Some(value)
+    }
+
+    // Returns the evicted values
+    pub(super) fn insert(&mut self, key: K, value: V) -> Vec<V> {
+        self.tick += 1;
+        let evicted = match self.limit {
+            Some(limit) if !self.entries.contains_key(&key) => self.evict(limit.saturating_sub(1)),
+            _ => Vec::new(),
+        };
+        self.entries.insert(key, (value, self.tick));
+        evicted
+    }
+
+    pub(super) fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        self.entries.retain(|key, (value, _)| f(key, value));
+    }
+
+    pub(super) fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values().map(|(value, _)| value)
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // Evicts the least recently used entries until at most `len` are left
+    fn evict(&mut self, len: usize) -> Vec<V> {
+        let count = self.entries.len().saturating_sub(len);
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let mut ticks = self.entries.values().map(|(_, tick)| *tick).collect::<Vec<_>>();
+        ticks.sort_unstable();
+        let threshold = ticks[count - 1];
+        let (kept, evicted): (HashMap<_, _>, HashMap<_, _>) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|(_, (_, tick))| *tick > threshold);
+        self.entries = kept;
+        evicted.into_values().map(|(value, _)| value).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache = LruCache::new(Some(2));
+        assert!(cache.insert(1, "a").is_empty());
+        assert!(cache.insert(2, "b").is_empty());
+        assert_eq!(cache.get(&1), Some(&"a"));
+
+        assert_eq!(cache.insert(3, "c"), vec!["b"]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some(&"a"));
+        assert_eq!(cache.get(&3), Some(&"c"));
+
+        // replacing an entry never evicts
+        assert!(cache.insert(3, "d").is_empty());
+        assert_eq!(cache.get(&3), Some(&"d"));
+    }
+
+    #[test]
+    fn lower_limit() {
+        let mut cache = LruCache::new(None);
+        for i in 0..10 {
+            cache.insert(i, i);
+        }
+        let mut evicted = cache.set_limit(Some(3));
+        evicted.sort_unstable();
+        assert_eq!(evicted, (0..7).collect::<Vec<_>>());
+        assert_eq!(cache.len(), 3);
+        assert!((7..10).all(|i| cache.get(&i).is_some()));
+    }
+}
@@ -16,6 +16,9 @@ pub enum GlesError {
     /// A framebuffer could not be bound
     #[error("Failed to bind Framebuffer")]
     FramebufferBindingError,
+    /// The buffer exceeds the maximum size of render targets
+    #[error("Buffer of size {0:?} exceeds the maximum size of render targets")]
+    TargetTooLarge(Size<i32, BufferCoord>),
     /// Required GL functions could not be loaded
     #[error("Failed to load GL functions from EGL")]
     GLFunctionLoaderError,
@@ -69,6 +72,9 @@ pub enum GlesError {
     /// The blitting operation was unsuccessful
     #[error("Error blitting between framebuffers")]
     BlitError,
+    /// A multisampled framebuffer was read without resolving it first
+    #[error("Multisampled framebuffers need to be resolved before being read")]
+    MultisampledFramebuffer,
     /// An error occured while creating the shader object.
     #[error("An error occured while creating the shader object.")]
     CreateShaderObject,
@@ -101,6 +107,7 @@ impl From<GlesError> for SwapBuffersError {
             | x @ GlesError::GLVersionNotSupported(_) => SwapBuffersError::ContextLost(Box::new(x)),
             GlesError::ContextActivationError(err) => err.into(),
             x @ GlesError::FramebufferBindingError
+            | x @ GlesError::TargetTooLarge(_)
             | x @ GlesError::BindBufferEGLError(_)
             | x @ GlesError::UnknownPixelFormat
             | x @ GlesError::UnsupportedPixelFormat(_)
@@ -111,6 +118,7 @@ impl From<GlesError> for SwapBuffersError {
             | x @ GlesError::UnexpectedSize
             | x @ GlesError::UnknownSize
             | x @ GlesError::BlitError
+            | x @ GlesError::MultisampledFramebuffer
             | x @ GlesError::CreateShaderObject
             | x @ GlesError::UniformTypeMismatch { .. }
             | x @ GlesError::UnknownUniform(_)
@@ -130,6 +138,7 @@ impl From<GlesError> for SwapBuffersError {
             | x @ GlesError::GLVersionNotSupported(_) => SwapBuffersError::ContextLost(Box::new(x)),
             GlesError::ContextActivationError(err) => err.into(),
             x @ GlesError::FramebufferBindingError
+            | x @ GlesError::TargetTooLarge(_)
             | x @ GlesError::MappingError
             | x @ GlesError::UnknownPixelFormat
             | x @ GlesError::UnsupportedPixelFormat(_)
@@ -137,6 +146,7 @@ impl From<GlesError> for SwapBuffersError {
             | x @ GlesError::UnexpectedSize
             | x @ GlesError::UnknownSize
             | x @ GlesError::BlitError
+            | x @ GlesError::MultisampledFramebuffer
             | x @ GlesError::CreateShaderObject
             | x @ GlesError::UniformTypeMismatch { .. }
             | x @ GlesError::UnknownUniform(_)
@@ -18,27 +18,37 @@ use std::{
 use tracing::{debug, error, info, info_span, instrument, span, span::EnteredSpan, trace, warn, Level};
 
 #[cfg(feature = "wayland_frontend")]
-use std::sync::Mutex;
+use std::sync::{Mutex, Weak};
 
+mod cache;
 pub mod element;
 mod error;
 pub mod format;
 mod shaders;
+mod shared;
 mod texture;
+mod timer;
 mod uniform;
 mod version;
 
+pub use cache::TextureCacheStats;
 pub use error::*;
 use format::*;
 pub use shaders::*;
+pub use shared::GlesSharedContext;
 pub use texture::*;
+pub use timer::GpuZone;
 pub use uniform::*;
 
-use self::version::GlVersion;
+use self::{
+    cache::LruCache,
+    timer::{GpuTimer, GpuZoneToken},
+    version::GlVersion,
+};
 
 use super::{
-    sync::SyncPoint, Bind, Blit, Color32F, DebugFlags, ExportMem, Frame, ImportDma, ImportMem, Offscreen,
-    Renderer, Texture, TextureFilter, TextureMapping, Unbind,
+    damage::output_tiles, sync::SyncPoint, Bind, Blit, Color32F, DebugFlags, ExportMem, Frame, ImportDma,
+    ImportMem, Offscreen, Renderer, Texture, TextureFilter, TextureMapping, Unbind,
 };
 use crate::backend::egl::{
     ffi::egl::{self as ffi_egl, types::EGLImage},
@@ -72,6 +82,7 @@ pub mod ffi {
 
 crate::utils::ids::id_gen!(renderer_id);
 struct RendererId(usize);
+crate::user_data_key!(RendererIdKey: RendererId = RendererId(renderer_id::next()));
 impl Drop for RendererId {
     fn drop(&mut self) {
         renderer_id::remove(self.0);
@@ -109,6 +120,7 @@ struct GlesRenderbufferInternal {
     format: ffi::types::GLenum,
     has_alpha: bool,
     size: Size<i32, BufferCoord>,
+    samples: u32,
     destruction_callback_sender: Sender<CleanupResource>,
 }
 
@@ -127,6 +139,19 @@ impl GlesRenderbuffer {
             fmt.and_then(get_opaque)
         }
     }
+
+    /// Number of samples per pixel, `1` if the renderbuffer is not multisampled
+    pub fn samples(&self) -> u32 {
+        self.0.samples.max(1)
+    }
+
+    /// Approximate memory used by the renderbuffer in bytes
+    ///
+    /// See [`estimated_memory_usage`](crate::backend::renderer::utils::estimated_memory_usage) for details.
+    pub fn memory_usage(&self) -> Option<usize> {
+        crate::backend::renderer::utils::estimated_memory_usage(self.size(), self.format()?)
+            .map(|usage| usage * self.samples() as usize)
+    }
 }
 
 impl Drop for GlesRenderbufferInternal {
@@ -228,6 +253,7 @@ impl Drop for GlesTarget {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 /// Capabilities of the [`GlesRenderer`]
+#[non_exhaustive]
 pub enum Capability {
     /// GlesRenderer supports Instancing for render optimizations
     Instancing,
@@ -241,6 +267,10 @@ pub enum Capability {
     Fencing,
     /// GlesRenderer supports GL debug
     Debug,
+    /// GlesRenderer supports multisampled renderbuffers
+    Multisample,
+    /// GlesRenderer supports timing operations on the gpu
+    TimerQuery,
 }
 
 /// A renderer utilizing OpenGL ES
@@ -268,12 +298,25 @@ pub struct GlesRenderer {
 
     // caches
     buffers: Vec<GlesBuffer>,
-    dmabuf_cache: std::collections::HashMap<WeakDmabuf, GlesTexture>,
+    dmabuf_cache: LruCache<WeakDmabuf, GlesTexture>,
+    // surfaces holding a texture of this renderer in their `ShmCache`, by address of the cache
+    #[cfg(feature = "wayland_frontend")]
+    shm_cache: LruCache<usize, Weak<Mutex<CacheMap>>>,
+    texture_cache_stats: TextureCacheStats,
     vbos: [ffi::types::GLuint; 2],
     vertices: Vec<f32>,
     non_opaque_damage: Vec<Rectangle<i32, Physical>>,
     opaque_damage: Vec<Rectangle<i32, Physical>>,
 
+    // profiling
+    gpu_timer: GpuTimer,
+
+    // limits
+    max_viewport_size: Size<i32, Physical>,
+    max_target_size: Size<i32, Physical>,
+    max_samples: u32,
+    tile_size: Option<Size<i32, Physical>>,
+
     // cleanup
     destruction_callback: Receiver<CleanupResource>,
     destruction_callback_sender: Sender<CleanupResource>,
@@ -297,8 +340,11 @@ pub struct GlesFrame<'frame> {
     current_projection: Matrix3<f32>,
     transform: Transform,
     size: Size<i32, Physical>,
+    // viewports to draw, if the target exceeds the maximum render size
+    tiles: Vec<Rectangle<i32, Physical>>,
     tex_program_override: Option<(GlesTexProgram, Vec<Uniform<'static>>)>,
     finished: AtomicBool,
+    gpu_zone: Option<GpuZoneToken>,
 
     span: EnteredSpan,
 }
@@ -311,6 +357,7 @@ impl fmt::Debug for GlesFrame<'_> {
             .field("transform", &self.transform)
             .field("tex_program_override", &self.tex_program_override)
             .field("size", &self.size)
+            .field("tiles", &self.tiles)
             .field("finished", &self.finished)
             .finish_non_exhaustive()
     }
@@ -326,12 +373,16 @@ impl fmt::Debug for GlesRenderer {
             .field("tex_program", &self.tex_program)
             .field("solid_program", &self.solid_program)
             .field("dmabuf_cache", &self.dmabuf_cache)
+            .field("texture_cache_stats", &self.texture_cache_stats)
             .field("egl", &self.egl)
             .field("gl_version", &self.gl_version)
             // ffi::Gles does not implement Debug
             .field("vbos", &self.vbos)
             .field("min_filter", &self.min_filter)
             .field("max_filter", &self.max_filter)
+            .field("max_viewport_size", &self.max_viewport_size)
+            .field("max_target_size", &self.max_target_size)
+            .field("tile_size", &self.tile_size)
             .finish()
     }
 }
@@ -418,6 +469,26 @@ impl GlesRenderer {
             debug!("GL Debug is supported");
         }
 
+        // implementations may expose the extension without supporting timestamps
+        if exts.iter().any(|ext| ext == "GL_EXT_disjoint_timer_query") {
+            let mut counter_bits = 0;
+            gl.GetQueryivEXT(ffi::TIMESTAMP_EXT, ffi::QUERY_COUNTER_BITS_EXT, &mut counter_bits);
+            if counter_bits > 0 {
+                capabilities.push(Capability::TimerQuery);
+                debug!("Timer queries are supported ({} bit timestamps)", counter_bits);
+            }
+        }
+
+        // required to render into multisampled renderbuffers and to resolve them
+        if gl_version >= version::GLES_3_0 {
+            let mut max_samples = 0;
+            gl.GetIntegerv(ffi::MAX_SAMPLES, &mut max_samples);
+            if max_samples > 1 {
+                capabilities.push(Capability::Multisample);
+                debug!("Multisampling is supported (max. {} samples)", max_samples);
+            }
+        }
+
         Ok(capabilities)
     }
 
@@ -477,10 +548,15 @@ impl GlesRenderer {
                 Capability::Instancing => {
                     GlesError::GLExtensionNotSupported(&["GL_EXT_instanced_arrays", "GL_EXT_draw_instanced"])
                 }
-                Capability::Blit | Capability::_10Bit => GlesError::GLVersionNotSupported(version::GLES_3_0),
+                Capability::Blit | Capability::_10Bit | Capability::Multisample => {
+                    GlesError::GLVersionNotSupported(version::GLES_3_0)
+                }
                 Capability::Renderbuffer => GlesError::GLExtensionNotSupported(&["GL_OES_rgb8_rgba8"]),
                 Capability::Fencing => GlesError::GLExtensionNotSupported(&["GL_OES_EGL_sync"]),
                 Capability::Debug => GlesError::GLExtensionNotSupported(&["GL_KHR_debug"]),
+                Capability::TimerQuery => {
+                    GlesError::GLExtensionNotSupported(&["GL_EXT_disjoint_timer_query"])
+                }
             };
             return Err(err);
         };
@@ -543,6 +619,20 @@ impl GlesRenderer {
             (gl, gl_version, exts, requested_capabilities, gl_debug_span)
         };
 
+        let mut max_viewport_dims = [0; 2];
+        gl.GetIntegerv(ffi::MAX_VIEWPORT_DIMS, max_viewport_dims.as_mut_ptr());
+        let max_viewport_size = Size::from((max_viewport_dims[0], max_viewport_dims[1]));
+        let mut max_texture_size = 0;
+        gl.GetIntegerv(ffi::MAX_TEXTURE_SIZE, &mut max_texture_size);
+        let mut max_renderbuffer_size = 0;
+        gl.GetIntegerv(ffi::MAX_RENDERBUFFER_SIZE, &mut max_renderbuffer_size);
+        let max_target_size = max_texture_size.min(max_renderbuffer_size);
+        let max_target_size = Size::from((max_target_size, max_target_size));
+        let mut max_samples = 0;
+        if capabilities.contains(&Capability::Multisample) {
+            gl.GetIntegerv(ffi::MAX_SAMPLES, &mut max_samples);
+        }
+
         let (tx, rx) = channel();
         let tex_program = texture_program(&gl, shaders::FRAGMENT_SHADER, &[], tx.clone())?;
         let solid_program = solid_program(&gl)?;
@@ -572,9 +662,8 @@ impl GlesRenderer {
         );
         gl.BindBuffer(ffi::ARRAY_BUFFER, 0);
 
-        context
-            .user_data()
-            .insert_if_missing_threadsafe(|| RendererId(renderer_id::next()));
+        // assign the id now, so it is shared by all renderers of this context
+        let _ = context.user_data().get_key::<RendererIdKey>();
         drop(_guard);
 
         let renderer = GlesRenderer {
@@ -595,11 +684,21 @@ impl GlesRenderer {
 
             target: None,
             buffers: Vec::new(),
-            dmabuf_cache: std::collections::HashMap::new(),
+            dmabuf_cache: LruCache::new(None),
+            #[cfg(feature = "wayland_frontend")]
+            shm_cache: LruCache::new(None),
+            texture_cache_stats: TextureCacheStats::default(),
             vertices: Vec::with_capacity(6 * 16),
             non_opaque_damage: Vec::with_capacity(16),
             opaque_damage: Vec::with_capacity(16),
 
+            gpu_timer: GpuTimer::new(),
+
+            max_viewport_size,
+            max_target_size,
+            max_samples: max_samples.max(1) as u32,
+            tile_size: None,
+
             destruction_callback: rx,
             destruction_callback_sender: tx,
 
@@ -624,7 +723,10 @@ impl GlesRenderer {
 
     #[profiling::function]
     fn cleanup(&mut self) {
+        unsafe { self.gpu_timer.poll(&self.gl) };
         self.dmabuf_cache.retain(|entry, _tex| !entry.is_gone());
+        #[cfg(feature = "wayland_frontend")]
+        self.shm_cache.retain(|_, cache| cache.strong_count() > 0);
         // Free outdated buffer resources
         // TODO: Replace with `drain_filter` once it lands
         let mut i = 0;
@@ -673,6 +775,224 @@ impl GlesRenderer {
     pub fn capabilities(&self) -> &[Capability] {
         &self.capabilities
     }
+
+    /// Returns the maximum number of textures cached for imported dmabufs
+    ///
+    /// `None` means the cache is only limited by the lifetime of the dmabufs, which is the default.
+    pub fn dmabuf_cache_limit(&self) -> Option<usize> {
+        self.dmabuf_cache.limit()
+    }
+
+    /// Limit the number of textures cached for imported dmabufs
+    ///
+    /// Once the limit is reached, the least recently used textures are evicted from the cache.
+    /// Evicted textures stay valid as long as they are referenced, but dmabufs need to be
+    /// imported again the next time they are used.
+    pub fn set_dmabuf_cache_limit(&mut self, limit: Option<usize>) {
+        let evicted = self.dmabuf_cache.set_limit(limit);
+        self.texture_cache_stats.dmabuf_evictions += evicted.len() as u64;
+    }
+
+    /// Drop all textures cached for imported dmabufs
+    pub fn flush_dmabuf_cache(&mut self) {
+        self.dmabuf_cache.clear();
+    }
+
+    /// Returns the maximum number of surfaces with a texture cached for their shm buffers
+    ///
+    /// `None` means the cache is only limited by the lifetime of the surfaces, which is the default.
+    #[cfg(feature = "wayland_frontend")]
+    pub fn shm_cache_limit(&self) -> Option<usize> {
+        self.shm_cache.limit()
+    }
+
+    /// Limit the number of surfaces with a texture cached for their shm buffers
+    ///
+    /// Every surface keeps the texture of its last imported shm buffer, so only the damaged parts of
+    /// the next buffer have to be uploaded. Once the limit is reached, the textures of the least recently
+    /// imported surfaces are evicted, their next buffer is uploaded in full.
+    #[cfg(feature = "wayland_frontend")]
+    pub fn set_shm_cache_limit(&mut self, limit: Option<usize>) {
+        let evicted = self.shm_cache.set_limit(limit);
+        self.evict_shm_textures(evicted);
+    }
+
+    /// Drop all textures cached for the shm buffers of surfaces
+    #[cfg(feature = "wayland_frontend")]
+    pub fn flush_shm_cache(&mut self) {
+        let id = self.id();
+        for cache in self.shm_cache.values().filter_map(Weak::upgrade) {
+            cache.lock().unwrap().remove(&id);
+        }
+        self.shm_cache.clear();
+    }
+
+    #[cfg(feature = "wayland_frontend")]
+    fn evict_shm_textures(&mut self, evicted: Vec<Weak<Mutex<CacheMap>>>) {
+        let id = self.id();
+        for cache in evicted.iter().filter_map(Weak::upgrade) {
+            cache.lock().unwrap().remove(&id);
+            self.texture_cache_stats.shm_evictions += 1;
+        }
+    }
+
+    /// Returns the statistics of the texture caches of this renderer
+    pub fn texture_cache_stats(&self) -> TextureCacheStats {
+        TextureCacheStats {
+            dmabuf_entries: self.dmabuf_cache.len(),
+            #[cfg(feature = "wayland_frontend")]
+            shm_entries: self.shm_cache.len(),
+            ..self.texture_cache_stats
+        }
+    }
+
+    /// Reset the counters of the texture cache statistics
+    pub fn reset_texture_cache_stats(&mut self) {
+        self.texture_cache_stats = TextureCacheStats::default();
+    }
+
+    /// Returns the maximum viewport size supported by the driver (`GL_MAX_VIEWPORT_DIMS`)
+    pub fn max_viewport_size(&self) -> Size<i32, Physical> {
+        self.max_viewport_size
+    }
+
+    /// Returns the maximum size of textures and renderbuffers, that can be rendered into
+    ///
+    /// This is the smaller one of `GL_MAX_TEXTURE_SIZE` and `GL_MAX_RENDERBUFFER_SIZE`.
+    /// Creating or binding larger buffers fails with [`GlesError::TargetTooLarge`].
+    /// Outputs exceeding this size have to be split into multiple buffers, see
+    /// [`output_tiles`](crate::backend::renderer::damage::output_tiles) and
+    /// [`OutputDamageTracker::for_tile`](crate::backend::renderer::damage::OutputDamageTracker::for_tile).
+    pub fn max_target_size(&self) -> Size<i32, Physical> {
+        self.max_target_size
+    }
+
+    fn check_target_size(&self, size: Size<i32, BufferCoord>) -> Result<(), GlesError> {
+        if size.w > self.max_target_size.w || size.h > self.max_target_size.h {
+            return Err(GlesError::TargetTooLarge(size));
+        }
+        Ok(())
+    }
+
+    /// Enable or disable timing of renderer operations on the gpu
+    ///
+    /// While enabled, the gpu time of rendering frames, blits and memory uploads is measured using timer
+    /// queries and can be retrieved with [`GlesRenderer::gpu_zones`], e.g. to tell gpu-bound from cpu-bound
+    /// frames. If smithay is built with the `profile-with-tracy` feature and a tracy client is running,
+    /// the timings are also reported as gpu zones to tracy. Puffin has no notion of gpu timelines,
+    /// so compositors profiling with puffin need to forward the [`gpu_zones`](GlesRenderer::gpu_zones) themselves.
+    ///
+    /// Requires [`Capability::TimerQuery`].
+    pub fn set_gpu_timing(&mut self, enabled: bool) -> Result<(), GlesError> {
+        if enabled && !self.capabilities.contains(&Capability::TimerQuery) {
+            return Err(GlesError::GLExtensionNotSupported(&[
+                "GL_EXT_disjoint_timer_query",
+            ]));
+        }
+        self.make_current()?;
+        unsafe { self.gpu_timer.set_enabled(&self.gl, enabled) };
+        Ok(())
+    }
+
+    /// Returns if gpu timing is enabled, see [`GlesRenderer::set_gpu_timing`]
+    pub fn gpu_timing(&self) -> bool {
+        self.gpu_timer.is_enabled()
+    }
+
+    /// Retrieve the gpu timings of operations finished since the last call
+    ///
+    /// Operations are returned in submission order. Results become available asynchronously,
+    /// usually a frame or two after submission. Timings lost to disjoint gpu clock changes
+    /// (e.g. by power management) are skipped.
+    pub fn gpu_zones(&mut self) -> Result<impl Iterator<Item = GpuZone> + '_, GlesError> {
+        self.make_current()?;
+        unsafe { self.gpu_timer.poll(&self.gl) };
+        Ok(self.gpu_timer.drain())
+    }
+
+    /// Returns the maximum number of samples per pixel of multisampled renderbuffers (`GL_MAX_SAMPLES`)
+    ///
+    /// Returns `1` if [`Capability::Multisample`] is not supported.
+    pub fn max_samples(&self) -> u32 {
+        self.max_samples
+    }
+
+    /// Returns the maximum size rendered in a single pass
+    ///
+    /// Frames for larger targets are split into tiles, which are drawn one after another.
+    /// This only works around the viewport limit of the driver, buffers exceeding
+    /// [`GlesRenderer::max_target_size`] cannot be rendered into at all.
+    pub fn max_render_size(&self) -> Size<i32, Physical> {
+        match self.tile_size {
+            Some(tile_size) => Size::from((
+                tile_size.w.min(self.max_viewport_size.w),
+                tile_size.h.min(self.max_viewport_size.h),
+            )),
+            None => self.max_viewport_size,
+        }
+    }
+
+    /// Override the maximum size rendered in a single pass
+    ///
+    /// Targets exceeding the maximum viewport size of the driver are always rendered in tiles.
+    /// Setting a smaller tile size is mostly useful to work around driver bugs or for testing.
+    /// `None` restores the default, which only splits rendering when necessary.
+    pub fn set_tile_size(&mut self, tile_size: Option<Size<i32, Physical>>) {
+        self.tile_size = tile_size.filter(|size| size.w > 0 && size.h > 0);
+    }
+}
+
+// Returns the viewports to render a target of `size` with, empty if a single pass is sufficient
+fn render_tiles(size: Size<i32, Physical>, max: Size<i32, Physical>) -> Vec<Rectangle<i32, Physical>> {
+    if max.w <= 0 || max.h <= 0 || (size.w <= max.w && size.h <= max.h) {
+        return Vec::new();
+    }
+    output_tiles(size, max)
+}
+
+// Maps the normalized device coordinates of the whole target to the ones of `tile`
+fn tile_projection(tile: Rectangle<i32, Physical>, size: Size<i32, Physical>) -> Matrix3<f32> {
+    let scale_x = size.w as f32 / tile.size.w as f32;
+    let scale_y = size.h as f32 / tile.size.h as f32;
+    let offset_x = (size.w - 2 * tile.loc.x) as f32 / tile.size.w as f32 - 1.0;
+    let offset_y = (size.h - 2 * tile.loc.y) as f32 / tile.size.h as f32 - 1.0;
+    Matrix3::new(scale_x, 0.0, 0.0, 0.0, scale_y, 0.0, offset_x, offset_y, 1.0)
+}
+
+// Issues `draw` once per tile with a matching viewport and projection, or once if there are no tiles
+unsafe fn draw_tiled(
+    gl: &ffi::Gles2,
+    tiles: &[Rectangle<i32, Physical>],
+    size: Size<i32, Physical>,
+    uniform_matrix: ffi::types::GLint,
+    matrix: &Matrix3<f32>,
+    mut draw: impl FnMut(),
+) {
+    if tiles.is_empty() {
+        draw();
+        return;
+    }
+
+    for tile in tiles {
+        let tile_matrix = tile_projection(*tile, size) * matrix;
+        gl.Viewport(tile.loc.x, tile.loc.y, tile.size.w, tile.size.h);
+        gl.UniformMatrix3fv(uniform_matrix, 1, ffi::FALSE, tile_matrix.as_ptr());
+        draw();
+    }
+}
+
+// why not store a `GlesTexture`? because the user might do so.
+#[cfg(feature = "wayland_frontend")]
+type CacheMap = HashMap<usize, Arc<GlesTextureInternal>>;
+#[cfg(feature = "wayland_frontend")]
+crate::user_data_key!(ShmCache: Arc<Mutex<CacheMap>> = Arc::new(Mutex::new(CacheMap::new())));
+
+/// Drops the textures of all renderers cached for the shm buffers of a surface
+#[cfg(feature = "wayland_frontend")]
+pub(crate) fn release_shm_cache(states: &crate::wayland::compositor::SurfaceData) {
+    if let Some(cache) = states.data_map.try_get_key::<ShmCache>() {
+        cache.lock().unwrap().clear();
+    }
 }
 
 #[cfg(feature = "wayland_frontend")]
@@ -687,10 +1007,6 @@ impl ImportMemWl for GlesRenderer {
     ) -> Result<GlesTexture, GlesError> {
         use crate::wayland::shm::with_buffer_contents;
 
-        // why not store a `GlesTexture`? because the user might do so.
-        // this is guaranteed a non-public internal type, so we are good.
-        type CacheMap = HashMap<usize, Arc<GlesTextureInternal>>;
-
         with_buffer_contents(buffer, |ptr, len, data| {
             self.make_current()?;
 
@@ -735,11 +1051,7 @@ impl ImportMemWl for GlesRenderer {
                     .and_then(|surface| {
                         surface
                             .data_map
-                            .insert_if_missing_threadsafe(|| Arc::new(Mutex::new(CacheMap::new())));
-                        surface
-                            .data_map
-                            .get::<Arc<Mutex<CacheMap>>>()
-                            .unwrap()
+                            .get_key::<ShmCache>()
                             .lock()
                             .unwrap()
                             .get(&id)
@@ -765,8 +1077,7 @@ impl ImportMemWl for GlesRenderer {
                             let copy = new.clone();
                             surface
                                 .data_map
-                                .get::<Arc<Mutex<CacheMap>>>()
-                                .unwrap()
+                                .get_key::<ShmCache>()
                                 .lock()
                                 .unwrap()
                                 .insert(id, copy);
@@ -774,8 +1085,22 @@ impl ImportMemWl for GlesRenderer {
                         new
                     }),
             );
+            if upload_full {
+                self.texture_cache_stats.shm_misses += 1;
+            } else {
+                self.texture_cache_stats.shm_hits += 1;
+            }
+            if let Some(surface) = surface {
+                // keep track of the surface, so its texture can be evicted
+                let cache = surface.data_map.get_key::<ShmCache>();
+                let evicted = self
+                    .shm_cache
+                    .insert(Arc::as_ptr(cache) as usize, Arc::downgrade(cache));
+                self.evict_shm_textures(evicted);
+            }
 
             unsafe {
+                let gpu_zone = self.gpu_timer.begin(&self.gl, "import_shm_buffer");
                 self.gl.BindTexture(ffi::TEXTURE_2D, texture.0.texture);
                 self.gl
                     .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
@@ -820,6 +1145,7 @@ impl ImportMemWl for GlesRenderer {
 
                 self.gl.PixelStorei(ffi::UNPACK_ROW_LENGTH, 0);
                 self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+                self.gpu_timer.end(&self.gl, gpu_zone);
             }
 
             Ok(texture)
@@ -888,6 +1214,7 @@ impl ImportMem for GlesRenderer {
         let texture = GlesTexture(Arc::new({
             let mut tex = 0;
             unsafe {
+                let gpu_zone = self.gpu_timer.begin(&self.gl, "import_memory");
                 self.gl.GenTextures(1, &mut tex);
                 self.gl.BindTexture(ffi::TEXTURE_2D, tex);
                 self.gl
@@ -906,6 +1233,7 @@ impl ImportMem for GlesRenderer {
                     data.as_ptr() as *const _,
                 );
                 self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+                self.gpu_timer.end(&self.gl, gpu_zone);
             }
             // new texture, upload in full
             GlesTextureInternal {
@@ -950,6 +1278,7 @@ impl ImportMem for GlesRenderer {
         }
 
         unsafe {
+            let gpu_zone = self.gpu_timer.begin(&self.gl, "update_memory");
             self.gl.BindTexture(ffi::TEXTURE_2D, texture.0.texture);
             self.gl
                 .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
@@ -973,6 +1302,7 @@ impl ImportMem for GlesRenderer {
             self.gl.PixelStorei(ffi::UNPACK_SKIP_PIXELS, 0);
             self.gl.PixelStorei(ffi::UNPACK_SKIP_ROWS, 0);
             self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+            self.gpu_timer.end(&self.gl, gpu_zone);
         }
 
         Ok(())
@@ -1077,6 +1407,7 @@ impl ImportDma for GlesRenderer {
 
         self.make_current()?;
         self.existing_dmabuf_texture(buffer)?.map(Ok).unwrap_or_else(|| {
+            self.texture_cache_stats.dmabuf_misses += 1;
             let is_external = !self.egl.dmabuf_render_formats().contains(&buffer.format());
             let image = self
                 .egl
@@ -1099,7 +1430,8 @@ impl ImportDma for GlesRenderer {
                 egl_images: Some(vec![image]),
                 destruction_callback_sender: self.destruction_callback_sender.clone(),
             }));
-            self.dmabuf_cache.insert(buffer.weak(), texture.clone());
+            let evicted = self.dmabuf_cache.insert(buffer.weak(), texture.clone());
+            self.texture_cache_stats.dmabuf_evictions += evicted.len() as u64;
             Ok(texture)
         })
     }
@@ -1118,8 +1450,8 @@ impl ImportDmaWl for GlesRenderer {}
 
 impl GlesRenderer {
     #[profiling::function]
-    fn existing_dmabuf_texture(&self, buffer: &Dmabuf) -> Result<Option<GlesTexture>, GlesError> {
-        let Some(texture) = self.dmabuf_cache.get(&buffer.weak()) else {
+    fn existing_dmabuf_texture(&mut self, buffer: &Dmabuf) -> Result<Option<GlesTexture>, GlesError> {
+        let Some(texture) = self.dmabuf_cache.get(&buffer.weak()).cloned() else {
             return Ok(None);
         };
 
@@ -1131,7 +1463,8 @@ impl GlesRenderer {
             let tex = Some(texture.0.texture);
             self.import_egl_image(egl_images[0], texture.0.is_external, tex)?;
         }
-        Ok(Some(texture.clone()))
+        self.texture_cache_stats.dmabuf_hits += 1;
+        Ok(Some(texture))
     }
 
     #[profiling::function]
@@ -1173,12 +1506,11 @@ impl ExportMem for GlesRenderer {
     ) -> Result<Self::TextureMapping, Self::Error> {
         self.make_current()?;
 
-        let (_, has_alpha) = self
-            .target
-            .as_ref()
-            .ok_or(GlesError::UnknownPixelFormat)?
-            .format()
-            .ok_or(GlesError::UnknownPixelFormat)?;
+        let target = self.target.as_ref().ok_or(GlesError::UnknownPixelFormat)?;
+        if matches!(target, GlesTarget::Renderbuffer { buf, .. } if buf.samples() > 1) {
+            return Err(GlesError::MultisampledFramebuffer);
+        }
+        let (_, has_alpha) = target.format().ok_or(GlesError::UnknownPixelFormat)?;
         let (_, format, layout) = fourcc_to_gl_formats(fourcc).ok_or(GlesError::UnknownPixelFormat)?;
 
         let mut pbo = 0;
@@ -1376,6 +1708,7 @@ impl Bind<Dmabuf> for GlesRenderer {
                 })
                 .map(|buf| Ok((buf.clone(), buf.dmabuf.upgrade().unwrap())))
                 .unwrap_or_else(|| {
+                    self.check_target_size(crate::backend::allocator::Buffer::size(&dmabuf))?;
                     trace!("Creating EGLImage for Dmabuf: {:?}", dmabuf);
                     let image = self
                         .egl
@@ -1492,6 +1825,7 @@ impl Offscreen<GlesTexture> for GlesRenderer {
         format: Fourcc,
         size: Size<i32, BufferCoord>,
     ) -> Result<GlesTexture, GlesError> {
+        self.check_target_size(size)?;
         self.make_current()?;
 
         let has_alpha = has_alpha(format);
@@ -1574,6 +1908,7 @@ impl Offscreen<GlesRenderbuffer> for GlesRenderer {
         if !self.capabilities.contains(&Capability::Renderbuffer) {
             return Err(GlesError::UnsupportedPixelFormat(format));
         }
+        self.check_target_size(size)?;
         self.make_current()?;
 
         let has_alpha = has_alpha(format);
@@ -1597,10 +1932,118 @@ impl Offscreen<GlesRenderbuffer> for GlesRenderer {
                 format: internal,
                 has_alpha,
                 size,
+                samples: 0,
+                destruction_callback_sender: self.destruction_callback_sender.clone(),
+            })))
+        }
+    }
+}
+
+impl GlesRenderer {
+    /// Create a multisampled offscreen renderbuffer
+    ///
+    /// Rendering into a multisampled renderbuffer anti-aliases the edges of the drawn elements,
+    /// e.g. of rotated windows or of vector graphics drawn by the compositor.
+    /// The renderbuffer can be bound and rendered to like any other [`GlesRenderbuffer`], but it needs
+    /// to be resolved into a single-sampled target using [`GlesRenderer::resolve_multisampled_buffer`]
+    /// before being read.
+    ///
+    /// The number of samples is clamped to [`GlesRenderer::max_samples`]. If multisampling is not supported,
+    /// a single-sampled renderbuffer is created instead, check [`GlesRenderbuffer::samples`] if necessary.
+    /// Like for [`Offscreen::create_buffer`], [`Capability::Renderbuffer`] is required and formats other
+    /// than 8-bit RGBA require [`Capability::_10Bit`].
+    #[instrument(level = "trace", parent = &self.span, skip(self))]
+    #[profiling::function]
+    pub fn create_multisampled_buffer(
+        &mut self,
+        format: Fourcc,
+        size: Size<i32, BufferCoord>,
+        samples: u32,
+    ) -> Result<GlesRenderbuffer, GlesError> {
+        let samples = samples.min(self.max_samples);
+        if samples <= 1 {
+            if self.max_samples <= 1 {
+                debug!("Multisampling unsupported, falling back to a single-sampled renderbuffer");
+            }
+            return Offscreen::<GlesRenderbuffer>::create_buffer(self, format, size);
+        }
+        if !self.capabilities.contains(&Capability::Renderbuffer) {
+            return Err(GlesError::UnsupportedPixelFormat(format));
+        }
+        self.check_target_size(size)?;
+        self.make_current()?;
+
+        let has_alpha = has_alpha(format);
+        let (internal, _, _) =
+            fourcc_to_gl_formats(format).ok_or(GlesError::UnsupportedPixelFormat(format))?;
+
+        if internal != ffi::RGBA8 && !self.capabilities.contains(&Capability::_10Bit) {
+            return Err(GlesError::UnsupportedPixelLayout);
+        }
+
+        unsafe {
+            let mut rbo = 0;
+            self.gl.GenRenderbuffers(1, &mut rbo);
+            self.gl.BindRenderbuffer(ffi::RENDERBUFFER, rbo);
+            while self.gl.GetError() != ffi::NO_ERROR {} // clear flag before
+            self.gl.RenderbufferStorageMultisample(
+                ffi::RENDERBUFFER,
+                samples as i32,
+                internal,
+                size.w,
+                size.h,
+            );
+            let err = self.gl.GetError();
+            self.gl.BindRenderbuffer(ffi::RENDERBUFFER, 0);
+
+            if err != ffi::NO_ERROR {
+                self.gl.DeleteRenderbuffers(1, &rbo);
+                // e.g. formats only supporting single-sampled storage
+                debug!(
+                    ?format,
+                    "Failed to allocate multisampled renderbuffer, falling back"
+                );
+                return Offscreen::<GlesRenderbuffer>::create_buffer(self, format, size);
+            }
+
+            Ok(GlesRenderbuffer(Rc::new(GlesRenderbufferInternal {
+                rbo,
+                format: internal,
+                has_alpha,
+                size,
+                samples,
                 destruction_callback_sender: self.destruction_callback_sender.clone(),
             })))
         }
     }
+
+    /// Resolve a multisampled renderbuffer into a single-sampled target
+    ///
+    /// The target needs to have the size of the renderbuffer and may not be multisampled itself.
+    /// Afterwards the target contains the anti-aliased content of the renderbuffer and can be read or
+    /// sampled from. The currently bound target stays bound.
+    #[instrument(level = "trace", parent = &self.span, skip(self, target))]
+    #[profiling::function]
+    pub fn resolve_multisampled_buffer<Target>(
+        &mut self,
+        buffer: &GlesRenderbuffer,
+        target: Target,
+    ) -> Result<(), GlesError>
+    where
+        Self: Bind<Target>,
+    {
+        let size = buffer.size();
+        let rect = Rectangle::from_size((size.w, size.h).into());
+
+        let previous = self.target.take();
+        let result = Bind::<GlesRenderbuffer>::bind(self, buffer.clone())
+            .and_then(|_| self.blit_to(target, rect, rect, TextureFilter::Nearest));
+        let unbind = self.unbind();
+
+        self.target = previous;
+        self.make_current()?;
+        result.and(unbind)
+    }
 }
 
 impl<Target> Blit<Target> for GlesRenderer
@@ -1716,6 +2159,7 @@ impl GlesRenderer {
 
         let errno = unsafe {
             while self.gl.GetError() != ffi::NO_ERROR {} // clear flag before
+            let gpu_zone = self.gpu_timer.begin(&self.gl, "blit");
             self.gl.BlitFramebuffer(
                 src.loc.x,
                 src.loc.y,
@@ -1731,7 +2175,9 @@ impl GlesRenderer {
                     TextureFilter::Nearest => ffi::NEAREST,
                 },
             );
-            self.gl.GetError()
+            let errno = self.gl.GetError();
+            self.gpu_timer.end(&self.gl, gpu_zone);
+            errno
         };
 
         if errno == ffi::INVALID_OPERATION {
@@ -1762,6 +2208,7 @@ impl Drop for GlesRenderer {
         unsafe {
             if self.egl.make_current().is_ok() {
                 self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+                self.gpu_timer.set_enabled(&self.gl, false);
                 self.gl.DeleteProgram(self.solid_program.program);
                 self.gl.DeleteBuffers(self.vbos.len() as i32, self.vbos.as_ptr());
 
@@ -2001,7 +2448,7 @@ impl Renderer for GlesRenderer {
     type Frame<'frame> = GlesFrame<'frame>;
 
     fn id(&self) -> usize {
-        self.egl.user_data().get::<RendererId>().unwrap().0
+        self.egl.user_data().get_key::<RendererIdKey>().0
     }
 
     fn downscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
@@ -2021,6 +2468,13 @@ impl Renderer for GlesRenderer {
     ) -> Result<GlesFrame<'_>, Self::Error> {
         self.make_current()?;
 
+        let gpu_zone = unsafe { self.gpu_timer.begin(&self.gl, "render") };
+
+        let tiles = render_tiles(output_size, self.max_render_size());
+        if !tiles.is_empty() {
+            trace!(size = ?output_size, tiles = tiles.len(), "Rendering in tiles");
+        }
+
         unsafe {
             self.gl.Viewport(0, 0, output_size.w, output_size.h);
 
@@ -2065,8 +2519,10 @@ impl Renderer for GlesRenderer {
             current_projection,
             transform,
             size: output_size,
+            tiles,
             tex_program_override: None,
             finished: AtomicBool::new(false),
+            gpu_zone,
 
             span,
         })
@@ -2288,6 +2744,9 @@ impl GlesFrame<'_> {
         unsafe {
             self.renderer.gl.Disable(ffi::SCISSOR_TEST);
             self.renderer.gl.Disable(ffi::BLEND);
+            self.renderer
+                .gpu_timer
+                .end(&self.renderer.gl, self.gpu_zone.take());
         }
 
         // delayed destruction until the next frame rendering.
@@ -2432,10 +2891,24 @@ impl GlesFrame<'_> {
 
                 gl.VertexAttribDivisor(self.renderer.solid_program.attrib_position as u32, 1);
 
-                gl.DrawArraysInstanced(ffi::TRIANGLE_STRIP, 0, 4, damage_len);
+                draw_tiled(
+                    gl,
+                    &self.tiles,
+                    self.size,
+                    self.renderer.solid_program.uniform_matrix,
+                    &mat,
+                    || gl.DrawArraysInstanced(ffi::TRIANGLE_STRIP, 0, 4, damage_len),
+                );
             } else {
                 let count = damage_len * 6;
-                gl.DrawArrays(ffi::TRIANGLES, 0, count);
+                draw_tiled(
+                    gl,
+                    &self.tiles,
+                    self.size,
+                    self.renderer.solid_program.uniform_matrix,
+                    &mat,
+                    || gl.DrawArrays(ffi::TRIANGLES, 0, count),
+                );
             }
 
             gl.DisableVertexAttribArray(self.renderer.solid_program.attrib_vert as u32);
@@ -2737,10 +3210,24 @@ impl GlesFrame<'_> {
                 gl.VertexAttribDivisor(program.attrib_vert as u32, 0);
                 gl.VertexAttribDivisor(program.attrib_vert_position as u32, 1);
 
-                gl.DrawArraysInstanced(ffi::TRIANGLE_STRIP, 0, 4, damage_len as i32);
+                draw_tiled(
+                    gl,
+                    &self.tiles,
+                    self.size,
+                    program.uniform_matrix,
+                    &matrix,
+                    || gl.DrawArraysInstanced(ffi::TRIANGLE_STRIP, 0, 4, damage_len as i32),
+                );
             } else {
                 let count = damage_len * 6;
-                gl.DrawArrays(ffi::TRIANGLES, 0, count as i32);
+                draw_tiled(
+                    gl,
+                    &self.tiles,
+                    self.size,
+                    program.uniform_matrix,
+                    &matrix,
+                    || gl.DrawArrays(ffi::TRIANGLES, 0, count as i32),
+                );
             }
 
             gl.BindTexture(target, 0);
@@ -2885,10 +3372,24 @@ impl GlesFrame<'_> {
                 gl.VertexAttribDivisor(program.attrib_vert as u32, 0);
                 gl.VertexAttribDivisor(program.attrib_position as u32, 1);
 
-                gl.DrawArraysInstanced(ffi::TRIANGLE_STRIP, 0, 4, damage_len);
+                draw_tiled(
+                    gl,
+                    &self.tiles,
+                    self.size,
+                    program.uniform_matrix,
+                    &matrix,
+                    || gl.DrawArraysInstanced(ffi::TRIANGLE_STRIP, 0, 4, damage_len),
+                );
             } else {
                 let count = damage_len * 6;
-                gl.DrawArrays(ffi::TRIANGLES, 0, count);
+                draw_tiled(
+                    gl,
+                    &self.tiles,
+                    self.size,
+                    program.uniform_matrix,
+                    &matrix,
+                    || gl.DrawArrays(ffi::TRIANGLES, 0, count),
+                );
             }
 
             gl.DisableVertexAttribArray(program.attrib_vert as u32);
@@ -2985,10 +3486,139 @@ fn build_texture_mat(
 
 #[cfg(test)]
 mod tests {
-    use super::build_texture_mat;
-    use crate::utils::{Buffer, Physical, Rectangle, Size, Transform};
+    use super::{
+        build_texture_mat, render_tiles, tile_projection, Capability, GlesError, GlesRenderbuffer,
+        GlesRenderer,
+    };
+    use crate::{
+        backend::{
+            allocator::Fourcc,
+            egl::{EGLContext, EGLDevice, EGLDisplay},
+            renderer::{Bind, Color32F, ExportMem, Frame, Offscreen, Renderer},
+        },
+        utils::{Buffer, Physical, Rectangle, Size, Transform},
+    };
     use cgmath::Vector3;
 
+    // renderer on the first egl device, e.g. mesa's software renderer, if any
+    fn renderer() -> Option<GlesRenderer> {
+        let device = EGLDevice::enumerate().ok()?.next()?;
+        let display = unsafe { EGLDisplay::new(device) }.ok()?;
+        let context = EGLContext::new(&display).ok()?;
+        unsafe { GlesRenderer::new(context) }.ok()
+    }
+
+    fn read_pixel(renderer: &mut GlesRenderer) -> Vec<u8> {
+        let mapping = renderer
+            .copy_framebuffer(Rectangle::from_size((1, 1).into()), Fourcc::Abgr8888)
+            .unwrap();
+        renderer.map_texture(&mapping).unwrap().to_vec()
+    }
+
+    #[test]
+    fn multisampled_buffer_resolve() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        if !renderer.capabilities().contains(&Capability::Multisample) {
+            return;
+        }
+
+        let size = Size::from((16, 16));
+        let buffer = renderer
+            .create_multisampled_buffer(Fourcc::Abgr8888, size, 4)
+            .unwrap();
+        assert_eq!(buffer.samples(), renderer.max_samples().min(4));
+
+        renderer.bind(buffer.clone()).unwrap();
+        let mut frame = renderer.render((16, 16).into(), Transform::Normal).unwrap();
+        frame
+            .clear(
+                Color32F::new(1.0, 0.0, 0.0, 1.0),
+                &[Rectangle::from_size((16, 16).into())],
+            )
+            .unwrap();
+        let _ = frame.finish().unwrap();
+
+        // reading requires resolving first
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_size((1, 1).into()), Fourcc::Abgr8888),
+            Err(GlesError::MultisampledFramebuffer)
+        ));
+
+        let resolved: GlesRenderbuffer = renderer.create_buffer(Fourcc::Abgr8888, size).unwrap();
+        renderer
+            .resolve_multisampled_buffer(&buffer, resolved.clone())
+            .unwrap();
+        // the multisampled buffer stays bound
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_size((1, 1).into()), Fourcc::Abgr8888),
+            Err(GlesError::MultisampledFramebuffer)
+        ));
+
+        renderer.bind(resolved).unwrap();
+        assert_eq!(read_pixel(&mut renderer), vec![255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn multisampled_buffer_limits() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        if !renderer.capabilities().contains(&Capability::Multisample) {
+            return;
+        }
+
+        let max = renderer.max_target_size();
+        let too_large = Size::from((max.w + 1, 1));
+        assert!(matches!(
+            renderer.create_multisampled_buffer(Fourcc::Abgr8888, too_large, 4),
+            Err(GlesError::TargetTooLarge(_))
+        ));
+        assert!(matches!(
+            renderer.create_multisampled_buffer(Fourcc::Nv12, (16, 16).into(), 4),
+            Err(GlesError::UnsupportedPixelFormat(_))
+        ));
+
+        // a single sample falls back to a regular renderbuffer
+        let buffer = renderer
+            .create_multisampled_buffer(Fourcc::Abgr8888, (16, 16).into(), 1)
+            .unwrap();
+        assert_eq!(buffer.samples(), 1);
+    }
+
+    #[test]
+    fn tiles_only_if_necessary() {
+        let size = Size::from((300, 100));
+        assert!(render_tiles(size, (300, 100).into()).is_empty());
+        assert!(render_tiles(size, (0, 0).into()).is_empty());
+        assert_eq!(
+            render_tiles(size, (128, 128).into()),
+            vec![
+                Rectangle::new((0, 0).into(), (128, 100).into()),
+                Rectangle::new((128, 0).into(), (128, 100).into()),
+                Rectangle::new((256, 0).into(), (44, 100).into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tile_projection_maps_tile_to_viewport() {
+        let size: Size<i32, Physical> = Size::from((200, 100));
+        let tile: Rectangle<i32, Physical> = Rectangle::new((100, 0).into(), (100, 50).into());
+        let projection = tile_projection(tile, size);
+
+        // corners of the tile in the normalized device coordinates of the whole target
+        let bottom_left = Vector3::new(0f32, -1f32, 1f32);
+        let top_right = Vector3::new(1f32, 0f32, 1f32);
+        assert_eq!(projection * bottom_left, Vector3::new(-1f32, -1f32, 1f32));
+        assert_eq!(projection * top_right, Vector3::new(1f32, 1f32, 1f32));
+
+        // a tile covering the whole target does not change the projection
+        let full = tile_projection(Rectangle::from_size(size), size);
+        assert_eq!(full * top_right, top_right);
+    }
+
     #[test]
     fn texture_normal_double_size() {
         let src: Rectangle<f64, Buffer> = Rectangle::from_size((1000f64, 500f64).into());
@@ -0,0 +1,138 @@
+use std::{fmt, sync::mpsc::Sender};
+
+use super::{ffi, Capability, CleanupResource, GlesError, GlesRenderer, GlesTexture, GlesTextureInternal};
+use crate::{
+    backend::{
+        egl::{context::ContextPriority, fence::EGLFence, EGLContext, Error as EGLError},
+        renderer::{sync::SyncPoint, Renderer},
+    },
+    utils::{Buffer as BufferCoord, Size},
+};
+
+/// Additional [`EGLContext`] sharing resources with a [`GlesRenderer`]
+///
+/// This allows running custom GL code next to the renderer, e.g. an effects engine or a video
+/// player like libmpv, without interfering with the state of the renderer. Textures created in this
+/// context can be turned into a [`GlesTexture`] using [`GlesSharedContext::texture_from_raw`] and
+/// rendered by the renderer, textures of the renderer can be used through [`GlesTexture::tex_id`].
+/// Both report the same [`Renderer::id`], so cached textures stay valid across them.
+///
+/// ## Threading
+///
+/// The context is only current during [`GlesSharedContext::with_context`] and can be sent to and used
+/// on another thread, while the renderer keeps rendering on its own. GL commands of different contexts
+/// are not ordered, so use the [`SyncPoint`] returned by [`GlesSharedContext::with_context`] before
+/// rendering textures written in the shared context. Textures must not be written to by the shared context
+/// while the renderer might read from them.
+pub struct GlesSharedContext {
+    egl: EGLContext,
+    gl: ffi::Gles2,
+    id: usize,
+    supports_fencing: bool,
+    destruction_callback_sender: Sender<CleanupResource>,
+}
+
+impl fmt::Debug for GlesSharedContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GlesSharedContext")
+            .field("egl", &self.egl)
+            .field("id", &self.id)
+            .field("supports_fencing", &self.supports_fencing)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GlesSharedContext {
+    /// Create a new context sharing resources with `renderer`
+    pub fn new(renderer: &GlesRenderer) -> Result<Self, EGLError> {
+        let egl = EGLContext::new_shared(renderer.egl.display(), &renderer.egl)?;
+        Ok(Self::from_context(renderer, egl))
+    }
+
+    /// Create a new context with the specified priority sharing resources with `renderer`
+    ///
+    /// Note: The priority is a hint that might be ignored by the underlying platform.
+    pub fn new_with_priority(renderer: &GlesRenderer, priority: ContextPriority) -> Result<Self, EGLError> {
+        let egl = EGLContext::new_shared_with_priority(renderer.egl.display(), &renderer.egl, priority)?;
+        Ok(Self::from_context(renderer, egl))
+    }
+
+    fn from_context(renderer: &GlesRenderer, egl: EGLContext) -> Self {
+        let gl = ffi::Gles2::load_with(|s| unsafe { crate::backend::egl::get_proc_address(s) } as *const _);
+        GlesSharedContext {
+            egl,
+            gl,
+            id: renderer.id(),
+            supports_fencing: renderer.capabilities.contains(&Capability::Fencing),
+            destruction_callback_sender: renderer.destruction_callback_sender.clone(),
+        }
+    }
+
+    /// Get access to the underlying [`EGLContext`]
+    pub fn egl_context(&self) -> &EGLContext {
+        &self.egl
+    }
+
+    /// Returns the [`Renderer::id`] of the renderer this context shares resources with
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Run custom code in this context
+    ///
+    /// The context is made current on the calling thread and unbound again afterwards.
+    /// The returned [`SyncPoint`] signals once the GL commands issued by `func` are completed.
+    pub fn with_context<F, R>(&mut self, func: F) -> Result<(R, SyncPoint), GlesError>
+    where
+        F: FnOnce(&ffi::Gles2) -> R,
+    {
+        // SAFETY: The context is unbound before returning, so it is never current on another thread
+        unsafe { self.egl.make_current()? };
+        let res = func(&self.gl);
+
+        let fence = if self.supports_fencing {
+            EGLFence::create(self.egl.display()).ok()
+        } else {
+            None
+        };
+        let sync = match fence {
+            Some(fence) => {
+                unsafe { self.gl.Flush() };
+                SyncPoint::from(fence)
+            }
+            None => {
+                unsafe { self.gl.Finish() };
+                SyncPoint::signaled()
+            }
+        };
+
+        self.egl.unbind()?;
+        Ok((res, sync))
+    }
+
+    /// Create a [`GlesTexture`] from a raw gl texture id created in this context.
+    ///
+    /// See [`GlesTexture::from_raw`] for details.
+    ///
+    /// # Safety
+    ///
+    /// The context cannot make sure `tex` is a valid texture id.
+    pub unsafe fn texture_from_raw(
+        &self,
+        internal_format: Option<ffi::types::GLenum>,
+        opaque: bool,
+        tex: ffi::types::GLuint,
+        size: Size<i32, BufferCoord>,
+    ) -> GlesTexture {
+        GlesTexture(std::sync::Arc::new(GlesTextureInternal {
+            texture: tex,
+            format: internal_format,
+            has_alpha: !opaque,
+            is_external: false,
+            y_inverted: false,
+            size,
+            egl_images: None,
+            destruction_callback_sender: self.destruction_callback_sender.clone(),
+        }))
+    }
+}
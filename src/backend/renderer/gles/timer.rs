@@ -0,0 +1,327 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use tracing::trace;
+
+use super::ffi::{self, types::GLuint};
+
+// zones started while this many zones are waiting for their results are not timed
+const MAX_PENDING_ZONES: usize = 64;
+// resolved zones not retrieved through `GlesRenderer::gpu_zones` are dropped beyond this limit
+const MAX_ZONES: usize = 256;
+
+/// Time an operation of the [`GlesRenderer`](super::GlesRenderer) took on the gpu
+///
+/// See [`GlesRenderer::set_gpu_timing`](super::GlesRenderer::set_gpu_timing).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuZone {
+    /// Name of the operation, e.g. `render`, `blit` or `import_shm_buffer`
+    pub name: &'static str,
+    /// Time the operation was submitted on the cpu
+    pub submitted: Instant,
+    /// Time between the gpu starting and finishing the operation
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct GpuZoneToken(u64);
+
+struct PendingZone {
+    id: u64,
+    name: &'static str,
+    submitted: Instant,
+    start: GLuint,
+    end: Option<GLuint>,
+    #[cfg(feature = "profile-with-tracy")]
+    span: Option<profiling::tracy_client::GpuSpan>,
+}
+
+/// Times renderer operations using `GL_EXT_disjoint_timer_query`
+///
+/// Query objects are reused and results are kept in bounded queues,
+/// so timing does not allocate once warmed up.
+pub(super) struct GpuTimer {
+    enabled: bool,
+    next_id: u64,
+    free_queries: Vec<GLuint>,
+    pending: VecDeque<PendingZone>,
+    zones: VecDeque<GpuZone>,
+    #[cfg(feature = "profile-with-tracy")]
+    tracy: Option<profiling::tracy_client::GpuContext>,
+}
+
+// tracy's gpu types don't implement `Debug`
+impl std::fmt::Debug for PendingZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingZone")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("submitted", &self.submitted)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for GpuTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuTimer")
+            .field("enabled", &self.enabled)
+            .field("next_id", &self.next_id)
+            .field("free_queries", &self.free_queries)
+            .field("pending", &self.pending)
+            .field("zones", &self.zones)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GpuTimer {
+    pub(super) fn new() -> Self {
+        GpuTimer {
+            enabled: false,
+            next_id: 0,
+            free_queries: Vec::new(),
+            pending: VecDeque::new(),
+            zones: VecDeque::new(),
+            #[cfg(feature = "profile-with-tracy")]
+            tracy: None,
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // requires the context to be current
+    pub(super) unsafe fn set_enabled(&mut self, gl: &ffi::Gles2, enabled: bool) {
+        if self.enabled == enabled {
+            return;
+        }
+        self.enabled = enabled;
+
+        if enabled {
+            self.pending.reserve(MAX_PENDING_ZONES);
+            self.zones.reserve(MAX_ZONES);
+            #[cfg(feature = "profile-with-tracy")]
+            {
+                self.tracy = self.create_tracy_context(gl);
+            }
+        } else {
+            #[cfg(feature = "profile-with-tracy")]
+            for zone in &mut self.pending {
+                if let Some(span) = zone.span.take() {
+                    span.upload_timestamp_start(0);
+                    span.upload_timestamp_end(0);
+                }
+            }
+            for zone in self.pending.drain(..) {
+                gl.DeleteQueriesEXT(1, &zone.start);
+                if let Some(end) = zone.end {
+                    gl.DeleteQueriesEXT(1, &end);
+                }
+            }
+            gl.DeleteQueriesEXT(self.free_queries.len() as i32, self.free_queries.as_ptr());
+            self.free_queries.clear();
+            self.zones.clear();
+            #[cfg(feature = "profile-with-tracy")]
+            {
+                self.tracy = None;
+            }
+        }
+    }
+
+    #[cfg(feature = "profile-with-tracy")]
+    unsafe fn create_tracy_context(
+        &mut self,
+        gl: &ffi::Gles2,
+    ) -> Option<profiling::tracy_client::GpuContext> {
+        let client = profiling::tracy_client::Client::running()?;
+
+        // calibrate the gpu clock, tracy correlates it with the cpu clock from here on
+        let query = self.query(gl);
+        gl.QueryCounterEXT(query, ffi::TIMESTAMP_EXT);
+        gl.Finish();
+        let mut timestamp = 0;
+        gl.GetQueryObjecti64vEXT(query, ffi::QUERY_RESULT_EXT, &mut timestamp);
+        self.free_queries.push(query);
+
+        client
+            .new_gpu_context(
+                Some("GlesRenderer"),
+                profiling::tracy_client::GpuContextType::OpenGL,
+                timestamp,
+                1.0,
+            )
+            .map_err(|err| tracing::debug!("Failed to create tracy gpu context: {}", err))
+            .ok()
+    }
+
+    unsafe fn query(&mut self, gl: &ffi::Gles2) -> GLuint {
+        self.free_queries.pop().unwrap_or_else(|| {
+            let mut query = 0;
+            gl.GenQueriesEXT(1, &mut query);
+            query
+        })
+    }
+
+    // requires the context to be current
+    pub(super) unsafe fn begin(&mut self, gl: &ffi::Gles2, name: &'static str) -> Option<GpuZoneToken> {
+        if !self.enabled {
+            return None;
+        }
+        if self.pending.len() >= MAX_PENDING_ZONES {
+            self.poll(gl);
+            if self.pending.len() >= MAX_PENDING_ZONES {
+                trace!(name, "Too many pending gpu zones, skipping");
+                return None;
+            }
+        }
+
+        let start = self.query(gl);
+        gl.QueryCounterEXT(start, ffi::TIMESTAMP_EXT);
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.pending.push_back(PendingZone {
+            id,
+            name,
+            submitted: Instant::now(),
+            start,
+            end: None,
+            #[cfg(feature = "profile-with-tracy")]
+            span: self
+                .tracy
+                .as_ref()
+                .and_then(|context| context.span_alloc(name, name, file!(), line!()).ok()),
+        });
+        Some(GpuZoneToken(id))
+    }
+
+    // requires the context to be current
+    pub(super) unsafe fn end(&mut self, gl: &ffi::Gles2, token: Option<GpuZoneToken>) {
+        let Some(token) = token else {
+            return;
+        };
+        let Some(index) = self.pending.iter().rposition(|zone| zone.id == token.0) else {
+            return;
+        };
+
+        let end = self.query(gl);
+        gl.QueryCounterEXT(end, ffi::TIMESTAMP_EXT);
+        let zone = &mut self.pending[index];
+        zone.end = Some(end);
+        #[cfg(feature = "profile-with-tracy")]
+        if let Some(span) = zone.span.as_mut() {
+            span.end_zone();
+        }
+    }
+
+    // requires the context to be current
+    pub(super) unsafe fn poll(&mut self, gl: &ffi::Gles2) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        // results are meaningless, if the gpu clock was disturbed (e.g. by power management)
+        let mut disjoint = 0;
+        gl.GetIntegerv(ffi::GPU_DISJOINT_EXT, &mut disjoint);
+
+        while let Some(zone) = self.pending.front() {
+            let Some(end) = zone.end else {
+                break;
+            };
+            let mut available = 0;
+            gl.GetQueryObjectuivEXT(end, ffi::QUERY_RESULT_AVAILABLE_EXT, &mut available);
+            if available == 0 {
+                break;
+            }
+
+            #[allow(unused_mut)]
+            let mut zone = self.pending.pop_front().unwrap();
+            let (mut start_time, mut end_time) = (0u64, 0u64);
+            gl.GetQueryObjectui64vEXT(zone.start, ffi::QUERY_RESULT_EXT, &mut start_time);
+            gl.GetQueryObjectui64vEXT(end, ffi::QUERY_RESULT_EXT, &mut end_time);
+            self.free_queries.push(zone.start);
+            self.free_queries.push(end);
+
+            #[cfg_attr(not(feature = "profile-with-tracy"), allow(unused_variables))]
+            let duration = self.resolve(zone.name, zone.submitted, start_time, end_time, disjoint != 0);
+
+            #[cfg(feature = "profile-with-tracy")]
+            if let Some(span) = zone.span.take() {
+                span.upload_timestamp_start(start_time as i64);
+                span.upload_timestamp_end((start_time + duration) as i64);
+            }
+        }
+    }
+
+    // returns the duration in nanoseconds reported for the zone
+    fn resolve(
+        &mut self,
+        name: &'static str,
+        submitted: Instant,
+        start_time: u64,
+        end_time: u64,
+        disjoint: bool,
+    ) -> u64 {
+        if disjoint {
+            trace!(name, "Discarding disjoint gpu zone");
+            return 0;
+        }
+
+        let duration = end_time.saturating_sub(start_time);
+        if self.zones.len() >= MAX_ZONES {
+            self.zones.pop_front();
+        }
+        self.zones.push_back(GpuZone {
+            name,
+            submitted,
+            duration: Duration::from_nanos(duration),
+        });
+        duration
+    }
+
+    pub(super) fn drain(&mut self) -> impl Iterator<Item = GpuZone> + '_ {
+        self.zones.drain(..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{GpuTimer, MAX_ZONES};
+
+    #[test]
+    fn resolved_zones() {
+        let mut timer = GpuTimer::new();
+        let submitted = Instant::now();
+        assert_eq!(timer.resolve("render", submitted, 1_000, 3_500, false), 2_500);
+        // the clock may have been reset in between
+        assert_eq!(timer.resolve("blit", submitted, 5_000, 4_000, false), 0);
+        assert_eq!(timer.resolve("blit", submitted, 0, 1_000, true), 0);
+
+        let zones = timer.drain().collect::<Vec<_>>();
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones[0].name, "render");
+        assert_eq!(zones[0].duration, Duration::from_nanos(2_500));
+        assert_eq!(zones[1].duration, Duration::ZERO);
+        assert_eq!(timer.drain().count(), 0);
+    }
+
+    #[test]
+    fn resolved_zones_are_bounded() {
+        let mut timer = GpuTimer::new();
+        let submitted = Instant::now();
+        for i in 0..MAX_ZONES as u64 + 10 {
+            timer.resolve("render", submitted, 0, i, false);
+        }
+
+        let zones = timer.drain().collect::<Vec<_>>();
+        assert_eq!(zones.len(), MAX_ZONES);
+        // the oldest zones are dropped
+        assert_eq!(zones[0].duration, Duration::from_nanos(10));
+    }
+}
@@ -152,7 +152,7 @@ impl<R, A: AsFd + Clone + Send + 'static> GbmGlesBackend<R, A> {
     }
 }
 
-impl<R: From<GlesRenderer> + Renderer<Error = GlesError>, A: AsFd + Clone + 'static> GraphicsApi
+impl<R: From<GlesRenderer> + Renderer<Error = GlesError>, A: AsFd + Clone + Send + Sync + 'static> GraphicsApi
     for GbmGlesBackend<R, A>
 {
     type Device = GbmGlesDevice<R>;
@@ -219,8 +219,11 @@ impl<R: From<GlesRenderer> + Renderer<Error = GlesError>, A: AsFd + Clone + 'sta
 }
 
 // TODO: Replace with specialization impl in multigpu/mod once possible
-impl<T: GraphicsApi, R: From<GlesRenderer> + Renderer<Error = GlesError>, A: AsFd + Clone + 'static>
-    std::convert::From<GlesError> for MultiError<GbmGlesBackend<R, A>, T>
+impl<
+        T: GraphicsApi,
+        R: From<GlesRenderer> + Renderer<Error = GlesError>,
+        A: AsFd + Clone + Send + Sync + 'static,
+    > std::convert::From<GlesError> for MultiError<GbmGlesBackend<R, A>, T>
 where
     T::Error: 'static,
     <<T::Device as ApiDevice>::Renderer as Renderer>::Error: 'static,
@@ -268,7 +271,7 @@ impl<R: Renderer> ApiDevice for GbmGlesDevice<R> {
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
 impl<R, A> ImportEgl for MultiRenderer<'_, '_, GbmGlesBackend<R, A>, GbmGlesBackend<R, A>>
 where
-    A: AsFd + Clone + 'static,
+    A: AsFd + Clone + Send + Sync + 'static,
     R: From<GlesRenderer>
         + BorrowMut<GlesRenderer>
         + Renderer<Error = GlesError>
@@ -312,7 +315,8 @@ where
         {
             let texture = MultiTexture::from_surface(surface, dmabuf.size(), dmabuf.format());
             let texture_ref = texture.0.clone();
-            let res = self.import_dmabuf_internal(&dmabuf, texture, Some(damage));
+            let pinned = self.pins.pinned_node(buffer, surface);
+            let res = self.import_dmabuf_internal(&dmabuf, texture, Some(damage), pinned);
             if res.is_ok() {
                 if let Some(surface) = surface {
                     surface.data_map.insert_if_missing_threadsafe(|| texture_ref);
@@ -328,7 +332,7 @@ where
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
 impl<R, A> MultiRenderer<'_, '_, GbmGlesBackend<R, A>, GbmGlesBackend<R, A>>
 where
-    A: AsFd + Clone + 'static,
+    A: AsFd + Clone + Send + Sync + 'static,
     R: From<GlesRenderer>
         + BorrowMut<GlesRenderer>
         + Renderer<Error = GlesError>
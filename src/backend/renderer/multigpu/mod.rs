@@ -73,7 +73,11 @@ use crate::{
 };
 use tracing::{debug, info, info_span, instrument, trace, trace_span, warn};
 #[cfg(feature = "wayland_frontend")]
-use wayland_server::protocol::{wl_buffer, wl_shm, wl_surface::WlSurface};
+use wayland_server::{
+    backend::ClientId,
+    protocol::{wl_buffer, wl_shm, wl_surface::WlSurface},
+    Resource,
+};
 
 #[cfg(all(feature = "backend_gbm", feature = "backend_egl", feature = "renderer_gl"))]
 pub mod gbm;
@@ -84,9 +88,35 @@ pub struct GpuManager<A: GraphicsApi> {
     api: A,
     devices: Vec<A::Device>,
     dmabuf_cache: HashMap<(DrmNode, DrmNode), Option<(bool, Dmabuf)>>,
+    pins: ImportPins,
     span: tracing::Span,
 }
 
+/// Devices buffers of specific clients should be imported on,
+/// overriding the default import heuristic.
+#[derive(Debug, Default)]
+struct ImportPins {
+    #[cfg(feature = "wayland_frontend")]
+    clients: HashMap<ClientId, DrmNode>,
+}
+
+impl ImportPins {
+    #[cfg(feature = "wayland_frontend")]
+    fn pinned_node(&self, buffer: &wl_buffer::WlBuffer, surface: Option<&SurfaceData>) -> Option<DrmNode> {
+        surface
+            .and_then(|surface| surface.data_map.try_get_key::<PinnedImportNode>())
+            .and_then(|pin| *pin.lock().unwrap())
+            .or_else(|| {
+                buffer
+                    .client()
+                    .and_then(|client| self.clients.get(&client.id()).copied())
+            })
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+crate::user_data_key!(PinnedImportNode: Mutex<Option<DrmNode>> = Mutex::new(None));
+
 /// Errors generated by [`GpuManager`] and [`MultiRenderer`].
 #[derive(thiserror::Error)]
 pub enum Error<R: GraphicsApi, T: GraphicsApi>
@@ -227,10 +257,70 @@ impl<A: GraphicsApi> GpuManager<A> {
             api,
             devices,
             dmabuf_cache: HashMap::new(),
+            pins: ImportPins::default(),
             span,
         })
     }
 
+    /// Pin the buffers of a given surface to be imported on a specific device.
+    ///
+    /// This overrides the default heuristic of importing a dmabuf on the device it was
+    /// allocated on (or the render device, if that is unknown), e.g. to use a dedicated
+    /// decode device for video applications. If the import on the pinned device fails,
+    /// the default heuristic is used as a fallback.
+    ///
+    /// Surface pins take precedence over client pins set via [`GpuManager::pin_client`].
+    ///
+    /// *Note*: This only affects imports done via [`ImportDmaWl`] or [`GpuManager::early_import`].
+    #[cfg(feature = "wayland_frontend")]
+    pub fn pin_surface(&self, surface: &WlSurface, node: DrmNode) {
+        crate::wayland::compositor::with_states(surface, |states| {
+            *states.data_map.get_key::<PinnedImportNode>().lock().unwrap() = Some(node);
+        });
+    }
+
+    /// Remove a pin previously set via [`GpuManager::pin_surface`].
+    #[cfg(feature = "wayland_frontend")]
+    pub fn unpin_surface(&self, surface: &WlSurface) {
+        crate::wayland::compositor::with_states(surface, |states| {
+            if let Some(pin) = states.data_map.try_get_key::<PinnedImportNode>() {
+                *pin.lock().unwrap() = None;
+            }
+        });
+    }
+
+    /// Pin all buffers of a given client to be imported on a specific device.
+    ///
+    /// See [`GpuManager::pin_surface`] for details.
+    ///
+    /// Pins are not automatically cleared once a client disconnects,
+    /// use [`GpuManager::unpin_client`] for that.
+    #[cfg(feature = "wayland_frontend")]
+    pub fn pin_client(&mut self, client: ClientId, node: DrmNode) {
+        self.pins.clients.insert(client, node);
+    }
+
+    /// Remove a pin previously set via [`GpuManager::pin_client`].
+    #[cfg(feature = "wayland_frontend")]
+    pub fn unpin_client(&mut self, client: &ClientId) {
+        self.pins.clients.remove(client);
+    }
+
+    /// Returns the device the current buffer of the given surface was imported on,
+    /// before being copied to any other devices.
+    ///
+    /// Returns `None` if the buffer was not yet imported by a [`MultiRenderer`].
+    /// This is mostly useful for debugging unexpected cross-gpu copies.
+    #[cfg(feature = "wayland_frontend")]
+    pub fn surface_import_node(surface: &WlSurface) -> Option<DrmNode> {
+        crate::wayland::compositor::with_states(surface, |states| {
+            states
+                .data_map
+                .get::<Arc<Mutex<MultiTextureInternal>>>()
+                .and_then(|texture| texture.lock().unwrap().import_node)
+        })
+    }
+
     /// Get all devices enumerated by the API.
     pub fn devices(&mut self) -> Result<impl Iterator<Item = &A::Device>, A::Error> {
         if self.api.needs_enumeration() {
@@ -275,6 +365,8 @@ impl<A: GraphicsApi> GpuManager<A> {
             render: render.remove(0),
             target: None,
             other_renderers: others,
+            pins: &self.pins,
+            pending_waits: Vec::new(),
             span: tracing::Span::current(),
         })
     }
@@ -336,6 +428,8 @@ impl<A: GraphicsApi> GpuManager<A> {
                     format: copy_format,
                 }),
                 other_renderers: others,
+                pins: &self.pins,
+                pending_waits: Vec::new(),
                 span: tracing::Span::current(),
             })
         } else {
@@ -343,11 +437,62 @@ impl<A: GraphicsApi> GpuManager<A> {
                 render: render.remove(0),
                 target: None,
                 other_renderers: others,
+                pins: &self.pins,
+                pending_waits: Vec::new(),
                 span: tracing::Span::current(),
             })
         }
     }
 
+    /// Returns the formats buffers can be shared in between two devices
+    ///
+    /// These are the formats the `render_device` can render into and the `target_device` can import
+    /// with an explicit modifier, as implicit modifiers aren't guaranteed to be compatible across devices.
+    /// A [`MultiRenderer`] created by [`GpuManager::renderer`] negotiates the buffers used to
+    /// export its frames to the `target_device` from this set and falls back to copying through
+    /// cpu memory, if no modifier of the `copy_format` is contained.
+    ///
+    /// This allows to use a device without display hardware, e.g. a render node returned by
+    /// [`render_only_gpus`](crate::backend::udev::render_only_gpus), for rendering the outputs of
+    /// a display device and to pick a `copy_format` upfront.
+    #[instrument(level = "trace", parent = &self.span, skip(self))]
+    pub fn shared_formats(
+        &mut self,
+        render_device: &DrmNode,
+        target_device: &DrmNode,
+    ) -> Result<FormatSet, Error<A, A>>
+    where
+        <A::Device as ApiDevice>::Renderer: Bind<Dmabuf> + ImportDma,
+    {
+        if !self.devices.iter().any(|device| device.node() == render_device)
+            || !self.devices.iter().any(|device| device.node() == target_device)
+            || self.api.needs_enumeration()
+        {
+            self.api
+                .enumerate(&mut self.devices)
+                .map_err(Error::RenderApiError)?;
+        }
+
+        let render = self
+            .devices
+            .iter()
+            .find(|device| device.node() == render_device)
+            .ok_or(Error::NoDevice(*render_device))?;
+        let target = self
+            .devices
+            .iter()
+            .find(|device| device.node() == target_device)
+            .ok_or(Error::NoDevice(*target_device))?;
+
+        let render_formats = Bind::<Dmabuf>::supported_formats(render.renderer()).unwrap_or_default();
+        let target_formats = ImportDma::dmabuf_formats(target.renderer());
+        Ok(render_formats
+            .intersection(&target_formats)
+            .filter(|format| format.modifier != Modifier::Invalid)
+            .copied()
+            .collect())
+    }
+
     /// Create a [`MultiRenderer`] from two different [`GraphicsApi`]s.
     ///
     /// - `render_api` should be the [`GpuManager`] used for the `render_device`.
@@ -432,6 +577,8 @@ impl<A: GraphicsApi> GpuManager<A> {
                     format: copy_format,
                 }),
                 other_renderers: others,
+                pins: &render_api.pins,
+                pending_waits: Vec::new(),
                 span: tracing::Span::current(),
             })
         } else {
@@ -439,6 +586,8 @@ impl<A: GraphicsApi> GpuManager<A> {
                 render: render.remove(0),
                 target: None,
                 other_renderers: others,
+                pins: &render_api.pins,
+                pending_waits: Vec::new(),
                 span: tracing::Span::current(),
             })
         }
@@ -466,7 +615,7 @@ impl<A: GraphicsApi> GpuManager<A> {
         <<A::Device as ApiDevice>::Renderer as ExportMem>::TextureMapping: 'static,
     {
         use crate::{
-            backend::renderer::utils::RendererSurfaceStateUserData,
+            backend::renderer::{sync::Fence, utils::RendererSurfaceStateUserData},
             wayland::compositor::{with_surface_tree_upward, TraversalAction},
         };
 
@@ -483,8 +632,13 @@ impl<A: GraphicsApi> GpuManager<A> {
                     let mut data_ref = data.lock().unwrap();
                     let data = &mut *data_ref;
                     if data.textures.is_empty() {
-                        // Import a new buffer if available
-                        if let Some(buffer) = data.buffer.as_ref() {
+                        // Import a new buffer if available, copies of buffers the client might still render to
+                        // are left to the import during rendering, which waits for the acquire point
+                        if let Some(buffer) = data
+                            .buffer
+                            .as_ref()
+                            .filter(|buffer| buffer.acquire_point().map_or(true, |point| point.is_signaled()))
+                        {
                             // We do an optimistic optimization here, so contrary to many much more defensive damage-tracking algorithms,
                             // we only import the most recent set of damage here.
                             // If we need more on rendering - which we cannot know at this point - we will call import_missing later
@@ -555,9 +709,11 @@ impl<A: GraphicsApi> GpuManager<A> {
                     return Err(Error::DeviceMissing);
                 }
 
+                let pinned = self.pins.pinned_node(buffer, Some(surface));
                 let mut devices = self.devices.iter_mut();
                 let first = devices.next().unwrap();
-                let src_node = import_on_src_node(dmabuf, Some(damage), &mut texture, first, None, devices)?;
+                let src_node =
+                    import_on_src_node(dmabuf, Some(damage), &mut texture, first, None, devices, pinned)?;
 
                 if src_node != target_node {
                     let mut texture_internal = texture.0.lock().unwrap();
@@ -724,6 +880,10 @@ pub struct MultiRenderer<'render, 'target, R: GraphicsApi, T: GraphicsApi> {
     render: &'render mut R::Device,
     target: Option<TargetData<'target, T>>,
     other_renderers: Vec<&'render mut R::Device>,
+    #[cfg_attr(not(feature = "wayland_frontend"), allow(dead_code))]
+    pins: &'render ImportPins,
+    // sync points the next copy from another device has to wait for
+    pending_waits: Vec<sync::SyncPoint>,
     span: tracing::Span,
 }
 
@@ -1114,7 +1274,12 @@ where
 
     #[profiling::function]
     fn wait(&mut self, sync: &sync::SyncPoint) -> Result<(), Self::Error> {
-        self.render.renderer_mut().wait(sync).map_err(Error::Render)
+        self.render.renderer_mut().wait(sync).map_err(Error::Render)?;
+        // buffers imported afterwards might be copied from another device
+        if !sync.is_reached() {
+            self.pending_waits.push(sync.clone());
+        }
+        Ok(())
     }
 
     #[profiling::function]
@@ -1403,6 +1568,7 @@ struct MultiTextureInternal {
     format: Option<Fourcc>,
     #[allow(dead_code)]
     buffer_format: Format,
+    import_node: Option<DrmNode>,
 }
 // SAFETY: We require `Send` for textures of renderers suitable for the MultiRenderer.
 //  Type erasure just forces us to do this instead.
@@ -1446,6 +1612,7 @@ impl MultiTexture {
                     size,
                     format: None,
                     buffer_format,
+                    import_node: None,
                 }))
             });
         {
@@ -1453,6 +1620,7 @@ impl MultiTexture {
             if internal.size != size || internal.buffer_format != buffer_format {
                 internal.textures.clear();
                 internal.format = None;
+                internal.import_node = None;
                 internal.size = size;
                 internal.buffer_format = buffer_format;
             }
@@ -1466,9 +1634,18 @@ impl MultiTexture {
             size,
             format: None,
             buffer_format,
+            import_node: None,
         })))
     }
 
+    /// Returns the device the buffer backing this texture was imported on,
+    /// before being copied to any other devices.
+    ///
+    /// Returns `None` for textures not created from a dmabuf.
+    pub fn import_node(&self) -> Option<DrmNode> {
+        self.0.lock().unwrap().import_node
+    }
+
     /// Attempt to get a texture of type `T: Renderer::TextureId` given the renderer type `A` for the given `DrmNode`.
     ///
     /// Will return `None` if either:
@@ -1872,7 +2049,8 @@ where
         let dmabuf = get_dmabuf(buffer).expect("import_dma_buffer without checking buffer type?");
         let texture = MultiTexture::from_surface(surface, dmabuf.size(), dmabuf.format());
         let texture_ref = texture.0.clone();
-        let res = self.import_dmabuf_internal(dmabuf, texture, Some(damage));
+        let pinned = self.pins.pinned_node(buffer, surface);
+        let res = self.import_dmabuf_internal(dmabuf, texture, Some(damage), pinned);
         if res.is_ok() {
             if let Some(surface) = surface {
                 surface.data_map.insert_if_missing_threadsafe(|| texture_ref);
@@ -1913,7 +2091,7 @@ where
         damage: Option<&[Rectangle<i32, BufferCoords>]>,
     ) -> Result<<Self as Renderer>::TextureId, <Self as Renderer>::Error> {
         let texture = MultiTexture::new(dmabuf.size(), dmabuf.format());
-        self.import_dmabuf_internal(dmabuf, texture, damage)
+        self.import_dmabuf_internal(dmabuf, texture, damage, None)
     }
 }
 
@@ -1923,7 +2101,8 @@ fn import_on_src_node<'a, R, T>(
     texture: &mut MultiTexture,
     render: &mut R::Device,
     mut target: Option<&mut T::Device>,
-    mut others: impl Iterator<Item = &'a mut R::Device>,
+    others: impl Iterator<Item = &'a mut R::Device>,
+    pinned: Option<DrmNode>,
 ) -> Result<DrmNode, Error<R, T>>
 where
     R: GraphicsApi + 'static,
@@ -1934,7 +2113,42 @@ where
     <<T as GraphicsApi>::Device as ApiDevice>::Renderer: Renderer + ImportDma,
     <<<T as GraphicsApi>::Device as ApiDevice>::Renderer as Renderer>::TextureId: 'static,
 {
-    match dmabuf.node() {
+    let mut others = others.collect::<Vec<_>>();
+
+    if let Some(pinned) = pinned.filter(|pinned| dmabuf.node() != Some(*pinned)) {
+        let imported = if pinned == *render.node() {
+            render
+                .renderer_mut()
+                .import_dmabuf(dmabuf, damage)
+                .map(|imported| texture.insert_texture::<R>(pinned, imported))
+                .map_err(|err| debug!(?err, ?pinned, "Failed to import dmabuf on pinned device"))
+                .is_ok()
+        } else if let Some(target) = target.as_mut().filter(|target| pinned == *target.node()) {
+            target
+                .renderer_mut()
+                .import_dmabuf(dmabuf, damage)
+                .map(|imported| texture.insert_texture::<T>(pinned, imported))
+                .map_err(|err| debug!(?err, ?pinned, "Failed to import dmabuf on pinned device"))
+                .is_ok()
+        } else if let Some(other) = others.iter_mut().find(|other| pinned == *other.node()) {
+            other
+                .renderer_mut()
+                .import_dmabuf(dmabuf, damage)
+                .map(|imported| texture.insert_texture::<R>(pinned, imported))
+                .map_err(|err| debug!(?err, ?pinned, "Failed to import dmabuf on pinned device"))
+                .is_ok()
+        } else {
+            debug!(?pinned, "Pinned device is not available for import");
+            false
+        };
+
+        if imported {
+            texture.0.lock().unwrap().import_node = Some(pinned);
+            return Ok(pinned);
+        }
+    }
+
+    let node = match dmabuf.node() {
         Some(node) => {
             if node == *render.node() {
                 let imported = render
@@ -1949,7 +2163,7 @@ where
                     .import_dmabuf(dmabuf, damage)
                     .map_err(Error::Target)?;
                 texture.insert_texture::<T>(node, imported);
-            } else if let Some(other) = others.find(|other| node == *other.node()) {
+            } else if let Some(other) = others.iter_mut().find(|other| node == *other.node()) {
                 let imported = other
                     .renderer_mut()
                     .import_dmabuf(dmabuf, damage)
@@ -1959,7 +2173,7 @@ where
                 return Err(Error::DeviceMissing);
             };
 
-            Ok(node)
+            node
         }
         None => {
             // try them all
@@ -1974,7 +2188,7 @@ where
                 let node = *target.as_ref().unwrap().node();
                 texture.insert_texture::<T>(node, imported);
                 node
-            } else if let Some((node, imported)) = others.find_map(|other| {
+            } else if let Some((node, imported)) = others.iter_mut().find_map(|other| {
                 other
                     .renderer_mut()
                     .import_dmabuf(dmabuf, damage)
@@ -1988,9 +2202,12 @@ where
             };
             dmabuf.set_node(node);
 
-            Ok(node)
+            node
         }
-    }
+    };
+
+    texture.0.lock().unwrap().import_node = Some(node);
+    Ok(node)
 }
 
 fn dma_shadow_copy<S, T>(
@@ -2417,6 +2634,7 @@ where
         dmabuf: &Dmabuf,
         mut texture: MultiTexture,
         damage: Option<&[Rectangle<i32, BufferCoords>]>,
+        pinned: Option<DrmNode>,
     ) -> Result<<Self as Renderer>::TextureId, <Self as Renderer>::Error> {
         let src_node = import_on_src_node::<R, T>(
             dmabuf,
@@ -2425,7 +2643,9 @@ where
             self.render,
             self.target.as_mut().map(|target| &mut *target.device),
             self.other_renderers.iter_mut().map(|d| &mut **d),
+            pinned,
         )?;
+        let pending_waits = std::mem::take(&mut self.pending_waits);
 
         if src_node == *self.render.node() {
             // when we are on the same node, we are done
@@ -2437,6 +2657,11 @@ where
                 .as_ref()
                 .is_some_and(|target| src_node == *target.device.node())
             {
+                let src = &mut *self.target.as_mut().unwrap().device;
+                for sync in &pending_waits {
+                    src.renderer_mut().wait(sync).map_err(Error::Target)?;
+                }
+
                 let mut texture_internal = texture.0.lock().unwrap();
                 // make sure our target exists
                 texture_internal.textures.entry(TypeId::of::<R>()).or_default();
@@ -2488,6 +2713,10 @@ where
                 .iter_mut()
                 .find(|other| src_node == *other.node())
             {
+                for sync in &pending_waits {
+                    other.renderer_mut().wait(sync).map_err(Error::Render)?;
+                }
+
                 let mut texture_internal = texture.0.lock().unwrap();
                 let api_textures = texture_internal.textures.get_mut(&TypeId::of::<R>()).unwrap();
                 let mut target_texture = api_textures.remove(self.render.node());
@@ -0,0 +1,250 @@
+use std::fmt;
+#[cfg(feature = "image")]
+use std::path::Path;
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            utils::dump::{to_rgba, DumpError},
+            ExportMem, Texture, TextureMapping,
+        },
+    },
+    utils::{Buffer, Point, Rectangle, Size},
+};
+
+/// Tightly packed 8-bit RGBA image used for comparing rendering results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    size: Size<i32, Buffer>,
+    data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Create a new image from tightly packed 8-bit RGBA data
+    ///
+    /// Returns `None` if `data` does not match the given size.
+    pub fn new(size: impl Into<Size<i32, Buffer>>, data: Vec<u8>) -> Option<Self> {
+        let size = size.into();
+        let len = size.w.checked_mul(size.h)?.checked_mul(4)?;
+        (size.w >= 0 && size.h >= 0 && data.len() == len as usize).then_some(RgbaImage { size, data })
+    }
+
+    /// Create a new image from pixel data of a given format
+    ///
+    /// Returns `None` if the format is not supported or `data` does not match the given size and stride.
+    pub fn from_memory(
+        data: &[u8],
+        format: Fourcc,
+        size: impl Into<Size<i32, Buffer>>,
+        stride: i32,
+    ) -> Option<Self> {
+        let size = size.into();
+        to_rgba(data, format, size, stride.max(0) as usize).and_then(|data| RgbaImage::new(size, data))
+    }
+
+    /// Read a region of the currently bound framebuffer of a renderer
+    pub fn from_framebuffer<R: ExportMem>(
+        renderer: &mut R,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<Self, DumpError<R::Error>> {
+        let mapping = renderer
+            .copy_framebuffer(region, Fourcc::Abgr8888)
+            .map_err(DumpError::Renderer)?;
+        let size = mapping.size();
+        let format = TextureMapping::format(&mapping);
+        let flipped = mapping.flipped();
+        let data = renderer.map_texture(&mapping).map_err(DumpError::Renderer)?;
+        let stride = data.len() / size.h.max(1) as usize;
+
+        let mut image = to_rgba(data, format, size, stride)
+            .and_then(|data| RgbaImage::new(size, data))
+            .ok_or(DumpError::UnsupportedFormat(format))?;
+        if flipped {
+            image.flip();
+        }
+        Ok(image)
+    }
+
+    /// Size of the image
+    pub fn size(&self) -> Size<i32, Buffer> {
+        self.size
+    }
+
+    /// Raw RGBA data of the image
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// RGBA value of the pixel at the given position
+    pub fn pixel(&self, x: i32, y: i32) -> [u8; 4] {
+        assert!(x >= 0 && y >= 0 && x < self.size.w && y < self.size.h);
+        let offset = ((y * self.size.w + x) * 4) as usize;
+        self.data[offset..offset + 4].try_into().unwrap()
+    }
+
+    /// Load an image from a PNG file, e.g. a golden image
+    #[cfg(feature = "image")]
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_rgba8();
+        let (w, h) = image.dimensions();
+        i32::try_from(w)
+            .ok()
+            .zip(i32::try_from(h).ok())
+            .and_then(|size| RgbaImage::new(size, image.into_raw()))
+            .ok_or_else(|| {
+                image::ImageError::Limits(image::error::LimitError::from_kind(
+                    image::error::LimitErrorKind::DimensionError,
+                ))
+            })
+    }
+
+    /// Save the image as a PNG file, e.g. to update a golden image
+    #[cfg(feature = "image")]
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), image::ImageError> {
+        image::save_buffer_with_format(
+            path,
+            &self.data,
+            self.size.w as u32,
+            self.size.h as u32,
+            image::ColorType::Rgba8,
+            image::ImageFormat::Png,
+        )
+    }
+
+    /// Compare this image to another one
+    ///
+    /// Pixels are considered equal, if none of their channels differ by more than `tolerance`.
+    pub fn compare(&self, other: &RgbaImage, tolerance: u8) -> ImageComparison {
+        if self.size != other.size {
+            return ImageComparison {
+                size_mismatch: Some((self.size, other.size)),
+                mismatched_pixels: 0,
+                max_difference: 0,
+                first_mismatch: None,
+            };
+        }
+
+        let mut comparison = ImageComparison {
+            size_mismatch: None,
+            mismatched_pixels: 0,
+            max_difference: 0,
+            first_mismatch: None,
+        };
+        for (idx, (a, b)) in self
+            .data
+            .chunks_exact(4)
+            .zip(other.data.chunks_exact(4))
+            .enumerate()
+        {
+            let difference = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0);
+            comparison.max_difference = comparison.max_difference.max(difference);
+            if difference > tolerance {
+                comparison.mismatched_pixels += 1;
+                comparison.first_mismatch.get_or_insert_with(|| {
+                    let idx = idx as i32;
+                    Point::from((idx % self.size.w, idx / self.size.w))
+                });
+            }
+        }
+        comparison
+    }
+
+    fn flip(&mut self) {
+        let stride = (self.size.w * 4) as usize;
+        let rows = self.data.chunks_exact(stride).rev().flatten().copied().collect();
+        self.data = rows;
+    }
+}
+
+/// Result of comparing two [`RgbaImage`]s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageComparison {
+    /// Sizes of both images, if they differ
+    pub size_mismatch: Option<(Size<i32, Buffer>, Size<i32, Buffer>)>,
+    /// Number of pixels exceeding the tolerance
+    pub mismatched_pixels: usize,
+    /// Largest difference of any channel of any pixel
+    pub max_difference: u8,
+    /// Position of the first pixel exceeding the tolerance
+    pub first_mismatch: Option<Point<i32, Buffer>>,
+}
+
+impl ImageComparison {
+    /// Returns `true` if both images were found to be equal within the tolerance
+    pub fn matches(&self) -> bool {
+        self.size_mismatch.is_none() && self.mismatched_pixels == 0
+    }
+}
+
+impl fmt::Display for ImageComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((expected, actual)) = self.size_mismatch {
+            write!(f, "image size {:?} does not match {:?}", actual, expected)
+        } else if let Some(first) = self.first_mismatch {
+            write!(
+                f,
+                "{} pixels differ (max difference {}), first at {:?}",
+                self.mismatched_pixels, self.max_difference, first
+            )
+        } else {
+            write!(f, "images match (max difference {})", self.max_difference)
+        }
+    }
+}
+
+/// Assert that a rendered image matches a golden image within a tolerance
+///
+/// If the images differ and the `SMITHAY_TEST_OUTPUT_DIR` environment variable is set,
+/// the actual image is written to `<SMITHAY_TEST_OUTPUT_DIR>/<name>.png` for inspection,
+/// when the `image` feature is enabled.
+#[track_caller]
+pub fn assert_image_matches(name: &str, expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) {
+    let comparison = expected.compare(actual, tolerance);
+    if comparison.matches() {
+        return;
+    }
+
+    #[cfg(feature = "image")]
+    if let Some(dir) = std::env::var_os("SMITHAY_TEST_OUTPUT_DIR") {
+        let path = Path::new(&dir).join(format!("{}.png", name));
+        if let Err(err) = actual.save_png(&path) {
+            panic!("{}: {} (failed to save actual image: {})", name, comparison, err);
+        }
+        panic!(
+            "{}: {} (actual image saved to {})",
+            name,
+            comparison,
+            path.display()
+        );
+    }
+    panic!("{}: {}", name, comparison);
+}
+
+/// Assert that a rendered image matches the golden image stored at `path` within a tolerance
+///
+/// If the `SMITHAY_UPDATE_GOLDEN` environment variable is set, `actual` is written to `path`
+/// instead, which can be used to create or update golden images.
+/// Mismatches are reported like [`assert_image_matches`] does, using the file stem as name.
+#[cfg(feature = "image")]
+#[track_caller]
+pub fn assert_golden_image(path: impl AsRef<Path>, actual: &RgbaImage, tolerance: u8) {
+    let path = path.as_ref();
+    if std::env::var_os("SMITHAY_UPDATE_GOLDEN").is_some() {
+        if let Err(err) = actual.save_png(path) {
+            panic!("failed to update golden image {}: {}", path.display(), err);
+        }
+        return;
+    }
+
+    let expected = match RgbaImage::load_png(path) {
+        Ok(expected) => expected,
+        Err(err) => panic!(
+            "failed to load golden image {} (set SMITHAY_UPDATE_GOLDEN to create it): {}",
+            path.display(),
+            err
+        ),
+    };
+    let name = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy();
+    assert_image_matches(&name, &expected, actual, tolerance);
+}
@@ -18,7 +18,8 @@ use crate::{
     backend::{
         allocator::{dmabuf::Dmabuf, Fourcc},
         renderer::{
-            sync::SyncPoint, DebugFlags, Frame, ImportDma, ImportMem, Renderer, Texture, TextureFilter,
+            sync::SyncPoint, Bind, DebugFlags, Frame, ImportDma, ImportMem, Offscreen, Renderer, Texture,
+            TextureFilter, Unbind,
         },
         SwapBuffersError,
     },
@@ -27,6 +28,14 @@ use crate::{
 
 use super::Color32F;
 
+mod compare;
+mod pattern;
+
+#[cfg(feature = "image")]
+pub use self::compare::assert_golden_image;
+pub use self::compare::{assert_image_matches, ImageComparison, RgbaImage};
+pub use self::pattern::Pattern;
+
 #[derive(Debug)]
 pub struct DummyRenderer {}
 
@@ -122,6 +131,31 @@ impl ImportMem for DummyRenderer {
     }
 }
 
+impl Bind<DummyTexture> for DummyRenderer {
+    fn bind(&mut self, _target: DummyTexture) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl Unbind for DummyRenderer {
+    fn unbind(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl Offscreen<DummyTexture> for DummyRenderer {
+    fn create_buffer(
+        &mut self,
+        _format: Fourcc,
+        size: Size<i32, Buffer>,
+    ) -> Result<DummyTexture, Self::Error> {
+        Ok(DummyTexture {
+            width: size.w as u32,
+            height: size.h as u32,
+        })
+    }
+}
+
 #[cfg(feature = "wayland_frontend")]
 impl ImportMemWl for DummyRenderer {
     fn import_shm_buffer(
@@ -275,3 +309,138 @@ impl Texture for DummyTexture {
         None
     }
 }
+
+#[cfg(all(test, feature = "renderer_pixman"))]
+mod tests {
+    use super::*;
+    use crate::backend::renderer::{
+        damage::OutputDamageTracker,
+        element::{
+            memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+            Kind,
+        },
+        pixman::{PixmanRenderBuffer, PixmanRenderer},
+        Bind, Offscreen,
+    };
+
+    fn render_pattern(buffer: &MemoryRenderBuffer, size: Size<i32, Buffer>) -> RgbaImage {
+        let mut renderer = PixmanRenderer::new().unwrap();
+        let target: PixmanRenderBuffer = renderer.create_buffer(Fourcc::Abgr8888, size).unwrap();
+        renderer.bind(target).unwrap();
+
+        let element = MemoryRenderBufferRenderElement::from_buffer(
+            &mut renderer,
+            (0.0, 0.0),
+            buffer,
+            None,
+            None,
+            None,
+            Kind::Unspecified,
+        )
+        .unwrap();
+        let mut damage_tracker = OutputDamageTracker::new((size.w, size.h), 1.0, Transform::Normal);
+        damage_tracker
+            .render_output(&mut renderer, 0, &[element], Color32F::BLACK)
+            .unwrap();
+
+        RgbaImage::from_framebuffer(&mut renderer, Rectangle::from_size(size)).unwrap()
+    }
+
+    #[cfg(feature = "image")]
+    fn golden_path(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/backend/renderer/test/golden")
+            .join(format!("{}.png", name))
+    }
+
+    #[test]
+    fn checkerboard_golden() {
+        let pattern = Pattern::Checkerboard {
+            cell_size: 4,
+            colors: [
+                Color32F::new(1.0, 0.0, 0.0, 1.0),
+                Color32F::new(0.0, 0.0, 1.0, 1.0),
+            ],
+        };
+        let size = Size::from((16, 8));
+        let memory = pattern.to_memory(size);
+        let expected = RgbaImage::from_memory(&memory, memory.format(), size, memory.stride()).unwrap();
+        assert_eq!(expected.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(expected.pixel(4, 0), [0, 0, 255, 255]);
+        assert_eq!(expected.pixel(4, 4), [255, 0, 0, 255]);
+
+        let actual = render_pattern(&pattern.to_render_buffer(size), size);
+        assert_image_matches("checkerboard_golden", &expected, &actual, 0);
+        #[cfg(feature = "image")]
+        assert_golden_image(golden_path("checkerboard"), &actual, 0);
+    }
+
+    #[test]
+    fn gradient_and_label_golden() {
+        let gradient = Pattern::HorizontalGradient {
+            start: Color32F::BLACK,
+            end: Color32F::new(1.0, 1.0, 1.0, 1.0),
+        };
+        let size = Size::from((32, 4));
+        let memory = gradient.to_memory(size);
+        let expected = RgbaImage::from_memory(&memory, memory.format(), size, memory.stride()).unwrap();
+        assert_eq!(expected.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(expected.pixel(31, 3), [255, 255, 255, 255]);
+        let actual = render_pattern(&gradient.to_render_buffer(size), size);
+        assert_image_matches("gradient_golden", &expected, &actual, 1);
+        #[cfg(feature = "image")]
+        assert_golden_image(golden_path("gradient"), &actual, 1);
+
+        let label = Pattern::Label {
+            text: "T1".into(),
+            scale: 2,
+            foreground: Color32F::new(1.0, 1.0, 1.0, 1.0),
+            background: Color32F::BLACK,
+        };
+        let size = Pattern::label_size("T1", 2);
+        assert_eq!(size, Size::from((24, 16)));
+        let memory = label.to_memory(size);
+        let expected = RgbaImage::from_memory(&memory, memory.format(), size, memory.stride()).unwrap();
+        // top bar of the "T"
+        assert_eq!(expected.pixel(0, 0), [255, 255, 255, 255]);
+        assert_eq!(expected.pixel(9, 1), [255, 255, 255, 255]);
+        // spacing between glyphs
+        assert_eq!(expected.pixel(10, 0), [0, 0, 0, 255]);
+        let actual = render_pattern(&label.to_render_buffer(size), size);
+        assert_image_matches("label_golden", &expected, &actual, 0);
+        #[cfg(feature = "image")]
+        assert_golden_image(golden_path("label"), &actual, 0);
+    }
+
+    #[test]
+    fn compare_tolerance() {
+        let a = RgbaImage::new((2, 1), vec![10, 10, 10, 255, 20, 20, 20, 255]).unwrap();
+        let b = RgbaImage::new((2, 1), vec![12, 10, 10, 255, 20, 20, 30, 255]).unwrap();
+
+        let comparison = a.compare(&b, 2);
+        assert!(!comparison.matches());
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert_eq!(comparison.max_difference, 10);
+        assert_eq!(comparison.first_mismatch, Some((1, 0).into()));
+        assert!(a.compare(&b, 10).matches());
+
+        let c = RgbaImage::new((1, 2), vec![0; 8]).unwrap();
+        assert!(!a.compare(&c, 255).matches());
+    }
+
+    #[test]
+    fn oversized_image() {
+        assert!(RgbaImage::new((i32::MAX, 2), Vec::new()).is_none());
+        assert!(RgbaImage::new((65536, 65536), Vec::new()).is_none());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn golden_images_load() {
+        let checkerboard = RgbaImage::load_png(golden_path("checkerboard")).unwrap();
+        assert_eq!(checkerboard.size(), Size::from((16, 8)));
+        assert_eq!(checkerboard.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(checkerboard.pixel(4, 0), [0, 0, 255, 255]);
+        assert!(RgbaImage::load_png(golden_path("missing")).is_err());
+    }
+}
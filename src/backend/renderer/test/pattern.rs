@@ -0,0 +1,211 @@
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::memory::{MemoryBuffer, MemoryRenderBuffer},
+            Color32F,
+        },
+    },
+    utils::{Buffer, Size, Transform},
+};
+
+/// Width of a single glyph of the built-in font including spacing
+const GLYPH_ADVANCE: i32 = 6;
+/// Height of a single line of the built-in font including spacing
+const LINE_HEIGHT: i32 = 8;
+
+/// Deterministic patterns to be used as test content
+///
+/// All patterns are generated on the cpu in [`Fourcc::Abgr8888`],
+/// so they produce the same pixels regardless of the renderer used.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// A checkerboard alternating between two colors
+    Checkerboard {
+        /// Size of a single cell in pixels
+        cell_size: i32,
+        /// Color of the top-left cell and the other cells
+        colors: [Color32F; 2],
+    },
+    /// A gradient from the left to the right edge
+    HorizontalGradient {
+        /// Color at the left edge
+        start: Color32F,
+        /// Color at the right edge
+        end: Color32F,
+    },
+    /// A gradient from the top to the bottom edge
+    VerticalGradient {
+        /// Color at the top edge
+        start: Color32F,
+        /// Color at the bottom edge
+        end: Color32F,
+    },
+    /// Text rendered with a built-in 5x7 pixel font
+    ///
+    /// Only ascii digits, letters (rendered in upper case) and some punctuation
+    /// are supported, other characters are rendered as filled boxes.
+    /// Lines can be separated by `\n`.
+    Label {
+        /// The text to render
+        text: String,
+        /// Integer scale applied to the font
+        scale: i32,
+        /// Color of the text
+        foreground: Color32F,
+        /// Color of the remaining pixels
+        background: Color32F,
+    },
+}
+
+impl Pattern {
+    /// Size necessary to fit the text of a [`Pattern::Label`]
+    pub fn label_size(text: &str, scale: i32) -> Size<i32, Buffer> {
+        let lines = text.lines().count().max(1) as i32;
+        let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as i32;
+        Size::from((columns * GLYPH_ADVANCE * scale, lines * LINE_HEIGHT * scale))
+    }
+
+    /// Color of the pixel at the given position of a pattern with the given size
+    pub fn pixel(&self, x: i32, y: i32, size: Size<i32, Buffer>) -> Color32F {
+        match self {
+            Pattern::Checkerboard { cell_size, colors } => {
+                let cell_size = (*cell_size).max(1);
+                colors[((x / cell_size + y / cell_size) % 2) as usize]
+            }
+            Pattern::HorizontalGradient { start, end } => lerp(*start, *end, x, size.w),
+            Pattern::VerticalGradient { start, end } => lerp(*start, *end, y, size.h),
+            Pattern::Label {
+                text,
+                scale,
+                foreground,
+                background,
+            } => {
+                let scale = (*scale).max(1);
+                let (x, y) = (x / scale, y / scale);
+                let set = text
+                    .lines()
+                    .nth((y / LINE_HEIGHT) as usize)
+                    .and_then(|line| line.chars().nth((x / GLYPH_ADVANCE) as usize))
+                    .map(|c| {
+                        let (column, row) = (x % GLYPH_ADVANCE, y % LINE_HEIGHT);
+                        column < 5 && row < 7 && glyph(c)[row as usize] & (0x10 >> column) != 0
+                    })
+                    .unwrap_or(false);
+                if set {
+                    *foreground
+                } else {
+                    *background
+                }
+            }
+        }
+    }
+
+    /// Render this pattern into a new [`MemoryBuffer`] of the given size
+    pub fn to_memory(&self, size: impl Into<Size<i32, Buffer>>) -> MemoryBuffer {
+        let size = size.into();
+        let mut buffer = MemoryBuffer::new(Fourcc::Abgr8888, size);
+        let stride = buffer.stride() as usize;
+        for y in 0..size.h {
+            for x in 0..size.w {
+                let offset = y as usize * stride + x as usize * 4;
+                buffer[offset..offset + 4].copy_from_slice(&to_bytes(self.pixel(x, y, size)));
+            }
+        }
+        buffer
+    }
+
+    /// Render this pattern into a new [`MemoryRenderBuffer`] of the given size
+    ///
+    /// The resulting buffer can be rendered using a
+    /// [`MemoryRenderBufferRenderElement`](crate::backend::renderer::element::memory::MemoryRenderBufferRenderElement).
+    pub fn to_render_buffer(&self, size: impl Into<Size<i32, Buffer>>) -> MemoryRenderBuffer {
+        let opaque = match self {
+            Pattern::Checkerboard { colors, .. } => colors.iter().all(Color32F::is_opaque),
+            Pattern::HorizontalGradient { start, end } | Pattern::VerticalGradient { start, end } => {
+                start.is_opaque() && end.is_opaque()
+            }
+            Pattern::Label {
+                foreground,
+                background,
+                ..
+            } => foreground.is_opaque() && background.is_opaque(),
+        };
+        let size = size.into();
+        let opaque_regions = opaque.then(|| vec![crate::utils::Rectangle::from_size(size)]);
+        MemoryRenderBuffer::from_memory(self.to_memory(size), 1, Transform::Normal, opaque_regions)
+    }
+}
+
+/// Convert a color to the bytes of a [`Fourcc::Abgr8888`] pixel
+pub(super) fn to_bytes(color: Color32F) -> [u8; 4] {
+    color
+        .components()
+        .map(|component| (component.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+fn lerp(start: Color32F, end: Color32F, position: i32, length: i32) -> Color32F {
+    let t = if length > 1 {
+        position as f32 / (length - 1) as f32
+    } else {
+        0.0
+    };
+    let (start, end) = (start.components(), end.components());
+    Color32F::from(std::array::from_fn(|i| start[i] + (end[i] - start[i]) * t))
+}
+
+/// Rows of a 5x7 glyph, the most significant of the lower 5 bits being the leftmost pixel
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ' ' => [0x00; 7],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x1F; 7],
+    }
+}
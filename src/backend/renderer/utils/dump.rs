@@ -0,0 +1,271 @@
+//! Helpers to dump the contents of buffers and textures to PNG files for debugging.
+//!
+//! Each function downloads the contents of its source into memory, converts them
+//! to 8-bit RGBA and writes a PNG to the given path. Writing files requires the `image` feature.
+//! This is slow and meant to be triggered manually, e.g. from a keybinding,
+//! to capture the state of the different stages of the rendering pipeline.
+//!
+//! - Textures (e.g. [`GlesTexture`](crate::backend::renderer::gles::GlesTexture)s) are downloaded via
+//!   [`ExportMem::copy_texture`](crate::backend::renderer::ExportMem::copy_texture)
+//! - The currently bound framebuffer is downloaded via
+//!   [`ExportMem::copy_framebuffer`](crate::backend::renderer::ExportMem::copy_framebuffer)
+//! - [`Dmabuf`](crate::backend::allocator::dmabuf::Dmabuf)s are imported into the renderer first, requiring
+//!   [`ImportDma`](crate::backend::renderer::ImportDma)
+//! - [`MemoryRenderBuffer`](crate::backend::renderer::element::memory::MemoryRenderBuffer)s and shm buffers
+//!   are read directly from memory
+
+use std::io;
+#[cfg(feature = "image")]
+use std::{convert::Infallible, path::Path};
+
+use crate::{
+    backend::allocator::Fourcc,
+    utils::{Buffer as BufferCoord, Size},
+};
+#[cfg(feature = "image")]
+use crate::{
+    backend::{
+        allocator::dmabuf::Dmabuf,
+        renderer::{element::memory::MemoryRenderBuffer, ExportMem, ImportDma, Texture, TextureMapping},
+    },
+    utils::Rectangle,
+};
+
+/// Errors returned by the dump functions
+#[derive(Debug, thiserror::Error)]
+pub enum DumpError<E: std::error::Error> {
+    /// The renderer failed to download the contents
+    #[error("The renderer failed to download the contents: {0}")]
+    Renderer(#[source] E),
+    /// The contents have a format, that cannot be converted to RGBA
+    #[error("Unsupported format for dumping: {0:?}")]
+    UnsupportedFormat(Fourcc),
+    /// The shm buffer has a format, that cannot be converted to RGBA
+    #[cfg(feature = "wayland_frontend")]
+    #[error("Unsupported shm format for dumping: {0:?}")]
+    UnsupportedShmFormat(wayland_server::protocol::wl_shm::Format),
+    /// The shm buffer could not be accessed
+    #[cfg(feature = "wayland_frontend")]
+    #[error("Failed to access the shm buffer: {0}")]
+    BufferAccess(#[from] crate::wayland::shm::BufferAccessError),
+    /// Writing the file failed
+    #[error("Failed to write the file: {0}")]
+    Io(#[from] io::Error),
+    /// Encoding the image failed
+    #[cfg(feature = "image")]
+    #[error("Failed to encode the image: {0}")]
+    Encode(#[from] image::ImageError),
+}
+
+/// Dump the contents of a texture to a PNG file.
+#[cfg(feature = "image")]
+pub fn dump_texture<R: ExportMem>(
+    renderer: &mut R,
+    texture: &R::TextureId,
+    path: impl AsRef<Path>,
+) -> Result<(), DumpError<R::Error>> {
+    let region = Rectangle::from_size(texture.size());
+    let mapping = renderer
+        .copy_texture(texture, region, Fourcc::Abgr8888)
+        .map_err(DumpError::Renderer)?;
+    dump_mapping(renderer, &mapping, path)
+}
+
+/// Dump a region of the currently bound framebuffer to a PNG file.
+#[cfg(feature = "image")]
+pub fn dump_framebuffer<R: ExportMem>(
+    renderer: &mut R,
+    region: Rectangle<i32, BufferCoord>,
+    path: impl AsRef<Path>,
+) -> Result<(), DumpError<R::Error>> {
+    let mapping = renderer
+        .copy_framebuffer(region, Fourcc::Abgr8888)
+        .map_err(DumpError::Renderer)?;
+    dump_mapping(renderer, &mapping, path)
+}
+
+/// Dump the contents of a [`Dmabuf`] to a PNG file.
+///
+/// The dmabuf is imported into the renderer for downloading its contents.
+#[cfg(feature = "image")]
+pub fn dump_dmabuf<R: ImportDma + ExportMem>(
+    renderer: &mut R,
+    dmabuf: &Dmabuf,
+    path: impl AsRef<Path>,
+) -> Result<(), DumpError<R::Error>> {
+    let texture = renderer
+        .import_dmabuf(dmabuf, None)
+        .map_err(DumpError::Renderer)?;
+    dump_texture(renderer, &texture, path)
+}
+
+/// Dump the contents of a [`MemoryRenderBuffer`] to a PNG file.
+#[cfg(feature = "image")]
+pub fn dump_memory_buffer(
+    buffer: &MemoryRenderBuffer,
+    path: impl AsRef<Path>,
+) -> Result<(), DumpError<Infallible>> {
+    let mem = buffer.memory();
+    dump_memory(&mem, mem.format(), mem.size(), mem.stride(), path)
+}
+
+/// Dump the contents of a shm [`WlBuffer`](wayland_server::protocol::wl_buffer::WlBuffer) to a PNG file.
+#[cfg(all(feature = "image", feature = "wayland_frontend"))]
+pub fn dump_shm_buffer(
+    buffer: &wayland_server::protocol::wl_buffer::WlBuffer,
+    path: impl AsRef<Path>,
+) -> Result<(), DumpError<Infallible>> {
+    use crate::wayland::shm::{shm_format_to_fourcc, with_buffer_contents};
+
+    with_buffer_contents(buffer, |ptr, len, data| {
+        let contents = shm_format_to_fourcc(data.format)
+            .ok_or(DumpError::UnsupportedShmFormat(data.format))
+            .and_then(|format| {
+                let offset = data.offset as usize;
+                let end = offset + (data.stride * data.height) as usize;
+                if end > len {
+                    return Err(DumpError::Io(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "shm buffer exceeds its pool",
+                    )));
+                }
+                Ok((format, offset, end))
+            });
+        contents.and_then(|(format, offset, end)| {
+            // SAFETY: The pool is valid for `len` bytes and we checked the buffer is contained in it.
+            let slice = unsafe { std::slice::from_raw_parts(ptr.add(offset), end - offset) };
+            dump_memory(slice, format, (data.width, data.height).into(), data.stride, path)
+        })
+    })?
+}
+
+/// Dump raw pixel data of a given format to a PNG file.
+#[cfg(feature = "image")]
+pub fn dump_memory<E: std::error::Error>(
+    data: &[u8],
+    format: Fourcc,
+    size: Size<i32, BufferCoord>,
+    stride: i32,
+    path: impl AsRef<Path>,
+) -> Result<(), DumpError<E>> {
+    let rgba = to_rgba(data, format, size, stride as usize).ok_or(DumpError::UnsupportedFormat(format))?;
+    write_png(path, &rgba, size, false)?;
+    Ok(())
+}
+
+#[cfg(feature = "image")]
+fn dump_mapping<R: ExportMem>(
+    renderer: &mut R,
+    mapping: &R::TextureMapping,
+    path: impl AsRef<Path>,
+) -> Result<(), DumpError<R::Error>> {
+    let size = mapping.size();
+    let format = TextureMapping::format(mapping);
+    let data = renderer.map_texture(mapping).map_err(DumpError::Renderer)?;
+    let stride = data.len() / size.h.max(1) as usize;
+    let rgba = to_rgba(data, format, size, stride).ok_or(DumpError::UnsupportedFormat(format))?;
+    write_png(path, &rgba, size, mapping.flipped())?;
+    Ok(())
+}
+
+/// Converts pixel data of a given format to tightly packed 8-bit RGBA.
+#[cfg_attr(not(any(feature = "image", feature = "renderer_test")), allow(dead_code))]
+pub(crate) fn to_rgba(
+    data: &[u8],
+    format: Fourcc,
+    size: Size<i32, BufferCoord>,
+    stride: usize,
+) -> Option<Vec<u8>> {
+    // byte indices of red, green, blue and alpha in a little-endian pixel
+    let (bpp, r, g, b, a) = match format {
+        Fourcc::Abgr8888 => (4, 0, 1, 2, Some(3)),
+        Fourcc::Xbgr8888 => (4, 0, 1, 2, None),
+        Fourcc::Argb8888 => (4, 2, 1, 0, Some(3)),
+        Fourcc::Xrgb8888 => (4, 2, 1, 0, None),
+        Fourcc::Rgba8888 => (4, 3, 2, 1, Some(0)),
+        Fourcc::Rgbx8888 => (4, 3, 2, 1, None),
+        Fourcc::Bgra8888 => (4, 1, 2, 3, Some(0)),
+        Fourcc::Bgrx8888 => (4, 1, 2, 3, None),
+        Fourcc::Rgb888 => (3, 2, 1, 0, None),
+        Fourcc::Bgr888 => (3, 0, 1, 2, None),
+        _ => return None,
+    };
+
+    let (width, height) = (size.w.max(0) as usize, size.h.max(0) as usize);
+    if width == 0 || height == 0 {
+        return Some(Vec::new());
+    }
+    if stride < width * bpp || data.len() < stride * height.saturating_sub(1) + width * bpp {
+        return None;
+    }
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in data.chunks(stride).take(height) {
+        for pixel in row[..width * bpp].chunks_exact(bpp) {
+            rgba.extend_from_slice(&[
+                pixel[r],
+                pixel[g],
+                pixel[b],
+                a.map(|a| pixel[a]).unwrap_or(u8::MAX),
+            ]);
+        }
+    }
+    Some(rgba)
+}
+
+/// Write tightly packed 8-bit RGBA data to a PNG file.
+///
+/// If `flipped` is set the rows are written in reverse order.
+#[cfg(feature = "image")]
+pub fn write_png(
+    path: impl AsRef<Path>,
+    rgba: &[u8],
+    size: Size<i32, BufferCoord>,
+    flipped: bool,
+) -> Result<(), image::ImageError> {
+    let (width, height) = (size.w.max(0) as u32, size.h.max(0) as u32);
+    let row_len = width as usize * 4;
+    let rows = rgba.get(..row_len * height as usize).unwrap_or(rgba);
+    let mut image = image::RgbaImage::from_raw(width, height, rows.to_vec()).ok_or_else(|| {
+        image::ImageError::Parameter(image::error::ParameterError::from_kind(
+            image::error::ParameterErrorKind::DimensionMismatch,
+        ))
+    })?;
+    if flipped {
+        image::imageops::flip_vertical_in_place(&mut image);
+    }
+    image.save_with_format(path, image::ImageFormat::Png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn png_flipped() {
+        let path = std::env::temp_dir().join(format!("smithay-dump-{}.png", std::process::id()));
+        write_png(&path, &[1, 2, 3, 4, 5, 6, 7, 8], Size::from((1, 2)), true).unwrap();
+
+        let decoded = image::open(&path).unwrap().into_rgba8();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(decoded.dimensions(), (1, 2));
+        assert_eq!(decoded.into_raw(), [5, 6, 7, 8, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn convert_argb() {
+        let size = Size::from((2, 1));
+        // stride includes padding
+        let data = [10, 20, 30, 40, 50, 60, 70, 80, 0, 0];
+        assert_eq!(
+            to_rgba(&data, Fourcc::Argb8888, size, 10).unwrap(),
+            [30, 20, 10, 40, 70, 60, 50, 80]
+        );
+        assert_eq!(
+            to_rgba(&data, Fourcc::Xrgb8888, size, 10).unwrap(),
+            [30, 20, 10, 255, 70, 60, 50, 255]
+        );
+        assert!(to_rgba(&data, Fourcc::Nv12, size, 10).is_none());
+    }
+}
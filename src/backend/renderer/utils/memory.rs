@@ -0,0 +1,32 @@
+use crate::{
+    backend::{
+        allocator::{format::get_bpp, Buffer, Fourcc},
+        renderer::Texture,
+    },
+    utils::{Buffer as BufferCoord, Size},
+};
+
+/// Returns the approximate memory in bytes used by an image of the given size and format
+///
+/// This does not account for padding, tiling or compression of the image and returns `None`
+/// for formats without a fixed number of bits per pixel, like most yuv formats.
+pub fn estimated_memory_usage(size: Size<i32, BufferCoord>, format: Fourcc) -> Option<usize> {
+    let bpp = get_bpp(format)?;
+    let pixels = size.w.max(0) as usize * size.h.max(0) as usize;
+    Some(pixels * bpp / 8)
+}
+
+/// Returns the approximate memory in bytes used by a texture
+///
+/// See [`estimated_memory_usage`] for details.
+/// Returns `None` as well, if the format of the texture is not known.
+pub fn texture_memory_usage<T: Texture + ?Sized>(texture: &T) -> Option<usize> {
+    estimated_memory_usage(texture.size(), texture.format()?)
+}
+
+/// Returns the approximate memory in bytes used by an allocated buffer
+///
+/// See [`estimated_memory_usage`] for details.
+pub fn buffer_memory_usage<B: Buffer + ?Sized>(buffer: &B) -> Option<usize> {
+    estimated_memory_usage(buffer.size(), buffer.format().code)
+}
@@ -2,26 +2,53 @@
 //! and [`RenderElement`](super::element::RenderElement)s with [`Renderer`](super::Renderer)s.
 
 use crate::utils::{Buffer as BufferCoord, Coordinate, Logical, Physical, Point, Rectangle, Size};
-use std::{collections::VecDeque, fmt, sync::Arc};
-
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+pub mod dump;
+mod memory;
 #[cfg(feature = "wayland_frontend")]
 mod wayland;
+pub use self::memory::*;
 #[cfg(feature = "wayland_frontend")]
 pub use self::wayland::*;
 
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(1);
+
 /// A simple wrapper for counting commits
 ///
 /// The purpose of the counter is to keep track
 /// on the number of times something has changed.
 /// It provides an easy way to obtain the distance
 /// between two instances of a [`CommitCounter`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
-pub struct CommitCounter(usize);
+///
+/// Every counter belongs to a generation. Counters created by a [`DamageBag`]
+/// get a unique generation, which changes whenever the bag is reset,
+/// so counters of unrelated or reset damage histories are never compared.
+/// Counters created with [`Default`] or [`From<usize>`] share the generation `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CommitCounter {
+    generation: usize,
+    counter: usize,
+}
 
 impl CommitCounter {
+    fn with_new_generation(counter: usize) -> Self {
+        CommitCounter {
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+            counter,
+        }
+    }
+
     /// Increment the commit counter
     pub fn increment(&mut self) {
-        self.0 = self.0.wrapping_add(1)
+        self.counter = self.counter.wrapping_add(1)
     }
 
     /// Get the distance between two [`CommitCounter`]s
@@ -30,24 +57,47 @@ impl CommitCounter {
     /// damage this returns the count of damage that happened
     /// between the [`CommitCounter`]s
     ///
-    /// Returns `None` in case the distance could not be calculated.
-    /// If uses as part of damage tracking the tracked element
+    /// Returns `None` in case the distance could not be calculated,
+    /// because the counters belong to different generations or the previous
+    /// commit is ahead of this one.
+    /// If used as part of damage tracking the tracked element
     /// should be considered as fully damaged.
+    ///
+    /// The distance is calculated with wrapping arithmetic, so counters
+    /// stay comparable after the counter overflows.
     pub fn distance(&self, previous_commit: Option<CommitCounter>) -> Option<usize> {
-        // if commit > commit_count we have overflown, in that case the following map might result
-        // in a false-positive, if commit is still very large. So we force false in those cases.
-        // That will result in a potentially sub-optimal full damage every usize::MAX frames,
-        // which is acceptable.
         previous_commit
-            .filter(|commit| commit <= self)
-            .map(|commit| self.0.wrapping_sub(commit.0))
+            .filter(|commit| commit.generation == self.generation)
+            .map(|commit| self.counter.wrapping_sub(commit.counter))
+            // a distance of more than half the counter range can only be reached
+            // if the previous commit is actually newer than this one
+            .filter(|distance| *distance <= usize::MAX / 2)
+    }
+}
+
+impl PartialOrd for CommitCounter {
+    /// Counters of different generations are unordered,
+    /// otherwise they are compared like in [`CommitCounter::distance`].
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        if self.generation != other.generation {
+            return None;
+        }
+        match self.counter.wrapping_sub(other.counter) {
+            0 => Some(std::cmp::Ordering::Equal),
+            distance if distance <= usize::MAX / 2 => Some(std::cmp::Ordering::Greater),
+            _ => Some(std::cmp::Ordering::Less),
+        }
     }
 }
 
 impl From<usize> for CommitCounter {
     #[inline]
     fn from(counter: usize) -> Self {
-        CommitCounter(counter)
+        CommitCounter {
+            generation: 0,
+            counter,
+        }
     }
 }
 
@@ -57,6 +107,10 @@ impl From<usize> for CommitCounter {
 /// and automatically caps the damage
 /// with the specified limit.
 ///
+/// Damage that exceeds the limit is folded into a bounding
+/// rectangle, so querying the damage for an old commit
+/// still results in partial damage instead of a full damage.
+///
 /// See [`DamageSnapshot`] for more
 /// information.
 pub struct DamageBag<N, Kind> {
@@ -73,7 +127,33 @@ pub struct DamageBag<N, Kind> {
 pub struct DamageSnapshot<N, Kind> {
     limit: usize,
     commit_counter: CommitCounter,
-    damage: Arc<VecDeque<smallvec::SmallVec<[Rectangle<N, Kind>; MAX_DAMAGE_RECTS]>>>,
+    damage: Arc<DamageHistory<N, Kind>>,
+}
+
+struct DamageHistory<N, Kind> {
+    entries: VecDeque<smallvec::SmallVec<[Rectangle<N, Kind>; MAX_DAMAGE_RECTS]>>,
+    /// Bounding box of all damage dropped from `entries` in the current generation
+    overflow: Option<Rectangle<N, Kind>>,
+}
+
+impl<N, Kind> Default for DamageHistory<N, Kind> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            overflow: None,
+        }
+    }
+}
+
+impl<N: Clone, Kind> Clone for DamageHistory<N, Kind> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            overflow: self.overflow.clone(),
+        }
+    }
 }
 
 impl<N, Kind> Clone for DamageSnapshot<N, Kind> {
@@ -118,7 +198,8 @@ impl<N: fmt::Debug> fmt::Debug for DamageSnapshot<N, BufferCoord> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("DamageSnapshot")
             .field("commit_counter", &self.commit_counter)
-            .field("damage", &self.damage)
+            .field("damage", &self.damage.entries)
+            .field("overflow", &self.damage.overflow)
             .finish()
     }
 }
@@ -127,7 +208,8 @@ impl<N: fmt::Debug> fmt::Debug for DamageSnapshot<N, Physical> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("DamageSnapshot")
             .field("commit_counter", &self.commit_counter)
-            .field("damage", &self.damage)
+            .field("damage", &self.damage.entries)
+            .field("overflow", &self.damage.overflow)
             .finish()
     }
 }
@@ -136,7 +218,8 @@ impl<N: fmt::Debug> fmt::Debug for DamageSnapshot<N, Logical> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("DamageSnapshot")
             .field("commit_counter", &self.commit_counter)
-            .field("damage", &self.damage)
+            .field("damage", &self.damage.entries)
+            .field("overflow", &self.damage.overflow)
             .finish()
     }
 }
@@ -156,8 +239,11 @@ impl<N: Clone, Kind> DamageSnapshot<N, Kind> {
     fn new(limit: usize) -> Self {
         DamageSnapshot {
             limit,
-            commit_counter: CommitCounter::default(),
-            damage: Arc::new(VecDeque::with_capacity(limit)),
+            commit_counter: CommitCounter::with_new_generation(0),
+            damage: Arc::new(DamageHistory {
+                entries: VecDeque::with_capacity(limit),
+                overflow: None,
+            }),
         }
     }
 
@@ -182,45 +268,55 @@ impl<N: Clone, Kind> DamageSnapshot<N, Kind> {
     }
 
     /// Provides raw access to the stored damage
+    ///
+    /// This does not include damage that exceeded the limit of the history.
     pub fn damage(&self) -> impl Iterator<Item = impl Iterator<Item = &Rectangle<N, Kind>>> {
-        self.damage.iter().map(|d| d.iter())
+        self.damage.entries.iter().map(|d| d.iter())
     }
 
     fn reset(&mut self) {
-        Arc::make_mut(&mut self.damage).clear();
-        self.commit_counter.increment();
+        let history = Arc::make_mut(&mut self.damage);
+        history.entries.clear();
+        history.overflow = None;
+        // Starting a new generation makes sure commits from before the
+        // reset will never be considered for partial damage
+        self.commit_counter = CommitCounter::with_new_generation(self.commit_counter.counter.wrapping_add(1));
     }
 }
 
 impl<N: Coordinate, Kind> DamageSnapshot<N, Kind> {
     /// Get the damage since the last commit
     ///
-    /// Returns `None` in case the [`CommitCounter`] is too old
-    /// or the damage has been reset. In that case the whole
-    /// element geometry should be considered as damaged
+    /// Returns `None` in case the [`CommitCounter`] is not known
+    /// or the damage has been reset since. In that case the whole
+    /// element geometry should be considered as damaged.
+    /// Commits older than the stored history additionally
+    /// get the bounding box of the dropped damage.
     ///
     /// If the commit is recent enough and no damage has occurred
     /// an empty `Vec` will be returned
     pub fn damage_since(&self, commit: Option<CommitCounter>) -> Option<DamageSet<N, Kind>> {
-        let distance = self.commit_counter.distance(commit);
-
-        if distance
-            .map(|distance| distance <= self.damage.len())
-            .unwrap_or(false)
-        {
-            let mut damage_set = DamageSet::default();
-            for damage in self.damage.iter().take(distance.unwrap()) {
-                damage_set.damage.extend_from_slice(damage);
-            }
-            Some(damage_set)
-        } else {
-            None
+        let distance = self.commit_counter.distance(commit)?;
+
+        let mut damage_set = DamageSet::default();
+        for damage in self.damage.entries.iter().take(distance) {
+            damage_set.damage.extend_from_slice(damage);
         }
+
+        if distance > self.damage.entries.len() {
+            // The commit is older than the stored history, but still from the current
+            // generation, so all damage since then is contained in the overflow
+            damage_set.damage.push(self.damage.overflow?);
+        }
+
+        Some(damage_set)
     }
 
     fn add(&mut self, damage: impl IntoIterator<Item = Rectangle<N, Kind>>) {
-        // FIXME: Get rid of this allocation here
-        let mut damage = damage.into_iter().filter(|d| !d.is_empty()).collect::<Vec<_>>();
+        let mut damage = damage
+            .into_iter()
+            .filter(|d| !d.is_empty())
+            .collect::<smallvec::SmallVec<[_; MAX_DAMAGE_RECTS]>>();
 
         if damage.is_empty() {
             // do not track empty damage
@@ -229,12 +325,29 @@ impl<N: Coordinate, Kind> DamageSnapshot<N, Kind> {
 
         damage.dedup();
 
-        let inner_damage = Arc::make_mut(&mut self.damage);
-        inner_damage.push_front(smallvec::SmallVec::from_vec(damage));
-        inner_damage.truncate(self.limit);
+        let history = Arc::make_mut(&mut self.damage);
+        history.entries.push_front(damage);
+        history.truncate(self.limit);
 
         self.commit_counter.increment();
     }
+
+    fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        Arc::make_mut(&mut self.damage).truncate(limit);
+    }
+}
+
+impl<N: Coordinate, Kind> DamageHistory<N, Kind> {
+    fn truncate(&mut self, limit: usize) {
+        while self.entries.len() > limit {
+            let dropped = self.entries.pop_back().unwrap();
+            self.overflow = dropped
+                .into_iter()
+                .chain(self.overflow)
+                .reduce(|bbox, rect| bbox.merge(rect));
+        }
+    }
 }
 
 impl<N: Clone, Kind> DamageBag<N, Kind> {
@@ -252,6 +365,12 @@ impl<N: Clone, Kind> DamageBag<N, Kind> {
         self.state.current_commit()
     }
 
+    /// Returns the number of damage states kept in the history
+    #[inline]
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
     /// Provides raw access to the stored damage
     pub fn damage(&self) -> impl Iterator<Item = impl Iterator<Item = &Rectangle<N, Kind>>> {
         self.state.damage()
@@ -279,11 +398,22 @@ impl<N: Coordinate, Kind> DamageBag<N, Kind> {
         self.state.add(damage)
     }
 
+    /// Change the number of damage states kept in the history
+    ///
+    /// Damage exceeding the new limit is folded into the bounding
+    /// rectangle of older damage.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.state.set_limit(limit)
+    }
+
     /// Get the damage since the last commit
     ///
-    /// Returns `None` in case the [`CommitCounter`] is too old
-    /// or the damage has been reset. In that case the whole
-    /// element geometry should be considered as damaged
+    /// Returns `None` in case the [`CommitCounter`] is not known
+    /// or the damage has been reset since. In that case the whole
+    /// element geometry should be considered as damaged.
+    /// Commits older than the stored history additionally
+    /// get the bounding box of the dropped damage.
     ///
     /// If the commit is recent enough and no damage has occurred
     /// an empty `Vec` will be returned
@@ -544,3 +674,102 @@ pub struct SurfaceView {
     /// The logical offset for a sub-surface
     pub offset: Point<i32, Logical>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Physical> {
+        Rectangle::new((x, y).into(), (w, h).into())
+    }
+
+    #[test]
+    fn distance_wraps_around() {
+        let previous = CommitCounter::from(usize::MAX - 1);
+        let mut current = previous;
+        for _ in 0..4 {
+            current.increment();
+        }
+        assert_eq!(current.distance(Some(previous)), Some(4));
+        assert_eq!(current.distance(Some(current)), Some(0));
+        // a commit ahead of the current one is never considered for partial damage
+        assert_eq!(previous.distance(Some(current)), None);
+
+        assert!(previous < current);
+        assert!(current > previous);
+        assert!(current <= current);
+    }
+
+    #[test]
+    fn generations_are_unordered() {
+        let mut bag = DamageBag::<i32, Physical>::new(4);
+        let other = DamageBag::<i32, Physical>::new(4);
+        let first = bag.current_commit();
+        bag.add([rect(0, 0, 10, 10)]);
+        assert!(first < bag.current_commit());
+
+        assert_eq!(bag.current_commit().partial_cmp(&other.current_commit()), None);
+        assert_eq!(bag.current_commit().distance(Some(other.current_commit())), None);
+    }
+
+    #[test]
+    fn damage_since_counter_wrap() {
+        let mut bag = DamageBag::<i32, Physical>::new(4);
+        bag.state.commit_counter.counter = usize::MAX;
+        let commit = bag.current_commit();
+
+        bag.add([rect(0, 0, 10, 10)]);
+        bag.add([rect(20, 20, 10, 10)]);
+
+        assert_eq!(bag.current_commit().counter, 1);
+        assert_eq!(
+            &*bag.damage_since(Some(commit)).unwrap(),
+            &[rect(20, 20, 10, 10), rect(0, 0, 10, 10)]
+        );
+    }
+
+    #[test]
+    fn damage_since_exceeding_limit() {
+        let mut bag = DamageBag::<i32, Physical>::new(2);
+        let commit = bag.current_commit();
+
+        bag.add([rect(0, 0, 10, 10)]);
+        bag.add([rect(90, 0, 10, 10)]);
+        bag.add([rect(40, 40, 10, 10)]);
+        bag.add([rect(50, 50, 10, 10)]);
+
+        // the two oldest states are folded into their bounding box
+        assert_eq!(
+            &*bag.damage_since(Some(commit)).unwrap(),
+            &[rect(50, 50, 10, 10), rect(40, 40, 10, 10), rect(0, 0, 100, 10)]
+        );
+
+        bag.set_limit(1);
+        assert_eq!(bag.limit(), 1);
+        assert_eq!(
+            &*bag.damage_since(Some(commit)).unwrap(),
+            &[rect(50, 50, 10, 10), rect(0, 0, 100, 50)]
+        );
+    }
+
+    #[test]
+    fn damage_since_other_generation() {
+        let mut bag = DamageBag::<i32, Physical>::new(4);
+        let other = DamageBag::<i32, Physical>::new(4);
+        let commit = bag.current_commit();
+
+        // commits of unrelated damage bags are not comparable, even with the same counter
+        assert_eq!(other.current_commit().counter, commit.counter);
+        assert!(bag.damage_since(Some(other.current_commit())).is_none());
+        assert!(bag
+            .damage_since(Some(CommitCounter::from(commit.counter)))
+            .is_none());
+
+        bag.add([rect(0, 0, 10, 10)]);
+        bag.reset();
+        bag.add([rect(20, 20, 10, 10)]);
+
+        assert!(bag.damage_since(Some(commit)).is_none());
+        assert_eq!(bag.damage_since(Some(bag.current_commit())).unwrap().len(), 0);
+    }
+}
@@ -1,13 +1,20 @@
 #[cfg(feature = "backend_drm")]
-use crate::wayland::drm_syncobj::{DrmSyncPoint, DrmSyncobjCachedState};
 use crate::{
-    backend::renderer::{buffer_dimensions, buffer_has_alpha, element::RenderElement, ImportAll, Renderer},
+    backend::renderer::sync::{Fence, SyncPoint},
+    wayland::drm_syncobj::{DrmSyncPoint, DrmSyncobjCachedState},
+};
+use crate::{
+    backend::renderer::{
+        buffer_dimensions, buffer_has_alpha,
+        element::{surface::ScaledViewCache, RenderElement},
+        ImportAll, Renderer,
+    },
     utils::{Buffer as BufferCoord, Coordinate, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
     wayland::{
         compositor::{
             self, add_destruction_hook, is_sync_subsurface, with_surface_tree_downward,
-            with_surface_tree_upward, BufferAssignment, Damage, RectangleKind, SubsurfaceCachedState,
-            SurfaceAttributes, SurfaceData, TraversalAction,
+            with_surface_tree_upward, BufferAssignment, Damage, RectangleKind, RegionAttributes,
+            SubsurfaceCachedState, SurfaceAttributes, SurfaceData, TraversalAction,
         },
         viewporter,
     },
@@ -17,8 +24,9 @@ use std::{
     any::TypeId,
     collections::{hash_map::Entry, HashMap},
     sync::Mutex,
+    time::Duration,
 };
-use tracing::{error, instrument, warn};
+use tracing::{error, instrument, trace, warn};
 
 use wayland_server::protocol::{wl_buffer::WlBuffer, wl_surface::WlSurface};
 
@@ -44,8 +52,46 @@ pub struct RendererSurfaceState {
     pub(crate) damage: DamageBag<i32, BufferCoord>,
     pub(crate) renderer_seen: HashMap<(TypeId, usize), CommitCounter>,
     pub(crate) textures: HashMap<(TypeId, usize), Box<dyn std::any::Any>>,
+    pub(crate) pending_uploads: HashMap<(TypeId, usize), Vec<Rectangle<i32, BufferCoord>>>,
     pub(crate) surface_view: Option<SurfaceView>,
     pub(crate) opaque_regions: Vec<Rectangle<i32, Logical>>,
+    pub(crate) scaled_views: Arc<Mutex<ScaledViewCache>>,
+    // accumulated `wl_surface.offset` of a subsurface
+    pub(crate) buffer_offset: Point<i32, Logical>,
+    held_buffer: Option<HeldBuffer>,
+}
+
+/// An inconsistent buffer waiting to replace the current one
+#[derive(Debug)]
+struct HeldBuffer {
+    buffer: Buffer,
+    dimensions: Size<i32, BufferCoord>,
+    has_alpha: Option<bool>,
+    scale: i32,
+    transform: Transform,
+    damage: Vec<Damage>,
+}
+
+/// Handling of buffers inconsistent with the previous state of their surface
+///
+/// See [`on_commit_buffer_handler_with_policy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InconsistentBufferPolicy {
+    /// Always use the newly attached buffer
+    #[default]
+    Accept,
+    /// Keep the previous buffer until the next frame, if the new buffer is inconsistent
+    ///
+    /// A buffer is considered inconsistent, if its size is not a multiple of its scale or if it
+    /// has the same size as the previous buffer, while its scale or the rotation of its transform
+    /// changed. This usually happens if a client updates the scale or transform of a surface
+    /// before rendering new contents, e.g. while the surface is moved between outputs or resized.
+    ///
+    /// The held buffer replaces the previous one once the surface is imported again
+    /// (see [`import_surface`]), unless a newer commit replaced it in the meantime.
+    /// A client intentionally attaching such a buffer is thus delayed by at most one frame,
+    /// even if it doesn't commit again.
+    HoldPrevious,
 }
 
 // SAFETY: Only thing unsafe here is the `Box<dyn std::any::Any>`, which are the textures.
@@ -109,10 +155,25 @@ impl Buffer {
     }
 
     #[cfg(feature = "backend_drm")]
-    #[allow(dead_code)]
     pub(crate) fn acquire_point(&self) -> Option<&DrmSyncPoint> {
         self.inner.acquire_point.as_ref()
     }
+
+    /// Returns the acquire point the renderer has to wait for before reading the buffer
+    ///
+    /// The transaction of a surface might have been released before the client's rendering finished,
+    /// see [`DrmSyncPoint::generate_submission_blocker`].
+    #[cfg(feature = "backend_drm")]
+    pub(crate) fn pending_acquire_point(&self) -> Option<SyncPoint> {
+        pending_sync_point(self.acquire_point())
+    }
+}
+
+#[cfg(feature = "backend_drm")]
+fn pending_sync_point<F: Fence + Clone + 'static>(fence: Option<&F>) -> Option<SyncPoint> {
+    fence
+        .filter(|fence| !fence.is_signaled())
+        .map(|fence| SyncPoint::from(fence.clone()))
 }
 
 impl std::ops::Deref for Buffer {
@@ -140,7 +201,7 @@ impl PartialEq<WlBuffer> for &Buffer {
 
 impl RendererSurfaceState {
     #[profiling::function]
-    pub(crate) fn update_buffer(&mut self, states: &SurfaceData) {
+    pub(crate) fn update_buffer(&mut self, states: &SurfaceData, policy: InconsistentBufferPolicy) {
         #[cfg(feature = "backend_drm")]
         let mut guard = states.cached_state.get::<DrmSyncobjCachedState>();
         #[cfg(feature = "backend_drm")]
@@ -149,53 +210,122 @@ impl RendererSurfaceState {
         let mut guard = states.cached_state.get::<SurfaceAttributes>();
         let attrs = guard.current();
 
-        let new_buffer = matches!(attrs.buffer, Some(BufferAssignment::NewBuffer(_)));
-        match attrs.buffer.take() {
+        // Root surfaces are positioned by the compositor, which has to apply their offset,
+        // but the position of subsurfaces is up to us.
+        if states.role == Some("subsurface") {
+            if let Some(delta) = attrs.buffer_delta.take() {
+                self.buffer_offset += delta;
+            }
+        }
+
+        let damage = match attrs.buffer.take() {
             Some(BufferAssignment::NewBuffer(buffer)) => {
-                self.buffer_dimensions = buffer_dimensions(&buffer);
-                if self.buffer_dimensions.is_none() {
+                let Some(dimensions) = buffer_dimensions(&buffer) else {
                     // This results in us rendering nothing (can happen e.g. for failed egl-buffer-calls),
                     // but it is better than crashing the compositor for a bad buffer
                     self.reset();
                     return;
-                }
-                self.buffer_has_alpha = buffer_has_alpha(&buffer);
-                self.buffer_scale = attrs.buffer_scale;
-                self.buffer_transform = attrs.buffer_transform.into();
-
-                if !self.buffer.as_ref().is_some_and(|b| b == buffer) {
-                    self.buffer = Some(Buffer {
-                        inner: Arc::new(InnerBuffer {
-                            buffer,
-                            #[cfg(feature = "backend_drm")]
-                            acquire_point: syncobj_state.acquire_point.take(),
-                            #[cfg(feature = "backend_drm")]
-                            release_point: syncobj_state.release_point.take(),
-                        }),
+                };
+                let scale = attrs.buffer_scale;
+                let transform = attrs.buffer_transform.into();
+                let has_alpha = buffer_has_alpha(&buffer);
+                let is_current = self.buffer.as_ref().is_some_and(|b| b == buffer);
+
+                let new_buffer = (!is_current).then(|| Buffer {
+                    inner: Arc::new(InnerBuffer {
+                        buffer: buffer.clone(),
+                        #[cfg(feature = "backend_drm")]
+                        acquire_point: syncobj_state.acquire_point.take(),
+                        #[cfg(feature = "backend_drm")]
+                        release_point: syncobj_state.release_point.take(),
+                    }),
+                });
+
+                if policy == InconsistentBufferPolicy::HoldPrevious
+                    && !is_current
+                    && self.is_inconsistent(dimensions, scale, transform)
+                {
+                    trace!(
+                        "holding new buffer until the next frame, it is inconsistent with the surface state"
+                    );
+                    // replacing a previously held buffer releases it
+                    self.held_buffer = new_buffer.map(|buffer| HeldBuffer {
+                        buffer,
+                        dimensions,
+                        has_alpha,
+                        scale,
+                        transform,
+                        damage: std::mem::take(&mut attrs.damage),
                     });
+                    None
+                } else {
+                    self.held_buffer = None;
+                    self.set_buffer(new_buffer, dimensions, has_alpha, scale, transform);
+                    Some(std::mem::take(&mut attrs.damage))
                 }
-
-                self.textures.clear();
             }
             Some(BufferAssignment::Removed) => {
                 self.reset();
                 return;
             }
-            None => {}
+            None => None,
         };
 
+        self.update_view(states, attrs, damage);
+    }
+
+    /// Replaces the current buffer by a held inconsistent buffer, see [`InconsistentBufferPolicy::HoldPrevious`]
+    pub(crate) fn apply_held_buffer(&mut self, states: &SurfaceData) {
+        let Some(held) = self.held_buffer.take() else {
+            return;
+        };
+        trace!("applying held buffer");
+        self.set_buffer(
+            Some(held.buffer),
+            held.dimensions,
+            held.has_alpha,
+            held.scale,
+            held.transform,
+        );
+        let mut guard = states.cached_state.get::<SurfaceAttributes>();
+        self.update_view(states, guard.current(), Some(held.damage));
+    }
+
+    fn set_buffer(
+        &mut self,
+        buffer: Option<Buffer>,
+        dimensions: Size<i32, BufferCoord>,
+        has_alpha: Option<bool>,
+        scale: i32,
+        transform: Transform,
+    ) {
+        self.buffer_dimensions = Some(dimensions);
+        self.buffer_has_alpha = has_alpha;
+        self.buffer_scale = scale;
+        self.buffer_transform = transform;
+        // `None` if the current buffer was attached again
+        if let Some(buffer) = buffer {
+            self.buffer = Some(buffer);
+        }
+        self.textures.clear();
+    }
+
+    /// Updates the view, damage and opaque regions, `damage` is only `Some` for a new buffer
+    fn update_view(&mut self, states: &SurfaceData, attrs: &SurfaceAttributes, damage: Option<Vec<Damage>>) {
         let Some(buffer_dimensions) = self.buffer_dimensions else {
             // nothing to be done without a buffer
             return;
         };
 
         let surface_size = buffer_dimensions.to_logical(self.buffer_scale, self.buffer_transform);
-        let surface_view = SurfaceView::from_states(states, surface_size, attrs.client_scale);
+        let mut surface_view = SurfaceView::from_states(states, surface_size, attrs.client_scale);
+        surface_view.offset += self.buffer_offset;
         let surface_view_changed = self.surface_view.replace(surface_view) != Some(surface_view);
 
         // if we received a new buffer also process the attached damage
-        if new_buffer {
-            let buffer_damage = attrs.damage.drain(..).flat_map(|dmg| {
+        let new_buffer = damage.is_some();
+        if let Some(damage) = damage {
+            let buffer_damage = damage.into_iter().flat_map(|dmg| {
                 match dmg {
                     Damage::Buffer(rect) => rect,
                     Damage::Surface(rect) => surface_view.rect_to_local(rect).to_i32_up().to_buffer(
@@ -211,50 +341,57 @@ impl RendererSurfaceState {
 
         // if the buffer or our view changed rebuild our opaque regions
         if new_buffer || surface_view_changed {
-            self.opaque_regions.clear();
-            if !self.buffer_has_alpha.unwrap_or(true) {
-                self.opaque_regions.push(Rectangle::from_size(surface_view.dst))
-            } else if let Some(region_attributes) = &attrs.opaque_region {
-                let opaque_regions = region_attributes
-                    .rects
-                    .iter()
-                    .map(|(kind, rect)| {
-                        let dest_size = surface_view.dst;
-
-                        let rect_constrained_loc = rect.loc.constrain(Rectangle::from_size(dest_size));
-                        let rect_clamped_size = rect
-                            .size
-                            .clamp((0, 0), (dest_size.to_point() - rect_constrained_loc).to_size());
-
-                        let rect = Rectangle::new(rect_constrained_loc, rect_clamped_size);
-
-                        (kind, rect)
-                    })
-                    .fold(
-                        std::mem::take(&mut self.opaque_regions),
-                        |mut new_regions, (kind, rect)| {
-                            match kind {
-                                RectangleKind::Add => {
-                                    let added_regions = rect.subtract_rects(
-                                        new_regions
-                                            .iter()
-                                            .filter(|region| region.overlaps_or_touches(rect))
-                                            .copied(),
-                                    );
-                                    new_regions.extend(added_regions);
-                                }
-                                RectangleKind::Subtract => {
-                                    new_regions =
-                                        Rectangle::subtract_rects_many_in_place(new_regions, [rect]);
-                                }
+            self.update_opaque_regions(surface_view, attrs.opaque_region.as_ref());
+        }
+    }
+
+    fn update_opaque_regions(&mut self, surface_view: SurfaceView, opaque_region: Option<&RegionAttributes>) {
+        // the cached physical views are only invalidated by new commits, but a new buffer
+        // without damage keeps the commit and might still change the opaque regions
+        self.scaled_views = Arc::default();
+
+        self.opaque_regions.clear();
+        if !self.buffer_has_alpha.unwrap_or(true) {
+            self.opaque_regions.push(Rectangle::from_size(surface_view.dst))
+        } else if let Some(region_attributes) = opaque_region {
+            let opaque_regions = region_attributes
+                .rects
+                .iter()
+                .map(|(kind, rect)| {
+                    let dest_size = surface_view.dst;
+
+                    let rect_constrained_loc = rect.loc.constrain(Rectangle::from_size(dest_size));
+                    let rect_clamped_size = rect
+                        .size
+                        .clamp((0, 0), (dest_size.to_point() - rect_constrained_loc).to_size());
+
+                    let rect = Rectangle::new(rect_constrained_loc, rect_clamped_size);
+
+                    (kind, rect)
+                })
+                .fold(
+                    std::mem::take(&mut self.opaque_regions),
+                    |mut new_regions, (kind, rect)| {
+                        match kind {
+                            RectangleKind::Add => {
+                                let added_regions = rect.subtract_rects(
+                                    new_regions
+                                        .iter()
+                                        .filter(|region| region.overlaps_or_touches(rect))
+                                        .copied(),
+                                );
+                                new_regions.extend(added_regions);
+                            }
+                            RectangleKind::Subtract => {
+                                new_regions = Rectangle::subtract_rects_many_in_place(new_regions, [rect]);
                             }
+                        }
 
-                            new_regions
-                        },
-                    );
+                        new_regions
+                    },
+                );
 
-                self.opaque_regions = opaque_regions;
-            }
+            self.opaque_regions = opaque_regions;
         }
     }
 
@@ -309,6 +446,20 @@ impl RendererSurfaceState {
         self.surface_view.map(|view| view.dst)
     }
 
+    /// Maps a surface-local point to the coordinate space of the attached buffer
+    ///
+    /// Takes the viewport, buffer scale and buffer transform of the surface into account.
+    /// Returns `None` if no buffer is attached.
+    pub fn surface_to_buffer_point(&self, point: Point<f64, Logical>) -> Option<Point<f64, BufferCoord>> {
+        let surface_view = self.surface_view?;
+        let surface_size = self.buffer_size()?.to_f64();
+        Some(surface_view.point_to_local(point).to_buffer(
+            self.buffer_scale as f64,
+            self.buffer_transform,
+            &surface_size,
+        ))
+    }
+
     /// Get the attached buffer.
     /// Can be used to check if surface is mapped
     pub fn buffer(&self) -> Option<&Buffer> {
@@ -345,14 +496,47 @@ impl RendererSurfaceState {
         self.surface_view
     }
 
+    fn is_inconsistent(&self, dimensions: Size<i32, BufferCoord>, scale: i32, transform: Transform) -> bool {
+        if scale > 1 && (dimensions.w % scale != 0 || dimensions.h % scale != 0) {
+            return true;
+        }
+
+        // the contents weren't updated, if the size stayed the same
+        if self.buffer.is_none() || self.buffer_dimensions != Some(dimensions) {
+            return false;
+        }
+        let rotated = |transform: Transform| transform.degrees() % 180 != 0;
+        scale != self.buffer_scale
+            || (dimensions.w != dimensions.h && rotated(transform) != rotated(self.buffer_transform))
+    }
+
+    fn release_textures(&mut self) {
+        self.textures.clear();
+        self.pending_uploads.clear();
+        // import the buffer in full next time
+        self.renderer_seen.clear();
+    }
+
+    fn invalidate(&mut self) {
+        self.release_textures();
+        // damage the whole buffer, so elements get drawn again
+        if let Some(size) = self.buffer_dimensions {
+            self.damage.add([Rectangle::from_size(size)]);
+        }
+    }
+
     fn reset(&mut self) {
         self.buffer_dimensions = None;
         self.buffer = None;
+        self.held_buffer = None;
         self.textures.clear();
+        self.pending_uploads.clear();
         self.damage.reset();
         self.surface_view = None;
         self.buffer_has_alpha = None;
         self.opaque_regions.clear();
+        self.scaled_views = Arc::default();
+        self.buffer_offset = Point::default();
     }
 }
 
@@ -367,6 +551,18 @@ impl RendererSurfaceState {
 /// become usable for surfaces handled this way.
 #[profiling::function]
 pub fn on_commit_buffer_handler<D: 'static>(surface: &WlSurface) {
+    on_commit_buffer_handler_with_policy::<D>(surface, InconsistentBufferPolicy::Accept)
+}
+
+/// Handler to let smithay take over buffer management, handling inconsistent buffers according to `policy`
+///
+/// Same as [`on_commit_buffer_handler`], but allows to hold the previous buffer of a surface,
+/// if a new buffer doesn't match the state of the surface, see [`InconsistentBufferPolicy`].
+#[profiling::function]
+pub fn on_commit_buffer_handler_with_policy<D: 'static>(
+    surface: &WlSurface,
+    policy: InconsistentBufferPolicy,
+) {
     if !is_sync_subsurface(surface) {
         let mut new_surfaces = Vec::new();
         with_surface_tree_upward(
@@ -386,7 +582,7 @@ pub fn on_commit_buffer_handler<D: 'static>(surface: &WlSurface) {
                     .unwrap()
                     .lock()
                     .unwrap();
-                data.update_buffer(states);
+                data.update_buffer(states, policy);
             },
             |_, _, _| true,
         );
@@ -454,6 +650,10 @@ impl SurfaceView {
         rect
     }
 
+    pub(crate) fn point_to_local(&self, point: Point<f64, Logical>) -> Point<f64, Logical> {
+        point.downscale(self.scale()) + self.src.loc
+    }
+
     fn scale(&self) -> Scale<f64> {
         Scale::from((
             self.dst.w as f64 / self.src.size.w,
@@ -477,6 +677,110 @@ where
     })
 }
 
+/// Budget limiting the amount of shm buffer contents uploaded per frame
+///
+/// Software-rendered clients can damage large parts of huge shm buffers every frame, e.g. a browser
+/// scrolling at 4K. Uploading all of that can take longer than a frame and ruin the frame pacing of the
+/// compositor. When importing with [`import_surface_with_budget`] or [`import_surface_tree_with_budget`],
+/// damaged regions of shm buffers exceeding the budget are deferred and uploaded during the next frames.
+///
+/// The cost of an upload is estimated as four bytes per damaged pixel. Buffers imported for the first time
+/// by a renderer and buffers not backed by shm are always imported in full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadBudget {
+    limit: Option<usize>,
+    remaining: usize,
+}
+
+impl Default for UploadBudget {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+impl UploadBudget {
+    /// Create a budget allowing to upload `bytes` per frame
+    pub fn new(bytes: usize) -> Self {
+        UploadBudget {
+            limit: Some(bytes),
+            remaining: bytes,
+        }
+    }
+
+    /// Create a budget never deferring any uploads
+    pub fn unlimited() -> Self {
+        UploadBudget {
+            limit: None,
+            remaining: usize::MAX,
+        }
+    }
+
+    /// Create a budget from the upload bandwidth of the renderer and the time uploads may take per frame
+    pub fn from_bandwidth(bytes_per_second: u64, time: Duration) -> Self {
+        let bytes = Ord::min(
+            bytes_per_second as u128 * time.as_nanos() / 1_000_000_000,
+            usize::MAX as u128,
+        );
+        Self::new(bytes as usize)
+    }
+
+    /// Returns the bytes left to upload in this frame
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Returns if uploads are deferred by this budget at all
+    pub fn is_unlimited(&self) -> bool {
+        self.limit.is_none()
+    }
+
+    /// Restore the full budget for the next frame
+    pub fn reset(&mut self) {
+        self.remaining = self.limit.unwrap_or(usize::MAX);
+    }
+
+    // Splits `damage` into the regions to upload now and the deferred regions.
+    //
+    // If `force` is set at least a single row is taken, as an empty damage would
+    // result in uploading the whole buffer.
+    fn take(
+        &mut self,
+        damage: &[Rectangle<i32, BufferCoord>],
+        force: bool,
+    ) -> (Vec<Rectangle<i32, BufferCoord>>, Vec<Rectangle<i32, BufferCoord>>) {
+        const BYTES_PER_PIXEL: usize = 4;
+
+        let mut upload = Vec::new();
+        let mut deferred = Vec::new();
+        for rect in damage.iter().copied().filter(|rect| !rect.is_empty()) {
+            if !deferred.is_empty() {
+                deferred.push(rect);
+                continue;
+            }
+
+            let row_bytes = rect.size.w as usize * BYTES_PER_PIXEL;
+            let mut rows = (self.remaining / row_bytes).min(rect.size.h as usize) as i32;
+            if rows == 0 && force && upload.is_empty() {
+                rows = 1;
+            }
+            self.remaining = self.remaining.saturating_sub(rows as usize * row_bytes);
+
+            if rows == rect.size.h {
+                upload.push(rect);
+                continue;
+            }
+            if rows > 0 {
+                upload.push(Rectangle::new(rect.loc, (rect.size.w, rows).into()));
+            }
+            deferred.push(Rectangle::new(
+                (rect.loc.x, rect.loc.y + rows).into(),
+                (rect.size.w, rect.size.h - rows).into(),
+            ));
+        }
+        (upload, deferred)
+    }
+}
+
 /// Imports buffers of a surface using a given [`Renderer`]
 ///
 /// This (or `import_surface_tree`) need to be called before`draw_render_elements`, if used later.
@@ -487,6 +791,28 @@ where
 #[instrument(level = "trace", skip_all)]
 #[profiling::function]
 pub fn import_surface<R>(renderer: &mut R, states: &SurfaceData) -> Result<(), <R as Renderer>::Error>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    import_surface_with_budget(renderer, states, &mut UploadBudget::unlimited())
+}
+
+/// Imports buffers of a surface using a given [`Renderer`], limiting shm uploads to a [`UploadBudget`]
+///
+/// Damaged regions exceeding the budget are deferred and uploaded by the next calls to this function,
+/// while the texture keeps showing their previous contents. The uploaded regions get damaged again,
+/// so render elements created afterwards pick them up.
+///
+/// As render elements import their surfaces without a budget, but never upload deferred regions
+/// on their own, this has to be called for all visible surfaces before creating the render elements of a frame.
+#[instrument(level = "trace", skip_all)]
+#[profiling::function]
+pub fn import_surface_with_budget<R>(
+    renderer: &mut R,
+    states: &SurfaceData,
+    budget: &mut UploadBudget,
+) -> Result<(), <R as Renderer>::Error>
 where
     R: Renderer + ImportAll,
     <R as Renderer>::TextureId: 'static,
@@ -495,26 +821,98 @@ where
         let texture_id = (TypeId::of::<<R as Renderer>::TextureId>(), renderer.id());
         let mut data_ref = data.lock().unwrap();
         let data = &mut *data_ref;
+        data.apply_held_buffer(states);
 
-        let last_commit = data.renderer_seen.get(&texture_id);
-        let buffer_damage = data.damage_since(last_commit.copied());
-        if let Entry::Vacant(e) = data.textures.entry(texture_id) {
-            if let Some(buffer) = data.buffer.as_ref() {
-                // There is no point in importing a single pixel buffer
-                if matches!(
-                    crate::backend::renderer::buffer_type(buffer),
-                    Some(crate::backend::renderer::BufferType::SinglePixel)
-                ) {
-                    return Ok(());
+        let last_commit = data.renderer_seen.get(&texture_id).copied();
+        let Some(buffer) = data.buffer.as_ref() else {
+            return Ok(());
+        };
+        let buffer_type = crate::backend::renderer::buffer_type(buffer);
+        // There is no point in importing a single pixel buffer
+        if matches!(
+            buffer_type,
+            Some(crate::backend::renderer::BufferType::SinglePixel)
+        ) {
+            return Ok(());
+        }
+        let deferrable = !budget.is_unlimited()
+            && last_commit.is_some()
+            && matches!(buffer_type, Some(crate::backend::renderer::BufferType::Shm));
+
+        match data.textures.entry(texture_id) {
+            Entry::Vacant(e) => {
+                let mut buffer_damage = data.damage.damage_since(last_commit).map_or_else(
+                    || {
+                        data.buffer_dimensions
+                            .map(|size| vec![Rectangle::from_size(size)])
+                            .unwrap_or_default()
+                    },
+                    |damage| damage.to_vec(),
+                );
+                // regions deferred for the previous buffer are still outdated
+                if let Some(pending) = data.pending_uploads.remove(&texture_id) {
+                    if !buffer_damage.is_empty() {
+                        buffer_damage.extend(pending);
+                    }
+                }
+                if deferrable && !buffer_damage.is_empty() {
+                    let (upload, deferred) = budget.take(&buffer_damage, true);
+                    if !deferred.is_empty() {
+                        trace!(?deferred, "deferring shm upload");
+                        data.pending_uploads.insert(texture_id, deferred);
+                    }
+                    buffer_damage = upload;
+                }
+
+                // copies made during the import have to wait for the client's rendering as well
+                #[cfg(feature = "backend_drm")]
+                if let Some(acquire_point) = buffer.pending_acquire_point() {
+                    renderer.wait(&acquire_point)?;
                 }
 
                 match renderer.import_buffer(buffer, Some(states), &buffer_damage) {
                     Some(Ok(m)) => {
                         e.insert(Box::new(m));
-                        data.renderer_seen.insert(texture_id, data.current_commit());
+                        data.renderer_seen
+                            .insert(texture_id, data.damage.current_commit());
+                    }
+                    Some(Err(err)) => {
+                        warn!("Error loading buffer: {}", err);
+                        data.pending_uploads.remove(&texture_id);
+                        return Err(err);
+                    }
+                    None => {
+                        error!("Unknown buffer format for: {:?}", buffer);
+                    }
+                }
+            }
+            Entry::Occupied(mut e) => {
+                if !deferrable || budget.remaining() == 0 {
+                    return Ok(());
+                }
+                let Some(pending) = data.pending_uploads.remove(&texture_id) else {
+                    return Ok(());
+                };
+
+                let (upload, deferred) = budget.take(&pending, false);
+                if !deferred.is_empty() {
+                    data.pending_uploads.insert(texture_id, deferred);
+                }
+                if upload.is_empty() {
+                    return Ok(());
+                }
+
+                match renderer.import_buffer(buffer, Some(states), &upload) {
+                    Some(Ok(m)) => {
+                        e.insert(Box::new(m));
+                        // draw the uploaded regions again
+                        data.damage.add(upload);
+                        data.renderer_seen
+                            .insert(texture_id, data.damage.current_commit());
                     }
                     Some(Err(err)) => {
                         warn!("Error loading buffer: {}", err);
+                        data.pending_uploads.remove(&texture_id);
                         return Err(err);
                     }
                     None => {
@@ -528,6 +926,56 @@ where
     Ok(())
 }
 
+/// Returns the approximate memory in bytes used by the textures renderers of type `R` imported for a surface
+///
+/// Textures of all renderers of this type are accounted for, textures of unknown formats are skipped.
+/// See [`estimated_memory_usage`](super::estimated_memory_usage) for details.
+pub fn surface_texture_memory_usage<R>(states: &SurfaceData) -> usize
+where
+    R: Renderer,
+    <R as Renderer>::TextureId: 'static,
+{
+    let Some(data) = states.data_map.get::<RendererSurfaceStateUserData>() else {
+        return 0;
+    };
+
+    let type_id = TypeId::of::<<R as Renderer>::TextureId>();
+    data.lock()
+        .unwrap()
+        .textures
+        .iter()
+        .filter(|((texture_type, _), _)| *texture_type == type_id)
+        .filter_map(|(_, texture)| texture.downcast_ref::<<R as Renderer>::TextureId>())
+        .filter_map(super::texture_memory_usage)
+        .sum()
+}
+
+/// Drops all textures imported from the buffer of a surface
+///
+/// The buffer itself is kept, so the next call to [`import_surface`] imports it again in full.
+/// This allows to free the memory used by surfaces not being displayed for a while,
+/// e.g. minimized windows, at the cost of a slower first frame once they are shown again.
+pub fn release_surface_textures(states: &SurfaceData) {
+    if let Some(data) = states.data_map.get::<RendererSurfaceStateUserData>() {
+        data.lock().unwrap().release_textures();
+    }
+    #[cfg(feature = "renderer_gl")]
+    crate::backend::renderer::gles::release_shm_cache(states);
+}
+
+/// Drops all textures imported from the buffer of a surface and damages it in full
+///
+/// Unlike [`release_surface_textures`] this makes sure the surface is drawn again
+/// even if nothing else changed, e.g. to recover from rendering artifacts or after
+/// the contents of the buffer were modified outside of the renderer.
+pub fn invalidate_surface_textures(states: &SurfaceData) {
+    if let Some(data) = states.data_map.get::<RendererSurfaceStateUserData>() {
+        data.lock().unwrap().invalidate();
+    }
+    #[cfg(feature = "renderer_gl")]
+    crate::backend::renderer::gles::release_shm_cache(states);
+}
+
 /// Imports buffers of a surface and its subsurfaces using a given [`Renderer`].
 ///
 /// This (or `import_surface`) need to be called before `draw_render_elements`, if used later.
@@ -538,6 +986,23 @@ where
 #[instrument(level = "trace", skip_all)]
 #[profiling::function]
 pub fn import_surface_tree<R>(renderer: &mut R, surface: &WlSurface) -> Result<(), <R as Renderer>::Error>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    import_surface_tree_with_budget(renderer, surface, &mut UploadBudget::unlimited())
+}
+
+/// Imports buffers of a surface and its subsurfaces using a given [`Renderer`], limiting shm uploads to a [`UploadBudget`]
+///
+/// See [`import_surface_with_budget`] for details.
+#[instrument(level = "trace", skip_all)]
+#[profiling::function]
+pub fn import_surface_tree_with_budget<R>(
+    renderer: &mut R,
+    surface: &WlSurface,
+    budget: &mut UploadBudget,
+) -> Result<(), <R as Renderer>::Error>
 where
     R: Renderer + ImportAll,
     <R as Renderer>::TextureId: 'static,
@@ -553,7 +1018,7 @@ where
         |_surface, states, location| {
             let mut location = *location;
             // Import a new buffer if necessary
-            if let Err(err) = import_surface(renderer, states) {
+            if let Err(err) = import_surface_with_budget(renderer, states, budget) {
                 result = Err(err);
             }
 
@@ -679,3 +1144,314 @@ where
 
     Ok(Some(render_damage))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{RendererSurfaceState, SurfaceView, UploadBudget};
+    use crate::{
+        utils::{Point, Rectangle, Size, Transform},
+        wayland::compositor::{RectangleKind, RegionAttributes},
+    };
+
+    #[test]
+    fn upload_budget_splits_damage() {
+        let damage = [Rectangle::new((0, 0).into(), (100, 10).into())];
+
+        // 4000 bytes cover 10 rows of 100 pixels
+        let mut budget = UploadBudget::new(4000);
+        let (upload, deferred) = budget.take(&damage, false);
+        assert_eq!(upload, vec![Rectangle::new((0, 0).into(), (100, 10).into())]);
+        assert!(deferred.is_empty());
+        assert_eq!(budget.remaining(), 0);
+
+        budget = UploadBudget::new(1000);
+        let (upload, deferred) = budget.take(&damage, false);
+        assert_eq!(upload, vec![Rectangle::new((0, 0).into(), (100, 2).into())]);
+        assert_eq!(deferred, vec![Rectangle::new((0, 2).into(), (100, 8).into())]);
+
+        // an exhausted budget still uploads a single row if forced
+        budget = UploadBudget::new(0);
+        assert!(budget.take(&damage, false).0.is_empty());
+        let (upload, _) = budget.take(&damage, true);
+        assert_eq!(upload, vec![Rectangle::new((0, 0).into(), (100, 1).into())]);
+
+        budget.reset();
+        assert_eq!(budget.remaining(), 0);
+        assert!(UploadBudget::unlimited().take(&damage, false).1.is_empty());
+    }
+
+    #[test]
+    fn surface_to_buffer_point_with_viewport_and_transform() {
+        let mut state = RendererSurfaceState::default();
+        assert!(state.surface_to_buffer_point((0.0, 0.0).into()).is_none());
+
+        // a rotated 400x200 buffer with scale 2 is 100x200 in surface space
+        state.buffer_dimensions = Some(Size::from((400, 200)));
+        state.buffer_scale = 2;
+        state.buffer_transform = Transform::_90;
+        // the viewport crops a 50x100 region at (10, 20) and scales it to 100x200
+        state.surface_view = Some(SurfaceView {
+            src: Rectangle::new((10.0, 20.0).into(), (50.0, 100.0).into()),
+            dst: Size::from((100, 200)),
+            offset: Point::from((0, 0)),
+        });
+
+        assert_eq!(
+            state.surface_to_buffer_point((0.0, 0.0).into()),
+            Some(Point::from((360.0, 20.0)))
+        );
+        assert_eq!(
+            state.surface_to_buffer_point((50.0, 100.0).into()),
+            Some(Point::from((260.0, 70.0)))
+        );
+    }
+
+    #[test]
+    fn new_buffer_without_damage_resets_scaled_views() {
+        let mut state = RendererSurfaceState {
+            buffer_dimensions: Some(Size::from((100, 100))),
+            buffer_has_alpha: Some(true),
+            ..Default::default()
+        };
+        let view = SurfaceView {
+            src: Rectangle::from_size((100.0, 100.0).into()),
+            dst: Size::from((100, 100)),
+            offset: Point::from((0, 0)),
+        };
+        let region = RegionAttributes {
+            rects: vec![(
+                RectangleKind::Add,
+                Rectangle::new((10, 10).into(), (20, 20).into()),
+            )],
+        };
+        state.update_opaque_regions(view, Some(&region));
+        assert_eq!(
+            state.opaque_regions,
+            vec![Rectangle::new((10, 10).into(), (20, 20).into())]
+        );
+
+        // an opaque buffer attached without damage does not advance the commit,
+        // elements created from the previous state must not share its cached views
+        let commit = state.current_commit();
+        let scaled_views = state.scaled_views.clone();
+        state.buffer_has_alpha = Some(false);
+        state.update_opaque_regions(view, Some(&region));
+        assert_eq!(state.current_commit(), commit);
+        assert_eq!(
+            state.opaque_regions,
+            vec![Rectangle::from_size((100, 100).into())]
+        );
+        assert!(!Arc::ptr_eq(&state.scaled_views, &scaled_views));
+    }
+}
+
+#[cfg(all(test, feature = "renderer_test"))]
+mod buffer_policy_tests {
+    use wayland_client::Proxy;
+    use wayland_server::{protocol::wl_buffer::WlBuffer, Resource};
+
+    use super::{
+        import_surface, invalidate_surface_textures, InconsistentBufferPolicy, RendererSurfaceStateUserData,
+    };
+    use crate::{
+        backend::renderer::{test::DummyRenderer, Renderer},
+        utils::Rectangle,
+        wayland::{compositor, test_utils::TestFixture},
+    };
+
+    fn current_buffer(surface: &wayland_server::protocol::wl_surface::WlSurface) -> Option<WlBuffer> {
+        compositor::with_states(surface, |states| {
+            let data = states.data_map.get::<RendererSurfaceStateUserData>().unwrap();
+            let data = data.lock().unwrap();
+            data.buffer().map(|buffer| (**buffer).clone())
+        })
+    }
+
+    fn import(surface: &wayland_server::protocol::wl_surface::WlSurface) {
+        let mut renderer = DummyRenderer::new();
+        compositor::with_states(surface, |states| import_surface(&mut renderer, states)).unwrap();
+    }
+
+    fn setup(
+        policy: InconsistentBufferPolicy,
+    ) -> (TestFixture, wayland_client::protocol::wl_surface::WlSurface) {
+        let mut fixture = TestFixture::new();
+        fixture.state.buffer_policy = policy;
+        let (surface, _) = fixture.create_surface();
+        let buffer = fixture.create_buffer(100, 100);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.commit();
+        fixture.roundtrip();
+        (fixture, surface)
+    }
+
+    #[test]
+    fn held_buffer_is_applied_on_the_next_import() {
+        let (mut fixture, surface) = setup(InconsistentBufferPolicy::HoldPrevious);
+        let server_surface = fixture.server_object(&surface);
+        let previous = current_buffer(&server_surface).unwrap();
+
+        // same size with a different scale, the client did not render for the new scale yet
+        let buffer = fixture.create_buffer(100, 100);
+        surface.set_buffer_scale(2);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.commit();
+        fixture.roundtrip();
+        assert_eq!(current_buffer(&server_surface), Some(previous.clone()));
+        assert!(fixture.client.released.is_empty());
+
+        // the client stays idle, the held buffer is shown by the next frame anyway
+        import(&server_surface);
+        let current = current_buffer(&server_surface).unwrap();
+        assert_eq!(current.id().protocol_id(), buffer.id().protocol_id());
+        compositor::with_states(&server_surface, |states| {
+            let data = states.data_map.get::<RendererSurfaceStateUserData>().unwrap();
+            assert_eq!(data.lock().unwrap().buffer_scale(), 2);
+        });
+        fixture.roundtrip();
+        assert_eq!(fixture.client.released.len(), 1);
+        assert_eq!(
+            fixture.client.released[0].id().protocol_id(),
+            previous.id().protocol_id()
+        );
+    }
+
+    #[test]
+    fn newer_commit_replaces_held_buffer() {
+        let (mut fixture, surface) = setup(InconsistentBufferPolicy::HoldPrevious);
+        let server_surface = fixture.server_object(&surface);
+
+        let held = fixture.create_buffer(100, 100);
+        surface.set_buffer_scale(2);
+        surface.attach(Some(&held), 0, 0);
+        surface.commit();
+        fixture.roundtrip();
+
+        let buffer = fixture.create_buffer(200, 200);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.commit();
+        fixture.roundtrip();
+
+        // the held buffer is released without ever being shown
+        let current = current_buffer(&server_surface).unwrap();
+        assert_eq!(current.id().protocol_id(), buffer.id().protocol_id());
+        assert!(fixture.client.released.contains(&held));
+        assert_eq!(fixture.client.released.len(), 2);
+
+        import(&server_surface);
+        let current = current_buffer(&server_surface).unwrap();
+        assert_eq!(current.id().protocol_id(), buffer.id().protocol_id());
+    }
+
+    #[test]
+    fn accept_applies_inconsistent_buffers() {
+        let (mut fixture, surface) = setup(InconsistentBufferPolicy::Accept);
+        let server_surface = fixture.server_object(&surface);
+
+        let buffer = fixture.create_buffer(100, 100);
+        surface.set_buffer_scale(2);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.commit();
+        fixture.roundtrip();
+
+        let current = current_buffer(&server_surface).unwrap();
+        assert_eq!(current.id().protocol_id(), buffer.id().protocol_id());
+        assert_eq!(fixture.client.released.len(), 1);
+    }
+
+    #[test]
+    fn invalidated_surfaces_are_damaged_in_full() {
+        let (fixture, surface) = setup(InconsistentBufferPolicy::Accept);
+        let server_surface = fixture.server_object(&surface);
+        let mut renderer = DummyRenderer::new();
+        compositor::with_states(&server_surface, |states| import_surface(&mut renderer, states)).unwrap();
+
+        let commit = compositor::with_states(&server_surface, |states| {
+            let data = states.data_map.get::<RendererSurfaceStateUserData>().unwrap();
+            let data = data.lock().unwrap();
+            assert!(data.texture::<DummyRenderer>(renderer.id()).is_some());
+            data.current_commit()
+        });
+
+        compositor::with_states(&server_surface, invalidate_surface_textures);
+        compositor::with_states(&server_surface, |states| {
+            let data = states.data_map.get::<RendererSurfaceStateUserData>().unwrap();
+            let data = data.lock().unwrap();
+            assert!(data.texture::<DummyRenderer>(renderer.id()).is_none());
+            assert_eq!(
+                data.damage_since(Some(commit)).to_vec(),
+                vec![Rectangle::from_size((100, 100).into())]
+            );
+        });
+
+        // the buffer is kept and imported again
+        compositor::with_states(&server_surface, |states| import_surface(&mut renderer, states)).unwrap();
+        compositor::with_states(&server_surface, |states| {
+            let data = states.data_map.get::<RendererSurfaceStateUserData>().unwrap();
+            assert!(data
+                .lock()
+                .unwrap()
+                .texture::<DummyRenderer>(renderer.id())
+                .is_some());
+        });
+    }
+}
+
+#[cfg(all(test, feature = "backend_drm", feature = "renderer_test"))]
+mod acquire_point_tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::pending_sync_point;
+    use crate::backend::renderer::{
+        sync::{Fence, Interrupted},
+        test::DummyRenderer,
+        Renderer,
+    };
+
+    #[derive(Debug, Clone, Default)]
+    struct TestFence {
+        signaled: Arc<AtomicBool>,
+        waits: Arc<AtomicUsize>,
+    }
+
+    impl Fence for TestFence {
+        fn is_signaled(&self) -> bool {
+            self.signaled.load(Ordering::SeqCst)
+        }
+
+        fn wait(&self) -> Result<(), Interrupted> {
+            self.waits.fetch_add(1, Ordering::SeqCst);
+            self.signaled.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn is_exportable(&self) -> bool {
+            false
+        }
+
+        fn export(&self) -> Option<std::os::unix::io::OwnedFd> {
+            None
+        }
+    }
+
+    #[test]
+    fn unsignaled_acquire_points_are_waited_for() {
+        let mut renderer = DummyRenderer::new();
+        let fence = TestFence::default();
+
+        let sync = pending_sync_point(Some(&fence)).expect("unsignaled acquire point");
+        assert!(!sync.is_reached());
+        renderer.wait(&sync).unwrap();
+        assert_eq!(fence.waits.load(Ordering::SeqCst), 1);
+        assert!(sync.is_reached());
+
+        // nothing to wait for once signaled
+        assert!(pending_sync_point(Some(&fence)).is_none());
+        assert!(pending_sync_point::<TestFence>(None).is_none());
+    }
+}
@@ -34,19 +34,52 @@
 //!
 //! Additionally this contains some utility functions related to scanning.
 //!
+//! ## Firmware framebuffers
+//!
+//! Early during boot the only available drm device might be provided by a firmware framebuffer
+//! driver like `simpledrm`, which drives the framebuffer set up by the firmware or bootloader.
+//! These devices support neither modesetting nor hardware acceleration. Once the real driver of
+//! the gpu is loaded the kernel removes the firmware device and adds a new device for the real
+//! driver, so a compositor started on the firmware device will receive a [`UdevEvent::Removed`]
+//! for it and a [`UdevEvent::Added`] for the new device.
+//!
+//! Use [`is_firmware_framebuffer`] to detect such devices. [`primary_gpu`] will only return a
+//! firmware framebuffer device if no other device is available.
+//!
+//! The [`UdevBackend`] detects the handover and sends a [`UdevEvent::FirmwareReplaced`] right after
+//! the [`UdevEvent::Added`] of the first real device added while a firmware framebuffer device is
+//! present or after one got removed. A compositor migrating from the firmware device to the new
+//! device should:
+//!
+//! - initialize the new device on [`UdevEvent::Added`] like any other device,
+//! - on [`UdevEvent::Removed`] for the firmware device drop its surfaces and the
+//!   [`DrmDevice`](crate::backend::drm::DrmDevice), its outputs are replaced by the outputs
+//!   of the matching connectors of the new device,
+//! - on [`UdevEvent::FirmwareReplaced`], if the firmware device was used as the primary gpu, make
+//!   the new device the primary gpu and recreate renderers and anything derived from them
+//!   (e.g. dmabuf globals and feedback).
+//!
+//! ## Persistent device identification
+//!
+//! Device nodes like `/dev/dri/card1` are numbered in probing order, which might change across reboots.
+//! To persist configurations per gpu use [`device_identifiers`] to query identifiers like the persistent
+//! path (`ID_PATH`) or the PCI ids of a device and [`devices_by_id_path`] or [`devices_by_pci_id`] to
+//! find the matching device nodes again. A [`DrmNode`](crate::backend::drm::DrmNode) can be created from
+//! the returned paths, the `dev_t` of a node is available via `DrmNode::dev_id`.
+//!
 //! See also `anvil/src/udev.rs` for pure hardware backed example of a compositor utilizing this
 //! backend.
 
 use libc::dev_t;
 use rustix::fs::stat;
 use std::{
-    collections::HashMap,
-    ffi::OsString,
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
     fmt, io,
     os::unix::io::{AsFd, BorrowedFd},
     path::{Path, PathBuf},
 };
-use udev::{Enumerator, EventType, MonitorBuilder, MonitorSocket};
+use udev::{Device, DeviceType, Enumerator, EventType, MonitorBuilder, MonitorSocket};
 
 use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
 
@@ -59,6 +92,7 @@ use tracing::{debug, debug_span, info, warn};
 /// attached monitors.
 pub struct UdevBackend {
     devices: HashMap<dev_t, PathBuf>,
+    firmware: FirmwareTracker,
     monitor: MonitorSocket,
     token: Option<Token>,
     span: tracing::Span,
@@ -70,6 +104,7 @@ impl fmt::Debug for UdevBackend {
         use udev::AsRaw;
         f.debug_struct("UdevBackend")
             .field("devices", &self.devices)
+            .field("firmware", &self.firmware)
             .field("monitor", &format!("MonitorSocket ({:?})", self.monitor.as_raw()))
             .finish()
     }
@@ -91,7 +126,7 @@ impl UdevBackend {
         let span = debug_span!("backend_udev", seat = seat.to_string());
         let _guard = span.enter();
 
-        let devices = all_gpus(seat)?
+        let devices: HashMap<dev_t, PathBuf> = all_gpus(seat)?
             .into_iter()
             // Create devices
             .flat_map(|path| match stat(&path) {
@@ -103,11 +138,17 @@ impl UdevBackend {
             })
             .collect();
 
+        let mut firmware = FirmwareTracker::default();
+        for &device_id in devices.keys() {
+            firmware.added(device_id, is_firmware_framebuffer(device_id).unwrap_or(false));
+        }
+
         let monitor = MonitorBuilder::new()?.match_subsystem("drm")?.listen()?;
 
         drop(_guard);
         Ok(UdevBackend {
             devices,
+            firmware,
             monitor,
             token: None,
             span,
@@ -164,6 +205,21 @@ impl EventSource for UdevBackend {
                                 },
                                 &mut (),
                             );
+
+                            let is_firmware = is_firmware_framebuffer(devnum).unwrap_or(false);
+                            if let Some(firmware_device_id) = self.firmware.added(devnum, is_firmware) {
+                                info!(
+                                    "Device #{} replaces firmware framebuffer #{}",
+                                    devnum, firmware_device_id
+                                );
+                                callback(
+                                    UdevEvent::FirmwareReplaced {
+                                        firmware_device_id,
+                                        device_id: devnum,
+                                    },
+                                    &mut (),
+                                );
+                            }
                         }
                     }
                 }
@@ -172,6 +228,7 @@ impl EventSource for UdevBackend {
                     if let Some(devnum) = event.devnum() {
                         info!("Device removed: #{}", devnum);
                         if self.devices.remove(&devnum).is_some() {
+                            self.firmware.removed(devnum);
                             callback(UdevEvent::Removed { device_id: devnum }, &mut ());
                         }
                     }
@@ -210,6 +267,7 @@ impl EventSource for UdevBackend {
 
 /// Events generated by the [`UdevBackend`], notifying you of changes in system devices
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum UdevEvent {
     /// A new device has been detected
     Added {
@@ -228,10 +286,58 @@ pub enum UdevEvent {
         /// ID of the removed device
         device_id: dev_t,
     },
+    /// A firmware framebuffer device was replaced by the device of the real driver
+    ///
+    /// Sent right after the [`UdevEvent::Added`] of the new device. The firmware device might
+    /// already be removed. See the [module documentation](self#firmware-framebuffers) for details.
+    FirmwareReplaced {
+        /// ID of the replaced firmware framebuffer device
+        firmware_device_id: dev_t,
+        /// ID of the new device
+        device_id: dev_t,
+    },
+}
+
+/// Tracks firmware framebuffer devices until they are replaced by a real device
+#[derive(Debug, Default)]
+struct FirmwareTracker {
+    /// Firmware framebuffer devices currently present
+    present: HashSet<dev_t>,
+    /// Firmware framebuffer device removed before any real device was added
+    removed: Option<dev_t>,
+    /// Whether a real device is present
+    has_gpu: bool,
+}
+
+impl FirmwareTracker {
+    /// Returns the firmware framebuffer device replaced by the added device, if any
+    fn added(&mut self, device_id: dev_t, is_firmware: bool) -> Option<dev_t> {
+        if is_firmware {
+            self.present.insert(device_id);
+            return None;
+        }
+
+        let replaced = if self.has_gpu {
+            None
+        } else {
+            self.removed.take().or_else(|| self.present.iter().min().copied())
+        };
+        self.has_gpu = true;
+        replaced
+    }
+
+    fn removed(&mut self, device_id: dev_t) {
+        if self.present.remove(&device_id) && !self.has_gpu {
+            self.removed = Some(device_id);
+        }
+    }
 }
 
 /// Returns the path of the primary GPU device if any
 ///
+/// Devices driven by a firmware framebuffer driver are only returned
+/// if no other device is available, see [`is_firmware_framebuffer`].
+///
 /// Might be used for filtering of [`UdevEvent::Added`] or for manual
 /// [`DrmDevice`](crate::backend::drm::DrmDevice) initialization.
 pub fn primary_gpu<S: AsRef<str>>(seat: S) -> io::Result<Option<PathBuf>> {
@@ -260,7 +366,20 @@ pub fn primary_gpu<S: AsRef<str>>(seat: S) -> io::Result<Option<PathBuf>> {
     {
         Ok(Some(path))
     } else {
-        all_gpus(seat).map(|all| all.into_iter().next())
+        let gpus = all_gpus(seat)?;
+        // Prefer any real gpu over a firmware framebuffer
+        let gpu = gpus
+            .iter()
+            .find(|path| {
+                stat(path.as_path())
+                    .ok()
+                    .and_then(|stat| is_firmware_framebuffer(stat.st_rdev).ok())
+                    .map(|is_firmware| !is_firmware)
+                    .unwrap_or(true)
+            })
+            .or_else(|| gpus.first())
+            .cloned();
+        Ok(gpu)
     }
 }
 
@@ -287,6 +406,46 @@ pub fn all_gpus<S: AsRef<str>>(seat: S) -> io::Result<Vec<PathBuf>> {
     Ok(gpus)
 }
 
+/// Returns the paths of the render nodes of all available GPUs without display hardware
+///
+/// These devices, e.g. the discrete GPU of some laptops, do not provide a primary node (`card*`)
+/// and are therefore neither reported by the [`UdevBackend`] nor by [`all_gpus`]. They can
+/// still be used for rendering by adding them to a
+/// [`GpuManager`](crate::backend::renderer::multigpu::GpuManager) and exporting the rendered
+/// frames to the display device, see [`GpuManager::shared_formats`](crate::backend::renderer::multigpu::GpuManager::shared_formats).
+pub fn render_only_gpus<S: AsRef<str>>(seat: S) -> io::Result<Vec<PathBuf>> {
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem("drm")?;
+    enumerator.match_sysname("card[0-9]*")?;
+    let display_devices = enumerator
+        .scan_devices()?
+        .filter_map(|device| device.parent().map(|parent| parent.syspath().to_path_buf()))
+        .collect::<Vec<_>>();
+
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem("drm")?;
+    enumerator.match_sysname("renderD[0-9]*")?;
+    let mut gpus = enumerator
+        .scan_devices()?
+        .filter(|device| {
+            device
+                .property_value("ID_SEAT")
+                .map(|x| x.to_os_string())
+                .unwrap_or_else(|| OsString::from("seat0"))
+                == *seat.as_ref()
+        })
+        .filter(|device| {
+            device
+                .parent()
+                .map(|parent| !display_devices.iter().any(|path| path == parent.syspath()))
+                .unwrap_or(true)
+        })
+        .flat_map(|device| device.devnode().map(PathBuf::from))
+        .collect::<Vec<_>>();
+    gpus.sort();
+    Ok(gpus)
+}
+
 /// Returns the loaded driver for a device named by it's [`dev_t`].
 pub fn driver(dev: dev_t) -> io::Result<Option<OsString>> {
     let mut enumerator = Enumerator::new()?;
@@ -307,3 +466,172 @@ pub fn driver(dev: dev_t) -> io::Result<Option<OsString>> {
         })
         .next())
 }
+
+/// Drivers only providing access to a framebuffer set up by the firmware
+const FIRMWARE_FRAMEBUFFER_DRIVERS: &[&str] =
+    &["simple-framebuffer", "simpledrm", "ofdrm", "efidrm", "vesadrm"];
+
+/// Returns if the device named by it's [`dev_t`] is driven by a firmware framebuffer driver like `simpledrm`.
+///
+/// Such a device will be replaced by a new device once the real driver of the gpu is loaded.
+/// See the [module documentation](self#firmware-framebuffers) for details.
+pub fn is_firmware_framebuffer(dev: dev_t) -> io::Result<bool> {
+    Ok(driver(dev)?
+        .map(|driver| FIRMWARE_FRAMEBUFFER_DRIVERS.iter().any(|name| driver == **name))
+        .unwrap_or(false))
+}
+
+/// PCI identifiers of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PciId {
+    /// Vendor id
+    pub vendor: u16,
+    /// Device id
+    pub device: u16,
+    /// Subsystem vendor id
+    pub subsystem_vendor: u16,
+    /// Subsystem device id
+    pub subsystem_device: u16,
+}
+
+impl PciId {
+    fn from_device(device: &Device) -> Option<PciId> {
+        let id = |name: &str| {
+            let value = device.attribute_value(name)?.to_str()?;
+            u16::from_str_radix(value.trim().trim_start_matches("0x"), 16).ok()
+        };
+
+        Some(PciId {
+            vendor: id("vendor")?,
+            device: id("device")?,
+            subsystem_vendor: id("subsystem_vendor")?,
+            subsystem_device: id("subsystem_device")?,
+        })
+    }
+}
+
+/// Identifiers of a drm device
+///
+/// See [`device_identifiers`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceIdentifiers {
+    /// Path of the device in sysfs, e.g. `/sys/devices/pci0000:00/0000:00:02.0/drm/card1`
+    pub syspath: PathBuf,
+    /// Path of the device node, e.g. `/dev/dri/card1`
+    pub devnode: Option<PathBuf>,
+    /// Persistent path of the device, e.g. `pci-0000:00:02.0`
+    ///
+    /// Provided by the `ID_PATH` property of udev, which is also used for the links in `/dev/dri/by-path`.
+    /// Stays stable across reboots as long as the hardware configuration doesn't change.
+    pub id_path: Option<OsString>,
+    /// PCI ids of the device, if it is a PCI device
+    ///
+    /// Multiple identical gpus share the same ids.
+    pub pci_id: Option<PciId>,
+}
+
+/// Returns the identifiers of the drm device named by it's [`dev_t`]
+///
+/// Returns `None` if the device is unknown to udev.
+pub fn device_identifiers(dev: dev_t) -> io::Result<Option<DeviceIdentifiers>> {
+    let device = match Device::from_devnum(DeviceType::Character, dev) {
+        Ok(device) => device,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let pci_id = device
+        .parent_with_subsystem(Path::new("pci"))?
+        .and_then(|pci| PciId::from_device(&pci));
+    Ok(Some(DeviceIdentifiers {
+        syspath: device.syspath().to_path_buf(),
+        devnode: device.devnode().map(PathBuf::from),
+        id_path: device.property_value("ID_PATH").map(OsStr::to_os_string),
+        pci_id,
+    }))
+}
+
+/// Returns the device node of the drm device at the given sysfs path
+///
+/// Returns `None` if the device has no device node.
+pub fn device_by_syspath(syspath: impl AsRef<Path>) -> io::Result<Option<PathBuf>> {
+    let device = Device::from_syspath(syspath.as_ref())?;
+    Ok(device.devnode().map(PathBuf::from))
+}
+
+/// Returns the device nodes of all drm devices with the given persistent path
+///
+/// This includes the primary node (`card*`) as well as the render node (`renderD*`) of the device,
+/// see [`DeviceIdentifiers::id_path`].
+pub fn devices_by_id_path(id_path: impl AsRef<OsStr>) -> io::Result<Vec<PathBuf>> {
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem("drm")?;
+    enumerator.match_property("ID_PATH", id_path)?;
+    let mut devices = enumerator
+        .scan_devices()?
+        .flat_map(|device| device.devnode().map(PathBuf::from))
+        .collect::<Vec<_>>();
+    devices.sort();
+    Ok(devices)
+}
+
+/// Returns the device nodes of all drm devices with the given PCI ids
+///
+/// This includes the primary nodes (`card*`) as well as the render nodes (`renderD*`) of the devices.
+pub fn devices_by_pci_id(pci_id: PciId) -> io::Result<Vec<PathBuf>> {
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem("drm")?;
+    let mut devices = enumerator
+        .scan_devices()?
+        .filter(|device| {
+            device
+                .parent_with_subsystem(Path::new("pci"))
+                .ok()
+                .flatten()
+                .and_then(|pci| PciId::from_device(&pci))
+                == Some(pci_id)
+        })
+        .flat_map(|device| device.devnode().map(PathBuf::from))
+        .collect::<Vec<_>>();
+    devices.sort();
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FirmwareTracker;
+
+    #[test]
+    fn firmware_removed_before_gpu_added() {
+        let mut tracker = FirmwareTracker::default();
+        assert_eq!(tracker.added(1, true), None);
+        tracker.removed(1);
+        assert_eq!(tracker.added(2, false), Some(1));
+        // only the first real device replaces the firmware device
+        assert_eq!(tracker.added(3, false), None);
+    }
+
+    #[test]
+    fn gpu_added_before_firmware_removed() {
+        let mut tracker = FirmwareTracker::default();
+        assert_eq!(tracker.added(1, true), None);
+        assert_eq!(tracker.added(2, false), Some(1));
+        tracker.removed(1);
+        assert_eq!(tracker.added(3, false), None);
+    }
+
+    #[test]
+    fn no_firmware_device() {
+        let mut tracker = FirmwareTracker::default();
+        assert_eq!(tracker.added(1, false), None);
+        tracker.removed(1);
+        assert_eq!(tracker.added(2, false), None);
+
+        // firmware devices showing up next to a real device are not replaced
+        let mut tracker = FirmwareTracker::default();
+        assert_eq!(tracker.added(1, false), None);
+        assert_eq!(tracker.added(2, true), None);
+        tracker.removed(2);
+        assert_eq!(tracker.added(3, false), None);
+    }
+}
@@ -7,9 +7,10 @@ use winit::{
 
 use crate::backend::input::{
     self, AbsolutePositionEvent, Axis, AxisRelativeDirection, AxisSource, ButtonState, Device,
-    DeviceCapability, Event, InputBackend, KeyState, KeyboardKeyEvent, Keycode, PointerAxisEvent,
-    PointerButtonEvent, PointerMotionAbsoluteEvent, TouchCancelEvent, TouchDownEvent, TouchEvent,
-    TouchMotionEvent, TouchSlot, TouchUpEvent, UnusedEvent,
+    DeviceCapability, Event, GestureBeginEvent, GestureEndEvent, GesturePinchBeginEvent,
+    GesturePinchEndEvent, GesturePinchUpdateEvent, InputBackend, KeyState, KeyboardKeyEvent, Keycode,
+    PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, TouchCancelEvent, TouchDownEvent,
+    TouchEvent, TouchMotionEvent, TouchSlot, TouchUpEvent, UnusedEvent,
 };
 
 /// Marker used to define the `InputBackend` types for the winit backend.
@@ -32,7 +33,10 @@ impl Device for WinitVirtualDevice {
     fn has_capability(&self, capability: DeviceCapability) -> bool {
         matches!(
             capability,
-            DeviceCapability::Keyboard | DeviceCapability::Pointer | DeviceCapability::Touch
+            DeviceCapability::Keyboard
+                | DeviceCapability::Pointer
+                | DeviceCapability::Touch
+                | DeviceCapability::Gesture
         )
     }
 
@@ -345,6 +349,94 @@ impl TouchEvent<WinitInput> for WinitTouchCancelledEvent {
     }
 }
 
+/// Winit-Backend internal event wrapping `winit`'s types into a [`GesturePinchBeginEvent`]
+///
+/// Trackpad pinch and rotation gestures are both reported as one pinch gesture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WinitGesturePinchBeginEvent {
+    pub(crate) time: u64,
+}
+
+impl Event<WinitInput> for WinitGesturePinchBeginEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GestureBeginEvent<WinitInput> for WinitGesturePinchBeginEvent {
+    fn fingers(&self) -> u32 {
+        // winit does not report the number of fingers, trackpad pinches use two
+        2
+    }
+}
+
+impl GesturePinchBeginEvent<WinitInput> for WinitGesturePinchBeginEvent {}
+
+/// Winit-Backend internal event wrapping `winit`'s types into a [`GesturePinchUpdateEvent`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WinitGesturePinchUpdateEvent {
+    pub(crate) time: u64,
+    pub(crate) scale: f64,
+    pub(crate) rotation: f64,
+}
+
+impl Event<WinitInput> for WinitGesturePinchUpdateEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GesturePinchUpdateEvent<WinitInput> for WinitGesturePinchUpdateEvent {
+    fn delta_x(&self) -> f64 {
+        0.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        0.0
+    }
+
+    fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    fn rotation(&self) -> f64 {
+        self.rotation
+    }
+}
+
+/// Winit-Backend internal event wrapping `winit`'s types into a [`GesturePinchEndEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WinitGesturePinchEndEvent {
+    pub(crate) time: u64,
+    pub(crate) cancelled: bool,
+}
+
+impl Event<WinitInput> for WinitGesturePinchEndEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GestureEndEvent<WinitInput> for WinitGesturePinchEndEvent {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl GesturePinchEndEvent<WinitInput> for WinitGesturePinchEndEvent {}
+
 impl From<ElementState> for KeyState {
     #[inline]
     fn from(state: ElementState) -> Self {
@@ -392,9 +484,9 @@ impl InputBackend for WinitInput {
     type GestureSwipeBeginEvent = UnusedEvent;
     type GestureSwipeUpdateEvent = UnusedEvent;
     type GestureSwipeEndEvent = UnusedEvent;
-    type GesturePinchBeginEvent = UnusedEvent;
-    type GesturePinchUpdateEvent = UnusedEvent;
-    type GesturePinchEndEvent = UnusedEvent;
+    type GesturePinchBeginEvent = WinitGesturePinchBeginEvent;
+    type GesturePinchUpdateEvent = WinitGesturePinchUpdateEvent;
+    type GesturePinchEndEvent = WinitGesturePinchEndEvent;
     type GestureHoldBeginEvent = UnusedEvent;
     type GestureHoldEndEvent = UnusedEvent;
 
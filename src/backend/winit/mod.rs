@@ -20,7 +20,10 @@
 
 use std::io::Error as IoError;
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 use std::time::Duration;
 
 use calloop::generic::Generic;
@@ -52,7 +55,7 @@ use crate::{
             Bind,
         },
     },
-    utils::{Clock, Monotonic, Physical, Rectangle, Size},
+    utils::{Clock, Monotonic, Physical, Rectangle, Size, Time},
 };
 
 mod input;
@@ -189,6 +192,7 @@ where
     drop(_guard);
 
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
+    let presentation_pending = Arc::new(AtomicBool::new(false));
     let event_loop = Generic::new(event_loop, Interest::READ, calloop::Mode::Level);
 
     Ok((
@@ -199,15 +203,18 @@ where
             egl_surface: egl,
             damage_tracking,
             bind_size: None,
+            presentation_pending: presentation_pending.clone(),
             renderer,
         },
         WinitEventLoop {
             inner: WinitEventLoopInner {
                 scale_factor: window.scale_factor(),
+                presentation_pending,
                 clock: Clock::<Monotonic>::new(),
                 key_counter: 0,
                 window,
                 is_x11,
+                pinch: None,
             },
             fake_token: None,
             event_loop,
@@ -250,6 +257,7 @@ pub struct WinitGraphicsBackend<R> {
     window: Arc<WinitWindow>,
     damage_tracking: bool,
     bind_size: Option<Size<i32, Physical>>,
+    presentation_pending: Arc<AtomicBool>,
     span: tracing::Span,
 }
 
@@ -353,6 +361,11 @@ where
         // Request frame callback.
         self.window.pre_present_notify();
         self.egl_surface.swap_buffers(damage.as_deref_mut())?;
+        // Winit delivers the redraw once the frame callback arrives, use it to signal presentation.
+        // A redraw is already requested, if the previous frame has not been presented yet.
+        if !self.presentation_pending.swap(true, Ordering::AcqRel) {
+            self.window.request_redraw();
+        }
         Ok(())
     }
 }
@@ -360,10 +373,20 @@ where
 #[derive(Debug)]
 struct WinitEventLoopInner {
     window: Arc<WinitWindow>,
+    presentation_pending: Arc<AtomicBool>,
     clock: Clock<Monotonic>,
     key_counter: u32,
     is_x11: bool,
     scale_factor: f64,
+    pinch: Option<PinchState>,
+}
+
+// Trackpad pinch and rotation gestures, which are forwarded as one pinch gesture
+#[derive(Debug)]
+struct PinchState {
+    scale: f64,
+    zooming: bool,
+    rotating: bool,
 }
 
 /// Abstracted event loop of a [`WinitWindow`].
@@ -420,6 +443,56 @@ impl<F: FnMut(WinitEvent)> WinitEventLoopApp<'_, F> {
     fn timestamp(&self) -> u64 {
         self.inner.clock.now().as_micros()
     }
+
+    fn pinch_gesture(&mut self, phase: TouchPhase, rotation: bool, scale_delta: f64, angle_delta: f64) {
+        let time = self.timestamp();
+        if phase == TouchPhase::Started && self.inner.pinch.is_none() {
+            self.inner.pinch = Some(PinchState {
+                scale: 1.0,
+                zooming: false,
+                rotating: false,
+            });
+            let event = InputEvent::GesturePinchBegin {
+                event: WinitGesturePinchBeginEvent { time },
+            };
+            (self.callback)(WinitEvent::Input(event));
+        }
+
+        let Some(pinch) = self.inner.pinch.as_mut() else {
+            return;
+        };
+        let active = if rotation {
+            &mut pinch.rotating
+        } else {
+            &mut pinch.zooming
+        };
+        match phase {
+            TouchPhase::Started => *active = true,
+            TouchPhase::Moved => {
+                pinch.scale *= 1.0 + scale_delta;
+                let event = InputEvent::GesturePinchUpdate {
+                    event: WinitGesturePinchUpdateEvent {
+                        time,
+                        scale: pinch.scale,
+                        rotation: angle_delta,
+                    },
+                };
+                (self.callback)(WinitEvent::Input(event));
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => *active = false,
+        }
+
+        if !pinch.zooming && !pinch.rotating {
+            self.inner.pinch = None;
+            let event = InputEvent::GesturePinchEnd {
+                event: WinitGesturePinchEndEvent {
+                    time,
+                    cancelled: phase == TouchPhase::Cancelled,
+                },
+            };
+            (self.callback)(WinitEvent::Input(event));
+        }
+    }
 }
 
 impl<F: FnMut(WinitEvent)> ApplicationHandler for WinitEventLoopApp<'_, F> {
@@ -453,6 +526,11 @@ impl<F: FnMut(WinitEvent)> ApplicationHandler for WinitEventLoopApp<'_, F> {
                 });
             }
             WindowEvent::RedrawRequested => {
+                if self.inner.presentation_pending.swap(false, Ordering::AcqRel) {
+                    (self.callback)(WinitEvent::Presented {
+                        time: self.inner.clock.now(),
+                    });
+                }
                 (self.callback)(WinitEvent::Redraw);
             }
             WindowEvent::CloseRequested => {
@@ -598,6 +676,13 @@ impl<F: FnMut(WinitEvent)> ApplicationHandler for WinitEventLoopApp<'_, F> {
                 };
                 (self.callback)(WinitEvent::Input(event));
             }
+            WindowEvent::PinchGesture { delta, phase, .. } => {
+                self.pinch_gesture(phase, false, delta, 0.0);
+            }
+            WindowEvent::RotationGesture { delta, phase, .. } => {
+                // winit reports counterclockwise rotation as positive, unlike libinput
+                self.pinch_gesture(phase, true, 0.0, -delta as f64);
+            }
             WindowEvent::DroppedFile(_)
             | WindowEvent::Destroyed
             | WindowEvent::CursorEntered { .. }
@@ -612,9 +697,7 @@ impl<F: FnMut(WinitEvent)> ApplicationHandler for WinitEventLoopApp<'_, F> {
             | WindowEvent::Occluded(_)
             | WindowEvent::DoubleTapGesture { .. }
             | WindowEvent::ThemeChanged(_)
-            | WindowEvent::PinchGesture { .. }
             | WindowEvent::TouchpadPressure { .. }
-            | WindowEvent::RotationGesture { .. }
             | WindowEvent::PanGesture { .. }
             | WindowEvent::ActivationTokenDone { .. } => (),
         }
@@ -689,6 +772,7 @@ impl EventSource for WinitEventLoop {
 
 /// Specific events generated by Winit
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum WinitEvent {
     /// The window has been resized
     Resized {
@@ -709,4 +793,16 @@ pub enum WinitEvent {
 
     /// A redraw was requested
     Redraw,
+
+    /// The last buffer submitted with [`WinitGraphicsBackend::submit`] was presented
+    ///
+    /// This is a best-effort estimate: On Wayland it is sent once the host compositor
+    /// signals that it is a good time to draw the next frame (using a frame callback), on X11
+    /// it is sent on the next iteration of the event loop. Neither implies the frame was presented
+    /// in sync with the vertical blank of the host. It is always followed by a
+    /// [`WinitEvent::Redraw`].
+    Presented {
+        /// Time the presentation was noticed
+        time: Time<Monotonic>,
+    },
 }
@@ -7,10 +7,66 @@ use crate::{
         DeviceCapability, InputBackend, KeyState, KeyboardKeyEvent, Keycode, PointerAxisEvent,
         PointerButtonEvent, PointerMotionAbsoluteEvent, UnusedEvent,
     },
-    utils::{Logical, Size},
+    utils::{Clock, Logical, Monotonic, Size},
 };
 use std::sync::Weak;
 
+/// Converts timestamps of the X server into the [`Monotonic`] clock
+///
+/// X servers report milliseconds in a 32 bit counter, which wraps around after ~49 days,
+/// with a base chosen by the server. The offset to the monotonic clock is estimated from
+/// the event with the lowest delivery latency seen so far.
+#[derive(Debug)]
+pub(crate) struct ServerTime {
+    clock: Clock<Monotonic>,
+    last: Option<u32>,
+    wraps: u64,
+    offset: Option<i64>,
+}
+
+impl ServerTime {
+    pub(crate) fn new() -> Self {
+        ServerTime {
+            clock: Clock::new(),
+            last: None,
+            wraps: 0,
+            offset: None,
+        }
+    }
+
+    /// Convert a server timestamp into microseconds of the monotonic clock
+    pub(crate) fn convert(&mut self, time: u32) -> u64 {
+        let now = self.clock.now().as_micros() as i64;
+        self.convert_at(time, now)
+    }
+
+    fn convert_at(&mut self, time: u32, now: i64) -> u64 {
+        let wraps = match self.last {
+            Some(last) if time < last && last - time > u32::MAX / 2 => {
+                self.wraps += 1;
+                self.last = Some(time);
+                self.wraps
+            }
+            // a late event from before the last wrap
+            Some(last) if time > last && time - last > u32::MAX / 2 => self.wraps.saturating_sub(1),
+            // slightly out of order
+            Some(last) if time < last => self.wraps,
+            _ => {
+                self.last = Some(time);
+                self.wraps
+            }
+        };
+
+        let server = (((wraps << 32) + time as u64) * 1000) as i64;
+        let offset = match self.offset {
+            Some(offset) => offset.min(now - server),
+            None => now - server,
+        };
+        self.offset = Some(offset);
+        (server + offset).max(0) as u64
+    }
+}
+
 /// Marker used to define the `InputBackend` types for the X11 backend.
 #[derive(Debug)]
 pub struct X11Input;
@@ -47,7 +103,8 @@ impl Device for X11VirtualDevice {
 /// X11-Backend internal event wrapping `X11`'s types into a [`KeyboardKeyEvent`].
 #[derive(Debug, Clone)]
 pub struct X11KeyboardInputEvent {
-    pub(crate) time: u32,
+    pub(crate) time: u64,
+    pub(crate) raw_time: u32,
     pub(crate) key: Keycode,
     pub(crate) count: u32,
     pub(crate) state: KeyState,
@@ -65,7 +122,11 @@ impl X11KeyboardInputEvent {
 
 impl input::Event<X11Input> for X11KeyboardInputEvent {
     fn time(&self) -> u64 {
-        self.time as u64 * 1000
+        self.time
+    }
+
+    fn raw_time(&self) -> u64 {
+        self.raw_time as u64 * 1000
     }
 
     fn device(&self) -> X11VirtualDevice {
@@ -90,7 +151,8 @@ impl KeyboardKeyEvent<X11Input> for X11KeyboardInputEvent {
 /// X11-Backend internal event wrapping `X11`'s types into a [`PointerAxisEvent`]
 #[derive(Debug, Clone)]
 pub struct X11MouseWheelEvent {
-    pub(crate) time: u32,
+    pub(crate) time: u64,
+    pub(crate) raw_time: u32,
     pub(crate) axis: Axis,
     pub(crate) amount: f64,
     pub(crate) window: Weak<WindowInner>,
@@ -107,7 +169,11 @@ impl X11MouseWheelEvent {
 
 impl input::Event<X11Input> for X11MouseWheelEvent {
     fn time(&self) -> u64 {
-        self.time as u64 * 1000
+        self.time
+    }
+
+    fn raw_time(&self) -> u64 {
+        self.raw_time as u64 * 1000
     }
 
     fn device(&self) -> X11VirtualDevice {
@@ -144,7 +210,8 @@ impl PointerAxisEvent<X11Input> for X11MouseWheelEvent {
 /// X11-Backend internal event wrapping `X11`'s types into a [`PointerButtonEvent`]
 #[derive(Debug, Clone)]
 pub struct X11MouseInputEvent {
-    pub(crate) time: u32,
+    pub(crate) time: u64,
+    pub(crate) raw_time: u32,
     pub(crate) raw: u32,
     pub(crate) state: ButtonState,
     pub(crate) window: Weak<WindowInner>,
@@ -161,7 +228,11 @@ impl X11MouseInputEvent {
 
 impl input::Event<X11Input> for X11MouseInputEvent {
     fn time(&self) -> u64 {
-        self.time as u64 * 1000
+        self.time
+    }
+
+    fn raw_time(&self) -> u64 {
+        self.raw_time as u64 * 1000
     }
 
     fn device(&self) -> X11VirtualDevice {
@@ -182,7 +253,8 @@ impl PointerButtonEvent<X11Input> for X11MouseInputEvent {
 /// X11-Backend internal event wrapping `X11`'s types into a [`PointerMotionAbsoluteEvent`]
 #[derive(Debug, Clone)]
 pub struct X11MouseMovedEvent {
-    pub(crate) time: u32,
+    pub(crate) time: u64,
+    pub(crate) raw_time: u32,
     pub(crate) x: f64,
     pub(crate) y: f64,
     pub(crate) size: Size<u16, Logical>,
@@ -200,7 +272,11 @@ impl X11MouseMovedEvent {
 
 impl input::Event<X11Input> for X11MouseMovedEvent {
     fn time(&self) -> u64 {
-        self.time as u64 * 1000
+        self.time
+    }
+
+    fn raw_time(&self) -> u64 {
+        self.raw_time as u64 * 1000
     }
 
     fn device(&self) -> X11VirtualDevice {
@@ -260,3 +336,24 @@ impl InputBackend for X11Input {
 
     type SpecialEvent = UnusedEvent;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ServerTime;
+
+    #[test]
+    fn server_time_wraps() {
+        let mut time = ServerTime::new();
+        assert_eq!(time.convert_at(1_000, 5_000_000), 5_000_000);
+        // delivered with less latency, the offset shrinks
+        assert_eq!(time.convert_at(1_500, 5_400_000), 5_400_000);
+        assert_eq!(time.convert_at(1_200, 5_500_000), 5_100_000);
+
+        let mut time = ServerTime::new();
+        time.convert_at(u32::MAX - 10, 0);
+        let after_wrap = time.convert_at(5, 16_000);
+        assert_eq!(after_wrap, 16_000);
+        // a late event from before the wrap
+        assert_eq!(time.convert_at(u32::MAX - 5, 20_000), 5_000);
+    }
+}
@@ -84,7 +84,7 @@ use crate::{
         egl::{native::X11DefaultDisplay, EGLDevice, EGLDisplay, Error as EGLError},
         input::{Axis, ButtonState, InputEvent, KeyState, Keycode},
     },
-    utils::{x11rb::X11Source, Logical, Size},
+    utils::{x11rb::X11Source, Logical, Monotonic, Size, Time},
 };
 use calloop::{EventSource, Poll, PostAction, Readiness, Token, TokenFactory};
 use drm::node::path_to_type;
@@ -104,6 +104,7 @@ use std::{
         atomic::{AtomicU32, Ordering},
         mpsc, Arc, Mutex, Weak,
     },
+    time::Duration,
 };
 use tracing::{debug_span, error, info, instrument, warn};
 use x11rb::{
@@ -166,9 +167,18 @@ pub enum X11Event {
     /// The last buffer presented to the window has been displayed.
     ///
     /// When this event is scheduled, the next frame may be rendered.
+    ///
+    /// The timing information can be used to send presentation feedback to clients.
     PresentCompleted {
         /// XID of the window
         window_id: u32,
+        /// Time the buffer was displayed, as reported by the X server
+        ///
+        /// The X server uses `CLOCK_MONOTONIC` for presentation timestamps, if it is
+        /// running on the same machine this is comparable to [`Clock<Monotonic>`](crate::utils::Clock).
+        time: Time<Monotonic>,
+        /// Media stream counter (the vblank sequence) at the time the buffer was displayed
+        msc: u64,
     },
 
     /// The window has received a request to be closed.
@@ -268,6 +278,7 @@ impl X11Backend {
             depth,
             visual_id,
             devices: false,
+            server_time: ServerTime::new(),
         };
 
         drop(_guard);
@@ -630,6 +641,7 @@ pub(crate) struct X11Inner {
     depth: x11::xproto::Depth,
     visual_id: u32,
     devices: bool,
+    server_time: ServerTime,
 }
 
 impl X11Inner {
@@ -639,6 +651,11 @@ impl X11Inner {
         inner.windows.get(id).cloned()
     }
 
+    // the guard must not outlive the conversion, the callback may access the backend again
+    fn convert_time(inner: &Arc<Mutex<X11Inner>>, time: u32) -> u64 {
+        inner.lock().unwrap().server_time.convert(time)
+    }
+
     #[profiling::function]
     fn process_event<F>(inner: &Arc<Mutex<X11Inner>>, event: x11::Event, callback: &mut F)
     where
@@ -722,7 +739,8 @@ impl X11Inner {
                             Input {
                                 event: InputEvent::PointerAxis {
                                     event: X11MouseWheelEvent {
-                                        time: button_press.time,
+                                        time: X11Inner::convert_time(inner, button_press.time),
+                                        raw_time: button_press.time,
                                         axis: match button_press.detail {
                                             // Up | Down
                                             4 | 5 => Axis::Vertical,
@@ -753,7 +771,8 @@ impl X11Inner {
                             Input {
                                 event: InputEvent::PointerButton {
                                     event: X11MouseInputEvent {
-                                        time: button_press.time,
+                                        time: X11Inner::convert_time(inner, button_press.time),
+                                        raw_time: button_press.time,
                                         raw: button_press.detail,
                                         state: ButtonState::Pressed,
                                         window,
@@ -780,7 +799,8 @@ impl X11Inner {
                         Input {
                             event: InputEvent::PointerButton {
                                 event: X11MouseInputEvent {
-                                    time: button_release.time,
+                                    time: X11Inner::convert_time(inner, button_release.time),
+                                    raw_time: button_release.time,
                                     raw: button_release.detail,
                                     state: ButtonState::Released,
                                     window,
@@ -806,7 +826,8 @@ impl X11Inner {
                         Input {
                             event: InputEvent::Keyboard {
                                 event: X11KeyboardInputEvent {
-                                    time: key_press.time,
+                                    time: X11Inner::convert_time(inner, key_press.time),
+                                    raw_time: key_press.time,
                                     key: Keycode::from(key_press.detail),
                                     count,
                                     state: KeyState::Pressed,
@@ -837,7 +858,8 @@ impl X11Inner {
                         Input {
                             event: InputEvent::Keyboard {
                                 event: X11KeyboardInputEvent {
-                                    time: key_release.time,
+                                    time: X11Inner::convert_time(inner, key_release.time),
+                                    raw_time: key_release.time,
                                     key: Keycode::from(key_release.detail),
                                     count,
                                     state: KeyState::Released,
@@ -865,7 +887,8 @@ impl X11Inner {
                         Input {
                             event: InputEvent::PointerMotionAbsolute {
                                 event: X11MouseMovedEvent {
-                                    time: motion_notify.time,
+                                    time: X11Inner::convert_time(inner, motion_notify.time),
+                                    raw_time: motion_notify.time,
                                     x,
                                     y,
                                     size: window_size,
@@ -966,6 +989,8 @@ impl X11Inner {
                     (callback)(
                         X11Event::PresentCompleted {
                             window_id: complete_notify.window,
+                            time: Time::from(Duration::from_micros(complete_notify.ust)),
+                            msc: complete_notify.msc,
                         },
                         &mut (),
                     );
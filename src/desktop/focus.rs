@@ -0,0 +1,563 @@
+//! Keyboard focus policies
+//!
+//! Most desktop compositors offer one or more of the classic focus models:
+//!
+//! - **Click to focus**: An element gains focus once it is clicked.
+//! - **Focus follows mouse**: The element under the pointer gains focus. Moving the pointer
+//!   onto an empty part of the [`Space`] removes the focus.
+//! - **Sloppy focus**: Like focus follows mouse, but moving the pointer onto an empty part of
+//!   the [`Space`] keeps the focus on the previous element.
+//!
+//! A [`FocusTracker`] implements these policies on top of the hit-testing of a [`Space`].
+//! It does not change the focus by itself, instead the compositor is called back with
+//! the element that should be focused, so it can e.g. raise the element and update the
+//! keyboard focus accordingly.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use smithay::desktop::{focus::{FocusPolicy, FocusTracker}, Space, Window};
+//! # use smithay::utils::{Clock, Monotonic};
+//! # let space: Space<Window> = Space::default();
+//! # let clock: Clock<Monotonic> = Clock::new();
+//! let mut tracker = FocusTracker::new(FocusPolicy::Sloppy {
+//!     delay: Duration::from_millis(100),
+//! });
+//!
+//! // on every pointer motion
+//! tracker.pointer_motion(&space, (100.0, 100.0), clock.now(), |window: Option<Window>| {
+//!     // set the keyboard focus to `window`
+//! });
+//!
+//! // whenever `tracker.deadline()` has passed, e.g. using a calloop timer
+//! tracker.poll(&space, clock.now(), |window: Option<Window>| {
+//!     // set the keyboard focus to `window`
+//! });
+//! ```
+//!
+//! Focus changes are only triggered when the pointer enters a different element, so
+//! changing the focus by other means (e.g. by keyboard shortcuts) is not undone by
+//! moving the pointer within the hovered element. Such focus changes should be reported
+//! to the tracker using [`FocusTracker::focus_changed`].
+//!
+//! ## Focus history
+//!
+//! A [`FocusHistory`] keeps the elements in most-recently-used order, e.g. one per workspace or output,
+//! to implement alt-tab style focus cycling and to pick the element to focus once the focused one is unmapped.
+//!
+//! ```no_run
+//! # use smithay::desktop::{focus::FocusHistory, Space, Window};
+//! # let space: Space<Window> = Space::default();
+//! # let window: Window = todo!();
+//! let mut history = FocusHistory::new();
+//!
+//! // whenever the keyboard focus changes
+//! history.focused(window);
+//!
+//! // alt-tab pressed, show `history.iter()` in a switcher and highlight the selection
+//! let selected = history.cycle_next();
+//! // alt released
+//! if let Some(window) = history.finish_cycle() {
+//!     // set the keyboard focus to `window`
+//! }
+//!
+//! // after unmapping elements
+//! history.refresh(&space);
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    backend::input::ButtonState,
+    desktop::{space::SpaceElement, Space},
+    utils::{IsAlive, Logical, Monotonic, Point, Time},
+};
+
+/// Policy deciding when an element gains focus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FocusPolicy {
+    /// Focus an element, when it is clicked
+    ClickToFocus,
+    /// Focus the element under the pointer
+    ///
+    /// Moving the pointer onto an empty part of the space removes the focus.
+    FollowsMouse {
+        /// Time the pointer has to rest on an element before it gets focused
+        delay: Duration,
+    },
+    /// Focus the element under the pointer
+    ///
+    /// Moving the pointer onto an empty part of the space keeps the current focus.
+    Sloppy {
+        /// Time the pointer has to rest on an element before it gets focused
+        delay: Duration,
+    },
+}
+
+impl FocusPolicy {
+    fn delay(&self) -> Option<Duration> {
+        match self {
+            FocusPolicy::ClickToFocus => None,
+            FocusPolicy::FollowsMouse { delay } | FocusPolicy::Sloppy { delay } => Some(*delay),
+        }
+    }
+}
+
+/// Tracks pointer input to implement a [`FocusPolicy`]
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct FocusTracker<E> {
+    policy: FocusPolicy,
+    focus: Option<E>,
+    hovered: Option<E>,
+    location: Point<f64, Logical>,
+    deadline: Option<Time<Monotonic>>,
+}
+
+impl<E: SpaceElement + PartialEq + Clone> FocusTracker<E> {
+    /// Create a new tracker using the given policy
+    pub fn new(policy: FocusPolicy) -> Self {
+        FocusTracker {
+            policy,
+            focus: None,
+            hovered: None,
+            location: Point::default(),
+            deadline: None,
+        }
+    }
+
+    /// Returns the policy of this tracker
+    pub fn policy(&self) -> FocusPolicy {
+        self.policy
+    }
+
+    /// Change the policy of this tracker
+    ///
+    /// Any pending delayed focus change is cancelled.
+    pub fn set_policy(&mut self, policy: FocusPolicy) {
+        self.policy = policy;
+        self.deadline = None;
+    }
+
+    /// Returns the element last focused through or reported to this tracker
+    pub fn focus(&self) -> Option<&E> {
+        self.focus.as_ref()
+    }
+
+    /// Report a focus change done by the compositor
+    ///
+    /// This should be called when the focus changes for other reasons than
+    /// this tracker, e.g. keyboard shortcuts or newly mapped elements.
+    pub fn focus_changed(&mut self, focus: Option<E>) {
+        self.focus = focus;
+    }
+
+    /// Returns the point in time when a delayed focus change becomes due
+    ///
+    /// [`FocusTracker::poll`] should be called once this time has passed.
+    pub fn deadline(&self) -> Option<Time<Monotonic>> {
+        self.deadline
+    }
+
+    /// Handle a motion of the pointer to `location`
+    ///
+    /// `focus` is called if the focus should change immediately.
+    pub fn pointer_motion(
+        &mut self,
+        space: &Space<E>,
+        location: impl Into<Point<f64, Logical>>,
+        now: Time<Monotonic>,
+        focus: impl FnOnce(Option<E>),
+    ) {
+        self.location = location.into();
+        let hovered = space.element_under(self.location).map(|(elem, _)| elem.clone());
+        if hovered == self.hovered {
+            return;
+        }
+        self.hovered = hovered;
+
+        let Some(delay) = self.policy.delay() else {
+            return;
+        };
+        if self.target().is_none() {
+            // the pointer moved back onto the focused element or onto empty space with sloppy focus
+            self.deadline = None;
+        } else {
+            self.deadline = Some(now + delay);
+            if delay.is_zero() {
+                self.poll(space, now, focus);
+            }
+        }
+    }
+
+    /// Handle a button event at the current pointer location
+    ///
+    /// Pressing a button immediately focuses the element under the pointer regardless of the policy.
+    /// Clicking onto an empty part of the space removes the focus, unless the policy is
+    /// [`FocusPolicy::Sloppy`].
+    pub fn button(&mut self, space: &Space<E>, state: ButtonState, focus: impl FnOnce(Option<E>)) {
+        if state != ButtonState::Pressed {
+            return;
+        }
+
+        self.deadline = None;
+        let clicked = space.element_under(self.location).map(|(elem, _)| elem.clone());
+        self.hovered.clone_from(&clicked);
+        if clicked.is_none() && matches!(self.policy, FocusPolicy::Sloppy { .. }) {
+            return;
+        }
+        if clicked != self.focus {
+            self.focus.clone_from(&clicked);
+            focus(clicked);
+        }
+    }
+
+    /// Apply a delayed focus change, if it is due
+    ///
+    /// The element under the pointer is checked again, so elements unmapped or moved
+    /// in the meantime do not gain focus.
+    pub fn poll(&mut self, space: &Space<E>, now: Time<Monotonic>, focus: impl FnOnce(Option<E>)) {
+        if !self.deadline.is_some_and(|deadline| deadline <= now) {
+            return;
+        }
+        self.deadline = None;
+
+        self.hovered = space.element_under(self.location).map(|(elem, _)| elem.clone());
+        if let Some(target) = self.target() {
+            self.focus.clone_from(&target);
+            focus(target);
+        }
+    }
+
+    // the focus the policy asks for, if it differs from the current one
+    fn target(&self) -> Option<Option<E>> {
+        let target = match self.policy {
+            FocusPolicy::ClickToFocus => return None,
+            FocusPolicy::FollowsMouse { .. } => self.hovered.clone(),
+            FocusPolicy::Sloppy { .. } => Some(self.hovered.clone()?),
+        };
+        (target != self.focus).then_some(target)
+    }
+}
+
+/// Elements in most-recently-used focus order
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct FocusHistory<E> {
+    elements: Vec<E>,
+    cycle: Option<usize>,
+}
+
+impl<E> Default for FocusHistory<E> {
+    fn default() -> Self {
+        FocusHistory {
+            elements: Vec::new(),
+            cycle: None,
+        }
+    }
+}
+
+impl<E: PartialEq> FocusHistory<E> {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report that `element` gained focus, moving it to the front of the history
+    ///
+    /// Any running cycle is cancelled.
+    pub fn focused(&mut self, element: E) {
+        self.cycle = None;
+        self.elements.retain(|e| *e != element);
+        self.elements.insert(0, element);
+    }
+
+    /// Remove an element from the history, e.g. when it was unmapped
+    ///
+    /// Returns the element to focus instead, if the removed element was focused.
+    pub fn remove(&mut self, element: &E) -> Option<&E> {
+        let index = self.elements.iter().position(|e| e == element)?;
+        self.elements.remove(index);
+        self.cycle = self.cycle.and_then(|cycle| match cycle.cmp(&index) {
+            std::cmp::Ordering::Less => Some(cycle),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(cycle - 1),
+        });
+        if index == 0 {
+            self.elements.first()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the most recently focused element
+    pub fn current(&self) -> Option<&E> {
+        self.elements.first()
+    }
+
+    /// Iterate over the elements, most recently focused first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &E> + ExactSizeIterator {
+        self.elements.iter()
+    }
+
+    /// Returns the number of elements in the history
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns `true` if the history is empty
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Select the next element in most-recently-used order, wrapping around at the end
+    ///
+    /// The first call starts a cycle at the previously focused element. The history is not reordered
+    /// until the cycle is finished with [`FocusHistory::finish_cycle`].
+    pub fn cycle_next(&mut self) -> Option<&E> {
+        if self.elements.is_empty() {
+            return None;
+        }
+        let len = self.elements.len();
+        let index = self.cycle.map_or(1 % len, |cycle| (cycle + 1) % len);
+        self.cycle = Some(index);
+        self.elements.get(index)
+    }
+
+    /// Select the previous element in most-recently-used order, wrapping around at the start
+    ///
+    /// The first call starts a cycle at the least recently focused element.
+    pub fn cycle_prev(&mut self) -> Option<&E> {
+        if self.elements.is_empty() {
+            return None;
+        }
+        let len = self.elements.len();
+        let index = self.cycle.map_or(len - 1, |cycle| (cycle + len - 1) % len);
+        self.cycle = Some(index);
+        self.elements.get(index)
+    }
+
+    /// Returns the element selected by the running cycle
+    pub fn cycle_selection(&self) -> Option<&E> {
+        self.elements.get(self.cycle?)
+    }
+
+    /// Finish the running cycle, moving the selected element to the front
+    ///
+    /// Returns the selected element, which should be focused.
+    pub fn finish_cycle(&mut self) -> Option<&E> {
+        let index = self.cycle.take()?;
+        let element = self.elements.remove(index);
+        self.elements.insert(0, element);
+        self.elements.first()
+    }
+
+    /// Cancel the running cycle without changing the order
+    pub fn cancel_cycle(&mut self) {
+        self.cycle = None;
+    }
+}
+
+impl<E: SpaceElement + PartialEq + IsAlive> FocusHistory<E> {
+    /// Remove elements, which died or are no longer mapped in `space`
+    ///
+    /// Returns the element to focus instead, if the focused element was removed.
+    pub fn refresh(&mut self, space: &Space<E>) -> Option<&E> {
+        let keep = |element: &E| element.alive() && space.elements().any(|e| e == element);
+        let current_removed = self.elements.first().is_some_and(|element| !keep(element));
+        self.elements.retain(keep);
+        if current_removed || self.cycle.is_some_and(|cycle| cycle >= self.elements.len()) {
+            self.cycle = None;
+        }
+        self.elements.first().filter(|_| current_removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FocusHistory, FocusPolicy, FocusTracker};
+    use crate::{
+        backend::input::ButtonState,
+        desktop::{space::SpaceElement, Space},
+        output::Output,
+        utils::{IsAlive, Logical, Monotonic, Point, Rectangle, Time},
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct TestElement(u32);
+
+    impl SpaceElement for TestElement {
+        fn bbox(&self) -> Rectangle<i32, Logical> {
+            Rectangle::from_size((100, 100).into())
+        }
+        fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
+            self.bbox().to_f64().contains(*point)
+        }
+        fn set_activate(&self, _activated: bool) {}
+        fn output_enter(&self, _output: &Output, _overlap: Rectangle<i32, Logical>) {}
+        fn output_leave(&self, _output: &Output) {}
+    }
+
+    impl IsAlive for TestElement {
+        fn alive(&self) -> bool {
+            true
+        }
+    }
+
+    // two elements side by side with a gap in between
+    fn space() -> Space<TestElement> {
+        let mut space = Space::default();
+        space.map_element(TestElement(1), (0, 0), false);
+        space.map_element(TestElement(2), (200, 0), false);
+        space
+    }
+
+    fn at(millis: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_millis(millis))
+    }
+
+    // returns the focus change requested by the tracker, if any
+    fn motion(
+        tracker: &mut FocusTracker<TestElement>,
+        space: &Space<TestElement>,
+        location: (f64, f64),
+        now: Time<Monotonic>,
+    ) -> Option<Option<u32>> {
+        let mut focus = None;
+        tracker.pointer_motion(space, location, now, |elem| focus = Some(elem.map(|e| e.0)));
+        focus
+    }
+
+    fn poll(
+        tracker: &mut FocusTracker<TestElement>,
+        space: &Space<TestElement>,
+        now: Time<Monotonic>,
+    ) -> Option<Option<u32>> {
+        let mut focus = None;
+        tracker.poll(space, now, |elem| focus = Some(elem.map(|e| e.0)));
+        focus
+    }
+
+    fn click(tracker: &mut FocusTracker<TestElement>, space: &Space<TestElement>) -> Option<Option<u32>> {
+        let mut focus = None;
+        tracker.button(space, ButtonState::Pressed, |elem| {
+            focus = Some(elem.map(|e| e.0))
+        });
+        focus
+    }
+
+    #[test]
+    fn click_to_focus() {
+        let space = space();
+        let mut tracker = FocusTracker::new(FocusPolicy::ClickToFocus);
+
+        assert_eq!(motion(&mut tracker, &space, (50.0, 50.0), at(0)), None);
+        assert_eq!(tracker.deadline(), None);
+        assert_eq!(click(&mut tracker, &space), Some(Some(1)));
+        // clicking the focused element again does not trigger a focus change
+        assert_eq!(click(&mut tracker, &space), None);
+
+        assert_eq!(motion(&mut tracker, &space, (250.0, 50.0), at(10)), None);
+        assert_eq!(click(&mut tracker, &space), Some(Some(2)));
+        // clicking onto empty space removes the focus
+        assert_eq!(motion(&mut tracker, &space, (150.0, 50.0), at(20)), None);
+        assert_eq!(click(&mut tracker, &space), Some(None));
+    }
+
+    #[test]
+    fn follows_mouse_with_delay() {
+        let space = space();
+        let mut tracker = FocusTracker::new(FocusPolicy::FollowsMouse {
+            delay: Duration::from_millis(100),
+        });
+
+        assert_eq!(motion(&mut tracker, &space, (50.0, 50.0), at(0)), None);
+        assert_eq!(tracker.deadline(), Some(at(100)));
+        assert_eq!(poll(&mut tracker, &space, at(50)), None);
+        assert_eq!(poll(&mut tracker, &space, at(100)), Some(Some(1)));
+        assert_eq!(tracker.focus(), Some(&TestElement(1)));
+
+        // leaving the element onto empty space removes the focus
+        assert_eq!(motion(&mut tracker, &space, (150.0, 50.0), at(200)), None);
+        assert_eq!(poll(&mut tracker, &space, at(300)), Some(None));
+
+        // moving back before the delay passed cancels the change
+        assert_eq!(motion(&mut tracker, &space, (250.0, 50.0), at(400)), None);
+        assert_eq!(motion(&mut tracker, &space, (150.0, 50.0), at(450)), None);
+        assert_eq!(tracker.deadline(), None);
+        assert_eq!(poll(&mut tracker, &space, at(500)), None);
+    }
+
+    #[test]
+    fn follows_mouse_without_delay() {
+        let space = space();
+        let mut tracker = FocusTracker::new(FocusPolicy::FollowsMouse {
+            delay: Duration::ZERO,
+        });
+
+        assert_eq!(motion(&mut tracker, &space, (50.0, 50.0), at(0)), Some(Some(1)));
+        // moving within the element does not refocus it
+        assert_eq!(motion(&mut tracker, &space, (60.0, 60.0), at(10)), None);
+        assert_eq!(motion(&mut tracker, &space, (250.0, 50.0), at(20)), Some(Some(2)));
+    }
+
+    #[test]
+    fn sloppy_keeps_focus_on_empty_space() {
+        let space = space();
+        let mut tracker = FocusTracker::new(FocusPolicy::Sloppy {
+            delay: Duration::ZERO,
+        });
+
+        assert_eq!(motion(&mut tracker, &space, (50.0, 50.0), at(0)), Some(Some(1)));
+        assert_eq!(motion(&mut tracker, &space, (150.0, 50.0), at(10)), None);
+        assert_eq!(tracker.deadline(), None);
+        assert_eq!(click(&mut tracker, &space), None);
+        assert_eq!(tracker.focus(), Some(&TestElement(1)));
+
+        // a focus change by the compositor is not undone by moving within the hovered element
+        assert_eq!(motion(&mut tracker, &space, (250.0, 50.0), at(20)), Some(Some(2)));
+        tracker.focus_changed(Some(TestElement(1)));
+        assert_eq!(motion(&mut tracker, &space, (260.0, 60.0), at(30)), None);
+        assert_eq!(tracker.focus(), Some(&TestElement(1)));
+    }
+
+    #[test]
+    fn delayed_focus_checks_unmapped_elements() {
+        let mut space = space();
+        let mut tracker = FocusTracker::new(FocusPolicy::Sloppy {
+            delay: Duration::from_millis(100),
+        });
+
+        assert_eq!(motion(&mut tracker, &space, (50.0, 50.0), at(0)), None);
+        space.unmap_elem(&TestElement(1));
+        assert_eq!(poll(&mut tracker, &space, at(100)), None);
+        assert_eq!(tracker.focus(), None);
+    }
+
+    #[test]
+    fn mru_cycling() {
+        let mut history = FocusHistory::new();
+        for element in [1, 2, 3] {
+            history.focused(element);
+        }
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), [3, 2, 1]);
+
+        assert_eq!(history.cycle_next(), Some(&2));
+        assert_eq!(history.cycle_next(), Some(&1));
+        assert_eq!(history.cycle_next(), Some(&3));
+        assert_eq!(history.cycle_prev(), Some(&1));
+        assert_eq!(history.finish_cycle(), Some(&1));
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), [1, 3, 2]);
+
+        assert_eq!(history.cycle_prev(), Some(&2));
+        history.cancel_cycle();
+        assert_eq!(history.cycle_selection(), None);
+
+        assert_eq!(history.remove(&3), None);
+        assert_eq!(history.remove(&1), Some(&2));
+        assert_eq!(history.current(), Some(&2));
+    }
+}
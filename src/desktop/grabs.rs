@@ -0,0 +1,985 @@
+//! Interactive move and resize grabs
+//!
+//! Most shells allow the user to move and resize windows with the pointer, either initiated
+//! by the client (e.g. `xdg_toplevel.move`) or by the compositor (e.g. a modifier and a button).
+//! [`MoveGrab`] and [`ResizeGrab`] implement these operations as [`PointerGrab`]s for
+//! elements mapped in a [`Space`].
+//!
+//! Both grabs
+//!
+//! - snap the element to the edges of outputs and other elements, see [`GrabConfig`],
+//! - report the geometry the element will end up with through [`MoveResizeHandler::preview_geometry`],
+//!   which can be used to render an outline,
+//! - optionally only apply the new geometry, once the grab ended (see [`GrabConfig::outline`]).
+//!
+//! The [`ResizeGrab`] additionally honors the minimum and maximum size of the element and
+//! supports keyboard modifiers to keep the aspect ratio or to resize symmetrically around the center,
+//! see [`MoveResizeHandler::grab_modifiers`].
+//!
+//! Geometries handled by this module always refer to the [`SpaceElement::geometry`] of the element
+//! in space coordinates as returned by [`Space::element_geometry`].
+//!
+//! ```no_run
+//! # use smithay::desktop::{grabs::{GrabConfig, MoveGrab, MoveResizeHandler, ResizeEdge}, Space, Window};
+//! # use smithay::input::{pointer::{GrabStartData, Focus}, Seat, SeatHandler, SeatState};
+//! # use smithay::utils::{Logical, Rectangle, Serial};
+//! # struct State { space: Space<Window>, seat_state: SeatState<State> }
+//! # impl SeatHandler for State {
+//! #     type KeyboardFocus = smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+//! #     type PointerFocus = smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+//! #     type TouchFocus = smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+//! #     fn seat_state(&mut self) -> &mut SeatState<Self> { &mut self.seat_state }
+//! # }
+//! impl MoveResizeHandler for State {
+//!     type Element = Window;
+//!
+//!     fn space(&self) -> &Space<Window> {
+//!         &self.space
+//!     }
+//!
+//!     fn space_mut(&mut self) -> &mut Space<Window> {
+//!         &mut self.space
+//!     }
+//!
+//!     fn resize_element(
+//!         &mut self,
+//!         window: &Window,
+//!         geometry: Rectangle<i32, Logical>,
+//!         edges: ResizeEdge,
+//!         finished: bool,
+//!     ) {
+//!         // send a configure with `geometry.size` to the window and
+//!         // reposition it on commit, if `edges` contains the top or left edge
+//!     }
+//! }
+//!
+//! # let state: State = todo!();
+//! # let seat: Seat<State> = todo!();
+//! # let window: Window = todo!();
+//! # let start_data: GrabStartData<State> = todo!();
+//! # let serial: Serial = todo!();
+//! # let mut state = state;
+//! // e.g. in `XdgShellHandler::move_request`
+//! if let Some(grab) = MoveGrab::new(&state, start_data, window, seat.get_keyboard(), GrabConfig::default()) {
+//!     let pointer = seat.get_pointer().unwrap();
+//!     pointer.set_grab(&mut state, grab, serial, Focus::Clear);
+//! }
+//! ```
+
+use std::fmt;
+
+#[cfg(feature = "xwayland")]
+use crate::xwayland::xwm::ResizeEdge as X11ResizeEdge;
+use crate::{
+    input::{
+        keyboard::{KeyboardHandle, ModifiersState},
+        pointer::{
+            AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
+            GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
+            GestureSwipeUpdateEvent, GrabStartData as PointerGrabStartData, MotionEvent, PointerGrab,
+            PointerInnerHandle, RelativeMotionEvent,
+        },
+        SeatHandler,
+    },
+    utils::{IsAlive, Logical, Point, Rectangle, Size},
+};
+#[cfg(feature = "wayland_frontend")]
+use wayland_protocols::xdg::shell::server::xdg_toplevel;
+
+use super::{space::SpaceElement, Space};
+
+bitflags::bitflags! {
+    /// Edges of an element affected by a resize operation
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct ResizeEdge: u32 {
+        /// No edge
+        const NONE = 0;
+        /// Top edge
+        const TOP = 1;
+        /// Bottom edge
+        const BOTTOM = 2;
+        /// Left edge
+        const LEFT = 4;
+        /// Top-left corner
+        const TOP_LEFT = 5;
+        /// Bottom-left corner
+        const BOTTOM_LEFT = 6;
+        /// Right edge
+        const RIGHT = 8;
+        /// Top-right corner
+        const TOP_RIGHT = 9;
+        /// Bottom-right corner
+        const BOTTOM_RIGHT = 10;
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl From<xdg_toplevel::ResizeEdge> for ResizeEdge {
+    #[inline]
+    fn from(x: xdg_toplevel::ResizeEdge) -> Self {
+        Self::from_bits_truncate(x as u32)
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl From<ResizeEdge> for xdg_toplevel::ResizeEdge {
+    #[inline]
+    fn from(x: ResizeEdge) -> Self {
+        Self::try_from(x.bits()).unwrap_or(xdg_toplevel::ResizeEdge::None)
+    }
+}
+
+#[cfg(feature = "xwayland")]
+impl From<X11ResizeEdge> for ResizeEdge {
+    #[inline]
+    fn from(edge: X11ResizeEdge) -> Self {
+        match edge {
+            X11ResizeEdge::Bottom => ResizeEdge::BOTTOM,
+            X11ResizeEdge::BottomLeft => ResizeEdge::BOTTOM_LEFT,
+            X11ResizeEdge::BottomRight => ResizeEdge::BOTTOM_RIGHT,
+            X11ResizeEdge::Left => ResizeEdge::LEFT,
+            X11ResizeEdge::Right => ResizeEdge::RIGHT,
+            X11ResizeEdge::Top => ResizeEdge::TOP,
+            X11ResizeEdge::TopLeft => ResizeEdge::TOP_LEFT,
+            X11ResizeEdge::TopRight => ResizeEdge::TOP_RIGHT,
+        }
+    }
+}
+
+/// Configuration of a [`MoveGrab`] or [`ResizeGrab`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GrabConfig {
+    /// Maximum distance in logical pixels an edge is snapped over, `0` disables snapping
+    pub snap_distance: i32,
+    /// Snap to the edges of outputs
+    pub snap_to_outputs: bool,
+    /// Snap to the edges of other elements in the space
+    pub snap_to_elements: bool,
+    /// Keep the top edge of a moved element below the top edge of the output under the pointer
+    ///
+    /// This prevents moving title bars out of reach.
+    pub keep_on_output: bool,
+    /// Only report the new geometry through [`MoveResizeHandler::preview_geometry`]
+    /// and apply it once the grab ended
+    pub outline: bool,
+}
+
+impl Default for GrabConfig {
+    #[inline]
+    fn default() -> Self {
+        GrabConfig {
+            snap_distance: 16,
+            snap_to_outputs: true,
+            snap_to_elements: true,
+            keep_on_output: true,
+            outline: false,
+        }
+    }
+}
+
+/// Behavior of a grab modified by the keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GrabModifiers {
+    /// Keep the aspect ratio of the element while resizing
+    pub keep_aspect_ratio: bool,
+    /// Resize symmetrically around the center of the element
+    pub centered: bool,
+    /// Snap to outputs and elements as configured by the [`GrabConfig`]
+    pub snap: bool,
+}
+
+impl Default for GrabModifiers {
+    #[inline]
+    fn default() -> Self {
+        GrabModifiers {
+            keep_aspect_ratio: false,
+            centered: false,
+            snap: true,
+        }
+    }
+}
+
+/// Handler trait for [`MoveGrab`] and [`ResizeGrab`]
+pub trait MoveResizeHandler: SeatHandler + Sized + 'static {
+    /// Type of the elements moved and resized
+    type Element: SpaceElement + PartialEq + Clone + fmt::Debug + Send + 'static;
+
+    /// Returns the space the elements are mapped in
+    fn space(&self) -> &Space<Self::Element>;
+
+    /// Returns the space the elements are mapped in
+    fn space_mut(&mut self) -> &mut Space<Self::Element>;
+
+    /// Move an element to a new location
+    ///
+    /// `location` is the location to map the element at, not the location of its geometry.
+    /// The default implementation maps the element at the new location without activating it.
+    fn move_element(&mut self, element: &Self::Element, location: Point<i32, Logical>) {
+        self.space_mut().map_element(element.clone(), location, false);
+    }
+
+    /// Resize an element to a new geometry
+    ///
+    /// The element should be resized to `geometry.size`. If `edges` contains the top or left edge,
+    /// the element also has to be moved to keep the opposite edges in place, usually once the element
+    /// committed a buffer of the new size. `finished` is set for the final call once the grab ended.
+    fn resize_element(
+        &mut self,
+        element: &Self::Element,
+        geometry: Rectangle<i32, Logical>,
+        edges: ResizeEdge,
+        finished: bool,
+    );
+
+    /// Returns the minimum and maximum size of an element
+    ///
+    /// A value of `0` means the dimension is unconstrained. The default implementation
+    /// does not constrain the size.
+    fn size_constraints(&self, element: &Self::Element) -> (Size<i32, Logical>, Size<i32, Logical>) {
+        let _ = element;
+        (Size::default(), Size::default())
+    }
+
+    /// The geometry an element will end up with changed
+    ///
+    /// `None` is reported once the grab ended.
+    fn preview_geometry(&mut self, element: &Self::Element, geometry: Option<Rectangle<i32, Logical>>) {
+        let _ = (element, geometry);
+    }
+
+    /// Map the current keyboard modifiers to the behavior of the grab
+    ///
+    /// By default holding shift keeps the aspect ratio and holding ctrl resizes around the center.
+    fn grab_modifiers(&self, modifiers: &ModifiersState) -> GrabModifiers {
+        GrabModifiers {
+            keep_aspect_ratio: modifiers.shift,
+            centered: modifiers.ctrl,
+            snap: true,
+        }
+    }
+}
+
+/// Pointer grab moving an element
+///
+/// The grab ends once all buttons are released.
+pub struct MoveGrab<D: MoveResizeHandler> {
+    start_data: PointerGrabStartData<D>,
+    element: D::Element,
+    initial_geometry: Rectangle<i32, Logical>,
+    last_geometry: Rectangle<i32, Logical>,
+    keyboard: Option<KeyboardHandle<D>>,
+    config: GrabConfig,
+}
+
+impl<D: MoveResizeHandler> fmt::Debug for MoveGrab<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MoveGrab")
+            .field("start_data", &self.start_data)
+            .field("element", &self.element)
+            .field("initial_geometry", &self.initial_geometry)
+            .field("last_geometry", &self.last_geometry)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<D: MoveResizeHandler> MoveGrab<D> {
+    /// Create a new grab moving `element`
+    ///
+    /// The `keyboard` is used to query the modifiers, see [`MoveResizeHandler::grab_modifiers`].
+    /// Returns `None` if the element is not mapped.
+    pub fn new(
+        data: &D,
+        start_data: PointerGrabStartData<D>,
+        element: D::Element,
+        keyboard: Option<KeyboardHandle<D>>,
+        config: GrabConfig,
+    ) -> Option<Self> {
+        let initial_geometry = data.space().element_geometry(&element)?;
+        Some(MoveGrab {
+            start_data,
+            element,
+            initial_geometry,
+            last_geometry: initial_geometry,
+            keyboard,
+            config,
+        })
+    }
+
+    /// Returns the element moved by this grab
+    pub fn element(&self) -> &D::Element {
+        &self.element
+    }
+
+    /// Returns the geometry the element ends up with
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.last_geometry
+    }
+
+    fn apply(&self, data: &mut D) {
+        let location = self.last_geometry.loc - self.element.geometry().loc;
+        data.move_element(&self.element, location);
+    }
+}
+
+impl<D: MoveResizeHandler> PointerGrab<D> for MoveGrab<D> {
+    fn motion(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        _focus: Option<(<D as SeatHandler>::PointerFocus, Point<f64, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // While the grab is active, no client has pointer focus
+        handle.motion(data, None, event);
+
+        if !self.element.alive() {
+            handle.unset_grab(self, data, event.serial, event.time, true);
+            return;
+        }
+
+        let modifiers = modifiers(data, self.keyboard.as_ref());
+        let delta = (event.location - self.start_data.location).to_i32_round();
+        let mut geometry = Rectangle::new(self.initial_geometry.loc + delta, self.initial_geometry.size);
+
+        if modifiers.snap {
+            let targets = snap_targets(data.space(), &self.element, &self.config);
+            geometry = snap_move(geometry, &targets, self.config.snap_distance);
+        }
+        if self.config.keep_on_output {
+            if let Some(output_geo) = data
+                .space()
+                .output_under(event.location)
+                .next()
+                .and_then(|output| data.space().output_geometry(output))
+            {
+                geometry.loc.y = geometry.loc.y.max(output_geo.loc.y);
+            }
+        }
+
+        if geometry != self.last_geometry {
+            self.last_geometry = geometry;
+            data.preview_geometry(&self.element, Some(geometry));
+            if !self.config.outline {
+                self.apply(data);
+            }
+        }
+    }
+
+    fn relative_motion(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        focus: Option<(<D as SeatHandler>::PointerFocus, Point<f64, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(data, focus, event);
+    }
+
+    fn button(&mut self, data: &mut D, handle: &mut PointerInnerHandle<'_, D>, event: &ButtonEvent) {
+        handle.button(data, event);
+        if handle.current_pressed().is_empty() {
+            // No more buttons are pressed, release the grab.
+            handle.unset_grab(self, data, event.serial, event.time, true);
+        }
+    }
+
+    fn axis(&mut self, data: &mut D, handle: &mut PointerInnerHandle<'_, D>, details: AxisFrame) {
+        handle.axis(data, details);
+    }
+
+    fn frame(&mut self, data: &mut D, handle: &mut PointerInnerHandle<'_, D>) {
+        handle.frame(data);
+    }
+
+    fn gesture_swipe_begin(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureSwipeBeginEvent,
+    ) {
+        handle.gesture_swipe_begin(data, event);
+    }
+
+    fn gesture_swipe_update(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureSwipeUpdateEvent,
+    ) {
+        handle.gesture_swipe_update(data, event);
+    }
+
+    fn gesture_swipe_end(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureSwipeEndEvent,
+    ) {
+        handle.gesture_swipe_end(data, event);
+    }
+
+    fn gesture_pinch_begin(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GesturePinchBeginEvent,
+    ) {
+        handle.gesture_pinch_begin(data, event);
+    }
+
+    fn gesture_pinch_update(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GesturePinchUpdateEvent,
+    ) {
+        handle.gesture_pinch_update(data, event);
+    }
+
+    fn gesture_pinch_end(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GesturePinchEndEvent,
+    ) {
+        handle.gesture_pinch_end(data, event);
+    }
+
+    fn gesture_hold_begin(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureHoldBeginEvent,
+    ) {
+        handle.gesture_hold_begin(data, event);
+    }
+
+    fn gesture_hold_end(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureHoldEndEvent,
+    ) {
+        handle.gesture_hold_end(data, event);
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<D> {
+        &self.start_data
+    }
+
+    fn unset(&mut self, data: &mut D) {
+        data.preview_geometry(&self.element, None);
+        if self.config.outline && self.element.alive() && self.last_geometry != self.initial_geometry {
+            self.apply(data);
+        }
+    }
+}
+
+/// Pointer grab resizing an element
+///
+/// The grab ends once all buttons are released.
+pub struct ResizeGrab<D: MoveResizeHandler> {
+    start_data: PointerGrabStartData<D>,
+    element: D::Element,
+    edges: ResizeEdge,
+    initial_geometry: Rectangle<i32, Logical>,
+    last_geometry: Rectangle<i32, Logical>,
+    keyboard: Option<KeyboardHandle<D>>,
+    config: GrabConfig,
+}
+
+impl<D: MoveResizeHandler> fmt::Debug for ResizeGrab<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResizeGrab")
+            .field("start_data", &self.start_data)
+            .field("element", &self.element)
+            .field("edges", &self.edges)
+            .field("initial_geometry", &self.initial_geometry)
+            .field("last_geometry", &self.last_geometry)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<D: MoveResizeHandler> ResizeGrab<D> {
+    /// Create a new grab resizing `element` using the given `edges`
+    ///
+    /// The `keyboard` is used to query the modifiers, see [`MoveResizeHandler::grab_modifiers`].
+    /// Returns `None` if the element is not mapped.
+    pub fn new(
+        data: &D,
+        start_data: PointerGrabStartData<D>,
+        element: D::Element,
+        edges: ResizeEdge,
+        keyboard: Option<KeyboardHandle<D>>,
+        config: GrabConfig,
+    ) -> Option<Self> {
+        let initial_geometry = data.space().element_geometry(&element)?;
+        Some(ResizeGrab {
+            start_data,
+            element,
+            edges,
+            initial_geometry,
+            last_geometry: initial_geometry,
+            keyboard,
+            config,
+        })
+    }
+
+    /// Returns the element resized by this grab
+    pub fn element(&self) -> &D::Element {
+        &self.element
+    }
+
+    /// Returns the edges used to resize the element
+    pub fn edges(&self) -> ResizeEdge {
+        self.edges
+    }
+
+    /// Returns the geometry the element ends up with
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.last_geometry
+    }
+}
+
+impl<D: MoveResizeHandler> PointerGrab<D> for ResizeGrab<D> {
+    fn motion(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        _focus: Option<(<D as SeatHandler>::PointerFocus, Point<f64, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // While the grab is active, no client has pointer focus
+        handle.motion(data, None, event);
+
+        // It is impossible to get the size constraints of a dead element, so we return early.
+        if !self.element.alive() {
+            handle.unset_grab(self, data, event.serial, event.time, true);
+            return;
+        }
+
+        let modifiers = modifiers(data, self.keyboard.as_ref());
+        let targets = if modifiers.snap {
+            snap_targets(data.space(), &self.element, &self.config)
+        } else {
+            Vec::new()
+        };
+        let (min_size, max_size) = data.size_constraints(&self.element);
+        let delta = (event.location - self.start_data.location).to_i32_round();
+        let geometry = resize_geometry(
+            self.initial_geometry,
+            self.edges,
+            delta,
+            (min_size, max_size),
+            modifiers,
+            &targets,
+            self.config.snap_distance,
+        );
+
+        if geometry != self.last_geometry {
+            self.last_geometry = geometry;
+            data.preview_geometry(&self.element, Some(geometry));
+            if !self.config.outline {
+                data.resize_element(&self.element, geometry, self.edges, false);
+            }
+        }
+    }
+
+    fn relative_motion(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        focus: Option<(<D as SeatHandler>::PointerFocus, Point<f64, Logical>)>,
+        event: &RelativeMotionEvent,
+    ) {
+        handle.relative_motion(data, focus, event);
+    }
+
+    fn button(&mut self, data: &mut D, handle: &mut PointerInnerHandle<'_, D>, event: &ButtonEvent) {
+        handle.button(data, event);
+        if handle.current_pressed().is_empty() {
+            // No more buttons are pressed, release the grab.
+            handle.unset_grab(self, data, event.serial, event.time, true);
+        }
+    }
+
+    fn axis(&mut self, data: &mut D, handle: &mut PointerInnerHandle<'_, D>, details: AxisFrame) {
+        handle.axis(data, details);
+    }
+
+    fn frame(&mut self, data: &mut D, handle: &mut PointerInnerHandle<'_, D>) {
+        handle.frame(data);
+    }
+
+    fn gesture_swipe_begin(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureSwipeBeginEvent,
+    ) {
+        handle.gesture_swipe_begin(data, event);
+    }
+
+    fn gesture_swipe_update(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureSwipeUpdateEvent,
+    ) {
+        handle.gesture_swipe_update(data, event);
+    }
+
+    fn gesture_swipe_end(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureSwipeEndEvent,
+    ) {
+        handle.gesture_swipe_end(data, event);
+    }
+
+    fn gesture_pinch_begin(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GesturePinchBeginEvent,
+    ) {
+        handle.gesture_pinch_begin(data, event);
+    }
+
+    fn gesture_pinch_update(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GesturePinchUpdateEvent,
+    ) {
+        handle.gesture_pinch_update(data, event);
+    }
+
+    fn gesture_pinch_end(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GesturePinchEndEvent,
+    ) {
+        handle.gesture_pinch_end(data, event);
+    }
+
+    fn gesture_hold_begin(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureHoldBeginEvent,
+    ) {
+        handle.gesture_hold_begin(data, event);
+    }
+
+    fn gesture_hold_end(
+        &mut self,
+        data: &mut D,
+        handle: &mut PointerInnerHandle<'_, D>,
+        event: &GestureHoldEndEvent,
+    ) {
+        handle.gesture_hold_end(data, event);
+    }
+
+    fn start_data(&self) -> &PointerGrabStartData<D> {
+        &self.start_data
+    }
+
+    fn unset(&mut self, data: &mut D) {
+        data.preview_geometry(&self.element, None);
+        if self.element.alive() {
+            data.resize_element(&self.element, self.last_geometry, self.edges, true);
+        }
+    }
+}
+
+fn modifiers<D: MoveResizeHandler>(data: &D, keyboard: Option<&KeyboardHandle<D>>) -> GrabModifiers {
+    keyboard
+        .map(|keyboard| data.grab_modifiers(&keyboard.modifier_state()))
+        .unwrap_or_default()
+}
+
+fn snap_targets<E: SpaceElement + PartialEq>(
+    space: &Space<E>,
+    element: &E,
+    config: &GrabConfig,
+) -> Vec<Rectangle<i32, Logical>> {
+    if config.snap_distance <= 0 {
+        return Vec::new();
+    }
+
+    let outputs = space
+        .outputs()
+        .filter(|_| config.snap_to_outputs)
+        .filter_map(|output| space.output_geometry(output));
+    let elements = space
+        .elements()
+        .filter(|_| config.snap_to_elements)
+        .filter(|other| *other != element)
+        .filter_map(|other| space.element_geometry(other));
+    outputs.chain(elements).collect()
+}
+
+// Returns the offset moving any of `edges` onto the closest edge of the `targets`.
+//
+// Only targets overlapping `span` (extended by `distance`) on the other axis are considered.
+fn snap_offset(
+    edges: &[i32],
+    span: (i32, i32),
+    targets: impl Iterator<Item = ((i32, i32), (i32, i32))>,
+    distance: i32,
+) -> i32 {
+    let mut best: Option<i32> = None;
+    for ((start, end), (other_start, other_end)) in targets {
+        if other_end < span.0 - distance || other_start > span.1 + distance {
+            continue;
+        }
+        for edge in edges {
+            for target in [start, end] {
+                let offset = target - edge;
+                if offset.abs() <= distance && !best.is_some_and(|best| best.abs() <= offset.abs()) {
+                    best = Some(offset);
+                }
+            }
+        }
+    }
+    best.unwrap_or(0)
+}
+
+fn horizontal_targets(
+    targets: &[Rectangle<i32, Logical>],
+) -> impl Iterator<Item = ((i32, i32), (i32, i32))> + '_ {
+    targets.iter().map(|rect| {
+        (
+            (rect.loc.x, rect.loc.x + rect.size.w),
+            (rect.loc.y, rect.loc.y + rect.size.h),
+        )
+    })
+}
+
+fn vertical_targets(
+    targets: &[Rectangle<i32, Logical>],
+) -> impl Iterator<Item = ((i32, i32), (i32, i32))> + '_ {
+    targets.iter().map(|rect| {
+        (
+            (rect.loc.y, rect.loc.y + rect.size.h),
+            (rect.loc.x, rect.loc.x + rect.size.w),
+        )
+    })
+}
+
+fn snap_move(
+    mut geometry: Rectangle<i32, Logical>,
+    targets: &[Rectangle<i32, Logical>],
+    distance: i32,
+) -> Rectangle<i32, Logical> {
+    let (x, y) = (geometry.loc.x, geometry.loc.y);
+    let (right, bottom) = (x + geometry.size.w, y + geometry.size.h);
+    geometry.loc.x += snap_offset(&[x, right], (y, bottom), horizontal_targets(targets), distance);
+    geometry.loc.y += snap_offset(&[y, bottom], (x, right), vertical_targets(targets), distance);
+    geometry
+}
+
+fn resize_geometry(
+    initial: Rectangle<i32, Logical>,
+    edges: ResizeEdge,
+    delta: Point<i32, Logical>,
+    (min_size, max_size): (Size<i32, Logical>, Size<i32, Logical>),
+    modifiers: GrabModifiers,
+    targets: &[Rectangle<i32, Logical>],
+    distance: i32,
+) -> Rectangle<i32, Logical> {
+    let horizontal = edges.intersects(ResizeEdge::LEFT | ResizeEdge::RIGHT);
+    let vertical = edges.intersects(ResizeEdge::TOP | ResizeEdge::BOTTOM);
+    let factor = if modifiers.centered { 2 } else { 1 };
+
+    let mut left = initial.loc.x;
+    let mut top = initial.loc.y;
+    let mut right = initial.loc.x + initial.size.w;
+    let mut bottom = initial.loc.y + initial.size.h;
+    if edges.contains(ResizeEdge::LEFT) {
+        left += delta.x * factor;
+    } else if edges.contains(ResizeEdge::RIGHT) {
+        right += delta.x * factor;
+    }
+    if edges.contains(ResizeEdge::TOP) {
+        top += delta.y * factor;
+    } else if edges.contains(ResizeEdge::BOTTOM) {
+        bottom += delta.y * factor;
+    }
+
+    // Snapping would break the aspect ratio or the symmetry
+    if !modifiers.keep_aspect_ratio && !modifiers.centered && distance > 0 {
+        let horizontal_targets = horizontal_targets(targets);
+        if edges.contains(ResizeEdge::LEFT) {
+            left += snap_offset(&[left], (top, bottom), horizontal_targets, distance);
+        } else if edges.contains(ResizeEdge::RIGHT) {
+            right += snap_offset(&[right], (top, bottom), horizontal_targets, distance);
+        }
+        let vertical_targets = vertical_targets(targets);
+        if edges.contains(ResizeEdge::TOP) {
+            top += snap_offset(&[top], (left, right), vertical_targets, distance);
+        } else if edges.contains(ResizeEdge::BOTTOM) {
+            bottom += snap_offset(&[bottom], (left, right), vertical_targets, distance);
+        }
+    }
+
+    let mut width = right - left;
+    let mut height = bottom - top;
+    if modifiers.keep_aspect_ratio && initial.size.w > 0 && initial.size.h > 0 {
+        let scale_x = width as f64 / initial.size.w as f64;
+        let scale_y = height as f64 / initial.size.h as f64;
+        let scale = match (horizontal, vertical) {
+            (true, false) => scale_x,
+            (false, true) => scale_y,
+            _ => scale_x.max(scale_y),
+        };
+        width = (initial.size.w as f64 * scale).round() as i32;
+        height = (initial.size.h as f64 * scale).round() as i32;
+    }
+
+    let max_width = if max_size.w == 0 { i32::MAX } else { max_size.w };
+    let max_height = if max_size.h == 0 { i32::MAX } else { max_size.h };
+    let width = width.min(max_width).max(min_size.w.max(1));
+    let height = height.min(max_height).max(min_size.h.max(1));
+
+    // Keep the edges opposite to the resized ones (or the center) in place
+    let x = if modifiers.centered && horizontal {
+        initial.loc.x + (initial.size.w - width) / 2
+    } else if edges.contains(ResizeEdge::LEFT) {
+        initial.loc.x + initial.size.w - width
+    } else {
+        initial.loc.x
+    };
+    let y = if modifiers.centered && vertical {
+        initial.loc.y + (initial.size.h - height) / 2
+    } else if edges.contains(ResizeEdge::TOP) {
+        initial.loc.y + initial.size.h - height
+    } else {
+        initial.loc.y
+    };
+
+    Rectangle::new((x, y).into(), (width, height).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resize_geometry, snap_move, GrabModifiers, ResizeEdge};
+    use crate::utils::{Logical, Rectangle, Size};
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::new((x, y).into(), (w, h).into())
+    }
+
+    #[test]
+    fn move_snaps_to_closest_edge() {
+        let output = rect(0, 0, 1920, 1080);
+        let other = rect(500, 100, 300, 300);
+
+        // the left edge is 10px away from the output edge
+        assert_eq!(
+            snap_move(rect(10, 500, 100, 100), &[output], 16),
+            rect(0, 500, 100, 100)
+        );
+        // the left edge is 5px away from the right edge of `other`
+        assert_eq!(
+            snap_move(rect(805, 200, 100, 100), &[output, other], 16),
+            rect(800, 200, 100, 100)
+        );
+        // `other` does not overlap vertically
+        assert_eq!(
+            snap_move(rect(805, 600, 100, 100), &[output, other], 16),
+            rect(805, 600, 100, 100)
+        );
+        // too far away
+        assert_eq!(
+            snap_move(rect(20, 500, 100, 100), &[output], 16),
+            rect(20, 500, 100, 100)
+        );
+    }
+
+    #[test]
+    fn resize_anchors_opposite_edge() {
+        let initial = rect(100, 100, 200, 200);
+        let geometry = resize_geometry(
+            initial,
+            ResizeEdge::TOP_LEFT,
+            (-50, 20).into(),
+            (Size::default(), Size::default()),
+            GrabModifiers::default(),
+            &[],
+            0,
+        );
+        assert_eq!(geometry, rect(50, 120, 250, 180));
+    }
+
+    #[test]
+    fn resize_honors_constraints() {
+        let initial = rect(100, 100, 200, 200);
+        let constraints = (Size::from((150, 0)), Size::from((0, 250)));
+        let geometry = resize_geometry(
+            initial,
+            ResizeEdge::BOTTOM_LEFT,
+            (100, 100).into(),
+            constraints,
+            GrabModifiers::default(),
+            &[],
+            0,
+        );
+        assert_eq!(geometry, rect(150, 100, 150, 250));
+    }
+
+    #[test]
+    fn resize_snaps_moving_edge() {
+        let initial = rect(100, 100, 200, 200);
+        let output = rect(0, 0, 1920, 1080);
+        let geometry = resize_geometry(
+            initial,
+            ResizeEdge::LEFT,
+            (-90, 0).into(),
+            (Size::default(), Size::default()),
+            GrabModifiers::default(),
+            &[output],
+            16,
+        );
+        assert_eq!(geometry, rect(0, 100, 300, 200));
+    }
+
+    #[test]
+    fn resize_with_modifiers() {
+        let initial = rect(100, 100, 200, 100);
+        let keep_aspect_ratio = GrabModifiers {
+            keep_aspect_ratio: true,
+            ..Default::default()
+        };
+        let geometry = resize_geometry(
+            initial,
+            ResizeEdge::RIGHT,
+            (100, 0).into(),
+            (Size::default(), Size::default()),
+            keep_aspect_ratio,
+            &[],
+            16,
+        );
+        assert_eq!(geometry, rect(100, 100, 300, 150));
+
+        let centered = GrabModifiers {
+            centered: true,
+            ..Default::default()
+        };
+        let geometry = resize_geometry(
+            initial,
+            ResizeEdge::BOTTOM_RIGHT,
+            (10, 20).into(),
+            (Size::default(), Size::default()),
+            centered,
+            &[],
+            16,
+        );
+        assert_eq!(geometry, rect(90, 80, 220, 140));
+    }
+}
@@ -18,6 +18,9 @@
 //! It provides a bunch of methods to calculate and retrieve its size, manage itself, attach additional user_data
 //! as well as a [drawing function](`crate::backend::renderer::element::AsRenderElements::render_elements`) to ease rendering it's related surfaces.
 //!
+//! Transitions between the normal, maximized and fullscreen [`WindowMode`]s can be requested through the window,
+//! which keeps track of the geometry to restore and reports once the client finished the transition.
+//!
 //! Note that a [`Window`] on it's own has no position. For that it needs to be placed inside a [`Space`].
 //!
 //! ### [`Space`]
@@ -37,32 +40,135 @@
 //! which [`LayerSurface`]s can be mapped upon. Associated layer maps are automatically rendered by [`render_output`](crate::desktop::space::render_output),
 //! but a [draw function](`crate::backend::renderer::element::AsRenderElements::render_elements`) is also provided for manual layer-surface management.
 //!
+//! An [`ExclusiveLayerFocus`] enforces the keyboard focus of layer surfaces with exclusive keyboard interactivity.
+//!
 //! ### Popups
 //!
 //! Provides a [`PopupManager`], which can be used to automatically keep track of popups and their
 //! relations to one-another. Popups are then automatically rendered with their matching toplevel surfaces,
 //! when either [`crate::backend::renderer::element::AsRenderElements::render_elements`] or [`render_output`](crate::desktop::space::render_output) is called.
 //!
+//! ### Focus policies
+//!
+//! A [`FocusTracker`](focus::FocusTracker) implements click-to-focus, focus-follows-mouse
+//! and sloppy focus on top of a [`Space`].
+//!
+//! ### Interactive move and resize
+//!
+//! [`MoveGrab`](grabs::MoveGrab) and [`ResizeGrab`](grabs::ResizeGrab) implement moving and resizing
+//! elements of a [`Space`] with the pointer, including snapping to outputs and other elements.
+//!
+//! ### Texture reclamation
+//!
+//! A [`TextureReclaimer`] releases the textures of elements, which stayed unmapped for a configurable time,
+//! to reduce memory usage. They are imported again, once the element is rendered the next time.
+//!
+//! ### First frame
+//!
+//! A [`FirstFrameTracker`] delays mapping new windows until they have drawn their first frame,
+//! to avoid showing empty windows. A timeout makes sure slow clients are mapped eventually.
+//!
+//! ### Launch feedback
+//!
+//! A [`LaunchTracker`] keeps track of applications being launched, announced through xdg activation tokens
+//! or X11 startup notification, until their windows show up. This can be used to show a busy cursor or
+//! other feedback while an application is starting.
+//!
+//! ### Frame statistics
+//!
+//! Per surface statistics about the commit rate, the latency from commit to presentation and dropped frames
+//! can be collected using [`utils::enable_frame_stats`] and queried with [`utils::surface_frame_stats`].
+//!
+//! ### Frame pacing
+//!
+//! A [`FramePacer`] sends the frame callbacks of surfaces in a [`FramePacingGroup`] shortly before the estimated
+//! next flip of an output instead of right after rendering, keeping e.g. a game and its overlays in lockstep
+//! with the display.
+//!
+//! ### Idle inhibition
+//!
+//! An [`IdleInhibitPolicy`] combines explicit idle inhibitors of visible surfaces with automatic inhibition
+//! for fullscreen surfaces playing video or games, detected through their content type or frame rate.
+//!
+//! ### Repaint scheduling
+//!
+//! An [`OutputContentSnapshot`] cheaply captures the surfaces, commits and cursor position shown on an output.
+//! A [`RepaintTracker`] compares it against the last rendered frame, so rendering can be skipped entirely
+//! while nothing changed, unless a repaint was scheduled or an animation is running.
+//!
+//! ### Window list
+//!
+//! A [`WindowListSnapshot`] captures the metadata of all windows, like their title, geometry and states,
+//! as plain data suitable for IPC or scripting interfaces. A [`WindowList`] notifies subscribers about
+//! the changes between snapshots. Windows are identified by a [`WindowListId`], which is never reused.
+//!
+//! ### Screenshots
+//!
+//! [`take_screenshot`](screenshot::take_screenshot) renders an output or a window offscreen and reads it back
+//! asynchronously, to encode it as an image without going through a capture protocol. It requires the
+//! `image` feature.
+//!
+//! ### Virtual outputs
+//!
+//! A [`VirtualOutput`] is an [`Output`](crate::output::Output) without a display. Its content is rendered into
+//! a texture and can be shown inside other outputs, e.g. as a picture-in-picture preview of another workspace.
+//! A [`VirtualOutputWindow`] shows it as a window in a [`Space`].
+//!
+//! ### Scene graph
+//!
+//! A [`Scene`](scene::Scene) is an opt-in alternative to building element lists by hand. It keeps a tree of
+//! elements with transforms, opacity, clipping and cached subtrees and lowers it into render elements.
+//!
 //! ## Remarks
 //!
 //! Note that the desktop abstractions are concerned with easing rendering different clients and therefore need to be able
 //! to manage client buffers to do so. If you plan to use the provided drawing functions, you need to use
 //! [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler).
 
+pub mod focus;
+pub mod grabs;
+pub mod scene;
+#[cfg(feature = "image")]
+pub mod screenshot;
 pub mod space;
 pub use self::space::Space;
 
 #[cfg(feature = "wayland_frontend")]
 pub use self::wayland::{
-    layer::{layer_map_for_output, LayerMap, LayerSurface},
+    first_frame::FirstFrameTracker,
+    frame_pacing::{FramePacer, FramePacingGroup},
+    fullscreen::{
+        fullscreen_surface_placement, map_fullscreen_surface, render_elements_from_fullscreen_surface,
+        FullscreenElement, FullscreenPlacement,
+    },
+    idle_inhibit::{IdleInhibitPolicy, IdleInhibitReason},
+    launch::{Launch, LaunchTracker},
+    layer::{layer_map_for_output, ExclusiveFocusChange, ExclusiveLayerFocus, LayerMap, LayerSurface},
     popup::*,
+    reclaim::TextureReclaimer,
+    repaint::{AnimationGuard, OutputContentSnapshot, RepaintTracker},
     utils,
+    virtual_output::{VirtualOutput, VirtualOutputRenderElement, VirtualOutputWindow},
     window::*,
+    window_list::{
+        WindowInfo, WindowList, WindowListChange, WindowListId, WindowListSnapshot, WindowListSubscription,
+        WindowStates,
+    },
 };
 #[cfg(feature = "wayland_frontend")]
 mod wayland {
+    pub(crate) mod first_frame;
+    pub(crate) mod frame_pacing;
+    pub(crate) mod frame_stats;
+    pub(crate) mod fullscreen;
+    pub(crate) mod idle_inhibit;
+    pub(crate) mod launch;
     pub(crate) mod layer;
     pub mod popup;
+    pub(crate) mod reclaim;
+    pub(crate) mod repaint;
     pub mod utils;
+    pub(crate) mod virtual_output;
     pub mod window;
+    pub(crate) mod window_list;
 }
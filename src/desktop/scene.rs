@@ -0,0 +1,793 @@
+//! Retained scene graph lowering to render elements
+//!
+//! Building a flat list of render elements every frame gets awkward for scenes with many groups of
+//! elements, like workspaces, an overview or animations moving whole sets of windows. A [`Scene`]
+//! keeps a tree of nodes instead, similar to `wlr_scene`:
+//!
+//! - Tree nodes group other nodes, element nodes hold an element implementing [`AsRenderElements`]
+//! - Every node has a location relative to its parent, a scale, an opacity and an optional clip,
+//!   which apply to its whole subtree
+//! - Trees can be marked as cached, rendering their subtree into an offscreen texture, that is only
+//!   updated where the subtree got damaged
+//!
+//! [`Scene::render_elements_for_region`] lowers the tree into render elements for the usual
+//! rendering pipeline. The elements keep their ids between frames, so the
+//! [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker) picks up moved,
+//! faded or clipped nodes as damage without any further bookkeeping.
+//!
+//! ```no_run
+//! # use smithay::{
+//! #     backend::renderer::gles::GlesRenderer,
+//! #     desktop::{scene::Scene, Window},
+//! #     utils::Rectangle,
+//! # };
+//! # let mut renderer: GlesRenderer = todo!();
+//! # let window: Window = todo!();
+//! let mut scene = Scene::new();
+//! let workspace = scene.add_tree(scene.root());
+//! let node = scene.add_element(workspace, window);
+//! scene.set_location(node, (100, 100));
+//!
+//! // slide the whole workspace out
+//! scene.set_location(workspace, (-200, 0));
+//! scene.set_opacity(workspace, 0.5);
+//!
+//! let elements = scene.render_elements_for_region(
+//!     &mut renderer,
+//!     Rectangle::from_size((1920, 1080).into()),
+//!     1.0,
+//!     1.0,
+//! );
+//! ```
+
+use std::{any::Any, collections::HashMap, fmt};
+
+use tracing::warn;
+
+use crate::{
+    backend::renderer::{
+        element::{
+            composite::{CompositeLayer, CompositeLayerElement},
+            utils::{CropRenderElement, RescaleRenderElement},
+            AsRenderElements, Kind, RenderElement,
+        },
+        Offscreen, Renderer, Texture,
+    },
+    utils::{Logical, Point, Rectangle, Scale},
+};
+
+use super::space::SpaceElement;
+
+// Number of regions, e.g. outputs, the texture of a cached tree is kept for
+const MAX_CACHED_REGIONS: usize = 4;
+
+/// Identifier of a node in a [`Scene`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SceneNodeId(usize);
+
+struct Node<E> {
+    parent: Option<SceneNodeId>,
+    kind: NodeKind<E>,
+    location: Point<i32, Logical>,
+    scale: f64,
+    opacity: f32,
+    clip: Option<Rectangle<i32, Logical>>,
+    enabled: bool,
+}
+
+enum NodeKind<E> {
+    Tree {
+        // bottom -> top
+        children: Vec<SceneNodeId>,
+        cached: bool,
+        // `CompositeLayer`s of the renderers texture type, by rendered region, most recently used last
+        cache: Vec<(Rectangle<i32, Logical>, Box<dyn Any>)>,
+    },
+    Element(E),
+}
+
+impl<E: fmt::Debug> fmt::Debug for Node<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Node");
+        debug
+            .field("parent", &self.parent)
+            .field("location", &self.location)
+            .field("scale", &self.scale)
+            .field("opacity", &self.opacity)
+            .field("clip", &self.clip)
+            .field("enabled", &self.enabled);
+        match &self.kind {
+            NodeKind::Tree { children, cached, .. } => {
+                debug.field("children", children).field("cached", cached)
+            }
+            NodeKind::Element(element) => debug.field("element", element),
+        };
+        debug.finish()
+    }
+}
+
+// accumulated state of the ancestors of a node
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    origin: Point<f64, Logical>,
+    scale: f64,
+    alpha: f32,
+    clip: Option<Rectangle<f64, Logical>>,
+}
+
+impl Placement {
+    fn child<E>(&self, node: &Node<E>) -> Placement {
+        let origin = self.origin + node.location.to_f64().upscale(self.scale);
+        let scale = self.scale * node.scale;
+        let clip = node.clip.map(|clip| {
+            Rectangle::new(
+                origin + clip.loc.to_f64().upscale(scale),
+                clip.size.to_f64().upscale(scale),
+            )
+        });
+        let clip = match (self.clip, clip) {
+            (Some(parent), Some(clip)) => Some(parent.intersection(clip).unwrap_or_default()),
+            (parent, clip) => parent.or(clip),
+        };
+        Placement {
+            origin,
+            scale,
+            alpha: self.alpha * node.opacity,
+            clip,
+        }
+    }
+}
+
+/// Retained tree of elements, see the [module-level documentation](self)
+#[derive(Debug)]
+pub struct Scene<E> {
+    nodes: HashMap<SceneNodeId, Node<E>>,
+    root: SceneNodeId,
+    next_id: usize,
+}
+
+impl<E> Default for Scene<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Scene<E> {
+    /// Create a new scene, only containing the root tree
+    pub fn new() -> Self {
+        let mut scene = Scene {
+            nodes: HashMap::new(),
+            root: SceneNodeId(0),
+            next_id: 0,
+        };
+        scene.root = scene.insert(None, NodeKind::new_tree());
+        scene
+    }
+
+    /// Returns the root tree of the scene
+    pub fn root(&self) -> SceneNodeId {
+        self.root
+    }
+
+    /// Add a new tree as the topmost child of `parent`
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not a tree of this scene.
+    pub fn add_tree(&mut self, parent: SceneNodeId) -> SceneNodeId {
+        self.add(parent, NodeKind::new_tree())
+    }
+
+    /// Add a new element as the topmost child of `parent`
+    ///
+    /// Elements are rendered at the location of their node,
+    /// like [`AsRenderElements::render_elements`] renders them at the given location.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not a tree of this scene.
+    pub fn add_element(&mut self, parent: SceneNodeId, element: E) -> SceneNodeId {
+        self.add(parent, NodeKind::Element(element))
+    }
+
+    fn add(&mut self, parent: SceneNodeId, kind: NodeKind<E>) -> SceneNodeId {
+        assert!(self.is_tree(parent), "Parent of a scene node has to be a tree");
+        let id = self.insert(Some(parent), kind);
+        self.children_mut(parent).unwrap().push(id);
+        id
+    }
+
+    fn insert(&mut self, parent: Option<SceneNodeId>, kind: NodeKind<E>) -> SceneNodeId {
+        let id = SceneNodeId(self.next_id);
+        self.next_id += 1;
+        self.nodes.insert(
+            id,
+            Node {
+                parent,
+                kind,
+                location: Point::default(),
+                scale: 1.0,
+                opacity: 1.0,
+                clip: None,
+                enabled: true,
+            },
+        );
+        id
+    }
+
+    /// Remove a node and its subtree, returning the removed elements
+    ///
+    /// The root cannot be removed, removing it only removes its children.
+    pub fn remove(&mut self, node: SceneNodeId) -> Vec<E> {
+        let mut elements = Vec::new();
+        if node == self.root {
+            for child in self.children(node).to_vec() {
+                elements.extend(self.remove(child));
+            }
+            return elements;
+        }
+
+        if let Some(parent) = self.parent(node) {
+            self.children_mut(parent).unwrap().retain(|child| *child != node);
+        }
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            match self.nodes.remove(&id).map(|node| node.kind) {
+                Some(NodeKind::Tree { children, .. }) => stack.extend(children),
+                Some(NodeKind::Element(element)) => elements.push(element),
+                None => {}
+            }
+        }
+        elements
+    }
+
+    /// Move a node and its subtree to become the topmost child of another tree
+    ///
+    /// Does nothing if `parent` is not a tree or is part of the subtree of `node`.
+    pub fn reparent(&mut self, node: SceneNodeId, parent: SceneNodeId) {
+        if node == self.root || !self.nodes.contains_key(&node) || !self.is_tree(parent) {
+            return;
+        }
+        let mut ancestor = Some(parent);
+        while let Some(id) = ancestor {
+            if id == node {
+                return;
+            }
+            ancestor = self.parent(id);
+        }
+
+        if let Some(old_parent) = self.parent(node) {
+            self.children_mut(old_parent)
+                .unwrap()
+                .retain(|child| *child != node);
+        }
+        self.children_mut(parent).unwrap().push(node);
+        self.nodes.get_mut(&node).unwrap().parent = Some(parent);
+    }
+
+    /// Stack a node above its siblings
+    pub fn raise_to_top(&mut self, node: SceneNodeId) {
+        self.restack(node, |children, node| children.push(node));
+    }
+
+    /// Stack a node below its siblings
+    pub fn lower_to_bottom(&mut self, node: SceneNodeId) {
+        self.restack(node, |children, node| children.insert(0, node));
+    }
+
+    /// Stack a node directly above one of its siblings
+    pub fn place_above(&mut self, node: SceneNodeId, sibling: SceneNodeId) {
+        if node == sibling || self.parent(node) != self.parent(sibling) {
+            return;
+        }
+        self.restack(node, |children, node| {
+            let index = children.iter().position(|child| *child == sibling).unwrap();
+            children.insert(index + 1, node);
+        });
+    }
+
+    fn restack(&mut self, node: SceneNodeId, insert: impl FnOnce(&mut Vec<SceneNodeId>, SceneNodeId)) {
+        let Some(parent) = self.parent(node) else {
+            return;
+        };
+        let children = self.children_mut(parent).unwrap();
+        children.retain(|child| *child != node);
+        insert(children, node);
+    }
+
+    /// Returns the parent of a node, `None` for the root
+    pub fn parent(&self, node: SceneNodeId) -> Option<SceneNodeId> {
+        self.nodes.get(&node).and_then(|node| node.parent)
+    }
+
+    /// Returns the children of a tree from bottom to top, empty for elements
+    pub fn children(&self, node: SceneNodeId) -> &[SceneNodeId] {
+        match self.nodes.get(&node).map(|node| &node.kind) {
+            Some(NodeKind::Tree { children, .. }) => children,
+            _ => &[],
+        }
+    }
+
+    fn children_mut(&mut self, node: SceneNodeId) -> Option<&mut Vec<SceneNodeId>> {
+        match self.nodes.get_mut(&node).map(|node| &mut node.kind) {
+            Some(NodeKind::Tree { children, .. }) => Some(children),
+            _ => None,
+        }
+    }
+
+    /// Returns if the node is a tree of this scene
+    pub fn is_tree(&self, node: SceneNodeId) -> bool {
+        matches!(
+            self.nodes.get(&node).map(|node| &node.kind),
+            Some(NodeKind::Tree { .. })
+        )
+    }
+
+    /// Returns the element of a node
+    pub fn element(&self, node: SceneNodeId) -> Option<&E> {
+        match self.nodes.get(&node).map(|node| &node.kind) {
+            Some(NodeKind::Element(element)) => Some(element),
+            _ => None,
+        }
+    }
+
+    /// Returns the element of a node mutably
+    pub fn element_mut(&mut self, node: SceneNodeId) -> Option<&mut E> {
+        match self.nodes.get_mut(&node).map(|node| &mut node.kind) {
+            Some(NodeKind::Element(element)) => Some(element),
+            _ => None,
+        }
+    }
+
+    /// Returns the node holding an element
+    pub fn find_element(&self, element: &E) -> Option<SceneNodeId>
+    where
+        E: PartialEq,
+    {
+        self.nodes.iter().find_map(|(id, node)| match &node.kind {
+            NodeKind::Element(e) if e == element => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Returns the location of a node relative to its parent
+    pub fn location(&self, node: SceneNodeId) -> Option<Point<i32, Logical>> {
+        self.nodes.get(&node).map(|node| node.location)
+    }
+
+    /// Set the location of a node relative to its parent
+    pub fn set_location(&mut self, node: SceneNodeId, location: impl Into<Point<i32, Logical>>) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            node.location = location.into();
+        }
+    }
+
+    /// Set the scale of a node and its subtree, relative to the location of the node
+    pub fn set_scale(&mut self, node: SceneNodeId, scale: f64) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            node.scale = scale;
+        }
+    }
+
+    /// Set the opacity of a node and its subtree
+    pub fn set_opacity(&mut self, node: SceneNodeId, opacity: f32) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            node.opacity = opacity.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Clip a node and its subtree to a rectangle relative to the location of the node
+    pub fn set_clip(&mut self, node: SceneNodeId, clip: Option<Rectangle<i32, Logical>>) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            node.clip = clip;
+        }
+    }
+
+    /// Show or hide a node and its subtree
+    pub fn set_enabled(&mut self, node: SceneNodeId, enabled: bool) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            node.enabled = enabled;
+        }
+    }
+
+    /// Returns if a node and all of its ancestors are enabled
+    pub fn is_visible(&self, node: SceneNodeId) -> bool {
+        let mut current = Some(node);
+        while let Some(id) = current {
+            match self.nodes.get(&id) {
+                Some(node) if node.enabled => current = node.parent,
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Cache the subtree of a tree in an offscreen texture
+    ///
+    /// Worth it for subtrees with many elements that change rarely, e.g. a workspace during
+    /// an animation. The opacity of a cached tree is applied to the texture, so overlapping
+    /// elements of the subtree do not shine through each other.
+    pub fn set_cached(&mut self, node: SceneNodeId, cached: bool) {
+        if let Some(NodeKind::Tree {
+            cached: node_cached,
+            cache,
+            ..
+        }) = self.nodes.get_mut(&node).map(|node| &mut node.kind)
+        {
+            *node_cached = cached;
+            if !cached {
+                cache.clear();
+            }
+        }
+    }
+
+    /// Drop the textures of cached trees, e.g. after an output was removed
+    pub fn clear_caches(&mut self) {
+        for node in self.nodes.values_mut() {
+            if let NodeKind::Tree { cache, .. } = &mut node.kind {
+                cache.clear();
+            }
+        }
+    }
+
+    /// Returns the location of a node in the coordinate space of the scene
+    pub fn global_location(&self, node: SceneNodeId) -> Option<Point<f64, Logical>> {
+        self.placement(node).map(|placement| placement.origin)
+    }
+
+    fn placement(&self, node: SceneNodeId) -> Option<Placement> {
+        let current = self.nodes.get(&node)?;
+        let parent = match current.parent {
+            Some(parent) => self.placement(parent)?,
+            None => Placement {
+                origin: Point::default(),
+                scale: 1.0,
+                alpha: 1.0,
+                clip: None,
+            },
+        };
+        Some(parent.child(current))
+    }
+
+    /// Returns the topmost visible element accepting input at a given point
+    ///
+    /// Also returns the location of the element in the coordinate space of the scene.
+    /// Input of scaled nodes has to be scaled with [`Scene::global_scale`].
+    pub fn element_under(
+        &self,
+        point: impl Into<Point<f64, Logical>>,
+    ) -> Option<(SceneNodeId, Point<f64, Logical>)>
+    where
+        E: SpaceElement,
+    {
+        let point = point.into();
+        let root = self.nodes.get(&self.root)?;
+        let placement = Placement {
+            origin: Point::default(),
+            scale: 1.0,
+            alpha: 1.0,
+            clip: None,
+        }
+        .child(root);
+        self.element_under_node(self.root, placement, point)
+    }
+
+    fn element_under_node(
+        &self,
+        id: SceneNodeId,
+        placement: Placement,
+        point: Point<f64, Logical>,
+    ) -> Option<(SceneNodeId, Point<f64, Logical>)>
+    where
+        E: SpaceElement,
+    {
+        let node = self.nodes.get(&id)?;
+        if !node.enabled || placement.clip.is_some_and(|clip| !clip.contains(point)) {
+            return None;
+        }
+        match &node.kind {
+            NodeKind::Tree { children, .. } => children.iter().rev().find_map(|child| {
+                let placement = placement.child(self.nodes.get(child)?);
+                self.element_under_node(*child, placement, point)
+            }),
+            NodeKind::Element(element) => {
+                let local = (point - placement.origin).downscale(placement.scale);
+                (element.bbox().to_f64().contains(local) && element.is_in_input_region(&local))
+                    .then_some((id, placement.origin))
+            }
+        }
+    }
+
+    /// Returns the accumulated scale of a node and its ancestors
+    pub fn global_scale(&self, node: SceneNodeId) -> Option<f64> {
+        self.placement(node).map(|placement| placement.scale)
+    }
+}
+
+impl<E> NodeKind<E> {
+    fn new_tree() -> Self {
+        NodeKind::Tree {
+            children: Vec::new(),
+            cached: false,
+            cache: Vec::new(),
+        }
+    }
+}
+
+crate::backend::renderer::element::render_elements! {
+    /// Render elements of a [`Scene`]
+    pub SceneRenderElements<R, E> where R: Renderer;
+    /// An element, scaled by its node and cropped to the clip of its node and the rendered region
+    Element=CropRenderElement<RescaleRenderElement<E>>,
+    /// The texture of a cached tree
+    Cached=CompositeLayerElement<<R as Renderer>::TextureId>,
+}
+
+impl<R: Renderer, E: RenderElement<R> + fmt::Debug> fmt::Debug for SceneRenderElements<R, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Element(arg0) => f.debug_tuple("Element").field(arg0).finish(),
+            Self::Cached(arg0) => f.debug_tuple("Cached").field(arg0).finish(),
+            Self::_GenericCatcher(_) => unreachable!(),
+        }
+    }
+}
+
+impl<E> Scene<E> {
+    /// Lower the scene into render elements for a region of the scene, e.g. the geometry of an output
+    ///
+    /// The elements are returned front-to-back and relative to the region. Cached trees render into
+    /// their textures, so this has to be called before binding the target of the output.
+    #[profiling::function]
+    pub fn render_elements_for_region<R>(
+        &mut self,
+        renderer: &mut R,
+        region: Rectangle<i32, Logical>,
+        scale: impl Into<Scale<f64>>,
+        alpha: f32,
+    ) -> Vec<SceneRenderElements<R, <E as AsRenderElements<R>>::RenderElement>>
+    where
+        R: Renderer + Offscreen<<R as Renderer>::TextureId>,
+        <R as Renderer>::TextureId: Texture + Clone + 'static,
+        E: AsRenderElements<R>,
+    {
+        let scale = scale.into();
+        let placement = Placement {
+            origin: Point::default(),
+            scale: 1.0,
+            alpha,
+            clip: None,
+        };
+        let mut elements = Vec::new();
+        let root = self.root;
+        self.lower(renderer, root, placement, region, scale, &mut elements);
+        elements
+    }
+
+    fn lower<R>(
+        &mut self,
+        renderer: &mut R,
+        id: SceneNodeId,
+        parent: Placement,
+        region: Rectangle<i32, Logical>,
+        scale: Scale<f64>,
+        elements: &mut Vec<SceneRenderElements<R, <E as AsRenderElements<R>>::RenderElement>>,
+    ) where
+        R: Renderer + Offscreen<<R as Renderer>::TextureId>,
+        <R as Renderer>::TextureId: Texture + Clone + 'static,
+        E: AsRenderElements<R>,
+    {
+        let Some(node) = self.nodes.get(&id) else {
+            return;
+        };
+        let placement = parent.child(node);
+        if !node.enabled || placement.alpha <= 0.0 {
+            return;
+        }
+        let visible = match placement.clip {
+            Some(clip) => clip.intersection(region.to_f64()),
+            None => Some(region.to_f64()),
+        };
+        let Some(visible) = visible.filter(|visible| !visible.is_empty()) else {
+            return;
+        };
+
+        match &node.kind {
+            NodeKind::Tree {
+                children,
+                cached: false,
+                ..
+            } => {
+                for child in children.clone().into_iter().rev() {
+                    self.lower(renderer, child, placement, region, scale, elements);
+                }
+            }
+            NodeKind::Tree {
+                children,
+                cached: true,
+                ..
+            } => {
+                let children = children.clone();
+                let opaque = Placement {
+                    alpha: 1.0,
+                    ..placement
+                };
+                let mut cached = Vec::new();
+                for child in children.into_iter().rev() {
+                    self.lower(renderer, child, opaque, region, scale, &mut cached);
+                }
+
+                let size = region.size.to_physical_precise_round(scale);
+                let Some(NodeKind::Tree { cache, .. }) = self.nodes.get_mut(&id).map(|node| &mut node.kind)
+                else {
+                    return;
+                };
+                match cache
+                    .iter()
+                    .position(|(cached_region, _)| *cached_region == region)
+                {
+                    Some(index) => {
+                        let entry = cache.remove(index);
+                        cache.push(entry);
+                    }
+                    None => {
+                        if cache.len() >= MAX_CACHED_REGIONS {
+                            cache.remove(0);
+                        }
+                        cache.push((
+                            region,
+                            Box::new(CompositeLayer::<<R as Renderer>::TextureId>::new()),
+                        ));
+                    }
+                };
+                let entry = &mut cache.last_mut().unwrap().1;
+                if !entry.is::<CompositeLayer<<R as Renderer>::TextureId>>() {
+                    // rendered with a different renderer type before
+                    *entry = Box::new(CompositeLayer::<<R as Renderer>::TextureId>::new());
+                }
+                let layer = entry
+                    .downcast_mut::<CompositeLayer<<R as Renderer>::TextureId>>()
+                    .unwrap();
+                match layer.render_element(renderer, size, scale, &cached, Kind::Unspecified) {
+                    Ok(element) => {
+                        elements.push(SceneRenderElements::Cached(element.with_alpha(placement.alpha)))
+                    }
+                    Err(err) => {
+                        warn!(?err, "Failed to render cached scene tree");
+                        elements.extend(cached);
+                    }
+                }
+            }
+            NodeKind::Element(element) => {
+                let location = (placement.origin - region.loc.to_f64())
+                    .to_physical(scale)
+                    .to_i32_round();
+                let crop = Rectangle::new(visible.loc - region.loc.to_f64(), visible.size)
+                    .to_physical_precise_round(scale);
+                // elements are rendered at the output scale, so surfaces pick matching buffers and
+                // textures, and get scaled relative to their location afterwards
+                elements.extend(
+                    element
+                        .render_elements::<<E as AsRenderElements<R>>::RenderElement>(
+                            renderer,
+                            location,
+                            scale,
+                            placement.alpha,
+                        )
+                        .into_iter()
+                        .map(|element| RescaleRenderElement::from_element(element, location, placement.scale))
+                        .filter_map(|element| CropRenderElement::from_element(element, scale, crop))
+                        .map(SceneRenderElements::Element),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scene_tree() {
+        let mut scene = Scene::new();
+        let root = scene.root();
+        let workspace = scene.add_tree(root);
+        let a = scene.add_element(workspace, "a");
+        let b = scene.add_element(workspace, "b");
+        let c = scene.add_element(root, "c");
+        assert_eq!(scene.children(workspace), [a, b]);
+
+        scene.lower_to_bottom(b);
+        assert_eq!(scene.children(workspace), [b, a]);
+        scene.place_above(b, a);
+        assert_eq!(scene.children(workspace), [a, b]);
+
+        scene.set_location(workspace, (100, 50));
+        scene.set_scale(workspace, 0.5);
+        scene.set_location(b, (20, 20));
+        assert_eq!(scene.global_location(b), Some(Point::from((110.0, 60.0))));
+        assert_eq!(scene.global_scale(b), Some(0.5));
+
+        // the workspace can't become a child of its own subtree
+        scene.reparent(workspace, a);
+        assert_eq!(scene.parent(workspace), Some(root));
+        scene.reparent(c, workspace);
+        assert_eq!(scene.children(workspace), [a, b, c]);
+
+        scene.set_enabled(workspace, false);
+        assert!(!scene.is_visible(c));
+
+        let mut removed = scene.remove(workspace);
+        removed.sort();
+        assert_eq!(removed, ["a", "b", "c"]);
+        assert!(scene.children(root).is_empty());
+        assert!(scene.element(a).is_none());
+    }
+
+    #[cfg(feature = "renderer_test")]
+    #[test]
+    fn scaled_element() {
+        use crate::backend::renderer::{
+            element::{solid::SolidColorBuffer, Element},
+            test::DummyRenderer,
+        };
+
+        let mut renderer = DummyRenderer::new();
+        let mut scene = Scene::new();
+        let workspace = scene.add_tree(scene.root());
+        scene.set_location(workspace, (100, 100));
+        scene.set_scale(workspace, 0.5);
+        let node = scene.add_element(workspace, SolidColorBuffer::new((100, 100), [1.0, 1.0, 1.0, 1.0]));
+        scene.set_location(node, (10, 10));
+
+        // rendered at the output scale and scaled down relative to the location of the node
+        let region = Rectangle::from_size((1000, 1000).into());
+        let elements = scene.render_elements_for_region(&mut renderer, region, 2.0, 1.0);
+        assert_eq!(elements.len(), 1);
+        assert_eq!(
+            elements[0].geometry(Scale::from(2.0)),
+            Rectangle::new((210, 210).into(), (100, 100).into())
+        );
+    }
+
+    #[cfg(feature = "renderer_test")]
+    #[test]
+    fn cached_tree_regions() {
+        use crate::backend::renderer::{
+            element::{solid::SolidColorBuffer, Element, Id},
+            test::DummyRenderer,
+        };
+
+        let mut renderer = DummyRenderer::new();
+        let mut scene = Scene::new();
+        let workspace = scene.add_tree(scene.root());
+        scene.set_cached(workspace, true);
+        scene.add_element(workspace, SolidColorBuffer::new((100, 100), [1.0, 1.0, 1.0, 1.0]));
+
+        let mut render = |scene: &mut Scene<SolidColorBuffer>, x: usize| -> Id {
+            let region = Rectangle::new((x as i32 * 10, 0).into(), (100, 100).into());
+            let elements = scene.render_elements_for_region(&mut renderer, region, 1.0, 1.0);
+            assert!(matches!(elements[..], [SceneRenderElements::Cached(_)]));
+            elements[0].id().clone()
+        };
+        let cached_regions = |scene: &Scene<SolidColorBuffer>| match &scene.nodes[&workspace].kind {
+            NodeKind::Tree { cache, .. } => cache.len(),
+            NodeKind::Element(_) => unreachable!(),
+        };
+
+        let first = render(&mut scene, 0);
+        let second = render(&mut scene, 1);
+        for x in 2..MAX_CACHED_REGIONS {
+            render(&mut scene, x);
+        }
+        assert_eq!(cached_regions(&scene), MAX_CACHED_REGIONS);
+        assert_eq!(render(&mut scene, 0), first);
+
+        // a new region evicts the least recently used one
+        render(&mut scene, MAX_CACHED_REGIONS);
+        assert_eq!(cached_regions(&scene), MAX_CACHED_REGIONS);
+        assert_eq!(render(&mut scene, 0), first);
+        assert_ne!(render(&mut scene, 1), second);
+    }
+}
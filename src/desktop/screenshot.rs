@@ -0,0 +1,357 @@
+//! Helper to take screenshots of outputs and windows
+//!
+//! [`take_screenshot`] renders the contents of an output or a single window into an offscreen
+//! buffer and starts downloading it via [`ExportMem`]. Renderers like the
+//! [`GlesRenderer`](crate::backend::renderer::gles::GlesRenderer) copy the pixels asynchronously,
+//! so the returned [`PendingScreenshot`] should be finished a bit later, e.g. after rendering the
+//! next frame, to avoid stalling on the gpu.
+//!
+//! Screenshots are encoded as PNG or WebP with the `image` crate, so this module requires the
+//! `image` feature.
+//!
+//! ```no_run
+//! # use smithay::{
+//! #     backend::renderer::gles::GlesRenderer,
+//! #     desktop::{screenshot::{take_screenshot, ScreenshotOptions, ScreenshotSource}, Space, Window},
+//! #     output::Output,
+//! # };
+//! # let mut renderer: GlesRenderer = todo!();
+//! # let space: Space<Window> = todo!();
+//! # let output: Output = todo!();
+//! // e.g. from a keybinding
+//! let pending = take_screenshot(
+//!     &mut renderer,
+//!     ScreenshotSource::Output { output: &output, space: &space },
+//!     ScreenshotOptions::default(),
+//! )
+//! .expect("Failed to take screenshot");
+//!
+//! // after rendering the next frame
+//! let image = pending.finish(&mut renderer).expect("Failed to read back screenshot");
+//! image.save("screenshot.png").expect("Failed to save screenshot");
+//! ```
+
+use std::{fs::File, io, io::Write, path::Path};
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::{Error as DamageError, OutputDamageTracker},
+            element::{AsRenderElements, RenderElement, Wrap},
+            utils::dump::to_rgba,
+            Color32F, ExportMem, Offscreen, Renderer, Texture, TextureMapping,
+        },
+    },
+    output::Output,
+    utils::{Buffer as BufferCoord, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+
+#[cfg(feature = "wayland_frontend")]
+use crate::backend::renderer::ImportAll;
+
+use super::space::{Space, SpaceElement, SpaceRenderElements};
+
+/// Contents to take a screenshot of
+#[derive(Debug)]
+pub enum ScreenshotSource<'a, E: SpaceElement> {
+    /// An output with the elements of a space and its layer surfaces
+    Output {
+        /// The output
+        output: &'a Output,
+        /// The space the output is mapped in
+        space: &'a Space<E>,
+    },
+    /// A single window, or any other element of a space, cropped to its geometry
+    Window(&'a E),
+}
+
+/// Format of an [`EncodedImage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// Portable Network Graphics
+    #[default]
+    Png,
+    /// Lossless WebP
+    WebP,
+}
+
+/// Options of [`take_screenshot`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenshotOptions {
+    /// Format the screenshot is encoded with
+    pub format: ImageFormat,
+    /// Scale windows are rendered with
+    ///
+    /// Outputs are always captured at their own scale.
+    pub window_scale: f64,
+    /// Color of the regions not covered by any element
+    pub clear_color: Color32F,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        ScreenshotOptions {
+            format: ImageFormat::default(),
+            window_scale: 1.0,
+            clear_color: Color32F::TRANSPARENT,
+        }
+    }
+}
+
+/// Errors of [`take_screenshot`] and [`PendingScreenshot::finish`]
+#[derive(Debug, thiserror::Error)]
+pub enum ScreenshotError<E: std::error::Error> {
+    /// The output has no mode or is not mapped in the space
+    #[error("The output is not mapped or has no mode")]
+    OutputUnmapped,
+    /// The source has no contents
+    #[error("The screenshot would be empty")]
+    Empty,
+    /// Rendering the source failed
+    #[error("Failed to render the screenshot: {0}")]
+    Render(#[source] DamageError<E>),
+    /// The renderer failed to create the buffer or to download its contents
+    #[error("The renderer failed to read back the screenshot: {0}")]
+    Renderer(#[source] E),
+    /// The contents were downloaded in a format, that cannot be encoded
+    #[error("Unsupported format for screenshots: {0:?}")]
+    UnsupportedFormat(Fourcc),
+    /// Encoding the image failed
+    #[error(transparent)]
+    Encode(#[from] EncodeError),
+}
+
+/// Errors encoding a [`Screenshot`]
+#[derive(Debug, thiserror::Error)]
+pub enum EncodeError {
+    /// The `image` crate failed
+    #[error("Failed to encode the image: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// Screenshot, that is still being downloaded from the renderer
+#[derive(Debug)]
+pub struct PendingScreenshot<M> {
+    mapping: M,
+    format: ImageFormat,
+}
+
+impl<M: TextureMapping> PendingScreenshot<M> {
+    /// Size of the screenshot in pixels
+    pub fn size(&self) -> Size<i32, BufferCoord> {
+        self.mapping.size()
+    }
+
+    /// Wait for the download to finish and return the pixels of the screenshot
+    ///
+    /// Has to be called with the renderer passed to [`take_screenshot`].
+    pub fn read<R>(self, renderer: &mut R) -> Result<Screenshot, ScreenshotError<R::Error>>
+    where
+        R: ExportMem<TextureMapping = M>,
+    {
+        let size = self.mapping.size();
+        let format = TextureMapping::format(&self.mapping);
+        let data = renderer
+            .map_texture(&self.mapping)
+            .map_err(ScreenshotError::Renderer)?;
+        let stride = data.len() / size.h.max(1) as usize;
+        let mut rgba =
+            to_rgba(data, format, size, stride).ok_or(ScreenshotError::UnsupportedFormat(format))?;
+        if self.mapping.flipped() {
+            let row_len = size.w as usize * 4;
+            rgba = rgba.chunks_exact(row_len).rev().flatten().copied().collect();
+        }
+
+        Ok(Screenshot { rgba, size })
+    }
+
+    /// Wait for the download to finish and encode the screenshot with the format of its options
+    ///
+    /// Use [`PendingScreenshot::read`] and encode the [`Screenshot`] on another thread
+    /// to avoid blocking the compositor on large outputs.
+    pub fn finish<R>(self, renderer: &mut R) -> Result<EncodedImage, ScreenshotError<R::Error>>
+    where
+        R: ExportMem<TextureMapping = M>,
+    {
+        let format = self.format;
+        let screenshot = self.read(renderer)?;
+        Ok(screenshot.encode(format)?)
+    }
+}
+
+/// Pixels of a screenshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    rgba: Vec<u8>,
+    size: Size<i32, BufferCoord>,
+}
+
+impl Screenshot {
+    /// Tightly packed 8-bit RGBA pixels, starting at the top-left corner
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Size of the screenshot in pixels
+    pub fn size(&self) -> Size<i32, BufferCoord> {
+        self.size
+    }
+
+    /// Encode the screenshot with a given format
+    pub fn encode(&self, format: ImageFormat) -> Result<EncodedImage, EncodeError> {
+        let data = match format {
+            ImageFormat::Png => {
+                let mut data = Vec::new();
+                self.write_image(image::codecs::png::PngEncoder::new(&mut data))?;
+                data
+            }
+            ImageFormat::WebP => {
+                let mut data = Vec::new();
+                self.write_image(image::codecs::webp::WebPEncoder::new_lossless(&mut data))?;
+                data
+            }
+        };
+        Ok(EncodedImage { format, data })
+    }
+
+    fn write_image(&self, encoder: impl image::ImageEncoder) -> Result<(), EncodeError> {
+        encoder.write_image(
+            &self.rgba,
+            self.size.w as u32,
+            self.size.h as u32,
+            image::ExtendedColorType::Rgba8,
+        )?;
+        Ok(())
+    }
+}
+
+/// Encoded screenshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedImage {
+    format: ImageFormat,
+    data: Vec<u8>,
+}
+
+impl EncodedImage {
+    /// Format of the image
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    /// Encoded bytes of the image
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the encoded bytes of the image
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Write the image to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        File::create(path)?.write_all(&self.data)
+    }
+}
+
+/// Render an output or a window offscreen and start downloading its contents
+///
+/// The renderer must not have a target bound, it is left unbound afterwards.
+#[profiling::function]
+pub fn take_screenshot<
+    'a,
+    #[cfg(feature = "wayland_frontend")] R: Renderer + ImportAll + Offscreen<<R as Renderer>::TextureId> + ExportMem,
+    #[cfg(not(feature = "wayland_frontend"))] R: Renderer + Offscreen<<R as Renderer>::TextureId> + ExportMem,
+    E,
+>(
+    renderer: &mut R,
+    source: ScreenshotSource<'a, E>,
+    options: ScreenshotOptions,
+) -> Result<PendingScreenshot<R::TextureMapping>, ScreenshotError<R::Error>>
+where
+    <R as Renderer>::TextureId: Clone + Texture + 'static,
+    E: SpaceElement + PartialEq + AsRenderElements<R> + 'a,
+    <E as AsRenderElements<R>>::RenderElement: 'a,
+    SpaceRenderElements<R, <E as AsRenderElements<R>>::RenderElement>:
+        From<Wrap<<E as AsRenderElements<R>>::RenderElement>>,
+{
+    let mapping = match source {
+        ScreenshotSource::Output { output, space } => {
+            let mode = output.current_mode().ok_or(ScreenshotError::OutputUnmapped)?;
+            let size = output.current_transform().transform_size(mode.size);
+            let scale = output.current_scale().fractional_scale();
+            let elements = space
+                .render_elements_for_output(renderer, output, 1.0)
+                .map_err(|_| ScreenshotError::OutputUnmapped)?;
+            render_and_copy(renderer, size, scale, &elements, options.clear_color)?
+        }
+        ScreenshotSource::Window(window) => {
+            let scale = Scale::from(options.window_scale);
+            let geometry = window.geometry();
+            let size = geometry.size.to_f64().to_physical(scale).to_i32_round();
+            let location = Point::default() - geometry.loc.to_f64().to_physical(scale).to_i32_round();
+            let elements = window
+                .render_elements::<<E as AsRenderElements<R>>::RenderElement>(renderer, location, scale, 1.0);
+            render_and_copy(renderer, size, scale, &elements, options.clear_color)?
+        }
+    };
+
+    Ok(PendingScreenshot {
+        mapping,
+        format: options.format,
+    })
+}
+
+fn render_and_copy<R, E>(
+    renderer: &mut R,
+    size: Size<i32, Physical>,
+    scale: impl Into<Scale<f64>>,
+    elements: &[E],
+    clear_color: Color32F,
+) -> Result<R::TextureMapping, ScreenshotError<R::Error>>
+where
+    R: Offscreen<<R as Renderer>::TextureId> + ExportMem,
+    <R as Renderer>::TextureId: Texture,
+    E: RenderElement<R>,
+{
+    if size.w <= 0 || size.h <= 0 {
+        return Err(ScreenshotError::Empty);
+    }
+
+    let buffer_size = Size::<i32, BufferCoord>::from((size.w, size.h));
+    let texture = renderer
+        .create_buffer(Fourcc::Abgr8888, buffer_size)
+        .map_err(ScreenshotError::Renderer)?;
+    let mut damage_tracker = OutputDamageTracker::new(size, scale, Transform::Normal);
+    damage_tracker
+        .render_output_with(renderer, texture, 0, elements, clear_color)
+        .map_err(ScreenshotError::Render)?;
+
+    let mapping = renderer.copy_framebuffer(Rectangle::from_size(buffer_size), Fourcc::Abgr8888);
+    let unbind = renderer.unbind();
+    let mapping = mapping.map_err(ScreenshotError::Renderer)?;
+    unbind.map_err(ScreenshotError::Renderer)?;
+    Ok(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_png() {
+        let screenshot = Screenshot {
+            rgba: (0..16).collect(),
+            size: Size::from((2, 2)),
+        };
+        let image = screenshot.encode(ImageFormat::Png).unwrap();
+        assert_eq!(image.format(), ImageFormat::Png);
+
+        let decoded = image::load_from_memory_with_format(image.data(), image::ImageFormat::Png)
+            .unwrap()
+            .into_rgba8();
+        assert_eq!(decoded.dimensions(), (2, 2));
+        assert_eq!(decoded.into_raw(), screenshot.rgba());
+    }
+}
@@ -78,6 +78,16 @@ pub trait SpaceElement: IsAlive {
     fn output_leave(&self, output: &Output);
     /// Periodically called to update internal state, if necessary
     fn refresh(&self) {}
+    /// Returns if this element is a transient child of `parent`, e.g. a dialog
+    ///
+    /// Transient children are kept stacked above their parent, when it is raised.
+    fn is_transient_for(&self, parent: &Self) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = parent;
+        false
+    }
 }
 
 impl<T: SpaceElement> SpaceElement for &T {
@@ -106,6 +116,9 @@ impl<T: SpaceElement> SpaceElement for &T {
     fn refresh(&self) {
         SpaceElement::refresh(*self)
     }
+    fn is_transient_for(&self, parent: &Self) -> bool {
+        SpaceElement::is_transient_for(*self, *parent)
+    }
 }
 
 #[derive(Debug)]
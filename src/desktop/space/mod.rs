@@ -8,7 +8,7 @@ use crate::{
         Color32F, Renderer, Texture,
     },
     output::{Output, OutputModeSource, OutputNoMode},
-    utils::{IsAlive, Logical, Point, Rectangle, Scale, Transform},
+    utils::{IsAlive, Logical, Physical, Point, Rectangle, Scale, Transform},
 };
 #[cfg(feature = "wayland_frontend")]
 use crate::{
@@ -125,11 +125,52 @@ impl<E: SpaceElement + PartialEq> Space<E> {
     /// If activate is true it will set the new windows state
     /// to be activate and removes that state from every
     /// other mapped window.
+    ///
+    /// Elements transient for the raised element, see [`SpaceElement::is_transient_for`],
+    /// are raised along with it and stay stacked above it.
     pub fn raise_element(&mut self, element: &E, activate: bool) {
         if let Some(pos) = self.elements.iter().position(|inner| &inner.element == element) {
             let inner = self.elements.remove(pos);
             self.insert_elem(inner, activate);
+            self.raise_transients(element);
+        }
+    }
+
+    fn raise_transients(&mut self, parent: &E) {
+        let Some(parent) = self.elements.iter().position(|inner| &inner.element == parent) else {
+            return;
+        };
+
+        // collect transient children of the element and their children
+        let mut group = vec![parent];
+        loop {
+            let len = group.len();
+            for (idx, inner) in self.elements.iter().enumerate() {
+                if !group.contains(&idx)
+                    && group
+                        .iter()
+                        .any(|parent| inner.element.is_transient_for(&self.elements[*parent].element))
+                {
+                    group.push(idx);
+                }
+            }
+            if group.len() == len {
+                break;
+            }
         }
+        if group.len() == 1 {
+            return;
+        }
+
+        // move them to the top, keeping their relative order
+        let mut transients = group.split_off(1);
+        transients.sort_unstable();
+        let mut raised = Vec::with_capacity(transients.len());
+        for idx in transients.into_iter().rev() {
+            raised.push(self.elements.remove(idx));
+        }
+        self.elements.extend(raised.into_iter().rev());
+        self.elements.sort_by_key(|e| e.element.z_index());
     }
 
     fn insert_elem(&mut self, elem: InnerElement<E>, activate: bool) {
@@ -182,6 +223,10 @@ impl<E: SpaceElement + PartialEq> Space<E> {
     ///
     /// Note that [`SpaceElement::is_in_input_region`] expects the point
     /// to be relative to the elements origin.
+    ///
+    /// The input region of a [`Window`](crate::desktop::Window) is scaled by its
+    /// [scale override](crate::desktop::Window::set_scale_override), the surface under the point
+    /// has to be looked up using [`Window::surface_under_in_space`](crate::desktop::Window::surface_under_in_space).
     pub fn element_under<P: Into<Point<f64, Logical>>>(&self, point: P) -> Option<(&E, Point<i32, Logical>)> {
         let point = point.into();
         self.elements
@@ -264,6 +309,21 @@ impl<E: SpaceElement + PartialEq> Space<E> {
         }
     }
 
+    /// Map an [`Output`] and change its transform
+    ///
+    /// Rotated outputs occupy the rotated size of their mode in the space, e.g. an output
+    /// with a landscape mode and [`Transform::_90`] is mapped as a vertical output.
+    /// See [`Space::map_output`] for details.
+    pub fn map_output_with_transform<P: Into<Point<i32, Logical>>>(
+        &mut self,
+        output: &Output,
+        location: P,
+        transform: Transform,
+    ) {
+        output.change_current_state(None, Some(transform), None, None);
+        self.map_output(output, location);
+    }
+
     /// Iterate over all mapped [`Output`]s of this space.
     pub fn outputs(&self) -> impl Iterator<Item = &Output> {
         self.outputs.iter()
@@ -304,6 +364,70 @@ impl<E: SpaceElement + PartialEq> Space<E> {
         })
     }
 
+    /// Convert a point of the space to the buffer coordinates of an [`Output`]
+    ///
+    /// This applies the location, scale and transform of the output, so the returned point
+    /// is in the physical pixels of the output buffer, as seen by the hardware.
+    ///
+    /// Returns `None` if the output is not mapped or has no mode.
+    pub fn point_to_output_buffer(
+        &self,
+        output: &Output,
+        point: impl Into<Point<f64, Logical>>,
+    ) -> Option<Point<f64, Physical>> {
+        let geometry = self.output_geometry(output)?;
+        let mode = output.current_mode()?;
+        // Output transform is specified in surface-rotation, so inversion gives us the
+        // render transform for the output itself.
+        let transform = output.current_transform().invert();
+        let scale = output.current_scale().fractional_scale();
+
+        let local = (point.into() - geometry.loc.to_f64()).to_physical(scale);
+        let area = transform.transform_size(mode.size).to_f64();
+        Some(transform.transform_point_in(local, &area))
+    }
+
+    /// Convert a point in the buffer coordinates of an [`Output`] to the space
+    ///
+    /// This is the inverse of [`Space::point_to_output_buffer`]. It can be used to map absolute
+    /// input devices attached to an output, like touch screens, which report positions in the
+    /// orientation of the panel regardless of the output transform.
+    ///
+    /// Returns `None` if the output is not mapped or has no mode.
+    pub fn point_from_output_buffer(
+        &self,
+        output: &Output,
+        point: impl Into<Point<f64, Physical>>,
+    ) -> Option<Point<f64, Logical>> {
+        let geometry = self.output_geometry(output)?;
+        let mode = output.current_mode()?;
+        let transform = output.current_transform();
+        let scale = output.current_scale().fractional_scale();
+
+        let local = transform.transform_point_in(point.into(), &mode.size.to_f64());
+        Some(local.to_logical(scale) + geometry.loc.to_f64())
+    }
+
+    /// Convert a rectangle of the space (e.g. damage) to the buffer coordinates of an [`Output`]
+    ///
+    /// See [`Space::point_to_output_buffer`] for details.
+    pub fn rect_to_output_buffer(
+        &self,
+        output: &Output,
+        rect: Rectangle<i32, Logical>,
+    ) -> Option<Rectangle<i32, Physical>> {
+        let geometry = self.output_geometry(output)?;
+        let mode = output.current_mode()?;
+        let transform = output.current_transform().invert();
+        let scale = output.current_scale().fractional_scale();
+
+        let local = Rectangle::new(rect.loc - geometry.loc, rect.size)
+            .to_f64()
+            .to_physical_precise_up(scale);
+        let area = transform.transform_size(mode.size);
+        Some(transform.transform_rect_in(local, &area))
+    }
+
     /// Returns all [`Output`]s a [`SpaceElement`] overlaps with.
     pub fn outputs_for_element(&self, elem: &E) -> Vec<Output> {
         if !self.elements.iter().any(|e| &e.element == elem) {
@@ -565,6 +689,22 @@ crate::backend::renderer::element::render_elements! {
     Custom=&'a C,
 }
 
+impl<
+        'a,
+        #[cfg(feature = "wayland_frontend")] R: Renderer + ImportAll,
+        #[cfg(not(feature = "wayland_frontend"))] R: Renderer,
+        E: RenderElement<R>,
+        C: RenderElement<R>,
+    > From<SpaceRenderElements<R, E>> for OutputRenderElements<'a, R, E, C>
+where
+    <R as Renderer>::TextureId: 'static,
+{
+    #[inline]
+    fn from(element: SpaceRenderElements<R, E>) -> Self {
+        OutputRenderElements::Space(element)
+    }
+}
+
 /// Get the render elements for a specific output
 ///
 /// If multiple spaces are given their elements will be stacked
@@ -573,6 +713,9 @@ crate::backend::renderer::element::render_elements! {
 /// *Note*: If the `wayland_frontend`-feature is enabled
 /// this will include layer-shell surfaces added to this
 /// outputs [`LayerMap`](crate::desktop::LayerMap).
+///
+/// See [`OutputRenderElementsBuilder`] for combining these
+/// elements with custom elements, cursors and dnd icons.
 #[instrument(level = "trace", skip(spaces, renderer))]
 #[profiling::function]
 pub fn space_render_elements<
@@ -593,69 +736,195 @@ where
     SpaceRenderElements<R, <E as AsRenderElements<R>>::RenderElement>:
         From<Wrap<<E as AsRenderElements<R>>::RenderElement>>,
 {
-    let mut render_elements = Vec::new();
-    let output_scale = output.current_scale().fractional_scale();
+    let mut builder = OutputRenderElementsBuilder::default();
+    builder.spaces(renderer, spaces, output, alpha);
+    Ok(builder.build())
+}
 
+/// Assembles the render elements of an output in front-to-back order
+///
+/// The builder collects elements into separate slots, which are stacked
+/// in the following order regardless of the order they were added in:
+///
+/// 1. cursor elements, see [`cursor`](Self::cursor)
+/// 2. dnd icon elements, see [`dnd_icon`](Self::dnd_icon)
+/// 3. custom elements, see [`custom`](Self::custom)
+/// 4. elements of the spaces including layer surfaces, see [`spaces`](Self::spaces)
+/// 5. custom elements below the spaces, see [`underlay`](Self::underlay)
+///
+/// Elements added to the same slot are stacked in the order they were added,
+/// the first element being the top-most one.
+/// The resulting list can be passed directly to
+/// [`OutputDamageTracker::render_output`] or `DrmCompositor::render_frame`.
+#[derive(Debug)]
+pub struct OutputRenderElementsBuilder<C> {
+    cursor: Vec<C>,
+    dnd_icon: Vec<C>,
+    custom: Vec<C>,
+    upper_layers: Vec<C>,
+    spaces: Vec<C>,
+    lower_layers: Vec<C>,
+    underlay: Vec<C>,
     #[cfg(feature = "wayland_frontend")]
-    let layer_map = layer_map_for_output(output);
-    #[cfg(feature = "wayland_frontend")]
-    let lower = {
-        let (lower, upper): (Vec<&LayerSurface>, Vec<&LayerSurface>) = layer_map
-            .layers()
-            .rev()
-            .partition(|s| matches!(s.layer(), Layer::Background | Layer::Bottom));
+    layers_added: bool,
+}
+
+impl<C> Default for OutputRenderElementsBuilder<C> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            cursor: Vec::new(),
+            dnd_icon: Vec::new(),
+            custom: Vec::new(),
+            upper_layers: Vec::new(),
+            spaces: Vec::new(),
+            lower_layers: Vec::new(),
+            underlay: Vec::new(),
+            #[cfg(feature = "wayland_frontend")]
+            layers_added: false,
+        }
+    }
+}
+
+impl<C> OutputRenderElementsBuilder<C> {
+    /// Add elements representing the cursor
+    pub fn cursor<I: Into<C>>(&mut self, elements: impl IntoIterator<Item = I>) -> &mut Self {
+        self.cursor.extend(elements.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add elements representing a dnd icon
+    pub fn dnd_icon<I: Into<C>>(&mut self, elements: impl IntoIterator<Item = I>) -> &mut Self {
+        self.dnd_icon.extend(elements.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add custom elements stacked above the spaces
+    pub fn custom<I: Into<C>>(&mut self, elements: impl IntoIterator<Item = I>) -> &mut Self {
+        self.custom.extend(elements.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add custom elements stacked below the spaces
+    pub fn underlay<I: Into<C>>(&mut self, elements: impl IntoIterator<Item = I>) -> &mut Self {
+        self.underlay.extend(elements.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add the elements of the given spaces for an output
+    ///
+    /// If multiple spaces are given their elements will be stacked
+    /// the same way. Subsequent calls stack the spaces below
+    /// the previously added ones.
+    ///
+    /// *Note*: If the `wayland_frontend`-feature is enabled
+    /// this will include layer-shell surfaces added to this
+    /// outputs [`LayerMap`](crate::desktop::LayerMap) on the first call.
+    #[instrument(level = "trace", skip_all)]
+    #[profiling::function]
+    pub fn spaces<
+        'a,
+        #[cfg(feature = "wayland_frontend")] R: Renderer + ImportAll,
+        #[cfg(not(feature = "wayland_frontend"))] R: Renderer,
+        E: SpaceElement + PartialEq + AsRenderElements<R> + 'a,
+        S: IntoIterator<Item = &'a Space<E>>,
+    >(
+        &mut self,
+        renderer: &mut R,
+        spaces: S,
+        output: &Output,
+        alpha: f32,
+    ) -> &mut Self
+    where
+        <R as Renderer>::TextureId: Clone + Texture + 'static,
+        <E as AsRenderElements<R>>::RenderElement: 'a,
+        C: From<SpaceRenderElements<R, <E as AsRenderElements<R>>::RenderElement>>,
+    {
+        let output_scale = output.current_scale().fractional_scale();
+
+        #[cfg(feature = "wayland_frontend")]
+        if !self.layers_added {
+            self.layers_added = true;
 
-        render_elements.extend(
-            upper
-                .into_iter()
-                .filter_map(|surface| layer_map.layer_geometry(surface).map(|geo| (geo.loc, surface)))
-                .flat_map(|(loc, surface)| {
+            let layer_map = layer_map_for_output(output);
+            for surface in layer_map.layers().rev() {
+                let Some(geo) = layer_map.layer_geometry(surface) else {
+                    continue;
+                };
+                let elements = if matches!(surface.layer(), Layer::Background | Layer::Bottom) {
+                    &mut self.lower_layers
+                } else {
+                    &mut self.upper_layers
+                };
+                elements.extend(
                     AsRenderElements::<R>::render_elements::<WaylandSurfaceRenderElement<R>>(
                         surface,
                         renderer,
-                        loc.to_physical_precise_round(output_scale),
+                        geo.loc.to_physical_precise_round(output_scale),
                         Scale::from(output_scale),
                         alpha,
                     )
                     .into_iter()
-                    .map(SpaceRenderElements::Surface)
-                }),
-        );
+                    .map(|e| C::from(SpaceRenderElements::Surface(e))),
+                );
+            }
+        }
 
-        lower
-    };
+        for space in spaces {
+            let Some(output_geo) = space.output_geometry(output) else {
+                continue;
+            };
 
-    for space in spaces {
-        let _guard = space.span.enter();
-        if let Some(output_geo) = space.output_geometry(output) {
-            render_elements.extend(
+            self.spaces.extend(
                 space
                     .render_elements_for_region(renderer, &output_geo, output_scale, alpha)
                     .into_iter()
-                    .map(|e| SpaceRenderElements::Element(Wrap::from(e))),
+                    .map(|e| C::from(SpaceRenderElements::Element(Wrap::from(e)))),
             );
         }
+
+        self
     }
 
-    #[cfg(feature = "wayland_frontend")]
-    render_elements.extend(
-        lower
-            .into_iter()
-            .filter_map(|surface| layer_map.layer_geometry(surface).map(|geo| (geo.loc, surface)))
-            .flat_map(|(loc, surface)| {
-                AsRenderElements::<R>::render_elements::<WaylandSurfaceRenderElement<R>>(
-                    surface,
-                    renderer,
-                    loc.to_physical_precise_round(output_scale),
-                    Scale::from(output_scale),
-                    alpha,
-                )
-                .into_iter()
-                .map(SpaceRenderElements::Surface)
-            }),
-    );
+    /// Returns the number of elements added so far
+    pub fn len(&self) -> usize {
+        self.cursor.len()
+            + self.dnd_icon.len()
+            + self.custom.len()
+            + self.upper_layers.len()
+            + self.spaces.len()
+            + self.lower_layers.len()
+            + self.underlay.len()
+    }
+
+    /// Returns `true` if no elements have been added so far
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-    Ok(render_elements)
+    /// Returns the assembled elements in front-to-back order
+    pub fn build(self) -> Vec<C> {
+        let len = self.len();
+        let Self {
+            mut cursor,
+            mut dnd_icon,
+            mut custom,
+            mut upper_layers,
+            mut spaces,
+            mut lower_layers,
+            mut underlay,
+            ..
+        } = self;
+
+        cursor.reserve_exact(len - cursor.len());
+        cursor.append(&mut dnd_icon);
+        cursor.append(&mut custom);
+        cursor.append(&mut upper_layers);
+        cursor.append(&mut spaces);
+        cursor.append(&mut lower_layers);
+        cursor.append(&mut underlay);
+        cursor
+    }
 }
 
 /// Render a output
@@ -692,13 +961,179 @@ where
         assert!(renderer_output == output);
     }
 
-    let space_render_elements = space_render_elements(renderer, spaces, output, alpha)?;
+    let mut builder: OutputRenderElementsBuilder<
+        OutputRenderElements<'a, R, <E as AsRenderElements<R>>::RenderElement, C>,
+    > = OutputRenderElementsBuilder::default();
+    builder
+        .custom(custom_elements.iter().map(OutputRenderElements::Custom))
+        .spaces(renderer, spaces, output, alpha);
+    let render_elements = builder.build();
 
-    let mut render_elements: Vec<OutputRenderElements<'a, R, <E as AsRenderElements<R>>::RenderElement, C>> =
-        Vec::with_capacity(custom_elements.len() + space_render_elements.len());
+    damage_tracker.render_output(renderer, age, &render_elements, clear_color)
+}
 
-    render_elements.extend(custom_elements.iter().map(OutputRenderElements::Custom));
-    render_elements.extend(space_render_elements.into_iter().map(OutputRenderElements::Space));
+#[cfg(test)]
+mod tests {
+    use super::{Space, SpaceElement};
+    use crate::{
+        output::{Mode, Output, PhysicalProperties, Scale as OutputScale, Subpixel},
+        utils::{IsAlive, Logical, Point, Rectangle, Transform},
+    };
 
-    damage_tracker.render_output(renderer, age, &render_elements, clear_color)
+    #[derive(Debug, PartialEq)]
+    struct TestElement;
+
+    impl SpaceElement for TestElement {
+        fn bbox(&self) -> Rectangle<i32, Logical> {
+            Rectangle::default()
+        }
+        fn is_in_input_region(&self, _point: &Point<f64, Logical>) -> bool {
+            false
+        }
+        fn set_activate(&self, _activated: bool) {}
+        fn output_enter(&self, _output: &Output, _overlap: Rectangle<i32, Logical>) {}
+        fn output_leave(&self, _output: &Output) {}
+    }
+
+    impl IsAlive for TestElement {
+        fn alive(&self) -> bool {
+            true
+        }
+    }
+
+    fn output(scale: i32) -> Output {
+        let output = Output::new(
+            "test".into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Test".into(),
+            },
+        );
+        output.change_current_state(
+            Some(Mode {
+                size: (1920, 1080).into(),
+                refresh: 60_000,
+            }),
+            None,
+            Some(OutputScale::Integer(scale)),
+            None,
+        );
+        output
+    }
+
+    #[test]
+    fn scaled_output_buffer_coordinates() {
+        let output = output(2);
+        let mut space = Space::<TestElement>::default();
+        space.map_output(&output, (100, 50));
+
+        assert_eq!(
+            space.output_geometry(&output),
+            Some(Rectangle::new((100, 50).into(), (960, 540).into()))
+        );
+        assert_eq!(
+            space.point_to_output_buffer(&output, (110.0, 70.0)),
+            Some((20.0, 40.0).into())
+        );
+        assert_eq!(
+            space.point_from_output_buffer(&output, (20.0, 40.0)),
+            Some((110.0, 70.0).into())
+        );
+        assert_eq!(
+            space.rect_to_output_buffer(&output, Rectangle::new((110, 70).into(), (10, 5).into())),
+            Some(Rectangle::new((20, 40).into(), (20, 10).into()))
+        );
+    }
+
+    #[test]
+    fn rotated_output_buffer_coordinates() {
+        let output = output(1);
+        let mut space = Space::<TestElement>::default();
+        space.map_output_with_transform(&output, (100, 0), Transform::_90);
+
+        // the landscape mode is mapped as a vertical output
+        assert_eq!(
+            space.output_geometry(&output),
+            Some(Rectangle::new((100, 0).into(), (1080, 1920).into()))
+        );
+
+        // the top-left corner of the output is the bottom-left corner of its buffer
+        let buffer_point = space.point_to_output_buffer(&output, (110.0, 20.0)).unwrap();
+        assert_eq!(buffer_point, (20.0, 1070.0).into());
+        assert_eq!(
+            space.point_from_output_buffer(&output, buffer_point),
+            Some((110.0, 20.0).into())
+        );
+
+        let rect = space
+            .rect_to_output_buffer(&output, Rectangle::new((110, 20).into(), (30, 10).into()))
+            .unwrap();
+        assert_eq!(rect, Rectangle::new((20, 1040).into(), (10, 30).into()));
+
+        // unmapped outputs have no buffer coordinates
+        space.unmap_output(&output);
+        assert_eq!(space.point_to_output_buffer(&output, (110.0, 20.0)), None);
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct StackElement {
+        id: u32,
+        parent: Option<u32>,
+    }
+
+    impl SpaceElement for StackElement {
+        fn bbox(&self) -> Rectangle<i32, Logical> {
+            Rectangle::default()
+        }
+        fn is_in_input_region(&self, _point: &Point<f64, Logical>) -> bool {
+            false
+        }
+        fn set_activate(&self, _activated: bool) {}
+        fn output_enter(&self, _output: &Output, _overlap: Rectangle<i32, Logical>) {}
+        fn output_leave(&self, _output: &Output) {}
+        fn is_transient_for(&self, parent: &Self) -> bool {
+            self.parent == Some(parent.id)
+        }
+    }
+
+    impl IsAlive for StackElement {
+        fn alive(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn raise_keeps_transients_above_parent() {
+        let element = |id, parent| StackElement { id, parent };
+        let (a, b, c, d) = (
+            element(1, None),
+            element(2, Some(1)),
+            element(3, None),
+            element(4, Some(2)),
+        );
+        let mut space = Space::<StackElement>::default();
+        for element in [&a, &b, &c, &d] {
+            space.map_element(element.clone(), (0, 0), false);
+        }
+        let order = |space: &Space<StackElement>| space.elements().map(|e| e.id).collect::<Vec<_>>();
+
+        space.raise_element(&c, false);
+        assert_eq!(order(&space), vec![1, 2, 4, 3]);
+
+        // transient children and their children follow the parent in their relative order
+        space.raise_element(&a, false);
+        assert_eq!(order(&space), vec![3, 1, 2, 4]);
+
+        space.raise_element(&d, false);
+        assert_eq!(order(&space), vec![3, 1, 2, 4]);
+
+        // raising a child doesn't raise its parent
+        space.raise_element(&b, false);
+        assert_eq!(order(&space), vec![3, 1, 2, 4]);
+        space.raise_element(&c, false);
+        space.raise_element(&b, false);
+        assert_eq!(order(&space), vec![1, 3, 2, 4]);
+    }
 }
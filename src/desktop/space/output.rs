@@ -5,34 +5,19 @@ use crate::{
 
 use std::{collections::HashMap, sync::Mutex};
 
-type OutputUserdata = Mutex<HashMap<usize, Point<i32, Logical>>>;
+crate::user_data_key!(OutputUserdata: Mutex<HashMap<usize, Point<i32, Logical>>> = Mutex::default());
 
 pub fn set_output_location(space: usize, o: &Output, new_loc: impl Into<Option<Point<i32, Logical>>>) {
-    let userdata = o.user_data();
-    userdata.insert_if_missing_threadsafe(OutputUserdata::default);
-
+    let mut locations = o.user_data().get_key::<OutputUserdata>().lock().unwrap();
     match new_loc.into() {
-        Some(loc) => userdata
-            .get::<OutputUserdata>()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .insert(space, loc),
-        None => userdata
-            .get::<OutputUserdata>()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .remove(&space),
+        Some(loc) => locations.insert(space, loc),
+        None => locations.remove(&space),
     };
 }
 
 pub fn output_location(space: usize, o: &Output) -> Point<i32, Logical> {
-    let userdata = o.user_data();
-    userdata.insert_if_missing_threadsafe(OutputUserdata::default);
-    *userdata
-        .get::<OutputUserdata>()
-        .unwrap()
+    *o.user_data()
+        .get_key::<OutputUserdata>()
         .lock()
         .unwrap()
         .entry(space)
@@ -0,0 +1,81 @@
+use crate::{
+    backend::renderer::{
+        element::{
+            surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+            AsRenderElements, Kind,
+        },
+        ImportAll, Renderer,
+    },
+    desktop::{space::SpaceElement, utils::under_from_surface_tree, FullscreenElement, WindowSurfaceType},
+    output::Output,
+    utils::{Logical, Physical, Point, Rectangle, Scale},
+};
+
+use super::output_update;
+
+impl SpaceElement for FullscreenElement {
+    fn bbox(&self) -> Rectangle<i32, Logical> {
+        self.placement()
+            .map(|placement| Rectangle::from_size(placement.geometry.size))
+            .unwrap_or_default()
+    }
+
+    fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
+        let Some(placement) = self.placement() else {
+            return false;
+        };
+        let point =
+            (*point + placement.geometry.loc.to_f64() - placement.location).downscale(placement.scale);
+        under_from_surface_tree(self.surface().wl_surface(), point, (0, 0), WindowSurfaceType::ALL).is_some()
+    }
+
+    fn set_activate(&self, _activated: bool) {}
+
+    fn output_enter(&self, output: &Output, overlap: Rectangle<i32, Logical>) {
+        let Some(placement) = self.placement() else {
+            return;
+        };
+        // translate the overlap into the coordinate space of the surface tree
+        let mut overlap = overlap.to_f64();
+        overlap.loc += placement.geometry.loc.to_f64() - placement.location;
+        let overlap = overlap.downscale(placement.scale).to_i32_round();
+        output_update(output, Some(overlap), self.surface().wl_surface());
+    }
+
+    fn output_leave(&self, output: &Output) {
+        output_update(output, None, self.surface().wl_surface());
+    }
+}
+
+impl<R> AsRenderElements<R> for FullscreenElement
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Clone + 'static,
+{
+    type RenderElement = WaylandSurfaceRenderElement<R>;
+
+    #[profiling::function]
+    fn render_elements<C: From<WaylandSurfaceRenderElement<R>>>(
+        &self,
+        renderer: &mut R,
+        location: Point<i32, Physical>,
+        scale: Scale<f64>,
+        alpha: f32,
+    ) -> Vec<C> {
+        let Some(placement) = self.placement() else {
+            return Vec::new();
+        };
+        let offset = (placement.location - placement.geometry.loc.to_f64())
+            .to_physical(scale)
+            .to_i32_round();
+
+        render_elements_from_surface_tree(
+            renderer,
+            self.surface().wl_surface(),
+            location + offset,
+            scale * placement.scale,
+            alpha,
+            Kind::Unspecified,
+        )
+    }
+}
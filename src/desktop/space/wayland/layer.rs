@@ -53,7 +53,7 @@ where
             location,
             scale,
             alpha,
-            Kind::Unspecified,
+            self.render_kind(),
         ));
 
         render_elements
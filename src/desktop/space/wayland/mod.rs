@@ -1,4 +1,4 @@
-use std::{cell::RefCell, collections::HashMap};
+use std::{collections::HashMap, sync::Mutex};
 
 use tracing::instrument;
 use wayland_server::protocol::wl_surface::WlSurface;
@@ -10,6 +10,7 @@ use crate::{
     wayland::compositor::{with_surface_tree_downward, TraversalAction},
 };
 
+mod fullscreen;
 mod layer;
 mod window;
 #[cfg(feature = "xwayland")]
@@ -84,4 +85,4 @@ pub fn output_update(output: &Output, output_overlap: Option<Rectangle<i32, Logi
 struct WindowOutputState {
     output_overlap: HashMap<WeakOutput, Rectangle<i32, Logical>>,
 }
-type WindowOutputUserData = RefCell<WindowOutputState>;
+crate::user_data_key!(WindowOutputUserData: Mutex<WindowOutputState> = Mutex::default());
@@ -16,15 +16,16 @@ use super::{output_update, WindowOutputUserData};
 
 impl SpaceElement for Window {
     fn geometry(&self) -> Rectangle<i32, Logical> {
-        self.geometry()
+        scaled(self.geometry(), self.scale_factor())
     }
 
     fn bbox(&self) -> Rectangle<i32, Logical> {
-        self.bbox_with_popups()
+        scaled(self.bbox_with_popups(), self.scale_factor())
     }
 
     fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
-        self.surface_under(*point, WindowSurfaceType::ALL).is_some()
+        self.surface_under(self.to_surface_space(*point), WindowSurfaceType::ALL)
+            .is_some()
     }
 
     fn z_index(&self) -> u8 {
@@ -35,15 +36,15 @@ impl SpaceElement for Window {
         self.set_activated(activated);
     }
 
+    fn is_transient_for(&self, parent: &Self) -> bool {
+        Window::is_transient_for(self, parent)
+    }
+
     #[profiling::function]
     fn output_enter(&self, output: &Output, overlap: Rectangle<i32, Logical>) {
-        self.user_data().insert_if_missing(WindowOutputUserData::default);
         {
-            let mut state = self
-                .user_data()
-                .get::<WindowOutputUserData>()
-                .unwrap()
-                .borrow_mut();
+            let mut state = self.user_data().get_key::<WindowOutputUserData>().lock().unwrap();
+            let overlap = overlap.to_f64().downscale(self.scale_factor()).to_i32_up();
             state.output_overlap.insert(output.downgrade(), overlap);
             state.output_overlap.retain(|weak, _| weak.is_alive());
         }
@@ -52,8 +53,12 @@ impl SpaceElement for Window {
 
     #[profiling::function]
     fn output_leave(&self, output: &Output) {
-        if let Some(state) = self.user_data().get::<WindowOutputUserData>() {
-            state.borrow_mut().output_overlap.retain(|weak, _| weak != output);
+        if let Some(state) = self.user_data().try_get_key::<WindowOutputUserData>() {
+            state
+                .lock()
+                .unwrap()
+                .output_overlap
+                .retain(|weak, _| weak != output);
         }
 
         if let Some(surface) = self.wl_surface() {
@@ -66,8 +71,7 @@ impl SpaceElement for Window {
 
     #[profiling::function]
     fn refresh(&self) {
-        self.user_data().insert_if_missing(WindowOutputUserData::default);
-        let state = self.user_data().get::<WindowOutputUserData>().unwrap().borrow();
+        let state = self.user_data().get_key::<WindowOutputUserData>().lock().unwrap();
 
         if let Some(surface) = self.wl_surface() {
             for (weak, overlap) in state.output_overlap.iter() {
@@ -99,6 +103,7 @@ where
         scale: Scale<f64>,
         alpha: f32,
     ) -> Vec<C> {
+        let scale = scale * self.scale_factor();
         match self.underlying_surface() {
             WindowSurface::Wayland(s) => {
                 let mut render_elements: Vec<C> = Vec::new();
@@ -136,3 +141,11 @@ where
         }
     }
 }
+
+fn scaled(rect: Rectangle<i32, Logical>, scale: f64) -> Rectangle<i32, Logical> {
+    if scale == 1.0 {
+        rect
+    } else {
+        rect.to_f64().upscale(scale).to_i32_round()
+    }
+}
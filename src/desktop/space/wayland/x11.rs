@@ -6,14 +6,14 @@ use crate::{
     backend::renderer::{
         element::{
             surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
-            Kind,
+            AsRenderElements, Kind,
         },
         ImportAll, Renderer,
     },
     desktop::{space::SpaceElement, utils::under_from_surface_tree, WindowSurfaceType},
     utils::{Logical, Physical, Point, Rectangle, Scale},
     wayland::seat::WaylandFocus,
-    xwayland::X11Surface,
+    xwayland::{xwm::X11SurfaceTree, X11Surface},
 };
 
 use super::{output_update, WindowOutputUserData};
@@ -45,13 +45,8 @@ impl SpaceElement for X11Surface {
     }
 
     fn output_enter(&self, output: &crate::output::Output, overlap: Rectangle<i32, Logical>) {
-        self.user_data().insert_if_missing(WindowOutputUserData::default);
         {
-            let mut state = self
-                .user_data()
-                .get::<WindowOutputUserData>()
-                .unwrap()
-                .borrow_mut();
+            let mut state = self.user_data().get_key::<WindowOutputUserData>().lock().unwrap();
             state.output_overlap.insert(output.downgrade(), overlap);
             state.output_overlap.retain(|weak, _| weak.is_alive());
         }
@@ -59,8 +54,12 @@ impl SpaceElement for X11Surface {
     }
 
     fn output_leave(&self, output: &crate::output::Output) {
-        if let Some(state) = self.user_data().get::<WindowOutputUserData>() {
-            state.borrow_mut().output_overlap.retain(|weak, _| weak != output);
+        if let Some(state) = self.user_data().try_get_key::<WindowOutputUserData>() {
+            state
+                .lock()
+                .unwrap()
+                .output_overlap
+                .retain(|weak, _| weak != output);
         }
 
         let state = self.state.lock().unwrap();
@@ -71,8 +70,7 @@ impl SpaceElement for X11Surface {
     }
 
     fn refresh(&self) {
-        self.user_data().insert_if_missing(WindowOutputUserData::default);
-        let wo_state = self.user_data().get::<WindowOutputUserData>().unwrap().borrow();
+        let wo_state = self.user_data().get_key::<WindowOutputUserData>().lock().unwrap();
 
         let state = self.state.lock().unwrap();
         let Some(surface) = state.wl_surface.as_ref() else {
@@ -94,7 +92,7 @@ impl SpaceElement for X11Surface {
     }
 }
 
-impl<R> crate::backend::renderer::element::AsRenderElements<R> for X11Surface
+impl<R> AsRenderElements<R> for X11Surface
 where
     R: Renderer + ImportAll,
     <R as Renderer>::TextureId: Clone + 'static,
@@ -116,3 +114,33 @@ where
         render_elements_from_surface_tree(renderer, surface, location, scale, alpha, Kind::Unspecified)
     }
 }
+
+impl<R> AsRenderElements<R> for X11SurfaceTree
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Clone + 'static,
+{
+    type RenderElement = WaylandSurfaceRenderElement<R>;
+
+    #[profiling::function]
+    fn render_elements<C: From<WaylandSurfaceRenderElement<R>>>(
+        &self,
+        renderer: &mut R,
+        location: Point<i32, Physical>,
+        scale: Scale<f64>,
+        alpha: f32,
+    ) -> Vec<C> {
+        // Render elements are ordered front to back, so the topmost child comes first
+        let mut elements = self
+            .children()
+            .iter()
+            .rev()
+            .flat_map(|child| {
+                let offset = self.child_offset(child).to_physical_precise_round(scale);
+                child.render_elements::<C>(renderer, location + offset, scale, alpha)
+            })
+            .collect::<Vec<_>>();
+        elements.extend(self.root().render_elements::<C>(renderer, location, scale, alpha));
+        elements
+    }
+}
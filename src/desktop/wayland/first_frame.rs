@@ -0,0 +1,316 @@
+use std::time::Duration;
+
+use wayland_protocols::xdg::shell::server::xdg_toplevel;
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    backend::renderer::utils::{with_renderer_surface_state, CommitCounter},
+    utils::{IsAlive, Monotonic, Time},
+    wayland::{
+        compositor::with_states,
+        seat::WaylandFocus,
+        shell::xdg::{SurfaceCachedState, XdgToplevelSurfaceData},
+    },
+};
+
+/// Delays mapping elements until their first frame is ready
+///
+/// Mapping a toplevel as soon as it is created shows an empty or unstyled window, until
+/// the client has drawn its first frame matching the initial configure.
+///
+/// Report new elements using [`FirstFrameTracker::add`] instead of mapping them and call
+/// [`FirstFrameTracker::commit`] for every commit of a root surface. It returns the element once
+/// its first frame is ready, which is the case after the client
+///
+/// - acknowledged a configure,
+/// - committed a non-empty buffer and
+/// - the size of the window doesn't exceed the configured size, if it is maximized or fullscreen.
+///
+/// Some clients commit a placeholder first and only draw their actual content after receiving
+/// a frame callback. [`FirstFrameTracker::set_wait_for_frame_callback`] additionally waits for the commit
+/// following the first frame. In that case frame callbacks have to be sent to the [pending](FirstFrameTracker::pending)
+/// elements, although they are not rendered yet.
+///
+/// Elements are never delayed longer than the configured timeout to not hide misbehaving clients.
+/// Call [`FirstFrameTracker::expire`] once [`FirstFrameTracker::deadline`] has passed to get
+/// the elements, which should be mapped regardless.
+#[derive(Debug)]
+pub struct FirstFrameTracker<E> {
+    timeout: Option<Duration>,
+    wait_for_frame_callback: bool,
+    pending: Vec<PendingElement<E>>,
+}
+
+#[derive(Debug)]
+struct PendingElement<E> {
+    element: E,
+    added: Time<Monotonic>,
+    // commit of the first frame, if waiting for the following commit
+    first_frame: Option<CommitCounter>,
+}
+
+impl<E: WaylandFocus + IsAlive + PartialEq> FirstFrameTracker<E> {
+    /// Create a new tracker delaying elements for at most `timeout`
+    ///
+    /// Elements are delayed until their first frame is ready, if `timeout` is `None`.
+    pub fn new(timeout: Option<Duration>) -> Self {
+        FirstFrameTracker {
+            timeout,
+            wait_for_frame_callback: false,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns the maximum time elements are delayed
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Set the maximum time elements are delayed
+    ///
+    /// Applies to already tracked elements as well.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns if elements are delayed until the commit following their first frame
+    pub fn wait_for_frame_callback(&self) -> bool {
+        self.wait_for_frame_callback
+    }
+
+    /// Set if elements are delayed until the commit following their first frame
+    ///
+    /// Defaults to `false`.
+    pub fn set_wait_for_frame_callback(&mut self, wait: bool) {
+        self.wait_for_frame_callback = wait;
+    }
+
+    /// Report a new element at `now`
+    ///
+    /// Does nothing if the element is already tracked.
+    pub fn add(&mut self, element: E, now: Time<Monotonic>) {
+        if !self.pending.iter().any(|p| p.element == element) {
+            self.pending.push(PendingElement {
+                element,
+                added: now,
+                first_frame: None,
+            });
+        }
+    }
+
+    /// Stop tracking an element, e.g. because it was destroyed
+    ///
+    /// Returns the element, if it was tracked.
+    pub fn remove(&mut self, element: &E) -> Option<E> {
+        let idx = self.pending.iter().position(|p| p.element == *element)?;
+        Some(self.pending.remove(idx).element)
+    }
+
+    /// Returns if an element is waiting for its first frame
+    pub fn is_pending(&self, element: &E) -> bool {
+        self.pending.iter().any(|p| p.element == *element)
+    }
+
+    /// Returns the elements waiting for their first frame
+    pub fn pending(&self) -> impl Iterator<Item = &E> {
+        self.pending.iter().map(|p| &p.element)
+    }
+
+    /// Handle a commit of `surface`
+    ///
+    /// Returns the element of `surface`, if its first frame is ready. It is not tracked anymore
+    /// and should be mapped now.
+    pub fn commit(&mut self, surface: &WlSurface) -> Option<E> {
+        let idx = self
+            .pending
+            .iter()
+            .position(|p| p.element.wl_surface().is_some_and(|s| *s == *surface))?;
+        let commit = first_frame_ready(surface)?;
+
+        let pending = &mut self.pending[idx];
+        if self.wait_for_frame_callback {
+            match pending.first_frame {
+                None => {
+                    pending.first_frame = Some(commit);
+                    return None;
+                }
+                Some(first_frame) if first_frame == commit => return None,
+                Some(_) => {}
+            }
+        }
+
+        Some(self.pending.remove(idx).element)
+    }
+
+    /// Returns the point in time when the next element exceeds the timeout
+    pub fn deadline(&self) -> Option<Time<Monotonic>> {
+        let timeout = self.timeout?;
+        self.pending.iter().map(|p| p.added + timeout).min()
+    }
+
+    /// Stop waiting for elements, which exceeded the timeout
+    ///
+    /// Returns the elements, which should be mapped regardless of their first frame.
+    /// Dead elements are dropped without being returned.
+    pub fn expire(&mut self, now: Time<Monotonic>) -> Vec<E> {
+        self.pending.retain(|p| p.element.alive());
+        let Some(timeout) = self.timeout else {
+            return Vec::new();
+        };
+
+        let (expired, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|p| p.added + timeout <= now);
+        self.pending = pending;
+        expired.into_iter().map(|p| p.element).collect()
+    }
+}
+
+// Returns the current commit of the surface, if it contains a frame consistent with the acked configure
+fn first_frame_ready(surface: &WlSurface) -> Option<CommitCounter> {
+    let (commit, surface_size) = with_renderer_surface_state(surface, |state| {
+        let size = state.surface_size().filter(|size| !size.is_empty())?;
+        state.buffer()?;
+        Some((state.current_commit(), size))
+    })??;
+
+    with_states(surface, |states| {
+        let Some(data) = states.data_map.get::<XdgToplevelSurfaceData>() else {
+            // not a xdg toplevel, there is no configure to match
+            return Some(commit);
+        };
+        let attributes = data.lock().unwrap();
+        attributes.current_serial?;
+
+        let constrained = attributes.current.states.contains(xdg_toplevel::State::Maximized)
+            || attributes
+                .current
+                .states
+                .contains(xdg_toplevel::State::Fullscreen);
+        if let Some(configured) = attributes.current.size.filter(|_| constrained) {
+            let size = states
+                .cached_state
+                .get::<SurfaceCachedState>()
+                .current()
+                .geometry
+                .map(|geometry| geometry.size)
+                .unwrap_or(surface_size);
+            let exceeds =
+                (configured.w > 0 && size.w > configured.w) || (configured.h > 0 && size.h > configured.h);
+            if exceeds {
+                return None;
+            }
+        }
+
+        Some(commit)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wayland_protocols::xdg::shell::server::xdg_toplevel;
+
+    use super::FirstFrameTracker;
+    use crate::{
+        desktop::Window,
+        utils::{Monotonic, Time},
+        wayland::{seat::WaylandFocus, test_utils::TestFixture},
+    };
+
+    fn time(millis: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn ready_after_buffer() {
+        let mut fixture = TestFixture::new();
+        let (surface, _toplevel, server_toplevel) = fixture.create_toplevel();
+        let window = Window::new_wayland_window(server_toplevel.clone());
+        let wl_surface = window.wl_surface().unwrap().into_owned();
+        let mut tracker = FirstFrameTracker::new(None);
+        tracker.add(window.clone(), time(0));
+        tracker.add(window.clone(), time(0));
+        assert_eq!(tracker.pending().count(), 1);
+
+        // the initial configure is acked, but there is no buffer yet
+        surface.commit();
+        fixture.roundtrip();
+        assert_eq!(tracker.commit(&wl_surface), None);
+
+        fixture.map(&surface, 100, 100);
+        assert_eq!(tracker.commit(&wl_surface), Some(window.clone()));
+        assert!(!tracker.is_pending(&window));
+        assert_eq!(tracker.commit(&wl_surface), None);
+    }
+
+    #[test]
+    fn constrained_size() {
+        let mut fixture = TestFixture::new();
+        let (surface, _toplevel, server_toplevel) = fixture.create_toplevel();
+        let window = Window::new_wayland_window(server_toplevel.clone());
+        let wl_surface = window.wl_surface().unwrap().into_owned();
+        let mut tracker = FirstFrameTracker::new(None);
+        tracker.add(window.clone(), time(0));
+
+        server_toplevel.with_pending_state(|state| {
+            state.states.set(xdg_toplevel::State::Maximized);
+            state.size = Some((800, 600).into());
+        });
+        server_toplevel.send_configure();
+        fixture.roundtrip();
+
+        // a frame drawn for the previous size
+        fixture.map(&surface, 1000, 700);
+        assert_eq!(tracker.commit(&wl_surface), None);
+
+        fixture.map(&surface, 800, 600);
+        assert_eq!(tracker.commit(&wl_surface), Some(window));
+    }
+
+    #[test]
+    fn wait_for_frame_callback() {
+        let mut fixture = TestFixture::new();
+        let (surface, _toplevel, server_toplevel) = fixture.create_toplevel();
+        let window = Window::new_wayland_window(server_toplevel.clone());
+        let wl_surface = window.wl_surface().unwrap().into_owned();
+        let mut tracker = FirstFrameTracker::new(None);
+        tracker.set_wait_for_frame_callback(true);
+        tracker.add(window.clone(), time(0));
+
+        fixture.map(&surface, 100, 100);
+        assert_eq!(tracker.commit(&wl_surface), None);
+        // the same frame reported again
+        assert_eq!(tracker.commit(&wl_surface), None);
+
+        fixture.map(&surface, 100, 100);
+        assert_eq!(tracker.commit(&wl_surface), Some(window));
+    }
+
+    #[test]
+    fn timeout() {
+        let mut fixture = TestFixture::new();
+        let (_, _, first) = fixture.create_toplevel();
+        let (_, _, second) = fixture.create_toplevel();
+        let first = Window::new_wayland_window(first);
+        let second = Window::new_wayland_window(second);
+
+        let mut tracker = FirstFrameTracker::new(None);
+        tracker.add(first.clone(), time(0));
+        assert_eq!(tracker.deadline(), None);
+        assert!(tracker.expire(time(10_000)).is_empty());
+        assert!(tracker.is_pending(&first));
+
+        tracker.set_timeout(Some(Duration::from_millis(100)));
+        tracker.add(second.clone(), time(50));
+        assert_eq!(tracker.deadline(), Some(time(100)));
+        assert!(tracker.expire(time(99)).is_empty());
+        assert_eq!(tracker.expire(time(100)), vec![first.clone()]);
+        assert_eq!(tracker.deadline(), Some(time(150)));
+
+        assert_eq!(tracker.remove(&second), Some(second));
+        assert_eq!(tracker.remove(&first), None);
+        assert_eq!(tracker.deadline(), None);
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wayland_server::{protocol::wl_surface::WlSurface, Resource};
+
+use super::utils::send_frames_surface_tree;
+use crate::{
+    output::Output,
+    utils::{Monotonic, Time},
+    wayland::compositor::{with_states, SurfaceData},
+};
+
+/// Group of surfaces receiving their frame callbacks paced to the flips of an output
+///
+/// All surfaces of a group, e.g. a game and its overlays, receive their frame callbacks at the same time,
+/// `lead` before the estimated next flip of the output, with the time of that flip as timestamp. This keeps
+/// clients rendering on frame callbacks in lockstep with each other and with the display, instead of with
+/// the end of compositor rendering, which reduces beat patterns and latency.
+///
+/// The group is a cheap handle and can be cloned.
+#[derive(Debug, Clone)]
+pub struct FramePacingGroup(Arc<Mutex<Duration>>);
+
+impl PartialEq for FramePacingGroup {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for FramePacingGroup {}
+
+crate::user_data_key!(FramePacingGroupUserData: Mutex<Option<FramePacingGroup>> = Mutex::new(None));
+
+impl FramePacingGroup {
+    /// Create a new group
+    ///
+    /// `lead` is the time the clients of the group need to produce a frame.
+    pub fn new(lead: Duration) -> Self {
+        FramePacingGroup(Arc::new(Mutex::new(lead)))
+    }
+
+    /// Returns how long before a flip the frame callbacks are sent
+    pub fn lead(&self) -> Duration {
+        *self.0.lock().unwrap()
+    }
+
+    /// Set how long before a flip the frame callbacks are sent
+    pub fn set_lead(&self, lead: Duration) {
+        *self.0.lock().unwrap() = lead;
+    }
+
+    /// Add a surface to this group, removing it from its previous group
+    ///
+    /// The group applies to the surface and its subsurfaces.
+    pub fn add_surface(&self, surface: &WlSurface) {
+        with_states(surface, |states| {
+            let data = states.data_map.get_key::<FramePacingGroupUserData>();
+            *data.lock().unwrap() = Some(self.clone());
+        });
+    }
+
+    /// Remove a surface from its group
+    pub fn remove_surface(surface: &WlSurface) {
+        with_states(surface, |states| {
+            if let Some(data) = states.data_map.try_get_key::<FramePacingGroupUserData>() {
+                *data.lock().unwrap() = None;
+            }
+        });
+    }
+
+    /// Returns the group of a surface
+    pub fn of_surface(surface: &WlSurface) -> Option<FramePacingGroup> {
+        with_states(surface, |states| {
+            states
+                .data_map
+                .try_get_key::<FramePacingGroupUserData>()
+                .and_then(|data| data.lock().unwrap().clone())
+        })
+    }
+}
+
+/// Paces frame callbacks of [`FramePacingGroup`]s to the flips of an output
+///
+/// One pacer should be kept per output. Flips have to be reported using [`FramePacer::presented`],
+/// usually from the presentation feedback of the backend. Frame callbacks are then sent through
+/// [`FramePacer::send_frames`] in place of [`send_frames_surface_tree`]: surfaces without a group receive
+/// them right away, while grouped surfaces are deferred until their group is due. The compositor has to
+/// call [`FramePacer::dispatch`] once [`FramePacer::deadline`] has passed, e.g. using a calloop timer.
+///
+/// Until the first flip was reported or while pacing is disabled, frame callbacks are sent right away.
+#[derive(Debug)]
+pub struct FramePacer {
+    enabled: bool,
+    last_flip: Option<Duration>,
+    refresh: Option<Duration>,
+    pending: Vec<PendingGroup>,
+}
+
+#[derive(Debug)]
+struct PendingGroup {
+    group: FramePacingGroup,
+    deadline: Duration,
+    flip: Duration,
+    surfaces: Vec<WlSurface>,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        FramePacer {
+            enabled: true,
+            last_flip: None,
+            refresh: None,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl FramePacer {
+    /// Create a new pacer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns if pacing is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable pacing, e.g. based on the configuration of the output
+    ///
+    /// Disabling pacing does not flush deferred frame callbacks, call [`FramePacer::dispatch`] for that.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            for pending in &mut self.pending {
+                pending.deadline = Duration::ZERO;
+            }
+        }
+    }
+
+    /// Report a flip of the output
+    ///
+    /// `refresh` is the refresh interval of the output, if known. Otherwise it is estimated
+    /// from consecutive flips.
+    pub fn presented(&mut self, time: Time<Monotonic>, refresh: Option<Duration>) {
+        let time = Duration::from(time);
+        let refresh = refresh.filter(|refresh| !refresh.is_zero()).or_else(|| {
+            // ignore repeated or out of order timestamps
+            let estimate = time
+                .checked_sub(self.last_flip?)
+                .filter(|estimate| !estimate.is_zero())?;
+            // ignore skipped flips
+            Some(self.refresh.map_or(estimate, |refresh| refresh.min(estimate)))
+        });
+        self.last_flip = Some(time);
+        if refresh.is_some() {
+            self.refresh = refresh;
+        }
+    }
+
+    /// Returns the estimated time of the next flip after `now`
+    pub fn next_flip(&self, now: Time<Monotonic>) -> Option<Time<Monotonic>> {
+        self.next_flip_after(now.into()).map(Time::from)
+    }
+
+    fn next_flip_after(&self, now: Duration) -> Option<Duration> {
+        let last_flip = self.last_flip?;
+        let refresh = self.refresh?;
+        if now < last_flip {
+            return Some(last_flip);
+        }
+        let flips = (now - last_flip).as_nanos() / refresh.as_nanos() + 1;
+        Some(last_flip + refresh * flips.min(u32::MAX as u128) as u32)
+    }
+
+    /// Returns when [`FramePacer::dispatch`] has to be called next
+    pub fn deadline(&self) -> Option<Time<Monotonic>> {
+        self.pending
+            .iter()
+            .map(|pending| pending.deadline)
+            .min()
+            .map(Time::from)
+    }
+
+    /// Send the frame callbacks of a surface tree on `output`
+    ///
+    /// Behaves like [`send_frames_surface_tree`], but defers the frame callbacks of surfaces
+    /// belonging to a [`FramePacingGroup`] until the group is due.
+    pub fn send_frames<F>(
+        &mut self,
+        surface: &WlSurface,
+        output: &Output,
+        now: Time<Monotonic>,
+        throttle: Option<Duration>,
+        mut primary_scan_out_output: F,
+    ) where
+        F: FnMut(&WlSurface, &SurfaceData) -> Option<Output>,
+    {
+        let now = Duration::from(now);
+        let group = FramePacingGroup::of_surface(surface).filter(|_| self.enabled);
+        let Some((group, flip)) = group.and_then(|group| {
+            let flip = self.flip_for(group.lead(), now)?;
+            Some((group, flip))
+        }) else {
+            send_frames_surface_tree(surface, output, now, throttle, primary_scan_out_output);
+            return;
+        };
+
+        // only defer surfaces on this output, everything else is subject to the throttle
+        let on_output = with_states(surface, |states| {
+            primary_scan_out_output(surface, states).is_some_and(|primary| primary == *output)
+        });
+        if !on_output {
+            send_frames_surface_tree(surface, output, now, throttle, primary_scan_out_output);
+            return;
+        }
+
+        match self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.group == group && pending.flip == flip)
+        {
+            Some(pending) => {
+                if !pending.surfaces.contains(surface) {
+                    pending.surfaces.push(surface.clone());
+                }
+            }
+            None => self.pending.push(PendingGroup {
+                deadline: flip.saturating_sub(group.lead()),
+                group,
+                flip,
+                surfaces: vec![surface.clone()],
+            }),
+        }
+    }
+
+    // the flip the frame callbacks sent at or after `now` target
+    fn flip_for(&self, lead: Duration, now: Duration) -> Option<Duration> {
+        let refresh = self.refresh?;
+        let mut flip = self.next_flip_after(now)?;
+        while flip.saturating_sub(lead) < now {
+            flip += refresh;
+        }
+        Some(flip)
+    }
+
+    /// Send the deferred frame callbacks of all groups due at `now`
+    ///
+    /// The callbacks carry the time of the flip they target.
+    pub fn dispatch(&mut self, output: &Output, now: Time<Monotonic>) {
+        let now = Duration::from(now);
+        let (due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|pending| pending.deadline <= now);
+        self.pending = pending;
+
+        for pending in due {
+            for surface in pending.surfaces.into_iter().filter(|surface| surface.is_alive()) {
+                send_frames_surface_tree(&surface, output, pending.flip, None, |_, _| Some(output.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FramePacer, FramePacingGroup, PendingGroup};
+    use crate::utils::Time;
+
+    #[test]
+    fn flip_estimation() {
+        let ms = Duration::from_millis;
+        let mut pacer = FramePacer::new();
+        assert_eq!(pacer.next_flip(Time::from(ms(5))), None);
+
+        pacer.presented(Time::from(ms(100)), None);
+        pacer.presented(Time::from(ms(116)), None);
+        // a skipped flip does not change the estimated refresh
+        pacer.presented(Time::from(ms(148)), None);
+        assert_eq!(pacer.next_flip(Time::from(ms(150))), Some(Time::from(ms(164))));
+        assert_eq!(pacer.next_flip(Time::from(ms(164))), Some(Time::from(ms(180))));
+
+        // callbacks are sent `lead` before the flip, targeting the next flip if that already passed
+        assert_eq!(pacer.flip_for(ms(4), ms(150)), Some(ms(164)));
+        assert_eq!(pacer.flip_for(ms(4), ms(161)), Some(ms(180)));
+
+        pacer.presented(Time::from(ms(200)), Some(ms(10)));
+        assert_eq!(pacer.next_flip(Time::from(ms(200))), Some(Time::from(ms(210))));
+    }
+
+    #[test]
+    fn repeated_timestamps_do_not_estimate_a_refresh() {
+        let ms = Duration::from_millis;
+        let mut pacer = FramePacer::new();
+
+        pacer.presented(Time::from(ms(100)), None);
+        pacer.presented(Time::from(ms(100)), None);
+        assert_eq!(pacer.next_flip(Time::from(ms(105))), None);
+        assert_eq!(pacer.flip_for(ms(4), ms(105)), None);
+
+        pacer.presented(Time::from(ms(116)), None);
+        pacer.presented(Time::from(ms(116)), None);
+        assert_eq!(pacer.next_flip(Time::from(ms(120))), Some(Time::from(ms(132))));
+        assert_eq!(pacer.flip_for(ms(4), ms(120)), Some(ms(132)));
+    }
+
+    #[test]
+    fn out_of_order_timestamps_keep_the_refresh() {
+        let ms = Duration::from_millis;
+        let mut pacer = FramePacer::new();
+
+        pacer.presented(Time::from(ms(100)), None);
+        pacer.presented(Time::from(ms(116)), None);
+        pacer.presented(Time::from(ms(110)), None);
+        assert_eq!(pacer.next_flip(Time::from(ms(111))), Some(Time::from(ms(126))));
+        // flips before the last one target the last one
+        assert_eq!(pacer.next_flip(Time::from(ms(105))), Some(Time::from(ms(110))));
+    }
+
+    #[test]
+    fn reported_refresh() {
+        let ms = Duration::from_millis;
+        let mut pacer = FramePacer::new();
+
+        // a zero refresh is treated as unknown
+        pacer.presented(Time::from(ms(100)), Some(Duration::ZERO));
+        assert_eq!(pacer.next_flip(Time::from(ms(100))), None);
+        pacer.presented(Time::from(ms(120)), Some(Duration::ZERO));
+        assert_eq!(pacer.next_flip(Time::from(ms(120))), Some(Time::from(ms(140))));
+
+        // a reported refresh replaces the estimate, even if it is longer
+        pacer.presented(Time::from(ms(140)), Some(ms(25)));
+        assert_eq!(pacer.next_flip(Time::from(ms(140))), Some(Time::from(ms(165))));
+        // and is kept for flips without one
+        pacer.presented(Time::from(ms(190)), None);
+        assert_eq!(pacer.next_flip(Time::from(ms(190))), Some(Time::from(ms(215))));
+    }
+
+    #[test]
+    fn lead_longer_than_refresh() {
+        let ms = Duration::from_millis;
+        let mut pacer = FramePacer::new();
+        pacer.presented(Time::from(ms(100)), Some(ms(10)));
+
+        // the callbacks target the first flip leaving the group enough time
+        assert_eq!(pacer.flip_for(ms(25), ms(100)), Some(ms(130)));
+        assert_eq!(pacer.flip_for(ms(25), ms(106)), Some(ms(140)));
+        assert_eq!(pacer.flip_for(Duration::ZERO, ms(106)), Some(ms(110)));
+    }
+
+    #[test]
+    fn disabling_flushes_deadlines() {
+        let ms = Duration::from_millis;
+        let mut pacer = FramePacer::new();
+        assert_eq!(pacer.deadline(), None);
+
+        for (lead, flip) in [(ms(4), ms(116)), (ms(8), ms(116)), (ms(4), ms(132))] {
+            pacer.pending.push(PendingGroup {
+                group: FramePacingGroup::new(lead),
+                deadline: flip - lead,
+                flip,
+                surfaces: Vec::new(),
+            });
+        }
+        assert_eq!(pacer.deadline(), Some(Time::from(ms(108))));
+
+        pacer.set_enabled(false);
+        assert!(!pacer.is_enabled());
+        assert_eq!(pacer.deadline(), Some(Time::from(Duration::ZERO)));
+    }
+}
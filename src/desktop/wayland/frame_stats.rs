@@ -0,0 +1,232 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    utils::{Monotonic, Time},
+    wayland::compositor::{with_states, SurfaceData},
+};
+
+/// Frame statistics of a surface
+///
+/// All values are calculated over the window configured in [`enable_frame_stats`].
+/// Comparing the commit rate with the presentation rate and the latency helps to tell apart
+/// clients being slow to produce frames from the compositor being slow to present them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SurfaceFrameStats {
+    /// Number of commits per second
+    pub commit_rate: f64,
+    /// Number of presented commits per second
+    pub presentation_rate: f64,
+    /// Average time from commit to presentation
+    pub average_latency: Option<Duration>,
+    /// Longest time from commit to presentation
+    pub max_latency: Option<Duration>,
+    /// Number of commits, which were never presented
+    ///
+    /// A commit is dropped, if it is replaced by a newer commit before it was presented
+    /// or if its presentation was discarded.
+    pub dropped_frames: usize,
+}
+
+crate::user_data_key!(
+    FrameStatsUserData: Arc<Mutex<FrameStatsState>> = Arc::new(Mutex::new(FrameStatsState::new(Duration::ZERO)))
+);
+
+#[derive(Debug)]
+struct FrameStatsState {
+    enabled: bool,
+    window: Duration,
+    commits: VecDeque<Time<Monotonic>>,
+    // presentation time and latency
+    presented: VecDeque<(Time<Monotonic>, Duration)>,
+    dropped: VecDeque<Time<Monotonic>>,
+    pending: Option<Time<Monotonic>>,
+}
+
+impl FrameStatsState {
+    fn new(window: Duration) -> Self {
+        FrameStatsState {
+            enabled: true,
+            window,
+            commits: VecDeque::new(),
+            presented: VecDeque::new(),
+            dropped: VecDeque::new(),
+            pending: None,
+        }
+    }
+
+    fn commit(&mut self, now: Time<Monotonic>) {
+        if self.pending.replace(now).is_some() {
+            self.dropped.push_back(now);
+        }
+        self.commits.push_back(now);
+        self.prune(now);
+    }
+
+    fn presented(&mut self, committed: Time<Monotonic>, presented: Time<Monotonic>) {
+        self.presented
+            .push_back((presented, Time::elapsed(&committed, presented)));
+        self.prune(presented);
+    }
+
+    fn discarded(&mut self, now: Time<Monotonic>) {
+        self.dropped.push_back(now);
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: Time<Monotonic>) {
+        let window = self.window;
+        let expired = |time: &Time<Monotonic>| *time + window < now;
+        while self.commits.front().is_some_and(expired) {
+            self.commits.pop_front();
+        }
+        while self.presented.front().is_some_and(|(time, _)| expired(time)) {
+            self.presented.pop_front();
+        }
+        while self.dropped.front().is_some_and(expired) {
+            self.dropped.pop_front();
+        }
+    }
+
+    fn stats(&mut self, now: Time<Monotonic>) -> SurfaceFrameStats {
+        self.prune(now);
+
+        let secs = self.window.as_secs_f64();
+        let rate = |count: usize| if secs > 0.0 { count as f64 / secs } else { 0.0 };
+        let latencies = self.presented.iter().map(|(_, latency)| *latency);
+        SurfaceFrameStats {
+            commit_rate: rate(self.commits.len()),
+            presentation_rate: rate(self.presented.len()),
+            average_latency: (!self.presented.is_empty())
+                .then(|| latencies.clone().sum::<Duration>() / self.presented.len() as u32),
+            max_latency: latencies.max(),
+            dropped_frames: self.dropped.len(),
+        }
+    }
+}
+
+/// Enable the collection of frame statistics for a surface
+///
+/// Statistics are calculated over the last `window`. Calling this again changes the window
+/// of already collected statistics.
+///
+/// Commits have to be reported using [`record_frame_commit`], presentations are tracked
+/// through [`take_presentation_feedback_surface_tree`](super::utils::take_presentation_feedback_surface_tree).
+pub fn enable_frame_stats(surface: &WlSurface, window: Duration) {
+    with_states(surface, |states| {
+        let stats = states.data_map.get_key::<FrameStatsUserData>();
+        let mut stats = stats.lock().unwrap();
+        stats.enabled = true;
+        stats.window = window;
+    });
+}
+
+/// Disable the collection of frame statistics for a surface and clear collected statistics
+pub fn disable_frame_stats(surface: &WlSurface) {
+    with_states(surface, |states| {
+        if let Some(stats) = states.data_map.try_get_key::<FrameStatsUserData>() {
+            let mut stats = stats.lock().unwrap();
+            let window = stats.window;
+            *stats = FrameStatsState::new(window);
+            stats.enabled = false;
+        }
+    });
+}
+
+/// Record a commit of a surface at `now`
+///
+/// This should be called from [`CompositorHandler::commit`](crate::wayland::compositor::CompositorHandler::commit)
+/// for commits attaching a new buffer. Does nothing, if statistics are not enabled for the surface.
+pub fn record_frame_commit(surface: &WlSurface, now: Time<Monotonic>) {
+    with_states(surface, |states| {
+        if let Some(stats) = states.data_map.try_get_key::<FrameStatsUserData>() {
+            let mut stats = stats.lock().unwrap();
+            if stats.enabled {
+                stats.commit(now);
+            }
+        }
+    });
+}
+
+/// Returns the frame statistics of a surface at `now`
+///
+/// Returns `None`, if statistics are not enabled for the surface.
+pub fn surface_frame_stats(surface: &WlSurface, now: Time<Monotonic>) -> Option<SurfaceFrameStats> {
+    with_states(surface, |states| {
+        let stats = states.data_map.try_get_key::<FrameStatsUserData>()?;
+        let mut stats = stats.lock().unwrap();
+        stats.enabled.then(|| stats.stats(now))
+    })
+}
+
+/// Pending commit of a surface waiting for its presentation
+#[derive(Debug)]
+pub(crate) struct PendingFrame {
+    stats: Arc<Mutex<FrameStatsState>>,
+    committed: Time<Monotonic>,
+}
+
+impl PendingFrame {
+    pub(crate) fn take_from_states(states: &SurfaceData) -> Option<Self> {
+        let stats = states.data_map.try_get_key::<FrameStatsUserData>()?;
+        let committed = stats.lock().unwrap().pending.take()?;
+        Some(PendingFrame {
+            stats: stats.clone(),
+            committed,
+        })
+    }
+
+    pub(crate) fn presented(self, time: Time<Monotonic>) {
+        let mut stats = self.stats.lock().unwrap();
+        if stats.enabled {
+            stats.presented(self.committed, time);
+        }
+    }
+
+    pub(crate) fn discarded(self) {
+        let mut stats = self.stats.lock().unwrap();
+        if stats.enabled {
+            stats.discarded(self.committed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FrameStatsState;
+    use crate::utils::{Monotonic, Time};
+
+    fn ms(ms: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn latency_and_dropped_frames() {
+        let mut state = FrameStatsState::new(Duration::from_secs(1));
+        state.commit(ms(1000));
+        // replaced before being presented
+        state.commit(ms(1010));
+        let committed = state.pending.take().unwrap();
+        state.presented(committed, ms(1030));
+
+        let stats = state.stats(ms(1100));
+        assert_eq!(stats.commit_rate, 2.0);
+        assert_eq!(stats.presentation_rate, 1.0);
+        assert_eq!(stats.average_latency, Some(Duration::from_millis(20)));
+        assert_eq!(stats.max_latency, Some(Duration::from_millis(20)));
+        assert_eq!(stats.dropped_frames, 1);
+
+        // everything left the window
+        let stats = state.stats(ms(3000));
+        assert_eq!(stats.commit_rate, 0.0);
+        assert_eq!(stats.average_latency, None);
+        assert_eq!(stats.dropped_frames, 0);
+    }
+}
@@ -0,0 +1,186 @@
+use std::borrow::Cow;
+
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    backend::renderer::{
+        element::{
+            surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+            Kind,
+        },
+        ImportAll, Renderer,
+    },
+    desktop::{space::SpaceElement, utils::bbox_from_surface_tree, Space},
+    output::{Output, WeakOutput},
+    utils::{IsAlive, Logical, Point, Rectangle, Scale},
+    wayland::{
+        seat::WaylandFocus,
+        shell::fullscreen::{FullscreenSurface, PresentMethod},
+    },
+};
+
+/// Placement of a [`FullscreenSurface`] on an [`Output`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FullscreenPlacement {
+    /// Area covered by the surface tree, relative to the output
+    pub geometry: Rectangle<i32, Logical>,
+    /// Location of the origin of the main surface, relative to the output
+    pub location: Point<f64, Logical>,
+    /// Scale to apply to the surface tree in addition to the output scale
+    pub scale: Scale<f64>,
+}
+
+/// Calculate the placement of a [`FullscreenSurface`] on a given output
+///
+/// The surface is centered on the output and scaled according to its [`PresentMethod`].
+/// [`PresentMethod::Default`] scales the surface down preserving its aspect ratio, if it doesn't fit
+/// the output, and centers it otherwise.
+///
+/// Returns `None` if the output has no mode set or the surface is not mapped.
+pub fn fullscreen_surface_placement(
+    surface: &FullscreenSurface,
+    output: &Output,
+) -> Option<FullscreenPlacement> {
+    let mode = output.current_mode()?;
+    let output_size = output
+        .current_transform()
+        .transform_size(mode.size)
+        .to_f64()
+        .to_logical(output.current_scale().fractional_scale());
+
+    let bbox = bbox_from_surface_tree(surface.wl_surface(), (0, 0));
+    if bbox.is_empty() {
+        return None;
+    }
+    let surface_size = bbox.size.to_f64();
+
+    let fit = f64::min(output_size.w / surface_size.w, output_size.h / surface_size.h);
+    let scale = match surface.method() {
+        PresentMethod::Default if fit < 1.0 => Scale::from(fit),
+        PresentMethod::Zoom => Scale::from(fit),
+        PresentMethod::ZoomCrop => Scale::from(f64::max(
+            output_size.w / surface_size.w,
+            output_size.h / surface_size.h,
+        )),
+        PresentMethod::Stretch => {
+            Scale::from((output_size.w / surface_size.w, output_size.h / surface_size.h))
+        }
+        _ => Scale::from(1.0),
+    };
+
+    let size = surface_size.upscale(scale);
+    let origin = Point::from(((output_size.w - size.w) / 2.0, (output_size.h - size.h) / 2.0));
+    Some(FullscreenPlacement {
+        geometry: Rectangle::new(origin, size).to_i32_round(),
+        location: origin - bbox.loc.to_f64().upscale(scale),
+        scale,
+    })
+}
+
+/// Retrieve the [`WaylandSurfaceRenderElement`]s of a [`FullscreenSurface`] presented on an output
+///
+/// The elements are positioned relative to the output and can directly be used to render the output,
+/// e.g. with a [`DrmCompositor`](crate::backend::drm::compositor::DrmCompositor).
+///
+/// Returns no elements if the surface can't be placed, see [`fullscreen_surface_placement`].
+pub fn render_elements_from_fullscreen_surface<R, E>(
+    renderer: &mut R,
+    surface: &FullscreenSurface,
+    output: &Output,
+    alpha: f32,
+    kind: Kind,
+) -> Vec<E>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Clone + 'static,
+    E: From<WaylandSurfaceRenderElement<R>>,
+{
+    let Some(placement) = fullscreen_surface_placement(surface, output) else {
+        return Vec::new();
+    };
+    let output_scale = output.current_scale().fractional_scale();
+    render_elements_from_surface_tree(
+        renderer,
+        surface.wl_surface(),
+        placement.location.to_physical(output_scale).to_i32_round(),
+        placement.scale * output_scale,
+        alpha,
+        kind,
+    )
+}
+
+/// A [`FullscreenSurface`] presented on a specific [`Output`], usable as a [`SpaceElement`]
+///
+/// Use [`map_fullscreen_surface`] to map it into a [`Space`] covering its output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullscreenElement {
+    surface: FullscreenSurface,
+    output: WeakOutput,
+}
+
+impl FullscreenElement {
+    /// Create a new element for a surface presented on the given output
+    pub fn new(surface: FullscreenSurface, output: &Output) -> Self {
+        FullscreenElement {
+            surface,
+            output: output.downgrade(),
+        }
+    }
+
+    /// Returns the presented [`FullscreenSurface`]
+    pub fn surface(&self) -> &FullscreenSurface {
+        &self.surface
+    }
+
+    /// Returns the output the surface is presented on, if it still exists
+    pub fn output(&self) -> Option<Output> {
+        self.output.upgrade()
+    }
+
+    /// Returns the current placement of the surface on its output
+    pub fn placement(&self) -> Option<FullscreenPlacement> {
+        self.output
+            .upgrade()
+            .and_then(|output| fullscreen_surface_placement(&self.surface, &output))
+    }
+}
+
+impl IsAlive for FullscreenElement {
+    #[inline]
+    fn alive(&self) -> bool {
+        self.surface.alive() && self.output.is_alive()
+    }
+}
+
+impl WaylandFocus for FullscreenElement {
+    #[inline]
+    fn wl_surface(&self) -> Option<Cow<'_, WlSurface>> {
+        Some(Cow::Borrowed(self.surface.wl_surface()))
+    }
+}
+
+/// Map a [`FullscreenElement`] into a [`Space`] covering its output
+///
+/// Returns `false` if the element could not be placed, because its output is not mapped in the space
+/// or the surface is not mapped.
+pub fn map_fullscreen_surface<E>(space: &mut Space<E>, element: FullscreenElement) -> bool
+where
+    E: SpaceElement + PartialEq + From<FullscreenElement>,
+{
+    let Some(output) = element.output() else {
+        return false;
+    };
+    let Some(output_geometry) = space.output_geometry(&output) else {
+        return false;
+    };
+    let Some(placement) = fullscreen_surface_placement(&element.surface, &output) else {
+        return false;
+    };
+
+    space.map_element(
+        E::from(element),
+        output_geometry.loc + placement.geometry.loc,
+        true,
+    );
+    true
+}
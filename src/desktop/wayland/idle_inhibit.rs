@@ -0,0 +1,397 @@
+use std::{fmt, time::Duration};
+
+use wayland_protocols::wp::content_type::v1::server::wp_content_type_v1;
+use wayland_server::{protocol::wl_surface::WlSurface, Resource};
+
+use super::frame_stats::{enable_frame_stats, surface_frame_stats};
+use crate::{
+    desktop::{layer_map_for_output, space::SpaceElement, Space},
+    utils::{Monotonic, Time},
+    wayland::{
+        compositor::{with_surface_tree_downward, TraversalAction},
+        content_type::ContentTypeSurfaceCachedState,
+        seat::WaylandFocus,
+    },
+};
+
+/// Reason for a surface inhibiting idle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdleInhibitReason {
+    /// The client created an idle inhibitor for the surface
+    Inhibitor,
+    /// The fullscreen surface announced video or game content
+    ContentType(wp_content_type_v1::Type),
+    /// The fullscreen surface commits new frames at the given rate per second
+    FrameRate(f64),
+}
+
+type VetoFn = Box<dyn Fn(&WlSurface, IdleInhibitReason) -> bool>;
+
+/// Policy deciding if idle should be inhibited
+///
+/// Besides explicit inhibitors created through the [idle-inhibit protocol](crate::wayland::idle_inhibit),
+/// which only count while their surface or the surface it is a subsurface of is visible, idle is inhibited automatically for visible fullscreen
+/// surfaces playing content. A fullscreen surface is considered playing if any surface of its tree announced
+/// video or game content through the [content-type protocol](crate::wayland::content_type), or if it commits
+/// frames at least at the rate configured by [`IdleInhibitPolicy::with_min_frame_rate`]. This matches
+/// the expectations of users for video playback without requiring support by every application.
+///
+/// The frame rate heuristic enables [frame statistics](super::utils::enable_frame_stats) for fullscreen
+/// surfaces, which only works if commits are reported using [`record_frame_commit`](super::utils::record_frame_commit).
+///
+/// ```no_run
+/// # use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+/// use smithay::desktop::IdleInhibitPolicy;
+/// use smithay::utils::{Clock, Monotonic};
+///
+/// # let fullscreen_surface: WlSurface = todo!();
+/// # let other_surface: WlSurface = todo!();
+/// let clock = Clock::<Monotonic>::new();
+/// let mut policy = IdleInhibitPolicy::new()
+///     // e.g. don't let a background client keep the session awake
+///     .with_veto(|_surface, _reason| false);
+///
+/// // forward `IdleInhibitHandler::inhibit` and `IdleInhibitHandler::uninhibit`
+/// policy.inhibit(other_surface.clone());
+///
+/// // every frame, with the visible surfaces and if they are fullscreen
+/// let inhibited = policy.update([(&fullscreen_surface, true), (&other_surface, false)], clock.now());
+/// // idle_notifier_state.set_is_inhibited(inhibited);
+/// ```
+///
+/// Compositors using a [`Space`] can use [`IdleInhibitPolicy::update_for_space`] instead, which also
+/// takes the layer surfaces of its outputs into account.
+pub struct IdleInhibitPolicy {
+    inhibitors: Vec<WlSurface>,
+    min_frame_rate: Option<f64>,
+    frame_stats_window: Duration,
+    content_types: bool,
+    veto: Option<VetoFn>,
+    inhibited: bool,
+}
+
+impl fmt::Debug for IdleInhibitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdleInhibitPolicy")
+            .field("inhibitors", &self.inhibitors)
+            .field("min_frame_rate", &self.min_frame_rate)
+            .field("frame_stats_window", &self.frame_stats_window)
+            .field("content_types", &self.content_types)
+            .field("veto", &self.veto.is_some())
+            .field("inhibited", &self.inhibited)
+            .finish()
+    }
+}
+
+impl Default for IdleInhibitPolicy {
+    fn default() -> Self {
+        IdleInhibitPolicy {
+            inhibitors: Vec::new(),
+            min_frame_rate: Some(20.0),
+            frame_stats_window: Duration::from_secs(2),
+            content_types: true,
+            veto: None,
+            inhibited: false,
+        }
+    }
+}
+
+impl IdleInhibitPolicy {
+    /// Create a new policy
+    ///
+    /// By default fullscreen surfaces announcing video or game content or committing at least
+    /// 20 frames per second inhibit idle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the frame rate a fullscreen surface has to commit at to inhibit idle, `None` disables the heuristic
+    pub fn with_min_frame_rate(mut self, rate: Option<f64>) -> Self {
+        self.min_frame_rate = rate;
+        self
+    }
+
+    /// Set the window the frame rate of fullscreen surfaces is averaged over
+    ///
+    /// Only applies to surfaces, which don't have frame statistics enabled yet.
+    pub fn with_frame_stats_window(mut self, window: Duration) -> Self {
+        self.frame_stats_window = window;
+        self
+    }
+
+    /// Set if fullscreen surfaces announcing video or game content inhibit idle
+    pub fn with_content_types(mut self, enabled: bool) -> Self {
+        self.content_types = enabled;
+        self
+    }
+
+    /// Set a callback to veto surfaces from inhibiting idle
+    ///
+    /// The callback is called for every surface, which would inhibit idle, and returns `true`
+    /// to ignore it.
+    pub fn with_veto<F>(mut self, veto: F) -> Self
+    where
+        F: Fn(&WlSurface, IdleInhibitReason) -> bool + 'static,
+    {
+        self.veto = Some(Box::new(veto));
+        self
+    }
+
+    /// Register an idle inhibitor of a surface
+    ///
+    /// This should be called from [`IdleInhibitHandler::inhibit`](crate::wayland::idle_inhibit::IdleInhibitHandler::inhibit).
+    pub fn inhibit(&mut self, surface: WlSurface) {
+        if !self.inhibitors.contains(&surface) {
+            self.inhibitors.push(surface);
+        }
+    }
+
+    /// Remove the idle inhibitor of a surface
+    ///
+    /// This should be called from [`IdleInhibitHandler::uninhibit`](crate::wayland::idle_inhibit::IdleInhibitHandler::uninhibit).
+    pub fn uninhibit(&mut self, surface: &WlSurface) {
+        self.inhibitors.retain(|s| s != surface);
+    }
+
+    /// Returns if idle was inhibited by the last call to [`IdleInhibitPolicy::update`]
+    pub fn is_inhibited(&self) -> bool {
+        self.inhibited
+    }
+
+    /// Returns why a visible surface would inhibit idle, ignoring the veto callback
+    ///
+    /// Inhibitors of the subsurfaces of `surface` are taken into account.
+    pub fn reason(
+        &self,
+        surface: &WlSurface,
+        fullscreen: bool,
+        now: Time<Monotonic>,
+    ) -> Option<IdleInhibitReason> {
+        if self.has_inhibitor(surface) {
+            return Some(IdleInhibitReason::Inhibitor);
+        }
+        if !fullscreen {
+            return None;
+        }
+
+        if self.content_types {
+            if let Some(content_type) = playing_content_type(surface) {
+                return Some(IdleInhibitReason::ContentType(content_type));
+            }
+        }
+
+        let min_rate = self.min_frame_rate?;
+        let stats = surface_frame_stats(surface, now)?;
+        (stats.commit_rate >= min_rate).then_some(IdleInhibitReason::FrameRate(stats.commit_rate))
+    }
+
+    /// Update the policy with the currently visible surfaces
+    ///
+    /// `visible` contains every visible toplevel surface together with if it is shown fullscreen.
+    /// Returns if idle should be inhibited, which can be forwarded to
+    /// [`IdleNotifierState::set_is_inhibited`](crate::wayland::idle_notify::IdleNotifierState::set_is_inhibited).
+    pub fn update<'a, I>(&mut self, visible: I, now: Time<Monotonic>) -> bool
+    where
+        I: IntoIterator<Item = (&'a WlSurface, bool)>,
+    {
+        self.inhibitors.retain(|surface| surface.is_alive());
+
+        let mut inhibited = false;
+        for (surface, fullscreen) in visible {
+            if fullscreen && self.min_frame_rate.is_some() && surface_frame_stats(surface, now).is_none() {
+                enable_frame_stats(surface, self.frame_stats_window);
+            }
+
+            if inhibited {
+                continue;
+            }
+            if let Some(reason) = self.reason(surface, fullscreen, now) {
+                let vetoed = self.veto.as_ref().is_some_and(|veto| veto(surface, reason));
+                inhibited = !vetoed;
+            }
+        }
+
+        self.inhibited = inhibited;
+        inhibited
+    }
+
+    /// Update the policy with the elements and layer surfaces visible on the outputs of a [`Space`]
+    ///
+    /// `fullscreen` returns if an element is shown fullscreen. Layer surfaces are never considered
+    /// fullscreen, so they only inhibit idle through explicit inhibitors, e.g. for video wallpapers.
+    /// See [`IdleInhibitPolicy::update`].
+    pub fn update_for_space<E, F>(&mut self, space: &Space<E>, fullscreen: F, now: Time<Monotonic>) -> bool
+    where
+        E: SpaceElement + PartialEq + WaylandFocus,
+        F: Fn(&E) -> bool,
+    {
+        let mut visible: Vec<(WlSurface, bool)> = Vec::new();
+        for output in space.outputs() {
+            let map = layer_map_for_output(output);
+            let layers = map.layers().map(|layer| (layer.wl_surface().clone(), false));
+            let elements = space.elements_for_output(output).filter_map(|element| {
+                element
+                    .wl_surface()
+                    .map(|surface| (surface.into_owned(), fullscreen(element)))
+            });
+            for entry in layers.chain(elements) {
+                // elements spanning multiple outputs are only visited once
+                if !visible.iter().any(|(surface, _)| *surface == entry.0) {
+                    visible.push(entry);
+                }
+            }
+        }
+
+        self.update(
+            visible.iter().map(|(surface, fullscreen)| (surface, *fullscreen)),
+            now,
+        )
+    }
+
+    fn has_inhibitor(&self, surface: &WlSurface) -> bool {
+        if self.inhibitors.is_empty() {
+            return false;
+        }
+
+        let mut found = false;
+        with_surface_tree_downward(
+            surface,
+            (),
+            |surface, _, _| {
+                if self.inhibitors.contains(surface) {
+                    found = true;
+                    TraversalAction::Break
+                } else {
+                    TraversalAction::DoChildren(())
+                }
+            },
+            |_, _, _| {},
+            |_, _, _| true,
+        );
+        found
+    }
+}
+
+fn playing_content_type(surface: &WlSurface) -> Option<wp_content_type_v1::Type> {
+    let mut found = None;
+    with_surface_tree_downward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |_, states, _| {
+            let content_type = *states
+                .cached_state
+                .get::<ContentTypeSurfaceCachedState>()
+                .current()
+                .content_type();
+            if matches!(
+                content_type,
+                wp_content_type_v1::Type::Video | wp_content_type_v1::Type::Game
+            ) {
+                found.get_or_insert(content_type);
+            }
+        },
+        |_, _, _| true,
+    );
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1;
+
+    use super::{IdleInhibitPolicy, IdleInhibitReason};
+    use crate::{
+        desktop::{layer_map_for_output, LayerSurface, Space, Window},
+        output::{Mode, Output, PhysicalProperties, Subpixel},
+        utils::{Clock, Monotonic},
+        wayland::test_utils::TestFixture,
+    };
+
+    fn output() -> Output {
+        let output = Output::new(
+            "test".into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Test".into(),
+            },
+        );
+        output.change_current_state(
+            Some(Mode {
+                size: (1920, 1080).into(),
+                refresh: 60_000,
+            }),
+            None,
+            None,
+            None,
+        );
+        output
+    }
+
+    #[test]
+    fn subsurface_inhibitors() {
+        let clock = Clock::<Monotonic>::new();
+        let mut fixture = TestFixture::new();
+        let (parent, server_parent) = fixture.create_surface();
+        let (_child, _subsurface, server_child) = fixture.create_subsurface(&parent);
+
+        let mut policy = IdleInhibitPolicy::new().with_min_frame_rate(None);
+        policy.inhibit(server_child.clone());
+        assert_eq!(
+            policy.reason(&server_parent, false, clock.now()),
+            Some(IdleInhibitReason::Inhibitor)
+        );
+        assert!(policy.update([(&server_parent, false)], clock.now()));
+
+        policy.uninhibit(&server_child);
+        assert!(!policy.update([(&server_parent, false)], clock.now()));
+    }
+
+    #[test]
+    fn vetoed_inhibitors() {
+        let clock = Clock::<Monotonic>::new();
+        let mut fixture = TestFixture::new();
+        let (_surface, server_surface) = fixture.create_surface();
+
+        let mut policy = IdleInhibitPolicy::new()
+            .with_min_frame_rate(None)
+            .with_veto(|_, reason| reason == IdleInhibitReason::Inhibitor);
+        policy.inhibit(server_surface.clone());
+        assert!(!policy.update([(&server_surface, false)], clock.now()));
+        assert!(!policy.is_inhibited());
+    }
+
+    #[test]
+    fn space_layer_surfaces() {
+        let clock = Clock::<Monotonic>::new();
+        let mut fixture = TestFixture::new();
+        let output = output();
+        let mut space = Space::<Window>::default();
+        space.map_output(&output, (0, 0));
+
+        let (surface, _, toplevel) = fixture.create_toplevel();
+        fixture.map(&surface, 100, 100);
+        let window = Window::new_wayland_window(toplevel.clone());
+        space.map_element(window, (0, 0), false);
+
+        let (layer_surface, _, layer) =
+            fixture.create_layer_surface(zwlr_layer_shell_v1::Layer::Background, (100, 100));
+        fixture.map(&layer_surface, 100, 100);
+        layer_map_for_output(&output)
+            .map_layer(&LayerSurface::new(layer.clone(), "test".into()))
+            .unwrap();
+
+        let mut policy = IdleInhibitPolicy::new().with_min_frame_rate(None);
+        assert!(!policy.update_for_space(&space, |_| false, clock.now()));
+
+        // e.g. a video wallpaper
+        policy.inhibit(layer.wl_surface().clone());
+        assert!(policy.update_for_space(&space, |_| false, clock.now()));
+
+        // layer surfaces of outputs not part of the space are not visible
+        space.unmap_output(&output);
+        assert!(!policy.update_for_space(&space, |_| false, clock.now()));
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use crate::{
+    input::pointer::CursorIcon,
+    utils::{Monotonic, Time},
+    wayland::xdg_activation::{XdgActivationToken, XdgActivationTokenData},
+};
+
+#[cfg(feature = "xwayland")]
+use crate::xwayland::{xwm::startup::StartupNotification, X11Surface};
+
+/// An application being launched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+    /// Id of the launch
+    ///
+    /// This is the xdg activation token or the X11 startup id, which are usually the same,
+    /// if the launcher sets both `XDG_ACTIVATION_TOKEN` and `DESKTOP_STARTUP_ID`.
+    pub id: String,
+    /// App id of the launched application, if known
+    ///
+    /// For X11 applications this is the `WMCLASS` announced by the launcher.
+    pub app_id: Option<String>,
+    /// Human readable name of the launched application, if known
+    pub name: Option<String>,
+    /// Whether a busy cursor should be shown while the application is launching
+    pub busy_cursor: bool,
+    /// Point in time the launch started
+    pub started: Time<Monotonic>,
+}
+
+impl Launch {
+    /// Create a new launch with the given id started at `now`
+    pub fn new(id: impl Into<String>, now: Time<Monotonic>) -> Self {
+        Launch {
+            id: id.into(),
+            app_id: None,
+            name: None,
+            busy_cursor: true,
+            started: now,
+        }
+    }
+}
+
+/// Tracks applications being launched to provide launch feedback
+///
+/// Launches are either started by the compositor, e.g. for tokens created using
+/// [`XdgActivationState::create_external_token`](crate::wayland::xdg_activation::XdgActivationState::create_external_token),
+/// or announced by clients through xdg activation tokens ([`LaunchTracker::start_activation`])
+/// and X11 startup notification ([`LaunchTracker::startup_notification`]).
+///
+/// A launch is resolved once the launched application shows up, either by requesting activation
+/// with the token of the launch ([`LaunchTracker::resolve_token`]), by mapping a toplevel with
+/// a matching app id ([`LaunchTracker::resolve_app_id`]) or by mapping an X11 window with a matching
+/// startup id or class ([`LaunchTracker::resolve_x11`]).
+///
+/// Applications failing to start never resolve their launch, so launches are dropped after a timeout.
+/// Call [`LaunchTracker::expire`] once [`LaunchTracker::deadline`] has passed.
+///
+/// While any launch requests it, [`LaunchTracker::cursor_icon`] returns a busy cursor, that can be shown
+/// instead of the default cursor.
+#[derive(Debug)]
+pub struct LaunchTracker {
+    timeout: Duration,
+    launches: Vec<Launch>,
+}
+
+impl LaunchTracker {
+    /// Create a new tracker dropping launches after `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        LaunchTracker {
+            timeout,
+            launches: Vec::new(),
+        }
+    }
+
+    /// Returns the time after which unresolved launches are dropped
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set the time after which unresolved launches are dropped
+    ///
+    /// Applies to already tracked launches as well.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Start tracking a launch
+    ///
+    /// Replaces an already tracked launch with the same id.
+    pub fn start(&mut self, launch: Launch) {
+        self.launches.retain(|l| l.id != launch.id);
+        self.launches.push(launch);
+    }
+
+    /// Start tracking a launch for an xdg activation token
+    ///
+    /// This is usually called from [`XdgActivationHandler::token_created`](crate::wayland::xdg_activation::XdgActivationHandler::token_created)
+    /// or after creating an external token to launch an application.
+    pub fn start_activation(
+        &mut self,
+        token: &XdgActivationToken,
+        data: &XdgActivationTokenData,
+        now: Time<Monotonic>,
+    ) {
+        let mut launch = Launch::new(token.as_str(), now);
+        launch.app_id = data.app_id.clone();
+        self.start(launch);
+    }
+
+    /// Update the tracked launches from an X11 startup notification message
+    ///
+    /// Returns the launch, if the message completed or canceled it.
+    #[cfg(feature = "xwayland")]
+    pub fn startup_notification(
+        &mut self,
+        notification: StartupNotification,
+        now: Time<Monotonic>,
+    ) -> Option<Launch> {
+        match notification {
+            StartupNotification::New { id, properties } => {
+                let mut launch = Launch::new(id, now);
+                launch.app_id = properties.get("WMCLASS").cloned();
+                launch.name = properties.get("NAME").cloned();
+                self.start(launch);
+                None
+            }
+            StartupNotification::Change { id, properties } => {
+                let launch = self.launches.iter_mut().find(|l| l.id == id)?;
+                if let Some(app_id) = properties.get("WMCLASS") {
+                    launch.app_id = Some(app_id.clone());
+                }
+                if let Some(name) = properties.get("NAME") {
+                    launch.name = Some(name.clone());
+                }
+                None
+            }
+            StartupNotification::Remove { id } => self.resolve_token(&id),
+        }
+    }
+
+    /// Returns the launch with the given id
+    pub fn get(&self, id: &str) -> Option<&Launch> {
+        self.launches.iter().find(|l| l.id == id)
+    }
+
+    /// Returns all tracked launches
+    pub fn launches(&self) -> impl Iterator<Item = &Launch> {
+        self.launches.iter()
+    }
+
+    /// Returns if any launch requests a busy cursor
+    pub fn is_busy(&self) -> bool {
+        self.launches.iter().any(|l| l.busy_cursor)
+    }
+
+    /// Returns the cursor icon to show while launches are pending
+    ///
+    /// Returns `None`, if no launch requests a busy cursor.
+    pub fn cursor_icon(&self) -> Option<CursorIcon> {
+        self.is_busy().then_some(CursorIcon::Progress)
+    }
+
+    /// Resolve the launch with the given xdg activation token or X11 startup id
+    ///
+    /// This is usually called from [`XdgActivationHandler::request_activation`](crate::wayland::xdg_activation::XdgActivationHandler::request_activation).
+    /// Returns the launch, which is not tracked anymore.
+    pub fn resolve_token(&mut self, id: &str) -> Option<Launch> {
+        let idx = self.launches.iter().position(|l| l.id == id)?;
+        Some(self.launches.remove(idx))
+    }
+
+    /// Resolve the oldest launch of the given app id
+    ///
+    /// This is usually called when a toplevel gets mapped, as many applications don't
+    /// use the activation token they were launched with.
+    /// Returns the launch, which is not tracked anymore.
+    pub fn resolve_app_id(&mut self, app_id: &str) -> Option<Launch> {
+        let idx = self
+            .launches
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.app_id.as_deref() == Some(app_id))
+            .min_by_key(|(_, l)| l.started)
+            .map(|(idx, _)| idx)?;
+        Some(self.launches.remove(idx))
+    }
+
+    /// Resolve the launch of an X11 window
+    ///
+    /// Matches the startup id of the window first and falls back to its class.
+    /// Returns the launch, which is not tracked anymore.
+    #[cfg(feature = "xwayland")]
+    pub fn resolve_x11(&mut self, window: &X11Surface) -> Option<Launch> {
+        window
+            .startup_id()
+            .and_then(|id| self.resolve_token(&id))
+            .or_else(|| self.resolve_app_id(&window.class()))
+    }
+
+    /// Returns the point in time when the next launch times out
+    pub fn deadline(&self) -> Option<Time<Monotonic>> {
+        self.launches.iter().map(|l| l.started + self.timeout).min()
+    }
+
+    /// Drop all launches, which timed out
+    ///
+    /// Returns the dropped launches.
+    pub fn expire(&mut self, now: Time<Monotonic>) -> Vec<Launch> {
+        let timeout = self.timeout;
+        let (expired, pending) = std::mem::take(&mut self.launches)
+            .into_iter()
+            .partition::<Vec<_>, _>(|l| l.started + timeout <= now);
+        self.launches = pending;
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Launch, LaunchTracker};
+    use crate::utils::{Monotonic, Time};
+
+    fn secs(secs: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn resolve_and_expire() {
+        let mut tracker = LaunchTracker::new(Duration::from_secs(10));
+        let mut launch = Launch::new("token-1", secs(1));
+        launch.app_id = Some("org.example.App".into());
+        tracker.start(launch);
+        let mut launch = Launch::new("token-2", secs(2));
+        launch.app_id = Some("org.example.App".into());
+        tracker.start(launch);
+        tracker.start(Launch::new("token-3", secs(3)));
+        assert!(tracker.cursor_icon().is_some());
+
+        assert_eq!(tracker.resolve_app_id("org.example.App").unwrap().id, "token-1");
+        assert_eq!(tracker.resolve_token("token-2").unwrap().id, "token-2");
+        assert_eq!(tracker.deadline(), Some(secs(13)));
+
+        assert!(tracker.expire(secs(12)).is_empty());
+        assert_eq!(tracker.expire(secs(13)).len(), 1);
+        assert!(!tracker.is_busy());
+        assert_eq!(tracker.cursor_icon(), None);
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{
+    backend::renderer::element::Kind,
     desktop::{utils::*, PopupManager},
     output::{Output, WeakOutput},
     utils::{user_data::UserDataMap, IsAlive, Logical, Point, Rectangle},
     wayland::{
-        compositor::{with_states, with_surface_tree_downward, SurfaceData, TraversalAction},
+        compositor::{get_parent, with_states, with_surface_tree_downward, SurfaceData, TraversalAction},
         dmabuf::DmabufFeedback,
         seat::WaylandFocus,
         shell::wlr_layer::{
@@ -81,7 +82,7 @@ impl LayerMap {
             if layer
                 .0
                 .userdata
-                .get::<LayerUserdata>()
+                .try_get_key::<LayerUserdata>()
                 .map(|s| s.lock().unwrap().location.is_some())
                 .unwrap_or(false)
             {
@@ -99,8 +100,7 @@ impl LayerMap {
         if self.layers.shift_remove(layer) {
             let _ = layer
                 .user_data()
-                .get::<LayerUserdata>()
-                .unwrap()
+                .get_key::<LayerUserdata>()
                 .lock()
                 .unwrap()
                 .location
@@ -169,6 +169,38 @@ impl LayerMap {
         })
     }
 
+    /// Returns the topmost surface under a given point on a given layer, if any.
+    ///
+    /// Unlike [`LayerMap::layer_under`] this respects the input regions, buffer transforms and viewports
+    /// of the surfaces, which is usually what input handling should use. The location of the returned surface
+    /// is relative to the output, like `point`.
+    pub fn surface_under<P: Into<Point<f64, Logical>>>(
+        &self,
+        layer: WlrLayer,
+        point: P,
+        surface_type: WindowSurfaceType,
+    ) -> Option<(&LayerSurface, WlSurface, Point<i32, Logical>)> {
+        let point = point.into();
+        self.layers_on(layer).rev().find_map(|l| {
+            let location = layer_state(l).location.unwrap_or_default();
+            l.surface_under(point - location.to_f64(), surface_type)
+                .map(|(surface, surface_loc)| (l, surface, surface_loc + location))
+        })
+    }
+
+    /// Returns the [`LayerSurface`], which requires exclusive keyboard focus, if any.
+    ///
+    /// As described by the layer-shell protocol only surfaces with [`KeyboardInteractivity::Exclusive`] on the
+    /// [`Top`](WlrLayer::Top) or [`Overlay`](WlrLayer::Overlay) layer lock the keyboard focus. If multiple surfaces
+    /// request it, the surface on the higher layer, and then the most recently mapped one wins.
+    pub fn exclusive_focus(&self) -> Option<&LayerSurface> {
+        [WlrLayer::Overlay, WlrLayer::Top].into_iter().find_map(|layer| {
+            self.layers_on(layer)
+                .rev()
+                .find(|l| l.cached_state().keyboard_interactivity == KeyboardInteractivity::Exclusive)
+        })
+    }
+
     /// Iterator over all [`LayerSurface`]s currently mapped.
     pub fn layers(&self) -> impl DoubleEndedIterator<Item = &LayerSurface> {
         self.layers.iter()
@@ -461,11 +493,10 @@ pub struct LayerState {
     pub location: Option<Point<i32, Logical>>,
 }
 
-type LayerUserdata = Mutex<LayerState>;
+crate::user_data_key!(LayerUserdata: Mutex<LayerState> = Mutex::default());
 pub fn layer_state(layer: &LayerSurface) -> MutexGuard<'_, LayerState> {
     let userdata = layer.user_data();
-    userdata.insert_if_missing_threadsafe(LayerUserdata::default);
-    userdata.get::<LayerUserdata>().unwrap().lock().unwrap()
+    userdata.get_key::<LayerUserdata>().lock().unwrap()
 }
 
 /// A [`LayerSurface`] represents a single layer surface as given by the wlr-layer-shell protocol.
@@ -493,6 +524,7 @@ pub(crate) struct LayerSurfaceInner {
     pub(crate) id: usize,
     surface: WlrLayerSurface,
     namespace: String,
+    render_kind: Mutex<Option<Kind>>,
     userdata: UserDataMap,
 }
 
@@ -517,6 +549,7 @@ impl LayerSurface {
             id: layer_id::next(),
             surface,
             namespace,
+            render_kind: Mutex::new(None),
             userdata: UserDataMap::new(),
         }))
     }
@@ -554,6 +587,19 @@ impl LayerSurface {
         })
     }
 
+    /// Returns true, if `surface` is this layer surface, one of its subsurfaces or popups.
+    ///
+    /// Note: You need to use a [`PopupManager`] to track popups, otherwise popups are not accounted for.
+    pub fn contains_surface(&self, surface: &WlSurface) -> bool {
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        root == *self.wl_surface()
+            || PopupManager::popups_for_surface(self.wl_surface())
+                .any(|(popup, _)| *popup.wl_surface() == root)
+    }
+
     /// Returns the layer this surface resides on, if any yet.
     pub fn layer(&self) -> WlrLayer {
         with_states(self.0.surface.wl_surface(), |states| {
@@ -570,6 +616,31 @@ impl LayerSurface {
         &self.0.namespace
     }
 
+    /// Returns the [`Kind`] of the render elements of this surface
+    ///
+    /// Unless overridden by [`LayerSurface::set_render_kind`], surfaces on the
+    /// [`Background`](WlrLayer::Background) and [`Bottom`](WlrLayer::Bottom) layer
+    /// are marked as [`Kind::ScanoutCandidate`], as they usually represent
+    /// static content like wallpapers.
+    /// Popups of this surface always use [`Kind::Unspecified`].
+    pub fn render_kind(&self) -> Kind {
+        if let Some(kind) = *self.0.render_kind.lock().unwrap() {
+            return kind;
+        }
+
+        match self.layer() {
+            WlrLayer::Background | WlrLayer::Bottom => Kind::ScanoutCandidate,
+            WlrLayer::Top | WlrLayer::Overlay => Kind::Unspecified,
+        }
+    }
+
+    /// Override the [`Kind`] of the render elements of this surface
+    ///
+    /// Passing `None` restores the default based on the layer of the surface.
+    pub fn set_render_kind(&self, kind: impl Into<Option<Kind>>) {
+        *self.0.render_kind.lock().unwrap() = kind.into();
+    }
+
     /// Returns the bounding box over this layer surface and its subsurfaces.
     pub fn bbox(&self) -> Rectangle<i32, Logical> {
         bbox_from_surface_tree(self.0.surface.wl_surface(), (0, 0))
@@ -722,3 +793,250 @@ impl WaylandFocus for LayerSurface {
         Some(Cow::Borrowed(self.0.surface.wl_surface()))
     }
 }
+
+/// Change of the keyboard focus required by [`ExclusiveLayerFocus`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExclusiveFocusChange {
+    /// The layer surface requires the keyboard focus
+    Focus(LayerSurface),
+    /// No layer surface requires exclusive focus anymore, the compositor should restore its regular focus
+    Release,
+}
+
+/// Tracks the [`LayerSurface`]s requiring exclusive keyboard focus per output
+///
+/// Layer surfaces with [`KeyboardInteractivity::Exclusive`] on the [`Top`](WlrLayer::Top) or
+/// [`Overlay`](WlrLayer::Overlay) layer, like lock screens or launchers, have to receive keyboard focus
+/// once they are mapped and keep it until they are unmapped or change their interactivity.
+///
+/// [`ExclusiveLayerFocus::refresh`] should be called after layer surfaces were committed, mapped or unmapped
+/// and reports the necessary focus changes. Before the compositor changes the keyboard focus for other
+/// reasons, e.g. a click onto a window, it should check [`ExclusiveLayerFocus::allows_focus`].
+#[derive(Debug, Default)]
+pub struct ExclusiveLayerFocus {
+    holders: Vec<(WeakOutput, LayerSurface)>,
+    current: Option<LayerSurface>,
+}
+
+impl ExclusiveLayerFocus {
+    /// Create a new tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Re-evaluate the layer surfaces mapped on `outputs`
+    ///
+    /// Returns the change of the keyboard focus necessary to enforce exclusive focus, if any.
+    pub fn refresh<'a>(
+        &mut self,
+        outputs: impl IntoIterator<Item = &'a Output>,
+    ) -> Option<ExclusiveFocusChange> {
+        let holders = outputs
+            .into_iter()
+            .filter_map(|output| {
+                let map = layer_map_for_output(output);
+                let holder = map.exclusive_focus().cloned();
+                holder.map(|holder| (output.downgrade(), holder))
+            })
+            .collect::<Vec<_>>();
+
+        // newly mapped surfaces take the focus
+        let new_holder = holders
+            .iter()
+            .find(|(_, holder)| !self.holders.iter().any(|(_, old)| old == holder))
+            .map(|(_, holder)| holder.clone());
+        self.holders = holders;
+
+        if let Some(holder) = new_holder {
+            self.current = Some(holder.clone());
+            return Some(ExclusiveFocusChange::Focus(holder));
+        }
+
+        let current = self.current.as_ref()?;
+        if self.holders.iter().any(|(_, holder)| holder == current) {
+            return None;
+        }
+        match self.holders.last() {
+            Some((_, holder)) => {
+                self.current = Some(holder.clone());
+                Some(ExclusiveFocusChange::Focus(holder.clone()))
+            }
+            None => {
+                self.current = None;
+                Some(ExclusiveFocusChange::Release)
+            }
+        }
+    }
+
+    /// Returns the layer surface currently holding the exclusive focus
+    pub fn current(&self) -> Option<&LayerSurface> {
+        self.current.as_ref()
+    }
+
+    /// Returns the layer surface requiring exclusive focus on a given output
+    pub fn holder(&self, output: &Output) -> Option<&LayerSurface> {
+        self.holders
+            .iter()
+            .find(|(weak, _)| weak == output)
+            .map(|(_, holder)| holder)
+    }
+
+    /// Returns if the keyboard focus may be moved to `surface`
+    ///
+    /// While any layer surface holds exclusive focus, only surfaces belonging to such layer surfaces
+    /// can be focused. Focusing another holder, e.g. on a different output, makes it the current one.
+    pub fn allows_focus(&mut self, surface: &WlSurface) -> bool {
+        if self.holders.is_empty() {
+            return true;
+        }
+        match self
+            .holders
+            .iter()
+            .find(|(_, holder)| holder.contains_surface(surface))
+        {
+            Some((_, holder)) => {
+                self.current = Some(holder.clone());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+
+    use super::{layer_map_for_output, ExclusiveFocusChange, ExclusiveLayerFocus, LayerSurface};
+    use crate::{
+        output::{Output, PhysicalProperties, Subpixel},
+        wayland::test_utils::TestFixture,
+    };
+
+    fn output(name: &str) -> Output {
+        Output::new(
+            name.into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Test".into(),
+            },
+        )
+    }
+
+    fn map_layer(
+        fixture: &mut TestFixture,
+        output: &Output,
+        layer: zwlr_layer_shell_v1::Layer,
+        interactivity: zwlr_layer_surface_v1::KeyboardInteractivity,
+    ) -> LayerSurface {
+        let (surface, layer_surface, server_layer) = fixture.create_layer_surface(layer, (100, 100));
+        layer_surface.set_keyboard_interactivity(interactivity);
+        fixture.map(&surface, 100, 100);
+        let layer = LayerSurface::new(server_layer, "test".into());
+        layer_map_for_output(output).map_layer(&layer).unwrap();
+        layer
+    }
+
+    #[test]
+    fn exclusive_layer_focus_follows_mapping() {
+        let mut fixture = TestFixture::new();
+        let output = output("test");
+        let (_, window) = fixture.create_surface();
+        let mut focus = ExclusiveLayerFocus::new();
+        assert_eq!(focus.refresh([&output]), None);
+
+        let layer = map_layer(
+            &mut fixture,
+            &output,
+            zwlr_layer_shell_v1::Layer::Top,
+            zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive,
+        );
+        assert_eq!(
+            focus.refresh([&output]),
+            Some(ExclusiveFocusChange::Focus(layer.clone()))
+        );
+        assert_eq!(focus.refresh([&output]), None);
+        assert_eq!(focus.current(), Some(&layer));
+        assert_eq!(focus.holder(&output), Some(&layer));
+        assert!(!focus.allows_focus(&window));
+        assert!(focus.allows_focus(layer.wl_surface()));
+
+        layer_map_for_output(&output).unmap_layer(&layer);
+        assert_eq!(focus.refresh([&output]), Some(ExclusiveFocusChange::Release));
+        assert_eq!(focus.current(), None);
+        assert!(focus.allows_focus(&window));
+        assert_eq!(focus.refresh([&output]), None);
+    }
+
+    #[test]
+    fn exclusive_layer_focus_ignores_other_layers() {
+        let mut fixture = TestFixture::new();
+        let output = output("test");
+        let mut focus = ExclusiveLayerFocus::new();
+
+        // exclusive interactivity on the lower layers is treated as on-demand
+        map_layer(
+            &mut fixture,
+            &output,
+            zwlr_layer_shell_v1::Layer::Bottom,
+            zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive,
+        );
+        map_layer(
+            &mut fixture,
+            &output,
+            zwlr_layer_shell_v1::Layer::Overlay,
+            zwlr_layer_surface_v1::KeyboardInteractivity::OnDemand,
+        );
+        assert_eq!(focus.refresh([&output]), None);
+        assert_eq!(focus.holder(&output), None);
+    }
+
+    #[test]
+    fn exclusive_layer_focus_multiple_outputs() {
+        let mut fixture = TestFixture::new();
+        let first = output("first");
+        let second = output("second");
+        let mut focus = ExclusiveLayerFocus::new();
+
+        let first_layer = map_layer(
+            &mut fixture,
+            &first,
+            zwlr_layer_shell_v1::Layer::Overlay,
+            zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive,
+        );
+        assert_eq!(
+            focus.refresh([&first, &second]),
+            Some(ExclusiveFocusChange::Focus(first_layer.clone()))
+        );
+
+        // the newly mapped holder takes the focus
+        let second_layer = map_layer(
+            &mut fixture,
+            &second,
+            zwlr_layer_shell_v1::Layer::Top,
+            zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive,
+        );
+        assert_eq!(
+            focus.refresh([&first, &second]),
+            Some(ExclusiveFocusChange::Focus(second_layer.clone()))
+        );
+
+        // focusing the holder of another output is allowed and makes it current
+        assert!(focus.allows_focus(first_layer.wl_surface()));
+        assert_eq!(focus.current(), Some(&first_layer));
+
+        // unmapping the current holder moves the focus to the remaining one
+        layer_map_for_output(&first).unmap_layer(&first_layer);
+        assert_eq!(
+            focus.refresh([&first, &second]),
+            Some(ExclusiveFocusChange::Focus(second_layer.clone()))
+        );
+        layer_map_for_output(&second).unmap_layer(&second_layer);
+        assert_eq!(
+            focus.refresh([&first, &second]),
+            Some(ExclusiveFocusChange::Release)
+        );
+    }
+}
@@ -142,11 +142,11 @@ impl PopupManager {
         let root = find_popup_root_surface(&popup)?;
 
         with_states(&root, |states| {
-            let tree = PopupTree::default();
-            if states.data_map.insert_if_missing_threadsafe(|| tree.clone()) {
-                self.popup_trees.push(tree);
-            };
-            let tree = states.data_map.get::<PopupTree>().unwrap();
+            let inserted = states.data_map.try_get_key::<PopupTreeKey>().is_none();
+            let tree = states.data_map.get_key::<PopupTreeKey>();
+            if inserted {
+                self.popup_trees.push(tree.clone());
+            }
             if !tree.alive() {
                 // if it previously had no popups, we likely removed it from our list already
                 self.popup_trees.push(tree.clone());
@@ -178,7 +178,7 @@ impl PopupManager {
         with_states(surface, |states| {
             states
                 .data_map
-                .get::<PopupTree>()
+                .try_get_key::<PopupTreeKey>()
                 .map(|x| x.iter_popups())
                 .into_iter()
                 .flatten()
@@ -191,7 +191,7 @@ impl PopupManager {
             return Err(DeadResource);
         }
         with_states(surface, |states| {
-            let tree = states.data_map.get::<PopupTree>();
+            let tree = states.data_map.try_get_key::<PopupTreeKey>();
 
             if let Some(tree) = tree {
                 tree.dismiss_popup(popup);
@@ -277,6 +277,7 @@ pub fn get_popup_toplevel_coords(popup: &PopupKind) -> Point<i32, Logical> {
 
 #[derive(Debug, Default, Clone)]
 struct PopupTree(Arc<Mutex<Vec<PopupNode>>>);
+crate::user_data_key!(PopupTreeKey: PopupTree = PopupTree::default());
 
 #[derive(Debug, Clone)]
 struct PopupNode {
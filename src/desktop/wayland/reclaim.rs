@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use crate::{
+    backend::renderer::utils::release_surface_textures,
+    desktop::{utils::with_surfaces_surface_tree, PopupManager},
+    utils::{IsAlive, Monotonic, Time},
+    wayland::seat::WaylandFocus,
+};
+
+/// Releases the textures of elements, which stayed unmapped for a while
+///
+/// Imported textures of an element stay resident after it was unmapped, e.g. when minimizing
+/// a window, so it can be shown again without delay. On devices with little memory this might
+/// not be desirable for elements staying unmapped for a long time.
+///
+/// Report elements to the tracker using [`TextureReclaimer::unmapped`] and [`TextureReclaimer::mapped`]
+/// and call [`TextureReclaimer::reclaim`] once [`TextureReclaimer::deadline`] has passed.
+/// This releases the textures of all surfaces of the elements unmapped for longer than the configured
+/// delay (see [`release_surface_textures`]). The attached buffers are kept, so the textures are imported
+/// again once the element is rendered after being mapped, which makes the first frame slower.
+///
+/// [`TextureReclaimer::reclaim_all`] can be used to release the textures of all tracked elements immediately,
+/// e.g. when running low on memory.
+#[derive(Debug)]
+pub struct TextureReclaimer<E> {
+    delay: Option<Duration>,
+    unmapped: Vec<(E, Time<Monotonic>)>,
+}
+
+impl<E: WaylandFocus + IsAlive + PartialEq> TextureReclaimer<E> {
+    /// Create a new tracker releasing textures of elements unmapped for longer than `delay`
+    ///
+    /// Textures are only released by [`TextureReclaimer::reclaim_all`], if `delay` is `None`.
+    pub fn new(delay: Option<Duration>) -> Self {
+        TextureReclaimer {
+            delay,
+            unmapped: Vec::new(),
+        }
+    }
+
+    /// Returns the delay after which textures of unmapped elements are released
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    /// Set the delay after which textures of unmapped elements are released
+    ///
+    /// Applies to already tracked elements as well.
+    pub fn set_delay(&mut self, delay: Option<Duration>) {
+        self.delay = delay;
+    }
+
+    /// Report an element as unmapped at `now`
+    ///
+    /// Does nothing if the element is already tracked.
+    pub fn unmapped(&mut self, element: E, now: Time<Monotonic>) {
+        if !self.unmapped.iter().any(|(e, _)| *e == element) {
+            self.unmapped.push((element, now));
+        }
+    }
+
+    /// Report an element as mapped again
+    pub fn mapped(&mut self, element: &E) {
+        self.unmapped.retain(|(e, _)| e != element);
+    }
+
+    /// Returns the point in time when the textures of the next element are due to be released
+    pub fn deadline(&self) -> Option<Time<Monotonic>> {
+        let delay = self.delay?;
+        self.unmapped.iter().map(|(_, unmapped)| *unmapped + delay).min()
+    }
+
+    /// Release the textures of all elements unmapped for longer than the delay
+    ///
+    /// Returns the elements whose textures were released, these are not tracked anymore.
+    pub fn reclaim(&mut self, now: Time<Monotonic>) -> Vec<E> {
+        let Some(delay) = self.delay else {
+            return Vec::new();
+        };
+
+        self.unmapped.retain(|(e, _)| e.alive());
+        let (due, pending) = std::mem::take(&mut self.unmapped)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, unmapped)| *unmapped + delay <= now);
+        self.unmapped = pending;
+
+        due.into_iter()
+            .map(|(element, _)| {
+                release_element_textures(&element);
+                element
+            })
+            .collect()
+    }
+
+    /// Release the textures of all tracked elements immediately
+    ///
+    /// Returns the elements whose textures were released, these are not tracked anymore.
+    pub fn reclaim_all(&mut self) -> Vec<E> {
+        self.unmapped
+            .drain(..)
+            .filter(|(element, _)| element.alive())
+            .map(|(element, _)| {
+                release_element_textures(&element);
+                element
+            })
+            .collect()
+    }
+}
+
+fn release_element_textures<E: WaylandFocus>(element: &E) {
+    let Some(surface) = element.wl_surface() else {
+        return;
+    };
+
+    with_surfaces_surface_tree(&surface, |_, states| release_surface_textures(states));
+    for (popup, _) in PopupManager::popups_for_surface(&surface) {
+        with_surfaces_surface_tree(popup.wl_surface(), |_, states| release_surface_textures(states));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::TextureReclaimer;
+    use crate::{
+        utils::{Monotonic, Time},
+        wayland::test_utils::TestFixture,
+    };
+
+    fn time(millis: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn reclaim_after_delay() {
+        let mut fixture = TestFixture::new();
+        let (_first, first) = fixture.create_surface();
+        let (_second, second) = fixture.create_surface();
+
+        let mut reclaimer = TextureReclaimer::new(Some(Duration::from_secs(1)));
+        assert_eq!(reclaimer.deadline(), None);
+        reclaimer.unmapped(first.clone(), time(0));
+        reclaimer.unmapped(second.clone(), time(500));
+        // already tracked elements keep their time
+        reclaimer.unmapped(first.clone(), time(700));
+        assert_eq!(reclaimer.deadline(), Some(time(1000)));
+
+        assert!(reclaimer.reclaim(time(999)).is_empty());
+        assert_eq!(reclaimer.reclaim(time(1000)), vec![first.clone()]);
+        assert_eq!(reclaimer.deadline(), Some(time(1500)));
+
+        // mapping an element stops tracking it
+        reclaimer.mapped(&second);
+        assert_eq!(reclaimer.deadline(), None);
+        assert!(reclaimer.reclaim(time(2000)).is_empty());
+    }
+
+    #[test]
+    fn reclaim_without_delay() {
+        let mut fixture = TestFixture::new();
+        let (_surface, surface) = fixture.create_surface();
+
+        let mut reclaimer = TextureReclaimer::new(None);
+        reclaimer.unmapped(surface.clone(), time(0));
+        assert_eq!(reclaimer.deadline(), None);
+        assert!(reclaimer.reclaim(time(u32::MAX as u64)).is_empty());
+
+        reclaimer.set_delay(Some(Duration::from_secs(1)));
+        assert_eq!(reclaimer.deadline(), Some(time(1000)));
+        assert_eq!(reclaimer.reclaim_all(), vec![surface]);
+        assert_eq!(reclaimer.deadline(), None);
+    }
+
+    #[test]
+    fn dead_elements_are_dropped() {
+        let mut fixture = TestFixture::new();
+        let (client_surface, surface) = fixture.create_surface();
+
+        let mut reclaimer = TextureReclaimer::new(Some(Duration::ZERO));
+        reclaimer.unmapped(surface, time(0));
+        client_surface.destroy();
+        fixture.roundtrip();
+        assert!(reclaimer.reclaim(time(0)).is_empty());
+        assert_eq!(reclaimer.deadline(), None);
+    }
+
+    #[cfg(feature = "renderer_test")]
+    #[test]
+    fn textures_are_released() {
+        use crate::backend::renderer::{
+            test::DummyRenderer,
+            utils::{import_surface, RendererSurfaceStateUserData},
+            Renderer,
+        };
+        use crate::wayland::compositor;
+
+        let mut fixture = TestFixture::new();
+        let (client_surface, surface) = fixture.create_surface();
+        let buffer = fixture.create_buffer(10, 10);
+        client_surface.attach(Some(&buffer), 0, 0);
+        client_surface.commit();
+        fixture.roundtrip();
+
+        let mut renderer = DummyRenderer::new();
+        let has_texture = |renderer: &DummyRenderer| {
+            compositor::with_states(&surface, |states| {
+                let data = states.data_map.get::<RendererSurfaceStateUserData>().unwrap();
+                let data = data.lock().unwrap();
+                data.texture::<DummyRenderer>(renderer.id()).is_some()
+            })
+        };
+        compositor::with_states(&surface, |states| import_surface(&mut renderer, states)).unwrap();
+        assert!(has_texture(&renderer));
+
+        let mut reclaimer = TextureReclaimer::new(Some(Duration::ZERO));
+        reclaimer.unmapped(surface.clone(), time(0));
+        assert_eq!(reclaimer.reclaim(time(0)), vec![surface.clone()]);
+        assert!(!has_texture(&renderer));
+
+        // the buffer is kept and imported again
+        compositor::with_states(&surface, |states| import_surface(&mut renderer, states)).unwrap();
+        assert!(has_texture(&renderer));
+    }
+}
@@ -0,0 +1,275 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use wayland_server::{backend::ObjectId, protocol::wl_surface::WlSurface, Resource};
+
+use crate::{
+    backend::renderer::utils::{CommitCounter, RendererSurfaceStateUserData},
+    desktop::{layer_map_for_output, space::SpaceElement, PopupManager, Space},
+    output::Output,
+    utils::{Logical, Point},
+    wayland::{
+        compositor::{with_surface_tree_downward, TraversalAction},
+        seat::WaylandFocus,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum ContentEntry {
+    Surface {
+        id: ObjectId,
+        location: Point<i32, Logical>,
+        commit: CommitCounter,
+    },
+    Cursor(Point<f64, Logical>),
+    Custom(u64),
+}
+
+/// Cheap snapshot of the content shown on an output
+///
+/// The snapshot records the position and commit of every surface shown on an output, without
+/// importing any buffers or creating render elements. Comparing it to the snapshot of the last
+/// rendered frame tells if anything changed, see [`RepaintTracker`].
+///
+/// Only surfaces handled by [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler)
+/// are recorded. Content not backed by surfaces, like server-side decorations, can be recorded with
+/// [`OutputContentSnapshot::add_custom`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OutputContentSnapshot {
+    entries: Vec<ContentEntry>,
+}
+
+impl OutputContentSnapshot {
+    /// Create an empty snapshot
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a snapshot of the elements, their popups and the layer surfaces shown on an output of a [`Space`]
+    pub fn from_space<E>(space: &Space<E>, output: &Output) -> Self
+    where
+        E: SpaceElement + PartialEq + WaylandFocus,
+    {
+        let mut snapshot = Self::new();
+
+        let map = layer_map_for_output(output);
+        for layer in map.layers() {
+            if let Some(geometry) = map.layer_geometry(layer) {
+                snapshot.add_surface_tree(layer.wl_surface(), geometry.loc);
+                for (popup, offset) in PopupManager::popups_for_surface(layer.wl_surface()) {
+                    snapshot.add_surface_tree(popup.wl_surface(), geometry.loc + offset);
+                }
+            }
+        }
+
+        for element in space.elements_for_output(output) {
+            let (Some(location), Some(surface)) = (space.element_location(element), element.wl_surface())
+            else {
+                continue;
+            };
+            let location = location - element.geometry().loc;
+            snapshot.add_surface_tree(&surface, location);
+            for (popup, offset) in PopupManager::popups_for_surface(&surface) {
+                snapshot.add_surface_tree(popup.wl_surface(), location + offset);
+            }
+        }
+
+        snapshot
+    }
+
+    /// Record a surface and its subsurfaces shown at the given location
+    ///
+    /// Like the render elements of the surface tree, subsurfaces are recorded at their own location,
+    /// unmapped surfaces and their children are skipped.
+    pub fn add_surface_tree(&mut self, surface: &WlSurface, location: impl Into<Point<i32, Logical>>) {
+        let location = location.into();
+        with_surface_tree_downward(
+            surface,
+            location,
+            |_, states, location| {
+                let view = states
+                    .data_map
+                    .get::<RendererSurfaceStateUserData>()
+                    .and_then(|data| data.lock().unwrap().view());
+                match view {
+                    Some(view) => TraversalAction::DoChildren(*location + view.offset),
+                    None => TraversalAction::SkipChildren,
+                }
+            },
+            |surface, states, location| {
+                let Some(data) = states.data_map.get::<RendererSurfaceStateUserData>() else {
+                    return;
+                };
+                let data = data.lock().unwrap();
+                let Some(view) = data.view() else {
+                    return;
+                };
+                self.entries.push(ContentEntry::Surface {
+                    id: surface.id(),
+                    location: *location + view.offset,
+                    commit: data.current_commit(),
+                });
+            },
+            |_, _, _| true,
+        );
+    }
+
+    /// Record the position of the cursor
+    ///
+    /// The cursor surface, if any, can be recorded using [`OutputContentSnapshot::add_surface_tree`].
+    pub fn add_cursor(&mut self, location: impl Into<Point<f64, Logical>>) {
+        self.entries.push(ContentEntry::Cursor(location.into()));
+    }
+
+    /// Record custom content, e.g. a hash or counter of state drawn by the compositor
+    pub fn add_custom(&mut self, value: u64) {
+        self.entries.push(ContentEntry::Custom(value));
+    }
+}
+
+/// Decides if an output has to be repainted
+///
+/// Compare the [`OutputContentSnapshot`] of an output with the last rendered one using
+/// [`RepaintTracker::needs_repaint`] before rendering a frame and skip rendering entirely, if nothing changed.
+/// A repaint is always needed after [`RepaintTracker::schedule_repaint`] was called, e.g. when the
+/// compositor changed the output configuration, and while any [`AnimationGuard`] created by
+/// [`RepaintTracker::animation`] is alive.
+#[derive(Debug, Default)]
+pub struct RepaintTracker {
+    last: Option<OutputContentSnapshot>,
+    scheduled: bool,
+    animations: Arc<AtomicUsize>,
+}
+
+/// Keeps the output of a [`RepaintTracker`] repainting while alive
+#[derive(Debug)]
+pub struct AnimationGuard {
+    animations: Arc<AtomicUsize>,
+}
+
+impl Drop for AnimationGuard {
+    fn drop(&mut self) {
+        self.animations.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl RepaintTracker {
+    /// Create a new tracker, requiring a repaint for the first frame
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Force a repaint for the next frame
+    pub fn schedule_repaint(&mut self) {
+        self.scheduled = true;
+    }
+
+    /// Register a running animation
+    ///
+    /// The output is repainted every frame until the returned guard is dropped.
+    pub fn animation(&self) -> AnimationGuard {
+        self.animations.fetch_add(1, Ordering::SeqCst);
+        AnimationGuard {
+            animations: self.animations.clone(),
+        }
+    }
+
+    /// Returns if any animation is running
+    pub fn is_animating(&self) -> bool {
+        self.animations.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns if the content of the output changed since the last rendered frame
+    pub fn needs_repaint(&self, snapshot: &OutputContentSnapshot) -> bool {
+        self.scheduled || self.is_animating() || self.last.as_ref() != Some(snapshot)
+    }
+
+    /// Report a frame was rendered with the content of `snapshot`
+    pub fn frame_rendered(&mut self, snapshot: OutputContentSnapshot) {
+        self.scheduled = false;
+        self.last = Some(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_server::Resource;
+
+    use super::{ContentEntry, OutputContentSnapshot, RepaintTracker};
+    use crate::wayland::test_utils::TestFixture;
+
+    #[test]
+    fn repaint_tracking() {
+        let mut tracker = RepaintTracker::new();
+        let mut snapshot = OutputContentSnapshot::new();
+        snapshot.add_cursor((10.0, 10.0));
+        assert!(tracker.needs_repaint(&snapshot));
+
+        tracker.frame_rendered(snapshot.clone());
+        assert!(!tracker.needs_repaint(&snapshot));
+
+        let mut moved = OutputContentSnapshot::new();
+        moved.add_cursor((11.0, 10.0));
+        assert!(tracker.needs_repaint(&moved));
+
+        tracker.schedule_repaint();
+        assert!(tracker.needs_repaint(&snapshot));
+        tracker.frame_rendered(snapshot.clone());
+
+        let animation = tracker.animation();
+        assert!(tracker.needs_repaint(&snapshot));
+        drop(animation);
+        assert!(!tracker.needs_repaint(&snapshot));
+    }
+
+    #[test]
+    fn subsurfaces_are_recorded_at_their_location() {
+        let mut fixture = TestFixture::new();
+        let (parent, server_parent) = fixture.create_surface();
+        let (child, subsurface, server_child) = fixture.create_subsurface(&parent);
+        subsurface.set_position(10, 20);
+        let buffer = fixture.create_buffer(10, 10);
+        child.attach(Some(&buffer), 0, 0);
+        child.commit();
+        let buffer = fixture.create_buffer(100, 100);
+        parent.attach(Some(&buffer), 0, 0);
+        parent.commit();
+        fixture.roundtrip();
+
+        let mut snapshot = OutputContentSnapshot::new();
+        snapshot.add_surface_tree(&server_parent, (5, 5));
+        let locations = snapshot
+            .entries
+            .iter()
+            .map(|entry| match entry {
+                ContentEntry::Surface { id, location, .. } => (id.clone(), *location),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        // topmost first, like the render elements
+        assert_eq!(
+            locations,
+            vec![
+                (server_child.id(), (15, 25).into()),
+                (server_parent.id(), (5, 5).into()),
+            ]
+        );
+
+        // moving the subsurface without new buffers needs a repaint
+        let mut tracker = RepaintTracker::new();
+        tracker.frame_rendered(snapshot);
+        subsurface.set_position(30, 20);
+        parent.commit();
+        fixture.roundtrip();
+        let mut moved = OutputContentSnapshot::new();
+        moved.add_surface_tree(&server_parent, (5, 5));
+        assert!(tracker.needs_repaint(&moved));
+        tracker.frame_rendered(moved);
+
+        let mut unchanged = OutputContentSnapshot::new();
+        unchanged.add_surface_tree(&server_parent, (5, 5));
+        assert!(!tracker.needs_repaint(&unchanged));
+    }
+}
@@ -5,11 +5,15 @@ use crate::{
         element::{
             PrimaryScanoutOutput, RenderElementPresentationState, RenderElementState, RenderElementStates,
         },
-        utils::{RendererSurfaceState, RendererSurfaceStateUserData},
+        utils::{
+            surface_texture_memory_usage, with_renderer_surface_state, RendererSurfaceState,
+            RendererSurfaceStateUserData,
+        },
+        Renderer,
     },
     desktop::WindowSurfaceType,
     output::{Output, WeakOutput},
-    utils::{Logical, Point, Rectangle, Time},
+    utils::{Buffer as BufferCoord, Logical, Monotonic, Point, Rectangle, Time},
     wayland::{
         compositor::{with_surface_tree_downward, SurfaceAttributes, SurfaceData, TraversalAction},
         dmabuf::{DmabufFeedback, SurfaceDmabufFeedbackState},
@@ -21,6 +25,10 @@ use wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
 use wayland_server::protocol::wl_surface;
 
 pub use super::super::space::wayland::output_update;
+use super::frame_stats::PendingFrame;
+pub use super::frame_stats::{
+    disable_frame_stats, enable_frame_stats, record_frame_commit, surface_frame_stats, SurfaceFrameStats,
+};
 
 impl RendererSurfaceState {
     fn contains_point<P: Into<Point<f64, Logical>>>(&self, attrs: &SurfaceAttributes, point: P) -> bool {
@@ -89,6 +97,9 @@ where
 ///
 /// In case no surface input region matches the point [`None`] is returned.
 ///
+/// Sub-surfaces are placed including the offsets set through `wl_surface.offset`, the offset of the
+/// root surface has to be applied by the compositor by moving the surface.
+///
 /// - `point` has to be the position to query, relative to (0, 0) of the given surface + `location`.
 /// - `location` can be used to offset the returned point.
 pub fn under_from_surface_tree<P>(
@@ -147,7 +158,43 @@ where
     found.into_inner()
 }
 
-type SurfacePrimaryScanoutOutput = Mutex<PrimaryScanoutOutput>;
+/// Returns the topmost (sub-)surface under a given position together with the surface-local position
+///
+/// Same as [`under_from_surface_tree`] with a `location` of `(0, 0)`, but returns the position relative
+/// to the found surface, which can be passed to pointer and touch events. Sub-surface positions and
+/// offsets as well as the destination size of viewports are accounted for, so the returned position is
+/// in the coordinate space of the surface as seen by the client.
+///
+/// - `point` has to be the position to query, relative to (0, 0) of the given surface.
+pub fn surface_local_point_from_surface_tree(
+    surface: &wl_surface::WlSurface,
+    point: Point<f64, Logical>,
+    surface_type: WindowSurfaceType,
+) -> Option<(wl_surface::WlSurface, Point<f64, Logical>)> {
+    under_from_surface_tree(surface, point, (0, 0), surface_type)
+        .map(|(surface, location)| (surface, point - location.to_f64()))
+}
+
+/// Maps a surface-local position to the coordinate space of the buffer attached to the surface
+///
+/// Takes the viewport source rectangle, buffer scale and buffer transform into account,
+/// e.g. to map pointer positions onto the content of a surface.
+///
+/// Returns `None` if the surface has no buffer attached.
+///
+/// Note: This will only work, if you are using
+/// [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler).
+pub fn surface_to_buffer_point(
+    surface: &wl_surface::WlSurface,
+    point: Point<f64, Logical>,
+) -> Option<Point<f64, BufferCoord>> {
+    with_renderer_surface_state(surface, |state| state.surface_to_buffer_point(point)).flatten()
+}
+
+crate::user_data_key!(SurfacePrimaryScanoutOutput: Mutex<PrimaryScanoutOutput> = Mutex::default());
+crate::user_data_key!(
+    SurfaceFrameThrottlingStateKey: SurfaceFrameThrottlingState = SurfaceFrameThrottlingState::default()
+);
 
 /// Run a closure on all surfaces of a surface tree
 pub fn with_surfaces_surface_tree<F>(surface: &wl_surface::WlSurface, mut processor: F)
@@ -163,6 +210,21 @@ where
     )
 }
 
+/// Returns the approximate memory in bytes used by the textures renderers of type `R` imported for a surface tree
+///
+/// See [`surface_texture_memory_usage`] for details.
+pub fn texture_memory_usage_surface_tree<R>(surface: &wl_surface::WlSurface) -> usize
+where
+    R: Renderer,
+    <R as Renderer>::TextureId: 'static,
+{
+    let mut usage = 0;
+    with_surfaces_surface_tree(surface, |_, states| {
+        usage += surface_texture_memory_usage::<R>(states);
+    });
+    usage
+}
+
 /// Retrieve a previously stored primary scan-out output from a surface
 ///
 /// This will always return `None` if [`update_surface_primary_scanout_output`] is not used.
@@ -170,10 +232,7 @@ pub fn surface_primary_scanout_output(
     _surface: &wl_surface::WlSurface,
     states: &SurfaceData,
 ) -> Option<Output> {
-    states
-        .data_map
-        .insert_if_missing_threadsafe(SurfacePrimaryScanoutOutput::default);
-    let surface_primary_scanout_output = states.data_map.get::<SurfacePrimaryScanoutOutput>().unwrap();
+    let surface_primary_scanout_output = states.data_map.get_key::<SurfacePrimaryScanoutOutput>();
     surface_primary_scanout_output.lock().unwrap().current_output()
 }
 
@@ -192,13 +251,7 @@ pub fn update_surface_primary_scanout_output<F>(
 where
     F: for<'a> Fn(&'a Output, &'a RenderElementState, &'a Output, &'a RenderElementState) -> &'a Output,
 {
-    surface_data
-        .data_map
-        .insert_if_missing_threadsafe(SurfacePrimaryScanoutOutput::default);
-    let surface_primary_scanout_output = surface_data
-        .data_map
-        .get::<SurfacePrimaryScanoutOutput>()
-        .unwrap();
+    let surface_primary_scanout_output = surface_data.data_map.get_key::<SurfacePrimaryScanoutOutput>();
     surface_primary_scanout_output
         .lock()
         .unwrap()
@@ -231,11 +284,7 @@ pub fn send_frames_surface_tree<T, F>(
         (),
         |_, _, &()| TraversalAction::DoChildren(()),
         |surface, states, &()| {
-            states
-                .data_map
-                .insert_if_missing_threadsafe(SurfaceFrameThrottlingState::default);
-            let surface_frame_throttling_state =
-                states.data_map.get::<SurfaceFrameThrottlingState>().unwrap();
+            let surface_frame_throttling_state = states.data_map.get_key::<SurfaceFrameThrottlingStateKey>();
 
             let on_primary_scanout_output = primary_scan_out_output(surface, states)
                 .map(|preferred_output| preferred_output == *output)
@@ -370,6 +419,7 @@ impl Drop for SurfacePresentationFeedback {
 pub struct OutputPresentationFeedback {
     output: WeakOutput,
     callbacks: Vec<SurfacePresentationFeedback>,
+    frames: Vec<PendingFrame>,
 }
 
 impl OutputPresentationFeedback {
@@ -378,6 +428,7 @@ impl OutputPresentationFeedback {
         OutputPresentationFeedback {
             output: output.downgrade(),
             callbacks: Vec::new(),
+            frames: Vec::new(),
         }
     }
 
@@ -408,6 +459,10 @@ impl OutputPresentationFeedback {
             for mut callback in self.callbacks.drain(..) {
                 callback.presented(&output, clk_id, time, refresh, seq, flags);
             }
+            let presented = Time::<Monotonic>::from(Duration::from(time));
+            for frame in self.frames.drain(..) {
+                frame.presented(presented);
+            }
         } else {
             self.discarded();
         }
@@ -418,6 +473,9 @@ impl OutputPresentationFeedback {
         for mut callback in self.callbacks.drain(..) {
             callback.discarded();
         }
+        for frame in self.frames.drain(..) {
+            frame.discarded();
+        }
     }
 }
 
@@ -428,6 +486,9 @@ impl OutputPresentationFeedback {
 /// to the [`OutputPresentationFeedback`]
 ///
 /// The flags closure can be used to set special flags per surface like [`wp_presentation_feedback::Kind::ZeroCopy`]
+///
+/// Commits of surfaces with [frame statistics](enable_frame_stats) enabled are tracked as well,
+/// regardless of the client requesting presentation feedback.
 pub fn take_presentation_feedback_surface_tree<F1, F2>(
     surface: &wl_surface::WlSurface,
     output_feedback: &mut OutputPresentationFeedback,
@@ -454,6 +515,9 @@ pub fn take_presentation_feedback_surface_tree<F1, F2>(
             if let Some(feedback) = SurfacePresentationFeedback::from_states(states, flags) {
                 output_feedback.callbacks.push(feedback);
             }
+            if let Some(frame) = PendingFrame::take_from_states(states) {
+                output_feedback.frames.push(frame);
+            }
         },
         |_, _, &()| true,
     );
@@ -499,3 +563,68 @@ impl SurfaceFrameThrottlingState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{surface_local_point_from_surface_tree, under_from_surface_tree};
+    use crate::{desktop::WindowSurfaceType, utils::Point, wayland::test_utils::TestFixture};
+
+    #[test]
+    fn subsurface_offset_input() {
+        let mut fixture = TestFixture::new();
+        let (parent, server_parent) = fixture.create_surface();
+        let (child, subsurface, server_child) = fixture.create_subsurface(&parent);
+        subsurface.set_position(10, 10);
+        subsurface.set_desync();
+        fixture.map(&child, 20, 20);
+        fixture.map(&parent, 100, 100);
+
+        let (surface, location) = under_from_surface_tree(
+            &server_parent,
+            (15.0, 15.0).into(),
+            (0, 0),
+            WindowSurfaceType::ALL,
+        )
+        .unwrap();
+        assert_eq!(surface, server_child);
+        assert_eq!(location, Point::from((10, 10)));
+
+        // the offset moves the subsurface relative to its previous content
+        let buffer = fixture.create_buffer(20, 20);
+        child.attach(Some(&buffer), 0, 0);
+        child.offset(15, 5);
+        child.commit();
+        fixture.roundtrip();
+
+        let (surface, local) = surface_local_point_from_surface_tree(
+            &server_parent,
+            (35.0, 30.0).into(),
+            WindowSurfaceType::ALL,
+        )
+        .unwrap();
+        assert_eq!(surface, server_child);
+        assert_eq!(local, Point::from((10.0, 15.0)));
+
+        // offsets accumulate
+        child.offset(10, 0);
+        child.commit();
+        fixture.roundtrip();
+        let (surface, _) = under_from_surface_tree(
+            &server_parent,
+            (30.0, 30.0).into(),
+            (0, 0),
+            WindowSurfaceType::ALL,
+        )
+        .unwrap();
+        assert_eq!(surface, server_parent);
+        let (surface, location) = under_from_surface_tree(
+            &server_parent,
+            (36.0, 16.0).into(),
+            (0, 0),
+            WindowSurfaceType::ALL,
+        )
+        .unwrap();
+        assert_eq!(surface, server_child);
+        assert_eq!(location, Point::from((35, 15)));
+    }
+}
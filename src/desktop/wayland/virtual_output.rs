@@ -0,0 +1,393 @@
+use std::{
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use crate::{
+    backend::renderer::{
+        damage::Error as DamageError,
+        element::{
+            composite::{CompositeLayer, CompositeLayerElement},
+            utils::{Relocate, RelocateRenderElement, RescaleRenderElement},
+            AsRenderElements, Element, Kind, RenderElement, RenderElementStates,
+        },
+        Offscreen, Renderer, Texture,
+    },
+    desktop::{
+        layer_map_for_output, space::SpaceElement, utils::surface_primary_scanout_output, Space, Window,
+    },
+    output::{Mode, Output, PhysicalProperties, Scale as OutputScale, Subpixel},
+    utils::{IsAlive, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+
+/// Render element showing the content of a [`VirtualOutput`]
+pub type VirtualOutputRenderElement<T> =
+    RelocateRenderElement<RescaleRenderElement<CompositeLayerElement<T>>>;
+
+/// Output without a display, whose content is shown inside other outputs
+///
+/// The output is a regular [`Output`], which can be mapped into a [`Space`] and advertised to clients,
+/// e.g. as a reference monitor for a screen recording or as a preview of another workspace. Its content
+/// is rendered into an offscreen texture, that is only updated in damaged regions, and shown through a
+/// [`VirtualOutputRenderElement`] wherever the compositor wants it, or through a [`VirtualOutputWindow`]
+/// mapped into a [`Space`] like any other window.
+///
+/// ```no_run
+/// # use smithay::{
+/// #     backend::renderer::{element::surface::WaylandSurfaceRenderElement, gles::{GlesRenderer, GlesTexture}},
+/// #     desktop::{Space, VirtualOutput, Window},
+/// # };
+/// # let mut renderer: GlesRenderer = todo!();
+/// # let mut space: Space<Window> = todo!();
+/// # let elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> = todo!();
+/// # let time = std::time::Duration::ZERO;
+/// let mut preview = VirtualOutput::<GlesTexture>::new("PREVIEW-1", (1280, 720), 60_000);
+/// space.map_output(preview.output(), (4000, 0));
+///
+/// // before rendering the output showing the preview
+/// preview.render(&mut renderer, &elements).expect("Failed to render the virtual output");
+/// preview.send_frames(&space, time, Some(std::time::Duration::from_secs(1)));
+/// let element = preview.render_element((100, 100), (640, 360));
+/// ```
+#[derive(Debug)]
+pub struct VirtualOutput<T> {
+    output: Output,
+    layer: CompositeLayer<T>,
+    element: Arc<Mutex<Option<CompositeLayerElement<T>>>>,
+}
+
+impl<T> VirtualOutput<T> {
+    /// Create a new virtual output with a single mode
+    ///
+    /// `refresh` is the refresh rate of the mode in mHz.
+    pub fn new(name: impl Into<String>, size: impl Into<Size<i32, Physical>>, refresh: i32) -> Self {
+        let output = Output::new(
+            name.into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Virtual".into(),
+            },
+        );
+        let mode = Mode {
+            size: size.into(),
+            refresh,
+        };
+        output.change_current_state(
+            Some(mode),
+            Some(Transform::Normal),
+            Some(OutputScale::Integer(1)),
+            None,
+        );
+        output.set_preferred(mode);
+
+        VirtualOutput {
+            output,
+            layer: CompositeLayer::new(),
+            element: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the output, e.g. to map it into a [`Space`] or to create a global for it
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Returns the time between two frames of the current mode
+    pub fn refresh_interval(&self) -> Duration {
+        self.output
+            .current_mode()
+            .filter(|mode| mode.refresh > 0)
+            .map(|mode| Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Returns the states of the elements of the last frame
+    ///
+    /// Use them to update the primary scan-out output of the rendered surfaces, like for any other output.
+    /// Surfaces only shown on the virtual output then receive their frame callbacks from
+    /// [`VirtualOutput::send_frames`].
+    pub fn render_element_states(&self) -> &RenderElementStates {
+        self.layer.render_element_states()
+    }
+
+    /// Drop the texture, e.g. while the content is not shown anywhere
+    pub fn reset(&mut self) {
+        self.layer.reset();
+        *self.element.lock().unwrap() = None;
+    }
+
+    /// Create a window showing the content of this output
+    ///
+    /// The window can be mapped into a [`Space`] and shows the last frame fitted into `size`.
+    pub fn window(&self, size: impl Into<Size<i32, Logical>>) -> VirtualOutputWindow<T> {
+        VirtualOutputWindow {
+            element: Arc::downgrade(&self.element),
+            size: Arc::new(Mutex::new(size.into())),
+        }
+    }
+
+    /// Send frame callbacks to the windows and layer surfaces on this output
+    ///
+    /// Should be called after every [`VirtualOutput::render`], see [`Window::send_frame`]
+    /// for the meaning of `throttle`.
+    pub fn send_frames(&self, space: &Space<Window>, time: impl Into<Duration>, throttle: Option<Duration>) {
+        let time = time.into();
+        for window in space.elements_for_output(&self.output) {
+            window.send_frame(&self.output, time, throttle, surface_primary_scanout_output);
+        }
+        for layer in layer_map_for_output(&self.output).layers() {
+            layer.send_frame(&self.output, time, throttle, surface_primary_scanout_output);
+        }
+    }
+}
+
+impl<T: Texture + Clone> VirtualOutput<T> {
+    /// Render the content of the output
+    ///
+    /// - `elements` in front-to-back order and relative to the output, like for a physical output
+    ///
+    /// Only the regions damaged since the last frame are drawn. The texture gets bound for
+    /// rendering, so this has to be called before binding the target of the output showing the content.
+    pub fn render<R, E>(&mut self, renderer: &mut R, elements: &[E]) -> Result<(), DamageError<R::Error>>
+    where
+        R: Renderer<TextureId = T> + Offscreen<T>,
+        E: RenderElement<R>,
+    {
+        let size = self
+            .output
+            .current_mode()
+            .map(|mode| mode.size)
+            .unwrap_or_default();
+        let scale = self.output.current_scale().fractional_scale();
+        let element = self
+            .layer
+            .render_element(renderer, size, scale, elements, Kind::Unspecified)?;
+        *self.element.lock().unwrap() = Some(element);
+        Ok(())
+    }
+
+    /// Returns an element showing the last frame, fitted into `size` at `location`
+    ///
+    /// The content keeps its aspect ratio. Returns `None` before the first frame was rendered.
+    pub fn render_element(
+        &self,
+        location: impl Into<Point<i32, Physical>>,
+        size: impl Into<Size<i32, Physical>>,
+    ) -> Option<VirtualOutputRenderElement<T>> {
+        let element = self.element.lock().unwrap().clone()?;
+        Some(fitted(element, location.into(), size.into()))
+    }
+}
+
+/// Element of a [`Space`] showing the content of a [`VirtualOutput`]
+///
+/// Created by [`VirtualOutput::window`]. The last frame of the output is fitted into the size
+/// of the window, keeping its aspect ratio. The window has no client, so input on it has to be
+/// handled by the compositor, e.g. to move it around. It is no longer alive once the
+/// [`VirtualOutput`] is dropped and gets removed from the space on the next [`Space::refresh`].
+#[derive(Debug, Clone)]
+pub struct VirtualOutputWindow<T> {
+    element: Weak<Mutex<Option<CompositeLayerElement<T>>>>,
+    size: Arc<Mutex<Size<i32, Logical>>>,
+}
+
+impl<T> PartialEq for VirtualOutputWindow<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.size, &other.size)
+    }
+}
+
+impl<T> VirtualOutputWindow<T> {
+    /// Returns the size of the window
+    pub fn size(&self) -> Size<i32, Logical> {
+        *self.size.lock().unwrap()
+    }
+
+    /// Set the size of the window
+    pub fn set_size(&self, size: impl Into<Size<i32, Logical>>) {
+        *self.size.lock().unwrap() = size.into();
+    }
+}
+
+impl<T> IsAlive for VirtualOutputWindow<T> {
+    fn alive(&self) -> bool {
+        self.element.strong_count() > 0
+    }
+}
+
+impl<T> SpaceElement for VirtualOutputWindow<T> {
+    fn bbox(&self) -> Rectangle<i32, Logical> {
+        Rectangle::from_size(self.size())
+    }
+
+    fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
+        self.bbox().to_f64().contains(*point)
+    }
+
+    fn set_activate(&self, _activated: bool) {}
+    fn output_enter(&self, _output: &Output, _overlap: Rectangle<i32, Logical>) {}
+    fn output_leave(&self, _output: &Output) {}
+}
+
+impl<R, T> AsRenderElements<R> for VirtualOutputWindow<T>
+where
+    R: Renderer<TextureId = T>,
+    T: Texture + Clone + 'static,
+{
+    type RenderElement = VirtualOutputRenderElement<T>;
+
+    fn render_elements<C: From<Self::RenderElement>>(
+        &self,
+        _renderer: &mut R,
+        location: Point<i32, Physical>,
+        scale: Scale<f64>,
+        alpha: f32,
+    ) -> Vec<C> {
+        let Some(element) = self
+            .element
+            .upgrade()
+            .and_then(|element| element.lock().unwrap().clone())
+        else {
+            return Vec::new();
+        };
+        let size = self.size().to_physical_precise_round(scale);
+        vec![C::from(fitted(element.with_alpha(alpha), location, size))]
+    }
+}
+
+fn fitted<T: Texture>(
+    element: CompositeLayerElement<T>,
+    location: Point<i32, Physical>,
+    size: Size<i32, Physical>,
+) -> VirtualOutputRenderElement<T> {
+    let scale = fit_scale(element.geometry(Scale::from(1.0)).size, size);
+    RelocateRenderElement::from_element(
+        RescaleRenderElement::from_element(element, Point::default(), scale),
+        location,
+        Relocate::Relative,
+    )
+}
+
+// uniform scale fitting content of `size` into `target`
+fn fit_scale(size: Size<i32, Physical>, target: Size<i32, Physical>) -> f64 {
+    if size.is_empty() {
+        return 1.0;
+    }
+    f64::min(target.w as f64 / size.w as f64, target.h as f64 / size.h as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_keeps_aspect_ratio() {
+        assert_eq!(fit_scale((1920, 1080).into(), (960, 540).into()), 0.5);
+        assert_eq!(fit_scale((1920, 1080).into(), (960, 960).into()), 0.5);
+        assert_eq!(fit_scale((1000, 1000).into(), (3000, 2000).into()), 2.0);
+        assert_eq!(fit_scale((0, 0).into(), (100, 100).into()), 1.0);
+    }
+
+    #[test]
+    fn refresh_interval() {
+        let output = VirtualOutput::<()>::new("VIRTUAL-1", (1280, 720), 50_000);
+        assert_eq!(output.refresh_interval(), Duration::from_millis(20));
+        assert_eq!(output.output().current_mode().unwrap().size, (1280, 720).into());
+    }
+
+    #[cfg(feature = "renderer_test")]
+    mod rendering {
+        use super::super::{VirtualOutput, VirtualOutputRenderElement, VirtualOutputWindow};
+        use crate::{
+            backend::renderer::{
+                element::{solid::SolidColorRenderElement, AsRenderElements, Element, Id, Kind},
+                test::{DummyRenderer, DummyTexture},
+                utils::CommitCounter,
+            },
+            desktop::Space,
+            utils::{IsAlive, Logical, Physical, Point, Rectangle, Scale},
+        };
+
+        fn solid(id: &Id, commit: CommitCounter) -> SolidColorRenderElement {
+            SolidColorRenderElement::new(
+                id.clone(),
+                Rectangle::new((10, 10).into(), (20, 20).into()),
+                commit,
+                [1.0, 0.0, 0.0, 1.0],
+                Kind::Unspecified,
+            )
+        }
+
+        #[test]
+        fn render_tracks_damage() {
+            let mut renderer = DummyRenderer::new();
+            let mut output = VirtualOutput::<DummyTexture>::new("VIRTUAL-1", (200, 100), 60_000);
+            assert!(output.render_element((0, 0), (100, 100)).is_none());
+
+            let id = Id::new();
+            let mut commit = CommitCounter::default();
+            output.render(&mut renderer, &[solid(&id, commit)]).unwrap();
+            assert!(output.render_element_states().element_was_presented(id.clone()));
+
+            // fitted into the target keeping the aspect ratio
+            let element = output.render_element((5, 5), (100, 100)).unwrap();
+            assert_eq!(
+                element.geometry(Scale::from(1.0)),
+                Rectangle::<i32, Physical>::new((5, 5).into(), (100, 50).into())
+            );
+            let first = element.current_commit();
+
+            // unchanged content keeps the commit, damaged content is reported
+            output.render(&mut renderer, &[solid(&id, commit)]).unwrap();
+            let element = output.render_element((5, 5), (100, 100)).unwrap();
+            assert_eq!(element.current_commit(), first);
+            commit.increment();
+            output.render(&mut renderer, &[solid(&id, commit)]).unwrap();
+            let element = output.render_element((5, 5), (100, 100)).unwrap();
+            assert_ne!(element.current_commit(), first);
+            assert!(!element.damage_since(Scale::from(1.0), Some(first)).is_empty());
+
+            output.reset();
+            assert!(output.render_element((5, 5), (100, 100)).is_none());
+        }
+
+        #[test]
+        fn window_in_space() {
+            let mut renderer = DummyRenderer::new();
+            let mut output = VirtualOutput::<DummyTexture>::new("VIRTUAL-1", (200, 100), 60_000);
+            let window = output.window((100, 100));
+            let mut space = Space::<VirtualOutputWindow<DummyTexture>>::default();
+            space.map_element(window.clone(), (50, 50), false);
+
+            let (under, location) = space.element_under((60.0, 60.0)).unwrap();
+            assert_eq!(under, &window);
+            assert_eq!(location, Point::<i32, Logical>::from((50, 50)));
+            assert!(space.element_under((160.0, 60.0)).is_none());
+
+            // nothing to show before the first frame
+            let elements: Vec<VirtualOutputRenderElement<DummyTexture>> =
+                window.render_elements(&mut renderer, (50, 50).into(), Scale::from(2.0), 1.0);
+            assert!(elements.is_empty());
+
+            output
+                .render(&mut renderer, &[solid(&Id::new(), CommitCounter::default())])
+                .unwrap();
+            let elements: Vec<VirtualOutputRenderElement<DummyTexture>> =
+                window.render_elements(&mut renderer, (100, 100).into(), Scale::from(2.0), 0.5);
+            assert_eq!(elements.len(), 1);
+            assert_eq!(
+                elements[0].geometry(Scale::from(1.0)),
+                Rectangle::<i32, Physical>::new((100, 100).into(), (200, 100).into())
+            );
+            assert_eq!(elements[0].alpha(), 0.5);
+
+            // the window goes away with the output
+            drop(output);
+            assert!(!window.alive());
+            space.refresh();
+            assert_eq!(space.elements().count(), 0);
+        }
+    }
+}
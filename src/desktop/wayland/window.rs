@@ -3,12 +3,12 @@ use crate::{desktop::space::SpaceElement, xwayland::X11Surface};
 use crate::{
     desktop::{space::RenderZindex, utils::*, PopupManager},
     output::Output,
-    utils::{user_data::UserDataMap, IsAlive, Logical, Point, Rectangle},
+    utils::{user_data::UserDataMap, IsAlive, Logical, Point, Rectangle, Serial},
     wayland::{
         compositor::{with_states, SurfaceData},
         dmabuf::DmabufFeedback,
         seat::WaylandFocus,
-        shell::xdg::{SurfaceCachedState, ToplevelSurface},
+        shell::xdg::{SurfaceCachedState, ToplevelSurface, XdgToplevelSurfaceData},
     },
 };
 use std::{
@@ -23,7 +23,7 @@ use std::{
 use wayland_protocols::{
     wp::presentation_time::server::wp_presentation_feedback, xdg::shell::server::xdg_toplevel,
 };
-use wayland_server::protocol::wl_surface;
+use wayland_server::protocol::{wl_output, wl_surface};
 
 crate::utils::ids::id_gen!(window_id);
 
@@ -43,6 +43,8 @@ pub(crate) struct WindowInner {
     surface: WindowSurface,
     bbox: Mutex<Rectangle<i32, Logical>>,
     pub(crate) z_index: AtomicU8,
+    mode: Mutex<ModeState>,
+    scale_override: Mutex<Option<f64>>,
     user_data: UserDataMap,
 }
 
@@ -101,6 +103,44 @@ bitflags::bitflags! {
     }
 }
 
+/// Display mode of a [`Window`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WindowMode {
+    /// The window is neither maximized nor fullscreen
+    #[default]
+    Normal,
+    /// The window is maximized
+    Maximized,
+    /// The window is fullscreen
+    Fullscreen,
+}
+
+/// Transition of a [`Window`] between two [`WindowMode`]s
+///
+/// See [`Window::take_finished_transition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeTransition {
+    /// Mode before the transition
+    pub from: WindowMode,
+    /// Mode after the transition
+    pub to: WindowMode,
+    /// Geometry of the window before the transition
+    pub from_geometry: Rectangle<i32, Logical>,
+    /// Geometry requested for the new mode
+    ///
+    /// This is `None` for transitions to [`WindowMode::Normal`] without a known restore geometry,
+    /// in which case the client picks its size.
+    pub to_geometry: Option<Rectangle<i32, Logical>>,
+}
+
+#[derive(Debug, Default)]
+struct ModeState {
+    mode: WindowMode,
+    restore_geometry: Option<Rectangle<i32, Logical>>,
+    pending: Option<(ModeTransition, Option<Serial>)>,
+    finished: Option<ModeTransition>,
+}
+
 impl Window {
     /// Construct a new [`Window`] from a xdg toplevel surface
     ///
@@ -119,6 +159,8 @@ impl Window {
             surface: WindowSurface::Wayland(toplevel),
             bbox: Mutex::new(Rectangle::zero()),
             z_index: AtomicU8::new(RenderZindex::Shell as u8),
+            mode: Mutex::new(ModeState::default()),
+            scale_override: Mutex::new(None),
             user_data: UserDataMap::new(),
         }))
     }
@@ -133,6 +175,8 @@ impl Window {
             surface: WindowSurface::X11(surface),
             bbox: Mutex::new(Rectangle::zero()),
             z_index: AtomicU8::new(RenderZindex::Shell as u8),
+            mode: Mutex::new(ModeState::default()),
+            scale_override: Mutex::new(None),
             user_data: UserDataMap::new(),
         }))
     }
@@ -218,6 +262,173 @@ impl Window {
         }
     }
 
+    /// Returns the mode last requested for this window
+    pub fn mode(&self) -> WindowMode {
+        self.0.mode.lock().unwrap().mode
+    }
+
+    /// Returns the geometry the window had before it was maximized or made fullscreen
+    pub fn restore_geometry(&self) -> Option<Rectangle<i32, Logical>> {
+        self.0.mode.lock().unwrap().restore_geometry
+    }
+
+    /// Request this window to be maximized to `geometry`
+    ///
+    /// `current_geometry` is the geometry of the window in the coordinate space used by the compositor,
+    /// usually the [`Space`](crate::desktop::Space). It is saved to be restored by [`Window::restore`],
+    /// unless the window is already maximized or fullscreen.
+    ///
+    /// For xdg toplevels a configure is sent, if the initial configure was already sent.
+    /// Once the client committed the new state, the transition is returned by [`Window::take_finished_transition`]
+    /// and the window should be moved to `geometry.loc`.
+    pub fn set_maximized(
+        &self,
+        current_geometry: Rectangle<i32, Logical>,
+        geometry: Rectangle<i32, Logical>,
+    ) {
+        self.request_mode(WindowMode::Maximized, current_geometry, Some(geometry), None);
+    }
+
+    /// Request this window to be made fullscreen with the given `geometry`
+    ///
+    /// `output` is the `wl_output` of the client, that is reported to xdg toplevels.
+    ///
+    /// See [`Window::set_maximized`] for details.
+    pub fn set_fullscreen(
+        &self,
+        current_geometry: Rectangle<i32, Logical>,
+        geometry: Rectangle<i32, Logical>,
+        output: Option<wl_output::WlOutput>,
+    ) {
+        self.request_mode(WindowMode::Fullscreen, current_geometry, Some(geometry), output);
+    }
+
+    /// Request this window to return to the [`WindowMode::Normal`] mode
+    ///
+    /// Returns the geometry saved by [`Window::set_maximized`] or [`Window::set_fullscreen`].
+    /// The window should be moved to its location once the transition finished,
+    /// see [`Window::take_finished_transition`].
+    pub fn restore(&self, current_geometry: Rectangle<i32, Logical>) -> Option<Rectangle<i32, Logical>> {
+        let geometry = self.0.mode.lock().unwrap().restore_geometry;
+        self.request_mode(WindowMode::Normal, current_geometry, geometry, None);
+        geometry
+    }
+
+    /// Returns the last mode transition, that the window finished since the last call
+    ///
+    /// A transition of a xdg toplevel finishes once the client committed a buffer for the
+    /// configure of the new mode, so this should be called after [`Window::on_commit`].
+    /// Transitions of X11 windows finish immediately. This can be used to start or end animations
+    /// and to move the window to its new location.
+    pub fn take_finished_transition(&self) -> Option<ModeTransition> {
+        self.0.mode.lock().unwrap().finished.take()
+    }
+
+    fn request_mode(
+        &self,
+        mode: WindowMode,
+        current_geometry: Rectangle<i32, Logical>,
+        geometry: Option<Rectangle<i32, Logical>>,
+        output: Option<wl_output::WlOutput>,
+    ) {
+        let mut state = self.0.mode.lock().unwrap();
+        let transition = ModeTransition {
+            from: state.mode,
+            to: mode,
+            from_geometry: current_geometry,
+            to_geometry: geometry,
+        };
+        match (state.mode, mode) {
+            (WindowMode::Normal, WindowMode::Maximized | WindowMode::Fullscreen) => {
+                state.restore_geometry = Some(current_geometry);
+            }
+            (_, WindowMode::Normal) => state.restore_geometry = None,
+            _ => {}
+        }
+        state.mode = mode;
+
+        match &self.0.surface {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.with_pending_state(|state| {
+                    match mode {
+                        WindowMode::Normal => {
+                            state.states.unset(xdg_toplevel::State::Maximized);
+                            state.states.unset(xdg_toplevel::State::Fullscreen);
+                        }
+                        WindowMode::Maximized => {
+                            state.states.set(xdg_toplevel::State::Maximized);
+                            state.states.unset(xdg_toplevel::State::Fullscreen);
+                        }
+                        WindowMode::Fullscreen => {
+                            state.states.set(xdg_toplevel::State::Fullscreen);
+                        }
+                    }
+                    state.size = geometry.map(|geometry| geometry.size);
+                    state.fullscreen_output = output;
+                });
+                if !toplevel.is_initial_configure_sent() {
+                    // The state is sent with the initial configure
+                    state.pending = Some((transition, None));
+                } else if let Some(serial) = toplevel.send_pending_configure() {
+                    state.pending = Some((transition, Some(serial)));
+                } else {
+                    // Nothing changed
+                    state.pending = None;
+                    state.finished = Some(transition);
+                }
+            }
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => {
+                let _ = surface.set_maximized(mode == WindowMode::Maximized);
+                let _ = surface.set_fullscreen(mode == WindowMode::Fullscreen);
+                if let Some(geometry) = geometry {
+                    let _ = surface.configure(geometry);
+                }
+                state.pending = None;
+                state.finished = Some(transition);
+            }
+        }
+    }
+
+    fn update_transition(&self) {
+        let Some(toplevel) = self.toplevel() else {
+            return;
+        };
+        let mut state = self.0.mode.lock().unwrap();
+        let Some((transition, serial)) = state.pending else {
+            return;
+        };
+
+        let finished = with_states(toplevel.wl_surface(), |states| {
+            let attributes = states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap();
+            let mode = if attributes
+                .current
+                .states
+                .contains(xdg_toplevel::State::Fullscreen)
+            {
+                WindowMode::Fullscreen
+            } else if attributes.current.states.contains(xdg_toplevel::State::Maximized) {
+                WindowMode::Maximized
+            } else {
+                WindowMode::Normal
+            };
+            match (attributes.current_serial, serial) {
+                (Some(current), Some(serial)) => current >= serial,
+                (Some(_), None) => mode == transition.to,
+                (None, _) => false,
+            }
+        });
+        if finished {
+            state.pending = None;
+            state.finished = Some(transition);
+        }
+    }
+
     /// Sends the frame callback to all the subsurfaces in this window that requested it
     ///
     /// See [`send_frames_surface_tree`] for more information
@@ -325,6 +536,7 @@ impl Window {
         if let Some(surface) = self.wl_surface() {
             *self.0.bbox.lock().unwrap() = bbox_from_surface_tree(&surface, (0, 0));
         }
+        self.update_transition();
     }
 
     /// Finds the topmost surface under this point matching the input regions of the surface and returns
@@ -378,11 +590,79 @@ impl Window {
         }
     }
 
+    /// Returns if this window is a transient child of `parent`
+    ///
+    /// This is the case for xdg toplevels with `parent` as their parent, including parents set
+    /// through [xdg-foreign](crate::wayland::xdg_foreign), and X11 windows transient for `parent`.
+    pub fn is_transient_for(&self, parent: &Window) -> bool {
+        match (&self.0.surface, &parent.0.surface) {
+            (WindowSurface::Wayland(child), WindowSurface::Wayland(parent)) => {
+                child.parent().as_ref() == Some(parent.wl_surface())
+            }
+            #[cfg(feature = "xwayland")]
+            (WindowSurface::X11(child), WindowSurface::X11(parent)) => {
+                child.is_transient_for() == Some(parent.window_id())
+            }
+            #[cfg(feature = "xwayland")]
+            _ => false,
+        }
+    }
+
     /// Returns the underlying surface
     pub fn underlying_surface(&self) -> &WindowSurface {
         &self.0.surface
     }
 
+    /// Returns the scale override of this window, see [`Window::set_scale_override`]
+    pub fn scale_override(&self) -> Option<f64> {
+        *self.0.scale_override.lock().unwrap()
+    }
+
+    /// Override the scale this window is shown with
+    ///
+    /// The window is rendered scaled by `scale` on top of the output scale, without the client
+    /// being aware of it. E.g. a scale of `2.0` shows an application without support for high-dpi
+    /// outputs at twice its size, blurry but usable. Non-positive scales are ignored.
+    ///
+    /// The scale applies to the [`SpaceElement`](crate::desktop::space::SpaceElement) implementation,
+    /// so the geometry, bounding box and input region of the window in a [`Space`](crate::desktop::Space)
+    /// are scaled, while [`Window::geometry`], [`Window::bbox`] and [`Window::surface_under`] stay in the
+    /// coordinate space of the client. Use [`Window::surface_under_in_space`] to look up the surface under
+    /// the pointer, which compensates the scale.
+    pub fn set_scale_override(&self, scale: Option<f64>) {
+        *self.0.scale_override.lock().unwrap() = scale.filter(|scale| scale.is_finite() && *scale > 0.0);
+    }
+
+    pub(crate) fn scale_factor(&self) -> f64 {
+        self.scale_override().unwrap_or(1.0)
+    }
+
+    /// Maps a point relative to (0,0) of the window as shown in a [`Space`](crate::desktop::Space)
+    /// to the coordinate space of its surfaces, compensating the [scale override](Window::set_scale_override)
+    ///
+    /// The result can be passed to [`Window::surface_under`]. To report the correct position to the focused
+    /// surface, the mapped point offset by the window location has to be used as the pointer location.
+    pub fn to_surface_space(&self, point: Point<f64, Logical>) -> Point<f64, Logical> {
+        point.downscale(self.scale_factor())
+    }
+
+    /// Finds the topmost surface under a point relative to (0,0) of the window as shown in a [`Space`](crate::desktop::Space)
+    ///
+    /// Like [`Window::surface_under`], but compensating the [scale override](Window::set_scale_override).
+    /// The returned location is relative to (0,0) of the window in the space and chosen, so that `point`
+    /// minus the location is the position of the point on the surface. With a scale override
+    /// the location is only valid for the given point and has to be updated on every pointer motion.
+    pub fn surface_under_in_space<P: Into<Point<f64, Logical>>>(
+        &self,
+        point: P,
+        surface_type: WindowSurfaceType,
+    ) -> Option<(wl_surface::WlSurface, Point<f64, Logical>)> {
+        let point = point.into();
+        let surface_point = self.to_surface_space(point);
+        self.surface_under(surface_point, surface_type)
+            .map(|(surface, location)| (surface, point - (surface_point - location.to_f64())))
+    }
+
     /// Override the z_index of this Window
     pub fn override_z_index(&self, z_index: u8) {
         self.0.z_index.store(z_index, Ordering::SeqCst);
@@ -405,3 +685,155 @@ impl WaylandFocus for Window {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wayland_protocols::xdg::shell::server::xdg_toplevel;
+
+    use super::{ModeTransition, Window, WindowMode};
+    use crate::{
+        desktop::{Space, WindowSurfaceType},
+        utils::{Logical, Point, Rectangle},
+        wayland::test_utils::TestFixture,
+    };
+
+    #[test]
+    fn scale_override_input() {
+        let mut fixture = TestFixture::new();
+        let (surface, _toplevel, server_toplevel) = fixture.create_toplevel();
+        fixture.map(&surface, 100, 100);
+        let window = Window::new_wayland_window(server_toplevel);
+        window.on_commit();
+
+        let mut space = Space::<Window>::default();
+        space.map_element(window.clone(), (10, 10), false);
+        assert!(space.element_under((150.0, 150.0)).is_none());
+
+        window.set_scale_override(Some(2.0));
+        let (element, location) = space.element_under((150.0, 150.0)).unwrap();
+        assert_eq!(element, &window);
+        assert_eq!(location, Point::from((10, 10)));
+
+        // the pointer is at (70, 70) on the surface
+        let point = Point::<f64, Logical>::from((140.0, 140.0));
+        let (under, surface_location) = window
+            .surface_under_in_space(point, WindowSurfaceType::ALL)
+            .unwrap();
+        assert_eq!(under, *window.toplevel().unwrap().wl_surface());
+        assert_eq!(point - surface_location, Point::from((70.0, 70.0)));
+
+        // outside of the scaled surface
+        assert!(window
+            .surface_under_in_space((201.0, 50.0), WindowSurfaceType::ALL)
+            .is_none());
+
+        // without the override the point is outside of the window
+        window.set_scale_override(None);
+        assert!(window
+            .surface_under_in_space(point, WindowSurfaceType::ALL)
+            .is_none());
+    }
+
+    #[test]
+    fn maximize_and_restore() {
+        let mut fixture = TestFixture::new();
+        let (surface, _toplevel, server_toplevel) = fixture.create_toplevel();
+        fixture.map(&surface, 100, 100);
+        let window = Window::new_wayland_window(server_toplevel);
+        window.on_commit();
+
+        let normal = Rectangle::<i32, Logical>::new((10, 10).into(), (100, 100).into());
+        let maximized = Rectangle::<i32, Logical>::new((0, 0).into(), (800, 600).into());
+        window.set_maximized(normal, maximized);
+        assert_eq!(window.mode(), WindowMode::Maximized);
+        assert_eq!(window.restore_geometry(), Some(normal));
+        assert_eq!(window.take_finished_transition(), None);
+
+        // acked, but not yet committed
+        fixture.roundtrip();
+        window.on_commit();
+        assert_eq!(window.take_finished_transition(), None);
+
+        fixture.map(&surface, 800, 600);
+        window.on_commit();
+        assert_eq!(
+            window.take_finished_transition(),
+            Some(ModeTransition {
+                from: WindowMode::Normal,
+                to: WindowMode::Maximized,
+                from_geometry: normal,
+                to_geometry: Some(maximized),
+            })
+        );
+        assert_eq!(window.take_finished_transition(), None);
+
+        assert_eq!(window.restore(maximized), Some(normal));
+        assert_eq!(window.mode(), WindowMode::Normal);
+        assert_eq!(window.restore_geometry(), None);
+        fixture.roundtrip();
+        fixture.map(&surface, 100, 100);
+        window.on_commit();
+        assert_eq!(
+            window.take_finished_transition(),
+            Some(ModeTransition {
+                from: WindowMode::Maximized,
+                to: WindowMode::Normal,
+                from_geometry: maximized,
+                to_geometry: Some(normal),
+            })
+        );
+    }
+
+    #[test]
+    fn fullscreen_keeps_restore_geometry() {
+        let mut fixture = TestFixture::new();
+        let (surface, _toplevel, server_toplevel) = fixture.create_toplevel();
+        fixture.map(&surface, 100, 100);
+        let window = Window::new_wayland_window(server_toplevel.clone());
+        window.on_commit();
+
+        let normal = Rectangle::<i32, Logical>::new((10, 10).into(), (100, 100).into());
+        let maximized = Rectangle::<i32, Logical>::new((0, 20).into(), (800, 580).into());
+        let fullscreen = Rectangle::<i32, Logical>::new((0, 0).into(), (800, 600).into());
+        window.set_maximized(normal, maximized);
+        window.set_fullscreen(maximized, fullscreen, None);
+        assert_eq!(window.mode(), WindowMode::Fullscreen);
+        assert_eq!(window.restore_geometry(), Some(normal));
+
+        // only the latest configure finishes the transition
+        fixture.roundtrip();
+        fixture.map(&surface, 800, 600);
+        window.on_commit();
+        let transition = window.take_finished_transition().unwrap();
+        assert_eq!(transition.from, WindowMode::Maximized);
+        assert_eq!(transition.to, WindowMode::Fullscreen);
+        assert!(server_toplevel
+            .current_state()
+            .states
+            .contains(xdg_toplevel::State::Fullscreen));
+
+        assert_eq!(window.restore(fullscreen), Some(normal));
+    }
+
+    #[test]
+    fn unchanged_mode_finishes_immediately() {
+        let mut fixture = TestFixture::new();
+        let (surface, _toplevel, server_toplevel) = fixture.create_toplevel();
+        let window = Window::new_wayland_window(server_toplevel);
+
+        let maximized = Rectangle::<i32, Logical>::new((0, 0).into(), (800, 600).into());
+        window.set_maximized(Rectangle::default(), maximized);
+        fixture.roundtrip();
+        fixture.map(&surface, 800, 600);
+        window.on_commit();
+        let transition = window.take_finished_transition().unwrap();
+        assert_eq!(transition.to, WindowMode::Maximized);
+
+        // no configure is necessary
+        window.set_maximized(maximized, maximized);
+        assert_eq!(
+            window.take_finished_transition().map(|t| t.to),
+            Some(WindowMode::Maximized)
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use wayland_protocols::xdg::shell::server::xdg_toplevel;
+
+use crate::{
+    desktop::{Space, Window, WindowSurface},
+    utils::{Logical, Rectangle},
+    wayland::{compositor::with_states, shell::xdg::XdgToplevelSurfaceData},
+};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+crate::user_data_key!(WindowListIdKey: WindowListId = WindowListId(NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+
+/// Identifier of a [`Window`] in a [`WindowListSnapshot`]
+///
+/// Unlike the window itself the id can be handed out to other processes. It is never reused,
+/// so stale ids of destroyed windows don't refer to new windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowListId(u64);
+
+impl WindowListId {
+    /// Returns the id of a window
+    pub fn of(window: &Window) -> Self {
+        *window.user_data().get_key::<WindowListIdKey>()
+    }
+
+    /// Returns the raw value of this id
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// Find the window with this id in a space, e.g. to apply a request of a scripting client
+    pub fn find_in<'a>(&self, space: &'a Space<Window>) -> Option<&'a Window> {
+        space.elements().find(|window| WindowListId::of(window) == *self)
+    }
+}
+
+impl fmt::Display for WindowListId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// States of a window in a [`WindowListSnapshot`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowStates {
+    /// The window is activated
+    pub activated: bool,
+    /// The window is maximized
+    pub maximized: bool,
+    /// The window is fullscreen
+    pub fullscreen: bool,
+    /// The window is minimized
+    pub minimized: bool,
+}
+
+/// Metadata of a window in a [`WindowListSnapshot`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowInfo {
+    /// Id of the window
+    pub id: WindowListId,
+    /// Application id, or class of X11 windows
+    pub app_id: Option<String>,
+    /// Title of the window
+    pub title: Option<String>,
+    /// Geometry of the window in its space, if mapped
+    pub geometry: Option<Rectangle<i32, Logical>>,
+    /// States of the window
+    pub states: WindowStates,
+    /// Names of the outputs the window is shown on
+    pub outputs: Vec<String>,
+    /// Workspace the window belongs to, if the compositor has any
+    pub workspace: Option<String>,
+}
+
+impl WindowInfo {
+    /// Collect the metadata of a window, which is not mapped in a space
+    pub fn from_window(window: &Window) -> Self {
+        let (app_id, title, states) = match window.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                let (app_id, title) = with_states(toplevel.wl_surface(), |states| {
+                    states
+                        .data_map
+                        .get::<XdgToplevelSurfaceData>()
+                        .map(|data| {
+                            let data = data.lock().unwrap();
+                            (data.app_id.clone(), data.title.clone())
+                        })
+                        .unwrap_or_default()
+                });
+                let current = toplevel.current_state();
+                let states = WindowStates {
+                    activated: current.states.contains(xdg_toplevel::State::Activated),
+                    maximized: current.states.contains(xdg_toplevel::State::Maximized),
+                    fullscreen: current.states.contains(xdg_toplevel::State::Fullscreen),
+                    minimized: false,
+                };
+                (app_id, title, states)
+            }
+            #[cfg(feature = "xwayland")]
+            WindowSurface::X11(surface) => {
+                let states = WindowStates {
+                    activated: surface.is_activated(),
+                    maximized: surface.is_maximized(),
+                    fullscreen: surface.is_fullscreen(),
+                    minimized: surface.is_minimized(),
+                };
+                (Some(surface.class()), Some(surface.title()), states)
+            }
+        };
+
+        WindowInfo {
+            id: WindowListId::of(window),
+            app_id,
+            title,
+            geometry: None,
+            states,
+            outputs: Vec::new(),
+            workspace: None,
+        }
+    }
+}
+
+/// Cheap snapshot of the windows managed by the compositor
+///
+/// The snapshot only contains plain data and can be kept, compared or sent to other processes,
+/// e.g. to implement a taskbar or a scripting interface, without touching any wayland objects.
+/// Windows are listed in stacking order, from bottom to top. Use [`WindowList`] to be notified
+/// about changes between snapshots.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WindowListSnapshot {
+    windows: Vec<WindowInfo>,
+}
+
+impl WindowListSnapshot {
+    /// Create an empty snapshot
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a snapshot of the windows mapped in a space
+    pub fn from_space(space: &Space<Window>) -> Self {
+        let mut snapshot = Self::new();
+        snapshot.add_space(space, None);
+        snapshot
+    }
+
+    /// Add the windows mapped in a space, e.g. for every workspace of the compositor
+    pub fn add_space(&mut self, space: &Space<Window>, workspace: Option<&str>) {
+        for window in space.elements() {
+            let mut info = WindowInfo::from_window(window);
+            info.geometry = space.element_geometry(window);
+            info.outputs = space
+                .outputs_for_element(window)
+                .into_iter()
+                .map(|output| output.name())
+                .collect();
+            info.workspace = workspace.map(str::to_owned);
+            self.windows.push(info);
+        }
+    }
+
+    /// Add a window, e.g. a minimized window not mapped in any space
+    pub fn add(&mut self, info: WindowInfo) {
+        self.windows.push(info);
+    }
+
+    /// Returns the windows of this snapshot
+    pub fn windows(&self) -> &[WindowInfo] {
+        &self.windows
+    }
+
+    /// Returns the window with the given id
+    pub fn get(&self, id: WindowListId) -> Option<&WindowInfo> {
+        self.windows.iter().find(|info| info.id == id)
+    }
+}
+
+/// Change between two [`WindowListSnapshot`]s
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowListChange<'a> {
+    /// A window was added
+    Added(&'a WindowInfo),
+    /// The metadata of a window changed
+    Changed(&'a WindowInfo),
+    /// A window was removed
+    Removed(WindowListId),
+    /// The stacking order of the windows changed
+    Restacked,
+}
+
+/// Handle of a callback subscribed to a [`WindowList`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WindowListSubscription(usize);
+
+type Subscriber = Box<dyn FnMut(&WindowListChange<'_>)>;
+
+/// Tracks [`WindowListSnapshot`]s and notifies subscribers about changes
+///
+/// The compositor updates the list with a new snapshot whenever convenient, e.g. once per frame
+/// or after handling client requests. Subscribers, e.g. an IPC server, are called for every
+/// difference to the previous snapshot.
+#[derive(Default)]
+pub struct WindowList {
+    current: WindowListSnapshot,
+    subscribers: Vec<(WindowListSubscription, Subscriber)>,
+    next_subscription: usize,
+}
+
+impl fmt::Debug for WindowList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WindowList")
+            .field("current", &self.current)
+            .field("subscribers", &self.subscribers.len())
+            .finish()
+    }
+}
+
+impl WindowList {
+    /// Create an empty window list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current snapshot
+    pub fn snapshot(&self) -> &WindowListSnapshot {
+        &self.current
+    }
+
+    /// Subscribe to changes of the window list
+    pub fn subscribe<F>(&mut self, callback: F) -> WindowListSubscription
+    where
+        F: FnMut(&WindowListChange<'_>) + 'static,
+    {
+        let subscription = WindowListSubscription(self.next_subscription);
+        self.next_subscription += 1;
+        self.subscribers.push((subscription, Box::new(callback)));
+        subscription
+    }
+
+    /// Remove a subscription
+    pub fn unsubscribe(&mut self, subscription: WindowListSubscription) {
+        self.subscribers.retain(|(s, _)| *s != subscription);
+    }
+
+    /// Replace the current snapshot, notifying subscribers about the differences
+    ///
+    /// Returns if anything changed.
+    pub fn update(&mut self, snapshot: WindowListSnapshot) -> bool {
+        let mut changes = Vec::new();
+        for old in &self.current.windows {
+            if snapshot.get(old.id).is_none() {
+                changes.push(WindowListChange::Removed(old.id));
+            }
+        }
+        for new in &snapshot.windows {
+            match self.current.get(new.id) {
+                None => changes.push(WindowListChange::Added(new)),
+                Some(old) if old != new => changes.push(WindowListChange::Changed(new)),
+                Some(_) => {}
+            }
+        }
+        let old_order = self
+            .current
+            .windows
+            .iter()
+            .map(|info| info.id)
+            .filter(|id| snapshot.get(*id).is_some());
+        let new_order = snapshot
+            .windows
+            .iter()
+            .map(|info| info.id)
+            .filter(|id| self.current.get(*id).is_some());
+        if !old_order.eq(new_order) {
+            changes.push(WindowListChange::Restacked);
+        }
+
+        for change in &changes {
+            for (_, subscriber) in &mut self.subscribers {
+                subscriber(change);
+            }
+        }
+        let changed = !changes.is_empty();
+        drop(changes);
+
+        self.current = snapshot;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    fn info(id: u64, title: &str) -> WindowInfo {
+        WindowInfo {
+            id: WindowListId(id),
+            app_id: None,
+            title: Some(title.into()),
+            geometry: None,
+            states: WindowStates::default(),
+            outputs: Vec::new(),
+            workspace: None,
+        }
+    }
+
+    #[test]
+    fn window_list_changes() {
+        let mut list = WindowList::new();
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let changes_ref = changes.clone();
+        list.subscribe(move |change| {
+            changes_ref.borrow_mut().push(match change {
+                WindowListChange::Added(info) => format!("added {}", info.id),
+                WindowListChange::Changed(info) => format!("changed {}", info.id),
+                WindowListChange::Removed(id) => format!("removed {}", id),
+                WindowListChange::Restacked => "restacked".into(),
+            })
+        });
+
+        let mut snapshot = WindowListSnapshot::new();
+        snapshot.add(info(1, "a"));
+        snapshot.add(info(2, "b"));
+        assert!(list.update(snapshot.clone()));
+        assert!(!list.update(snapshot));
+        assert_eq!(*changes.borrow(), ["added 1", "added 2"]);
+        changes.borrow_mut().clear();
+
+        let mut snapshot = WindowListSnapshot::new();
+        snapshot.add(info(2, "b"));
+        snapshot.add(info(1, "c"));
+        snapshot.add(info(3, "d"));
+        assert!(list.update(snapshot));
+        assert_eq!(*changes.borrow(), ["changed 1", "added 3", "restacked"]);
+        changes.borrow_mut().clear();
+
+        list.update(WindowListSnapshot::new());
+        assert_eq!(*changes.borrow(), ["removed 2", "removed 1", "removed 3"]);
+    }
+}
@@ -29,6 +29,11 @@ pub use keymap_file::KeymapFile;
 mod modifiers_state;
 pub use modifiers_state::{ModifiersState, SerializedMods};
 
+mod shortcuts;
+pub use shortcuts::{
+    Accelerator, AcceleratorParseError, GlobalShortcut, ShortcutConflict, ShortcutEvent, ShortcutRegistry,
+};
+
 mod xkb_config;
 pub use xkb_config::XkbConfig;
 
@@ -136,7 +141,7 @@ impl LedState {
 pub struct Xkb {
     context: xkb::Context,
     keymap: xkb::Keymap,
-    state: xkb::State,
+    pub(crate) state: xkb::State,
 }
 
 impl Xkb {
@@ -208,6 +213,10 @@ pub(crate) struct KbdInternal<D: SeatHandler> {
     pending_focus: Option<<D as SeatHandler>::KeyboardFocus>,
     pub(crate) pressed_keys: HashSet<Keycode>,
     pub(crate) forwarded_pressed_keys: HashSet<Keycode>,
+    enter_keys: EnterKeys,
+    suppress_unmatched_releases: bool,
+    // release of a key unknown to the focus, which is currently passed through the grab
+    unmatched_release: Option<Keycode>,
     pub(crate) mods_state: ModifiersState,
     xkb: Arc<Mutex<Xkb>>,
     pub(crate) repeat_rate: i32,
@@ -225,6 +234,9 @@ impl<D: SeatHandler> fmt::Debug for KbdInternal<D> {
             .field("pending_focus", &self.pending_focus)
             .field("pressed_keys", &self.pressed_keys)
             .field("forwarded_pressed_keys", &self.forwarded_pressed_keys)
+            .field("enter_keys", &self.enter_keys)
+            .field("suppress_unmatched_releases", &self.suppress_unmatched_releases)
+            .field("unmatched_release", &self.unmatched_release)
             .field("mods_state", &self.mods_state)
             .field("xkb", &self.xkb)
             .field("repeat_rate", &self.repeat_rate)
@@ -255,6 +267,9 @@ impl<D: SeatHandler + 'static> KbdInternal<D> {
             pending_focus: None,
             pressed_keys: HashSet::new(),
             forwarded_pressed_keys: HashSet::new(),
+            enter_keys: EnterKeys::default(),
+            suppress_unmatched_releases: false,
+            unmatched_release: None,
             mods_state: ModifiersState::default(),
             xkb: Arc::new(Mutex::new(Xkb {
                 context,
@@ -296,6 +311,41 @@ impl<D: SeatHandler + 'static> KbdInternal<D> {
         (modifiers_changed, leds_changed)
     }
 
+    // rebuilds the xkb state from the given pressed keys, keeping locked modifiers and layouts,
+    // returns whether the modifiers or led state has changed
+    fn resync_keys(&mut self, pressed_keys: HashSet<Keycode>) -> (bool, bool) {
+        let mut xkb = self.xkb.lock().unwrap();
+        let locked_mods = xkb.state.serialize_mods(xkb::STATE_MODS_LOCKED);
+        let locked_layout = xkb.state.serialize_layout(xkb::STATE_LAYOUT_LOCKED);
+
+        let mut state = xkb::State::new(&xkb.keymap);
+        for key in &pressed_keys {
+            state.update_key(*key, xkb::KeyDirection::Down);
+        }
+        // Replaying a held lock key toggles the lock, the previous lock state is the correct one.
+        // Latched modifiers are dropped, as the key that latched them might have been lost.
+        let depressed_mods = state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let depressed_layout = state.serialize_layout(xkb::STATE_LAYOUT_DEPRESSED);
+        state.update_mask(depressed_mods, 0, locked_mods, depressed_layout, 0, locked_layout);
+        xkb.state = state;
+
+        let old_mods_state = self.mods_state;
+        self.mods_state.update_with(&xkb.state);
+        let modifiers_changed = old_mods_state != self.mods_state;
+        let leds_changed = self.led_state.update_with(&xkb.state, &self.led_mapping);
+        self.pressed_keys = pressed_keys;
+        (modifiers_changed, leds_changed)
+    }
+
+    // updates the keys the new focus will be told about according to `enter_keys`
+    fn update_entered_keys(&mut self) {
+        match self.enter_keys {
+            EnterKeys::Forwarded => {}
+            EnterKeys::Pressed => self.forwarded_pressed_keys.clone_from(&self.pressed_keys),
+            EnterKeys::None => self.forwarded_pressed_keys.clear(),
+        }
+    }
+
     fn with_grab<F>(&mut self, data: &mut D, seat: &Seat<D>, f: F)
     where
         F: FnOnce(&mut D, &mut KeyboardInnerHandle<'_, D>, &mut dyn KeyboardGrab<D>),
@@ -512,6 +562,57 @@ impl XkbContext<'_> {
         *self.leds_changed = self.leds_state.update_with(&xkb.state, self.leds_mapping);
     }
 
+    /// Lock or unlock the modifier with the given name, e.g. [`xkb::MOD_NAME_NUM`].
+    ///
+    /// Returns `false` if the keymap has no modifier with this name.
+    pub fn set_modifier_locked(&mut self, name: &str, locked: bool) -> bool {
+        let mut xkb = self.xkb.lock().unwrap();
+        let index = xkb.keymap.mod_get_index(name);
+        if index == xkb::MOD_INVALID {
+            return false;
+        }
+
+        let mask = 1 << index;
+        let mut locked_mods = xkb.state.serialize_mods(xkb::STATE_MODS_LOCKED);
+        if locked {
+            locked_mods |= mask;
+        } else {
+            locked_mods &= !mask;
+        }
+
+        let depressed_mods = xkb.state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let latched_mods = xkb.state.serialize_mods(xkb::STATE_MODS_LATCHED);
+        let depressed_layout = xkb.state.serialize_layout(xkb::STATE_LAYOUT_DEPRESSED);
+        let latched_layout = xkb.state.serialize_layout(xkb::STATE_LAYOUT_LATCHED);
+        let locked_layout = xkb.state.serialize_layout(xkb::STATE_LAYOUT_LOCKED);
+        let state = xkb.state.update_mask(
+            depressed_mods,
+            latched_mods,
+            locked_mods,
+            depressed_layout,
+            latched_layout,
+            locked_layout,
+        );
+
+        if state != 0 {
+            self.mods_state.update_with(&xkb.state);
+            *self.mods_changed = true;
+        }
+
+        *self.leds_changed = self.leds_state.update_with(&xkb.state, self.leds_mapping);
+        true
+    }
+
+    /// Enable or disable num lock.
+    pub fn set_num_lock(&mut self, enabled: bool) -> bool {
+        self.set_modifier_locked(xkb::MOD_NAME_NUM, enabled)
+    }
+
+    /// Enable or disable caps lock.
+    pub fn set_caps_lock(&mut self, enabled: bool) -> bool {
+        self.set_modifier_locked(xkb::MOD_NAME_CAPS, enabled)
+    }
+
     /// Switches layout forward cycling when it reaches the end.
     pub fn cycle_next_layout(&mut self) {
         let xkb = self.xkb.lock().unwrap();
@@ -554,6 +655,22 @@ pub enum FilterResult<T> {
     Intercept(T),
 }
 
+/// Keys reported as pressed to a newly focused client
+///
+/// See [`KeyboardHandle::set_enter_keys`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EnterKeys {
+    /// Keys currently held down, that were forwarded to a client
+    ///
+    /// Keys intercepted by the compositor (see [`FilterResult::Intercept`]) are not included.
+    #[default]
+    Forwarded,
+    /// All keys currently held down, including keys intercepted by the compositor
+    Pressed,
+    /// No keys, the new focus will not receive any events for keys pressed before it was focused
+    None,
+}
+
 /// Data about the event that started the grab.
 pub struct GrabStartData<D: SeatHandler> {
     /// The focused surface, if any, at the start of the grab.
@@ -775,6 +892,23 @@ impl<D: SeatHandler + 'static> KeyboardHandle<D> {
             state.update_key(*key, xkb::KeyDirection::Down);
         }
 
+        // Keep num and caps lock, modifier indices may differ between keymaps.
+        let mut locked_mods = 0;
+        for (name, locked) in [
+            (xkb::MOD_NAME_NUM, internal.mods_state.num_lock),
+            (xkb::MOD_NAME_CAPS, internal.mods_state.caps_lock),
+        ] {
+            let index = keymap.mod_get_index(name);
+            if locked && index != xkb::MOD_INVALID {
+                locked_mods |= 1 << index;
+            }
+        }
+        if locked_mods != 0 {
+            let depressed_mods = state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+            let depressed_layout = state.serialize_layout(xkb::STATE_LAYOUT_DEPRESSED);
+            state.update_mask(depressed_mods, 0, locked_mods, depressed_layout, 0, 0);
+        }
+
         let led_mapping = LedMapping::from_keymap(&keymap);
         internal.led_mapping = led_mapping;
         internal.mods_state.update_with(&state);
@@ -837,9 +971,12 @@ impl<D: SeatHandler + 'static> KeyboardHandle<D> {
     }
 
     /// Access the underlying Xkb state and perform mutable operations on it, like
-    /// changing layouts.
+    /// changing layouts or locking modifiers.
     ///
     /// The changes to the state are automatically broadcasted to the focused client on exit.
+    /// Changes of the led state are reported through [`SeatHandler::led_state_changed`], which
+    /// can be used to update the leds of all keyboards of the seat. For example num lock can be enabled
+    /// at startup using [`XkbContext::set_num_lock`].
     pub fn with_xkb_state<F, T>(&self, data: &mut D, mut callback: F) -> T
     where
         F: FnMut(XkbContext<'_>) -> T,
@@ -877,6 +1014,64 @@ impl<D: SeatHandler + 'static> KeyboardHandle<D> {
         result
     }
 
+    /// Re-synchronize the keyboard state with the keys currently held down
+    ///
+    /// Key events can get lost, e.g. while the session is paused during a VT switch or
+    /// when a device is unplugged while keys are held down. Afterwards keys and modifiers
+    /// would be stuck until they are pressed and released again.
+    ///
+    /// `pressed_keys` should contain all keys currently held down on any keyboard of the seat,
+    /// e.g. as queried from the evdev devices using the `EVIOCGKEY` ioctl, or be empty if this
+    /// state is unknown. (E.g. libinput reports all keys as released when resuming a session.)
+    ///
+    /// The xkb state is rebuilt from the pressed keys. Locked modifiers (like caps lock) and the
+    /// locked layout are preserved, latched modifiers are cleared. Keys forwarded to the focused
+    /// client, that are not pressed anymore, are released using the provided `serial` and `time`.
+    #[instrument(level = "debug", parent = &self.arc.span, skip(self, data, pressed_keys))]
+    pub fn resync_pressed_keys(
+        &self,
+        data: &mut D,
+        pressed_keys: impl IntoIterator<Item = Keycode>,
+        serial: Serial,
+        time: u32,
+    ) {
+        let mut guard = self.arc.internal.lock().unwrap();
+        let pressed_keys = pressed_keys.into_iter().collect::<HashSet<_>>();
+        let released_keys = guard
+            .forwarded_pressed_keys
+            .difference(&pressed_keys)
+            .copied()
+            .collect::<Vec<_>>();
+        guard
+            .forwarded_pressed_keys
+            .retain(|keycode| pressed_keys.contains(keycode));
+        let (mods_changed, leds_changed) = guard.resync_keys(pressed_keys);
+        let mods_state = guard.mods_state;
+        let led_state = guard.led_state;
+        trace!(?released_keys, ?mods_state, "Keyboard state re-synchronized");
+
+        let seat = self.get_seat(data);
+        if !released_keys.is_empty() {
+            let count = released_keys.len();
+            guard.with_grab(data, &seat, |data, handle, grab| {
+                for (idx, keycode) in released_keys.into_iter().enumerate() {
+                    // only send the modifiers with the last key event
+                    let modifiers = (mods_changed && idx + 1 == count).then_some(mods_state);
+                    grab.input(data, handle, keycode, KeyState::Released, modifiers, serial, time);
+                }
+            });
+        } else if mods_changed {
+            if let Some((focus, _)) = guard.focus.as_mut() {
+                focus.modifiers(&seat, data, mods_state, serial);
+            }
+        }
+        std::mem::drop(guard);
+
+        if leds_changed {
+            data.led_state_changed(&seat, led_state);
+        }
+    }
+
     /// Change the current grab on this keyboard to the provided grab
     ///
     /// Overwrites any current grab.
@@ -1020,21 +1215,25 @@ impl<D: SeatHandler + 'static> KeyboardHandle<D> {
         mods_changed: bool,
     ) {
         let mut guard = self.arc.internal.lock().unwrap();
-        match state {
+        let matched = match state {
             KeyState::Pressed => {
                 guard.forwarded_pressed_keys.insert(keycode);
+                true
             }
-            KeyState::Released => {
-                guard.forwarded_pressed_keys.remove(&keycode);
-            }
+            KeyState::Released => guard.forwarded_pressed_keys.remove(&keycode),
         };
 
-        // forward to client if no keybinding is triggered
         let seat = self.get_seat(data);
         let modifiers = mods_changed.then_some(guard.mods_state);
+        // the grab still sees the release, but the focus is not told about it,
+        // see `KeyboardInnerHandle::input`
+        guard.unmatched_release = (!matched && guard.suppress_unmatched_releases).then_some(keycode);
+
+        // forward to client if no keybinding is triggered
         guard.with_grab(data, &seat, |data, handle, grab| {
             grab.input(data, handle, keycode, state, modifiers, serial, time);
         });
+        guard.unmatched_release = None;
         if guard.focus.is_some() {
             trace!("Input forwarded to client");
         } else {
@@ -1058,6 +1257,29 @@ impl<D: SeatHandler + 'static> KeyboardHandle<D> {
         });
     }
 
+    /// Configure which pressed keys are reported to a newly focused client
+    ///
+    /// Clients receive the keys currently held down when they gain focus (e.g. with
+    /// [`wl_keyboard::Event::Enter`](wayland_server::protocol::wl_keyboard::Event::Enter)),
+    /// and the release events of these keys afterwards. Defaults to [`EnterKeys::Forwarded`].
+    pub fn set_enter_keys(&self, enter_keys: EnterKeys) {
+        self.arc.internal.lock().unwrap().enter_keys = enter_keys;
+    }
+
+    /// Configure whether releases of keys unknown to the focused client are forwarded
+    ///
+    /// A key released after a focus change is unknown to the new focus, if it was not part
+    /// of the keys reported as pressed on enter (see [`KeyboardHandle::set_enter_keys`]).
+    /// The same applies to keys pressed while the compositor intercepted the input.
+    /// If enabled, these releases are not forwarded to the focused client, only the changed
+    /// modifiers are sent. An active grab still receives them, only forwarding them to the
+    /// client via [`KeyboardInnerHandle::input`] is suppressed.
+    ///
+    /// Disabled by default, so every release is forwarded.
+    pub fn set_suppress_unmatched_releases(&self, suppress: bool) {
+        self.arc.internal.lock().unwrap().suppress_unmatched_releases = suppress;
+    }
+
     /// Return the key codes of the currently pressed keys.
     pub fn pressed_keys(&self) -> HashSet<Keycode> {
         let guard = self.arc.internal.lock().unwrap();
@@ -1236,6 +1458,15 @@ impl<D: SeatHandler + 'static> KeyboardInnerHandle<'_, D> {
             None => return,
         };
 
+        if key_state == KeyState::Released && self.inner.unmatched_release == Some(keycode) {
+            // the focus never saw the key being pressed, only tell it about changed modifiers
+            trace!("Suppressing release of key not known to the client");
+            if let Some(mods) = modifiers {
+                focus.modifiers(self.seat, data, mods, serial);
+            }
+            return;
+        }
+
         // Ensure keymap is up to date.
         #[cfg(feature = "wayland_frontend")]
         if let Some(keyboard_handle) = self.seat.get_keyboard() {
@@ -1292,6 +1523,7 @@ impl<D: SeatHandler + 'static> KeyboardInnerHandle<'_, D> {
                 }
                 (focus, Some((old_focus, _))) => {
                     trace!("Focus set to new surface");
+                    self.inner.update_entered_keys();
                     let keys = self
                         .inner
                         .forwarded_pressed_keys
@@ -1306,6 +1538,7 @@ impl<D: SeatHandler + 'static> KeyboardInnerHandle<'_, D> {
                     data.focus_changed(self.seat, Some(&focus));
                 }
                 (focus, None) => {
+                    self.inner.update_entered_keys();
                     let keys = self
                         .inner
                         .forwarded_pressed_keys
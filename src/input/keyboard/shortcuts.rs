@@ -0,0 +1,545 @@
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+use tracing::debug;
+use xkbcommon::xkb::{self, Keycode, Keysym};
+
+use super::{KeysymHandle, ModifiersState};
+use crate::backend::input::KeyState;
+
+/// Key combination triggering a shortcut
+///
+/// Accelerators are written in the format of the
+/// [shortcuts specification](https://specifications.freedesktop.org/shortcuts-spec/latest/) used by the
+/// GlobalShortcuts portal, e.g. `CTRL+SHIFT+a`. Keys are matched layout independently, using
+/// [`KeysymHandle::raw_latin_sym_or_raw_current_sym`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Accelerator {
+    /// The "control" key has to be held
+    pub ctrl: bool,
+    /// The "alt" key has to be held
+    pub alt: bool,
+    /// The "shift" key has to be held
+    pub shift: bool,
+    /// The "logo" key has to be held
+    pub logo: bool,
+    /// The key triggering the shortcut
+    pub keysym: Keysym,
+}
+
+impl Accelerator {
+    /// Create an accelerator for a key without any modifiers
+    pub fn new(keysym: Keysym) -> Self {
+        Accelerator {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            keysym,
+        }
+    }
+
+    /// Returns if the accelerator matches the given modifiers and key
+    ///
+    /// Locked modifiers like caps lock are ignored.
+    pub fn matches(&self, modifiers: &ModifiersState, keysym: Keysym) -> bool {
+        self.keysym == keysym
+            && self.ctrl == modifiers.ctrl
+            && self.alt == modifiers.alt
+            && self.shift == modifiers.shift
+            && self.logo == modifiers.logo
+    }
+}
+
+/// Error parsing an [`Accelerator`]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AcceleratorParseError {
+    /// The accelerator does not contain a key
+    #[error("Accelerator does not contain a key")]
+    MissingKey,
+    /// A modifier is unknown
+    #[error("Unknown modifier: {0}")]
+    UnknownModifier(String),
+    /// The key is unknown
+    #[error("Unknown key: {0}")]
+    UnknownKey(String),
+}
+
+impl FromStr for Accelerator {
+    type Err = AcceleratorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts
+            .pop()
+            .filter(|key| !key.is_empty())
+            .ok_or(AcceleratorParseError::MissingKey)?;
+
+        let keysym = xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE);
+        if keysym == Keysym::NoSymbol {
+            return Err(AcceleratorParseError::UnknownKey(key.to_owned()));
+        }
+
+        let mut accelerator = Accelerator::new(keysym);
+        for modifier in parts {
+            match modifier.to_ascii_uppercase().as_str() {
+                "CTRL" | "CONTROL" => accelerator.ctrl = true,
+                "ALT" => accelerator.alt = true,
+                "SHIFT" => accelerator.shift = true,
+                "LOGO" | "SUPER" => accelerator.logo = true,
+                _ => return Err(AcceleratorParseError::UnknownModifier(modifier.to_owned())),
+            }
+        }
+        Ok(accelerator)
+    }
+}
+
+impl fmt::Display for Accelerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "CTRL"),
+            (self.alt, "ALT"),
+            (self.shift, "SHIFT"),
+            (self.logo, "LOGO"),
+        ] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", xkb::keysym_get_name(self.keysym))
+    }
+}
+
+/// Shortcut registered by a client, e.g. through the GlobalShortcuts portal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalShortcut {
+    /// Application the shortcut belongs to
+    pub app_id: String,
+    /// Identifier of the shortcut chosen by the application
+    pub id: String,
+    /// Human readable description of the shortcut
+    pub description: String,
+    /// Accelerator assigned to the shortcut, if any
+    pub trigger: Option<Accelerator>,
+}
+
+/// Reason an accelerator could not be assigned to a [`GlobalShortcut`]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ShortcutConflict {
+    /// The accelerator is bound by the compositor
+    #[error("Accelerator is bound by the compositor")]
+    Compositor,
+    /// The accelerator is assigned to another global shortcut
+    #[error("Accelerator is assigned to shortcut {id} of {app_id}")]
+    Global {
+        /// Application of the conflicting shortcut
+        app_id: String,
+        /// Identifier of the conflicting shortcut
+        id: String,
+    },
+    /// The shortcut is not registered
+    #[error("Shortcut is not registered")]
+    Unknown,
+}
+
+/// Shortcut triggered by a key event, see [`ShortcutRegistry::handle_key`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShortcutEvent<'a, T> {
+    /// A compositor binding was triggered
+    Compositor(&'a T),
+    /// The key of a triggered compositor binding was released
+    CompositorReleased,
+    /// A global shortcut was triggered, the application should be sent an `Activated` signal
+    Activated(&'a GlobalShortcut),
+    /// The key of a global shortcut was released, the application should be sent a `Deactivated` signal
+    Deactivated(&'a GlobalShortcut),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ActiveShortcut {
+    Compositor,
+    Global { app_id: String, id: String },
+}
+
+type PersistenceHook = Box<dyn FnMut(&GlobalShortcut) + Send>;
+
+/// Central registry of keyboard shortcuts
+///
+/// The registry holds the key bindings of the compositor, with actions of type `T`, together with
+/// the [`GlobalShortcut`]s requested by clients, e.g. through the GlobalShortcuts portal.
+/// Compositor bindings always take precedence: a global shortcut requesting an accelerator already
+/// in use is registered without a trigger, and binding an accelerator in the compositor unassigns it
+/// from any global shortcut.
+///
+/// Assignments chosen by the user can be persisted using [`ShortcutRegistry::set_persistence_hook`],
+/// which is called whenever the trigger of a global shortcut changes, and loaded again using
+/// [`ShortcutRegistry::restore`] before the application registers its shortcuts.
+///
+/// Key events are matched using [`ShortcutRegistry::handle_key`], usually from the filter passed to
+/// [`KeyboardHandle::input`](super::KeyboardHandle::input). Any key event resulting in a
+/// [`ShortcutEvent`] should be intercepted.
+pub struct ShortcutRegistry<T> {
+    bindings: Vec<(Accelerator, T)>,
+    globals: Vec<GlobalShortcut>,
+    restored: Vec<GlobalShortcut>,
+    active: Vec<(Keycode, ActiveShortcut)>,
+    persistence_hook: Option<PersistenceHook>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for ShortcutRegistry<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShortcutRegistry")
+            .field("bindings", &self.bindings)
+            .field("globals", &self.globals)
+            .field("restored", &self.restored)
+            .field("active", &self.active)
+            .field("persistence_hook", &self.persistence_hook.is_some())
+            .finish()
+    }
+}
+
+impl<T> Default for ShortcutRegistry<T> {
+    fn default() -> Self {
+        ShortcutRegistry {
+            bindings: Vec::new(),
+            globals: Vec::new(),
+            restored: Vec::new(),
+            active: Vec::new(),
+            persistence_hook: None,
+        }
+    }
+}
+
+impl<T> ShortcutRegistry<T> {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a callback called whenever the trigger of a global shortcut is assigned or changed
+    pub fn set_persistence_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&GlobalShortcut) + Send + 'static,
+    {
+        self.persistence_hook = Some(Box::new(hook));
+    }
+
+    /// Bind an accelerator to a compositor action, returning the previous action
+    ///
+    /// Global shortcuts using the accelerator lose their trigger.
+    pub fn bind(&mut self, accelerator: Accelerator, action: T) -> Option<T> {
+        for shortcut in self
+            .globals
+            .iter_mut()
+            .filter(|shortcut| shortcut.trigger == Some(accelerator))
+        {
+            debug!(
+                app_id = %shortcut.app_id,
+                id = %shortcut.id,
+                %accelerator,
+                "Unassigning global shortcut in favor of compositor binding"
+            );
+            shortcut.trigger = None;
+            if let Some(hook) = self.persistence_hook.as_mut() {
+                hook(shortcut);
+            }
+        }
+
+        match self.bindings.iter_mut().find(|(bound, _)| *bound == accelerator) {
+            Some((_, bound_action)) => Some(std::mem::replace(bound_action, action)),
+            None => {
+                self.bindings.push((accelerator, action));
+                None
+            }
+        }
+    }
+
+    /// Remove a compositor binding, returning its action
+    pub fn unbind(&mut self, accelerator: &Accelerator) -> Option<T> {
+        let idx = self.bindings.iter().position(|(bound, _)| bound == accelerator)?;
+        Some(self.bindings.remove(idx).1)
+    }
+
+    /// Returns the compositor bindings
+    pub fn bindings(&self) -> impl Iterator<Item = (&Accelerator, &T)> {
+        self.bindings
+            .iter()
+            .map(|(accelerator, action)| (accelerator, action))
+    }
+
+    /// Restore a previously persisted trigger of a global shortcut
+    ///
+    /// The trigger is used in place of the preferred trigger, once the application registers the shortcut.
+    /// A `None` trigger keeps the shortcut unassigned, e.g. if the user removed its trigger.
+    pub fn restore(
+        &mut self,
+        app_id: impl Into<String>,
+        id: impl Into<String>,
+        trigger: Option<Accelerator>,
+    ) {
+        let (app_id, id) = (app_id.into(), id.into());
+        self.restored
+            .retain(|shortcut| shortcut.app_id != app_id || shortcut.id != id);
+        self.restored.push(GlobalShortcut {
+            app_id,
+            id,
+            description: String::new(),
+            trigger,
+        });
+    }
+
+    /// Register a global shortcut of an application and return its assigned trigger
+    ///
+    /// The trigger is taken from [`ShortcutRegistry::restore`], if available, otherwise from `preferred_trigger`.
+    /// If the trigger conflicts with a compositor binding or another global shortcut, the shortcut is
+    /// registered without a trigger. Registering an already registered shortcut updates its description.
+    pub fn register_global(
+        &mut self,
+        app_id: impl Into<String>,
+        id: impl Into<String>,
+        description: impl Into<String>,
+        preferred_trigger: Option<Accelerator>,
+    ) -> Option<Accelerator> {
+        let (app_id, id, description) = (app_id.into(), id.into(), description.into());
+
+        if let Some(shortcut) = self
+            .globals
+            .iter_mut()
+            .find(|shortcut| shortcut.app_id == app_id && shortcut.id == id)
+        {
+            shortcut.description = description;
+            return shortcut.trigger;
+        }
+
+        let restored = self
+            .restored
+            .iter()
+            .position(|shortcut| shortcut.app_id == app_id && shortcut.id == id)
+            .map(|idx| self.restored.remove(idx));
+        let trigger = match restored {
+            Some(restored) => restored.trigger,
+            None => preferred_trigger,
+        }
+        .filter(|trigger| match self.conflict(trigger, &app_id, &id) {
+            Some(conflict) => {
+                debug!(%app_id, %id, %trigger, %conflict, "Registering global shortcut without trigger");
+                false
+            }
+            None => true,
+        });
+
+        let shortcut = GlobalShortcut {
+            app_id,
+            id,
+            description,
+            trigger,
+        };
+        if let Some(hook) = self.persistence_hook.as_mut() {
+            hook(&shortcut);
+        }
+        self.globals.push(shortcut);
+        trigger
+    }
+
+    /// Remove all global shortcuts of an application, e.g. once its portal session is closed
+    ///
+    /// The assigned triggers are kept and restored, when the application registers its shortcuts again.
+    pub fn unregister_globals(&mut self, app_id: &str) {
+        let (removed, kept) = std::mem::take(&mut self.globals)
+            .into_iter()
+            .partition::<Vec<_>, _>(|shortcut| shortcut.app_id == app_id);
+        self.globals = kept;
+        self.active.retain(|(_, active)| {
+            !matches!(active, ActiveShortcut::Global { app_id: active_app_id, .. } if active_app_id == app_id)
+        });
+        for shortcut in removed {
+            self.restore(shortcut.app_id, shortcut.id, shortcut.trigger);
+        }
+    }
+
+    /// Change the trigger of a registered global shortcut, e.g. after the user picked a different one
+    pub fn set_global_trigger(
+        &mut self,
+        app_id: &str,
+        id: &str,
+        trigger: Option<Accelerator>,
+    ) -> Result<(), ShortcutConflict> {
+        let index = self
+            .globals
+            .iter()
+            .position(|shortcut| shortcut.app_id == app_id && shortcut.id == id)
+            .ok_or(ShortcutConflict::Unknown)?;
+        if let Some(conflict) = trigger.and_then(|trigger| self.conflict(&trigger, app_id, id)) {
+            return Err(conflict);
+        }
+        let shortcut = &mut self.globals[index];
+        shortcut.trigger = trigger;
+        if let Some(hook) = self.persistence_hook.as_mut() {
+            hook(shortcut);
+        }
+        Ok(())
+    }
+
+    /// Returns the registered global shortcuts of an application
+    pub fn global_shortcuts<'a>(&'a self, app_id: &'a str) -> impl Iterator<Item = &'a GlobalShortcut> + 'a {
+        self.globals
+            .iter()
+            .filter(move |shortcut| shortcut.app_id == app_id)
+    }
+
+    fn conflict(&self, trigger: &Accelerator, app_id: &str, id: &str) -> Option<ShortcutConflict> {
+        if self.bindings.iter().any(|(bound, _)| bound == trigger) {
+            return Some(ShortcutConflict::Compositor);
+        }
+        self.globals
+            .iter()
+            .find(|shortcut| {
+                shortcut.trigger.as_ref() == Some(trigger) && (shortcut.app_id != app_id || shortcut.id != id)
+            })
+            .map(|shortcut| ShortcutConflict::Global {
+                app_id: shortcut.app_id.clone(),
+                id: shortcut.id.clone(),
+            })
+    }
+
+    /// Match a key event against the registered shortcuts
+    pub fn handle_key(
+        &mut self,
+        handle: &KeysymHandle<'_>,
+        modifiers: &ModifiersState,
+        state: KeyState,
+    ) -> Option<ShortcutEvent<'_, T>> {
+        self.handle_keysym(
+            handle.raw_code(),
+            handle.raw_latin_sym_or_raw_current_sym(),
+            modifiers,
+            state,
+        )
+    }
+
+    /// Match a key event against the registered shortcuts using a given keysym
+    pub fn handle_keysym(
+        &mut self,
+        keycode: Keycode,
+        keysym: Option<Keysym>,
+        modifiers: &ModifiersState,
+        state: KeyState,
+    ) -> Option<ShortcutEvent<'_, T>> {
+        if state == KeyState::Released {
+            let idx = self.active.iter().position(|(code, _)| *code == keycode)?;
+            return match self.active.remove(idx).1 {
+                ActiveShortcut::Compositor => Some(ShortcutEvent::CompositorReleased),
+                ActiveShortcut::Global { app_id, id } => self
+                    .globals
+                    .iter()
+                    .find(|shortcut| shortcut.app_id == app_id && shortcut.id == id)
+                    .map(ShortcutEvent::Deactivated),
+            };
+        }
+
+        let keysym = keysym?;
+        if let Some(idx) = self
+            .bindings
+            .iter()
+            .position(|(accelerator, _)| accelerator.matches(modifiers, keysym))
+        {
+            self.active.retain(|(code, _)| *code != keycode);
+            self.active.push((keycode, ActiveShortcut::Compositor));
+            return Some(ShortcutEvent::Compositor(&self.bindings[idx].1));
+        }
+
+        let idx = self.globals.iter().position(|shortcut| {
+            shortcut
+                .trigger
+                .is_some_and(|trigger| trigger.matches(modifiers, keysym))
+        })?;
+        let shortcut = &self.globals[idx];
+        self.active.retain(|(code, _)| *code != keycode);
+        self.active.push((
+            keycode,
+            ActiveShortcut::Global {
+                app_id: shortcut.app_id.clone(),
+                id: shortcut.id.clone(),
+            },
+        ));
+        Some(ShortcutEvent::Activated(shortcut))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xkbcommon::xkb::{Keycode, Keysym};
+
+    use super::{Accelerator, AcceleratorParseError, ShortcutConflict, ShortcutEvent, ShortcutRegistry};
+    use crate::{backend::input::KeyState, input::keyboard::ModifiersState};
+
+    #[test]
+    fn accelerator_parsing() {
+        let accelerator = "CTRL+Shift+A".parse::<Accelerator>().unwrap();
+        assert!(accelerator.ctrl && accelerator.shift && !accelerator.alt && !accelerator.logo);
+        assert_eq!(accelerator.keysym, Keysym::a);
+        assert_eq!(accelerator.to_string(), "CTRL+SHIFT+a");
+
+        assert_eq!(
+            "CTRL+".parse::<Accelerator>(),
+            Err(AcceleratorParseError::MissingKey)
+        );
+        assert_eq!(
+            "HYPER+a".parse::<Accelerator>(),
+            Err(AcceleratorParseError::UnknownModifier("HYPER".into()))
+        );
+    }
+
+    #[test]
+    fn conflict_resolution() {
+        let mut registry = ShortcutRegistry::new();
+        let ctrl_a = "CTRL+a".parse::<Accelerator>().unwrap();
+        let ctrl_b = "CTRL+b".parse::<Accelerator>().unwrap();
+        registry.bind(ctrl_a, "close");
+
+        // compositor bindings take precedence
+        assert_eq!(
+            registry.register_global("app", "mute", "Mute", Some(ctrl_a)),
+            None
+        );
+        assert_eq!(
+            registry.set_global_trigger("app", "mute", Some(ctrl_a)),
+            Err(ShortcutConflict::Compositor)
+        );
+        assert_eq!(registry.set_global_trigger("app", "mute", Some(ctrl_b)), Ok(()));
+        // shortcuts have to be registered before assigning a trigger, even a conflicting one
+        assert_eq!(
+            registry.set_global_trigger("other", "record", Some(ctrl_b)),
+            Err(ShortcutConflict::Unknown)
+        );
+        assert_eq!(registry.register_global("other", "record", "Record", None), None);
+        assert!(matches!(
+            registry.set_global_trigger("other", "record", Some(ctrl_b)),
+            Err(ShortcutConflict::Global { .. })
+        ));
+
+        let ctrl = ModifiersState {
+            ctrl: true,
+            ..Default::default()
+        };
+        let key = Keycode::new(56);
+        assert!(matches!(
+            registry.handle_keysym(key, Some(Keysym::b), &ctrl, KeyState::Pressed),
+            Some(ShortcutEvent::Activated(shortcut)) if shortcut.id == "mute"
+        ));
+        assert!(matches!(
+            registry.handle_keysym(key, Some(Keysym::b), &ctrl, KeyState::Released),
+            Some(ShortcutEvent::Deactivated(shortcut)) if shortcut.id == "mute"
+        ));
+
+        // triggers are kept across sessions
+        registry.unregister_globals("app");
+        assert_eq!(
+            registry.register_global("app", "mute", "Mute", None),
+            Some(ctrl_b)
+        );
+
+        registry.bind(ctrl_b, "minimize");
+        assert_eq!(registry.global_shortcuts("app").next().unwrap().trigger, None);
+    }
+}
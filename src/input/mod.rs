@@ -140,6 +140,7 @@ use crate::utils::{user_data::UserDataMap, Serial};
 pub mod keyboard;
 pub mod pointer;
 pub mod touch;
+pub mod widget;
 
 /// Handler trait for Seats
 pub trait SeatHandler: Sized {
@@ -0,0 +1,442 @@
+use crate::utils::{Logical, Point};
+
+/// Distance a pointer is kept away from a barrier, when blocked in positive direction
+///
+/// Matches the precision of the fixed point coordinates used by the wayland protocol.
+const BARRIER_EPSILON: f64 = 1.0 / 256.0;
+
+bitflags::bitflags! {
+    /// Directions in which a [`PointerBarrier`] can be crossed
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct BarrierDirections: u32 {
+        /// Motion towards increasing x coordinates
+        const POSITIVE_X = 1;
+        /// Motion towards increasing y coordinates
+        const POSITIVE_Y = 2;
+        /// Motion towards decreasing x coordinates
+        const NEGATIVE_X = 4;
+        /// Motion towards decreasing y coordinates
+        const NEGATIVE_Y = 8;
+    }
+}
+
+/// Orientation of a [`PointerBarrier`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BarrierOrientation {
+    /// The barrier is a vertical line with a constant x coordinate
+    Vertical,
+    /// The barrier is a horizontal line with a constant y coordinate
+    Horizontal,
+}
+
+/// A line the pointer cannot cross
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerBarrier {
+    /// Orientation of the barrier
+    pub orientation: BarrierOrientation,
+    /// Position of the line, the x coordinate for vertical and the y coordinate for horizontal barriers
+    pub position: f64,
+    /// Start and end of the line (inclusive) along its orientation
+    pub range: (f64, f64),
+    /// Directions the barrier can be crossed freely
+    pub passable: BarrierDirections,
+    /// Velocity in logical pixels per second perpendicular to the barrier,
+    /// which lets the pointer break through the barrier
+    pub breakthrough_velocity: Option<f64>,
+}
+
+impl PointerBarrier {
+    /// Create a new vertical barrier at `x` reaching from `y1` to `y2`
+    pub fn vertical(x: f64, y1: f64, y2: f64) -> Self {
+        PointerBarrier {
+            orientation: BarrierOrientation::Vertical,
+            position: x,
+            range: (y1.min(y2), y1.max(y2)),
+            passable: BarrierDirections::empty(),
+            breakthrough_velocity: None,
+        }
+    }
+
+    /// Create a new horizontal barrier at `y` reaching from `x1` to `x2`
+    pub fn horizontal(y: f64, x1: f64, x2: f64) -> Self {
+        PointerBarrier {
+            orientation: BarrierOrientation::Horizontal,
+            position: y,
+            range: (x1.min(x2), x1.max(x2)),
+            passable: BarrierDirections::empty(),
+            breakthrough_velocity: None,
+        }
+    }
+
+    // returns the coordinates perpendicular and parallel to the barrier
+    fn split(&self, point: Point<f64, Logical>) -> (f64, f64) {
+        match self.orientation {
+            BarrierOrientation::Vertical => (point.x, point.y),
+            BarrierOrientation::Horizontal => (point.y, point.x),
+        }
+    }
+
+    fn join(&self, perpendicular: f64, parallel: f64) -> Point<f64, Logical> {
+        match self.orientation {
+            BarrierOrientation::Vertical => (perpendicular, parallel).into(),
+            BarrierOrientation::Horizontal => (parallel, perpendicular).into(),
+        }
+    }
+
+    // returns the relative position of the intersection on the motion and the direction of the crossing
+    fn crossing(
+        &self,
+        from: Point<f64, Logical>,
+        to: Point<f64, Logical>,
+    ) -> Option<(f64, BarrierDirections)> {
+        let (from_perp, from_par) = self.split(from);
+        let (to_perp, to_par) = self.split(to);
+        let positive = from_perp < self.position && to_perp >= self.position;
+        let negative = from_perp >= self.position && to_perp < self.position;
+        if !positive && !negative {
+            return None;
+        }
+
+        let t = (self.position - from_perp) / (to_perp - from_perp);
+        let intersection = from_par + t * (to_par - from_par);
+        if intersection < self.range.0 || intersection > self.range.1 {
+            return None;
+        }
+
+        let direction = match (self.orientation, positive) {
+            (BarrierOrientation::Vertical, true) => BarrierDirections::POSITIVE_X,
+            (BarrierOrientation::Vertical, false) => BarrierDirections::NEGATIVE_X,
+            (BarrierOrientation::Horizontal, true) => BarrierDirections::POSITIVE_Y,
+            (BarrierOrientation::Horizontal, false) => BarrierDirections::NEGATIVE_Y,
+        };
+        Some((t, direction))
+    }
+
+    fn clamp(&self, to: Point<f64, Logical>, direction: BarrierDirections) -> Point<f64, Logical> {
+        let (_, parallel) = self.split(to);
+        let perpendicular =
+            if direction.intersects(BarrierDirections::POSITIVE_X | BarrierDirections::POSITIVE_Y) {
+                self.position - BARRIER_EPSILON
+            } else {
+                self.position
+            };
+        self.join(perpendicular, parallel)
+    }
+}
+
+/// Identifier of a barrier added to [`PointerBarriers`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BarrierId(usize);
+
+/// Details about the pointer hitting a barrier
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarrierHit {
+    /// The barrier that was hit
+    pub id: BarrierId,
+    /// Location of the pointer after the hit
+    pub location: Point<f64, Logical>,
+    /// Direction the pointer tried to cross the barrier
+    pub direction: BarrierDirections,
+    /// Distance the pointer would have moved past the barrier
+    pub pressure: f64,
+    /// Velocity in logical pixels per second perpendicular to the barrier, if known
+    pub velocity: Option<f64>,
+    /// Whether the pointer broke through the barrier
+    pub released: bool,
+}
+
+/// Set of [`PointerBarrier`]s applied to pointer motion
+///
+/// Every pointer has a set of barriers, which is applied to the location of every
+/// [`PointerHandle::motion`](super::PointerHandle::motion) and can be accessed using
+/// [`PointerHandle::with_barriers`](super::PointerHandle::with_barriers). The hits of the
+/// barriers are collected and can be retrieved with [`PointerBarriers::take_hits`].
+/// A standalone set can be used with [`PointerBarriers::apply`] instead, e.g. to only
+/// restrict relative pointer motion, as absolute motion (e.g. from tablets or touchpads
+/// in absolute mode) usually should not be restricted.
+///
+/// The reported [`BarrierHit`]s can be used to implement hot corners or other actions
+/// triggered by pushing against a screen edge. Using a
+/// [`breakthrough_velocity`](PointerBarrier::breakthrough_velocity) sticky edges can be
+/// created, e.g. between outputs with different scales.
+#[derive(Debug, Default)]
+pub struct PointerBarriers {
+    barriers: Vec<(BarrierId, PointerBarrier)>,
+    next_id: usize,
+    last_time: Option<u64>,
+    hits: Vec<BarrierHit>,
+}
+
+impl PointerBarriers {
+    /// Create an empty set of barriers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a new barrier
+    pub fn add(&mut self, barrier: PointerBarrier) -> BarrierId {
+        let id = BarrierId(self.next_id);
+        self.next_id += 1;
+        self.barriers.push((id, barrier));
+        id
+    }
+
+    /// Remove a barrier
+    pub fn remove(&mut self, id: BarrierId) -> Option<PointerBarrier> {
+        let idx = self
+            .barriers
+            .iter()
+            .position(|(barrier_id, _)| *barrier_id == id)?;
+        Some(self.barriers.remove(idx).1)
+    }
+
+    /// Access a barrier
+    pub fn get(&self, id: BarrierId) -> Option<&PointerBarrier> {
+        self.barriers
+            .iter()
+            .find(|(barrier_id, _)| *barrier_id == id)
+            .map(|(_, barrier)| barrier)
+    }
+
+    /// Mutably access a barrier
+    pub fn get_mut(&mut self, id: BarrierId) -> Option<&mut PointerBarrier> {
+        self.barriers
+            .iter_mut()
+            .find(|(barrier_id, _)| *barrier_id == id)
+            .map(|(_, barrier)| barrier)
+    }
+
+    /// Iterate over all barriers
+    pub fn iter(&self) -> impl Iterator<Item = (BarrierId, &PointerBarrier)> {
+        self.barriers.iter().map(|(id, barrier)| (*id, barrier))
+    }
+
+    /// Remove all barriers
+    pub fn clear(&mut self) {
+        self.barriers.clear();
+    }
+
+    /// Returns if no barriers were added
+    pub fn is_empty(&self) -> bool {
+        self.barriers.is_empty()
+    }
+
+    /// Take the barrier hits of the pointer motion since the last call
+    ///
+    /// Only hits of barriers applied by [`PointerHandle::motion`](super::PointerHandle::motion) are collected.
+    pub fn take_hits(&mut self) -> Vec<BarrierHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    pub(super) fn apply_and_collect(
+        &mut self,
+        from: Point<f64, Logical>,
+        to: Point<f64, Logical>,
+        time: u64,
+    ) -> Point<f64, Logical> {
+        let mut hits = std::mem::take(&mut self.hits);
+        let location = self.apply(from, to, time, |hit| hits.push(*hit));
+        self.hits = hits;
+        location
+    }
+
+    /// Apply the barriers to a pointer motion from `from` to `to` at `time` in microseconds
+    ///
+    /// Returns the location the pointer should be moved to. When a barrier is hit, the
+    /// pointer slides along the barrier. `on_hit` is called for every barrier hit by the motion.
+    pub fn apply(
+        &mut self,
+        from: Point<f64, Logical>,
+        to: Point<f64, Logical>,
+        time: u64,
+        mut on_hit: impl FnMut(&BarrierHit),
+    ) -> Point<f64, Logical> {
+        let elapsed = self
+            .last_time
+            .replace(time)
+            .and_then(|last| time.checked_sub(last))
+            .filter(|elapsed| *elapsed > 0)
+            .map(|elapsed| elapsed as f64 / 1_000_000.0);
+
+        let mut to = to;
+        let mut hit = Vec::new();
+        // every barrier can only be hit once, as the location is clamped in front of it
+        while hit.len() < self.barriers.len() {
+            let Some((_, idx, direction)) = self
+                .barriers
+                .iter()
+                .enumerate()
+                .filter(|(_, (id, _))| !hit.contains(id))
+                .filter_map(|(idx, (_, barrier))| {
+                    let (t, direction) = barrier.crossing(from, to)?;
+                    (!barrier.passable.contains(direction)).then_some((t, idx, direction))
+                })
+                .min_by(|(t1, _, _), (t2, _, _)| t1.total_cmp(t2))
+            else {
+                break;
+            };
+
+            let (id, barrier) = self.barriers[idx];
+            hit.push(id);
+
+            let (from_perp, _) = barrier.split(from);
+            let (to_perp, _) = barrier.split(to);
+            let velocity = elapsed.map(|elapsed| (to_perp - from_perp).abs() / elapsed);
+            let released = matches!(
+                (barrier.breakthrough_velocity, velocity),
+                (Some(threshold), Some(velocity)) if velocity >= threshold
+            );
+            if !released {
+                to = barrier.clamp(to, direction);
+            }
+
+            on_hit(&BarrierHit {
+                id,
+                location: to,
+                direction,
+                pressure: (to_perp - barrier.position).abs(),
+                velocity,
+                released,
+            });
+        }
+
+        to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BarrierDirections, PointerBarrier, PointerBarriers, BARRIER_EPSILON};
+
+    #[test]
+    fn blocks_and_slides() {
+        let mut barriers = PointerBarriers::new();
+        let id = barriers.add(PointerBarrier::vertical(100.0, 0.0, 100.0));
+
+        let mut hits = Vec::new();
+        let location = barriers.apply((90.0, 50.0).into(), (110.0, 60.0).into(), 0, |hit| {
+            hits.push(*hit)
+        });
+        assert_eq!(location, (100.0 - BARRIER_EPSILON, 60.0).into());
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, id);
+        assert_eq!(hits[0].direction, BarrierDirections::POSITIVE_X);
+        assert_eq!(hits[0].pressure, 10.0);
+        assert!(!hits[0].released);
+
+        // moving back is not blocked
+        let location = barriers.apply(location, (90.0, 60.0).into(), 1, |_| panic!("unexpected hit"));
+        assert_eq!(location, (90.0, 60.0).into());
+
+        // blocked from the other side
+        let location = barriers.apply((110.0, 50.0).into(), (90.0, 50.0).into(), 2, |_| {});
+        assert_eq!(location, (100.0, 50.0).into());
+        let location = barriers.apply(location, (110.0, 50.0).into(), 3, |_| panic!("unexpected hit"));
+        assert_eq!(location, (110.0, 50.0).into());
+    }
+
+    #[test]
+    fn range_and_passable() {
+        let mut barriers = PointerBarriers::new();
+        barriers.add(PointerBarrier {
+            passable: BarrierDirections::NEGATIVE_Y,
+            ..PointerBarrier::horizontal(100.0, 0.0, 100.0)
+        });
+
+        // outside of the range
+        let location = barriers.apply((150.0, 90.0).into(), (150.0, 110.0).into(), 0, |_| {
+            panic!("unexpected hit")
+        });
+        assert_eq!(location, (150.0, 110.0).into());
+
+        // passable direction
+        let location = barriers.apply((50.0, 110.0).into(), (50.0, 90.0).into(), 1, |_| {
+            panic!("unexpected hit")
+        });
+        assert_eq!(location, (50.0, 90.0).into());
+
+        let location = barriers.apply((50.0, 90.0).into(), (50.0, 110.0).into(), 2, |_| {});
+        assert_eq!(location, (50.0, 100.0 - BARRIER_EPSILON).into());
+    }
+
+    #[test]
+    fn breakthrough() {
+        let mut barriers = PointerBarriers::new();
+        barriers.add(PointerBarrier {
+            breakthrough_velocity: Some(1000.0),
+            ..PointerBarrier::vertical(100.0, 0.0, 100.0)
+        });
+
+        // the first event has no known velocity
+        let location = barriers.apply((90.0, 50.0).into(), (110.0, 50.0).into(), 0, |_| {});
+        assert_eq!(location.x, 100.0 - BARRIER_EPSILON);
+
+        // 5px in 10ms, 500px/s
+        let location = barriers.apply(location, (105.0, 50.0).into(), 10_000, |hit| {
+            assert!(hit
+                .velocity
+                .is_some_and(|velocity| (velocity - 500.0).abs() < 1.0));
+            assert!(!hit.released);
+        });
+        assert_eq!(location.x, 100.0 - BARRIER_EPSILON);
+
+        // 20px in 10ms, 2000px/s
+        let mut released = false;
+        let location = barriers.apply(location, (120.0, 50.0).into(), 20_000, |hit| {
+            released = hit.released;
+        });
+        assert!(released);
+        assert_eq!(location, (120.0, 50.0).into());
+    }
+
+    #[test]
+    fn corner() {
+        let mut barriers = PointerBarriers::new();
+        barriers.add(PointerBarrier::vertical(100.0, 0.0, 100.0));
+        barriers.add(PointerBarrier::horizontal(100.0, 0.0, 100.0));
+
+        let mut hits = 0;
+        let location = barriers.apply((90.0, 90.0).into(), (120.0, 110.0).into(), 0, |_| hits += 1);
+        assert_eq!(hits, 2);
+        assert_eq!(
+            location,
+            (100.0 - BARRIER_EPSILON, 100.0 - BARRIER_EPSILON).into()
+        );
+    }
+
+    #[cfg(feature = "wayland_frontend")]
+    #[test]
+    fn pointer_motion_is_restricted() {
+        use crate::{input::pointer::MotionEvent, utils::SERIAL_COUNTER, wayland::test_utils::TestFixture};
+
+        let mut fixture = TestFixture::new();
+        let pointer = fixture.state.seat.add_pointer();
+        let id = pointer.with_barriers(|barriers| barriers.add(PointerBarrier::vertical(100.0, 0.0, 100.0)));
+
+        let motion = |fixture: &mut TestFixture, location: (f64, f64), time: u32| {
+            let event = MotionEvent {
+                location: location.into(),
+                serial: SERIAL_COUNTER.next_serial(),
+                time,
+            };
+            pointer.motion(&mut fixture.state, None, &event);
+            pointer.current_location()
+        };
+
+        assert_eq!(motion(&mut fixture, (90.0, 50.0), 0), (90.0, 50.0).into());
+        assert_eq!(
+            motion(&mut fixture, (110.0, 60.0), 10),
+            (100.0 - BARRIER_EPSILON, 60.0).into()
+        );
+        let hits = pointer.with_barriers(|barriers| barriers.take_hits());
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, id);
+        assert_eq!(hits[0].direction, BarrierDirections::POSITIVE_X);
+        assert!(pointer.with_barriers(|barriers| barriers.take_hits()).is_empty());
+
+        // without the barrier the pointer moves freely
+        pointer.with_barriers(|barriers| barriers.remove(id));
+        assert_eq!(motion(&mut fixture, (110.0, 60.0), 20), (110.0, 60.0).into());
+        assert!(pointer.with_barriers(|barriers| barriers.take_hits()).is_empty());
+    }
+}
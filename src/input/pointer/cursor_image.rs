@@ -3,7 +3,10 @@ use wayland_server::protocol::wl_surface::WlSurface;
 
 pub use cursor_icon::CursorIcon;
 
-use crate::utils::{Logical, Point};
+use crate::{
+    output::{Output, WeakOutput},
+    utils::{Logical, Point},
+};
 use std::sync::Mutex;
 
 /// The role representing a surface set as the pointer cursor
@@ -48,3 +51,79 @@ impl CursorImageStatus {
         Self::Named(CursorIcon::Default)
     }
 }
+
+/// Tracks the scale a compositor-drawn cursor should be rendered at
+///
+/// [`CursorImageStatus::Named`] cursors are drawn by the compositor, usually from a cursor theme.
+/// To keep the same physical size and stay crisp on outputs with a scale other than 1,
+/// the cursor image has to be loaded at the nominal size multiplied by the scale
+/// of the output the pointer is currently on.
+///
+/// [`CursorScale::update`] should be called on pointer motion (and when the scale of outputs
+/// changes) with the outputs under the pointer, e.g. from
+/// [`Space::output_under`](crate::desktop::Space::output_under), and returns the new scale,
+/// when the pointer crossed into an output with a different scale.
+///
+/// Cursors scanned out on a drm cursor plane by a
+/// [`DrmCompositor`](crate::backend::drm::compositor::DrmCompositor) are re-rendered into a
+/// new buffer automatically, when the size of the cursor changes. Cursors bigger than the
+/// cursor size of the device fall back to being composited on the primary plane.
+#[derive(Debug, Clone)]
+pub struct CursorScale {
+    output: Option<WeakOutput>,
+    scale: f64,
+}
+
+impl Default for CursorScale {
+    fn default() -> Self {
+        CursorScale {
+            output: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl CursorScale {
+    /// Create a new tracker with a scale of 1
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current fractional scale
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Returns the integer scale cursor images should be loaded with
+    ///
+    /// This rounds up fractional scales, so the cursor is downscaled instead of upscaled.
+    pub fn buffer_scale(&self) -> i32 {
+        self.scale.ceil() as i32
+    }
+
+    /// Returns the size a cursor image with the given nominal size should be loaded with
+    pub fn cursor_size(&self, nominal_size: u32) -> u32 {
+        nominal_size * self.buffer_scale() as u32
+    }
+
+    /// Returns the output the pointer was last on
+    pub fn output(&self) -> Option<Output> {
+        self.output.as_ref().and_then(|output| output.upgrade())
+    }
+
+    /// Update the scale from the outputs under the pointer
+    ///
+    /// The first output is used. If no output is under the pointer the previous scale is kept.
+    /// Returns the new scale, if it changed.
+    pub fn update<'a>(&mut self, outputs: impl IntoIterator<Item = &'a Output>) -> Option<f64> {
+        let output = outputs.into_iter().next()?;
+        self.output = Some(output.downgrade());
+
+        let scale = output.current_scale().fractional_scale();
+        if scale == self.scale {
+            return None;
+        }
+        self.scale = scale;
+        Some(scale)
+    }
+}
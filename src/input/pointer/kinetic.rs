@@ -0,0 +1,258 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle, RegistrationToken,
+};
+use tracing::trace;
+
+use super::{AxisFrame, PointerHandle};
+use crate::{
+    backend::input::{Axis, AxisSource},
+    input::SeatHandler,
+};
+
+/// Axis events older than this are not considered for the velocity of a fling
+const VELOCITY_WINDOW: u32 = 100;
+
+/// Parameters of the synthesized fling of a [`KineticScroll`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KineticScrollConfig {
+    /// Rate of the exponential decay of the velocity per second
+    pub friction: f64,
+    /// Velocity in scroll units per second required to start a fling, and at which a fling stops
+    pub min_velocity: f64,
+    /// Interval between two synthesized axis frames
+    pub interval: Duration,
+}
+
+impl Default for KineticScrollConfig {
+    fn default() -> Self {
+        KineticScrollConfig {
+            friction: 4.0,
+            min_velocity: 50.0,
+            interval: Duration::from_millis(16),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct KineticScrollInner {
+    samples: VecDeque<(u32, (f64, f64))>,
+    source: Option<AxisSource>,
+    velocity: (f64, f64),
+    time: u32,
+    token: Option<RegistrationToken>,
+}
+
+impl KineticScrollInner {
+    // velocity in units per second of the samples within the velocity window before `time`
+    fn release_velocity(&mut self, time: u32) -> (f64, f64) {
+        self.samples
+            .retain(|(sample_time, _)| time.wrapping_sub(*sample_time) <= VELOCITY_WINDOW);
+        let Some((first, _)) = self.samples.front() else {
+            return (0.0, 0.0);
+        };
+        let duration = time.wrapping_sub(*first).max(1) as f64 / 1000.0;
+        let (x, y) = self
+            .samples
+            .drain(..)
+            .fold((0.0, 0.0), |(x, y), (_, delta)| (x + delta.0, y + delta.1));
+        (x / duration, y / duration)
+    }
+}
+
+/// Synthesizes kinetic scrolling for axis events without an inertia of their own
+///
+/// Clients only implement kinetic scrolling for [`AxisSource::Finger`] events of touchpads.
+/// Axis events emulated by the compositor, e.g. from dragging on a touchscreen or from a
+/// compositor gesture, should be sent with [`AxisSource::Continuous`] through
+/// [`KineticScroll::scroll`] instead. Once the touch ends, [`KineticScroll::release`] continues
+/// the motion with decaying axis frames on a calloop timer, until the velocity drops below
+/// [`KineticScrollConfig::min_velocity`] and an axis stop is sent.
+///
+/// Touching again should stop the fling using [`KineticScroll::cancel`].
+pub struct KineticScroll<D: SeatHandler + 'static> {
+    pointer: PointerHandle<D>,
+    loop_handle: LoopHandle<'static, D>,
+    config: KineticScrollConfig,
+    inner: Arc<Mutex<KineticScrollInner>>,
+}
+
+impl<D: SeatHandler + 'static> fmt::Debug for KineticScroll<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KineticScroll")
+            .field("pointer", &self.pointer)
+            .field("config", &self.config)
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: SeatHandler + 'static> KineticScroll<D> {
+    /// Create a new kinetic scroll synthesizer for a pointer
+    pub fn new(pointer: &PointerHandle<D>, loop_handle: LoopHandle<'static, D>) -> Self {
+        KineticScroll {
+            pointer: pointer.clone(),
+            loop_handle,
+            config: KineticScrollConfig::default(),
+            inner: Arc::new(Mutex::new(KineticScrollInner::default())),
+        }
+    }
+
+    /// Returns the parameters of synthesized flings
+    pub fn config(&self) -> KineticScrollConfig {
+        self.config
+    }
+
+    /// Change the parameters of future flings
+    pub fn set_config(&mut self, config: KineticScrollConfig) {
+        self.config = config;
+    }
+
+    /// Returns if a fling is currently running
+    pub fn is_active(&self) -> bool {
+        self.inner.lock().unwrap().token.is_some()
+    }
+
+    /// Send an axis frame to the pointer and track its velocity for a later fling
+    ///
+    /// A running fling is cancelled first. The frame should not contain axis stops,
+    /// those are sent once the fling ends.
+    pub fn scroll(&self, data: &mut D, frame: AxisFrame) {
+        self.cancel(data);
+
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.samples.push_back((frame.time, frame.axis));
+            while inner.samples.len() > 1 && frame.time.wrapping_sub(inner.samples[0].0) > VELOCITY_WINDOW {
+                inner.samples.pop_front();
+            }
+            inner.source = frame.source;
+        }
+
+        self.pointer.axis(data, frame);
+        self.pointer.frame(data);
+    }
+
+    /// The scrolling input ended, start a fling with the velocity of the last axis frames
+    ///
+    /// If the velocity is too low, the axis stop is sent right away.
+    /// Returns if a fling was started.
+    pub fn release(&self, data: &mut D, time: u32) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let velocity = inner.release_velocity(time);
+        let source = inner.source.unwrap_or(AxisSource::Continuous);
+
+        if velocity.0.hypot(velocity.1) < self.config.min_velocity {
+            drop(inner);
+            self.send_stop(data, time, source);
+            return false;
+        }
+
+        trace!(?velocity, "Starting kinetic scroll");
+        inner.velocity = velocity;
+        inner.time = time;
+
+        let config = self.config;
+        let pointer = self.pointer.clone();
+        let state = self.inner.clone();
+        let token =
+            self.loop_handle
+                .insert_source(Timer::from_duration(config.interval), move |_, _, data| {
+                    let mut inner = state.lock().unwrap();
+                    let frame = fling_step(&mut inner, &config, source);
+                    let stopped = frame.stop.0;
+                    if stopped {
+                        inner.token = None;
+                    }
+                    drop(inner);
+
+                    pointer.axis(data, frame);
+                    pointer.frame(data);
+
+                    if stopped {
+                        TimeoutAction::Drop
+                    } else {
+                        TimeoutAction::ToDuration(config.interval)
+                    }
+                });
+        inner.token = token.ok();
+        inner.token.is_some()
+    }
+
+    /// Stop a running fling, e.g. because the user touched the screen again
+    pub fn cancel(&self, data: &mut D) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(token) = inner.token.take() else {
+            return;
+        };
+        self.loop_handle.remove(token);
+        let (time, source) = (inner.time, inner.source.unwrap_or(AxisSource::Continuous));
+        drop(inner);
+
+        self.send_stop(data, time, source);
+    }
+
+    fn send_stop(&self, data: &mut D, time: u32, source: AxisSource) {
+        let frame = AxisFrame::new(time)
+            .source(source)
+            .stop(Axis::Horizontal)
+            .stop(Axis::Vertical);
+        self.pointer.axis(data, frame);
+        self.pointer.frame(data);
+    }
+}
+
+// advances the fling by one interval, returning a frame with axis stops once the fling ends
+fn fling_step(inner: &mut KineticScrollInner, config: &KineticScrollConfig, source: AxisSource) -> AxisFrame {
+    let dt = config.interval.as_secs_f64();
+    let decay = (-config.friction * dt).exp();
+    inner.velocity = (inner.velocity.0 * decay, inner.velocity.1 * decay);
+    inner.time = inner.time.wrapping_add(config.interval.as_millis() as u32);
+
+    let frame = AxisFrame::new(inner.time).source(source);
+    if inner.velocity.0.hypot(inner.velocity.1) < config.min_velocity {
+        return frame.stop(Axis::Horizontal).stop(Axis::Vertical);
+    }
+    frame
+        .value(Axis::Horizontal, inner.velocity.0 * dt)
+        .value(Axis::Vertical, inner.velocity.1 * dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fling_decays_and_stops() {
+        let mut inner = KineticScrollInner::default();
+        inner
+            .samples
+            .extend([(0, (0.0, 10.0)), (50, (0.0, 20.0)), (90, (0.0, 10.0))]);
+        // the first sample is outside of the velocity window
+        inner.velocity = inner.release_velocity(150);
+        assert_eq!(inner.velocity, (0.0, 300.0));
+        inner.time = 150;
+
+        let config = KineticScrollConfig::default();
+        let mut last = f64::INFINITY;
+        let mut frames = 0;
+        loop {
+            let frame = fling_step(&mut inner, &config, AxisSource::Continuous);
+            frames += 1;
+            if frame.stop == (true, true) {
+                assert_eq!(frame.axis, (0.0, 0.0));
+                break;
+            }
+            assert!(frame.axis.1 > 0.0 && frame.axis.1 < last);
+            last = frame.axis.1;
+        }
+        assert_eq!(inner.time, 150 + frames * 16);
+    }
+}
@@ -12,13 +12,21 @@ use crate::{
     utils::{Clock, IsAlive, Logical, Monotonic, Point},
 };
 
+mod barrier;
+pub use barrier::{
+    BarrierDirections, BarrierHit, BarrierId, BarrierOrientation, PointerBarrier, PointerBarriers,
+};
+
 mod cursor_image;
 pub use cursor_icon::CursorIcon;
-pub use cursor_image::{CursorImageAttributes, CursorImageStatus, CursorImageSurfaceData};
+pub use cursor_image::{CursorImageAttributes, CursorImageStatus, CursorImageSurfaceData, CursorScale};
 
 mod grab;
 use grab::DefaultGrab;
 pub use grab::{ClickGrab, GrabStartData, PointerGrab};
+
+mod kinetic;
+pub use kinetic::{KineticScroll, KineticScrollConfig};
 use tracing::{info_span, instrument};
 
 /// An handle to a pointer handler
@@ -207,6 +215,11 @@ impl<D: SeatHandler + 'static> PointerHandle<D> {
         }
     }
 
+    /// Access the [`PointerBarriers`] restricting the motion of this pointer
+    pub fn with_barriers<T>(&self, f: impl FnOnce(&mut PointerBarriers) -> T) -> T {
+        f(&mut self.inner.lock().unwrap().barriers)
+    }
+
     /// Calls `f` with the active grab, if any.
     pub fn with_grab<T>(&self, f: impl FnOnce(Serial, &dyn PointerGrab<D>) -> T) -> Option<T> {
         let guard = self.inner.lock().unwrap();
@@ -228,6 +241,10 @@ impl<D: SeatHandler + 'static> PointerHandle<D> {
     ///
     /// This will internally take care of notifying the appropriate client objects
     /// of enter/motion/leave events.
+    ///
+    /// The location is restricted by the [`PointerBarriers`] of this pointer, see
+    /// [`PointerHandle::with_barriers`]. If a barrier was hit, the location of the focus
+    /// is kept, so the position on the focused surface stays consistent with the restricted location.
     #[instrument(level = "trace", parent = &self.span, skip(self, data, focus), fields(focus = ?focus.as_ref().map(|(_, loc)| ("...", loc))))]
     pub fn motion(
         &self,
@@ -238,6 +255,7 @@ impl<D: SeatHandler + 'static> PointerHandle<D> {
         let mut inner = self.inner.lock().unwrap();
         inner.pending_focus.clone_from(&focus);
         let seat = self.get_seat(data);
+        let event = &inner.restrict_motion(event);
         inner.with_grab(data, &seat, |data, handle, grab| {
             grab.motion(data, handle, focus, event);
         });
@@ -248,6 +266,10 @@ impl<D: SeatHandler + 'static> PointerHandle<D> {
     /// This will internally send the appropriate button event to the client
     /// objects matching with the currently focused surface, if the client uses
     /// the relative pointer protocol.
+    ///
+    /// If the pointer is currently pushed against one of its [`PointerBarriers`], the
+    /// motion towards the barrier is still reported, as the relative pointer protocol
+    /// requires deltas to be sent even if the pointer is restricted.
     #[instrument(level = "trace", parent = &self.span, skip(self, data, focus), fields(focus = ?focus.as_ref().map(|(_, loc)| ("...", loc))))]
     pub fn relative_motion(
         &self,
@@ -705,6 +727,7 @@ pub(crate) struct PointerInternal<D: SeatHandler> {
     location: Point<f64, Logical>,
     grab: GrabStatus<dyn PointerGrab<D>>,
     pressed_buttons: Vec<u32>,
+    barriers: PointerBarriers,
 }
 
 // image_callback does not implement debug, so we have to impl Debug manually
@@ -716,6 +739,7 @@ impl<D: SeatHandler> fmt::Debug for PointerInternal<D> {
             .field("location", &self.location)
             .field("grab", &self.grab)
             .field("pressed_buttons", &self.pressed_buttons)
+            .field("barriers", &self.barriers)
             .field("image_callback", &"...")
             .finish()
     }
@@ -729,6 +753,21 @@ impl<D: SeatHandler + 'static> PointerInternal<D> {
             location: (0.0, 0.0).into(),
             grab: GrabStatus::None,
             pressed_buttons: Vec::new(),
+            barriers: PointerBarriers::new(),
+        }
+    }
+
+    fn restrict_motion(&mut self, event: &MotionEvent) -> MotionEvent {
+        if self.barriers.is_empty() {
+            return event.clone();
+        }
+
+        let time = event.time as u64 * 1000;
+        MotionEvent {
+            location: self
+                .barriers
+                .apply_and_collect(self.location, event.location, time),
+            ..event.clone()
         }
     }
 
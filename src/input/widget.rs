@@ -0,0 +1,447 @@
+//! Compositor-internal input targets
+//!
+//! Compositors often draw some of their user interface themselves, e.g. panels or
+//! launchers rendered with a toolkit like `egui` or `iced`. This module provides
+//! [`WidgetFocus`], a [`PointerTarget`] and [`KeyboardTarget`] that translates the
+//! input of a [`Seat`] into toolkit-agnostic [`WidgetEvent`]s, so such widgets can
+//! take focus like regular clients.
+//!
+//! To use it, add a variant holding a [`WidgetFocus`] to the types used as
+//! [`SeatHandler::PointerFocus`] and [`SeatHandler::KeyboardFocus`] and forward the
+//! target methods to it. The widget then receives pointer locations relative to
+//! the location of the focus as passed to the [`PointerHandle`](crate::input::pointer::PointerHandle).
+//!
+//! ```
+//! use smithay::input::widget::{Widget, WidgetEvent, WidgetFocus};
+//! # use smithay::input::{Seat, SeatHandler};
+//!
+//! struct SearchField {
+//!     text: String,
+//! }
+//!
+//! impl<D: SeatHandler> Widget<D> for SearchField {
+//!     fn handle_event(&mut self, _seat: &Seat<D>, _data: &mut D, event: WidgetEvent) {
+//!         if let WidgetEvent::Key { text: Some(text), .. } = event {
+//!             self.text.push_str(&text);
+//!         }
+//!     }
+//! }
+//!
+//! # fn create<D: SeatHandler + 'static>() -> WidgetFocus<D> {
+//! let focus = WidgetFocus::new(SearchField { text: String::new() });
+//! # focus
+//! # }
+//! ```
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use xkbcommon::xkb::{Keycode, Keysym};
+
+use crate::{
+    backend::input::{AxisSource, ButtonState, KeyState, MouseButton},
+    input::{
+        keyboard::{KeyboardTarget, KeysymHandle, ModifiersState},
+        pointer::{
+            AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
+            GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
+            GestureSwipeUpdateEvent, MotionEvent, PointerTarget, RelativeMotionEvent,
+        },
+        Seat, SeatHandler,
+    },
+    utils::{IsAlive, Logical, Point, Serial},
+};
+
+/// Input event delivered to a [`Widget`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum WidgetEvent {
+    /// The pointer entered the widget
+    PointerEnter {
+        /// Location of the pointer relative to the widget
+        location: Point<f64, Logical>,
+    },
+    /// The pointer moved over the widget
+    PointerMotion {
+        /// Location of the pointer relative to the widget
+        location: Point<f64, Logical>,
+        /// Timestamp of the event, with millisecond granularity
+        time: u32,
+    },
+    /// A pointer button was pressed or released
+    PointerButton {
+        /// The button, if it is a standard mouse button
+        button: Option<MouseButton>,
+        /// Button code as defined in `linux/input-event-codes.h`
+        code: u32,
+        /// State of the button
+        state: ButtonState,
+        /// Timestamp of the event, with millisecond granularity
+        time: u32,
+    },
+    /// The pointer scrolled
+    PointerAxis {
+        /// Source of the scroll event, if known
+        source: Option<AxisSource>,
+        /// Scroll amount on the horizontal and vertical axis
+        delta: (f64, f64),
+        /// Discrete scroll amount in fractions of 120 per wheel click, if available
+        v120: Option<(i32, i32)>,
+        /// Timestamp of the event, with millisecond granularity
+        time: u32,
+    },
+    /// The pointer left the widget
+    PointerLeave,
+    /// The widget received keyboard focus
+    KeyboardEnter,
+    /// The widget lost keyboard focus
+    KeyboardLeave,
+    /// A key was pressed or released while the widget had keyboard focus
+    Key {
+        /// Logical key with the current keymap state applied
+        key: WidgetKey,
+        /// Raw keycode of the key
+        keycode: Keycode,
+        /// State of the key
+        state: KeyState,
+        /// Text produced by the key press, if any
+        text: Option<String>,
+        /// Timestamp of the event, with millisecond granularity
+        time: u32,
+    },
+    /// The state of the keyboard modifiers changed
+    ///
+    /// Also sent after the widget received keyboard focus.
+    Modifiers(ModifiersState),
+}
+
+/// Logical key of a [`WidgetEvent::Key`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WidgetKey {
+    /// A key not producing any text
+    Named(NamedKey),
+    /// A key producing the given character
+    Character(String),
+    /// A keysym with no known translation
+    Unidentified(Keysym),
+}
+
+/// Keys not producing text commonly handled by toolkits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NamedKey {
+    /// The Enter or Return key
+    Enter,
+    /// The Tab key
+    Tab,
+    /// The Space key
+    Space,
+    /// The Backspace key
+    Backspace,
+    /// The Escape key
+    Escape,
+    /// The Delete key
+    Delete,
+    /// The Insert key
+    Insert,
+    /// The Home key
+    Home,
+    /// The End key
+    End,
+    /// The Page Up key
+    PageUp,
+    /// The Page Down key
+    PageDown,
+    /// The left arrow key
+    ArrowLeft,
+    /// The right arrow key
+    ArrowRight,
+    /// The up arrow key
+    ArrowUp,
+    /// The down arrow key
+    ArrowDown,
+    /// A Shift key
+    Shift,
+    /// A Control key
+    Control,
+    /// An Alt key
+    Alt,
+    /// A Super/Logo key
+    Super,
+    /// The Caps Lock key
+    CapsLock,
+    /// A function key, `F(1)` being F1
+    F(u8),
+}
+
+impl WidgetKey {
+    /// Translate a keysym into a logical key
+    pub fn from_keysym(sym: Keysym) -> WidgetKey {
+        let named = match sym {
+            Keysym::Return | Keysym::KP_Enter | Keysym::ISO_Enter => Some(NamedKey::Enter),
+            Keysym::Tab | Keysym::KP_Tab | Keysym::ISO_Left_Tab => Some(NamedKey::Tab),
+            Keysym::space | Keysym::KP_Space => Some(NamedKey::Space),
+            Keysym::BackSpace => Some(NamedKey::Backspace),
+            Keysym::Escape => Some(NamedKey::Escape),
+            Keysym::Delete | Keysym::KP_Delete => Some(NamedKey::Delete),
+            Keysym::Insert | Keysym::KP_Insert => Some(NamedKey::Insert),
+            Keysym::Home | Keysym::KP_Home => Some(NamedKey::Home),
+            Keysym::End | Keysym::KP_End => Some(NamedKey::End),
+            Keysym::Page_Up | Keysym::KP_Page_Up => Some(NamedKey::PageUp),
+            Keysym::Page_Down | Keysym::KP_Page_Down => Some(NamedKey::PageDown),
+            Keysym::Left | Keysym::KP_Left => Some(NamedKey::ArrowLeft),
+            Keysym::Right | Keysym::KP_Right => Some(NamedKey::ArrowRight),
+            Keysym::Up | Keysym::KP_Up => Some(NamedKey::ArrowUp),
+            Keysym::Down | Keysym::KP_Down => Some(NamedKey::ArrowDown),
+            Keysym::Shift_L | Keysym::Shift_R => Some(NamedKey::Shift),
+            Keysym::Control_L | Keysym::Control_R => Some(NamedKey::Control),
+            Keysym::Alt_L | Keysym::Alt_R | Keysym::ISO_Level3_Shift => Some(NamedKey::Alt),
+            Keysym::Super_L | Keysym::Super_R => Some(NamedKey::Super),
+            Keysym::Caps_Lock => Some(NamedKey::CapsLock),
+            sym if (Keysym::F1.raw()..=Keysym::F35.raw()).contains(&sym.raw()) => {
+                Some(NamedKey::F((sym.raw() - Keysym::F1.raw() + 1) as u8))
+            }
+            _ => None,
+        };
+
+        match named {
+            Some(named) => WidgetKey::Named(named),
+            None => match sym.key_char() {
+                Some(c) if !c.is_control() => WidgetKey::Character(c.to_string()),
+                _ => WidgetKey::Unidentified(sym),
+            },
+        }
+    }
+}
+
+/// A compositor-internal widget able to receive input
+///
+/// The widget is locked while it handles an event, so it must not try to access
+/// itself through `data`.
+pub trait Widget<D: SeatHandler>: Send {
+    /// Handle an input event of the given seat
+    fn handle_event(&mut self, seat: &Seat<D>, data: &mut D, event: WidgetEvent);
+
+    /// Returns false once the widget was closed and should not receive input anymore
+    fn alive(&self) -> bool {
+        true
+    }
+}
+
+/// Focus target delivering the input of a [`Seat`] to a [`Widget`]
+///
+/// Clones refer to the same widget and compare equal.
+pub struct WidgetFocus<D: SeatHandler> {
+    widget: Arc<Mutex<dyn Widget<D>>>,
+}
+
+impl<D: SeatHandler + 'static> WidgetFocus<D> {
+    /// Create a new focus target for a widget
+    pub fn new<W: Widget<D> + 'static>(widget: W) -> Self {
+        WidgetFocus {
+            widget: Arc::new(Mutex::new(widget)),
+        }
+    }
+
+    /// Create a new focus target for a widget, which is also accessed elsewhere, e.g. for rendering
+    pub fn from_shared<W: Widget<D> + 'static>(widget: Arc<Mutex<W>>) -> Self {
+        WidgetFocus { widget }
+    }
+}
+
+impl<D: SeatHandler> WidgetFocus<D> {
+    /// Access the underlying widget
+    pub fn with_widget<T>(&self, f: impl FnOnce(&mut dyn Widget<D>) -> T) -> T {
+        f(&mut *self.widget.lock().unwrap())
+    }
+
+    fn send(&self, seat: &Seat<D>, data: &mut D, event: WidgetEvent) {
+        self.widget.lock().unwrap().handle_event(seat, data, event);
+    }
+}
+
+impl<D: SeatHandler> Clone for WidgetFocus<D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        WidgetFocus {
+            widget: self.widget.clone(),
+        }
+    }
+}
+
+impl<D: SeatHandler> PartialEq for WidgetFocus<D> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.widget, &other.widget)
+    }
+}
+
+impl<D: SeatHandler> fmt::Debug for WidgetFocus<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WidgetFocus")
+            .field("widget", &Arc::as_ptr(&self.widget))
+            .finish()
+    }
+}
+
+impl<D: SeatHandler> IsAlive for WidgetFocus<D> {
+    #[inline]
+    fn alive(&self) -> bool {
+        self.widget.lock().unwrap().alive()
+    }
+}
+
+impl<D: SeatHandler> PointerTarget<D> for WidgetFocus<D> {
+    fn enter(&self, seat: &Seat<D>, data: &mut D, event: &MotionEvent) {
+        self.send(
+            seat,
+            data,
+            WidgetEvent::PointerEnter {
+                location: event.location,
+            },
+        );
+    }
+
+    fn motion(&self, seat: &Seat<D>, data: &mut D, event: &MotionEvent) {
+        self.send(
+            seat,
+            data,
+            WidgetEvent::PointerMotion {
+                location: event.location,
+                time: event.time,
+            },
+        );
+    }
+
+    fn relative_motion(&self, _seat: &Seat<D>, _data: &mut D, _event: &RelativeMotionEvent) {}
+
+    fn button(&self, seat: &Seat<D>, data: &mut D, event: &ButtonEvent) {
+        self.send(
+            seat,
+            data,
+            WidgetEvent::PointerButton {
+                button: MouseButton::from_button_code(event.button),
+                code: event.button,
+                state: event.state,
+                time: event.time,
+            },
+        );
+    }
+
+    fn axis(&self, seat: &Seat<D>, data: &mut D, frame: AxisFrame) {
+        self.send(
+            seat,
+            data,
+            WidgetEvent::PointerAxis {
+                source: frame.source,
+                delta: frame.axis,
+                v120: frame.v120,
+                time: frame.time,
+            },
+        );
+    }
+
+    fn frame(&self, _seat: &Seat<D>, _data: &mut D) {}
+
+    fn gesture_swipe_begin(&self, _seat: &Seat<D>, _data: &mut D, _event: &GestureSwipeBeginEvent) {}
+
+    fn gesture_swipe_update(&self, _seat: &Seat<D>, _data: &mut D, _event: &GestureSwipeUpdateEvent) {}
+
+    fn gesture_swipe_end(&self, _seat: &Seat<D>, _data: &mut D, _event: &GestureSwipeEndEvent) {}
+
+    fn gesture_pinch_begin(&self, _seat: &Seat<D>, _data: &mut D, _event: &GesturePinchBeginEvent) {}
+
+    fn gesture_pinch_update(&self, _seat: &Seat<D>, _data: &mut D, _event: &GesturePinchUpdateEvent) {}
+
+    fn gesture_pinch_end(&self, _seat: &Seat<D>, _data: &mut D, _event: &GesturePinchEndEvent) {}
+
+    fn gesture_hold_begin(&self, _seat: &Seat<D>, _data: &mut D, _event: &GestureHoldBeginEvent) {}
+
+    fn gesture_hold_end(&self, _seat: &Seat<D>, _data: &mut D, _event: &GestureHoldEndEvent) {}
+
+    fn leave(&self, seat: &Seat<D>, data: &mut D, _serial: Serial, _time: u32) {
+        self.send(seat, data, WidgetEvent::PointerLeave);
+    }
+}
+
+impl<D: SeatHandler> KeyboardTarget<D> for WidgetFocus<D> {
+    fn enter(&self, seat: &Seat<D>, data: &mut D, _keys: Vec<KeysymHandle<'_>>, _serial: Serial) {
+        self.send(seat, data, WidgetEvent::KeyboardEnter);
+    }
+
+    fn leave(&self, seat: &Seat<D>, data: &mut D, _serial: Serial) {
+        self.send(seat, data, WidgetEvent::KeyboardLeave);
+    }
+
+    fn key(
+        &self,
+        seat: &Seat<D>,
+        data: &mut D,
+        key: KeysymHandle<'_>,
+        state: KeyState,
+        _serial: Serial,
+        time: u32,
+    ) {
+        let text = if state == KeyState::Pressed {
+            let xkb = key.xkb().lock().unwrap();
+            Some(xkb.state.key_get_utf8(key.raw_code())).filter(|text| !text.chars().all(char::is_control))
+        } else {
+            None
+        };
+
+        self.send(
+            seat,
+            data,
+            WidgetEvent::Key {
+                key: WidgetKey::from_keysym(key.modified_sym()),
+                keycode: key.raw_code(),
+                state,
+                text,
+                time,
+            },
+        );
+    }
+
+    fn modifiers(&self, seat: &Seat<D>, data: &mut D, modifiers: ModifiersState, _serial: Serial) {
+        self.send(seat, data, WidgetEvent::Modifiers(modifiers));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keysym_translation() {
+        assert_eq!(
+            WidgetKey::from_keysym(Keysym::Return),
+            WidgetKey::Named(NamedKey::Enter)
+        );
+        assert_eq!(
+            WidgetKey::from_keysym(Keysym::KP_Left),
+            WidgetKey::Named(NamedKey::ArrowLeft)
+        );
+        assert_eq!(
+            WidgetKey::from_keysym(Keysym::F1),
+            WidgetKey::Named(NamedKey::F(1))
+        );
+        assert_eq!(
+            WidgetKey::from_keysym(Keysym::F12),
+            WidgetKey::Named(NamedKey::F(12))
+        );
+        assert_eq!(
+            WidgetKey::from_keysym(Keysym::a),
+            WidgetKey::Character("a".into())
+        );
+        assert_eq!(
+            WidgetKey::from_keysym(Keysym::adiaeresis),
+            WidgetKey::Character("ä".into())
+        );
+        assert_eq!(
+            WidgetKey::from_keysym(Keysym::XF86_AudioPlay),
+            WidgetKey::Unidentified(Keysym::XF86_AudioPlay)
+        );
+    }
+}
@@ -0,0 +1,585 @@
+//! Integration of async code running on a tokio or async-std executor
+//!
+//! Many crates useful for services around a compositor, like `zbus` or the async apis
+//! of `pipewire-rs`, expect to run on an async executor, while the compositor itself is
+//! driven by a calloop event loop.
+//!
+//! An [`AsyncBridge`] runs futures on a tokio runtime with its own worker threads
+//! (requires the `async_tokio` feature) or on the global executor of async-std
+//! (requires the `async_std` feature). Futures spawned onto it can access the compositor
+//! state through an [`AsyncHandle`], which executes closures on the event loop and returns
+//! their results as futures. Calloop timers and the readiness of file descriptors are exposed
+//! as futures in the same way, so async code can wait on them without blocking the event loop.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use smithay::utils::async_bridge::AsyncBridge;
+//! # use smithay::reexports::calloop::EventLoop;
+//! # struct State { idle: bool }
+//! # let event_loop: EventLoop<'static, State> = EventLoop::try_new().unwrap();
+//! # #[cfg(feature = "async_tokio")]
+//! let bridge = AsyncBridge::new(&event_loop.handle(), 1).expect("failed to start the tokio runtime");
+//! # #[cfg(not(feature = "async_tokio"))]
+//! # let bridge = AsyncBridge::with_async_std(&event_loop.handle()).unwrap();
+//!
+//! let handle = bridge.handle();
+//! bridge.spawn(async move {
+//!     handle.sleep(Duration::from_secs(60)).await?;
+//!     handle.run(|state: &mut State, _| state.idle = true).await
+//! });
+//! ```
+//!
+//! The futures returned by an [`AsyncHandle`] do not depend on the executor, so they can also be awaited
+//! on other executors, as long as the event loop keeps running.
+
+use std::{
+    fmt,
+    future::Future,
+    io,
+    os::unix::io::{AsFd, OwnedFd},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use calloop::{
+    channel::{channel, Event, Sender},
+    generic::Generic,
+    timer::{TimeoutAction, Timer},
+    Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+};
+#[cfg(feature = "async_tokio")]
+use tokio::runtime::{Builder, Handle, Runtime};
+
+type LoopTask<D> = Box<dyn FnOnce(&mut D, &LoopHandle<'static, D>) + Send>;
+
+/// Errors of the [`AsyncBridge`]
+#[derive(Debug, thiserror::Error)]
+pub enum AsyncBridgeError {
+    /// Failed to create the tokio runtime
+    #[error("failed to create the tokio runtime")]
+    Runtime(#[source] io::Error),
+    /// Failed to insert the bridge into the event loop
+    #[error("failed to insert the bridge into the event loop")]
+    EventLoop(#[source] calloop::Error),
+    /// The event loop source of the bridge was removed
+    #[error("the event loop source of the bridge was removed")]
+    Disconnected,
+    /// Failed to register a source with the event loop
+    #[error("failed to register a source with the event loop")]
+    Io(#[source] io::Error),
+    /// The spawned future panicked or was cancelled
+    #[error("the spawned future panicked or was cancelled")]
+    Task,
+}
+
+/// Async executor bridged into a calloop event loop
+///
+/// Dropping a bridge using a tokio runtime shuts down the runtime without waiting for spawned futures
+/// to finish. Futures spawned onto async-std keep running on its global executor.
+///
+/// See the [module documentation](self) for details.
+pub struct AsyncBridge<D> {
+    #[cfg(feature = "async_tokio")]
+    runtime: Option<Runtime>,
+    handle: AsyncHandle<D>,
+    token: RegistrationToken,
+}
+
+impl<D> fmt::Debug for AsyncBridge<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("AsyncBridge");
+        #[cfg(feature = "async_tokio")]
+        debug.field("runtime", &self.runtime);
+        debug
+            .field("executor", &self.handle.executor)
+            .field("token", &self.token)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: 'static> AsyncBridge<D> {
+    /// Start a tokio runtime using `worker_threads` threads and bridge it into the event loop of `loop_handle`
+    #[cfg(feature = "async_tokio")]
+    pub fn new(
+        loop_handle: &LoopHandle<'static, D>,
+        worker_threads: usize,
+    ) -> Result<Self, AsyncBridgeError> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(worker_threads.max(1))
+            .thread_name("smithay-async")
+            .enable_all()
+            .build()
+            .map_err(AsyncBridgeError::Runtime)?;
+
+        let (handle, token) = AsyncHandle::new(loop_handle, Executor::Tokio(runtime.handle().clone()))?;
+        Ok(AsyncBridge {
+            runtime: Some(runtime),
+            handle,
+            token,
+        })
+    }
+
+    /// Bridge the global executor of async-std into the event loop of `loop_handle`
+    #[cfg(feature = "async_std")]
+    pub fn with_async_std(loop_handle: &LoopHandle<'static, D>) -> Result<Self, AsyncBridgeError> {
+        let (handle, token) = AsyncHandle::new(loop_handle, Executor::AsyncStd)?;
+        Ok(AsyncBridge {
+            #[cfg(feature = "async_tokio")]
+            runtime: None,
+            handle,
+            token,
+        })
+    }
+
+    /// Returns a handle to spawn futures and to access the event loop from them
+    pub fn handle(&self) -> AsyncHandle<D> {
+        self.handle.clone()
+    }
+
+    /// Returns the token of the event loop source of this bridge
+    pub fn token(&self) -> RegistrationToken {
+        self.token
+    }
+
+    /// Spawn a future onto the executor
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.handle.spawn(future)
+    }
+}
+
+#[cfg(feature = "async_tokio")]
+impl<D> Drop for AsyncBridge<D> {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            // Don't block the event loop on futures, which might wait for it
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Executor {
+    #[cfg(feature = "async_tokio")]
+    Tokio(Handle),
+    #[cfg(feature = "async_std")]
+    AsyncStd,
+}
+
+/// Handle of a future spawned with [`AsyncHandle::spawn`]
+///
+/// Awaiting it returns the output of the future. Dropping it detaches the future.
+#[derive(Debug)]
+pub struct JoinHandle<T>(JoinHandleInner<T>);
+
+#[derive(Debug)]
+enum JoinHandleInner<T> {
+    #[cfg(feature = "async_tokio")]
+    Tokio(tokio::task::JoinHandle<T>),
+    #[cfg(feature = "async_std")]
+    AsyncStd(async_std::task::JoinHandle<T>),
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, AsyncBridgeError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.0 {
+            #[cfg(feature = "async_tokio")]
+            JoinHandleInner::Tokio(handle) => Pin::new(handle)
+                .poll(cx)
+                .map(|res| res.map_err(|_| AsyncBridgeError::Task)),
+            #[cfg(feature = "async_std")]
+            JoinHandleInner::AsyncStd(handle) => Pin::new(handle).poll(cx).map(Ok),
+        }
+    }
+}
+
+/// Thread-safe handle of an [`AsyncBridge`]
+///
+/// The futures returned by this handle fail with [`AsyncBridgeError::Disconnected`],
+/// if the event loop source of the bridge was removed or the event loop was dropped.
+pub struct AsyncHandle<D> {
+    executor: Executor,
+    sender: Sender<LoopTask<D>>,
+}
+
+impl<D> fmt::Debug for AsyncHandle<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncHandle")
+            .field("executor", &self.executor)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D> Clone for AsyncHandle<D> {
+    fn clone(&self) -> Self {
+        AsyncHandle {
+            executor: self.executor.clone(),
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<D: 'static> AsyncHandle<D> {
+    fn new(
+        loop_handle: &LoopHandle<'static, D>,
+        executor: Executor,
+    ) -> Result<(Self, RegistrationToken), AsyncBridgeError> {
+        let (sender, channel) = channel::<LoopTask<D>>();
+        let task_handle = loop_handle.clone();
+        let token = loop_handle
+            .insert_source(channel, move |event, _, state| {
+                if let Event::Msg(task) = event {
+                    task(state, &task_handle);
+                }
+            })
+            .map_err(|err| AsyncBridgeError::EventLoop(err.error))?;
+        Ok((AsyncHandle { executor, sender }, token))
+    }
+
+    /// Spawn a future onto the executor
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.executor {
+            #[cfg(feature = "async_tokio")]
+            Executor::Tokio(runtime) => JoinHandle(JoinHandleInner::Tokio(runtime.spawn(future))),
+            #[cfg(feature = "async_std")]
+            Executor::AsyncStd => JoinHandle(JoinHandleInner::AsyncStd(async_std::task::spawn(future))),
+        }
+    }
+
+    /// Execute a closure on the event loop and return its result
+    pub async fn run<F, T>(&self, f: F) -> Result<T, AsyncBridgeError>
+    where
+        F: FnOnce(&mut D, &LoopHandle<'static, D>) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (reply, result) = oneshot();
+        self.sender
+            .send(Box::new(move |state, handle| {
+                reply.send(f(state, handle));
+            }))
+            .map_err(|_| AsyncBridgeError::Disconnected)?;
+        result.await
+    }
+
+    /// Wait for `duration` using a calloop [`Timer`]
+    pub async fn sleep(&self, duration: Duration) -> Result<(), AsyncBridgeError> {
+        self.wait_for_source(move |handle, mut fired| {
+            handle
+                .insert_source(Timer::from_duration(duration), move |_, _, _| {
+                    fired.fire();
+                    TimeoutAction::Drop
+                })
+                .map_err(|err| io::Error::other(err.error))
+        })
+        .await
+    }
+
+    /// Wait for `fd` to become readable
+    pub async fn readable(&self, fd: impl AsFd) -> Result<(), AsyncBridgeError> {
+        self.ready(fd, Interest::READ).await
+    }
+
+    /// Wait for `fd` to become writable
+    pub async fn writable(&self, fd: impl AsFd) -> Result<(), AsyncBridgeError> {
+        self.ready(fd, Interest::WRITE).await
+    }
+
+    async fn ready(&self, fd: impl AsFd, interest: Interest) -> Result<(), AsyncBridgeError> {
+        // The source owns a duplicate, readiness is shared with the original file description
+        let fd: OwnedFd = fd.as_fd().try_clone_to_owned().map_err(AsyncBridgeError::Io)?;
+        self.wait_for_source(move |handle, mut fired| {
+            handle
+                .insert_source(Generic::new(fd, interest, Mode::OneShot), move |_, _, _| {
+                    fired.fire();
+                    Ok(PostAction::Remove)
+                })
+                .map_err(|err| io::Error::other(err.error))
+        })
+        .await
+    }
+
+    // Insert a source, which calls `SourceFired::fire` once and removes itself afterwards.
+    // The source is removed early, if the returned future is dropped before that.
+    async fn wait_for_source<I>(&self, insert: I) -> Result<(), AsyncBridgeError>
+    where
+        I: FnOnce(&LoopHandle<'static, D>, SourceFired) -> io::Result<RegistrationToken> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot();
+        let state = Arc::new(Mutex::new(SourceState::Pending));
+        let _guard = SourceGuard {
+            state: state.clone(),
+            sender: self.sender.clone(),
+        };
+
+        let fired = SourceFired {
+            sender: Some(sender),
+            state: state.clone(),
+        };
+        self.run(move |_, handle| {
+            let token = insert(handle, fired)?;
+            let mut state = state.lock().unwrap();
+            match *state {
+                SourceState::Pending => *state = SourceState::Inserted(token),
+                // the future was dropped in the meantime
+                SourceState::Finished => handle.remove(token),
+                SourceState::Inserted(_) => unreachable!(),
+            }
+            Ok(())
+        })
+        .await?
+        .map_err(AsyncBridgeError::Io)?;
+
+        receiver.await
+    }
+
+    /// Schedule a closure on the event loop without waiting for it
+    pub fn schedule<F>(&self, f: F) -> Result<(), AsyncBridgeError>
+    where
+        F: FnOnce(&mut D, &LoopHandle<'static, D>) + Send + 'static,
+    {
+        self.sender
+            .send(Box::new(f))
+            .map_err(|_| AsyncBridgeError::Disconnected)
+    }
+}
+
+#[derive(Debug)]
+enum SourceState {
+    Pending,
+    Inserted(RegistrationToken),
+    Finished,
+}
+
+// Called by a source inserted with `AsyncHandle::wait_for_source`
+struct SourceFired {
+    sender: Option<OneshotSender<()>>,
+    state: Arc<Mutex<SourceState>>,
+}
+
+impl SourceFired {
+    fn fire(&mut self) {
+        // the source removes itself
+        *self.state.lock().unwrap() = SourceState::Finished;
+        if let Some(sender) = self.sender.take() {
+            sender.send(());
+        }
+    }
+}
+
+// Removes the source of a dropped `AsyncHandle::wait_for_source` future from the event loop
+struct SourceGuard<D> {
+    state: Arc<Mutex<SourceState>>,
+    sender: Sender<LoopTask<D>>,
+}
+
+impl<D> Drop for SourceGuard<D> {
+    fn drop(&mut self) {
+        let state = std::mem::replace(&mut *self.state.lock().unwrap(), SourceState::Finished);
+        if let SourceState::Inserted(token) = state {
+            let _ = self.sender.send(Box::new(move |_, handle| handle.remove(token)));
+        }
+    }
+}
+
+// Executor independent channel to send a single value to a future
+fn oneshot<T>() -> (OneshotSender<T>, OneshotReceiver<T>) {
+    let inner = Arc::new(Mutex::new(OneshotInner {
+        value: None,
+        sender_alive: true,
+        waker: None,
+    }));
+    (OneshotSender(inner.clone()), OneshotReceiver(inner))
+}
+
+struct OneshotInner<T> {
+    value: Option<T>,
+    sender_alive: bool,
+    waker: Option<Waker>,
+}
+
+struct OneshotSender<T>(Arc<Mutex<OneshotInner<T>>>);
+
+impl<T> OneshotSender<T> {
+    fn send(self, value: T) {
+        self.0.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<T> Drop for OneshotSender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.sender_alive = false;
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct OneshotReceiver<T>(Arc<Mutex<OneshotInner<T>>>);
+
+impl<T> Future for OneshotReceiver<T> {
+    type Output = Result<T, AsyncBridgeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.0.lock().unwrap();
+        if let Some(value) = inner.value.take() {
+            Poll::Ready(Ok(value))
+        } else if !inner.sender_alive {
+            Poll::Ready(Err(AsyncBridgeError::Disconnected))
+        } else {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Wake, Waker},
+        time::{Duration, Instant},
+    };
+
+    use calloop::EventLoop;
+
+    use super::AsyncBridge;
+
+    #[derive(Debug, Default)]
+    struct State {
+        value: u32,
+        done: bool,
+    }
+
+    type Loop = EventLoop<'static, State>;
+
+    fn dispatch_until(event_loop: &mut Loop, state: &mut State, cond: impl Fn(&State) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !cond(state) {
+            assert!(Instant::now() < deadline, "timed out waiting for the bridge");
+            event_loop
+                .dispatch(Some(Duration::from_millis(10)), state)
+                .unwrap();
+        }
+    }
+
+    fn check_run(event_loop: &mut Loop, bridge: AsyncBridge<State>) {
+        let mut state = State::default();
+        let handle = bridge.handle();
+        bridge.spawn(async move {
+            let previous = handle
+                .run(|state: &mut State, _| std::mem::replace(&mut state.value, 1))
+                .await?;
+            handle.schedule(move |state, _| {
+                state.value += previous + 1;
+                state.done = true;
+            })
+        });
+        dispatch_until(event_loop, &mut state, |state| state.done);
+        assert_eq!(state.value, 2);
+    }
+
+    fn check_sleep(event_loop: &mut Loop, bridge: AsyncBridge<State>) {
+        let mut state = State::default();
+        let handle = bridge.handle();
+        let start = Instant::now();
+        bridge.spawn(async move {
+            handle.sleep(Duration::from_millis(20)).await?;
+            handle.schedule(|state, _| state.done = true)
+        });
+        dispatch_until(event_loop, &mut state, |state| state.done);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    fn check_readable(event_loop: &mut Loop, bridge: AsyncBridge<State>) {
+        let mut state = State::default();
+        let (local, mut remote) = UnixStream::pair().unwrap();
+        let handle = bridge.handle();
+        bridge.spawn(async move {
+            handle.readable(&local).await?;
+            handle.schedule(|state, _| state.done = true)
+        });
+
+        for _ in 0..5 {
+            event_loop
+                .dispatch(Some(Duration::from_millis(5)), &mut state)
+                .unwrap();
+        }
+        assert!(!state.done);
+        remote.write_all(&[1]).unwrap();
+        dispatch_until(event_loop, &mut state, |state| state.done);
+    }
+
+    #[cfg(feature = "async_tokio")]
+    #[test]
+    fn tokio_executor() {
+        let mut event_loop = Loop::try_new().unwrap();
+        let bridge = AsyncBridge::new(&event_loop.handle(), 1).unwrap();
+        check_run(&mut event_loop, bridge);
+        let bridge = AsyncBridge::new(&event_loop.handle(), 1).unwrap();
+        check_sleep(&mut event_loop, bridge);
+        let bridge = AsyncBridge::new(&event_loop.handle(), 1).unwrap();
+        check_readable(&mut event_loop, bridge);
+    }
+
+    #[cfg(feature = "async_std")]
+    #[test]
+    fn async_std_executor() {
+        let mut event_loop = Loop::try_new().unwrap();
+        let bridge = AsyncBridge::with_async_std(&event_loop.handle()).unwrap();
+        check_run(&mut event_loop, bridge);
+        let bridge = AsyncBridge::with_async_std(&event_loop.handle()).unwrap();
+        check_sleep(&mut event_loop, bridge);
+        let bridge = AsyncBridge::with_async_std(&event_loop.handle()).unwrap();
+        check_readable(&mut event_loop, bridge);
+    }
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn dropped_future_removes_source() {
+        let mut event_loop = Loop::try_new().unwrap();
+        #[cfg(feature = "async_tokio")]
+        let bridge = AsyncBridge::new(&event_loop.handle(), 1).unwrap();
+        #[cfg(not(feature = "async_tokio"))]
+        let bridge = AsyncBridge::with_async_std(&event_loop.handle()).unwrap();
+        let handle = bridge.handle();
+        let mut state = State::default();
+
+        let (local, mut remote) = UnixStream::pair().unwrap();
+        remote.set_nonblocking(true).unwrap();
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        {
+            let mut future = pin!(handle.readable(&local));
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+            // inserts the source
+            event_loop.dispatch(Some(Duration::ZERO), &mut state).unwrap();
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        }
+        // the source held a duplicate of the socket, the remote end only sees
+        // the connection closing, once it was removed
+        drop(local);
+        event_loop.dispatch(Some(Duration::ZERO), &mut state).unwrap();
+        assert_eq!(remote.read(&mut [0]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,234 @@
+//! Scheduling closures on the event loop of the compositor from other threads
+//!
+//! Compositors often integrate services running on their own threads, like IPC servers,
+//! D-Bus interfaces or pipewire callbacks. These need to modify the compositor state,
+//! which is only accessible from the thread running the event loop.
+//!
+//! A [`CompositorHandle`] can be cloned and sent to other threads to schedule closures,
+//! which are executed on the event loop with access to the compositor state and the
+//! [`DisplayHandle`]. Clients are flushed after each closure, so any events sent by it
+//! are delivered without waiting for the next iteration of the event loop.
+//!
+//! The number of queued closures is bounded. Once the queue is full,
+//! [`CompositorHandle::schedule`] blocks until the event loop catches up,
+//! while [`CompositorHandle::try_schedule`] fails instead.
+//!
+//! ```no_run
+//! # use smithay::utils::compositor_handle::CompositorHandle;
+//! # use smithay::reexports::{calloop::EventLoop, wayland_server::Display};
+//! # struct State { volume: u32 }
+//! # let event_loop: EventLoop<State> = EventLoop::try_new().unwrap();
+//! # let display: Display<State> = Display::new().unwrap();
+//! let (handle, _token) = CompositorHandle::new(&event_loop.handle(), display.handle(), 16)
+//!     .expect("failed to insert the compositor handle");
+//!
+//! std::thread::spawn(move || {
+//!     // e.g. inside of a D-Bus method call
+//!     handle
+//!         .schedule(|state: &mut State, _dh| state.volume = 50)
+//!         .unwrap();
+//!     let volume = handle.call(|state: &mut State, _dh| state.volume).unwrap();
+//! });
+//! ```
+
+use std::{fmt, sync::mpsc};
+
+use calloop::{
+    channel::{sync_channel, Event, SyncSender},
+    LoopHandle, RegistrationToken,
+};
+use tracing::warn;
+use wayland_server::DisplayHandle;
+
+type Task<D> = Box<dyn FnOnce(&mut D, &DisplayHandle) + Send>;
+
+/// Errors of scheduling a closure using a [`CompositorHandle`]
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleError {
+    /// The event loop source of the handle was removed
+    #[error("the event loop source of the handle was removed")]
+    Disconnected,
+    /// Too many closures are queued
+    #[error("too many closures are queued")]
+    Full,
+}
+
+/// Thread-safe handle to schedule closures on the event loop of the compositor
+///
+/// See the [module documentation](self) for details.
+pub struct CompositorHandle<D> {
+    sender: SyncSender<Task<D>>,
+}
+
+impl<D> fmt::Debug for CompositorHandle<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompositorHandle").finish_non_exhaustive()
+    }
+}
+
+impl<D> Clone for CompositorHandle<D> {
+    fn clone(&self) -> Self {
+        CompositorHandle {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<D: 'static> CompositorHandle<D> {
+    /// Create a new handle executing closures on the event loop of `loop_handle`
+    ///
+    /// At most `capacity` closures are queued, before scheduling further closures blocks or fails.
+    /// Removing the returned source from the event loop disconnects all clones of the handle.
+    pub fn new(
+        loop_handle: &LoopHandle<'_, D>,
+        mut display: DisplayHandle,
+        capacity: usize,
+    ) -> Result<(Self, RegistrationToken), calloop::Error> {
+        let (sender, channel) = sync_channel::<Task<D>>(capacity);
+        let token = loop_handle
+            .insert_source(channel, move |event, _, state| {
+                if let Event::Msg(task) = event {
+                    task(state, &display);
+                    if let Err(err) = display.flush_clients() {
+                        warn!(?err, "Failed to flush clients");
+                    }
+                }
+            })
+            .map_err(|err| err.error)?;
+
+        Ok((CompositorHandle { sender }, token))
+    }
+
+    /// Schedule a closure to be executed on the event loop
+    ///
+    /// Blocks while the queue is full. Calling this from the thread running the event loop
+    /// may therefore deadlock, use [`CompositorHandle::try_schedule`] instead.
+    pub fn schedule<F>(&self, f: F) -> Result<(), ScheduleError>
+    where
+        F: FnOnce(&mut D, &DisplayHandle) + Send + 'static,
+    {
+        self.sender
+            .send(Box::new(f))
+            .map_err(|_| ScheduleError::Disconnected)
+    }
+
+    /// Schedule a closure to be executed on the event loop, failing if the queue is full
+    pub fn try_schedule<F>(&self, f: F) -> Result<(), ScheduleError>
+    where
+        F: FnOnce(&mut D, &DisplayHandle) + Send + 'static,
+    {
+        self.sender.try_send(Box::new(f)).map_err(|err| match err {
+            mpsc::TrySendError::Full(_) => ScheduleError::Full,
+            mpsc::TrySendError::Disconnected(_) => ScheduleError::Disconnected,
+        })
+    }
+
+    /// Execute a closure on the event loop and wait for its result
+    ///
+    /// This must not be called from the thread running the event loop, as it would wait forever.
+    pub fn call<F, T>(&self, f: F) -> Result<T, ScheduleError>
+    where
+        F: FnOnce(&mut D, &DisplayHandle) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (reply, result) = mpsc::sync_channel(1);
+        self.schedule(move |state, dh| {
+            let _ = reply.send(f(state, dh));
+        })?;
+        // the closure is dropped without running, if the source is removed in the meantime
+        result.recv().map_err(|_| ScheduleError::Disconnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use calloop::EventLoop;
+    use wayland_server::Display;
+
+    use super::{CompositorHandle, ScheduleError};
+
+    #[derive(Default)]
+    struct State {
+        values: Vec<u32>,
+    }
+
+    fn setup(capacity: usize) -> (EventLoop<'static, State>, Display<State>, CompositorHandle<State>) {
+        let event_loop = EventLoop::try_new().unwrap();
+        let display = Display::new().unwrap();
+        let (handle, _) = CompositorHandle::new(&event_loop.handle(), display.handle(), capacity).unwrap();
+        (event_loop, display, handle)
+    }
+
+    #[test]
+    fn scheduled_closures_run_in_order() {
+        let (mut event_loop, _display, handle) = setup(4);
+        let mut state = State::default();
+
+        let thread_handle = handle.clone();
+        std::thread::spawn(move || {
+            for value in 0..3 {
+                thread_handle
+                    .schedule(move |state: &mut State, _| state.values.push(value))
+                    .unwrap();
+            }
+        })
+        .join()
+        .unwrap();
+
+        event_loop.dispatch(Duration::ZERO, &mut state).unwrap();
+        assert_eq!(state.values, [0, 1, 2]);
+    }
+
+    #[test]
+    fn backpressure() {
+        let (mut event_loop, _display, handle) = setup(1);
+        let mut state = State::default();
+
+        handle
+            .try_schedule(|state: &mut State, _| state.values.push(1))
+            .unwrap();
+        assert!(matches!(
+            handle.try_schedule(|state: &mut State, _| state.values.push(2)),
+            Err(ScheduleError::Full)
+        ));
+
+        event_loop.dispatch(Duration::ZERO, &mut state).unwrap();
+        assert_eq!(state.values, [1]);
+        handle
+            .try_schedule(|state: &mut State, _| state.values.push(3))
+            .unwrap();
+    }
+
+    #[test]
+    fn call_returns_result() {
+        let (mut event_loop, _display, handle) = setup(1);
+        let mut state = State { values: vec![7] };
+
+        let thread = std::thread::spawn(move || handle.call(|state: &mut State, _| state.values[0] * 6));
+        while !thread.is_finished() {
+            event_loop
+                .dispatch(Duration::from_millis(10), &mut state)
+                .unwrap();
+        }
+        assert_eq!(thread.join().unwrap().unwrap(), 42);
+    }
+
+    #[test]
+    fn removed_source_disconnects() {
+        let event_loop = EventLoop::<State>::try_new().unwrap();
+        let display = Display::<State>::new().unwrap();
+        let (handle, token) = CompositorHandle::new(&event_loop.handle(), display.handle(), 1).unwrap();
+
+        event_loop.handle().remove(token);
+        assert!(matches!(
+            handle.schedule(|state: &mut State, _| state.values.push(1)),
+            Err(ScheduleError::Disconnected)
+        ));
+        assert!(matches!(
+            handle.call(|_: &mut State, _| ()),
+            Err(ScheduleError::Disconnected)
+        ));
+    }
+}
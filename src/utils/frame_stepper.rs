@@ -0,0 +1,180 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use calloop::ping::Ping;
+
+use super::{Monotonic, Time};
+
+/// Manually driven frame production
+///
+/// In stepping mode a compositor only produces a new frame whenever one was requested through
+/// [`FrameStepper::step`], instead of following the refresh cycle of its backend. Every frame gets a
+/// deterministic timestamp advancing by exactly one refresh interval, which should be used in place of the
+/// real clock for frame callbacks and presentation feedback. Together this allows integration tests to
+/// advance clients and animations frame by frame and to compare the resulting output against reference images.
+///
+/// The stepper is a cheap handle and can be cloned and sent to other threads, e.g. to the task reading
+/// commands of a test harness. Use [`FrameStepper::set_waker`] to wake up the event loop of the compositor
+/// once new frames are requested.
+///
+/// ```
+/// use smithay::utils::FrameStepper;
+///
+/// let stepper = FrameStepper::new(60_000);
+/// assert!(stepper.next_frame().is_none());
+///
+/// stepper.step(2);
+/// let first = stepper.next_frame().unwrap();
+/// let second = stepper.next_frame().unwrap();
+/// assert_eq!(second.sequence, first.sequence + 1);
+/// assert!(stepper.next_frame().is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameStepper {
+    inner: Arc<Mutex<StepperState>>,
+}
+
+#[derive(Debug)]
+struct StepperState {
+    interval: Duration,
+    start: Duration,
+    sequence: u64,
+    pending: u64,
+    waker: Option<Ping>,
+}
+
+/// A frame produced by a [`FrameStepper`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SteppedFrame {
+    /// Sequence number of the frame, starting at 1 for the first frame
+    pub sequence: u64,
+    /// Deterministic timestamp of the frame
+    pub time: Time<Monotonic>,
+    /// Refresh interval of the stepper
+    pub refresh: Duration,
+}
+
+impl FrameStepper {
+    /// Create a new stepper for the given refresh rate in millihertz
+    ///
+    /// The timestamps start at zero and no frames are requested initially.
+    pub fn new(refresh: i32) -> Self {
+        let interval = Duration::from_secs_f64(1_000f64 / refresh.max(1) as f64);
+        FrameStepper {
+            inner: Arc::new(Mutex::new(StepperState {
+                interval,
+                start: Duration::ZERO,
+                sequence: 0,
+                pending: 0,
+                waker: None,
+            })),
+        }
+    }
+
+    /// Set the timestamp of the frame preceding the first frame
+    ///
+    /// Only has an effect before the first frame was produced.
+    pub fn with_start_time(self, start: Time<Monotonic>) -> Self {
+        {
+            let mut state = self.inner.lock().unwrap();
+            if state.sequence == 0 {
+                state.start = start.into();
+            }
+        }
+        self
+    }
+
+    /// Set a [`Ping`] to be triggered whenever new frames are requested
+    pub fn set_waker(&self, ping: Ping) {
+        self.inner.lock().unwrap().waker = Some(ping);
+    }
+
+    /// Request `frames` additional frames to be produced
+    pub fn step(&self, frames: u64) {
+        let mut state = self.inner.lock().unwrap();
+        state.pending = state.pending.saturating_add(frames);
+        if frames > 0 {
+            if let Some(waker) = state.waker.as_ref() {
+                waker.ping();
+            }
+        }
+    }
+
+    /// Returns the number of requested frames not yet produced
+    pub fn pending(&self) -> u64 {
+        self.inner.lock().unwrap().pending
+    }
+
+    /// Returns the refresh interval of the stepper
+    pub fn refresh_interval(&self) -> Duration {
+        self.inner.lock().unwrap().interval
+    }
+
+    /// Returns the sequence number of the last produced frame, `0` if no frame was produced yet
+    pub fn sequence(&self) -> u64 {
+        self.inner.lock().unwrap().sequence
+    }
+
+    /// Returns the timestamp of the last produced frame
+    ///
+    /// This should be used in place of the real clock while stepping.
+    pub fn now(&self) -> Time<Monotonic> {
+        let state = self.inner.lock().unwrap();
+        Time::from(state.time_of(state.sequence))
+    }
+
+    /// Consume one requested frame
+    ///
+    /// Returns `None` if no frame is pending, in which case no frame should be rendered.
+    pub fn next_frame(&self) -> Option<SteppedFrame> {
+        let mut state = self.inner.lock().unwrap();
+        if state.pending == 0 {
+            return None;
+        }
+        state.pending -= 1;
+        state.sequence += 1;
+        Some(SteppedFrame {
+            sequence: state.sequence,
+            time: Time::from(state.time_of(state.sequence)),
+            refresh: state.interval,
+        })
+    }
+}
+
+impl StepperState {
+    fn time_of(&self, sequence: u64) -> Duration {
+        self.start + self.interval.saturating_mul(sequence.min(u32::MAX as u64) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FrameStepper;
+    use crate::utils::{Monotonic, Time};
+
+    #[test]
+    fn deterministic_timestamps() {
+        let stepper = FrameStepper::new(50_000).with_start_time(Time::from(Duration::from_secs(1)));
+        assert_eq!(stepper.refresh_interval(), Duration::from_millis(20));
+        assert_eq!(stepper.now(), Time::<Monotonic>::from(Duration::from_secs(1)));
+        assert!(stepper.next_frame().is_none());
+
+        let (ping, _source) = calloop::ping::make_ping().unwrap();
+        stepper.set_waker(ping);
+        stepper.clone().step(3);
+        assert_eq!(stepper.pending(), 3);
+
+        let frames = std::iter::from_fn(|| stepper.next_frame()).collect::<Vec<_>>();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].sequence, 1);
+        assert_eq!(frames[0].time, Time::from(Duration::from_millis(1020)));
+        assert_eq!(frames[2].time, Time::from(Duration::from_millis(1060)));
+        assert_eq!(stepper.now(), frames[2].time);
+        assert_eq!(stepper.pending(), 0);
+        assert_eq!(stepper.sequence(), 3);
+    }
+}
@@ -1,20 +1,55 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 crate::utils::ids::id_gen!(hooks_id);
 
 /// Unique hook identifier used to unregister commit/descruction hooks
+///
+/// An id stays unique as long as its hook is registered, so removing a hook by its id never
+/// removes a hook registered later on.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct HookId(usize);
 
+/// Priority class of a commit hook
+///
+/// Hooks are invoked in ascending order of their priority class and in the order they were
+/// registered within the same class.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HookPriority {
+    /// Invoked before any hooks of smithay's protocol implementations
+    ///
+    /// Pre-commit hooks of this class see the pending state as requested by the client,
+    /// before it is validated.
+    Early,
+    /// Used by smithay's protocol implementations
+    ///
+    /// Pre-commit hooks of this class validate the pending state and post protocol errors,
+    /// post-commit hooks apply the state of protocol extensions, like the acked configure of a shell surface.
+    Protocol,
+    /// Default class for hooks of the compositor
+    ///
+    /// Pre-commit hooks of this class see the validated pending state, e.g. the acquire point set through
+    /// the syncobj protocol, and are the place to add blockers.
+    #[default]
+    Normal,
+    /// Invoked after all other hooks
+    Late,
+}
+
 pub(crate) struct Hook<T: ?Sized> {
     pub id: HookId,
+    pub priority: HookPriority,
     pub cb: Arc<T>,
+    removed: Arc<AtomicBool>,
 }
 
 impl<T: ?Sized> std::fmt::Debug for Hook<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Hook")
             .field("id", &self.id)
+            .field("priority", &self.priority)
             .finish_non_exhaustive()
     }
 }
@@ -23,22 +58,145 @@ impl<T: ?Sized> Clone for Hook<T> {
     fn clone(&self) -> Self {
         Self {
             id: self.id,
+            priority: self.priority,
             cb: self.cb.clone(),
+            removed: self.removed.clone(),
         }
     }
 }
 
 impl<T: ?Sized> Hook<T> {
     pub fn new(cb: Arc<T>) -> Self {
+        Self::with_priority(cb, HookPriority::Normal)
+    }
+
+    pub fn with_priority(cb: Arc<T>, priority: HookPriority) -> Self {
         Self {
             id: HookId(hooks_id::next()),
+            priority,
             cb,
+            removed: Arc::new(AtomicBool::new(false)),
         }
     }
+
+    /// Returns if the hook was removed from its [`HookList`], e.g. by a previously invoked hook
+    pub fn is_removed(&self) -> bool {
+        self.removed.load(Ordering::Acquire)
+    }
+
+    fn release(&self) {
+        self.removed.store(true, Ordering::Release);
+        hooks_id::remove(self.id.0);
+    }
+}
+
+/// Registered hooks, ordered by their priority class
+///
+/// Hooks are cloned to invoke them without holding a lock, the id of a hook is only released
+/// once it is removed from the list or the list is dropped.
+pub(crate) struct HookList<T: ?Sized> {
+    hooks: Vec<Hook<T>>,
+}
+
+impl<T: ?Sized> Default for HookList<T> {
+    fn default() -> Self {
+        Self { hooks: Vec::new() }
+    }
 }
 
-impl<T: ?Sized> Drop for Hook<T> {
+impl<T: ?Sized> std::fmt::Debug for HookList<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(&self.hooks).finish()
+    }
+}
+
+impl<T: ?Sized> HookList<T> {
+    /// Insert the hook after all hooks of the same or a lower priority class
+    pub fn insert(&mut self, hook: Hook<T>) -> HookId {
+        let id = hook.id;
+        let pos = self.hooks.partition_point(|h| h.priority <= hook.priority);
+        self.hooks.insert(pos, hook);
+        id
+    }
+
+    pub fn remove(&mut self, id: HookId) {
+        if let Some(pos) = self.hooks.iter().position(|hook| hook.id == id) {
+            self.hooks.remove(pos).release();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    /// Clones the hooks to invoke them after releasing the lock of the list
+    ///
+    /// Hooks removed in the meantime have to be skipped using [`Hook::is_removed`].
+    pub fn snapshot(&self) -> Vec<Hook<T>> {
+        self.hooks.clone()
+    }
+}
+
+impl<T: ?Sized> Drop for HookList<T> {
     fn drop(&mut self) {
-        hooks_id::remove(self.id.0);
+        for hook in &self.hooks {
+            hook.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Hook, HookList, HookPriority};
+
+    type TestHook = Hook<dyn Fn() -> u32>;
+
+    fn hook(value: u32, priority: HookPriority) -> TestHook {
+        Hook::with_priority(Arc::new(move || value), priority)
+    }
+
+    #[test]
+    fn priority_order() {
+        let mut hooks: HookList<dyn Fn() -> u32> = HookList::default();
+        hooks.insert(hook(2, HookPriority::Normal));
+        hooks.insert(hook(4, HookPriority::Late));
+        hooks.insert(hook(1, HookPriority::Protocol));
+        hooks.insert(hook(3, HookPriority::Normal));
+        hooks.insert(hook(0, HookPriority::Early));
+        assert_eq!(
+            hooks
+                .snapshot()
+                .iter()
+                .map(|hook| (hook.cb)())
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn removal() {
+        let mut hooks: HookList<dyn Fn() -> u32> = HookList::default();
+        let id = hooks.insert(hook(0, HookPriority::Normal));
+        hooks.insert(hook(1, HookPriority::Normal));
+
+        // dropping a snapshot does not release the id of a registered hook
+        let snapshot = hooks.snapshot();
+        drop(hooks.snapshot());
+        assert!(snapshot.iter().all(|hook| !hook.is_removed()));
+
+        // hooks removed while a snapshot is invoked are skipped
+        hooks.remove(id);
+        assert_eq!(hooks.len(), 1);
+        assert!(snapshot[0].is_removed());
+        assert!(!snapshot[1].is_removed());
+        // the id was released
+        assert!(!super::hooks_id::remove(id.0));
+
+        let remaining = snapshot[1].id;
+        drop(hooks);
+        assert!(snapshot[1].is_removed());
+        assert!(!super::hooks_id::remove(remaining.0));
     }
 }
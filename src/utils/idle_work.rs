@@ -0,0 +1,294 @@
+use std::{cell::RefCell, collections::VecDeque, fmt, rc::Rc, time::Duration};
+
+use calloop::{
+    ping::{make_ping, Ping},
+    timer::{TimeoutAction, Timer},
+    LoopHandle, RegistrationToken,
+};
+
+use super::{Clock, Monotonic, Time};
+
+/// Queue of deferred work executed while the event loop is idle
+///
+/// Expensive cleanup, like destroying a batch of textures or unmapping shm pools of a disconnected client,
+/// can cause frame-time spikes when done all at once. Instead such work can be scheduled on this queue,
+/// which executes it in small slices limited by [`IdleWorkQueue::with_budget`], whenever the event loop is
+/// idle and the next frame deadline set by [`IdleWorkQueue::set_deadline`] is not imminent.
+///
+/// Scheduled work can be cancelled using the returned [`IdleWorkToken`], e.g. if a resource is re-used
+/// before it was cleaned up. The queue is a cheap handle and can be cloned, but is bound to the thread
+/// of the event loop.
+///
+/// ```no_run
+/// use smithay::reexports::calloop::EventLoop;
+/// use smithay::utils::IdleWorkQueue;
+///
+/// struct State { cache: Vec<Vec<u8>> }
+///
+/// let event_loop = EventLoop::<State>::try_new().unwrap();
+/// let queue = IdleWorkQueue::<State>::new();
+/// queue.register(&event_loop.handle()).unwrap();
+///
+/// let token = queue.schedule(|state: &mut State| state.cache.clear());
+/// // ...
+/// queue.cancel(token);
+/// ```
+pub struct IdleWorkQueue<D> {
+    inner: Rc<RefCell<Inner<D>>>,
+}
+
+type Work<D> = Box<dyn FnOnce(&mut D)>;
+
+struct Inner<D> {
+    items: VecDeque<(IdleWorkToken, Work<D>)>,
+    next_token: u64,
+    budget: Duration,
+    margin: Duration,
+    deadline: Option<Time<Monotonic>>,
+    waker: Option<Ping>,
+}
+
+/// Token of work scheduled on an [`IdleWorkQueue`], used to cancel it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdleWorkToken(u64);
+
+impl<D> fmt::Debug for IdleWorkQueue<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("IdleWorkQueue")
+            .field("items", &inner.items.len())
+            .field("budget", &inner.budget)
+            .field("margin", &inner.margin)
+            .field("deadline", &inner.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D> Clone for IdleWorkQueue<D> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<D> Default for IdleWorkQueue<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> IdleWorkQueue<D> {
+    /// Create a new queue with a budget of 1ms per slice and a margin of 4ms to the frame deadline
+    pub fn new() -> Self {
+        IdleWorkQueue {
+            inner: Rc::new(RefCell::new(Inner {
+                items: VecDeque::new(),
+                next_token: 0,
+                budget: Duration::from_millis(1),
+                margin: Duration::from_millis(4),
+                deadline: None,
+                waker: None,
+            })),
+        }
+    }
+
+    /// Set the time a single slice of work may take
+    ///
+    /// At least one item is executed per slice, regardless of the budget.
+    pub fn with_budget(self, budget: Duration) -> Self {
+        self.inner.borrow_mut().budget = budget;
+        self
+    }
+
+    /// Set the minimal time left until the frame deadline for work to be executed
+    pub fn with_margin(self, margin: Duration) -> Self {
+        self.inner.borrow_mut().margin = margin;
+        self
+    }
+
+    /// Set the deadline of the next frame
+    ///
+    /// No work is executed while the deadline is less than the configured margin away.
+    /// Usually this is updated with the estimated start of rendering every time a frame is scheduled.
+    pub fn set_deadline(&self, deadline: Option<Time<Monotonic>>) {
+        self.inner.borrow_mut().deadline = deadline;
+    }
+
+    /// Schedule work to be executed once the loop is idle
+    pub fn schedule<F>(&self, work: F) -> IdleWorkToken
+    where
+        F: FnOnce(&mut D) + 'static,
+    {
+        let mut inner = self.inner.borrow_mut();
+        let token = IdleWorkToken(inner.next_token);
+        inner.next_token += 1;
+        inner.items.push_back((token, Box::new(work)));
+        if inner.items.len() == 1 {
+            if let Some(waker) = inner.waker.as_ref() {
+                waker.ping();
+            }
+        }
+        token
+    }
+
+    /// Cancel scheduled work
+    ///
+    /// Returns `false` if the work was already executed or cancelled.
+    pub fn cancel(&self, token: IdleWorkToken) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        let len = inner.items.len();
+        inner.items.retain(|(t, _)| *t != token);
+        inner.items.len() != len
+    }
+
+    /// Returns the number of items waiting to be executed
+    pub fn len(&self) -> usize {
+        self.inner.borrow().items.len()
+    }
+
+    /// Returns `true` if no work is scheduled
+    pub fn is_empty(&self) -> bool {
+        self.inner.borrow().items.is_empty()
+    }
+
+    /// Returns the time until the next slice may be executed, `None` if it may be executed right away
+    fn blocked_for(&self, now: Time<Monotonic>) -> Option<Duration> {
+        let inner = self.inner.borrow();
+        let deadline = inner.deadline?;
+        let remaining = Time::elapsed(&now, deadline);
+        if remaining > inner.margin || now > deadline {
+            None
+        } else {
+            Some(remaining)
+        }
+    }
+
+    /// Execute a single slice of work, unless the frame deadline is imminent
+    ///
+    /// Returns the number of executed items.
+    pub fn run(&self, state: &mut D) -> usize {
+        let clock = Clock::<Monotonic>::new();
+        let start = clock.now();
+        if self.blocked_for(start).is_some() {
+            return 0;
+        }
+
+        let budget = self.inner.borrow().budget;
+        let mut executed = 0;
+        loop {
+            // don't hold the borrow while executing, so work may schedule more work
+            let Some((_, work)) = self.inner.borrow_mut().items.pop_front() else {
+                break;
+            };
+            work(state);
+            executed += 1;
+            if Time::elapsed(&start, clock.now()) >= budget {
+                break;
+            }
+        }
+        executed
+    }
+
+    /// Execute all scheduled work immediately, e.g. before shutting down
+    pub fn flush(&self, state: &mut D) {
+        loop {
+            let Some((_, work)) = self.inner.borrow_mut().items.pop_front() else {
+                break;
+            };
+            work(state);
+        }
+    }
+}
+
+impl<D: 'static> IdleWorkQueue<D> {
+    /// Register the queue with an event loop
+    ///
+    /// Work is executed in idle callbacks of the loop, one slice per loop iteration.
+    /// If the frame deadline is imminent, execution is postponed until it passed.
+    pub fn register(&self, handle: &LoopHandle<'static, D>) -> Result<RegistrationToken, calloop::Error> {
+        let (ping, source) = make_ping()?;
+        let queue = self.clone();
+        let loop_handle = handle.clone();
+        let token = handle
+            .insert_source(source, move |_, _, _| {
+                let queue = queue.clone();
+                let timer_handle = loop_handle.clone();
+                loop_handle.insert_idle(move |state| queue.dispatch_idle(state, &timer_handle));
+            })
+            .map_err(|err| err.error)?;
+
+        let mut inner = self.inner.borrow_mut();
+        if !inner.items.is_empty() {
+            ping.ping();
+        }
+        inner.waker = Some(ping);
+        Ok(token)
+    }
+
+    fn dispatch_idle(&self, state: &mut D, handle: &LoopHandle<'static, D>) {
+        let now = Clock::<Monotonic>::new().now();
+        if let Some(remaining) = self.blocked_for(now) {
+            // try again once the deadline passed
+            let queue = self.clone();
+            let res = handle.insert_source(Timer::from_duration(remaining), move |_, _, _| {
+                queue.wake();
+                TimeoutAction::Drop
+            });
+            if let Err(err) = res {
+                tracing::warn!(?err, "Failed to postpone idle work");
+            }
+            return;
+        }
+
+        self.run(state);
+        if !self.is_empty() {
+            self.wake();
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.inner.borrow().waker.as_ref() {
+            waker.ping();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::IdleWorkQueue;
+    use crate::utils::{Clock, Monotonic};
+
+    #[test]
+    fn run_and_cancel() {
+        let queue = IdleWorkQueue::<Vec<u32>>::new().with_budget(Duration::ZERO);
+        queue.schedule(|state| state.push(1));
+        let cancelled = queue.schedule(|state| state.push(2));
+        let inner_queue = queue.clone();
+        queue.schedule(move |state| {
+            state.push(3);
+            inner_queue.schedule(|state| state.push(4));
+        });
+        assert_eq!(queue.len(), 3);
+        assert!(queue.cancel(cancelled));
+        assert!(!queue.cancel(cancelled));
+
+        let mut state = Vec::new();
+        // a zero budget executes a single item per slice
+        assert_eq!(queue.run(&mut state), 1);
+        assert_eq!(state, [1]);
+
+        // nothing is executed right before the frame deadline
+        let now = Clock::<Monotonic>::new().now();
+        queue.set_deadline(Some(now + Duration::from_secs(1)));
+        let queue = queue.with_margin(Duration::from_secs(10));
+        assert_eq!(queue.run(&mut state), 0);
+
+        queue.set_deadline(None);
+        queue.flush(&mut state);
+        assert_eq!(state, [1, 3, 4]);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    EventIterator, EventSource, InsertError, LoopHandle, Poll, PostAction, Readiness, RegistrationToken,
+    Token, TokenFactory,
+};
+
+/// Statistics about the iterations of an event loop, see [`LoopMetrics`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoopStats {
+    /// Number of finished iterations
+    pub iterations: u64,
+    /// Total time spent waiting for events
+    pub idle: Duration,
+    /// Total time spent between waking up and waiting for events again
+    pub busy: Duration,
+    /// Time the last finished iteration waited for events
+    pub last_idle: Duration,
+    /// Time the last finished iteration was busy
+    pub last_busy: Duration,
+    /// Longest time a single iteration was busy
+    pub max_busy: Duration,
+}
+
+impl LoopStats {
+    /// Fraction of the time the event loop was busy, between `0.0` and `1.0`
+    pub fn load(&self) -> f64 {
+        let total = self.idle + self.busy;
+        if total.is_zero() {
+            0.0
+        } else {
+            self.busy.as_secs_f64() / total.as_secs_f64()
+        }
+    }
+
+    fn record(&mut self, idle: Duration, busy: Duration) {
+        self.iterations += 1;
+        self.idle += idle;
+        self.busy += busy;
+        self.last_idle = idle;
+        self.last_busy = busy;
+        self.max_busy = self.max_busy.max(busy);
+    }
+}
+
+/// Measures how long the iterations of an event loop wait for events and how long they are busy
+///
+/// An iteration is busy from waking up until it waits for events again, which includes dispatching
+/// all event sources and anything done between calls to [`EventLoop::dispatch`](calloop::EventLoop::dispatch),
+/// like rendering. A high load or long busy iterations point to a cpu-bound compositor, while frames
+/// missing their deadline despite a low load are usually gpu-bound
+/// (see [`GlesRenderer::set_gpu_timing`](crate::backend::renderer::gles::GlesRenderer::set_gpu_timing)).
+///
+/// Measuring does not allocate. If smithay is built with the `profile-with-tracy` feature
+/// and a tracy client is running, every iteration is additionally reported as plots to tracy.
+///
+/// ```no_run
+/// use smithay::reexports::calloop::EventLoop;
+/// use smithay::utils::LoopMetrics;
+///
+/// let mut event_loop = EventLoop::<()>::try_new().unwrap();
+/// let metrics = LoopMetrics::new();
+/// metrics.register(&event_loop.handle()).unwrap();
+///
+/// loop {
+///     event_loop.dispatch(None, &mut ()).unwrap();
+///     if metrics.stats().max_busy.as_millis() > 16 {
+///         // ...
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LoopMetrics {
+    stats: Rc<Cell<LoopStats>>,
+}
+
+impl LoopMetrics {
+    /// Create new metrics, which need to be registered with an event loop
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start measuring the iterations of an event loop
+    ///
+    /// The first iteration is recorded once the loop waited for events after registering.
+    pub fn register<D>(&self, handle: &LoopHandle<'_, D>) -> Result<RegistrationToken, InsertError<()>> {
+        let source = LoopMetricsSource {
+            stats: self.stats.clone(),
+            sleeping_since: None,
+            woken_up: None,
+        };
+        handle
+            .insert_source(source, |_, _, _| {})
+            .map_err(|err| InsertError {
+                inserted: (),
+                error: err.error,
+            })
+    }
+
+    /// Returns the statistics of all iterations since registering or the last [`LoopMetrics::reset`]
+    pub fn stats(&self) -> LoopStats {
+        self.stats.get()
+    }
+
+    /// Reset the statistics
+    pub fn reset(&self) {
+        self.stats.set(LoopStats::default());
+    }
+}
+
+#[derive(Debug)]
+struct LoopMetricsSource {
+    stats: Rc<Cell<LoopStats>>,
+    sleeping_since: Option<Instant>,
+    woken_up: Option<(Instant, Duration)>,
+}
+
+impl LoopMetricsSource {
+    fn iteration_finished(&mut self, idle: Duration, busy: Duration) {
+        let mut stats = self.stats.get();
+        stats.record(idle, busy);
+        self.stats.set(stats);
+
+        #[cfg(feature = "profile-with-tracy")]
+        if let Some(client) = profiling::tracy_client::Client::running() {
+            client.plot(
+                profiling::tracy_client::plot_name!("event loop idle (ms)"),
+                idle.as_secs_f64() * 1000.0,
+            );
+            client.plot(
+                profiling::tracy_client::plot_name!("event loop busy (ms)"),
+                busy.as_secs_f64() * 1000.0,
+            );
+        }
+    }
+}
+
+impl EventSource for LoopMetricsSource {
+    type Event = ();
+    type Metadata = ();
+    type Ret = ();
+    type Error = std::convert::Infallible;
+
+    const NEEDS_EXTRA_LIFECYCLE_EVENTS: bool = true;
+
+    fn before_sleep(&mut self) -> calloop::Result<Option<(Readiness, Token)>> {
+        let now = Instant::now();
+        if let Some((woken_up, idle)) = self.woken_up.take() {
+            self.iteration_finished(idle, now.saturating_duration_since(woken_up));
+        }
+        self.sleeping_since = Some(now);
+        Ok(None)
+    }
+
+    fn before_handle_events(&mut self, _events: EventIterator<'_>) {
+        let now = Instant::now();
+        if let Some(sleeping_since) = self.sleeping_since.take() {
+            self.woken_up = Some((now, now.saturating_duration_since(sleeping_since)));
+        }
+    }
+
+    fn process_events<F>(
+        &mut self,
+        _readiness: Readiness,
+        _token: Token,
+        _callback: F,
+    ) -> Result<PostAction, Self::Error>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        Ok(PostAction::Continue)
+    }
+
+    fn register(&mut self, _poll: &mut Poll, _token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        Ok(())
+    }
+
+    fn reregister(&mut self, _poll: &mut Poll, _token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        Ok(())
+    }
+
+    fn unregister(&mut self, _poll: &mut Poll) -> calloop::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use calloop::{
+        timer::{TimeoutAction, Timer},
+        EventLoop,
+    };
+
+    use super::{LoopMetrics, LoopStats};
+
+    #[test]
+    fn iterations_are_measured() {
+        let mut event_loop = EventLoop::<()>::try_new().unwrap();
+        let metrics = LoopMetrics::new();
+        metrics.register(&event_loop.handle()).unwrap();
+
+        // the timer is idle time, the callback busy time
+        event_loop
+            .handle()
+            .insert_source(Timer::from_duration(Duration::from_millis(20)), |_, _, _| {
+                std::thread::sleep(Duration::from_millis(10));
+                TimeoutAction::Drop
+            })
+            .unwrap();
+        event_loop.dispatch(None, &mut ()).unwrap();
+        assert_eq!(metrics.stats().iterations, 0);
+
+        // the iteration finishes once the loop waits again
+        event_loop.dispatch(Duration::ZERO, &mut ()).unwrap();
+        let stats = metrics.stats();
+        assert_eq!(stats.iterations, 1);
+        assert!(stats.last_idle >= Duration::from_millis(15));
+        assert!(stats.last_busy >= Duration::from_millis(10));
+        assert_eq!(stats.max_busy, stats.last_busy);
+        assert!(stats.load() > 0.0 && stats.load() < 1.0);
+
+        metrics.reset();
+        assert_eq!(metrics.stats(), LoopStats::default());
+    }
+
+    #[test]
+    fn load() {
+        let mut stats = LoopStats::default();
+        assert_eq!(stats.load(), 0.0);
+        stats.record(Duration::from_millis(30), Duration::from_millis(10));
+        stats.record(Duration::from_millis(10), Duration::from_millis(30));
+        assert_eq!(stats.iterations, 2);
+        assert_eq!(stats.max_busy, Duration::from_millis(30));
+        assert_eq!(stats.last_idle, Duration::from_millis(10));
+        assert_eq!(stats.load(), 0.5);
+    }
+}
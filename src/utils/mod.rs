@@ -7,6 +7,9 @@ pub mod signaling;
 pub mod x11rb;
 
 pub(crate) mod ids;
+
+#[cfg(any(feature = "async_tokio", feature = "async_std"))]
+pub mod async_bridge;
 pub mod user_data;
 
 pub(crate) mod alive_tracker;
@@ -30,6 +33,19 @@ pub use serial::*;
 mod clock;
 pub use clock::*;
 
+pub mod positioner;
+
+mod frame_stepper;
+pub use frame_stepper::{FrameStepper, SteppedFrame};
+
+mod idle_work;
+pub use idle_work::{IdleWorkQueue, IdleWorkToken};
+
+mod loop_metrics;
+pub use loop_metrics::{LoopMetrics, LoopStats};
+
+#[cfg(feature = "wayland_frontend")]
+pub mod compositor_handle;
 #[cfg(feature = "wayland_frontend")]
 pub(crate) mod hook;
 #[cfg(feature = "wayland_frontend")]
@@ -0,0 +1,522 @@
+//! Positioning of popups relative to an anchor rectangle
+//!
+//! This implements the placement rules of the `xdg_positioner` protocol object independently of
+//! any wayland objects, so compositors can use them for their own popups, e.g. menus or tooltips
+//! drawn by the compositor itself. The `xdg_shell` implementation uses the same rules through
+//! [`PositionerState`](crate::wayland::shell::xdg::PositionerState).
+//!
+//! ```
+//! use smithay::utils::{
+//!     positioner::{solve, ConstraintAdjustment, Edges, Positioner},
+//!     Rectangle,
+//! };
+//!
+//! let positioner = Positioner {
+//!     size: (200, 300).into(),
+//!     anchor_rect: Rectangle::new((950, 10).into(), (40, 20).into()),
+//!     anchor: Edges::BOTTOM | Edges::RIGHT,
+//!     gravity: Edges::BOTTOM | Edges::RIGHT,
+//!     constraint_adjustment: ConstraintAdjustment::FLIP_X | ConstraintAdjustment::SLIDE_Y,
+//!     ..Default::default()
+//! };
+//!
+//! // the popup does not fit to the right, so it opens to the left of the anchor rectangle
+//! let target = Rectangle::from_size((1000, 800).into());
+//! assert_eq!(solve(&positioner, target), Rectangle::new((750, 30).into(), (200, 300).into()));
+//! ```
+
+use std::cmp::min;
+
+use super::{Logical, Point, Rectangle, Size};
+
+bitflags::bitflags! {
+    /// Edges of a rectangle, used for the anchor and the gravity of a [`Positioner`]
+    ///
+    /// An empty set or a set containing opposite edges refers to the center on that axis.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Edges: u32 {
+        /// The top edge
+        const TOP = 1;
+        /// The bottom edge
+        const BOTTOM = 2;
+        /// The left edge
+        const LEFT = 4;
+        /// The right edge
+        const RIGHT = 8;
+    }
+}
+
+impl Edges {
+    fn flipped_x(self) -> Self {
+        let mut flipped = self - (Edges::LEFT | Edges::RIGHT);
+        flipped.set(Edges::LEFT, self.contains(Edges::RIGHT));
+        flipped.set(Edges::RIGHT, self.contains(Edges::LEFT));
+        flipped
+    }
+
+    fn flipped_y(self) -> Self {
+        let mut flipped = self - (Edges::TOP | Edges::BOTTOM);
+        flipped.set(Edges::TOP, self.contains(Edges::BOTTOM));
+        flipped.set(Edges::BOTTOM, self.contains(Edges::TOP));
+        flipped
+    }
+
+    fn is_left(self) -> bool {
+        self.contains(Edges::LEFT) && !self.contains(Edges::RIGHT)
+    }
+
+    fn is_right(self) -> bool {
+        self.contains(Edges::RIGHT) && !self.contains(Edges::LEFT)
+    }
+
+    fn is_top(self) -> bool {
+        self.contains(Edges::TOP) && !self.contains(Edges::BOTTOM)
+    }
+
+    fn is_bottom(self) -> bool {
+        self.contains(Edges::BOTTOM) && !self.contains(Edges::TOP)
+    }
+}
+
+bitflags::bitflags! {
+    /// Adjustments applied by [`solve`], if a popup does not fit into the target rectangle
+    ///
+    /// The values match the `constraint_adjustment` enum of the `xdg_positioner` protocol.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct ConstraintAdjustment: u32 {
+        /// Move the popup along the x axis until it is no longer constrained
+        const SLIDE_X = 1;
+        /// Move the popup along the y axis until it is no longer constrained
+        const SLIDE_Y = 2;
+        /// Invert the anchor and gravity on the x axis
+        const FLIP_X = 4;
+        /// Invert the anchor and gravity on the y axis
+        const FLIP_Y = 8;
+        /// Shrink the popup horizontally
+        const RESIZE_X = 16;
+        /// Shrink the popup vertically
+        const RESIZE_Y = 32;
+    }
+}
+
+/// Rules for placing a popup, see the `xdg_positioner` protocol for the details
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Positioner {
+    /// Size of the popup
+    pub size: Size<i32, Logical>,
+    /// Rectangle the popup is placed relative to, e.g. the menu entry opening a submenu
+    pub anchor_rect: Rectangle<i32, Logical>,
+    /// Point on the anchor rectangle the popup is placed at
+    pub anchor: Edges,
+    /// Direction the popup extends to, starting at the anchor point
+    pub gravity: Edges,
+    /// Offset of the popup from the anchor point
+    pub offset: Point<i32, Logical>,
+    /// Adjustments applied by [`solve`], if the popup does not fit
+    pub constraint_adjustment: ConstraintAdjustment,
+}
+
+impl Positioner {
+    /// Returns the geometry of the popup without considering any constraints
+    ///
+    /// The geometry is in the coordinate space of the anchor rectangle.
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        // the offset is applied to the anchor point, see xdg_positioner.set_offset
+        let mut geometry = Rectangle::new(self.offset, self.size);
+        let anchor_rect = self.anchor_rect;
+
+        // a corner anchor places the anchor point at that corner, an edge anchor centers it on that
+        // edge, and no anchor centers it within the anchor rectangle
+        geometry.loc.y += if self.anchor.is_top() {
+            anchor_rect.loc.y
+        } else if self.anchor.is_bottom() {
+            anchor_rect.loc.y + anchor_rect.size.h
+        } else {
+            anchor_rect.loc.y + anchor_rect.size.h / 2
+        };
+        geometry.loc.x += if self.anchor.is_left() {
+            anchor_rect.loc.x
+        } else if self.anchor.is_right() {
+            anchor_rect.loc.x + anchor_rect.size.w
+        } else {
+            anchor_rect.loc.x + anchor_rect.size.w / 2
+        };
+
+        // the popup extends towards the gravity, or is centered over the anchor point on any axis
+        // without gravity
+        if self.gravity.is_top() {
+            geometry.loc.y -= geometry.size.h;
+        } else if !self.gravity.is_bottom() {
+            geometry.loc.y -= geometry.size.h / 2;
+        }
+        if self.gravity.is_left() {
+            geometry.loc.x -= geometry.size.w;
+        } else if !self.gravity.is_right() {
+            geometry.loc.x -= geometry.size.w / 2;
+        }
+
+        geometry
+    }
+}
+
+/// Returns the geometry of a popup, after trying to fit it into `target`
+///
+/// `target` is in the same coordinate space as the anchor rectangle, e.g. the area of the output
+/// relative to the parent window geometry. The [`ConstraintAdjustment`]s of the positioner are tried
+/// in the order defined by the protocol: flip, slide and resize. A flip, that does not remove
+/// the constraint on its axis, is not applied. The axes are adjusted independently of each other.
+pub fn solve(positioner: &Positioner, target: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+    let adjustment = positioner.constraint_adjustment;
+    let mut positioner = *positioner;
+    let mut geo = positioner.geometry();
+    let (mut off_left, mut off_right, mut off_top, mut off_bottom) = offsets(target, geo);
+
+    if (off_left > 0 || off_right > 0) && adjustment.contains(ConstraintAdjustment::FLIP_X) {
+        let mut flipped = positioner;
+        flipped.anchor = flipped.anchor.flipped_x();
+        flipped.gravity = flipped.gravity.flipped_x();
+        let flipped_geo = flipped.geometry();
+        let (new_off_left, new_off_right, _, _) = offsets(target, flipped_geo);
+
+        if new_off_left <= 0 && new_off_right <= 0 {
+            positioner = flipped;
+            geo = flipped_geo;
+            off_left = 0;
+            off_right = 0;
+        }
+    }
+
+    if (off_top > 0 || off_bottom > 0) && adjustment.contains(ConstraintAdjustment::FLIP_Y) {
+        let mut flipped = positioner;
+        flipped.anchor = flipped.anchor.flipped_y();
+        flipped.gravity = flipped.gravity.flipped_y();
+        let flipped_geo = flipped.geometry();
+        let (_, _, new_off_top, new_off_bottom) = offsets(target, flipped_geo);
+
+        if new_off_top <= 0 && new_off_bottom <= 0 {
+            geo = flipped_geo;
+            off_top = 0;
+            off_bottom = 0;
+        }
+    }
+
+    // slides prefer to show the top-left corner of the popup, so a resize can shrink it afterwards
+    if (off_left > 0 || off_right > 0) && adjustment.contains(ConstraintAdjustment::SLIDE_X) {
+        if off_left > 0 {
+            geo.loc.x += off_left;
+        } else {
+            geo.loc.x -= min(off_right, -off_left);
+        }
+        (_, off_right, _, _) = offsets(target, geo);
+    }
+
+    if (off_top > 0 || off_bottom > 0) && adjustment.contains(ConstraintAdjustment::SLIDE_Y) {
+        if off_top > 0 {
+            geo.loc.y += off_top;
+        } else {
+            geo.loc.y -= min(off_bottom, -off_top);
+        }
+        (_, _, _, off_bottom) = offsets(target, geo);
+    }
+
+    // resizing only helps, if the popup starts before the right or bottom edge of the target
+    if off_right > 0 && off_right < geo.size.w && adjustment.contains(ConstraintAdjustment::RESIZE_X) {
+        geo.size.w -= off_right;
+    }
+
+    if off_bottom > 0 && off_bottom < geo.size.h && adjustment.contains(ConstraintAdjustment::RESIZE_Y) {
+        geo.size.h -= off_bottom;
+    }
+
+    geo
+}
+
+// distances the popup extends beyond each edge of the target, negative if it is inside
+fn offsets(target: Rectangle<i32, Logical>, popup: Rectangle<i32, Logical>) -> (i32, i32, i32, i32) {
+    let off_left = target.loc.x - popup.loc.x;
+    let off_right = (popup.loc.x + popup.size.w) - (target.loc.x + target.size.w);
+    let off_top = target.loc.y - popup.loc.y;
+    let off_bottom = (popup.loc.y + popup.size.h) - (target.loc.y + target.size.h);
+    (off_left, off_right, off_top, off_bottom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> Rectangle<i32, Logical> {
+        Rectangle::from_size((100, 100).into())
+    }
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::new((x, y).into(), (w, h).into())
+    }
+
+    fn new_positioner(
+        anchor_rect: Rectangle<i32, Logical>,
+        anchor: Edges,
+        gravity: Edges,
+        size: (i32, i32),
+        constraint_adjustment: ConstraintAdjustment,
+    ) -> Positioner {
+        Positioner {
+            size: size.into(),
+            anchor_rect,
+            anchor,
+            gravity,
+            offset: Point::default(),
+            constraint_adjustment,
+        }
+    }
+
+    #[test]
+    fn geometry_for_all_anchors_and_gravities() {
+        let edges = [
+            Edges::empty(),
+            Edges::TOP,
+            Edges::BOTTOM,
+            Edges::LEFT,
+            Edges::RIGHT,
+            Edges::TOP | Edges::LEFT,
+            Edges::TOP | Edges::RIGHT,
+            Edges::BOTTOM | Edges::LEFT,
+            Edges::BOTTOM | Edges::RIGHT,
+        ];
+        // anchor rect (100, 100, 20, 10), popup size (40, 30)
+        let anchor_x = |e: Edges| match (e.contains(Edges::LEFT), e.contains(Edges::RIGHT)) {
+            (true, false) => 100,
+            (false, true) => 120,
+            _ => 110,
+        };
+        let anchor_y = |e: Edges| match (e.contains(Edges::TOP), e.contains(Edges::BOTTOM)) {
+            (true, false) => 100,
+            (false, true) => 110,
+            _ => 105,
+        };
+        let gravity_x = |e: Edges| match (e.contains(Edges::LEFT), e.contains(Edges::RIGHT)) {
+            (true, false) => -40,
+            (false, true) => 0,
+            _ => -20,
+        };
+        let gravity_y = |e: Edges| match (e.contains(Edges::TOP), e.contains(Edges::BOTTOM)) {
+            (true, false) => -30,
+            (false, true) => 0,
+            _ => -15,
+        };
+
+        for anchor in edges {
+            for gravity in edges {
+                let mut positioner = new_positioner(
+                    rect(100, 100, 20, 10),
+                    anchor,
+                    gravity,
+                    (40, 30),
+                    ConstraintAdjustment::empty(),
+                );
+                positioner.offset = (3, 4).into();
+                let expected = rect(
+                    anchor_x(anchor) + gravity_x(gravity) + 3,
+                    anchor_y(anchor) + gravity_y(gravity) + 4,
+                    40,
+                    30,
+                );
+                assert_eq!(positioner.geometry(), expected, "{:?} {:?}", anchor, gravity);
+                // nothing is constrained within a large target
+                assert_eq!(solve(&positioner, rect(0, 0, 1000, 1000)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn opposite_edges_are_centered() {
+        let positioner = new_positioner(
+            rect(0, 0, 20, 20),
+            Edges::all(),
+            Edges::LEFT | Edges::RIGHT,
+            (10, 10),
+            ConstraintAdjustment::empty(),
+        );
+        assert_eq!(positioner.geometry(), rect(5, 5, 10, 10));
+    }
+
+    #[test]
+    fn constraints_without_adjustment() {
+        let positioner = new_positioner(
+            rect(80, 10, 10, 10),
+            Edges::RIGHT,
+            Edges::RIGHT,
+            (30, 20),
+            ConstraintAdjustment::empty(),
+        );
+        assert_eq!(solve(&positioner, target()), rect(90, 5, 30, 20));
+    }
+
+    #[test]
+    fn flip() {
+        let positioner = new_positioner(
+            rect(80, 10, 10, 10),
+            Edges::RIGHT,
+            Edges::RIGHT,
+            (30, 20),
+            ConstraintAdjustment::FLIP_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(50, 5, 30, 20));
+
+        let positioner = new_positioner(
+            rect(10, 85, 10, 10),
+            Edges::BOTTOM,
+            Edges::BOTTOM,
+            (20, 20),
+            ConstraintAdjustment::FLIP_Y,
+        );
+        assert_eq!(solve(&positioner, target()), rect(5, 65, 20, 20));
+
+        // a flip on one axis does not flip the other
+        let positioner = new_positioner(
+            rect(80, 85, 10, 10),
+            Edges::BOTTOM | Edges::RIGHT,
+            Edges::BOTTOM | Edges::RIGHT,
+            (30, 20),
+            ConstraintAdjustment::FLIP_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(50, 95, 30, 20));
+    }
+
+    #[test]
+    fn flip_reverted_if_still_constrained() {
+        let positioner = new_positioner(
+            rect(40, 10, 20, 10),
+            Edges::RIGHT,
+            Edges::RIGHT,
+            (70, 20),
+            ConstraintAdjustment::FLIP_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(60, 5, 70, 20));
+    }
+
+    #[test]
+    fn slide() {
+        let positioner = new_positioner(
+            rect(0, 10, 10, 10),
+            Edges::LEFT,
+            Edges::LEFT,
+            (30, 20),
+            ConstraintAdjustment::SLIDE_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(0, 5, 30, 20));
+
+        let positioner = new_positioner(
+            rect(10, 90, 10, 10),
+            Edges::BOTTOM,
+            Edges::BOTTOM,
+            (20, 30),
+            ConstraintAdjustment::SLIDE_Y,
+        );
+        assert_eq!(solve(&positioner, target()), rect(5, 70, 20, 30));
+    }
+
+    #[test]
+    fn slide_after_failed_flip() {
+        let positioner = new_positioner(
+            rect(40, 10, 20, 10),
+            Edges::RIGHT,
+            Edges::RIGHT,
+            (70, 20),
+            ConstraintAdjustment::FLIP_X | ConstraintAdjustment::SLIDE_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(30, 5, 70, 20));
+    }
+
+    #[test]
+    fn no_slide_after_successful_flip() {
+        let positioner = new_positioner(
+            rect(80, 10, 10, 10),
+            Edges::RIGHT,
+            Edges::RIGHT,
+            (30, 20),
+            ConstraintAdjustment::FLIP_X | ConstraintAdjustment::SLIDE_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(50, 5, 30, 20));
+    }
+
+    #[test]
+    fn flip_and_slide_on_different_axes() {
+        let positioner = new_positioner(
+            rect(80, 90, 10, 10),
+            Edges::BOTTOM | Edges::RIGHT,
+            Edges::BOTTOM | Edges::RIGHT,
+            (30, 20),
+            ConstraintAdjustment::FLIP_X | ConstraintAdjustment::SLIDE_Y,
+        );
+        assert_eq!(solve(&positioner, target()), rect(50, 80, 30, 20));
+
+        let positioner = new_positioner(
+            rect(80, 90, 10, 10),
+            Edges::BOTTOM | Edges::RIGHT,
+            Edges::BOTTOM | Edges::RIGHT,
+            (30, 20),
+            ConstraintAdjustment::SLIDE_X | ConstraintAdjustment::FLIP_Y,
+        );
+        assert_eq!(solve(&positioner, target()), rect(70, 70, 30, 20));
+    }
+
+    #[test]
+    fn slide_keeps_top_left_corner_visible() {
+        let positioner = new_positioner(
+            rect(10, 10, 10, 10),
+            Edges::LEFT,
+            Edges::RIGHT,
+            (150, 20),
+            ConstraintAdjustment::SLIDE_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(0, 5, 150, 20));
+
+        let positioner = Positioner {
+            constraint_adjustment: ConstraintAdjustment::SLIDE_X | ConstraintAdjustment::RESIZE_X,
+            ..positioner
+        };
+        assert_eq!(solve(&positioner, target()), rect(0, 5, 100, 20));
+    }
+
+    #[test]
+    fn resize() {
+        let positioner = new_positioner(
+            rect(10, 80, 10, 10),
+            Edges::BOTTOM,
+            Edges::BOTTOM,
+            (20, 40),
+            ConstraintAdjustment::RESIZE_Y,
+        );
+        assert_eq!(solve(&positioner, target()), rect(5, 90, 20, 10));
+
+        // a popup completely outside of the target is not resized
+        let positioner = new_positioner(
+            rect(100, 10, 10, 10),
+            Edges::RIGHT,
+            Edges::RIGHT,
+            (20, 20),
+            ConstraintAdjustment::RESIZE_X,
+        );
+        assert_eq!(solve(&positioner, target()), rect(110, 5, 20, 20));
+    }
+
+    #[test]
+    fn all_adjustments() {
+        // flip fails on both axes, slide moves the popup into the target, resize is not needed
+        let positioner = new_positioner(
+            rect(40, 40, 20, 20),
+            Edges::BOTTOM | Edges::RIGHT,
+            Edges::BOTTOM | Edges::RIGHT,
+            (60, 60),
+            ConstraintAdjustment::all(),
+        );
+        assert_eq!(solve(&positioner, target()), rect(40, 40, 60, 60));
+
+        // the popup is larger than the target
+        let positioner = Positioner {
+            size: (120, 30).into(),
+            ..positioner
+        };
+        assert_eq!(solve(&positioner, target()), rect(0, 60, 100, 30));
+    }
+}
@@ -3,6 +3,7 @@
 use once_cell::sync::OnceCell;
 
 use std::any::Any;
+use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
 use std::thread::{self, ThreadId};
 
@@ -105,6 +106,56 @@ impl Drop for UserData {
     }
 }
 
+/// Typed key of a value stored in a [`UserDataMap`]
+///
+/// Values are usually looked up by their type, so two crates storing e.g. a `Mutex<u32>` in the
+/// user data of the same object would access the same value. Values accessed through a key are
+/// stored separately for every key type instead, and are initialized on first access.
+///
+/// Keys are usually declared using the [`user_data_key!`](crate::user_data_key) macro.
+pub trait UserDataKey: 'static {
+    /// Type of the value stored for this key
+    type Value: Send + Sync + 'static;
+
+    /// Create the initial value, when the key is accessed for the first time
+    fn init() -> Self::Value;
+}
+
+// wrapper giving values stored by key a distinct type per key
+struct KeyedValue<K: UserDataKey>(K::Value, PhantomData<fn() -> K>);
+
+/// Declare a [`UserDataKey`](crate::utils::user_data::UserDataKey) with its value type and initializer
+///
+/// ```
+/// use std::sync::Mutex;
+/// use smithay::utils::user_data::UserDataMap;
+///
+/// smithay::user_data_key! {
+///     /// Number of times something happened
+///     pub Counter: Mutex<u32> = Mutex::new(0);
+/// }
+///
+/// let map = UserDataMap::new();
+/// *map.get_key::<Counter>().lock().unwrap() += 1;
+/// assert_eq!(*map.get_key::<Counter>().lock().unwrap(), 1);
+/// ```
+#[macro_export]
+macro_rules! user_data_key {
+    ($(#[$attr:meta])* $vis:vis $name:ident: $ty:ty = $init:expr $(;)?) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        $vis struct $name;
+
+        impl $crate::utils::user_data::UserDataKey for $name {
+            type Value = $ty;
+
+            fn init() -> $ty {
+                $init
+            }
+        }
+    };
+}
+
 /// A storage able to store several values of `UserData`
 /// of different types. It behaves similarly to a `TypeMap`.
 #[derive(Debug)]
@@ -161,6 +212,20 @@ impl UserDataMap {
         }
     }
 
+    /// Access the value stored for a [`UserDataKey`], initializing it if required.
+    ///
+    /// The value is threadsafe and visible from all threads.
+    pub fn get_key<K: UserDataKey>(&self) -> &K::Value {
+        &self
+            .get_or_insert_threadsafe(|| KeyedValue::<K>(K::init(), PhantomData))
+            .0
+    }
+
+    /// Access the value stored for a [`UserDataKey`], if it was initialized before.
+    pub fn try_get_key<K: UserDataKey>(&self) -> Option<&K::Value> {
+        self.get::<KeyedValue<K>>().map(|value| &value.0)
+    }
+
     /// Insert a value in the map if it is not already there
     ///
     /// This is the non-threadsafe variant, the type you insert don't have to be
@@ -368,8 +433,13 @@ mod list {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Mutex;
+
     use super::UserDataMap;
 
+    crate::user_data_key!(First: Mutex<u32> = Mutex::new(1));
+    crate::user_data_key!(Second: Mutex<u32> = Mutex::new(2));
+
     #[test]
     fn insert_twice() {
         let map = UserDataMap::new();
@@ -379,4 +449,15 @@ mod tests {
         assert!(!map.insert_if_missing(|| 43usize));
         assert_eq!(map.get::<usize>(), Some(&42));
     }
+
+    #[test]
+    fn keys_are_distinct() {
+        let map = UserDataMap::new();
+
+        assert!(map.try_get_key::<First>().is_none());
+        *map.get_key::<First>().lock().unwrap() += 10;
+        assert_eq!(*map.get_key::<First>().lock().unwrap(), 11);
+        assert_eq!(*map.get_key::<Second>().lock().unwrap(), 2);
+        assert_eq!(map.get::<Mutex<u32>>().map(|v| *v.lock().unwrap()), None);
+    }
 }
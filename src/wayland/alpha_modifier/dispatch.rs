@@ -3,15 +3,14 @@ use wayland_protocols::wp::alpha_modifier::v1::server::{
     wp_alpha_modifier_v1::{self, WpAlphaModifierV1},
 };
 
-use wayland_server::{
-    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
-};
+use wayland_server::{backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New};
 
 use super::{
-    AlphaModifierState, AlphaModifierSurfaceCachedState, AlphaModifierSurfaceData,
+    AlphaModifierState, AlphaModifierSurfaceCachedState, AlphaModifierSurfaceDataKey,
     AlphaModifierSurfaceUserData,
 };
 use crate::wayland::compositor;
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 impl<D> GlobalDispatch<WpAlphaModifierV1, (), D> for AlphaModifierState
 where
@@ -50,10 +49,7 @@ where
         match request {
             wp_alpha_modifier_v1::Request::GetSurface { id, surface } => {
                 let already_taken = compositor::with_states(&surface, |states| {
-                    states
-                        .data_map
-                        .insert_if_missing_threadsafe(AlphaModifierSurfaceData::new);
-                    let data = states.data_map.get::<AlphaModifierSurfaceData>().unwrap();
+                    let data = states.data_map.get_key::<AlphaModifierSurfaceDataKey>();
 
                     let already_taken = data.is_resource_attached();
 
@@ -65,9 +61,11 @@ where
                 });
 
                 if already_taken {
-                    manager.post_error(
+                    post_error(
+                        manager,
                         wp_alpha_modifier_v1::Error::AlreadyConstructed,
                         "wl_surface already has a alpha modifier object attached",
+                        ErrorContext::new().request("wp_alpha_modifier_v1.get_surface"),
                     )
                 } else {
                     data_init.init(id, AlphaModifierSurfaceUserData::new(surface));
@@ -96,9 +94,11 @@ where
         match request {
             wp_alpha_modifier_surface_v1::Request::SetMultiplier { factor } => {
                 let Some(surface) = data.wl_surface() else {
-                    obj.post_error(
+                    post_error(
+                        obj,
                         wp_alpha_modifier_surface_v1::Error::NoSurface,
                         "wl_surface was destroyed",
+                        ErrorContext::new().request("wp_alpha_modifier_surface_v1.set_multiplier"),
                     );
                     return;
                 };
@@ -114,9 +114,11 @@ where
             // Switch back to not specifying the alpha multiplier of this surface.
             wp_alpha_modifier_surface_v1::Request::Destroy => {
                 let Some(surface) = data.wl_surface() else {
-                    obj.post_error(
+                    post_error(
+                        obj,
                         wp_alpha_modifier_surface_v1::Error::NoSurface,
                         "wl_surface was destroyed",
+                        ErrorContext::new().request("wp_alpha_modifier_surface_v1.destroy"),
                     );
                     return;
                 };
@@ -124,8 +126,7 @@ where
                 compositor::with_states(&surface, |states| {
                     states
                         .data_map
-                        .get::<AlphaModifierSurfaceData>()
-                        .unwrap()
+                        .get_key::<AlphaModifierSurfaceDataKey>()
                         .set_is_resource_attached(false);
 
                     states
@@ -115,6 +115,8 @@ impl Cacheable for AlphaModifierSurfaceCachedState {
     }
 }
 
+crate::user_data_key!(AlphaModifierSurfaceDataKey: AlphaModifierSurfaceData = AlphaModifierSurfaceData::new());
+
 #[derive(Debug)]
 struct AlphaModifierSurfaceData {
     is_resource_attached: AtomicBool,
@@ -91,9 +91,10 @@ use wayland_server::{
     New, Resource, Weak,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     utils::Time,
-    wayland::compositor::{add_blocker, add_pre_commit_hook},
+    wayland::compositor::{add_blocker, add_pre_commit_hook_with_priority, HookPriority},
 };
 
 use super::compositor::{with_states, Barrier};
@@ -201,34 +202,40 @@ where
 
                 // The protocol mandates that only a single commit timer object is associated with a surface at all times
                 if has_active_commit_timer {
-                    surface.post_error(
+                    post_error(
+                        &surface,
                         wp_commit_timing_manager_v1::Error::CommitTimerExists,
                         "the surface has already a commit timer object associated",
+                        ErrorContext::new().request("wp_commit_timing_manager_v1.get_timer"),
                     );
                     return;
                 }
 
                 // Make sure we do not install the hook more then once in case the surface is being reused
                 if is_managed && is_initial {
-                    add_pre_commit_hook::<D, _>(&surface, |_, _, surface| {
-                        let timestamp = with_states(surface, |states| {
-                            states
-                                .data_map
-                                .get::<CommitTimerStateUserData>()
-                                .and_then(|state| state.borrow_mut().timestamp.take())
-                        });
-
-                        if let Some(timestamp) = timestamp {
-                            let barrier = with_states(surface, |states| {
-                                let barrier_state = states
+                    add_pre_commit_hook_with_priority::<D, _>(
+                        &surface,
+                        HookPriority::Protocol,
+                        |_, _, surface| {
+                            let timestamp = with_states(surface, |states| {
+                                states
                                     .data_map
-                                    .get_or_insert(CommitTimerBarrierStateUserData::default);
-                                barrier_state.lock().unwrap().register(timestamp)
+                                    .get::<CommitTimerStateUserData>()
+                                    .and_then(|state| state.borrow_mut().timestamp.take())
                             });
 
-                            add_blocker(surface, barrier);
-                        }
-                    });
+                            if let Some(timestamp) = timestamp {
+                                let barrier = with_states(surface, |states| {
+                                    let barrier_state = states
+                                        .data_map
+                                        .get_or_insert(CommitTimerBarrierStateUserData::default);
+                                    barrier_state.lock().unwrap().register(timestamp)
+                                });
+
+                                add_blocker(surface, barrier);
+                            }
+                        },
+                    );
                 }
 
                 let commit_timer: WpCommitTimerV1 = data_init.init(id, surface.downgrade());
@@ -272,9 +279,11 @@ where
                 tv_nsec,
             } => {
                 let Ok(surface) = data.upgrade() else {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_commit_timer_v1::Error::SurfaceDestroyed as u32,
                         "the surface associated with this commit timer object has been destroyed".to_string(),
+                        ErrorContext::new().request("wp_commit_timer_v1.set_timestamp"),
                     );
                     return;
                 };
@@ -301,9 +310,11 @@ where
                 });
 
                 if already_has_timestamp {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_commit_timer_v1::Error::TimestampExists as u32,
                         "the surface already has a timestamp associated for this commit".to_string(),
+                        ErrorContext::new().request("wp_commit_timer_v1.set_timestamp"),
                     );
                 }
             }
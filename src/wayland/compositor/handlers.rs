@@ -15,9 +15,12 @@ use wayland_server::{
     DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
 };
 
-use crate::utils::{
-    alive_tracker::{AliveTracker, IsAlive},
-    Client, Logical, Point,
+use crate::{
+    utils::{
+        alive_tracker::{AliveTracker, IsAlive},
+        Client, Logical, Point,
+    },
+    wayland::protocol_error::{post_error, ErrorContext},
 };
 
 use super::{
@@ -183,9 +186,11 @@ where
                     offset
                 } else {
                     if offset.is_some() {
-                        surface.post_error(
+                        post_error(
+                            surface,
                             wl_surface::Error::InvalidOffset,
                             "Passing non-zero x,y is protocol violation since versions 5",
+                            ErrorContext::new().request("wl_surface.attach"),
                         );
                     }
 
@@ -297,7 +302,12 @@ where
                             .buffer_scale = scale;
                     });
                 } else {
-                    surface.post_error(wl_surface::Error::InvalidScale, "Scale must be positive");
+                    post_error(
+                        surface,
+                        wl_surface::Error::InvalidScale,
+                        "Scale must be positive",
+                        ErrorContext::new().request("wl_surface.set_buffer_scale"),
+                    );
                 }
             }
             wl_surface::Request::DamageBuffer { x, y, width, height } => {
@@ -445,8 +455,14 @@ where
         match request {
             wl_subcompositor::Request::GetSubsurface { id, surface, parent } => {
                 if let Err(AlreadyHasRole) = PrivateSurfaceData::set_parent(&surface, &parent) {
-                    subcompositor
-                        .post_error(wl_subcompositor::Error::BadSurface, "Surface already has a role.");
+                    post_error(
+                        subcompositor,
+                        wl_subcompositor::Error::BadSurface,
+                        "Surface already has a role.",
+                        ErrorContext::new()
+                            .request("wl_subcompositor.get_subsurface")
+                            .state("role", PrivateSurfaceData::get_role(&surface)),
+                    );
                     return;
                 }
 
@@ -458,7 +474,7 @@ where
                 );
 
                 super::with_states(&surface, |states| {
-                    states.data_map.insert_if_missing_threadsafe(SubsurfaceState::new)
+                    states.data_map.get_key::<SubsurfaceStateKey>();
                 });
 
                 state.new_subsurface(&surface, &parent);
@@ -513,6 +529,8 @@ impl Cacheable for SubsurfaceCachedState {
     }
 }
 
+crate::user_data_key!(pub(crate) SubsurfaceStateKey: SubsurfaceState = SubsurfaceState::new());
+
 pub(crate) struct SubsurfaceState {
     pub(crate) sync: AtomicBool,
 }
@@ -530,7 +548,7 @@ pub fn is_effectively_sync(surface: &wl_surface::WlSurface) -> bool {
     let is_direct_sync = PrivateSurfaceData::with_states(surface, |state| {
         state
             .data_map
-            .get::<SubsurfaceState>()
+            .try_get_key::<SubsurfaceStateKey>()
             .map(|s| s.sync.load(Ordering::Acquire))
             .unwrap_or(false)
     });
@@ -572,33 +590,35 @@ where
             }
             wl_subsurface::Request::PlaceAbove { sibling } => {
                 if let Err(()) = PrivateSurfaceData::reorder(&data.surface, Location::After, &sibling) {
-                    subsurface.post_error(
+                    post_error(
+                        subsurface,
                         wl_subsurface::Error::BadSurface,
                         "Provided surface is not a sibling or parent.",
+                        ErrorContext::new().request("wl_subsurface.place_above"),
                     )
                 }
             }
             wl_subsurface::Request::PlaceBelow { sibling } => {
                 if let Err(()) = PrivateSurfaceData::reorder(&data.surface, Location::Before, &sibling) {
-                    subsurface.post_error(
+                    post_error(
+                        subsurface,
                         wl_subsurface::Error::BadSurface,
                         "Provided surface is not a sibling or parent.",
+                        ErrorContext::new().request("wl_subsurface.place_below"),
                     )
                 }
             }
             wl_subsurface::Request::SetSync => PrivateSurfaceData::with_states(&data.surface, |state| {
                 state
                     .data_map
-                    .get::<SubsurfaceState>()
-                    .unwrap()
+                    .get_key::<SubsurfaceStateKey>()
                     .sync
                     .store(true, Ordering::Release);
             }),
             wl_subsurface::Request::SetDesync => PrivateSurfaceData::with_states(&data.surface, |state| {
                 state
                     .data_map
-                    .get::<SubsurfaceState>()
-                    .unwrap()
+                    .get_key::<SubsurfaceStateKey>()
                     .sync
                     .store(false, Ordering::Release);
             }),
@@ -619,8 +639,7 @@ where
         PrivateSurfaceData::with_states(&data.surface, |state| {
             state
                 .data_map
-                .get::<SubsurfaceState>()
-                .unwrap()
+                .get_key::<SubsurfaceStateKey>()
                 .sync
                 .store(true, Ordering::Release);
 
@@ -97,6 +97,14 @@
 //!    using the [`add_destruction_hook`] function. They are typically used to cleanup associated
 //!    state.
 //!
+//! Commit hooks are invoked ordered by their [`HookPriority`] and in the order they were registered
+//! within the same priority class. Smithay's protocol implementations register their hooks as
+//! [`HookPriority::Protocol`], while [`add_pre_commit_hook`] and [`add_post_commit_hook`] default to
+//! [`HookPriority::Normal`]. So pre-commit hooks of the compositor always see the validated pending
+//! state of protocol extensions, e.g. to add blockers for it, and post-commit hooks see their applied state,
+//! regardless of when the hooks were registered. Hooks can be removed by their [`HookId`] at any time,
+//! including from within another hook.
+//!
 //! ### Surface roles
 //!
 //! The wayland protocol specifies that a surface needs to be assigned a role before it can
@@ -121,7 +129,7 @@ use self::transaction::TransactionQueue;
 pub use self::transaction::{Barrier, Blocker, BlockerState};
 pub use self::tree::{AlreadyHasRole, TraversalAction};
 use self::tree::{PrivateSurfaceData, SuggestedSurfaceState};
-pub use crate::utils::hook::HookId;
+pub use crate::utils::hook::{HookId, HookPriority};
 use crate::utils::Transform;
 use crate::utils::{user_data::UserDataMap, Buffer, Logical, Point, Rectangle};
 use wayland_server::backend::GlobalId;
@@ -448,7 +456,21 @@ pub fn get_region_attributes(region: &wl_region::WlRegion) -> RegionAttributes {
 /// post-commit hook to apply state changes (i.e. copy last acked state to current).
 ///
 /// Compositors should use this for adding blockers if needed, e.g. the DMA-BUF readiness blocker.
+///
+/// The hook is registered with [`HookPriority::Normal`], so it is invoked after the hooks of smithay's
+/// protocol implementations. See [`add_pre_commit_hook_with_priority`] for other priority classes.
 pub fn add_pre_commit_hook<D, F>(surface: &WlSurface, hook: F) -> HookId
+where
+    F: Fn(&mut D, &DisplayHandle, &WlSurface) + Send + Sync + 'static,
+    D: 'static,
+{
+    add_pre_commit_hook_with_priority(surface, HookPriority::Normal, hook)
+}
+
+/// Register a pre-commit hook with the given [`HookPriority`]
+///
+/// See [`add_pre_commit_hook`] for details.
+pub fn add_pre_commit_hook_with_priority<D, F>(surface: &WlSurface, priority: HookPriority, hook: F) -> HookId
 where
     F: Fn(&mut D, &DisplayHandle, &WlSurface) + Send + Sync + 'static,
     D: 'static,
@@ -466,7 +488,7 @@ where
         let state = state.downcast_mut::<D>().unwrap();
         hook(state, dh, surface);
     };
-    PrivateSurfaceData::add_pre_commit_hook(surface, hook)
+    PrivateSurfaceData::add_pre_commit_hook(surface, priority, hook)
 }
 
 /// Register a post-commit hook to be invoked on surface commit
@@ -476,7 +498,25 @@ where
 ///
 /// Protocol implementations should apply state changes here, i.e. copy last acked state into
 /// current.
+///
+/// The hook is registered with [`HookPriority::Normal`], so it is invoked after the hooks of smithay's
+/// protocol implementations. See [`add_post_commit_hook_with_priority`] for other priority classes.
 pub fn add_post_commit_hook<D, F>(surface: &WlSurface, hook: F) -> HookId
+where
+    F: Fn(&mut D, &DisplayHandle, &WlSurface) + Send + Sync + 'static,
+    D: 'static,
+{
+    add_post_commit_hook_with_priority(surface, HookPriority::Normal, hook)
+}
+
+/// Register a post-commit hook with the given [`HookPriority`]
+///
+/// See [`add_post_commit_hook`] for details.
+pub fn add_post_commit_hook_with_priority<D, F>(
+    surface: &WlSurface,
+    priority: HookPriority,
+    hook: F,
+) -> HookId
 where
     F: Fn(&mut D, &DisplayHandle, &WlSurface) + Send + Sync + 'static,
     D: 'static,
@@ -494,7 +534,7 @@ where
         let state = state.downcast_mut::<D>().unwrap();
         hook(state, dh, surface);
     };
-    PrivateSurfaceData::add_post_commit_hook(surface, hook)
+    PrivateSurfaceData::add_post_commit_hook(surface, priority, hook)
 }
 
 /// Register a destruction hook to be invoked on surface destruction
@@ -525,11 +565,15 @@ where
 }
 
 /// Unregister a pre-commit hook
+///
+/// A hook removed while the hooks of a surface are invoked, e.g. by another hook, is not invoked anymore.
 pub fn remove_pre_commit_hook(surface: &WlSurface, hook_id: HookId) {
     PrivateSurfaceData::remove_pre_commit_hook(surface, hook_id)
 }
 
 /// Unregister a post-commit hook
+///
+/// A hook removed while the hooks of a surface are invoked, e.g. by another hook, is not invoked anymore.
 pub fn remove_post_commit_hook(surface: &WlSurface, hook_id: HookId) {
     PrivateSurfaceData::remove_post_commit_hook(surface, hook_id)
 }
@@ -801,4 +845,68 @@ mod tests {
         assert!(region.contains((5, 5)));
         assert!(region.contains((2, 2)));
     }
+
+    #[test]
+    fn commit_hook_order() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::wayland::test_utils::{TestFixture, TestState};
+
+        let mut fixture = TestFixture::new();
+        let (surface, server_surface) = fixture.create_surface();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let hook = |name: &'static str| {
+            let calls = calls.clone();
+            move |_: &mut TestState, _: &DisplayHandle, _: &WlSurface| calls.lock().unwrap().push(name)
+        };
+        // registered before the protocol hook, but still invoked after it
+        add_pre_commit_hook(&server_surface, hook("normal"));
+        add_pre_commit_hook_with_priority(&server_surface, HookPriority::Late, hook("late"));
+        add_pre_commit_hook_with_priority(&server_surface, HookPriority::Protocol, hook("protocol"));
+        add_pre_commit_hook_with_priority(&server_surface, HookPriority::Early, hook("early"));
+        add_post_commit_hook(&server_surface, hook("post"));
+
+        surface.commit();
+        fixture.roundtrip();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["early", "protocol", "normal", "late", "post"]
+        );
+    }
+
+    #[test]
+    fn commit_hook_removed_by_hook() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::wayland::test_utils::{TestFixture, TestState};
+
+        let mut fixture = TestFixture::new();
+        let (surface, server_surface) = fixture.create_surface();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let removed = Arc::new(Mutex::new(None));
+        let removing = {
+            let calls = calls.clone();
+            let removed = removed.clone();
+            move |_: &mut TestState, _: &DisplayHandle, surface: &WlSurface| {
+                calls.lock().unwrap().push("removing");
+                if let Some(id) = removed.lock().unwrap().take() {
+                    remove_pre_commit_hook(surface, id);
+                }
+            }
+        };
+        add_pre_commit_hook(&server_surface, removing);
+        let id = add_pre_commit_hook(&server_surface, {
+            let calls = calls.clone();
+            move |_: &mut TestState, _: &DisplayHandle, _: &WlSurface| calls.lock().unwrap().push("removed")
+        });
+        *removed.lock().unwrap() = Some(id);
+
+        surface.commit();
+        fixture.roundtrip();
+        surface.commit();
+        fixture.roundtrip();
+        assert_eq!(*calls.lock().unwrap(), ["removing", "removing"]);
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{
     utils::{
-        hook::{Hook, HookId},
+        hook::{Hook, HookId, HookList, HookPriority},
         Serial,
     },
     wayland::compositor::SUBSURFACE_ROLE,
@@ -44,9 +44,9 @@ pub struct PrivateSurfaceData {
     public_data: SurfaceData,
     pending_transaction: PendingTransaction,
     current_txid: Serial,
-    pre_commit_hooks: Vec<Hook<CommitHook>>,
-    post_commit_hooks: Vec<Hook<CommitHook>>,
-    destruction_hooks: Vec<Hook<DestructionHook>>,
+    pre_commit_hooks: HookList<CommitHook>,
+    post_commit_hooks: HookList<CommitHook>,
+    destruction_hooks: HookList<DestructionHook>,
 }
 
 impl fmt::Debug for PrivateSurfaceData {
@@ -109,9 +109,9 @@ impl PrivateSurfaceData {
             },
             pending_transaction: Default::default(),
             current_txid: Serial(0),
-            pre_commit_hooks: Vec::new(),
-            post_commit_hooks: Vec::new(),
-            destruction_hooks: Vec::new(),
+            pre_commit_hooks: HookList::default(),
+            post_commit_hooks: HookList::default(),
+            destruction_hooks: HookList::default(),
         })
     }
 
@@ -151,12 +151,15 @@ impl PrivateSurfaceData {
             buffer.release();
         };
 
-        let hooks = my_data.destruction_hooks.clone();
+        let hooks = my_data.destruction_hooks.snapshot();
         // don't hold the mutex while the hooks are invoked
         drop(guard);
         drop(my_data);
         for hook in hooks {
-            (hook.cb)(state, surface)
+            // skip hooks removed by a previous hook
+            if !hook.is_removed() {
+                (hook.cb)(state, surface)
+            }
         }
     }
 
@@ -189,41 +192,33 @@ impl PrivateSurfaceData {
     }
 
     pub fn remove_pre_commit_hook(surface: &WlSurface, hook_id: HookId) {
-        Self::lock_user_data(surface)
-            .pre_commit_hooks
-            .retain(|hook| hook.id != hook_id);
+        Self::lock_user_data(surface).pre_commit_hooks.remove(hook_id);
     }
 
     pub fn remove_post_commit_hook(surface: &WlSurface, hook_id: HookId) {
-        Self::lock_user_data(surface)
-            .post_commit_hooks
-            .retain(|hook| hook.id != hook_id);
+        Self::lock_user_data(surface).post_commit_hooks.remove(hook_id);
     }
 
     pub fn remove_destruction_hook(surface: &WlSurface, hook_id: HookId) {
-        Self::lock_user_data(surface)
-            .destruction_hooks
-            .retain(|hook| hook.id != hook_id);
+        Self::lock_user_data(surface).destruction_hooks.remove(hook_id);
     }
 
     pub fn add_pre_commit_hook(
         surface: &WlSurface,
+        priority: HookPriority,
         hook: impl Fn(&mut dyn Any, &DisplayHandle, &WlSurface) + Send + Sync + 'static,
     ) -> HookId {
-        let hook: Hook<CommitHook> = Hook::new(Arc::new(hook));
-        let id = hook.id;
-        Self::lock_user_data(surface).pre_commit_hooks.push(hook);
-        id
+        let hook: Hook<CommitHook> = Hook::with_priority(Arc::new(hook), priority);
+        Self::lock_user_data(surface).pre_commit_hooks.insert(hook)
     }
 
     pub fn add_post_commit_hook(
         surface: &WlSurface,
+        priority: HookPriority,
         hook: impl Fn(&mut dyn Any, &DisplayHandle, &WlSurface) + Send + Sync + 'static,
     ) -> HookId {
-        let hook: Hook<CommitHook> = Hook::new(Arc::new(hook));
-        let id = hook.id;
-        Self::lock_user_data(surface).post_commit_hooks.push(hook);
-        id
+        let hook: Hook<CommitHook> = Hook::with_priority(Arc::new(hook), priority);
+        Self::lock_user_data(surface).post_commit_hooks.insert(hook)
     }
 
     pub fn add_destruction_hook(
@@ -231,24 +226,28 @@ impl PrivateSurfaceData {
         hook: impl Fn(&mut dyn Any, &WlSurface) + Send + Sync + 'static,
     ) -> HookId {
         let hook: Hook<DestructionHook> = Hook::new(Arc::new(hook));
-        let id = hook.id;
-        Self::lock_user_data(surface).destruction_hooks.push(hook);
-        id
+        Self::lock_user_data(surface).destruction_hooks.insert(hook)
     }
 
     pub fn invoke_pre_commit_hooks<D: 'static>(state: &mut D, dh: &DisplayHandle, surface: &WlSurface) {
         // don't hold the mutex while the hooks are invoked
-        let hooks = Self::lock_user_data(surface).pre_commit_hooks.clone();
+        let hooks = Self::lock_user_data(surface).pre_commit_hooks.snapshot();
         for hook in hooks {
-            (hook.cb)(state, dh, surface);
+            // skip hooks removed by a previous hook
+            if !hook.is_removed() {
+                (hook.cb)(state, dh, surface);
+            }
         }
     }
 
     pub fn invoke_post_commit_hooks<D: 'static>(state: &mut D, dh: &DisplayHandle, surface: &WlSurface) {
         // don't hold the mutex while the hooks are invoked
-        let hooks = Self::lock_user_data(surface).post_commit_hooks.clone();
+        let hooks = Self::lock_user_data(surface).post_commit_hooks.snapshot();
         for hook in hooks {
-            (hook.cb)(state, dh, surface);
+            // skip hooks removed by a previous hook
+            if !hook.is_removed() {
+                (hook.cb)(state, dh, surface);
+            }
         }
     }
 
@@ -290,7 +289,7 @@ impl PrivateSurfaceData {
             let is_child_sync = child_data
                 .public_data
                 .data_map
-                .get::<super::handlers::SubsurfaceState>()
+                .try_get_key::<super::handlers::SubsurfaceStateKey>()
                 .map(|s| s.sync.load(Ordering::Acquire))
                 .unwrap_or(false);
 
@@ -2,12 +2,13 @@ use wayland_protocols::wp::content_type::v1::server::{
     wp_content_type_manager_v1::{self, WpContentTypeManagerV1},
     wp_content_type_v1::{self, WpContentTypeV1},
 };
-use wayland_server::{
-    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
-};
+use wayland_server::{backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New};
 
-use super::{ContentTypeState, ContentTypeSurfaceCachedState, ContentTypeSurfaceData, ContentTypeUserData};
+use super::{
+    ContentTypeState, ContentTypeSurfaceCachedState, ContentTypeSurfaceDataKey, ContentTypeUserData,
+};
 use crate::wayland::compositor;
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 impl<D> GlobalDispatch<WpContentTypeManagerV1, (), D> for ContentTypeState
 where
@@ -46,10 +47,7 @@ where
         match request {
             wp_content_type_manager_v1::Request::GetSurfaceContentType { id, surface } => {
                 let already_taken = compositor::with_states(&surface, |states| {
-                    states
-                        .data_map
-                        .insert_if_missing_threadsafe(ContentTypeSurfaceData::new);
-                    let data = states.data_map.get::<ContentTypeSurfaceData>().unwrap();
+                    let data = states.data_map.get_key::<ContentTypeSurfaceDataKey>();
 
                     let already_taken = data.is_resource_attached();
 
@@ -61,9 +59,11 @@ where
                 });
 
                 if already_taken {
-                    manager.post_error(
+                    post_error(
+                        manager,
                         wp_content_type_manager_v1::Error::AlreadyConstructed,
                         "WlSurface already has WpSurfaceContentType attached",
+                        ErrorContext::new().request("wp_content_type_manager_v1.get_surface_content_type"),
                     )
                 } else {
                     data_init.init(id, ContentTypeUserData::new(surface));
@@ -117,8 +117,7 @@ where
                 compositor::with_states(&surface, |states| {
                     states
                         .data_map
-                        .get::<ContentTypeSurfaceData>()
-                        .unwrap()
+                        .get_key::<ContentTypeSurfaceDataKey>()
                         .set_is_resource_attached(false);
 
                     states
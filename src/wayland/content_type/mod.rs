@@ -111,6 +111,8 @@ impl Cacheable for ContentTypeSurfaceCachedState {
     }
 }
 
+crate::user_data_key!(ContentTypeSurfaceDataKey: ContentTypeSurfaceData = ContentTypeSurfaceData::new());
+
 #[derive(Debug)]
 struct ContentTypeSurfaceData {
     is_resource_attached: AtomicBool,
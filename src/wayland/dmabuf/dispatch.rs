@@ -8,6 +8,7 @@ use wayland_server::{
     Resource,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     backend::allocator::dmabuf::{Dmabuf, Plane, MAX_PLANES},
     wayland::{buffer::BufferHandler, compositor},
@@ -15,7 +16,8 @@ use crate::{
 
 use super::{
     DmabufData, DmabufFeedbackData, DmabufGlobal, DmabufGlobalData, DmabufHandler, DmabufParamsData,
-    DmabufState, Import, ImportNotifier, Modifier, SurfaceDmabufFeedbackState,
+    DmabufState, Import, ImportFailure, ImportFailureReason, ImportNotifier, Modifier,
+    SurfaceDmabufFeedbackState,
 };
 
 impl<D> Dispatch<wl_buffer::WlBuffer, Dmabuf, D> for DmabufState
@@ -74,6 +76,7 @@ where
                         formats: data.formats.clone(),
                         modifier: Mutex::new(None),
                         planes: Mutex::new(Vec::with_capacity(MAX_PLANES)),
+                        diagnostics: data.diagnostics.clone(),
                     },
                 );
             }
@@ -188,6 +191,7 @@ where
             id: global_data.id,
             default_feedback: global_data.default_feedback.clone(),
             known_default_feedbacks: global_data.known_default_feedbacks.clone(),
+            diagnostics: global_data.diagnostics.clone(),
         };
 
         let zwp_dmabuf = data_init.init(resource, data);
@@ -252,9 +256,11 @@ where
 
                 // Plane index should not be too large
                 if plane_idx as usize >= MAX_PLANES {
-                    params.post_error(
+                    post_error(
+                        params,
                         zwp_linux_buffer_params_v1::Error::PlaneIdx,
                         format!("Plane index {} is out of bounds", plane_idx),
+                        ErrorContext::new().request("zwp_linux_buffer_params_v1.add"),
                     );
                     return;
                 }
@@ -263,9 +269,11 @@ where
 
                 // Is the index already set?
                 if planes.iter().any(|plane| plane.plane_idx == plane_idx) {
-                    params.post_error(
+                    post_error(
+                        params,
                         zwp_linux_buffer_params_v1::Error::PlaneSet,
                         format!("Plane index {} is already set.", plane_idx),
+                        ErrorContext::new().request("zwp_linux_buffer_params_v1.add"),
                     );
                     return;
                 }
@@ -281,12 +289,14 @@ where
                 let mut data_modifier = data.modifier.lock().unwrap();
                 if let Some(data_modifier) = *data_modifier {
                     if params.version() >= 5 && modifier != data_modifier {
-                        params.post_error(
+                        post_error(
+                            params,
                             zwp_linux_buffer_params_v1::Error::InvalidFormat,
                             format!(
                                 "Planes have non-matching modifiers: {:?} != {:?}",
                                 modifier, data_modifier
                             ),
+                            ErrorContext::new().request("zwp_linux_buffer_params_v1.add"),
                         );
                     }
                 } else {
@@ -308,10 +318,18 @@ where
                             dh.clone(),
                             dmabuf.clone(),
                             Import::Falliable,
+                            DmabufGlobal { id: data.id },
+                            data.diagnostics.clone(),
                         );
                         state.dmabuf_imported(&DmabufGlobal { id: data.id }, dmabuf, notifier);
                     } else {
                         // If the dmabuf global was destroyed, we cannot import any buffers.
+                        data.diagnostics.record(ImportFailure::new(
+                            params,
+                            DmabufGlobal { id: data.id },
+                            &dmabuf,
+                            ImportFailureReason::GlobalDestroyed,
+                        ));
                         params.failed();
                     }
                 }
@@ -335,14 +353,24 @@ where
                             dh.clone(),
                             dmabuf.clone(),
                             Import::Infallible(buffer),
+                            DmabufGlobal { id: data.id },
+                            data.diagnostics.clone(),
                         );
                         state.dmabuf_imported(&DmabufGlobal { id: data.id }, dmabuf, notifier);
                     } else {
                         // Buffer import failed. The protocol documentation heavily implies killing the
                         // client is the right thing to do here.
-                        params.post_error(
+                        data.diagnostics.record(ImportFailure::new(
+                            params,
+                            DmabufGlobal { id: data.id },
+                            &dmabuf,
+                            ImportFailureReason::GlobalDestroyed,
+                        ));
+                        post_error(
+                            params,
                             zwp_linux_buffer_params_v1::Error::InvalidWlBuffer,
                             "dmabuf global was destroyed on server",
+                            ErrorContext::new().request("zwp_linux_buffer_params_v1.create_immed"),
                         );
                     }
                 }
@@ -188,7 +188,7 @@
 mod dispatch;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     ops::Sub,
     os::unix::io::AsFd,
     sync::{
@@ -204,7 +204,7 @@ use wayland_protocols::wp::linux_dmabuf::zv1::server::{
     zwp_linux_dmabuf_feedback_v1, zwp_linux_dmabuf_v1,
 };
 use wayland_server::{
-    backend::{GlobalId, InvalidId},
+    backend::{ClientId, GlobalId, InvalidId},
     protocol::{
         wl_buffer::{self, WlBuffer},
         wl_surface::WlSurface,
@@ -214,12 +214,13 @@ use wayland_server::{
 
 #[cfg(feature = "backend_drm")]
 use crate::backend::drm::DrmNode;
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     backend::allocator::{
         dmabuf::{Dmabuf, DmabufFlags, Plane},
-        Format, Fourcc, Modifier,
+        Buffer, Format, Fourcc, Modifier,
     },
-    utils::{ids::id_gen, SealedFile, UnmanagedResource},
+    utils::{ids::id_gen, Buffer as BufferCoords, SealedFile, Size, UnmanagedResource},
 };
 
 use super::{buffer::BufferHandler, compositor};
@@ -580,6 +581,7 @@ struct DmabufGlobalState {
 pub struct DmabufState {
     /// Globals managed by the dmabuf handler.
     globals: HashMap<usize, DmabufGlobalState>,
+    diagnostics: Arc<ImportDiagnostics>,
 }
 
 impl DmabufState {
@@ -588,9 +590,40 @@ impl DmabufState {
     pub fn new() -> DmabufState {
         DmabufState {
             globals: HashMap::new(),
+            diagnostics: Arc::new(ImportDiagnostics::default()),
         }
     }
 
+    /// Returns the most recent failed dmabuf imports of all globals, oldest first.
+    ///
+    /// Only the last [`MAX_RECORDED_IMPORT_FAILURES`] failures are kept.
+    pub fn import_failures(&self) -> Vec<ImportFailure> {
+        self.diagnostics
+            .failures
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the most recent failed dmabuf imports of the given client, oldest first.
+    pub fn import_failures_for_client(&self, client: &ClientId) -> Vec<ImportFailure> {
+        self.diagnostics
+            .failures
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|failure| failure.client.as_ref() == Some(client))
+            .cloned()
+            .collect()
+    }
+
+    /// Clears all recorded failed dmabuf imports.
+    pub fn clear_import_failures(&self) {
+        self.diagnostics.failures.lock().unwrap().clear();
+    }
+
     /// Creates a dmabuf global with the specified supported formats.
     ///
     /// Note: This function will create a version 3 dmabuf global and thus not call [`DmabufHandler::new_surface_feedback`],
@@ -727,6 +760,7 @@ impl DmabufState {
             formats,
             default_feedback: default_feedback.clone(),
             known_default_feedbacks: known_default_feedbacks.clone(),
+            diagnostics: self.diagnostics.clone(),
             id,
         };
 
@@ -800,6 +834,7 @@ pub struct DmabufGlobalData {
     default_feedback: Option<Arc<Mutex<DmabufFeedback>>>,
     known_default_feedbacks:
         Arc<Mutex<Vec<wayland_server::Weak<zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1>>>>,
+    diagnostics: Arc<ImportDiagnostics>,
     id: usize,
 }
 
@@ -812,6 +847,7 @@ pub struct DmabufData {
     default_feedback: Option<Arc<Mutex<DmabufFeedback>>>,
     known_default_feedbacks:
         Arc<Mutex<Vec<wayland_server::Weak<zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1>>>>,
+    diagnostics: Arc<ImportDiagnostics>,
 }
 
 /// Data associated with a dmabuf global protocol object.
@@ -836,6 +872,8 @@ pub struct DmabufParamsData {
     /// Pending planes for the params.
     modifier: Mutex<Option<Modifier>>,
     planes: Mutex<Vec<Plane>>,
+
+    diagnostics: Arc<ImportDiagnostics>,
 }
 
 /// A handle to a registered dmabuf global.
@@ -846,6 +884,100 @@ pub struct DmabufGlobal {
     id: usize,
 }
 
+/// Maximum number of failed imports kept by [`DmabufState`] for diagnostics.
+pub const MAX_RECORDED_IMPORT_FAILURES: usize = 64;
+
+/// Reason a dmabuf import failed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ImportFailureReason {
+    /// The parameters provided by the client were invalid, resulting in a protocol error.
+    #[error("invalid parameters: {0}")]
+    InvalidParams(String),
+    /// The number of planes does not match the format.
+    #[error("missing or too many planes")]
+    Incomplete,
+    /// The width or height of the buffer is invalid.
+    #[error("invalid dimensions")]
+    InvalidDimensions,
+    /// The format and plane combination is invalid.
+    #[error("invalid format and plane combination")]
+    InvalidFormat,
+    /// The format was not advertised by the global, resulting in a protocol error.
+    #[error("format not supported by the global")]
+    UnsupportedFormat,
+    /// The renderer failed to import the buffer.
+    ///
+    /// Contains a description of the renderer error, e.g. the EGL error code.
+    #[error("renderer error: {0}")]
+    Renderer(String),
+    /// The global was destroyed before the buffer was imported.
+    #[error("dmabuf global was destroyed")]
+    GlobalDestroyed,
+    /// Import failed for an implementation dependent reason, that was not further specified.
+    #[error("unspecified failure")]
+    Unspecified,
+}
+
+/// A failed dmabuf import recorded by [`DmabufState`].
+#[derive(Debug, Clone)]
+pub struct ImportFailure {
+    /// The client that tried to import the buffer
+    pub client: Option<ClientId>,
+    /// The global the import was attempted on
+    pub global: DmabufGlobal,
+    /// The format of the buffer, if it was valid
+    pub format: Option<Format>,
+    /// The size of the buffer
+    pub size: Size<i32, BufferCoords>,
+    /// The number of planes provided by the client
+    pub planes: usize,
+    /// Why the import failed
+    pub reason: ImportFailureReason,
+}
+
+impl ImportFailure {
+    fn new(
+        params: &ZwpLinuxBufferParamsV1,
+        global: DmabufGlobal,
+        dmabuf: &Dmabuf,
+        reason: ImportFailureReason,
+    ) -> Self {
+        ImportFailure {
+            client: params.client().map(|client| client.id()),
+            global,
+            format: Some(dmabuf.format()),
+            size: dmabuf.size(),
+            planes: dmabuf.num_planes(),
+            reason,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ImportDiagnostics {
+    failures: Mutex<VecDeque<ImportFailure>>,
+}
+
+impl ImportDiagnostics {
+    fn record(&self, failure: ImportFailure) {
+        tracing::warn!(
+            client = ?failure.client,
+            format = ?failure.format,
+            size = ?failure.size,
+            planes = failure.planes,
+            reason = %failure.reason,
+            "Dmabuf import failed",
+        );
+
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() >= MAX_RECORDED_IMPORT_FAILURES {
+            failures.pop_front();
+        }
+        failures.push_back(failure);
+    }
+}
+
 /// An object to allow asynchronous creation of a [`Dmabuf`] backed [`WlBuffer`].
 ///
 /// This object is [`Send`] to allow import of a [`Dmabuf`] to take place on another thread if desired.
@@ -856,6 +988,8 @@ pub struct ImportNotifier {
     display: DisplayHandle,
     dmabuf: Dmabuf,
     import: Import,
+    global: DmabufGlobal,
+    diagnostics: Arc<ImportDiagnostics>,
     drop_ignore: bool,
 }
 
@@ -908,9 +1042,11 @@ impl ImportNotifier {
                     }
                 } else {
                     tracing::error!("client was dead while creating wl_buffer resource");
-                    self.inner.post_error(
+                    post_error(
+                        &self.inner,
                         zwp_linux_buffer_params_v1::Error::InvalidWlBuffer,
                         "create_immed failed and produced an invalid wl_buffer",
+                        ErrorContext::new().request("zwp_linux_buffer_params_v1.create_immed"),
                     );
                     Err(InvalidId)
                 }
@@ -926,18 +1062,24 @@ impl ImportNotifier {
     ///
     /// This may be the result of too few or too many planes being used when creating a buffer.
     pub fn incomplete(mut self) {
-        self.inner.post_error(
+        self.record_failure(ImportFailureReason::Incomplete);
+        post_error(
+            &self.inner,
             zwp_linux_buffer_params_v1::Error::Incomplete,
             "missing or too many planes to create a buffer",
+            ErrorContext::new(),
         );
         self.drop_ignore = true;
     }
 
     /// The buffer being imported has an invalid width or height.
     pub fn invalid_dimensions(mut self) {
-        self.inner.post_error(
+        self.record_failure(ImportFailureReason::InvalidDimensions);
+        post_error(
+            &self.inner,
             zwp_linux_buffer_params_v1::Error::InvalidDimensions,
             "width or height of dmabuf is invalid",
+            ErrorContext::new(),
         );
         self.drop_ignore = true;
     }
@@ -946,32 +1088,64 @@ impl ImportNotifier {
     ///
     /// This is always a client error and will result in the client being killed.
     pub fn invalid_format(mut self) {
-        self.inner.post_error(
+        self.record_failure(ImportFailureReason::InvalidFormat);
+        post_error(
+            &self.inner,
             zwp_linux_buffer_params_v1::Error::InvalidFormat,
             "format and plane combination are not valid",
+            ErrorContext::new(),
         );
         self.drop_ignore = true;
     }
 
     /// Import failed for an implementation dependent reason.
-    pub fn failed(mut self) {
+    ///
+    /// Prefer [`ImportNotifier::failed_with_reason`] to make the failure easier to diagnose.
+    pub fn failed(self) {
+        self.failed_with_reason(ImportFailureReason::Unspecified)
+    }
+
+    /// Import failed for the given reason.
+    ///
+    /// The client is only notified of a generic failure, but the reason is logged and
+    /// recorded, so it can be queried via [`DmabufState::import_failures`].
+    pub fn failed_with_reason(mut self, reason: ImportFailureReason) {
         if matches!(self.import, Import::Falliable) {
             self.inner.failed();
         } else {
-            self.inner.post_error(
+            post_error(
+                &self.inner,
                 zwp_linux_buffer_params_v1::Error::InvalidWlBuffer,
                 "create_immed failed and produced an invalid wl_buffer",
+                ErrorContext::new()
+                    .request("zwp_linux_buffer_params_v1.create_immed")
+                    .state("reason", reason.to_string()),
             );
         }
+        self.record_failure(reason);
         self.drop_ignore = true;
     }
 
-    fn new(params: ZwpLinuxBufferParamsV1, display: DisplayHandle, dmabuf: Dmabuf, import: Import) -> Self {
+    fn record_failure(&self, reason: ImportFailureReason) {
+        self.diagnostics
+            .record(ImportFailure::new(&self.inner, self.global, &self.dmabuf, reason));
+    }
+
+    fn new(
+        params: ZwpLinuxBufferParamsV1,
+        display: DisplayHandle,
+        dmabuf: Dmabuf,
+        import: Import,
+        global: DmabufGlobal,
+        diagnostics: Arc<ImportDiagnostics>,
+    ) -> Self {
         Self {
             inner: params,
             display,
             dmabuf,
             import,
+            global,
+            diagnostics,
             drop_ignore: false,
         }
     }
@@ -1072,9 +1246,11 @@ impl DmabufParamsData {
             return true;
         }
 
-        params.post_error(
+        post_error(
+            params,
             zwp_linux_buffer_params_v1::Error::AlreadyUsed,
             "This buffer_params has already been used to create a buffer.",
+            ErrorContext::new(),
         );
 
         false
@@ -1102,12 +1278,19 @@ impl DmabufParamsData {
 
         self.used.store(true, Ordering::Relaxed);
 
+        let dimensions = Size::from((width, height));
+
         let format = match Fourcc::try_from(format) {
             Ok(format) => format,
             Err(_) => {
-                params.post_error(
+                self.invalid_params(
+                    params,
                     zwp_linux_buffer_params_v1::Error::InvalidFormat,
                     format!("Format {:x} is not supported", format),
+                    Some(ImportFailureReason::UnsupportedFormat),
+                    None,
+                    dimensions,
+                    self.planes.lock().unwrap().len(),
                 );
 
                 return None;
@@ -1117,25 +1300,40 @@ impl DmabufParamsData {
         // Validate buffer parameters:
         // 1. Must have known format
         if !self.formats.contains_key(&format) {
-            params.post_error(
+            self.invalid_params(
+                params,
                 zwp_linux_buffer_params_v1::Error::InvalidFormat,
                 format!("Format {:?}/{:x} is not supported.", format, format as u32),
+                Some(ImportFailureReason::UnsupportedFormat),
+                Some(format),
+                dimensions,
+                self.planes.lock().unwrap().len(),
             );
             return None;
         }
 
         // 2. Width and height must be positive
         if width < 1 {
-            params.post_error(
+            self.invalid_params(
+                params,
                 zwp_linux_buffer_params_v1::Error::InvalidDimensions,
                 "invalid width",
+                Some(ImportFailureReason::InvalidDimensions),
+                Some(format),
+                dimensions,
+                self.planes.lock().unwrap().len(),
             );
         }
 
         if height < 1 {
-            params.post_error(
+            self.invalid_params(
+                params,
                 zwp_linux_buffer_params_v1::Error::InvalidDimensions,
                 "invalid height",
+                Some(ImportFailureReason::InvalidDimensions),
+                Some(format),
+                dimensions,
+                self.planes.lock().unwrap().len(),
             );
         }
 
@@ -1152,9 +1350,14 @@ impl DmabufParamsData {
                 Some(e) => e,
 
                 None => {
-                    params.post_error(
+                    self.invalid_params(
+                        params,
                         zwp_linux_buffer_params_v1::Error::OutOfBounds,
                         format!("Size overflow for plane {}.", plane.plane_idx),
+                        None,
+                        Some(format),
+                        dimensions,
+                        planes.len(),
                     );
 
                     return None;
@@ -1166,18 +1369,28 @@ impl DmabufParamsData {
                 let _ = seek(&plane.fd, SeekFrom::Start(0));
 
                 if plane.offset as u64 > size {
-                    params.post_error(
+                    self.invalid_params(
+                        params,
                         zwp_linux_buffer_params_v1::Error::OutOfBounds,
                         format!("Invalid offset {} for plane {}.", plane.offset, plane.plane_idx),
+                        None,
+                        Some(format),
+                        dimensions,
+                        planes.len(),
                     );
 
                     return None;
                 }
 
                 if (plane.offset + plane.stride) as u64 > size {
-                    params.post_error(
+                    self.invalid_params(
+                        params,
                         zwp_linux_buffer_params_v1::Error::OutOfBounds,
                         format!("Invalid stride {} for plane {}.", plane.stride, plane.plane_idx),
+                        None,
+                        Some(format),
+                        dimensions,
+                        planes.len(),
                     );
 
                     return None;
@@ -1185,12 +1398,17 @@ impl DmabufParamsData {
 
                 // Planes > 0 can be subsampled, in which case 'size' will be smaller than expected.
                 if plane.plane_idx == 0 && end as u64 > size {
-                    params.post_error(
+                    self.invalid_params(
+                        params,
                         zwp_linux_buffer_params_v1::Error::OutOfBounds,
                         format!(
                             "Invalid stride ({}) or height ({}) for plane {}.",
                             plane.stride, height, plane.plane_idx
                         ),
+                        None,
+                        Some(format),
+                        dimensions,
+                        planes.len(),
                     );
 
                     return None;
@@ -1221,9 +1439,14 @@ impl DmabufParamsData {
             Some(buf) => buf,
 
             None => {
-                params.post_error(
-                    zwp_linux_buffer_params_v1::Error::Incomplete as u32,
+                self.invalid_params(
+                    params,
+                    zwp_linux_buffer_params_v1::Error::Incomplete,
                     "Provided buffer is incomplete, it has zero planes",
+                    Some(ImportFailureReason::Incomplete),
+                    Some(format),
+                    dimensions,
+                    0,
                 );
                 return None;
             }
@@ -1231,6 +1454,217 @@ impl DmabufParamsData {
 
         Some(dmabuf)
     }
+
+    /// Posts a protocol error about invalid parameters and records it for diagnostics.
+    ///
+    /// Without a more specific `reason` the failure is recorded as [`ImportFailureReason::InvalidParams`].
+    #[allow(clippy::too_many_arguments)]
+    fn invalid_params(
+        &self,
+        params: &ZwpLinuxBufferParamsV1,
+        error: zwp_linux_buffer_params_v1::Error,
+        message: impl Into<String>,
+        reason: Option<ImportFailureReason>,
+        format: Option<Fourcc>,
+        dimensions: Size<i32, BufferCoords>,
+        planes: usize,
+    ) {
+        let message = message.into();
+        let modifier = self.modifier.lock().unwrap().unwrap_or(Modifier::Invalid);
+        self.diagnostics.record(ImportFailure {
+            client: params.client().map(|client| client.id()),
+            global: DmabufGlobal { id: self.id },
+            format: format.map(|code| Format { code, modifier }),
+            size: dimensions,
+            planes,
+            reason: reason.unwrap_or_else(|| ImportFailureReason::InvalidParams(message.clone())),
+        });
+        post_error(params, error, message, ErrorContext::new());
+    }
 }
 
 id_gen!(global_id);
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, os::unix::io::AsFd};
+
+    use wayland_protocols::wp::linux_dmabuf::zv1::client::{
+        zwp_linux_buffer_params_v1::{self, ZwpLinuxBufferParamsV1},
+        zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+    };
+
+    use super::{DmabufGlobal, ImportFailureReason};
+    use crate::{
+        backend::allocator::{Format, Fourcc, Modifier},
+        wayland::test_utils::{TestFixture, TestState},
+    };
+
+    const SIZE: i32 = 64;
+
+    fn setup() -> (TestFixture, DmabufGlobal, ZwpLinuxDmabufV1) {
+        let mut fixture = TestFixture::new();
+        let dh = fixture.display.handle();
+        let global = fixture.state.dmabuf.create_global::<TestState>(
+            &dh,
+            [Format {
+                code: Fourcc::Argb8888,
+                modifier: Modifier::Linear,
+            }],
+        );
+        fixture.roundtrip();
+        let dmabuf = fixture.bind::<ZwpLinuxDmabufV1>(3);
+        (fixture, global, dmabuf)
+    }
+
+    fn params(fixture: &TestFixture, dmabuf: &ZwpLinuxDmabufV1) -> ZwpLinuxBufferParamsV1 {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&vec![0u8; (SIZE * SIZE * 4) as usize]).unwrap();
+        let params = dmabuf.create_params(&fixture.handle(), ());
+        let modifier = u64::from(Modifier::Linear);
+        params.add(
+            file.as_fd(),
+            0,
+            0,
+            SIZE as u32 * 4,
+            (modifier >> 32) as u32,
+            modifier as u32,
+        );
+        params
+    }
+
+    #[test]
+    fn renderer_failure_is_recorded() {
+        let (mut fixture, global, dmabuf) = setup();
+        let params = params(&fixture, &dmabuf);
+        params.create(
+            SIZE,
+            SIZE,
+            Fourcc::Argb8888 as u32,
+            zwp_linux_buffer_params_v1::Flags::empty(),
+        );
+        fixture.roundtrip();
+
+        let (_, notifier) = fixture.state.dmabuf_imports.pop().unwrap();
+        let client = notifier.client().unwrap().id();
+        notifier.failed_with_reason(ImportFailureReason::Renderer("EGL_BAD_MATCH".into()));
+        fixture.roundtrip();
+        assert_eq!(fixture.client.dmabuf_failed, 1);
+
+        let failures = fixture.state.dmabuf.import_failures();
+        assert_eq!(failures.len(), 1);
+        let failure = &failures[0];
+        assert_eq!(failure.client.as_ref(), Some(&client));
+        assert_eq!(failure.global, global);
+        assert_eq!(
+            failure.format,
+            Some(Format {
+                code: Fourcc::Argb8888,
+                modifier: Modifier::Linear
+            })
+        );
+        assert_eq!(failure.size, (SIZE, SIZE).into());
+        assert_eq!(failure.planes, 1);
+        assert_eq!(
+            failure.reason,
+            ImportFailureReason::Renderer("EGL_BAD_MATCH".into())
+        );
+        assert_eq!(fixture.state.dmabuf.import_failures_for_client(&client).len(), 1);
+
+        fixture.state.dmabuf.clear_import_failures();
+        assert!(fixture.state.dmabuf.import_failures().is_empty());
+    }
+
+    #[test]
+    fn create_immed_failure_reports_reason() {
+        let (mut fixture, _, dmabuf) = setup();
+        let params = params(&fixture, &dmabuf);
+        params.create_immed(
+            SIZE,
+            SIZE,
+            Fourcc::Argb8888 as u32,
+            zwp_linux_buffer_params_v1::Flags::empty(),
+            &fixture.handle(),
+            (),
+        );
+        fixture.roundtrip();
+
+        let (_, notifier) = fixture.state.dmabuf_imports.pop().unwrap();
+        notifier.failed_with_reason(ImportFailureReason::Renderer("EGL_BAD_MATCH".into()));
+        let error = fixture.protocol_error().expect("client not disconnected");
+        assert_eq!(
+            error.code,
+            zwp_linux_buffer_params_v1::Error::InvalidWlBuffer as u32
+        );
+        assert!(error.message.contains("renderer error: EGL_BAD_MATCH"));
+    }
+
+    #[test]
+    fn invalid_params_are_recorded() {
+        let (mut fixture, _, dmabuf) = setup();
+        let params = params(&fixture, &dmabuf);
+        params.create(
+            SIZE,
+            SIZE,
+            Fourcc::Xrgb8888 as u32,
+            zwp_linux_buffer_params_v1::Flags::empty(),
+        );
+        let error = fixture.protocol_error().expect("client not disconnected");
+        assert_eq!(
+            error.code,
+            zwp_linux_buffer_params_v1::Error::InvalidFormat as u32
+        );
+
+        let failures = fixture.state.dmabuf.import_failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].reason, ImportFailureReason::UnsupportedFormat);
+        assert_eq!(
+            failures[0].format.map(|format| format.code),
+            Some(Fourcc::Xrgb8888)
+        );
+        assert!(fixture.state.dmabuf_imports.is_empty());
+    }
+
+    #[test]
+    fn invalid_dimensions_are_recorded() {
+        let (mut fixture, _, dmabuf) = setup();
+        let params = params(&fixture, &dmabuf);
+        params.create(
+            0,
+            SIZE,
+            Fourcc::Argb8888 as u32,
+            zwp_linux_buffer_params_v1::Flags::empty(),
+        );
+        let error = fixture.protocol_error().expect("client not disconnected");
+        assert_eq!(
+            error.code,
+            zwp_linux_buffer_params_v1::Error::InvalidDimensions as u32
+        );
+
+        let failures = fixture.state.dmabuf.import_failures();
+        assert_eq!(failures[0].reason, ImportFailureReason::InvalidDimensions);
+        assert_eq!(failures[0].size, (0, SIZE).into());
+    }
+
+    #[test]
+    fn destroyed_global_is_recorded() {
+        let (mut fixture, global, dmabuf) = setup();
+        fixture.roundtrip();
+        let dh = fixture.display.handle();
+        fixture.state.dmabuf.destroy_global::<TestState>(&dh, global);
+
+        let params = params(&fixture, &dmabuf);
+        params.create(
+            SIZE,
+            SIZE,
+            Fourcc::Argb8888 as u32,
+            zwp_linux_buffer_params_v1::Flags::empty(),
+        );
+        fixture.roundtrip();
+        assert_eq!(fixture.client.dmabuf_failed, 1);
+        assert_eq!(
+            fixture.state.dmabuf.import_failures()[0].reason,
+            ImportFailureReason::GlobalDestroyed
+        );
+    }
+}
@@ -93,6 +93,7 @@ use wayland_server::backend::GlobalId;
 use wayland_server::{Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource};
 
 use crate::backend::drm::{DrmDevice, DrmDeviceFd, DrmNode, NodeType, PlaneClaim};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 /// Delegate type for a drm_lease global
 #[derive(Debug)]
@@ -848,27 +849,33 @@ where
                         .find(|conn| conn.known_instances.iter().any(|obj| obj == connector))
                     {
                         if connector_handles.iter().any(|handle| handle == &conn.handle) {
-                            resource.post_error(
+                            post_error(
+                                resource,
                                 wp_drm_lease_request_v1::Error::DuplicateConnector,
                                 format!("Duplicate connector: {}", conn.name),
+                                ErrorContext::new().request("wp_drm_lease_request_v1.submit"),
                             );
                             return;
                         } else {
                             connector_handles.push(conn.handle);
                         }
                     } else {
-                        resource.post_error(
+                        post_error(
+                            resource,
                             wp_drm_lease_request_v1::Error::WrongDevice,
                             "Requested lease for wrong device",
+                            ErrorContext::new().request("wp_drm_lease_request_v1.submit"),
                         );
                         return;
                     }
                 }
 
                 if connector_handles.is_empty() {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_drm_lease_request_v1::Error::EmptyLease,
                         "Lease doesn't contain any connectors",
+                        ErrorContext::new().request("wp_drm_lease_request_v1.submit"),
                     );
                 }
 
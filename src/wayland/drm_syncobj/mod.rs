@@ -3,8 +3,22 @@
 //! This module implement the `linux-drm-syncobj-v1` protocol, used to support
 //! explicit sync.
 //!
-//! Currently, the implementation here assumes acquire fences are already signalled
-//! when the surface transaction is ready. Use [`DrmSyncPointBlocker`].
+//! The acquire point of a buffer has to be handled by the compositor using a [`DrmSyncPointBlocker`].
+//! Blockers created by [`DrmSyncPoint::generate_blocker`] are released once the client's rendering
+//! is complete. Alternatively [`DrmSyncPoint::generate_submission_blocker`] only waits until the client
+//! submitted its rendering and lets the renderer wait for the acquire point on the gpu. This avoids stalling
+//! the surface transaction, if the renderer supports importing native fences.
+//!
+//! The [`DrmCompositor`](crate::backend::drm::compositor::DrmCompositor) passes an unsignaled acquire point
+//! to the kernel as an in-fence, when directly scanning out the buffer. If a fence timeout is set using
+//! [`DrmCompositor::set_fence_timeout`](crate::backend::drm::compositor::DrmCompositor::set_fence_timeout),
+//! buffers with unsignaled acquire points are composited instead or their plane keeps showing the previous
+//! buffer, like buffers with unsignaled implicit fences.
+//!
+//! Everything reading the buffer afterwards has to wait for the acquire point as well. Importing
+//! the buffer with [`import_surface`](crate::backend::renderer::utils::import_surface) inserts the wait
+//! into the renderer, so later copies like the ones of the multi-gpu renderer or
+//! [`ExportMem`](crate::backend::renderer::ExportMem) are ordered after it.
 //!
 //! The server should only expose the protocol if [`supports_syncobj_eventfd`] returns
 //! `true`. Or it won't be possible to create the blocker. This is similar to other
@@ -55,6 +69,7 @@ use super::{
     dmabuf::get_dmabuf,
 };
 use crate::backend::drm::DrmDeviceFd;
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 mod sync_point;
 pub use sync_point::*;
@@ -186,37 +201,47 @@ fn commit_hook<D: DrmSyncobjHandler>(_data: &mut D, _dh: &DisplayHandle, surface
                 let mut cached = states.cached_state.get::<DrmSyncobjCachedState>();
                 let pending = cached.pending();
                 if pending.acquire_point.is_some() && new_buffer.is_none() {
-                    syncobj_surface.post_error(
+                    post_error(
+                        syncobj_surface,
                         wp_linux_drm_syncobj_surface_v1::Error::NoBuffer as u32,
                         "acquire point without buffer".to_string(),
+                        ErrorContext::new().request("wl_surface.commit"),
                     );
                 } else if pending.acquire_point.is_some() && pending.release_point.is_none() {
-                    syncobj_surface.post_error(
+                    post_error(
+                        syncobj_surface,
                         wp_linux_drm_syncobj_surface_v1::Error::NoReleasePoint as u32,
                         "acquire point without release point".to_string(),
+                        ErrorContext::new().request("wl_surface.commit"),
                     );
                 } else if pending.acquire_point.is_none() && pending.release_point.is_some() {
-                    syncobj_surface.post_error(
+                    post_error(
+                        syncobj_surface,
                         wp_linux_drm_syncobj_surface_v1::Error::NoAcquirePoint as u32,
                         "release point without acquire point".to_string(),
+                        ErrorContext::new().request("wl_surface.commit"),
                     );
                 } else if let (Some(acquire), Some(release)) =
                     (pending.acquire_point.as_ref(), pending.release_point.as_ref())
                 {
                     if acquire.timeline == release.timeline && release.point <= acquire.point {
-                        syncobj_surface.post_error(
+                        post_error(
+                            syncobj_surface,
                             wp_linux_drm_syncobj_surface_v1::Error::ConflictingPoints as u32,
                             format!(
                                 "release point {} is not greater than acquire point {}",
                                 release.point, acquire.point
                             ),
+                            ErrorContext::new().request("wl_surface.commit"),
                         );
                     }
                     if let Some(buffer) = new_buffer {
                         if get_dmabuf(buffer).is_err() {
-                            syncobj_surface.post_error(
+                            post_error(
+                                syncobj_surface,
                                 wp_linux_drm_syncobj_surface_v1::Error::UnsupportedBuffer as u32,
                                 "sync points with non-dmabuf buffer".to_string(),
+                                ErrorContext::new().request("wl_surface.commit"),
                             );
                         }
                     }
@@ -267,13 +292,19 @@ where
                         .unwrap_or(false)
                 });
                 if already_exists {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_linux_drm_syncobj_manager_v1::Error::SurfaceExists as u32,
                         "the surface already has a syncobj_surface object associated".to_string(),
+                        ErrorContext::new().request("wp_linux_drm_syncobj_manager_v1.get_surface"),
                     );
                     return;
                 }
-                let commit_hook_id = compositor::add_pre_commit_hook::<D, _>(&surface, commit_hook);
+                let commit_hook_id = compositor::add_pre_commit_hook_with_priority::<D, _>(
+                    &surface,
+                    compositor::HookPriority::Protocol,
+                    commit_hook,
+                );
                 let destruction_hook_id =
                     compositor::add_destruction_hook::<D, _>(&surface, destruction_hook);
                 let syncobj_surface = data_init.init::<_, _>(
@@ -296,9 +327,11 @@ where
                         data_init.init::<_, _>(id, DrmSyncobjTimelineData { timeline });
                     }
                     Err(err) => {
-                        resource.post_error(
+                        post_error(
+                            resource,
                             wp_linux_drm_syncobj_manager_v1::Error::InvalidTimeline as u32,
                             format!("failed to import syncobj timeline: {}", err),
+                            ErrorContext::new().request("wp_linux_drm_syncobj_manager_v1.import_timeline"),
                         );
                     }
                 }
@@ -359,9 +392,11 @@ where
                 point_lo,
             } => {
                 let Ok(surface) = data.surface.upgrade() else {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_linux_drm_syncobj_surface_v1::Error::NoSurface,
                         "Set acquire point for destroyed surface.",
+                        ErrorContext::new().request("wp_linux_drm_syncobj_surface_v1.set_acquire_point"),
                     );
                     return;
                 };
@@ -386,9 +421,11 @@ where
                 point_lo,
             } => {
                 let Ok(surface) = data.surface.upgrade() else {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_linux_drm_syncobj_surface_v1::Error::NoSurface,
                         "Set release point for destroyed surface.",
+                        ErrorContext::new().request("wp_linux_drm_syncobj_surface_v1.set_release_point"),
                     );
                     return;
                 };
@@ -59,14 +59,28 @@ pub struct DrmSyncPoint {
 impl DrmSyncPoint {
     /// Create an eventfd that will be signaled by the syncpoint
     pub fn eventfd(&self) -> io::Result<OwnedFd> {
+        self.eventfd_with(false)
+    }
+
+    /// Create an eventfd that will be signaled once a fence was submitted for the syncpoint
+    ///
+    /// The fence itself might still be pending at that point, but can be exported
+    /// using [`DrmSyncPoint::export_sync_file`].
+    pub fn submitted_eventfd(&self) -> io::Result<OwnedFd> {
+        self.eventfd_with(true)
+    }
+
+    fn eventfd_with(&self, wait_available: bool) -> io::Result<OwnedFd> {
         let fd = rustix::event::eventfd(
             0,
             rustix::event::EventfdFlags::CLOEXEC | rustix::event::EventfdFlags::NONBLOCK,
         )?;
-        self.timeline
-            .0
-            .device
-            .syncobj_eventfd(self.timeline.0.syncobj, self.point, fd.as_fd(), false)?;
+        self.timeline.0.device.syncobj_eventfd(
+            self.timeline.0.syncobj,
+            self.point,
+            fd.as_fd(),
+            wait_available,
+        )?;
         Ok(fd)
     }
 
@@ -110,7 +124,25 @@ impl DrmSyncPoint {
     /// This will fail if `drmSyncobjEventfd` isn't supported by the device. See
     /// [`supports_syncobj_eventfd`](super::supports_syncobj_eventfd).
     pub fn generate_blocker(&self) -> io::Result<(DrmSyncPointBlocker, DrmSyncPointSource)> {
-        let fd = self.eventfd()?;
+        Self::blocker_for(self.eventfd()?)
+    }
+
+    /// Create an [`calloop::EventSource`] and [`Blocker`] released once a fence was submitted for this sync point.
+    ///
+    /// Unlike [`DrmSyncPoint::generate_blocker`] this doesn't wait for the client's rendering to complete.
+    /// Instead the renderer inserts a wait for the acquire point on the gpu when drawing the buffer and
+    /// direct scan-out passes it to the kernel as an in-fence, unless a fence timeout is set
+    /// (see [`DrmCompositor::set_fence_timeout`](crate::backend::drm::compositor::DrmCompositor::set_fence_timeout)).
+    /// The compositor can already prepare the next frame while the client is still rendering.
+    ///
+    /// This should only be used if the renderer can wait for native fences on the gpu,
+    /// e.g. [`EGLFence::supports_importing`](crate::backend::egl::fence::EGLFence::supports_importing)
+    /// for the [`GlesRenderer`](crate::backend::renderer::gles::GlesRenderer), or it will block on the cpu instead.
+    pub fn generate_submission_blocker(&self) -> io::Result<(DrmSyncPointBlocker, DrmSyncPointSource)> {
+        Self::blocker_for(self.submitted_eventfd()?)
+    }
+
+    fn blocker_for(fd: OwnedFd) -> io::Result<(DrmSyncPointBlocker, DrmSyncPointSource)> {
         let signal = Arc::new(AtomicBool::new(false));
         let blocker = DrmSyncPointBlocker {
             signal: signal.clone(),
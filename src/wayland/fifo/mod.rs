@@ -90,7 +90,8 @@ use wayland_server::{
     New, Resource, Weak,
 };
 
-use crate::wayland::compositor::{add_blocker, add_pre_commit_hook};
+use crate::wayland::compositor::{add_blocker, add_pre_commit_hook_with_priority, HookPriority};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 use super::compositor::{is_sync_subsurface, with_states, Barrier, Cacheable};
 
@@ -197,66 +198,72 @@ where
 
                 // The protocol mandates that only a single fifo object is associated with a surface at all times
                 if has_active_fifo {
-                    surface.post_error(
+                    post_error(
+                        &surface,
                         wp_fifo_manager_v1::Error::AlreadyExists,
                         "the surface has already a fifo object associated",
+                        ErrorContext::new().request("wp_fifo_manager_v1.get_fifo"),
                     );
                     return;
                 }
 
                 // Make sure we do not install the hook more then once in case the surface is being reused
                 if is_managed && is_initial {
-                    add_pre_commit_hook::<D, _>(&surface, |_, _, surface| {
-                        let fifo_barrier = with_states(surface, |states| {
-                            let fifo_state = *states.cached_state.get::<FifoCachedState>().pending();
-
-                            // The pending state will contain any previously set barrier on this surface
-                            // In case this commit updates the barrier with `set_barrier`, but also mandates to
-                            // wait for a previously set barrier it is important to first retrieve the previously
-                            // set barrier to not overwrite it with our own.
-                            let fifo_barrier = fifo_state
-                                .wait_barrier
-                                .then(|| {
+                    add_pre_commit_hook_with_priority::<D, _>(
+                        &surface,
+                        HookPriority::Protocol,
+                        |_, _, surface| {
+                            let fifo_barrier = with_states(surface, |states| {
+                                let fifo_state = *states.cached_state.get::<FifoCachedState>().pending();
+
+                                // The pending state will contain any previously set barrier on this surface
+                                // In case this commit updates the barrier with `set_barrier`, but also mandates to
+                                // wait for a previously set barrier it is important to first retrieve the previously
+                                // set barrier to not overwrite it with our own.
+                                let fifo_barrier = fifo_state
+                                    .wait_barrier
+                                    .then(|| {
+                                        states
+                                            .cached_state
+                                            .get::<FifoBarrierCachedState>()
+                                            .pending()
+                                            .barrier
+                                            .take()
+                                    })
+                                    .flatten();
+
+                                // If requested set the barrier for this commit.
+                                // The barrier will be available for the next commit requesting to wait on it
+                                // in the pending state.
+                                // The barrier will also be either put in the current state in case this commit
+                                // is not blocked or into a transaction otherwise eventually ending in the current
+                                // state when it is unblocked.
+                                if fifo_state.set_barrier {
                                     states
                                         .cached_state
                                         .get::<FifoBarrierCachedState>()
                                         .pending()
-                                        .barrier
-                                        .take()
-                                })
-                                .flatten();
-
-                            // If requested set the barrier for this commit.
-                            // The barrier will be available for the next commit requesting to wait on it
-                            // in the pending state.
-                            // The barrier will also be either put in the current state in case this commit
-                            // is not blocked or into a transaction otherwise eventually ending in the current
-                            // state when it is unblocked.
-                            if fifo_state.set_barrier {
-                                states
-                                    .cached_state
-                                    .get::<FifoBarrierCachedState>()
-                                    .pending()
-                                    .barrier = Some(Barrier::new(false));
+                                        .barrier = Some(Barrier::new(false));
+                                }
+
+                                fifo_barrier
+                            });
+
+                            if let Some(barrier) = fifo_barrier {
+                                // If multiple consecutive commits only call wait_barrier, but not set_barrier
+                                // we might end up with the same barrier in multiple commits. It could happen
+                                // that the barrier is already signaled in which case there is no need to
+                                // further delay this commit
+                                //
+                                // In addition the spec also defines that the constraint must be ignored for
+                                // sync subsurfaces
+                                let skip = barrier.is_signaled() || is_sync_subsurface(surface);
+                                if !skip {
+                                    add_blocker(surface, barrier);
+                                }
                             }
-
-                            fifo_barrier
-                        });
-
-                        if let Some(barrier) = fifo_barrier {
-                            // If multiple consecutive commits only call wait_barrier, but not set_barrier
-                            // we might end up with the same barrier in multiple commits. It could happen
-                            // that the barrier is already signaled in which case there is no need to
-                            // further delay this commit
-                            //
-                            // In addition the spec also defines that the constraint must be ignored for
-                            // sync subsurfaces
-                            let skip = barrier.is_signaled() || is_sync_subsurface(surface);
-                            if !skip {
-                                add_blocker(surface, barrier);
-                            }
-                        }
-                    });
+                        },
+                    );
                 }
 
                 let fifo: WpFifoV1 = data_init.init(id, surface.downgrade());
@@ -296,9 +303,11 @@ where
         match request {
             wp_fifo_v1::Request::SetBarrier => {
                 let Ok(surface) = data.upgrade() else {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_fifo_v1::Error::SurfaceDestroyed as u32,
                         "the surface associated with this fifo object has been destroyed".to_string(),
+                        ErrorContext::new().request("wp_fifo_v1.set_barrier"),
                     );
                     return;
                 };
@@ -308,9 +317,11 @@ where
             }
             wp_fifo_v1::Request::WaitBarrier => {
                 let Ok(surface) = data.upgrade() else {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_fifo_v1::Error::SurfaceDestroyed as u32,
                         "the surface associated with this fifo object has been destroyed".to_string(),
+                        ErrorContext::new().request("wp_fifo_v1.wait_barrier"),
                     );
                     return;
                 };
@@ -70,6 +70,7 @@ use wayland_server::{
 };
 
 use super::compositor::{with_states, SurfaceData};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 /// State of the wp_fractional_scale_manager_v1 Global
 #[derive(Debug)]
@@ -150,9 +151,11 @@ where
                 });
 
                 if already_has_fractional_scale {
-                    surface.post_error(
+                    post_error(
+                        &surface,
                         wp_fractional_scale_manager_v1::Error::FractionalScaleExists as u32,
                         "the surface already has a fractional_scale object associated".to_string(),
+                        ErrorContext::new().request("wp_fractional_scale_manager_v1.get_fractional_scale"),
                     );
                     return;
                 }
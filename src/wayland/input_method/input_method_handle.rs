@@ -14,6 +14,7 @@ use wayland_server::{
     protocol::wl_keyboard::KeymapFormat, Client, DataInit, Dispatch, DisplayHandle, Resource,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     input::{keyboard::KeyboardHandle, SeatHandler},
     utils::{alive_tracker::AliveTracker, Logical, Rectangle, SERIAL_COUNTER},
@@ -250,7 +251,12 @@ where
                     && compositor::get_role(&surface) != Some(INPUT_POPUP_SURFACE_ROLE)
                 {
                     // Protocol requires this raise an error, but doesn't define an error enum
-                    seat.post_error(0u32, "Surface already has a role.");
+                    post_error(
+                        seat,
+                        0u32,
+                        "Surface already has a role.",
+                        ErrorContext::new().request("zwp_input_method_v2.get_input_popup_surface"),
+                    );
                     return;
                 }
 
@@ -14,6 +14,7 @@ use wayland_server::{
 };
 
 use crate::input::{Seat, SeatHandler};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 use super::{KeyboardShortcutsInhibitHandler, KeyboardShortcutsInhibitState};
 
@@ -72,9 +73,12 @@ where
                     .map(|list| list.borrow().surface_has_inhibitor(&surface))
                     .unwrap_or(false)
                 {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         zwp_keyboard_shortcuts_inhibit_manager_v1::Error::AlreadyInhibited,
                         "Keyboare shortcuts for this surface are already inhibited",
+                        ErrorContext::new()
+                            .request("zwp_keyboard_shortcuts_inhibit_manager_v1.inhibit_shortcuts"),
                     );
                     return;
                 }
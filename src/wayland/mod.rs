@@ -67,6 +67,7 @@ pub mod output;
 pub mod pointer_constraints;
 pub mod pointer_gestures;
 pub mod presentation;
+pub mod protocol_error;
 pub mod relative_pointer;
 pub mod seat;
 pub mod security_context;
@@ -88,3 +89,6 @@ pub mod xdg_toplevel_icon;
 pub mod xwayland_keyboard_grab;
 #[cfg(feature = "xwayland")]
 pub mod xwayland_shell;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
@@ -22,6 +22,7 @@ use wayland_server::{
 };
 
 use super::compositor::{self, RegionAttributes};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     input::{pointer::PointerHandle, SeatHandler},
     utils::{Logical, Point},
@@ -320,9 +321,11 @@ fn add_constraint<D: SeatHandler + PointerConstraintsHandler + 'static>(
         let mut data = data.lock().unwrap();
 
         if data.constraints.contains_key(pointer) {
-            pointer_constraints.post_error(
+            post_error(
+                pointer_constraints,
                 zwp_pointer_constraints_v1::Error::AlreadyConstrained,
                 "pointer constrait already exists for surface and seat",
+                ErrorContext::new(),
             );
         } else {
             data.constraints.insert(pointer.clone(), constraint);
@@ -330,7 +333,11 @@ fn add_constraint<D: SeatHandler + PointerConstraintsHandler + 'static>(
     });
 
     if added {
-        compositor::add_post_commit_hook(surface, commit_hook::<D>);
+        compositor::add_post_commit_hook_with_priority(
+            surface,
+            compositor::HookPriority::Protocol,
+            commit_hook::<D>,
+        );
     }
 }
 
@@ -15,6 +15,11 @@
 //!     - [`PointerHandle::gesture_hold_begin`]
 //!     - [`PointerHandle::gesture_hold_end`]
 //!
+//! These work with gestures of any origin, e.g. the gesture events of an
+//! [`InputBackend`](crate::backend::input::InputBackend), which are reported by the libinput backend
+//! and, for trackpad pinch and rotation gestures, the winit backend, or gestures synthesized by the
+//! compositor itself.
+//!
 //! ```
 //! extern crate wayland_server;
 //! extern crate smithay;
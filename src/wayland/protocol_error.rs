@@ -0,0 +1,320 @@
+//! Diagnostics for protocol errors posted to clients
+//!
+//! A client receiving a protocol error is disconnected. For the user the application usually
+//! just disappears, while the reason only ends up in the log of the compositor and, if at all,
+//! of the client.
+//!
+//! Smithay attaches context to the protocol errors it posts, like the request causing the error
+//! and a summary of the relevant state, see [`post_error`]. The context is appended to the message
+//! sent to the client and logged as structured fields.
+//!
+//! Compositors get notified about protocol errors through
+//! [`ClientData::disconnected`](wayland_server::backend::ClientData::disconnected) and can build a
+//! [`ProtocolErrorReport`] to present the error to the user. As the process of the client might
+//! be gone by then, its details should be captured when the client connects using [`ClientProcess`]:
+//!
+//! ```no_run
+//! # use std::sync::OnceLock;
+//! # use smithay::reexports::wayland_server::{backend::{ClientData, ClientId, DisconnectReason}, Client, DisplayHandle};
+//! use smithay::wayland::protocol_error::{ClientProcess, ProtocolErrorReport};
+//!
+//! #[derive(Default)]
+//! struct ClientState {
+//!     process: OnceLock<ClientProcess>,
+//! }
+//!
+//! impl ClientData for ClientState {
+//!     fn disconnected(&self, client_id: ClientId, reason: DisconnectReason) {
+//!         if let Some(report) = ProtocolErrorReport::new(client_id, &reason, self.process.get().cloned()) {
+//!             // e.g. show a notification to the user
+//!             tracing::warn!("{}", report);
+//!         }
+//!     }
+//! }
+//!
+//! # let client: Client = unimplemented!();
+//! # let dh: DisplayHandle = unimplemented!();
+//! // after inserting the client into the display
+//! if let Some(process) = ClientProcess::new(&client, &dh) {
+//!     let _ = client.get_data::<ClientState>().unwrap().process.set(process);
+//! }
+//! ```
+
+use std::fmt;
+
+use tracing::warn;
+use wayland_server::{
+    backend::{ClientId, DisconnectReason},
+    Client, DisplayHandle, Resource,
+};
+
+/// Context of a protocol error
+///
+/// See [`post_error`].
+#[derive(Debug, Default, Clone)]
+pub struct ErrorContext {
+    request: Option<&'static str>,
+    state: Vec<(&'static str, String)>,
+}
+
+impl ErrorContext {
+    /// Create an empty context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the request causing the error
+    pub fn request(mut self, request: &'static str) -> Self {
+        self.request = Some(request);
+        self
+    }
+
+    /// Add a value summarizing the state relevant for the error
+    pub fn state(mut self, key: &'static str, value: impl fmt::Debug) -> Self {
+        self.state.push((key, format!("{:?}", value)));
+        self
+    }
+
+    /// Returns if the context contains no information
+    pub fn is_empty(&self) -> bool {
+        self.request.is_none() && self.state.is_empty()
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let request = self.request.map(|request| ("request", request));
+        let state = self.state.iter().map(|(key, value)| (*key, value.as_str()));
+        for (i, (key, value)) in request.into_iter().chain(state).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Post a protocol error on `resource` including the given context
+///
+/// The context is appended to the message sent to the client
+/// and logged together with the error.
+pub fn post_error<R: Resource>(
+    resource: &R,
+    code: impl Into<u32>,
+    message: impl Into<String>,
+    context: ErrorContext,
+) {
+    let code = code.into();
+    let message = message.into();
+    warn!(
+        object = %resource.id(),
+        code,
+        context = %context,
+        "Posting protocol error: {}",
+        message
+    );
+
+    if context.is_empty() {
+        resource.post_error(code, message);
+    } else {
+        resource.post_error(code, format!("{} ({})", message, context));
+    }
+}
+
+/// Process of a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientProcess {
+    /// Process id
+    pub pid: i32,
+    /// User id
+    pub uid: u32,
+    /// Name of the process, if available
+    pub name: Option<String>,
+}
+
+impl ClientProcess {
+    /// Query the process of a client
+    ///
+    /// Returns `None` if the client is already disconnected.
+    pub fn new(client: &Client, dh: &DisplayHandle) -> Option<Self> {
+        let credentials = client.get_credentials(dh).ok()?;
+        let name = std::fs::read_to_string(format!("/proc/{}/comm", credentials.pid))
+            .ok()
+            .map(|name| name.trim_end().to_string())
+            .filter(|name| !name.is_empty());
+
+        Some(ClientProcess {
+            pid: credentials.pid,
+            uid: credentials.uid,
+            name,
+        })
+    }
+}
+
+/// Details of a protocol error, that caused a client to be disconnected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolErrorReport {
+    /// The disconnected client
+    pub client: ClientId,
+    /// Process of the client, if known
+    pub process: Option<ClientProcess>,
+    /// Interface of the object the error was posted on
+    pub interface: String,
+    /// Protocol id of the object the error was posted on
+    pub object_id: u32,
+    /// Error code, specific to the interface
+    pub code: u32,
+    /// Message sent to the client, including the context
+    pub message: String,
+}
+
+impl ProtocolErrorReport {
+    /// Create a report for a disconnected client
+    ///
+    /// Returns `None` if the client wasn't disconnected because of a protocol error.
+    pub fn new(client: ClientId, reason: &DisconnectReason, process: Option<ClientProcess>) -> Option<Self> {
+        let DisconnectReason::ProtocolError(error) = reason else {
+            return None;
+        };
+
+        Some(ProtocolErrorReport {
+            client,
+            process,
+            interface: error.object_interface.clone(),
+            object_id: error.object_id,
+            code: error.code,
+            message: error.message.clone(),
+        })
+    }
+}
+
+impl fmt::Display for ProtocolErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.process {
+            Some(ClientProcess {
+                pid,
+                name: Some(name),
+                ..
+            }) => write!(f, "Application {} ({})", name, pid)?,
+            Some(ClientProcess { pid, .. }) => write!(f, "Application with pid {}", pid)?,
+            None => write!(f, "Client {:?}", self.client)?,
+        }
+        write!(
+            f,
+            " was disconnected because of protocol error {} on {}@{}: {}",
+            self.code, self.interface, self.object_id, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_client::protocol::{wl_subcompositor, wl_surface};
+    use wayland_server::{
+        backend::{protocol::ProtocolError, DisconnectReason},
+        Resource,
+    };
+
+    use super::{ClientProcess, ErrorContext, ProtocolErrorReport};
+    use crate::wayland::test_utils::TestFixture;
+
+    #[test]
+    fn context_display() {
+        let context = ErrorContext::new();
+        assert!(context.is_empty());
+        assert_eq!(context.to_string(), "");
+
+        let context = ErrorContext::new().state("size", (0, 0));
+        assert!(!context.is_empty());
+        assert_eq!(context.to_string(), "size: (0, 0)");
+
+        let context = ErrorContext::new()
+            .request("wl_surface.attach")
+            .state("offset", (1, 2))
+            .state("version", 5);
+        assert_eq!(
+            context.to_string(),
+            "request: wl_surface.attach, offset: (1, 2), version: 5"
+        );
+    }
+
+    #[test]
+    fn error_contains_request() {
+        let mut fixture = TestFixture::new();
+        let (surface, _) = fixture.create_surface();
+        surface.set_buffer_scale(0);
+
+        let error = fixture.protocol_error().expect("client not disconnected");
+        assert_eq!(error.object_interface, "wl_surface");
+        assert_eq!(error.code, wl_surface::Error::InvalidScale as u32);
+        assert_eq!(
+            error.message,
+            "Scale must be positive (request: wl_surface.set_buffer_scale)"
+        );
+    }
+
+    #[test]
+    fn error_contains_state() {
+        let mut fixture = TestFixture::new();
+        let (toplevel, _, _) = fixture.create_toplevel();
+        let (parent, _) = fixture.create_surface();
+        let subcompositor = fixture.bind::<wl_subcompositor::WlSubcompositor>(1);
+        subcompositor.get_subsurface(&toplevel, &parent, &fixture.handle(), ());
+
+        let error = fixture.protocol_error().expect("client not disconnected");
+        assert_eq!(error.object_interface, "wl_subcompositor");
+        assert_eq!(error.code, wl_subcompositor::Error::BadSurface as u32);
+        assert_eq!(
+            error.message,
+            "Surface already has a role. (request: wl_subcompositor.get_subsurface, role: Some(\"xdg_toplevel\"))"
+        );
+    }
+
+    #[test]
+    fn report() {
+        let mut fixture = TestFixture::new();
+        let (_, surface) = fixture.create_surface();
+        let client = surface.client().unwrap().id();
+        let error = ProtocolError {
+            code: 2,
+            object_id: 3,
+            object_interface: "wl_surface".into(),
+            message: "Scale must be positive".into(),
+        };
+
+        assert_eq!(
+            ProtocolErrorReport::new(client.clone(), &DisconnectReason::ConnectionClosed, None),
+            None
+        );
+
+        let process = ClientProcess {
+            pid: 42,
+            uid: 1000,
+            name: Some("weston-terminal".into()),
+        };
+        let report = ProtocolErrorReport::new(
+            client.clone(),
+            &DisconnectReason::ProtocolError(error.clone()),
+            Some(process.clone()),
+        )
+        .unwrap();
+        assert_eq!(report.interface, "wl_surface");
+        assert_eq!(report.object_id, 3);
+        assert_eq!(report.code, 2);
+        assert_eq!(
+            report.to_string(),
+            "Application weston-terminal (42) was disconnected because of protocol error 2 on wl_surface@3: Scale must be positive"
+        );
+
+        let process = ClientProcess {
+            name: None,
+            ..process
+        };
+        let report =
+            ProtocolErrorReport::new(client, &DisconnectReason::ProtocolError(error), Some(process)).unwrap();
+        assert!(report
+            .to_string()
+            .starts_with("Application with pid 42 was disconnected"));
+    }
+}
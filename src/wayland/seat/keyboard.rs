@@ -251,3 +251,233 @@ impl TryFrom<WlKeyState> for KeyState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use wayland_client::protocol::{wl_keyboard, wl_seat};
+    use wayland_server::protocol::wl_surface::WlSurface;
+
+    use crate::{
+        backend::input::{KeyState, Keycode},
+        input::keyboard::{EnterKeys, FilterResult, KeyboardHandle, XkbConfig},
+        utils::SERIAL_COUNTER,
+        wayland::test_utils::{TestFixture, TestState},
+    };
+
+    fn setup() -> (TestFixture, KeyboardHandle<TestState>, WlSurface, WlSurface) {
+        let mut fixture = TestFixture::new();
+        let keyboard = fixture
+            .state
+            .seat
+            .add_keyboard(XkbConfig::default(), 200, 25)
+            .unwrap();
+        let seat = fixture.bind::<wl_seat::WlSeat>(7);
+        seat.get_keyboard(&fixture.handle(), ());
+        let (_, first) = fixture.create_surface();
+        let (_, second) = fixture.create_surface();
+        (fixture, keyboard, first, second)
+    }
+
+    fn key(fixture: &mut TestFixture, keyboard: &KeyboardHandle<TestState>, code: u32, state: KeyState) {
+        keyboard.input::<(), _>(
+            &mut fixture.state,
+            Keycode::new(code),
+            state,
+            SERIAL_COUNTER.next_serial(),
+            0,
+            |_, _, _| FilterResult::Forward,
+        );
+    }
+
+    fn focus(fixture: &mut TestFixture, keyboard: &KeyboardHandle<TestState>, surface: &WlSurface) {
+        keyboard.set_focus(
+            &mut fixture.state,
+            Some(surface.clone()),
+            SERIAL_COUNTER.next_serial(),
+        );
+    }
+
+    // evdev codes of the keys the client received on enter
+    fn entered_keys(fixture: &mut TestFixture) -> Vec<u32> {
+        fixture.roundtrip();
+        let mut keys = fixture
+            .client
+            .keyboard_events
+            .drain(..)
+            .find_map(|event| match event {
+                wl_keyboard::Event::Enter { keys, .. } => Some(
+                    keys.chunks_exact(4)
+                        .map(|key| u32::from_ne_bytes(key.try_into().unwrap()))
+                        .collect::<Vec<_>>(),
+                ),
+                _ => None,
+            })
+            .expect("no enter event");
+        keys.sort_unstable();
+        keys
+    }
+
+    fn key_events(fixture: &mut TestFixture) -> Vec<(u32, wl_keyboard::KeyState)> {
+        fixture.roundtrip();
+        fixture
+            .client
+            .keyboard_events
+            .drain(..)
+            .filter_map(|event| match event {
+                wl_keyboard::Event::Key { key, state, .. } => Some((key, state.into_result().unwrap())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn enter_keys() {
+        let (mut fixture, keyboard, first, second) = setup();
+
+        focus(&mut fixture, &keyboard, &first);
+        key(&mut fixture, &keyboard, 38, KeyState::Pressed);
+        keyboard.input::<(), _>(
+            &mut fixture.state,
+            Keycode::new(39),
+            KeyState::Pressed,
+            SERIAL_COUNTER.next_serial(),
+            0,
+            |_, _, _| FilterResult::Intercept(()),
+        );
+        fixture.roundtrip();
+        fixture.client.keyboard_events.clear();
+
+        // intercepted keys are not reported by default
+        focus(&mut fixture, &keyboard, &second);
+        assert_eq!(entered_keys(&mut fixture), vec![30]);
+
+        keyboard.set_enter_keys(EnterKeys::Pressed);
+        focus(&mut fixture, &keyboard, &first);
+        assert_eq!(entered_keys(&mut fixture), vec![30, 31]);
+
+        keyboard.set_enter_keys(EnterKeys::None);
+        focus(&mut fixture, &keyboard, &second);
+        assert_eq!(entered_keys(&mut fixture), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn unmatched_releases() {
+        let (mut fixture, keyboard, first, second) = setup();
+        keyboard.set_enter_keys(EnterKeys::None);
+
+        // releases of keys unknown to the focus are forwarded by default
+        focus(&mut fixture, &keyboard, &first);
+        key(&mut fixture, &keyboard, 38, KeyState::Pressed);
+        focus(&mut fixture, &keyboard, &second);
+        fixture.roundtrip();
+        fixture.client.keyboard_events.clear();
+        key(&mut fixture, &keyboard, 38, KeyState::Released);
+        assert_eq!(
+            key_events(&mut fixture),
+            vec![(30, wl_keyboard::KeyState::Released)]
+        );
+
+        keyboard.set_suppress_unmatched_releases(true);
+        key(&mut fixture, &keyboard, 38, KeyState::Pressed);
+        focus(&mut fixture, &keyboard, &first);
+        fixture.roundtrip();
+        fixture.client.keyboard_events.clear();
+        key(&mut fixture, &keyboard, 38, KeyState::Released);
+        assert_eq!(key_events(&mut fixture), Vec::new());
+
+        // keys pressed while focused are still released
+        key(&mut fixture, &keyboard, 38, KeyState::Pressed);
+        key(&mut fixture, &keyboard, 38, KeyState::Released);
+        assert_eq!(
+            key_events(&mut fixture),
+            vec![
+                (30, wl_keyboard::KeyState::Pressed),
+                (30, wl_keyboard::KeyState::Released)
+            ]
+        );
+    }
+
+    #[test]
+    fn resync_releases_lost_keys() {
+        let (mut fixture, keyboard, first, _) = setup();
+        focus(&mut fixture, &keyboard, &first);
+        // shift and a
+        key(&mut fixture, &keyboard, 50, KeyState::Pressed);
+        key(&mut fixture, &keyboard, 38, KeyState::Pressed);
+        assert!(keyboard.modifier_state().shift);
+        fixture.roundtrip();
+        fixture.client.keyboard_events.clear();
+
+        // the release of shift got lost
+        keyboard.resync_pressed_keys(
+            &mut fixture.state,
+            [Keycode::new(38)],
+            SERIAL_COUNTER.next_serial(),
+            0,
+        );
+        assert!(!keyboard.modifier_state().shift);
+        assert_eq!(keyboard.pressed_keys(), [Keycode::new(38)].into_iter().collect());
+        fixture.roundtrip();
+        let events = std::mem::take(&mut fixture.client.keyboard_events);
+        assert!(matches!(
+            &events[..],
+            [
+                wl_keyboard::Event::Key { key: 42, .. },
+                wl_keyboard::Event::Modifiers {
+                    mods_depressed: 0,
+                    ..
+                },
+            ]
+        ));
+
+        // the remaining key is released as usual
+        key(&mut fixture, &keyboard, 38, KeyState::Released);
+        assert_eq!(
+            key_events(&mut fixture),
+            vec![(30, wl_keyboard::KeyState::Released)]
+        );
+    }
+    #[test]
+    fn lock_modifiers() {
+        let (mut fixture, keyboard, first, _) = setup();
+        focus(&mut fixture, &keyboard, &first);
+        fixture.roundtrip();
+        fixture.client.keyboard_events.clear();
+
+        assert!(keyboard.with_xkb_state(&mut fixture.state, |mut context| context.set_num_lock(true)));
+        assert!(keyboard.modifier_state().num_lock);
+        assert_eq!(keyboard.led_state().num, Some(true));
+        assert_eq!(
+            fixture.state.led_states.last().and_then(|leds| leds.num),
+            Some(true)
+        );
+        fixture.roundtrip();
+        assert!(fixture.client.keyboard_events.iter().any(|event| matches!(
+            event,
+            wl_keyboard::Event::Modifiers { mods_locked, .. } if *mods_locked != 0
+        )));
+
+        assert!(!keyboard.with_xkb_state(&mut fixture.state, |mut context| {
+            context.set_modifier_locked("NoSuchModifier", true)
+        }));
+        assert!(keyboard.with_xkb_state(&mut fixture.state, |mut context| context.set_num_lock(false)));
+        assert!(!keyboard.modifier_state().num_lock);
+        assert_eq!(keyboard.led_state().num, Some(false));
+    }
+
+    #[test]
+    fn locks_survive_keymap_changes() {
+        let (mut fixture, keyboard, _, _) = setup();
+        keyboard.with_xkb_state(&mut fixture.state, |mut context| {
+            context.set_caps_lock(true);
+        });
+        assert!(keyboard.modifier_state().caps_lock);
+
+        keyboard
+            .set_xkb_config(&mut fixture.state, XkbConfig::default())
+            .unwrap();
+        assert!(keyboard.modifier_state().caps_lock);
+        assert!(!keyboard.modifier_state().num_lock);
+        assert_eq!(keyboard.led_state().caps, Some(true));
+    }
+}
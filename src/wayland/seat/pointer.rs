@@ -15,6 +15,7 @@ use wayland_server::{
     Dispatch, DisplayHandle, Resource, Weak,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     backend::input::{Axis, AxisSource, ButtonState},
     input::{
@@ -38,6 +39,7 @@ struct V120UserData {
     x: i32,
     y: i32,
 }
+crate::user_data_key!(V120UserDataKey: Mutex<V120UserData> = Mutex::default());
 
 /// WlSurface role of a cursor image icon
 pub const CURSOR_IMAGE_ROLE: &str = "cursor_image";
@@ -135,11 +137,7 @@ impl WlPointerHandle {
                         }
                     } else {
                         compositor::with_states(surface, |states| {
-                            let mut data = states
-                                .data_map
-                                .get_or_insert_threadsafe(Mutex::<V120UserData>::default)
-                                .lock()
-                                .unwrap();
+                            let mut data = states.data_map.get_key::<V120UserDataKey>().lock().unwrap();
 
                             data.x += x;
                             if data.x.abs() >= 120 {
@@ -160,7 +158,7 @@ impl WlPointerHandle {
                     ptr.axis_stop(details.time, WlAxis::HorizontalScroll);
 
                     compositor::with_states(surface, |states| {
-                        if let Some(data) = states.data_map.get::<Mutex<V120UserData>>() {
+                        if let Some(data) = states.data_map.try_get_key::<V120UserDataKey>() {
                             data.lock().unwrap().x = 0;
                         }
                     });
@@ -169,7 +167,7 @@ impl WlPointerHandle {
                     ptr.axis_stop(details.time, WlAxis::VerticalScroll);
 
                     compositor::with_states(surface, |states| {
-                        if let Some(data) = states.data_map.get::<Mutex<V120UserData>>() {
+                        if let Some(data) = states.data_map.try_get_key::<V120UserDataKey>() {
                             data.lock().unwrap().y = 0;
                         }
                     });
@@ -252,7 +250,7 @@ where
         }
 
         compositor::with_states(self, |states| {
-            if let Some(data) = states.data_map.get::<Mutex<V120UserData>>() {
+            if let Some(data) = states.data_map.try_get_key::<V120UserDataKey>() {
                 *data.lock().unwrap() = Default::default();
             }
         });
@@ -382,7 +380,12 @@ where
                         if compositor::give_role(&surface, CURSOR_IMAGE_ROLE).is_err()
                             && compositor::get_role(&surface) != Some(CURSOR_IMAGE_ROLE)
                         {
-                            pointer.post_error(wl_pointer::Error::Role, "Given wl_surface has another role.");
+                            post_error(
+                                pointer,
+                                wl_pointer::Error::Role,
+                                "Given wl_surface has another role.",
+                                ErrorContext::new().request("wl_pointer.set_cursor"),
+                            );
                             return;
                         }
 
@@ -1,5 +1,6 @@
 //! Utilities for handling the security context protocol
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use std::{
     os::unix::{io::OwnedFd, net::UnixListener},
     sync::Mutex,
@@ -11,7 +12,7 @@ use wayland_protocols::wp::security_context::v1::server::{
 };
 use wayland_server::{
     backend::{ClientId, GlobalId},
-    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
 };
 
 mod listener_source;
@@ -172,9 +173,11 @@ where
         }
 
         let Some(builder) = &mut *data else {
-            context.post_error(
+            post_error(
+                context,
                 wp_security_context_v1::Error::AlreadyUsed,
                 "Security context already used",
+                ErrorContext::new().request("wp_security_context_v1.destroy"),
             );
             return;
         };
@@ -182,27 +185,33 @@ where
         match request {
             wp_security_context_v1::Request::SetSandboxEngine { name } => {
                 if builder.sandbox_engine.is_some() {
-                    context.post_error(
+                    post_error(
+                        context,
                         wp_security_context_v1::Error::AlreadySet,
                         "Security context already has a sandbox engine",
+                        ErrorContext::new().request("wp_security_context_v1.set_sandbox_engine"),
                     );
                 }
                 builder.sandbox_engine = Some(name);
             }
             wp_security_context_v1::Request::SetAppId { app_id } => {
                 if builder.app_id.is_some() {
-                    context.post_error(
+                    post_error(
+                        context,
                         wp_security_context_v1::Error::AlreadySet,
                         "Security context already has an app id",
+                        ErrorContext::new().request("wp_security_context_v1.set_app_id"),
                     );
                 }
                 builder.app_id = Some(app_id);
             }
             wp_security_context_v1::Request::SetInstanceId { instance_id } => {
                 if builder.instance_id.is_some() {
-                    context.post_error(
+                    post_error(
+                        context,
                         wp_security_context_v1::Error::AlreadySet,
                         "Security context already has an instance id",
+                        ErrorContext::new().request("wp_security_context_v1.set_instance_id"),
                     );
                 }
                 builder.instance_id = Some(instance_id);
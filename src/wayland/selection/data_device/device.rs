@@ -9,6 +9,7 @@ use wayland_server::{
     Client, DataInit, Dispatch, DisplayHandle, Resource,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     input::{pointer::Focus, Seat, SeatHandler},
     utils::Serial,
@@ -72,9 +73,11 @@ where
                     if pointer.has_grab(serial) {
                         if let Some(ref icon) = icon {
                             if compositor::give_role(icon, DND_ICON_ROLE).is_err() {
-                                resource.post_error(
+                                post_error(
+                                    resource,
                                     wl_data_device::Error::Role,
                                     "Given surface already has an other role",
+                                    ErrorContext::new().request("wl_data_device.start_drag"),
                                 );
                                 return;
                             }
@@ -82,12 +85,12 @@ where
                         // The StartDrag is in response to a pointer implicit grab, all is good
                         handler.started(source.clone(), icon.clone(), seat.clone());
                         let start_data = pointer.grab_start_data().unwrap();
-                        pointer.set_grab(
-                            handler,
-                            dnd_grab::DnDGrab::new_pointer(dh, start_data, source, origin, seat, icon),
-                            serial,
-                            Focus::Clear,
+                        let config = handler.data_device_state().dnd_grab_config();
+                        let grab = dnd_grab::DnDGrab::new_pointer(
+                            dh, start_data, source, origin, seat, icon, config,
                         );
+                        grab.grab_keyboard(handler, serial);
+                        pointer.set_grab(handler, grab, serial, Focus::Clear);
                         return;
                     }
                 }
@@ -95,9 +98,11 @@ where
                     if touch.has_grab(serial) {
                         if let Some(ref icon) = icon {
                             if compositor::give_role(icon, DND_ICON_ROLE).is_err() {
-                                resource.post_error(
+                                post_error(
+                                    resource,
                                     wl_data_device::Error::Role,
                                     "Given surface already has an other role",
+                                    ErrorContext::new().request("wl_data_device.start_drag"),
                                 );
                                 return;
                             }
@@ -105,11 +110,11 @@ where
                         // The StartDrag is in response to a touch implicit grab, all is good
                         handler.started(source.clone(), icon.clone(), seat.clone());
                         let start_data = touch.grab_start_data().unwrap();
-                        touch.set_grab(
-                            handler,
-                            dnd_grab::DnDGrab::new_touch(dh, start_data, source, origin, seat, icon),
-                            serial,
-                        );
+                        let config = handler.data_device_state().dnd_grab_config();
+                        let grab =
+                            dnd_grab::DnDGrab::new_touch(dh, start_data, source, origin, seat, icon, config);
+                        grab.grab_keyboard(handler, serial);
+                        touch.set_grab(handler, grab, serial);
                         return;
                     }
                 }
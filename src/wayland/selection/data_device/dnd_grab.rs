@@ -3,6 +3,7 @@ use std::{
     fmt,
     os::unix::io::{AsFd, OwnedFd},
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
 use wayland_server::{
@@ -16,8 +17,14 @@ use wayland_server::{
     DisplayHandle, Resource,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
+    backend::input::KeyState,
     input::{
+        keyboard::{
+            GrabStartData as KeyboardGrabStartData, KeyboardGrab, KeyboardInnerHandle, Keycode,
+            ModifiersState,
+        },
         pointer::{
             AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent,
             GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
@@ -31,7 +38,7 @@ use crate::{
     wayland::{seat::WaylandFocus, selection::seat_data::SeatData},
 };
 
-use super::{with_source_metadata, ClientDndGrabHandler, DataDeviceHandler};
+use super::{with_source_metadata, ClientDndGrabHandler, DataDeviceHandler, DndGrabConfig};
 
 /// Grab during a client-initiated DnD operation.
 pub struct DnDGrab<D: SeatHandler> {
@@ -40,11 +47,23 @@ pub struct DnDGrab<D: SeatHandler> {
     touch_start_data: Option<TouchGrabStartData<D>>,
     data_source: Option<wl_data_source::WlDataSource>,
     current_focus: Option<WlSurface>,
-    pending_offers: Vec<wl_data_offer::WlDataOffer>,
-    offer_data: Option<Arc<Mutex<OfferData>>>,
+    state: Arc<Mutex<DndState>>,
     icon: Option<WlSurface>,
     origin: WlSurface,
     seat: Seat<D>,
+    config: DndGrabConfig,
+    created: Instant,
+    // the threshold of the config was not reached yet
+    pending: bool,
+}
+
+// State shared between the pointer or touch grab and the keyboard grab of a drag
+#[derive(Debug, Default)]
+struct DndState {
+    pending_offers: Vec<wl_data_offer::WlDataOffer>,
+    offer_data: Option<Arc<Mutex<OfferData>>>,
+    modifiers: ModifiersState,
+    cancelled: bool,
 }
 
 impl<D: SeatHandler + 'static> fmt::Debug for DnDGrab<D> {
@@ -55,16 +74,18 @@ impl<D: SeatHandler + 'static> fmt::Debug for DnDGrab<D> {
             .field("touch_start_data", &self.touch_start_data)
             .field("data_source", &self.data_source)
             .field("current_focus", &self.current_focus)
-            .field("pending_offers", &self.pending_offers)
-            .field("offer_data", &self.offer_data)
+            .field("state", &self.state)
             .field("icon", &self.icon)
             .field("origin", &self.origin)
             .field("seat", &self.seat)
+            .field("config", &self.config)
+            .field("created", &self.created)
+            .field("pending", &self.pending)
             .finish()
     }
 }
 
-impl<D: SeatHandler> DnDGrab<D> {
+impl<D: SeatHandler + 'static> DnDGrab<D> {
     pub(crate) fn new_pointer(
         dh: &DisplayHandle,
         start_data: PointerGrabStartData<D>,
@@ -72,6 +93,7 @@ impl<D: SeatHandler> DnDGrab<D> {
         origin: WlSurface,
         seat: Seat<D>,
         icon: Option<WlSurface>,
+        config: DndGrabConfig,
     ) -> Self {
         Self {
             dh: dh.clone(),
@@ -79,11 +101,13 @@ impl<D: SeatHandler> DnDGrab<D> {
             touch_start_data: None,
             data_source: source,
             current_focus: None,
-            pending_offers: Vec::with_capacity(1),
-            offer_data: None,
+            state: DndState::new(&seat),
             origin,
             icon,
             seat,
+            config,
+            created: Instant::now(),
+            pending: config.threshold > 0.0,
         }
     }
 
@@ -94,6 +118,7 @@ impl<D: SeatHandler> DnDGrab<D> {
         origin: WlSurface,
         seat: Seat<D>,
         icon: Option<WlSurface>,
+        config: DndGrabConfig,
     ) -> Self {
         Self {
             dh: dh.clone(),
@@ -101,12 +126,40 @@ impl<D: SeatHandler> DnDGrab<D> {
             touch_start_data: Some(start_data),
             data_source: source,
             current_focus: None,
-            pending_offers: Vec::with_capacity(1),
-            offer_data: None,
+            state: DndState::new(&seat),
             origin,
             icon,
             seat,
+            config,
+            created: Instant::now(),
+            pending: config.threshold > 0.0,
+        }
+    }
+
+    // Whether the drag started, surfaces are only entered afterwards
+    fn update_pending(&mut self, location: Point<f64, Logical>) -> bool {
+        if self.pending {
+            let start = match (&self.pointer_start_data, &self.touch_start_data) {
+                (Some(start_data), _) => start_data.location,
+                (_, Some(start_data)) => start_data.location,
+                (None, None) => location,
+            };
+            self.pending = !self.config.drag_started(start, location, self.created.elapsed());
         }
+        !self.pending
+    }
+}
+
+impl DndState {
+    fn new<D: SeatHandler + 'static>(seat: &Seat<D>) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(DndState {
+            pending_offers: Vec::with_capacity(1),
+            modifiers: seat
+                .get_keyboard()
+                .map(|keyboard| keyboard.modifier_state())
+                .unwrap_or_default(),
+            ..Default::default()
+        }))
     }
 }
 
@@ -116,6 +169,30 @@ where
     D: SeatHandler,
     D: 'static,
 {
+    /// Grab the keyboard of the seat for the duration of the drag, unless it is already grabbed
+    ///
+    /// The keyboard grab updates the chosen action on modifier changes and cancels the drag on
+    /// the keys configured by [`ClientDndGrabHandler::cancel_key`].
+    pub(crate) fn grab_keyboard(&self, data: &mut D, serial: Serial) {
+        let Some(keyboard) = self.seat.get_keyboard() else {
+            return;
+        };
+        if keyboard.is_grabbed() {
+            return;
+        }
+
+        let grab = DnDKeyboardGrab {
+            start_data: KeyboardGrabStartData {
+                focus: keyboard.current_focus(),
+            },
+            data_source: self.data_source.clone(),
+            state: self.state.clone(),
+            touch: self.touch_start_data.is_some(),
+            seat: self.seat.clone(),
+        };
+        keyboard.set_grab(data, grab, serial);
+    }
+
     fn update_focus<F: WaylandFocus>(
         &mut self,
         focus: Option<(F, Point<f64, Logical>)>,
@@ -140,8 +217,9 @@ where
                         }
                     }
                     // disable the offers
-                    self.pending_offers.clear();
-                    if let Some(offer_data) = self.offer_data.take() {
+                    let mut state = self.state.lock().unwrap();
+                    state.pending_offers.clear();
+                    if let Some(offer_data) = state.offer_data.take() {
                         offer_data.lock().unwrap().active = false;
                     }
                 }
@@ -160,12 +238,16 @@ where
             if self.current_focus.is_none() {
                 // We entered a new surface, send the data offer if appropriate
                 if let Some(ref source) = self.data_source {
+                    let mut state = self.state.lock().unwrap();
                     let offer_data = Arc::new(Mutex::new(OfferData {
                         active: true,
                         dropped: false,
                         accepted: true,
                         finished: false,
                         chosen_action: DndAction::empty(),
+                        dnd_actions: DndAction::empty(),
+                        preferred_action: DndAction::empty(),
+                        modifiers: state.modifiers,
                     }));
                     for device in seat_data
                         .known_data_devices()
@@ -197,9 +279,9 @@ where
                         })
                         .unwrap();
                         device.enter(serial.into(), &surface, x, y, Some(&offer));
-                        self.pending_offers.push(offer);
+                        state.pending_offers.push(offer);
                     }
-                    self.offer_data = Some(offer_data);
+                    state.offer_data = Some(offer_data);
                 } else {
                     // only send if we are on a surface of the same client
                     if self.origin.id().same_client_as(&surface.id()) {
@@ -232,11 +314,14 @@ where
             .get::<RefCell<SeatData<D::SelectionUserData>>>()
             .unwrap()
             .borrow_mut();
-        let validated = if let Some(ref data) = self.offer_data {
-            let data = data.lock().unwrap();
-            data.accepted && (!data.chosen_action.is_empty())
-        } else {
-            false
+        let state = self.state.lock().unwrap();
+        let on_origin = self.config.cancel_on_origin && self.current_focus.as_ref() == Some(&self.origin);
+        let validated = match state.offer_data {
+            Some(ref data) if !state.cancelled && !on_origin => {
+                let data = data.lock().unwrap();
+                data.accepted && (!data.chosen_action.is_empty())
+            }
+            _ => false,
         };
         if let Some(ref surface) = self.current_focus {
             if self.data_source.is_some() || self.origin.id().same_client_as(&surface.id()) {
@@ -247,7 +332,7 @@ where
                 }
             }
         }
-        if let Some(ref offer_data) = self.offer_data {
+        if let Some(ref offer_data) = state.offer_data {
             let mut data = offer_data.lock().unwrap();
             if validated {
                 data.dropped = true;
@@ -263,6 +348,9 @@ where
             }
         }
 
+        let cancelled = state.cancelled;
+        std::mem::drop(state);
+
         ClientDndGrabHandler::dropped(data, self.current_focus.clone(), validated, self.seat.clone());
         self.icon = None;
         // in all cases abandon the drop
@@ -274,6 +362,19 @@ where
                 }
             }
         }
+        std::mem::drop(seat_data);
+
+        // a cancelled drag was ended by the keyboard grab, which releases itself
+        if !cancelled {
+            if let Some(keyboard) = self.seat.get_keyboard() {
+                if keyboard
+                    .with_grab(|_, grab| grab.is::<DnDKeyboardGrab<D>>())
+                    .unwrap_or(false)
+                {
+                    keyboard.unset_grab(data);
+                }
+            }
+        }
     }
 }
 
@@ -294,7 +395,9 @@ where
         // While the grab is active, no client has pointer focus
         handle.motion(data, None, event);
 
-        self.update_focus(focus, event.location, event.serial, event.time);
+        if self.update_pending(event.location) {
+            self.update_focus(focus, event.location, event.serial, event.time);
+        }
     }
 
     fn relative_motion(
@@ -448,7 +551,9 @@ where
             return;
         }
 
-        self.update_focus(focus, event.location, SERIAL_COUNTER.next_serial(), event.time);
+        if self.update_pending(event.location) {
+            self.update_focus(focus, event.location, SERIAL_COUNTER.next_serial(), event.time);
+        }
     }
 
     fn frame(
@@ -496,6 +601,117 @@ where
     }
 }
 
+/// Keyboard grab during a client-initiated DnD operation.
+///
+/// Forwards all input to the focused client, except for keys cancelling the drag.
+pub(crate) struct DnDKeyboardGrab<D: SeatHandler> {
+    start_data: KeyboardGrabStartData<D>,
+    data_source: Option<WlDataSource>,
+    state: Arc<Mutex<DndState>>,
+    touch: bool,
+    seat: Seat<D>,
+}
+
+impl<D: SeatHandler + 'static> fmt::Debug for DnDKeyboardGrab<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnDKeyboardGrab")
+            .field("start_data", &self.start_data)
+            .field("data_source", &self.data_source)
+            .field("state", &self.state)
+            .field("touch", &self.touch)
+            .field("seat", &self.seat)
+            .finish()
+    }
+}
+
+impl<D> DnDKeyboardGrab<D>
+where
+    D: DataDeviceHandler,
+    D: SeatHandler,
+    D: 'static,
+{
+    fn modifiers_changed(&mut self, data: &mut D, modifiers: ModifiersState) {
+        let mut state = self.state.lock().unwrap();
+        state.modifiers = modifiers;
+
+        let (Some(offer_data), Some(source)) = (state.offer_data.as_ref(), self.data_source.as_ref()) else {
+            return;
+        };
+        let mut offer_data = offer_data.lock().unwrap();
+        offer_data.modifiers = modifiers;
+        if let Some(chosen_action) = offer_data.update_action(data, source) {
+            for offer in &state.pending_offers {
+                if offer.version() >= wl_data_offer::EVT_ACTION_SINCE {
+                    offer.action(chosen_action);
+                }
+            }
+            source.action(chosen_action);
+        }
+    }
+
+    fn cancel(&mut self, data: &mut D, serial: Serial, time: u32) {
+        self.state.lock().unwrap().cancelled = true;
+
+        // The drag grab is still active, as it releases this grab when ending.
+        // Ending it performs the drop, which cancels the drag now.
+        if self.touch {
+            if let Some(touch) = self.seat.get_touch() {
+                touch.unset_grab(data);
+            }
+        } else if let Some(pointer) = self.seat.get_pointer() {
+            pointer.unset_grab(data, serial, time);
+        }
+    }
+}
+
+impl<D> KeyboardGrab<D> for DnDKeyboardGrab<D>
+where
+    D: DataDeviceHandler,
+    D: SeatHandler,
+    D: 'static,
+{
+    fn input(
+        &mut self,
+        data: &mut D,
+        handle: &mut KeyboardInnerHandle<'_, D>,
+        keycode: Keycode,
+        state: KeyState,
+        modifiers: Option<ModifiersState>,
+        serial: Serial,
+        time: u32,
+    ) {
+        if state == KeyState::Pressed {
+            let keysym = handle.keysym_handle(keycode).modified_sym();
+            if ClientDndGrabHandler::cancel_key(data, keysym, self.seat.clone()) {
+                self.cancel(data, serial, time);
+                handle.unset_grab(self, data, serial, false);
+                return;
+            }
+        }
+
+        if let Some(modifiers) = modifiers {
+            self.modifiers_changed(data, modifiers);
+        }
+        handle.input(data, keycode, state, modifiers, serial, time);
+    }
+
+    fn set_focus(
+        &mut self,
+        data: &mut D,
+        handle: &mut KeyboardInnerHandle<'_, D>,
+        focus: Option<<D as SeatHandler>::KeyboardFocus>,
+        serial: Serial,
+    ) {
+        handle.set_focus(data, focus, serial);
+    }
+
+    fn start_data(&self) -> &KeyboardGrabStartData<D> {
+        &self.start_data
+    }
+
+    fn unset(&mut self, _data: &mut D) {}
+}
+
 #[derive(Debug)]
 struct OfferData {
     active: bool,
@@ -503,6 +719,34 @@ struct OfferData {
     accepted: bool,
     finished: bool,
     chosen_action: DndAction,
+    dnd_actions: DndAction,
+    preferred_action: DndAction,
+    modifiers: ModifiersState,
+}
+
+impl OfferData {
+    // Returns the newly chosen action, if it changed
+    fn update_action<D: DataDeviceHandler>(
+        &mut self,
+        handler: &mut D,
+        source: &WlDataSource,
+    ) -> Option<DndAction> {
+        let source_actions =
+            with_source_metadata(source, |meta| meta.dnd_action).unwrap_or_else(|_| DndAction::empty());
+        let possible_actions = source_actions & self.dnd_actions;
+        let chosen_action =
+            handler.action_choice_with_modifiers(possible_actions, self.preferred_action, &self.modifiers);
+        // check that the user provided callback respects that one precise action should be chosen
+        debug_assert!(
+            [DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask].contains(&chosen_action),
+            "Only one precise action should be chosen"
+        );
+        if chosen_action == self.chosen_action {
+            return None;
+        }
+        self.chosen_action = chosen_action;
+        Some(chosen_action)
+    }
 }
 
 #[derive(Debug)]
@@ -578,30 +822,38 @@ where
         }
         Request::Finish => {
             if !data.active {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish,
                     "Cannot finish a data offer that is no longer active.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
             if !data.accepted {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish,
                     "Cannot finish a data offer that has not been accepted.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
             if !data.dropped {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish,
                     "Cannot finish a data offer that has not been dropped.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
             if data.chosen_action.is_empty() {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish,
                     "Cannot finish a data offer with no valid action.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
@@ -620,21 +872,18 @@ where
             if ![DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask]
                 .contains(&preferred_action)
             {
-                offer.post_error(wl_data_offer::Error::InvalidAction, "Invalid preferred action.");
+                post_error(
+                    offer,
+                    wl_data_offer::Error::InvalidAction,
+                    "Invalid preferred action.",
+                    ErrorContext::new().request("wl_data_offer.set_actions"),
+                );
                 return;
             }
 
-            let source_actions =
-                with_source_metadata(source, |meta| meta.dnd_action).unwrap_or_else(|_| DndAction::empty());
-            let possible_actions = source_actions & dnd_actions;
-            let chosen_action = handler.action_choice(possible_actions, preferred_action);
-            // check that the user provided callback respects that one precise action should be chosen
-            debug_assert!(
-                [DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask].contains(&chosen_action),
-                "Only one precise action should be chosen"
-            );
-            if chosen_action != data.chosen_action {
-                data.chosen_action = chosen_action;
+            data.dnd_actions = dnd_actions;
+            data.preferred_action = preferred_action;
+            if let Some(chosen_action) = data.update_action(handler, source) {
                 offer.action(chosen_action);
                 source.action(chosen_action);
             }
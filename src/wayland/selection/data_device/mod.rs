@@ -19,6 +19,12 @@
 //! - the freestanding function [`start_dnd`] allows you to initiate a drag'n'drop event from the compositor
 //!   itself and receive interactions of clients with it via an other dedicated callback.
 //!
+//! During client initiated drag'n'drop the keyboard is grabbed, unless it already is: modifier changes
+//! update the chosen action (see [`DataDeviceHandler::action_choice_with_modifiers`]) and pressing
+//! a cancel key (see [`ClientDndGrabHandler::cancel_key`]) cancels the drag. A movement threshold
+//! before the drag starts and cancelling by dropping on the origin surface can be configured
+//! with [`DataDeviceState::set_dnd_grab_config`].
+//!
 //! The module defines the role `"dnd_icon"` that is assigned to surfaces used as drag'n'drop icons.
 //!
 //! ## Initialization
@@ -69,8 +75,10 @@
 //! ```
 
 use std::{
+    borrow::Cow,
     cell::{Ref, RefCell},
     os::unix::io::OwnedFd,
+    time::Duration,
 };
 
 use tracing::instrument;
@@ -86,11 +94,12 @@ use wayland_server::{
 
 use crate::{
     input::{
+        keyboard::{Keysym, ModifiersState},
         pointer::{Focus, GrabStartData as PointerGrabStartData},
         touch::GrabStartData as TouchGrabStartData,
         Seat, SeatHandler,
     },
-    utils::Serial,
+    utils::{Logical, Point, Serial},
     wayland::seat::WaylandFocus,
 };
 
@@ -119,6 +128,26 @@ pub trait DataDeviceHandler: Sized + SelectionHandler + ClientDndGrabHandler + S
     fn action_choice(&mut self, available: DndAction, preferred: DndAction) -> DndAction {
         default_action_chooser(available, preferred)
     }
+
+    /// Action chooser for client initiated DnD, taking the pressed keyboard modifiers into account
+    ///
+    /// Called whenever the actions offered by the target or the modifiers change during the drag.
+    /// The default implementation chooses copy while ctrl and move while shift is pressed,
+    /// if available, and falls back to [`DataDeviceHandler::action_choice`] otherwise.
+    fn action_choice_with_modifiers(
+        &mut self,
+        available: DndAction,
+        preferred: DndAction,
+        modifiers: &ModifiersState,
+    ) -> DndAction {
+        if modifiers.ctrl && available.contains(DndAction::Copy) {
+            DndAction::Copy
+        } else if modifiers.shift && available.contains(DndAction::Move) {
+            DndAction::Move
+        } else {
+            self.action_choice(available, preferred)
+        }
+    }
 }
 
 /// Events that are generated during client initiated drag'n'drop
@@ -145,6 +174,16 @@ pub trait ClientDndGrabHandler: SeatHandler + Sized {
     ///                 was cancelled or otherwise not successful.
     /// * `seat` - The seat on which the DnD action was finished.
     fn dropped(&mut self, target: Option<WlSurface>, validated: bool, seat: Seat<Self>) {}
+
+    /// Whether pressing `keysym` cancels the drag'n'drop operation
+    ///
+    /// Only called if the keyboard was not grabbed when the drag started.
+    /// If this returns `true`, the drag is cancelled and [`ClientDndGrabHandler::dropped`]
+    /// is called while the keyboard is still being processed, so it must not access the keyboard
+    /// of the seat. The default implementation cancels on `Escape`.
+    fn cancel_key(&mut self, keysym: Keysym, seat: Seat<Self>) -> bool {
+        keysym == Keysym::Escape
+    }
 }
 
 /// Event generated by the interactions of clients with a server initiated drag'n'drop
@@ -191,10 +230,43 @@ pub trait ServerDndGrabHandler: SeatHandler {
     fn finished(&mut self, seat: Seat<Self>) {}
 }
 
+/// Behavior of drag'n'drop grabs
+///
+/// Applies to client initiated drags as well as drags started with [`start_dnd`], where the surface
+/// focused when the grab started is the origin of the drag.
+///
+/// The default starts dragging right away and never cancels a drop on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DndGrabConfig {
+    /// Distance in logical pixels the pointer or touch point has to move away from where it was
+    /// pressed, before the drag starts
+    ///
+    /// Until the drag starts no surface is entered, releasing cancels the drag.
+    pub threshold: f64,
+    /// Time after which the drag starts with the next motion, even if it didn't reach the `threshold`
+    pub hold_time: Option<Duration>,
+    /// Whether dropping on the surface the drag originated from cancels the drag
+    pub cancel_on_origin: bool,
+}
+
+impl DndGrabConfig {
+    // Whether a drag pressed at `start` and held for `held` starts when moved to `location`
+    pub(crate) fn drag_started(
+        &self,
+        start: Point<f64, Logical>,
+        location: Point<f64, Logical>,
+        held: Duration,
+    ) -> bool {
+        let delta = location - start;
+        delta.x.hypot(delta.y) >= self.threshold || self.hold_time.is_some_and(|hold_time| held >= hold_time)
+    }
+}
+
 /// State of data device
 #[derive(Debug)]
 pub struct DataDeviceState {
     manager_global: GlobalId,
+    dnd_grab_config: DndGrabConfig,
 }
 
 impl DataDeviceState {
@@ -206,13 +278,28 @@ impl DataDeviceState {
     {
         let manager_global = display.create_global::<D, WlDataDeviceManager, _>(3, ());
 
-        Self { manager_global }
+        Self {
+            manager_global,
+            dnd_grab_config: DndGrabConfig::default(),
+        }
     }
 
     /// [WlDataDeviceManager] GlobalId getter
     pub fn global(&self) -> GlobalId {
         self.manager_global.clone()
     }
+
+    /// Returns the behavior of drag'n'drop grabs
+    pub fn dnd_grab_config(&self) -> DndGrabConfig {
+        self.dnd_grab_config
+    }
+
+    /// Change the behavior of drag'n'drop grabs
+    ///
+    /// Only drags started afterwards are affected.
+    pub fn set_dnd_grab_config(&mut self, config: DndGrabConfig) {
+        self.dnd_grab_config = config;
+    }
 }
 
 /// A simple action chooser for DnD negociation
@@ -395,17 +482,42 @@ pub fn start_dnd<D>(
 {
     seat.user_data()
         .insert_if_missing(|| RefCell::new(SeatData::<D::SelectionUserData>::new()));
+    let config = data.data_device_state().dnd_grab_config();
     if let (Some(pointer_start_data), Some(pointer)) = (pointer_start_data, seat.get_pointer()) {
+        let origin = pointer_start_data
+            .focus
+            .as_ref()
+            .and_then(|(focus, _)| focus.wl_surface())
+            .map(Cow::into_owned);
         pointer.set_grab(
             data,
-            server_dnd_grab::ServerDnDGrab::new_pointer(dh, pointer_start_data, metadata, seat.clone()),
+            server_dnd_grab::ServerDnDGrab::new_pointer(
+                dh,
+                pointer_start_data,
+                metadata,
+                origin,
+                seat.clone(),
+                config,
+            ),
             serial,
             Focus::Keep,
         );
     } else if let (Some(touch_start_data), Some(touch)) = (touch_start_data, seat.get_touch()) {
+        let origin = touch_start_data
+            .focus
+            .as_ref()
+            .and_then(|(focus, _)| focus.wl_surface())
+            .map(Cow::into_owned);
         touch.set_grab(
             data,
-            server_dnd_grab::ServerDnDGrab::new_touch(dh, touch_start_data, metadata, seat.clone()),
+            server_dnd_grab::ServerDnDGrab::new_touch(
+                dh,
+                touch_start_data,
+                metadata,
+                origin,
+                seat.clone(),
+                config,
+            ),
             serial,
         );
     }
@@ -520,3 +632,317 @@ macro_rules! delegate_data_device {
         ] => $crate::wayland::selection::data_device::DataDeviceState);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wayland_client::protocol::{wl_data_device_manager, wl_data_source, wl_seat, wl_surface};
+    use wayland_server::protocol::{wl_data_device_manager::DndAction, wl_surface::WlSurface};
+
+    use super::{start_dnd, DndGrabConfig, SourceMetadata};
+    use crate::{
+        backend::input::{ButtonState, KeyState, Keycode},
+        input::{
+            keyboard::{FilterResult, KeyboardHandle, XkbConfig},
+            pointer::{ButtonEvent, GrabStartData, MotionEvent, PointerHandle},
+        },
+        utils::{Serial, SERIAL_COUNTER},
+        wayland::test_utils::{TestFixture, TestState},
+    };
+
+    const BTN_LEFT: u32 = 0x110;
+
+    struct Drag {
+        fixture: TestFixture,
+        pointer: PointerHandle<TestState>,
+        device_manager: wl_data_device_manager::WlDataDeviceManager,
+        device: wayland_client::protocol::wl_data_device::WlDataDevice,
+        surface: wl_surface::WlSurface,
+        server_surface: WlSurface,
+    }
+
+    impl Drag {
+        fn new(config: DndGrabConfig) -> Self {
+            let mut fixture = TestFixture::new();
+            fixture.state.data_device.set_dnd_grab_config(config);
+            let pointer = fixture.state.seat.add_pointer();
+            let seat = fixture.bind::<wl_seat::WlSeat>(1);
+            let device_manager = fixture.bind::<wl_data_device_manager::WlDataDeviceManager>(3);
+            let device = device_manager.get_data_device(&seat, &fixture.handle(), ());
+            let (surface, server_surface) = fixture.create_surface();
+            fixture.map(&surface, 100, 100);
+
+            let mut drag = Drag {
+                fixture,
+                pointer,
+                device_manager,
+                device,
+                surface,
+                server_surface,
+            };
+            drag.motion((10.0, 10.0));
+            drag
+        }
+
+        fn motion(&mut self, location: (f64, f64)) {
+            let event = MotionEvent {
+                location: location.into(),
+                serial: SERIAL_COUNTER.next_serial(),
+                time: 0,
+            };
+            let focus = Some((self.server_surface.clone(), (0.0, 0.0).into()));
+            self.pointer.motion(&mut self.fixture.state, focus, &event);
+            self.fixture.roundtrip();
+        }
+
+        fn button(&mut self, state: ButtonState) -> Serial {
+            let serial = SERIAL_COUNTER.next_serial();
+            let event = ButtonEvent {
+                serial,
+                time: 0,
+                button: BTN_LEFT,
+                state,
+            };
+            self.pointer.button(&mut self.fixture.state, &event);
+            self.fixture.roundtrip();
+            serial
+        }
+
+        fn start_client_drag(&mut self, actions: wl_data_device_manager::DndAction) {
+            let serial = self.button(ButtonState::Pressed);
+            let source = self.device_manager.create_data_source(&self.fixture.handle(), ());
+            source.offer("text/plain".into());
+            source.set_actions(actions);
+            self.device
+                .start_drag(Some(&source), &self.surface, None, serial.into());
+            self.fixture.roundtrip();
+        }
+
+        fn start_server_drag(&mut self) {
+            let serial = self.button(ButtonState::Pressed);
+            let dh = self.fixture.display.handle();
+            let seat = self.fixture.state.seat.clone();
+            let start_data = GrabStartData {
+                focus: Some((self.server_surface.clone(), (0.0, 0.0).into())),
+                button: BTN_LEFT,
+                location: (10.0, 10.0).into(),
+            };
+            let metadata = SourceMetadata {
+                mime_types: vec!["text/plain".into()],
+                dnd_action: DndAction::Copy,
+            };
+            start_dnd(
+                &dh,
+                &seat,
+                &mut self.fixture.state,
+                serial,
+                Some(start_data),
+                None,
+                metadata,
+            );
+        }
+
+        fn key(&mut self, keyboard: &KeyboardHandle<TestState>, code: u32, state: KeyState) {
+            keyboard.input::<(), _>(
+                &mut self.fixture.state,
+                Keycode::new(code),
+                state,
+                SERIAL_COUNTER.next_serial(),
+                0,
+                |_, _, _| FilterResult::Forward,
+            );
+            self.fixture.roundtrip();
+        }
+
+        // Actions chosen for the source of the drag since the last call
+        fn source_actions(&mut self) -> Vec<wl_data_device_manager::DndAction> {
+            self.fixture
+                .client
+                .source_events
+                .drain(..)
+                .filter_map(|event| match event {
+                    wl_data_source::Event::Action { dnd_action } => dnd_action.into_result().ok(),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        // Accept the offer of the last entered surface and drop on it
+        fn accept_and_drop(&mut self) {
+            let offer = self.fixture.client.dnd_entered.last().cloned().flatten().unwrap();
+            offer.accept(0, Some("text/plain".into()));
+            offer.set_actions(
+                wl_data_device_manager::DndAction::Copy,
+                wl_data_device_manager::DndAction::Copy,
+            );
+            self.fixture.roundtrip();
+            self.button(ButtonState::Released);
+        }
+    }
+
+    #[test]
+    fn drag_threshold_and_hold_time() {
+        let config = DndGrabConfig {
+            threshold: 10.0,
+            hold_time: Some(Duration::from_millis(500)),
+            cancel_on_origin: false,
+        };
+        let start = (10.0, 10.0).into();
+        assert!(!config.drag_started(start, (15.0, 15.0).into(), Duration::ZERO));
+        assert!(config.drag_started(start, (20.0, 10.0).into(), Duration::ZERO));
+        assert!(config.drag_started(start, (15.0, 15.0).into(), Duration::from_millis(500)));
+        assert!(DndGrabConfig::default().drag_started(start, start, Duration::ZERO));
+    }
+
+    #[test]
+    fn client_drag_threshold() {
+        let mut drag = Drag::new(DndGrabConfig {
+            threshold: 10.0,
+            ..Default::default()
+        });
+        drag.start_client_drag(wl_data_device_manager::DndAction::Copy);
+
+        drag.motion((15.0, 15.0));
+        assert!(drag.fixture.client.dnd_entered.is_empty());
+        drag.motion((25.0, 10.0));
+        assert_eq!(drag.fixture.client.dnd_entered.len(), 1);
+
+        drag.accept_and_drop();
+        assert_eq!(drag.fixture.state.dnd_dropped, vec![true]);
+        assert_eq!(drag.fixture.client.dnd_dropped, 1);
+    }
+
+    #[test]
+    fn client_drag_cancel_on_origin() {
+        let mut drag = Drag::new(DndGrabConfig {
+            cancel_on_origin: true,
+            ..Default::default()
+        });
+        drag.start_client_drag(wl_data_device_manager::DndAction::Copy);
+        drag.motion((20.0, 20.0));
+        assert_eq!(drag.fixture.client.dnd_entered.len(), 1);
+
+        drag.accept_and_drop();
+        assert_eq!(drag.fixture.state.dnd_dropped, vec![false]);
+        assert_eq!(drag.fixture.client.dnd_dropped, 0);
+    }
+
+    #[test]
+    fn client_drag_escape_cancels() {
+        let mut drag = Drag::new(DndGrabConfig::default());
+        let keyboard = drag
+            .fixture
+            .state
+            .seat
+            .add_keyboard(XkbConfig::default(), 200, 25)
+            .unwrap();
+        drag.start_client_drag(wl_data_device_manager::DndAction::Copy);
+        assert!(keyboard.is_grabbed());
+        drag.motion((20.0, 20.0));
+        assert_eq!(drag.fixture.client.dnd_entered.len(), 1);
+
+        // Escape
+        drag.key(&keyboard, 9, KeyState::Pressed);
+        assert_eq!(drag.fixture.state.dnd_dropped, vec![false]);
+        assert!(!drag.pointer.is_grabbed());
+        assert!(!keyboard.is_grabbed());
+        assert!(drag
+            .fixture
+            .client
+            .source_events
+            .iter()
+            .any(|event| matches!(event, wl_data_source::Event::Cancelled)));
+        assert_eq!(drag.fixture.client.dnd_dropped, 0);
+    }
+
+    #[test]
+    fn client_drag_ends_keyboard_grab() {
+        let mut drag = Drag::new(DndGrabConfig::default());
+        let keyboard = drag
+            .fixture
+            .state
+            .seat
+            .add_keyboard(XkbConfig::default(), 200, 25)
+            .unwrap();
+        drag.start_client_drag(wl_data_device_manager::DndAction::Copy);
+        drag.motion((20.0, 20.0));
+        assert!(keyboard.is_grabbed());
+
+        drag.accept_and_drop();
+        assert_eq!(drag.fixture.state.dnd_dropped, vec![true]);
+        assert!(!keyboard.is_grabbed());
+    }
+
+    #[test]
+    fn client_drag_modifiers_choose_action() {
+        let mut drag = Drag::new(DndGrabConfig::default());
+        let keyboard = drag
+            .fixture
+            .state
+            .seat
+            .add_keyboard(XkbConfig::default(), 200, 25)
+            .unwrap();
+        drag.start_client_drag(
+            wl_data_device_manager::DndAction::Copy | wl_data_device_manager::DndAction::Move,
+        );
+        drag.motion((20.0, 20.0));
+
+        let offer = drag.fixture.client.dnd_entered.last().cloned().flatten().unwrap();
+        offer.accept(0, Some("text/plain".into()));
+        offer.set_actions(
+            wl_data_device_manager::DndAction::Copy | wl_data_device_manager::DndAction::Move,
+            wl_data_device_manager::DndAction::Move,
+        );
+        drag.fixture.roundtrip();
+        assert_eq!(
+            drag.source_actions(),
+            vec![wl_data_device_manager::DndAction::Move]
+        );
+
+        // Left Ctrl
+        drag.key(&keyboard, 37, KeyState::Pressed);
+        assert_eq!(
+            drag.source_actions(),
+            vec![wl_data_device_manager::DndAction::Copy]
+        );
+        drag.key(&keyboard, 37, KeyState::Released);
+        assert_eq!(
+            drag.source_actions(),
+            vec![wl_data_device_manager::DndAction::Move]
+        );
+    }
+
+    #[test]
+    fn server_drag_threshold() {
+        let mut drag = Drag::new(DndGrabConfig {
+            threshold: 10.0,
+            ..Default::default()
+        });
+        drag.start_server_drag();
+
+        drag.motion((15.0, 15.0));
+        assert!(drag.fixture.client.dnd_entered.is_empty());
+        drag.motion((25.0, 10.0));
+        assert_eq!(drag.fixture.client.dnd_entered.len(), 1);
+
+        drag.accept_and_drop();
+        assert_eq!(drag.fixture.state.server_dnd_cancelled, 0);
+        assert_eq!(drag.fixture.client.dnd_dropped, 1);
+    }
+
+    #[test]
+    fn server_drag_cancel_on_origin() {
+        let mut drag = Drag::new(DndGrabConfig {
+            cancel_on_origin: true,
+            ..Default::default()
+        });
+        drag.start_server_drag();
+        drag.motion((20.0, 20.0));
+        assert_eq!(drag.fixture.client.dnd_entered.len(), 1);
+
+        drag.accept_and_drop();
+        assert_eq!(drag.fixture.state.server_dnd_cancelled, 1);
+        assert_eq!(drag.fixture.client.dnd_dropped, 0);
+    }
+}
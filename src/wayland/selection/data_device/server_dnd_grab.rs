@@ -3,6 +3,7 @@ use std::{
     fmt,
     os::unix::io::OwnedFd,
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
 use wayland_server::{
@@ -17,6 +18,7 @@ use wayland_server::{
 };
 
 use crate::utils::{Logical, Point, Serial, SERIAL_COUNTER};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::seat::WaylandFocus;
 use crate::{
     input::{
@@ -32,7 +34,7 @@ use crate::{
     wayland::selection::seat_data::SeatData,
 };
 
-use super::{DataDeviceHandler, DataDeviceUserData, ServerDndGrabHandler, SourceMetadata};
+use super::{DataDeviceHandler, DataDeviceUserData, DndGrabConfig, ServerDndGrabHandler, SourceMetadata};
 
 /// Grab during a compositor-initiated DnD operation.
 pub struct ServerDnDGrab<D: SeatHandler> {
@@ -43,7 +45,12 @@ pub struct ServerDnDGrab<D: SeatHandler> {
     current_focus: Option<WlSurface>,
     pending_offers: Vec<wl_data_offer::WlDataOffer>,
     offer_data: Option<Arc<Mutex<ServerDndOfferData>>>,
+    origin: Option<WlSurface>,
     seat: Seat<D>,
+    config: DndGrabConfig,
+    created: Instant,
+    // the threshold of the config was not reached yet
+    pending: bool,
 }
 
 impl<D: SeatHandler + 'static> fmt::Debug for ServerDnDGrab<D> {
@@ -56,7 +63,11 @@ impl<D: SeatHandler + 'static> fmt::Debug for ServerDnDGrab<D> {
             .field("current_focus", &self.current_focus)
             .field("pending_offers", &self.pending_offers)
             .field("offer_data", &self.offer_data)
+            .field("origin", &self.origin)
             .field("seat", &self.seat)
+            .field("config", &self.config)
+            .field("created", &self.created)
+            .field("pending", &self.pending)
             .finish()
     }
 }
@@ -66,7 +77,9 @@ impl<D: SeatHandler> ServerDnDGrab<D> {
         dh: &DisplayHandle,
         start_data: PointerGrabStartData<D>,
         metadata: super::SourceMetadata,
+        origin: Option<WlSurface>,
         seat: Seat<D>,
+        config: DndGrabConfig,
     ) -> Self {
         Self {
             dh: dh.clone(),
@@ -76,7 +89,11 @@ impl<D: SeatHandler> ServerDnDGrab<D> {
             current_focus: None,
             pending_offers: Vec::with_capacity(1),
             offer_data: None,
+            origin,
             seat,
+            config,
+            created: Instant::now(),
+            pending: config.threshold > 0.0,
         }
     }
 
@@ -84,7 +101,9 @@ impl<D: SeatHandler> ServerDnDGrab<D> {
         dh: &DisplayHandle,
         start_data: TouchGrabStartData<D>,
         metadata: super::SourceMetadata,
+        origin: Option<WlSurface>,
         seat: Seat<D>,
+        config: DndGrabConfig,
     ) -> Self {
         Self {
             dh: dh.clone(),
@@ -94,7 +113,11 @@ impl<D: SeatHandler> ServerDnDGrab<D> {
             current_focus: None,
             pending_offers: Vec::with_capacity(1),
             offer_data: None,
+            origin,
             seat,
+            config,
+            created: Instant::now(),
+            pending: config.threshold > 0.0,
         }
     }
 }
@@ -105,6 +128,19 @@ where
     D: SeatHandler,
     D: 'static,
 {
+    // Whether the drag started, surfaces are only entered afterwards
+    fn update_pending(&mut self, location: Point<f64, Logical>) -> bool {
+        if self.pending {
+            let start = match (&self.pointer_start_data, &self.touch_start_data) {
+                (Some(start_data), _) => start_data.location,
+                (_, Some(start_data)) => start_data.location,
+                (None, None) => location,
+            };
+            self.pending = !self.config.drag_started(start, location, self.created.elapsed());
+        }
+        !self.pending
+    }
+
     fn update_focus<F: WaylandFocus>(
         &mut self,
         focus: Option<(F, Point<f64, Logical>)>,
@@ -205,11 +241,14 @@ where
             .get::<RefCell<SeatData<D::SelectionUserData>>>()
             .unwrap()
             .borrow_mut();
-        let validated = if let Some(ref data) = self.offer_data {
-            let data = data.lock().unwrap();
-            data.accepted && (!data.chosen_action.is_empty())
-        } else {
-            false
+        let on_origin =
+            self.config.cancel_on_origin && self.current_focus.is_some() && self.current_focus == self.origin;
+        let validated = match self.offer_data {
+            Some(ref data) if !on_origin => {
+                let data = data.lock().unwrap();
+                data.accepted && (!data.chosen_action.is_empty())
+            }
+            _ => false,
         };
         if let Some(ref surface) = self.current_focus {
             for device in seat_data.known_data_devices() {
@@ -264,7 +303,9 @@ where
         // While the grab is active, no client has pointer focus
         handle.motion(data, None, event);
 
-        self.update_focus(focus, location, serial, time);
+        if self.update_pending(location) {
+            self.update_focus(focus, location, serial, time);
+        }
     }
 
     fn relative_motion(
@@ -425,7 +466,9 @@ where
         let location = event.location;
         let time = event.time;
 
-        self.update_focus(focus, location, SERIAL_COUNTER.next_serial(), time);
+        if self.update_pending(location) {
+            self.update_focus(focus, location, SERIAL_COUNTER.next_serial(), time);
+        }
     }
 
     fn frame(
@@ -557,30 +600,38 @@ fn handle_server_dnd<D>(
         Request::Destroy => {}
         Request::Finish => {
             if !data.active {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish as u32,
                     "Cannot finish a data offer that is no longer active.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
             if !data.accepted {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish,
                     "Cannot finish a data offer that has not been accepted.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
             if !data.dropped {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish,
                     "Cannot finish a data offer that has not been dropped.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
             if data.chosen_action.is_empty() {
-                offer.post_error(
+                post_error(
+                    offer,
                     wl_data_offer::Error::InvalidFinish,
                     "Cannot finish a data offer with no valid action.",
+                    ErrorContext::new().request("wl_data_offer.finish"),
                 );
                 return;
             }
@@ -599,7 +650,12 @@ fn handle_server_dnd<D>(
             if ![DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask]
                 .contains(&preferred_action)
             {
-                offer.post_error(wl_data_offer::Error::InvalidAction, "Invalid preferred action.");
+                post_error(
+                    offer,
+                    wl_data_offer::Error::InvalidAction,
+                    "Invalid preferred action.",
+                    ErrorContext::new().request("wl_data_offer.set_actions"),
+                );
                 return;
             }
             let possible_actions = metadata.dnd_action & dnd_actions;
@@ -18,6 +18,9 @@
 //! - the `PrimarySelectionHandle` gives you the option to inspect new selections
 //!   by overriding [`SelectionHandler::new_selection`].
 //!
+//! When mirroring the primary selection to and from Xwayland, a [`PrimarySelectionSync`]
+//! can be used to debounce rapid selection changes and to decide which side owns the selection.
+//!
 //! ## Initialization
 //!
 //! To initialize this implementation, create the [`PrimarySelectionState`], store it inside your `State` struct
@@ -79,9 +82,11 @@ use crate::{
 
 mod device;
 mod source;
+mod sync;
 
 pub use device::PrimaryDeviceUserData;
 pub use source::{PrimarySourceUserData, SourceMetadata};
+pub use sync::{PrimarySelectionChange, PrimarySelectionOrigin, PrimarySelectionSync};
 
 use super::source::CompositorSelectionProvider;
 use super::SelectionHandler;
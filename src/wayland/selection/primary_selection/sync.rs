@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use crate::utils::{Monotonic, Time};
+
+/// Side a primary selection originates from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrimarySelectionOrigin {
+    /// The selection was set by a wayland client
+    Wayland,
+    /// The selection was set by an X11 client
+    X11,
+}
+
+/// Change of the primary selection, that should be forwarded to the other side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimarySelectionChange {
+    /// Side the selection was set on
+    pub origin: PrimarySelectionOrigin,
+    /// Offered mime types, `None` if the selection was cleared
+    pub mime_types: Option<Vec<String>>,
+}
+
+/// Keeps the X11 `PRIMARY` selection and the wayland primary selection coherent
+///
+/// Selecting text in many clients updates the primary selection on every pointer motion.
+/// Forwarding each of these changes to the other side causes a new offer for every
+/// focused client and, if clients on both sides react to losing the selection, ownership
+/// wars between X11 and wayland clients.
+///
+/// The tracker forwards the first change immediately and coalesces any further changes
+/// happening within the debounce interval, so at most one change is forwarded per interval.
+/// If both sides change the selection within an interval, the most recent change wins.
+/// Changes not altering the offered mime types of the current owner are never forwarded,
+/// as reads are always served by the current owner anyway.
+///
+/// Report changes from [`SelectionHandler::new_selection`](crate::wayland::selection::SelectionHandler::new_selection)
+/// and the new and cleared selection callbacks of the `XwmHandler` using [`PrimarySelectionSync::selection_changed`]
+/// and forward the returned changes to the other side, e.g. using
+/// [`set_primary_selection`](super::set_primary_selection) or `X11Wm::new_selection`.
+/// Delayed changes are returned by [`PrimarySelectionSync::poll`] once [`PrimarySelectionSync::deadline`] has passed.
+#[derive(Debug)]
+pub struct PrimarySelectionSync {
+    debounce: Duration,
+    current: Option<PrimarySelectionChange>,
+    last_forwarded: Option<Time<Monotonic>>,
+    pending: Option<PrimarySelectionChange>,
+}
+
+impl PrimarySelectionSync {
+    /// Create a new tracker coalescing changes within `debounce`
+    pub fn new(debounce: Duration) -> Self {
+        PrimarySelectionSync {
+            debounce,
+            current: None,
+            last_forwarded: None,
+            pending: None,
+        }
+    }
+
+    /// Returns the side owning the last forwarded selection
+    ///
+    /// Returns `None` if no selection was forwarded yet or the last forwarded change cleared the selection.
+    pub fn owner(&self) -> Option<PrimarySelectionOrigin> {
+        self.current
+            .as_ref()
+            .filter(|change| change.mime_types.is_some())
+            .map(|change| change.origin)
+    }
+
+    /// Returns the point in time when a delayed change becomes due
+    ///
+    /// [`PrimarySelectionSync::poll`] should be called once this time has passed.
+    pub fn deadline(&self) -> Option<Time<Monotonic>> {
+        self.pending.as_ref()?;
+        self.last_forwarded.map(|last| last + self.debounce)
+    }
+
+    /// Report a change of the primary selection on one side
+    ///
+    /// Returns the change to forward to the other side immediately, if any.
+    pub fn selection_changed(
+        &mut self,
+        origin: PrimarySelectionOrigin,
+        mime_types: Option<Vec<String>>,
+        now: Time<Monotonic>,
+    ) -> Option<PrimarySelectionChange> {
+        let change = PrimarySelectionChange { origin, mime_types };
+        if self.current.as_ref() == Some(&change) {
+            // a newer change of the other side might be pending, it would be outdated by now
+            self.pending = None;
+            return None;
+        }
+
+        if self.last_forwarded.is_some_and(|last| now < last + self.debounce) {
+            self.pending = Some(change);
+            return None;
+        }
+
+        self.forward(change, now)
+    }
+
+    /// Returns a delayed change, if it is due
+    pub fn poll(&mut self, now: Time<Monotonic>) -> Option<PrimarySelectionChange> {
+        if !self.deadline().is_some_and(|deadline| deadline <= now) {
+            return None;
+        }
+
+        let change = self.pending.take()?;
+        if self.current.as_ref() == Some(&change) {
+            return None;
+        }
+        self.forward(change, now)
+    }
+
+    fn forward(
+        &mut self,
+        change: PrimarySelectionChange,
+        now: Time<Monotonic>,
+    ) -> Option<PrimarySelectionChange> {
+        self.pending = None;
+        self.last_forwarded = Some(now);
+        self.current = Some(change.clone());
+        Some(change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{PrimarySelectionChange, PrimarySelectionOrigin, PrimarySelectionSync};
+    use crate::utils::{Monotonic, Time};
+
+    fn at(ms: u64) -> Time<Monotonic> {
+        Time::from(Duration::from_millis(ms))
+    }
+
+    fn text() -> Option<Vec<String>> {
+        Some(vec![String::from("text/plain")])
+    }
+
+    #[test]
+    fn coalesce_rapid_changes() {
+        let mut sync = PrimarySelectionSync::new(Duration::from_millis(50));
+
+        let change = sync.selection_changed(PrimarySelectionOrigin::X11, text(), at(0));
+        assert_eq!(
+            change,
+            Some(PrimarySelectionChange {
+                origin: PrimarySelectionOrigin::X11,
+                mime_types: text(),
+            })
+        );
+        assert_eq!(sync.owner(), Some(PrimarySelectionOrigin::X11));
+
+        let html = Some(vec![String::from("text/html")]);
+        assert_eq!(
+            sync.selection_changed(PrimarySelectionOrigin::X11, None, at(10)),
+            None
+        );
+        assert_eq!(
+            sync.selection_changed(PrimarySelectionOrigin::X11, html.clone(), at(20)),
+            None
+        );
+        assert_eq!(sync.deadline(), Some(at(50)));
+        assert_eq!(sync.poll(at(40)), None);
+        assert_eq!(
+            sync.poll(at(50)),
+            Some(PrimarySelectionChange {
+                origin: PrimarySelectionOrigin::X11,
+                mime_types: html,
+            })
+        );
+        assert_eq!(sync.deadline(), None);
+    }
+
+    #[test]
+    fn unchanged_selection_is_not_forwarded() {
+        let mut sync = PrimarySelectionSync::new(Duration::from_millis(50));
+
+        assert!(sync
+            .selection_changed(PrimarySelectionOrigin::Wayland, text(), at(0))
+            .is_some());
+        assert_eq!(
+            sync.selection_changed(PrimarySelectionOrigin::Wayland, text(), at(100)),
+            None
+        );
+        assert_eq!(sync.deadline(), None);
+    }
+
+    #[test]
+    fn latest_side_wins() {
+        let mut sync = PrimarySelectionSync::new(Duration::from_millis(50));
+
+        assert!(sync
+            .selection_changed(PrimarySelectionOrigin::Wayland, text(), at(0))
+            .is_some());
+        assert_eq!(
+            sync.selection_changed(PrimarySelectionOrigin::X11, text(), at(10)),
+            None
+        );
+        assert_eq!(
+            sync.poll(at(60)),
+            Some(PrimarySelectionChange {
+                origin: PrimarySelectionOrigin::X11,
+                mime_types: text(),
+            })
+        );
+        assert_eq!(sync.owner(), Some(PrimarySelectionOrigin::X11));
+
+        // briefly clearing and restoring the selection within the interval is not forwarded
+        assert_eq!(
+            sync.selection_changed(PrimarySelectionOrigin::X11, None, at(70)),
+            None
+        );
+        assert_eq!(
+            sync.selection_changed(PrimarySelectionOrigin::X11, text(), at(80)),
+            None
+        );
+        assert_eq!(sync.deadline(), None);
+        assert_eq!(sync.poll(at(200)), None);
+    }
+}
@@ -7,6 +7,7 @@ use crate::backend::renderer::buffer_dimensions;
 use crate::utils::Size;
 use crate::wayland::compositor::SurfaceAttributes;
 use crate::wayland::compositor::{self, BufferAssignment};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::viewporter::{ViewportCachedState, ViewporterSurfaceState};
 use _session_lock::ext_session_lock_surface_v1::ExtSessionLockSurfaceV1;
 use _session_lock::ext_session_lock_v1::{Error, ExtSessionLockV1, Request};
@@ -53,14 +54,24 @@ where
             Request::GetLockSurface { id, surface, output } => {
                 // Assign surface a role and ensure it never had one before.
                 if compositor::give_role(&surface, LOCK_SURFACE_ROLE).is_err() {
-                    lock.post_error(Error::Role, "Surface already has a role.");
+                    post_error(
+                        lock,
+                        Error::Role,
+                        "Surface already has a role.",
+                        ErrorContext::new().request("ext_session_lock_v1.get_lock_surface"),
+                    );
                     return;
                 }
 
                 // Ensure output is not already locked.
                 let lock_state = state.lock_state();
                 if lock_state.locked_outputs.contains(&output) {
-                    lock.post_error(Error::DuplicateOutput, "Output is already locked.");
+                    post_error(
+                        lock,
+                        Error::DuplicateOutput,
+                        "Output is already locked.",
+                        ErrorContext::new().request("ext_session_lock_v1.get_lock_surface"),
+                    );
                     return;
                 }
                 lock_state.locked_outputs.push(output.clone());
@@ -74,7 +85,12 @@ where
                     pending || current
                 });
                 if has_buffer {
-                    lock.post_error(Error::AlreadyConstructed, "Surface has a buffer attached.");
+                    post_error(
+                        lock,
+                        Error::AlreadyConstructed,
+                        "Surface has a buffer attached.",
+                        ErrorContext::new().request("ext_session_lock_v1.get_lock_surface"),
+                    );
                     return;
                 }
 
@@ -101,75 +117,91 @@ where
                 });
 
                 // Add pre-commit hook for updating surface state.
-                compositor::add_pre_commit_hook::<D, _>(&surface, |_state, _dh, surface| {
-                    compositor::with_states(surface, |states| {
-                        let attributes = states.data_map.get::<Mutex<LockSurfaceAttributes>>();
-                        let attributes = attributes.unwrap().lock().unwrap();
-
-                        let Some(state) = attributes.last_acked else {
-                            attributes.surface.post_error(
-                                ext_session_lock_surface_v1::Error::CommitBeforeFirstAck,
-                                "Committed before the first ack_configure.",
-                            );
-                            return;
-                        };
-
-                        // Verify the attached buffer: ext-session-lock requires no NULL buffers
-                        // and an exact dimentions match.
-                        let mut guard = states.cached_state.get::<SurfaceAttributes>();
-                        let surface_attrs = guard.pending();
-                        if let Some(assignment) = surface_attrs.buffer.as_ref() {
-                            match assignment {
-                                BufferAssignment::Removed => {
-                                    attributes.surface.post_error(
-                                        ext_session_lock_surface_v1::Error::NullBuffer,
-                                        "Surface attached a NULL buffer.",
-                                    );
-                                }
-                                BufferAssignment::NewBuffer(buffer) => {
-                                    if let Some(buf_size) = buffer_dimensions(buffer) {
-                                        let viewport = states
-                                            .data_map
-                                            .get::<ViewporterSurfaceState>()
-                                            .map(|v| v.lock().unwrap());
-                                        let surface_size = if let Some(dest) =
-                                            viewport.as_ref().and_then(|_| {
-                                                let mut guard =
-                                                    states.cached_state.get::<ViewportCachedState>();
-                                                let viewport_state = guard.pending();
-                                                viewport_state.dst
-                                            }) {
-                                            Size::from((dest.w as u32, dest.h as u32))
-                                        } else {
-                                            let scale = surface_attrs.buffer_scale;
-                                            let transform = surface_attrs.buffer_transform.into();
-                                            let surface_size = buf_size.to_logical(scale, transform);
-
-                                            Size::from((surface_size.w as u32, surface_size.h as u32))
-                                        };
-
-                                        if Some(surface_size) != state.size {
-                                            attributes.surface.post_error(
-                                                ext_session_lock_surface_v1::Error::DimensionsMismatch,
-                                                "Surface dimensions do not match acked configure.",
-                                            );
+                compositor::add_pre_commit_hook_with_priority::<D, _>(
+                    &surface,
+                    compositor::HookPriority::Protocol,
+                    |_state, _dh, surface| {
+                        compositor::with_states(surface, |states| {
+                            let attributes = states.data_map.get::<Mutex<LockSurfaceAttributes>>();
+                            let attributes = attributes.unwrap().lock().unwrap();
+
+                            let Some(state) = attributes.last_acked else {
+                                post_error(
+                                    &attributes.surface,
+                                    ext_session_lock_surface_v1::Error::CommitBeforeFirstAck,
+                                    "Committed before the first ack_configure.",
+                                    ErrorContext::new().request("ext_session_lock_v1.get_lock_surface"),
+                                );
+                                return;
+                            };
+
+                            // Verify the attached buffer: ext-session-lock requires no NULL buffers
+                            // and an exact dimentions match.
+                            let mut guard = states.cached_state.get::<SurfaceAttributes>();
+                            let surface_attrs = guard.pending();
+                            if let Some(assignment) = surface_attrs.buffer.as_ref() {
+                                match assignment {
+                                    BufferAssignment::Removed => {
+                                        post_error(
+                                            &attributes.surface,
+                                            ext_session_lock_surface_v1::Error::NullBuffer,
+                                            "Surface attached a NULL buffer.",
+                                            ErrorContext::new()
+                                                .request("ext_session_lock_v1.get_lock_surface"),
+                                        );
+                                    }
+                                    BufferAssignment::NewBuffer(buffer) => {
+                                        if let Some(buf_size) = buffer_dimensions(buffer) {
+                                            let viewport = states
+                                                .data_map
+                                                .try_get_key::<ViewporterSurfaceState>()
+                                                .map(|v| v.lock().unwrap());
+                                            let surface_size = if let Some(dest) =
+                                                viewport.as_ref().and_then(|_| {
+                                                    let mut guard =
+                                                        states.cached_state.get::<ViewportCachedState>();
+                                                    let viewport_state = guard.pending();
+                                                    viewport_state.dst
+                                                }) {
+                                                Size::from((dest.w as u32, dest.h as u32))
+                                            } else {
+                                                let scale = surface_attrs.buffer_scale;
+                                                let transform = surface_attrs.buffer_transform.into();
+                                                let surface_size = buf_size.to_logical(scale, transform);
+
+                                                Size::from((surface_size.w as u32, surface_size.h as u32))
+                                            };
+
+                                            if Some(surface_size) != state.size {
+                                                post_error(
+                                                    &attributes.surface,
+                                                    ext_session_lock_surface_v1::Error::DimensionsMismatch,
+                                                    "Surface dimensions do not match acked configure.",
+                                                    ErrorContext::new()
+                                                        .request("ext_session_lock_v1.get_lock_surface"),
+                                                );
+                                            }
                                         }
                                     }
                                 }
                             }
-                        }
-                    });
-                });
-                compositor::add_post_commit_hook::<D, _>(&surface, |_state, _dh, surface| {
-                    compositor::with_states(surface, |states| {
-                        let attributes = states.data_map.get::<Mutex<LockSurfaceAttributes>>();
-                        let mut attributes = attributes.unwrap().lock().unwrap();
-
-                        if let Some(state) = attributes.last_acked {
-                            attributes.current = state;
-                        }
-                    });
-                });
+                        });
+                    },
+                );
+                compositor::add_post_commit_hook_with_priority::<D, _>(
+                    &surface,
+                    compositor::HookPriority::Protocol,
+                    |_state, _dh, surface| {
+                        compositor::with_states(surface, |states| {
+                            let attributes = states.data_map.get::<Mutex<LockSurfaceAttributes>>();
+                            let mut attributes = attributes.unwrap().lock().unwrap();
+
+                            if let Some(state) = attributes.last_acked {
+                                attributes.current = state;
+                            }
+                        });
+                    },
+                );
 
                 // Call compositor handler.
                 let lock_surface = LockSurface::new(surface, lock_surface);
@@ -181,7 +213,12 @@ where
             Request::UnlockAndDestroy => {
                 // Ensure session is locked.
                 if !data.lock_status.load(Ordering::Relaxed) {
-                    lock.post_error(Error::InvalidUnlock, "Session is not locked.");
+                    post_error(
+                        lock,
+                        Error::InvalidUnlock,
+                        "Session is not locked.",
+                        ErrorContext::new().request("ext_session_lock_v1.unlock_and_destroy"),
+                    );
                 }
 
                 state.lock_state().locked_outputs.clear();
@@ -190,7 +227,12 @@ where
             Request::Destroy => {
                 // Ensure session is not locked.
                 if data.lock_status.load(Ordering::Relaxed) {
-                    lock.post_error(Error::InvalidDestroy, "Cannot destroy session lock while locked.");
+                    post_error(
+                        lock,
+                        Error::InvalidDestroy,
+                        "Cannot destroy session lock while locked.",
+                        ErrorContext::new().request("ext_session_lock_v1.destroy"),
+                    );
                 }
             }
             _ => unreachable!(),
@@ -4,10 +4,11 @@ use std::sync::Mutex;
 
 use crate::utils::{IsAlive, Logical, Serial, Size, SERIAL_COUNTER};
 use crate::wayland::compositor;
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use _session_lock::ext_session_lock_surface_v1::{Error, ExtSessionLockSurfaceV1, Request};
 use wayland_protocols::ext::session_lock::v1::server::{self as _session_lock, ext_session_lock_surface_v1};
 use wayland_server::protocol::wl_surface::WlSurface;
-use wayland_server::{Client, DataInit, Dispatch, DisplayHandle, Resource, Weak};
+use wayland_server::{Client, DataInit, Dispatch, DisplayHandle, Weak};
 
 use crate::wayland::session_lock::{SessionLockHandler, SessionLockManagerState};
 
@@ -50,9 +51,11 @@ where
 
                 match configure {
                     Some(configure) => state.ack_configure(surface.clone(), configure),
-                    None => lock_surface.post_error(
+                    None => post_error(
+                        lock_surface,
                         Error::InvalidSerial,
                         format!("wrong configure serial: {}", <u32>::from(serial)),
+                        ErrorContext::new().request("ext_session_lock_surface_v1.ack_configure"),
                     ),
                 }
             }
@@ -0,0 +1,614 @@
+//! Utilities for handling the `zwp_fullscreen_shell_v1` protocol
+//!
+//! The fullscreen shell allows a client to present a single surface per output, without
+//! any window management involved. It is mostly useful for kiosk-style compositors or for
+//! displaying nested compositors.
+//!
+//! The surfaces presented on every output are tracked by the [`FullscreenShellState`],
+//! the [`desktop`](crate::desktop) module provides helpers to place them in a
+//! [`Space`](crate::desktop::Space) or to render them directly.
+//! Commits of presented surfaces are reported by
+//! [`FullscreenShellHandler::presented_surface_committed`], which is the place to update their placement.
+//!
+//! ## How to use it
+//!
+//! ### Initialization
+//!
+//! To initialize this implementation create the [`FullscreenShellState`] and
+//! implement the [`FullscreenShellHandler`], as shown in this example:
+//!
+//! ```
+//! use smithay::delegate_fullscreen_shell;
+//! use smithay::output::Output;
+//! use smithay::wayland::shell::fullscreen::{
+//!     Capability, FullscreenShellHandler, FullscreenShellState, FullscreenSurface, ModeFeedback,
+//! };
+//!
+//! # struct State { fullscreen_shell_state: FullscreenShellState, outputs: Vec<Output> }
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//! // Create the fullscreen shell state
+//! let fullscreen_shell_state = FullscreenShellState::new::<State>(
+//!     &display.handle(),
+//!     [Capability::CursorPlane],
+//! );
+//!
+//! // Insert the FullscreenShellState into your state.
+//!
+//! // Implement the necessary trait.
+//! impl FullscreenShellHandler for State {
+//!     fn fullscreen_shell_state(&mut self) -> &mut FullscreenShellState {
+//!         &mut self.fullscreen_shell_state
+//!     }
+//!
+//!     fn present_surface(&mut self, surface: Option<FullscreenSurface>, output: Option<Output>) {
+//!         // Without a specific output, present the surface everywhere.
+//!         let outputs = output.map(|o| vec![o]).unwrap_or_else(|| self.outputs.clone());
+//!         for output in outputs {
+//!             self.fullscreen_shell_state.present(&output, surface.clone());
+//!         }
+//!     }
+//!
+//!     fn present_surface_for_mode(
+//!         &mut self,
+//!         _surface: FullscreenSurface,
+//!         _output: Output,
+//!         feedback: ModeFeedback,
+//!     ) {
+//!         // Mode switches are not supported, dropping the feedback reports a failure.
+//!         drop(feedback);
+//!     }
+//! }
+//! delegate_fullscreen_shell!(State);
+//!
+//! // You're now ready to go!
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols::wp::fullscreen_shell::zv1::server::{
+    zwp_fullscreen_shell_mode_feedback_v1::ZwpFullscreenShellModeFeedbackV1,
+    zwp_fullscreen_shell_v1::{self, ZwpFullscreenShellV1},
+};
+use wayland_server::{
+    backend::GlobalId, protocol::wl_surface::WlSurface, Client, DataInit, Dispatch, DisplayHandle,
+    GlobalDispatch, New, Resource, WEnum,
+};
+
+use crate::wayland::protocol_error::{post_error, ErrorContext};
+use crate::{
+    output::{Output, WeakOutput},
+    utils::IsAlive,
+    wayland::compositor,
+};
+
+pub use zwp_fullscreen_shell_v1::{Capability, PresentMethod};
+
+/// Surface role of surfaces presented using the fullscreen shell
+pub const FULLSCREEN_SHELL_ROLE: &str = "zwp_fullscreen_shell_v1";
+
+const SHELL_VERSION: u32 = 1;
+
+/// A surface presented by a client using the fullscreen shell
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullscreenSurface {
+    surface: WlSurface,
+    method: PresentMethod,
+    framerate: Option<i32>,
+}
+
+impl FullscreenSurface {
+    /// Access the underlying [`WlSurface`]
+    pub fn wl_surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    /// Hint of the client how to handle a size mismatch between the surface and the output
+    ///
+    /// Surfaces presented for a specific mode always use [`PresentMethod::Default`].
+    pub fn method(&self) -> PresentMethod {
+        self.method
+    }
+
+    /// Framerate in mHz requested for the mode of the output, if the surface was
+    /// presented for a specific mode and the client stated a preference
+    pub fn framerate(&self) -> Option<i32> {
+        self.framerate
+    }
+
+    /// Returns true if the surface was presented for a specific mode
+    pub fn is_for_mode(&self) -> bool {
+        self.framerate.is_some()
+    }
+}
+
+impl IsAlive for FullscreenSurface {
+    #[inline]
+    fn alive(&self) -> bool {
+        self.surface.alive()
+    }
+}
+
+#[derive(Debug)]
+struct PendingModeSwitch {
+    output: WeakOutput,
+    feedback: Arc<Mutex<Option<ZwpFullscreenShellModeFeedbackV1>>>,
+}
+
+/// State of the [`ZwpFullscreenShellV1`] global
+#[derive(Debug)]
+pub struct FullscreenShellState {
+    global: GlobalId,
+    presented: Vec<(WeakOutput, FullscreenSurface)>,
+    pending: Vec<PendingModeSwitch>,
+}
+
+/// Data associated with the [`ZwpFullscreenShellV1`] global
+#[allow(missing_debug_implementations)]
+pub struct FullscreenShellGlobalData {
+    capabilities: Vec<Capability>,
+    filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+impl FullscreenShellState {
+    /// Create a new [`ZwpFullscreenShellV1`] global advertising the given capabilities
+    pub fn new<D>(display: &DisplayHandle, capabilities: impl IntoIterator<Item = Capability>) -> Self
+    where
+        D: GlobalDispatch<ZwpFullscreenShellV1, FullscreenShellGlobalData>
+            + Dispatch<ZwpFullscreenShellV1, ()>
+            + Dispatch<ZwpFullscreenShellModeFeedbackV1, ()>
+            + FullscreenShellHandler
+            + 'static,
+    {
+        Self::new_with_filter::<D, _>(display, capabilities, |_| true)
+    }
+
+    /// Create a new [`ZwpFullscreenShellV1`] global with a filter.
+    ///
+    /// Filters can be used to limit visibility of a global to certain clients.
+    pub fn new_with_filter<D, F>(
+        display: &DisplayHandle,
+        capabilities: impl IntoIterator<Item = Capability>,
+        filter: F,
+    ) -> Self
+    where
+        D: GlobalDispatch<ZwpFullscreenShellV1, FullscreenShellGlobalData>
+            + Dispatch<ZwpFullscreenShellV1, ()>
+            + Dispatch<ZwpFullscreenShellModeFeedbackV1, ()>
+            + FullscreenShellHandler
+            + 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let data = FullscreenShellGlobalData {
+            capabilities: capabilities.into_iter().collect(),
+            filter: Box::new(filter),
+        };
+        let global = display.create_global::<D, ZwpFullscreenShellV1, _>(SHELL_VERSION, data);
+
+        Self {
+            global,
+            presented: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns the id of the [`ZwpFullscreenShellV1`] global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Present a surface on an output, replacing any previously presented surface
+    ///
+    /// Passing `None` removes the content of the output.
+    pub fn present(&mut self, output: &Output, surface: Option<FullscreenSurface>) {
+        self.presented
+            .retain(|(o, s)| o.is_alive() && s.alive() && o != output);
+        if let Some(surface) = surface {
+            self.presented.push((output.downgrade(), surface));
+        }
+    }
+
+    /// Remove all surfaces presented on an output, e.g. because the output was disabled
+    pub fn output_removed(&mut self, output: &Output) {
+        self.present(output, None);
+        self.cancel_pending(Some(output));
+    }
+
+    /// Returns the surface currently presented on an output
+    pub fn presented_surface(&self, output: &Output) -> Option<&FullscreenSurface> {
+        self.presented
+            .iter()
+            .find(|(o, s)| o == output && s.alive())
+            .map(|(_, s)| s)
+    }
+
+    /// Iterate over all outputs and the surfaces presented on them
+    pub fn presented_surfaces(&self) -> impl Iterator<Item = (Output, &FullscreenSurface)> {
+        self.presented
+            .iter()
+            .filter(|(_, s)| s.alive())
+            .filter_map(|(o, s)| o.upgrade().map(|o| (o, s)))
+    }
+
+    /// Cancel pending mode switches of an output or of all outputs for `None`
+    fn cancel_pending(&mut self, output: Option<&Output>) {
+        self.pending.retain(|pending| {
+            let mut feedback = pending.feedback.lock().unwrap();
+            if output.map_or(true, |output| pending.output == *output) {
+                if let Some(feedback) = feedback.take() {
+                    feedback.present_cancelled();
+                }
+            }
+            feedback.is_some() && pending.output.is_alive()
+        });
+    }
+}
+
+/// Handler trait for the fullscreen shell
+#[allow(unused_variables)]
+pub trait FullscreenShellHandler {
+    /// [`FullscreenShellState`] getter
+    fn fullscreen_shell_state(&mut self) -> &mut FullscreenShellState;
+
+    /// A client requested a surface to be presented
+    ///
+    /// If `output` is `None` the compositor is free to present the surface on any or
+    /// all of its outputs. A `surface` of `None` requests the contents of the output(s)
+    /// to be removed.
+    ///
+    /// Use [`FullscreenShellState::present`] to update the presented surfaces.
+    /// The presentation takes effect once the surface gets committed.
+    fn present_surface(&mut self, surface: Option<FullscreenSurface>, output: Option<Output>);
+
+    /// A client requested a surface to be presented with a matching output mode
+    ///
+    /// The compositor should try to change the mode of the output to match the surface
+    /// and report the result using the provided [`ModeFeedback`]. The surface should only
+    /// replace the previously presented surface if the mode switch was successful.
+    fn present_surface_for_mode(
+        &mut self,
+        surface: FullscreenSurface,
+        output: Output,
+        feedback: ModeFeedback,
+    );
+
+    /// A surface presented on `output` was committed
+    ///
+    /// Called once for every output the surface is presented on. The size of the surface may have
+    /// changed, so this is the place to update its placement, e.g. with
+    /// [`map_fullscreen_surface`](crate::desktop::map_fullscreen_surface), and to schedule a new
+    /// frame for the output.
+    fn presented_surface_committed(&mut self, surface: &FullscreenSurface, output: &Output) {}
+}
+
+/// Feedback for a mode switch requested by [`FullscreenShellHandler::present_surface_for_mode`]
+///
+/// Dropping the feedback without calling any of its methods reports a failed mode switch.
+/// The feedback is automatically cancelled, if another surface is presented on the output
+/// before it was resolved.
+#[derive(Debug)]
+pub struct ModeFeedback {
+    feedback: Arc<Mutex<Option<ZwpFullscreenShellModeFeedbackV1>>>,
+}
+
+impl ModeFeedback {
+    /// Returns true if the feedback was cancelled by the client presenting another surface
+    pub fn is_cancelled(&self) -> bool {
+        self.feedback.lock().unwrap().is_none()
+    }
+
+    /// Notify the client that the mode switch succeeded
+    ///
+    /// The surface fills the output without scaling from now on.
+    pub fn successful(self) {
+        if let Some(feedback) = self.feedback.lock().unwrap().take() {
+            feedback.mode_successful();
+        }
+    }
+
+    /// Notify the client that the mode switch failed
+    pub fn failed(self) {
+        // the drop impl sends the failure
+    }
+}
+
+impl Drop for ModeFeedback {
+    fn drop(&mut self) {
+        if let Some(feedback) = self.feedback.lock().unwrap().take() {
+            feedback.mode_failed();
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZwpFullscreenShellV1, FullscreenShellGlobalData, D> for FullscreenShellState
+where
+    D: GlobalDispatch<ZwpFullscreenShellV1, FullscreenShellGlobalData>
+        + Dispatch<ZwpFullscreenShellV1, ()>
+        + Dispatch<ZwpFullscreenShellModeFeedbackV1, ()>
+        + FullscreenShellHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwpFullscreenShellV1>,
+        global_data: &FullscreenShellGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let shell = data_init.init(resource, ());
+        for capability in &global_data.capabilities {
+            shell.capability(*capability);
+        }
+    }
+
+    fn can_view(client: Client, global_data: &FullscreenShellGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwpFullscreenShellV1, (), D> for FullscreenShellState
+where
+    D: Dispatch<ZwpFullscreenShellV1, ()>
+        + Dispatch<ZwpFullscreenShellModeFeedbackV1, ()>
+        + FullscreenShellHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        shell: &ZwpFullscreenShellV1,
+        request: zwp_fullscreen_shell_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwp_fullscreen_shell_v1::Request::PresentSurface {
+                surface,
+                method,
+                output,
+            } => {
+                let WEnum::Value(method) = method else {
+                    post_error(
+                        shell,
+                        zwp_fullscreen_shell_v1::Error::InvalidMethod,
+                        "Unknown present method.",
+                        ErrorContext::new().request("zwp_fullscreen_shell_v1.present_surface"),
+                    );
+                    return;
+                };
+
+                if let Some(surface) = surface.as_ref() {
+                    if !assign_role::<D>(shell, surface) {
+                        return;
+                    }
+                }
+
+                let output = match output.as_ref() {
+                    Some(wl_output) => match Output::from_resource(wl_output) {
+                        Some(output) => Some(output),
+                        // the output is already gone, nothing to present on
+                        None => return,
+                    },
+                    None => None,
+                };
+
+                state.fullscreen_shell_state().cancel_pending(output.as_ref());
+
+                let surface = surface.map(|surface| FullscreenSurface {
+                    surface,
+                    method,
+                    framerate: None,
+                });
+                state.present_surface(surface, output);
+            }
+            zwp_fullscreen_shell_v1::Request::PresentSurfaceForMode {
+                surface,
+                output,
+                framerate,
+                feedback,
+            } => {
+                let feedback = data_init.init(feedback, ());
+                if !assign_role::<D>(shell, &surface) {
+                    return;
+                }
+
+                let Some(output) = Output::from_resource(&output) else {
+                    feedback.mode_failed();
+                    return;
+                };
+
+                let fullscreen_state = state.fullscreen_shell_state();
+                fullscreen_state.cancel_pending(Some(&output));
+                let feedback = Arc::new(Mutex::new(Some(feedback)));
+                fullscreen_state.pending.push(PendingModeSwitch {
+                    output: output.downgrade(),
+                    feedback: feedback.clone(),
+                });
+
+                let surface = FullscreenSurface {
+                    surface,
+                    method: PresentMethod::Default,
+                    framerate: Some(framerate),
+                };
+                state.present_surface_for_mode(surface, output, ModeFeedback { feedback });
+            }
+            zwp_fullscreen_shell_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpFullscreenShellModeFeedbackV1, (), D> for FullscreenShellState
+where
+    D: Dispatch<ZwpFullscreenShellModeFeedbackV1, ()>,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _feedback: &ZwpFullscreenShellModeFeedbackV1,
+        _request: <ZwpFullscreenShellModeFeedbackV1 as Resource>::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        // zwp_fullscreen_shell_mode_feedback_v1 has no requests
+    }
+}
+
+fn assign_role<D>(shell: &ZwpFullscreenShellV1, surface: &WlSurface) -> bool
+where
+    D: FullscreenShellHandler + 'static,
+{
+    let has_role = compositor::get_role(surface) == Some(FULLSCREEN_SHELL_ROLE);
+    if compositor::give_role(surface, FULLSCREEN_SHELL_ROLE).is_err() {
+        post_error(
+            shell,
+            zwp_fullscreen_shell_v1::Error::Role,
+            "Surface already has a different role.",
+            ErrorContext::new(),
+        );
+        return false;
+    }
+
+    if !has_role {
+        compositor::add_post_commit_hook::<D, _>(surface, |state, _dh, surface| {
+            let presented = state
+                .fullscreen_shell_state()
+                .presented
+                .iter()
+                .filter(|(_, presented)| presented.wl_surface() == surface)
+                .filter_map(|(output, presented)| output.upgrade().map(|output| (output, presented.clone())))
+                .collect::<Vec<_>>();
+            for (output, presented) in presented {
+                state.presented_surface_committed(&presented, &output);
+            }
+        });
+    }
+    true
+}
+
+/// Macro to delegate implementation of the fullscreen shell protocol to [`FullscreenShellState`].
+///
+/// You must also implement [`FullscreenShellHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_fullscreen_shell {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::wp::fullscreen_shell::zv1::server::zwp_fullscreen_shell_v1::ZwpFullscreenShellV1: $crate::wayland::shell::fullscreen::FullscreenShellGlobalData
+        ] => $crate::wayland::shell::fullscreen::FullscreenShellState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::wp::fullscreen_shell::zv1::server::zwp_fullscreen_shell_v1::ZwpFullscreenShellV1: ()
+        ] => $crate::wayland::shell::fullscreen::FullscreenShellState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::wp::fullscreen_shell::zv1::server::zwp_fullscreen_shell_mode_feedback_v1::ZwpFullscreenShellModeFeedbackV1: ()
+        ] => $crate::wayland::shell::fullscreen::FullscreenShellState);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_client::protocol::wl_output::WlOutput;
+    use wayland_protocols::wp::fullscreen_shell::zv1::client::{
+        zwp_fullscreen_shell_mode_feedback_v1::Event as FeedbackEvent,
+        zwp_fullscreen_shell_v1::{self as client_shell, ZwpFullscreenShellV1 as ClientShell},
+    };
+
+    use super::*;
+    use crate::wayland::test_utils::TestFixture;
+
+    #[test]
+    fn commits_of_presented_surfaces_are_reported() {
+        let mut fixture = TestFixture::new();
+        let shell = fixture.bind::<ClientShell>(1);
+        let (surface, server_surface) = fixture.create_surface();
+
+        shell.present_surface(Some(&surface), client_shell::PresentMethod::Zoom, None);
+        fixture.roundtrip();
+        let output = fixture.state.output.clone();
+        let presented = fixture.state.fullscreen_shell.presented_surface(&output).unwrap();
+        assert_eq!(presented.wl_surface(), &server_surface);
+        assert_eq!(presented.method(), PresentMethod::Zoom);
+        assert!(!presented.is_for_mode());
+
+        fixture.map(&surface, 20, 10);
+        assert_eq!(fixture.state.fullscreen_commits.len(), 1);
+        let (committed, committed_output) = &fixture.state.fullscreen_commits[0];
+        assert_eq!(committed.wl_surface(), &server_surface);
+        assert_eq!(committed_output, &output);
+
+        // presenting the surface again doesn't report commits twice
+        shell.present_surface(Some(&surface), client_shell::PresentMethod::Zoom, None);
+        surface.commit();
+        fixture.roundtrip();
+        assert_eq!(fixture.state.fullscreen_commits.len(), 2);
+
+        // removed surfaces are not reported anymore
+        shell.present_surface(None, client_shell::PresentMethod::Default, None);
+        surface.commit();
+        fixture.roundtrip();
+        assert!(fixture
+            .state
+            .fullscreen_shell
+            .presented_surface(&output)
+            .is_none());
+        assert_eq!(fixture.state.fullscreen_commits.len(), 2);
+    }
+
+    #[test]
+    fn surface_with_other_role() {
+        let mut fixture = TestFixture::new();
+        let shell = fixture.bind::<ClientShell>(1);
+        let (surface, _, _) = fixture.create_toplevel();
+
+        shell.present_surface(Some(&surface), client_shell::PresentMethod::Default, None);
+        let error = fixture.protocol_error().unwrap();
+        assert_eq!(error.code, zwp_fullscreen_shell_v1::Error::Role as u32);
+    }
+
+    #[test]
+    fn mode_switch_feedback() {
+        let mut fixture = TestFixture::new();
+        let shell = fixture.bind::<ClientShell>(1);
+        let output = fixture.bind::<WlOutput>(4);
+        let (surface, server_surface) = fixture.create_surface();
+
+        shell.present_surface_for_mode(&surface, &output, 60_000, &fixture.handle(), ());
+        fixture.roundtrip();
+        let (presented, feedback) = fixture.state.fullscreen_mode_switches.pop().unwrap();
+        assert_eq!(presented.wl_surface(), &server_surface);
+        assert_eq!(presented.framerate(), Some(60_000));
+        feedback.successful();
+        fixture.roundtrip();
+        assert!(matches!(
+            fixture.client.mode_feedback_events[..],
+            [FeedbackEvent::ModeSuccessful]
+        ));
+
+        // dropping the feedback reports a failure
+        shell.present_surface_for_mode(&surface, &output, 0, &fixture.handle(), ());
+        fixture.roundtrip();
+        drop(fixture.state.fullscreen_mode_switches.pop());
+        fixture.roundtrip();
+        assert!(matches!(
+            fixture.client.mode_feedback_events[1..],
+            [FeedbackEvent::ModeFailed]
+        ));
+
+        // presenting another surface on the output cancels the pending switch
+        shell.present_surface_for_mode(&surface, &output, 0, &fixture.handle(), ());
+        fixture.roundtrip();
+        shell.present_surface(None, client_shell::PresentMethod::Default, Some(&output));
+        fixture.roundtrip();
+        let (_, feedback) = fixture.state.fullscreen_mode_switches.pop().unwrap();
+        assert!(feedback.is_cancelled());
+        drop(feedback);
+        fixture.roundtrip();
+        assert!(matches!(
+            fixture.client.mode_feedback_events[2..],
+            [FeedbackEvent::PresentCancelled]
+        ));
+    }
+}
@@ -10,6 +10,7 @@ use crate::utils::{
     alive_tracker::{AliveTracker, IsAlive},
     Serial,
 };
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::shell::xdg::XdgPopupSurfaceData;
 use crate::wayland::{compositor, shell::wlr_layer::Layer};
 
@@ -75,16 +76,23 @@ where
                 let layer: Layer = match layer.try_into() {
                     Ok(layer) => layer,
                     Err(layer) => {
-                        shell.post_error(
+                        post_error(
+                            shell,
                             zwlr_layer_shell_v1::Error::InvalidLayer,
                             format!("invalid layer: {:?}", layer),
+                            ErrorContext::new().request("zwlr_layer_shell_v1.get_layer_surface"),
                         );
                         return;
                     }
                 };
 
                 if compositor::give_role(&wl_surface, LAYER_SURFACE_ROLE).is_err() {
-                    shell.post_error(zwlr_layer_shell_v1::Error::Role, "Surface already has a role.");
+                    post_error(
+                        shell,
+                        zwlr_layer_shell_v1::Error::Role,
+                        "Surface already has a role.",
+                        ErrorContext::new().request("zwlr_layer_shell_v1.get_layer_surface"),
+                    );
                     return;
                 }
 
@@ -122,49 +130,61 @@ where
                 });
 
                 if initial {
-                    compositor::add_pre_commit_hook::<D, _>(&wl_surface, |_state, _dh, surface| {
-                        compositor::with_states(surface, |states| {
-                            let guard = states
-                                .data_map
-                                .get::<Mutex<LayerSurfaceAttributes>>()
-                                .unwrap()
-                                .lock()
-                                .unwrap();
-
-                            let mut cached_guard = states.cached_state.get::<LayerSurfaceCachedState>();
-                            let pending = cached_guard.pending();
-
-                            if pending.size.w == 0 && !pending.anchor.anchored_horizontally() {
-                                guard.surface.post_error(
-                                    zwlr_layer_surface_v1::Error::InvalidSize,
-                                    "width 0 requested without setting left and right anchors",
-                                );
-                                return;
-                            }
-
-                            if pending.size.h == 0 && !pending.anchor.anchored_vertically() {
-                                guard.surface.post_error(
-                                    zwlr_layer_surface_v1::Error::InvalidSize,
-                                    "height 0 requested without setting top and bottom anchors",
-                                );
-                            }
-                        });
-                    });
-
-                    compositor::add_post_commit_hook::<D, _>(&wl_surface, |_state, _dh, surface| {
-                        compositor::with_states(surface, |states| {
-                            let mut guard = states
-                                .data_map
-                                .get::<Mutex<LayerSurfaceAttributes>>()
-                                .unwrap()
-                                .lock()
-                                .unwrap();
-
-                            if let Some(state) = guard.last_acked.clone() {
-                                guard.current = state;
-                            }
-                        });
-                    });
+                    compositor::add_pre_commit_hook_with_priority::<D, _>(
+                        &wl_surface,
+                        compositor::HookPriority::Protocol,
+                        |_state, _dh, surface| {
+                            compositor::with_states(surface, |states| {
+                                let guard = states
+                                    .data_map
+                                    .get::<Mutex<LayerSurfaceAttributes>>()
+                                    .unwrap()
+                                    .lock()
+                                    .unwrap();
+
+                                let mut cached_guard = states.cached_state.get::<LayerSurfaceCachedState>();
+                                let pending = cached_guard.pending();
+
+                                if pending.size.w == 0 && !pending.anchor.anchored_horizontally() {
+                                    post_error(
+                                        &guard.surface,
+                                        zwlr_layer_surface_v1::Error::InvalidSize,
+                                        "width 0 requested without setting left and right anchors",
+                                        ErrorContext::new().request("zwlr_layer_shell_v1.get_layer_surface"),
+                                    );
+                                    return;
+                                }
+
+                                if pending.size.h == 0 && !pending.anchor.anchored_vertically() {
+                                    post_error(
+                                        &guard.surface,
+                                        zwlr_layer_surface_v1::Error::InvalidSize,
+                                        "height 0 requested without setting top and bottom anchors",
+                                        ErrorContext::new().request("zwlr_layer_shell_v1.get_layer_surface"),
+                                    );
+                                }
+                            });
+                        },
+                    );
+
+                    compositor::add_post_commit_hook_with_priority::<D, _>(
+                        &wl_surface,
+                        compositor::HookPriority::Protocol,
+                        |_state, _dh, surface| {
+                            compositor::with_states(surface, |states| {
+                                let mut guard = states
+                                    .data_map
+                                    .get::<Mutex<LayerSurfaceAttributes>>()
+                                    .unwrap()
+                                    .lock()
+                                    .unwrap();
+
+                                if let Some(state) = guard.last_acked.clone() {
+                                    guard.current = state;
+                                }
+                            });
+                        },
+                    );
                 }
 
                 let handle = super::LayerSurface {
@@ -240,7 +260,12 @@ where
                         });
                     }
                     Err((err, msg)) => {
-                        layer_surface.post_error(err, msg);
+                        post_error(
+                            layer_surface,
+                            err,
+                            msg,
+                            ErrorContext::new().request("zwlr_layer_surface_v1.set_anchor"),
+                        );
                     }
                 };
             }
@@ -274,7 +299,12 @@ where
                         });
                     }
                     Err((err, msg)) => {
-                        layer_surface.post_error(err, msg);
+                        post_error(
+                            layer_surface,
+                            err,
+                            msg,
+                            ErrorContext::new().request("zwlr_layer_surface_v1.set_keyboard_interactivity"),
+                        );
                     }
                 };
             }
@@ -286,7 +316,12 @@ where
                         });
                     }
                     Err((err, msg)) => {
-                        layer_surface.post_error(err, msg);
+                        post_error(
+                            layer_surface,
+                            err,
+                            msg,
+                            ErrorContext::new().request("zwlr_layer_surface_v1.set_layer"),
+                        );
                     }
                 };
             }
@@ -335,9 +370,11 @@ where
                 let configure = match found_configure {
                     Some(configure) => configure,
                     None => {
-                        layer_surface.post_error(
+                        post_error(
+                            layer_surface,
                             zwlr_layer_surface_v1::Error::InvalidSurfaceState,
                             format!("wrong configure serial: {}", <u32>::from(serial)),
+                            ErrorContext::new().request("zwlr_layer_surface_v1.ack_configure"),
                         );
                         return;
                     }
@@ -54,9 +54,10 @@ use wayland_protocols_wlr::layer_shell::v1::server::{
 use wayland_server::{
     backend::GlobalId,
     protocol::{wl_output::WlOutput, wl_surface},
-    Client, DisplayHandle, GlobalDispatch, Resource,
+    Client, DisplayHandle, GlobalDispatch,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     utils::{alive_tracker::IsAlive, Logical, Serial, Size, SERIAL_COUNTER},
     wayland::{
@@ -414,9 +415,11 @@ impl LayerSurface {
                 .configured
         });
         if !configured {
-            self.shell_surface.post_error(
+            post_error(
+                &self.shell_surface,
                 zwlr_layer_shell_v1::Error::AlreadyConstructed,
                 "layer_surface has never been configured",
+                ErrorContext::new().request("wl_surface.commit"),
             );
         }
         configured
@@ -96,6 +96,7 @@ use wayland_server::{
 };
 
 use super::{ToplevelSurface, XdgShellHandler};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::shell::xdg::XdgShellSurfaceUserData;
 
 /// Delegate type for handling xdg decoration events.
@@ -244,9 +245,11 @@ where
                 let mut decoration_guard = data.decoration.lock().unwrap();
 
                 if decoration_guard.is_some() {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         zxdg_toplevel_decoration_v1::Error::AlreadyConstructed,
                         "toplevel decoration is already constructed",
+                        ErrorContext::new().request("zxdg_toplevel_decoration_v1.get_toplevel_decoration"),
                     );
                     return;
                 }
@@ -84,6 +84,7 @@ use wayland_server::{
 };
 
 use super::{ToplevelSurface, XdgShellHandler};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::{
     compositor,
     shell::xdg::{XdgShellSurfaceUserData, XdgToplevelSurfaceData},
@@ -180,9 +181,11 @@ impl<D: XdgDialogHandler> Dispatch<XdgWmDialogV1, (), D> for XdgDialogState {
                 let mut dialog_guard = data.dialog.lock().unwrap();
 
                 if dialog_guard.is_some() {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         xdg_wm_dialog_v1::Error::AlreadyUsed,
                         "toplevel dialog is already constructed",
+                        ErrorContext::new().request("xdg_wm_dialog_v1.get_xdg_dialog"),
                     );
                     return;
                 }
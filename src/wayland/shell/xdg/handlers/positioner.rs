@@ -1,10 +1,11 @@
 use std::sync::Mutex;
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{utils::Rectangle, utils::Serial};
 
 use wayland_protocols::xdg::shell::server::{xdg_positioner, xdg_positioner::XdgPositioner};
 
-use wayland_server::{DataInit, Dispatch, DisplayHandle, Resource, WEnum};
+use wayland_server::{DataInit, Dispatch, DisplayHandle, WEnum};
 
 use super::{PositionerState, XdgShellHandler, XdgShellState};
 
@@ -37,9 +38,11 @@ where
         match request {
             xdg_positioner::Request::SetSize { width, height } => {
                 if width < 1 || height < 1 {
-                    positioner.post_error(
+                    post_error(
+                        positioner,
                         xdg_positioner::Error::InvalidInput,
                         "Invalid size for positioner.",
+                        ErrorContext::new().request("xdg_positioner.set_size"),
                     );
                 } else {
                     state.rect_size = (width, height).into();
@@ -47,9 +50,11 @@ where
             }
             xdg_positioner::Request::SetAnchorRect { x, y, width, height } => {
                 if width < 1 || height < 1 {
-                    positioner.post_error(
+                    post_error(
+                        positioner,
                         xdg_positioner::Error::InvalidInput,
                         "Invalid size for positioner's anchor rectangle.",
+                        ErrorContext::new().request("xdg_positioner.set_anchor_rect"),
                     );
                 } else {
                     state.anchor_rect = Rectangle::new((x, y).into(), (width, height).into());
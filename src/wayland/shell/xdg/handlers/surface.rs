@@ -10,6 +10,7 @@ use crate::{
     utils::{Rectangle, Serial},
     wayland::{
         compositor,
+        protocol_error::{post_error, ErrorContext},
         shell::xdg::{PopupState, XdgShellState, XDG_POPUP_ROLE, XDG_TOPLEVEL_ROLE},
     },
 };
@@ -82,9 +83,13 @@ where
                 }
 
                 if data.has_active_role.load(Ordering::Acquire) {
-                    data.wm_base.post_error(
+                    post_error(
+                        &data.wm_base,
                         xdg_wm_base::Error::Role,
                         "xdg_surface was destroyed before its role object",
+                        ErrorContext::new()
+                            .request("xdg_surface.destroy")
+                            .state("role", compositor::get_role(&data.wl_surface)),
                     );
                 }
             }
@@ -94,7 +99,14 @@ where
                 let shell = &data.wm_base;
 
                 if compositor::give_role(surface, XDG_TOPLEVEL_ROLE).is_err() {
-                    shell.post_error(xdg_wm_base::Error::Role, "Surface already has a role.");
+                    post_error(
+                        shell,
+                        xdg_wm_base::Error::Role,
+                        "Surface already has a role.",
+                        ErrorContext::new()
+                            .request("xdg_surface.get_toplevel")
+                            .state("role", compositor::get_role(surface)),
+                    );
                     return;
                 }
 
@@ -121,8 +133,9 @@ where
                 });
 
                 if initial {
-                    compositor::add_post_commit_hook::<D, _>(
+                    compositor::add_post_commit_hook_with_priority::<D, _>(
                         surface,
+                        compositor::HookPriority::Protocol,
                         super::super::ToplevelSurface::commit_hook,
                     );
                 }
@@ -179,7 +192,14 @@ where
                     ..Default::default()
                 };
                 if compositor::give_role(surface, XDG_POPUP_ROLE).is_err() {
-                    shell.post_error(xdg_wm_base::Error::Role, "Surface already has a role.");
+                    post_error(
+                        shell,
+                        xdg_wm_base::Error::Role,
+                        "Surface already has a role.",
+                        ErrorContext::new()
+                            .request("xdg_surface.get_popup")
+                            .state("role", compositor::get_role(surface)),
+                    );
                     return;
                 }
 
@@ -199,12 +219,14 @@ where
                 });
 
                 if initial {
-                    compositor::add_pre_commit_hook::<D, _>(
+                    compositor::add_pre_commit_hook_with_priority::<D, _>(
                         surface,
+                        compositor::HookPriority::Protocol,
                         super::super::PopupSurface::pre_commit_hook,
                     );
-                    compositor::add_post_commit_hook::<D, _>(
+                    compositor::add_post_commit_hook_with_priority::<D, _>(
                         surface,
+                        compositor::HookPriority::Protocol,
                         super::super::PopupSurface::post_commit_hook,
                     );
                 }
@@ -239,17 +261,23 @@ where
                 let role = compositor::get_role(surface);
 
                 if role.is_none() {
-                    xdg_surface.post_error(
+                    post_error(
+                        xdg_surface,
                         xdg_surface::Error::NotConstructed,
                         "xdg_surface must have a role.",
+                        ErrorContext::new().request("xdg_surface.set_window_geometry"),
                     );
                     return;
                 }
 
                 if role != Some(XDG_TOPLEVEL_ROLE) && role != Some(XDG_POPUP_ROLE) {
-                    data.wm_base.post_error(
+                    post_error(
+                        &data.wm_base,
                         xdg_wm_base::Error::Role,
                         "xdg_surface must have a role of xdg_toplevel or xdg_popup.",
+                        ErrorContext::new()
+                            .request("xdg_surface.set_window_geometry")
+                            .state("role", role),
                     );
                 }
 
@@ -266,9 +294,11 @@ where
                 // or xdg_popup. If none of the role matches the xdg_surface has no role set
                 // which is a protocol error.
                 if compositor::get_role(surface).is_none() {
-                    xdg_surface.post_error(
+                    post_error(
+                        xdg_surface,
                         xdg_surface::Error::NotConstructed,
                         "xdg_surface must have a role.",
+                        ErrorContext::new().request("xdg_surface.ack_configure"),
                     );
                     return;
                 }
@@ -310,16 +340,24 @@ where
                 let configure = match found_configure {
                     Ok(Some(configure)) => configure,
                     Ok(None) => {
-                        data.wm_base.post_error(
+                        post_error(
+                            &data.wm_base,
                             xdg_wm_base::Error::InvalidSurfaceState,
                             format!("wrong configure serial: {}", <u32>::from(serial)),
+                            ErrorContext::new()
+                                .request("xdg_surface.ack_configure")
+                                .state("role", compositor::get_role(surface)),
                         );
                         return;
                     }
                     Err(()) => {
-                        data.wm_base.post_error(
-                            xdg_wm_base::Error::Role as u32,
+                        post_error(
+                            &data.wm_base,
+                            xdg_wm_base::Error::Role,
                             "xdg_surface must have a role of xdg_toplevel or xdg_popup.",
+                            ErrorContext::new()
+                                .request("xdg_surface.ack_configure")
+                                .state("role", compositor::get_role(surface)),
                         );
                         return;
                     }
@@ -1,5 +1,6 @@
 use std::sync::atomic::Ordering;
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     utils::Serial,
     wayland::{
@@ -54,7 +55,12 @@ where
 
                 // Parent is not double buffered, we can set it directly
                 if !set_parent(toplevel, parent_surface) {
-                    toplevel.post_error(xdg_toplevel::Error::InvalidParent, "invalid parent toplevel");
+                    post_error(
+                        toplevel,
+                        xdg_toplevel::Error::InvalidParent,
+                        "invalid parent toplevel",
+                        ErrorContext::new().request("xdg_toplevel.set_parent"),
+                    );
                 }
 
                 if changed {
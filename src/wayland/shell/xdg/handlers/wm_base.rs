@@ -2,6 +2,7 @@ use std::sync::{atomic::AtomicBool, Arc, Mutex};
 
 use indexmap::IndexSet;
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     utils::{alive_tracker::AliveTracker, IsAlive, Serial},
     wayland::shell::xdg::XdgShellState,
@@ -96,9 +97,11 @@ where
             }
             xdg_wm_base::Request::Destroy => {
                 if !data.known_surfaces.lock().unwrap().is_empty() {
-                    wm_base.post_error(
+                    post_error(
+                        wm_base,
                         xdg_wm_base::Error::DefunctSurfaces,
                         "xdg_wm_base was destroyed before children",
+                        ErrorContext::new().request("xdg_wm_base.destroy"),
                     );
                 }
             }
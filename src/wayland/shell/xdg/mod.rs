@@ -119,15 +119,18 @@
 //! the [`XdgShellHandler`], or via methods on the [`XdgShellState`].
 
 use crate::utils::alive_tracker::IsAlive;
+use crate::utils::{
+    positioner::{self, Edges, Positioner},
+    Serial, SERIAL_COUNTER,
+};
 use crate::utils::{user_data::UserDataMap, Logical, Point, Rectangle, Size};
-use crate::utils::{Serial, SERIAL_COUNTER};
 use crate::wayland::compositor;
 use crate::wayland::compositor::Cacheable;
-use std::cmp::min;
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use std::{collections::HashSet, fmt::Debug, sync::Mutex};
 
 use wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1;
-use wayland_protocols::xdg::shell::server::xdg_positioner::{Anchor, ConstraintAdjustment, Gravity};
+use wayland_protocols::xdg::shell::server::xdg_positioner::{Anchor, Gravity};
 use wayland_protocols::xdg::shell::server::xdg_surface;
 use wayland_protocols::xdg::shell::server::xdg_wm_base::XdgWmBase;
 use wayland_protocols::xdg::shell::server::{xdg_popup, xdg_positioner, xdg_toplevel, xdg_wm_base};
@@ -543,55 +546,40 @@ impl Default for PositionerState {
 }
 
 impl PositionerState {
-    pub(crate) fn anchor_has_edge(&self, edge: xdg_positioner::Anchor) -> bool {
-        match edge {
-            xdg_positioner::Anchor::Top => {
-                self.anchor_edges == xdg_positioner::Anchor::Top
-                    || self.anchor_edges == xdg_positioner::Anchor::TopLeft
-                    || self.anchor_edges == xdg_positioner::Anchor::TopRight
-            }
-            xdg_positioner::Anchor::Bottom => {
-                self.anchor_edges == xdg_positioner::Anchor::Bottom
-                    || self.anchor_edges == xdg_positioner::Anchor::BottomLeft
-                    || self.anchor_edges == xdg_positioner::Anchor::BottomRight
-            }
-            xdg_positioner::Anchor::Left => {
-                self.anchor_edges == xdg_positioner::Anchor::Left
-                    || self.anchor_edges == xdg_positioner::Anchor::TopLeft
-                    || self.anchor_edges == xdg_positioner::Anchor::BottomLeft
-            }
-            xdg_positioner::Anchor::Right => {
-                self.anchor_edges == xdg_positioner::Anchor::Right
-                    || self.anchor_edges == xdg_positioner::Anchor::TopRight
-                    || self.anchor_edges == xdg_positioner::Anchor::BottomRight
-            }
-            _ => unreachable!(),
-        }
-    }
+    /// Returns the placement rules of this positioner for use with [`positioner::solve`]
+    pub fn positioner(&self) -> Positioner {
+        let anchor = match self.anchor_edges {
+            Anchor::Top => Edges::TOP,
+            Anchor::Bottom => Edges::BOTTOM,
+            Anchor::Left => Edges::LEFT,
+            Anchor::Right => Edges::RIGHT,
+            Anchor::TopLeft => Edges::TOP | Edges::LEFT,
+            Anchor::BottomLeft => Edges::BOTTOM | Edges::LEFT,
+            Anchor::TopRight => Edges::TOP | Edges::RIGHT,
+            Anchor::BottomRight => Edges::BOTTOM | Edges::RIGHT,
+            _ => Edges::empty(),
+        };
+        let gravity = match self.gravity {
+            Gravity::Top => Edges::TOP,
+            Gravity::Bottom => Edges::BOTTOM,
+            Gravity::Left => Edges::LEFT,
+            Gravity::Right => Edges::RIGHT,
+            Gravity::TopLeft => Edges::TOP | Edges::LEFT,
+            Gravity::BottomLeft => Edges::BOTTOM | Edges::LEFT,
+            Gravity::TopRight => Edges::TOP | Edges::RIGHT,
+            Gravity::BottomRight => Edges::BOTTOM | Edges::RIGHT,
+            _ => Edges::empty(),
+        };
 
-    pub(crate) fn gravity_has_edge(&self, edge: xdg_positioner::Gravity) -> bool {
-        match edge {
-            xdg_positioner::Gravity::Top => {
-                self.gravity == xdg_positioner::Gravity::Top
-                    || self.gravity == xdg_positioner::Gravity::TopLeft
-                    || self.gravity == xdg_positioner::Gravity::TopRight
-            }
-            xdg_positioner::Gravity::Bottom => {
-                self.gravity == xdg_positioner::Gravity::Bottom
-                    || self.gravity == xdg_positioner::Gravity::BottomLeft
-                    || self.gravity == xdg_positioner::Gravity::BottomRight
-            }
-            xdg_positioner::Gravity::Left => {
-                self.gravity == xdg_positioner::Gravity::Left
-                    || self.gravity == xdg_positioner::Gravity::TopLeft
-                    || self.gravity == xdg_positioner::Gravity::BottomLeft
-            }
-            xdg_positioner::Gravity::Right => {
-                self.gravity == xdg_positioner::Gravity::Right
-                    || self.gravity == xdg_positioner::Gravity::TopRight
-                    || self.gravity == xdg_positioner::Gravity::BottomRight
-            }
-            _ => unreachable!(),
+        Positioner {
+            size: self.rect_size,
+            anchor_rect: self.anchor_rect,
+            anchor,
+            gravity,
+            offset: self.offset,
+            constraint_adjustment: positioner::ConstraintAdjustment::from_bits_truncate(
+                self.constraint_adjustment.bits(),
+            ),
         }
     }
 
@@ -610,58 +598,7 @@ impl PositionerState {
     ///
     /// [`PositionerState::get_unconstrained_geometry`] does take `constraint_adjustment` into account.
     pub fn get_geometry(&self) -> Rectangle<i32, Logical> {
-        // From the `xdg_shell` prococol specification:
-        //
-        // set_offset:
-        //
-        //  Specify the surface position offset relative to the position of the
-        //  anchor on the anchor rectangle and the anchor on the surface. For
-        //  example if the anchor of the anchor rectangle is at (x, y), the surface
-        //  has the gravity bottom|right, and the offset is (ox, oy), the calculated
-        //  surface position will be (x + ox, y + oy)
-        let mut geometry = Rectangle::new(self.offset, self.rect_size);
-
-        // Defines the anchor point for the anchor rectangle. The specified anchor
-        // is used derive an anchor point that the child surface will be
-        // positioned relative to. If a corner anchor is set (e.g. 'top_left' or
-        // 'bottom_right'), the anchor point will be at the specified corner;
-        // otherwise, the derived anchor point will be centered on the specified
-        // edge, or in the center of the anchor rectangle if no edge is specified.
-        if self.anchor_has_edge(xdg_positioner::Anchor::Top) {
-            geometry.loc.y += self.anchor_rect.loc.y;
-        } else if self.anchor_has_edge(xdg_positioner::Anchor::Bottom) {
-            geometry.loc.y += self.anchor_rect.loc.y + self.anchor_rect.size.h;
-        } else {
-            geometry.loc.y += self.anchor_rect.loc.y + self.anchor_rect.size.h / 2;
-        }
-
-        if self.anchor_has_edge(xdg_positioner::Anchor::Left) {
-            geometry.loc.x += self.anchor_rect.loc.x;
-        } else if self.anchor_has_edge(xdg_positioner::Anchor::Right) {
-            geometry.loc.x += self.anchor_rect.loc.x + self.anchor_rect.size.w;
-        } else {
-            geometry.loc.x += self.anchor_rect.loc.x + self.anchor_rect.size.w / 2;
-        }
-
-        // Defines in what direction a surface should be positioned, relative to
-        // the anchor point of the parent surface. If a corner gravity is
-        // specified (e.g. 'bottom_right' or 'top_left'), then the child surface
-        // will be placed towards the specified gravity; otherwise, the child
-        // surface will be centered over the anchor point on any axis that had no
-        // gravity specified.
-        if self.gravity_has_edge(xdg_positioner::Gravity::Top) {
-            geometry.loc.y -= geometry.size.h;
-        } else if !self.gravity_has_edge(xdg_positioner::Gravity::Bottom) {
-            geometry.loc.y -= geometry.size.h / 2;
-        }
-
-        if self.gravity_has_edge(xdg_positioner::Gravity::Left) {
-            geometry.loc.x -= geometry.size.w;
-        } else if !self.gravity_has_edge(xdg_positioner::Gravity::Right) {
-            geometry.loc.x -= geometry.size.w / 2;
-        }
-
-        geometry
+        self.positioner().geometry()
     }
 
     /// Get the geometry for a popup as defined by this positioner, after trying to fit the popup into the
@@ -675,163 +612,10 @@ impl PositionerState {
     ///
     /// This method does consider `constrain_adjustment` by trying to fit the popup into the provided target
     /// rectangle. The target rectangle is in the same coordinate system as the rectangle returned by this
-    /// method. So, it is relative to the parent surface's geometry.
-    pub fn get_unconstrained_geometry(mut self, target: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
-        // The protocol defines the following order for adjustments: flip, slide, resize. If the flip fails
-        // to remove the constraints, it is reverted.
-        //
-        // The adjustments are applied individually between axes. We can do that reasonably safely, given
-        // that both our target and our popup are simple rectangles. The code is grouped per adjustment for
-        // easier copy-paste checking, and because flips replace the geometry entirely, while further
-        // adjustments change individual fields.
-        let mut geo = self.get_geometry();
-        let (mut off_left, mut off_right, mut off_top, mut off_bottom) = compute_offsets(target, geo);
-
-        // Try to flip horizontally.
-        if (off_left > 0 || off_right > 0) && self.constraint_adjustment.contains(ConstraintAdjustment::FlipX)
-        {
-            let mut new = self;
-            new.anchor_edges = invert_anchor_x(new.anchor_edges);
-            new.gravity = invert_gravity_x(new.gravity);
-            let new_geo = new.get_geometry();
-            let (new_off_left, new_off_right, _, _) = compute_offsets(target, new_geo);
-
-            // Apply flip only if it removed the constraint.
-            if new_off_left <= 0 && new_off_right <= 0 {
-                self = new;
-                geo = new_geo;
-                off_left = 0;
-                off_right = 0;
-                // off_top and off_bottom are unchanged since we're using rectangles.
-            }
-        }
-
-        // Try to flip vertically.
-        if (off_top > 0 || off_bottom > 0) && self.constraint_adjustment.contains(ConstraintAdjustment::FlipY)
-        {
-            let mut new = self;
-            new.anchor_edges = invert_anchor_y(new.anchor_edges);
-            new.gravity = invert_gravity_y(new.gravity);
-            let new_geo = new.get_geometry();
-            let (_, _, new_off_top, new_off_bottom) = compute_offsets(target, new_geo);
-
-            // Apply flip only if it removed the constraint.
-            if new_off_top <= 0 && new_off_bottom <= 0 {
-                self = new;
-                geo = new_geo;
-                off_top = 0;
-                off_bottom = 0;
-                // off_left and off_right are unchanged since we're using rectangles.
-            }
-        }
-
-        // Try to slide horizontally.
-        if (off_left > 0 || off_right > 0)
-            && self.constraint_adjustment.contains(ConstraintAdjustment::SlideX)
-        {
-            // Prefer to show the top-left corner of the popup so that we can easily do a resize
-            // adjustment next.
-            if off_left > 0 {
-                geo.loc.x += off_left;
-            } else if off_right > 0 {
-                geo.loc.x -= min(off_right, -off_left);
-            }
-
-            (_, off_right, _, _) = compute_offsets(target, geo);
-            // off_top and off_bottom are the same since we're using rectangles.
-        }
-
-        // Try to slide vertically.
-        if (off_top > 0 || off_bottom > 0)
-            && self.constraint_adjustment.contains(ConstraintAdjustment::SlideY)
-        {
-            // Prefer to show the top-left corner of the popup so that we can easily do a resize
-            // adjustment next.
-            if off_top > 0 {
-                geo.loc.y += off_top;
-            } else if off_bottom > 0 {
-                geo.loc.y -= min(off_bottom, -off_top);
-            }
-
-            (_, _, _, off_bottom) = compute_offsets(target, geo);
-            // off_left and off_right are the same since we're using rectangles.
-        }
-
-        // Try to resize horizontally. This makes sense only if the popup is at least partially to the left
-        // of the right target edge, which is the same as checking that the offset is smaller than the width.
-        if off_right > 0
-            && off_right < geo.size.w
-            && self.constraint_adjustment.contains(ConstraintAdjustment::ResizeX)
-        {
-            geo.size.w -= off_right;
-        }
-
-        // Try to resize vertically. This makes sense only if the popup is at least partially to the top of
-        // the bottom target edge, which is the same as checking that the offset is smaller than the height.
-        if off_bottom > 0
-            && off_bottom < geo.size.h
-            && self.constraint_adjustment.contains(ConstraintAdjustment::ResizeY)
-        {
-            geo.size.h -= off_bottom;
-        }
-
-        geo
-    }
-}
-
-fn compute_offsets(target: Rectangle<i32, Logical>, popup: Rectangle<i32, Logical>) -> (i32, i32, i32, i32) {
-    let off_left = target.loc.x - popup.loc.x;
-    let off_right = (popup.loc.x + popup.size.w) - (target.loc.x + target.size.w);
-    let off_top = target.loc.y - popup.loc.y;
-    let off_bottom = (popup.loc.y + popup.size.h) - (target.loc.y + target.size.h);
-    (off_left, off_right, off_top, off_bottom)
-}
-
-fn invert_anchor_x(anchor: Anchor) -> Anchor {
-    match anchor {
-        Anchor::Left => Anchor::Right,
-        Anchor::Right => Anchor::Left,
-        Anchor::TopLeft => Anchor::TopRight,
-        Anchor::TopRight => Anchor::TopLeft,
-        Anchor::BottomLeft => Anchor::BottomRight,
-        Anchor::BottomRight => Anchor::BottomLeft,
-        x => x,
-    }
-}
-
-fn invert_anchor_y(anchor: Anchor) -> Anchor {
-    match anchor {
-        Anchor::Top => Anchor::Bottom,
-        Anchor::Bottom => Anchor::Top,
-        Anchor::TopLeft => Anchor::BottomLeft,
-        Anchor::TopRight => Anchor::BottomRight,
-        Anchor::BottomLeft => Anchor::TopLeft,
-        Anchor::BottomRight => Anchor::TopRight,
-        x => x,
-    }
-}
-
-fn invert_gravity_x(gravity: Gravity) -> Gravity {
-    match gravity {
-        Gravity::Left => Gravity::Right,
-        Gravity::Right => Gravity::Left,
-        Gravity::TopLeft => Gravity::TopRight,
-        Gravity::TopRight => Gravity::TopLeft,
-        Gravity::BottomLeft => Gravity::BottomRight,
-        Gravity::BottomRight => Gravity::BottomLeft,
-        x => x,
-    }
-}
-
-fn invert_gravity_y(gravity: Gravity) -> Gravity {
-    match gravity {
-        Gravity::Top => Gravity::Bottom,
-        Gravity::Bottom => Gravity::Top,
-        Gravity::TopLeft => Gravity::BottomLeft,
-        Gravity::TopRight => Gravity::BottomRight,
-        Gravity::BottomLeft => Gravity::TopLeft,
-        Gravity::BottomRight => Gravity::TopRight,
-        x => x,
+    /// method. So, it is relative to the parent surface's geometry. See [`positioner::solve`] for the
+    /// details.
+    pub fn get_unconstrained_geometry(self, target: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        positioner::solve(&self.positioner(), target)
     }
 }
 
@@ -1348,9 +1132,11 @@ impl ShellClient {
         if !self.alive() {
             return Err(crate::utils::DeadResource);
         }
-        self.kind.post_error(
+        post_error(
+            &self.kind,
             xdg_wm_base::Error::Unresponsive,
             "client did not respond to ping on time",
+            ErrorContext::new(),
         );
 
         Ok(())
@@ -1640,9 +1426,11 @@ impl ToplevelSurface {
                 .shell_surface
                 .data::<self::handlers::XdgShellSurfaceUserData>()
                 .unwrap();
-            data.xdg_surface.post_error(
+            post_error(
+                &data.xdg_surface,
                 xdg_surface::Error::NotConstructed,
                 "Surface has not been configured yet.",
+                ErrorContext::new().request("wl_surface.commit"),
             );
         }
         configured
@@ -1961,9 +1749,11 @@ impl PopupSurface {
         });
         if let Some(handle) = send_error_to {
             let data = handle.data::<self::handlers::XdgShellSurfaceUserData>().unwrap();
-            data.xdg_surface.post_error(
+            post_error(
+                &data.xdg_surface,
                 xdg_surface::Error::NotConstructed,
                 "Surface has not been configured yet.",
+                ErrorContext::new().request("wl_surface.commit"),
             );
         }
     }
@@ -2033,9 +1823,11 @@ impl PopupSurface {
                 .shell_surface
                 .data::<self::handlers::XdgShellSurfaceUserData>()
                 .unwrap();
-            data.xdg_surface.post_error(
+            post_error(
+                &data.xdg_surface,
                 xdg_surface::Error::NotConstructed,
                 "Surface has not been configured yet.",
+                ErrorContext::new().request("wl_surface.commit"),
             );
         }
         configured
@@ -1,3 +1,4 @@
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::{
     buffer::BufferHandler,
     shm::{wl_bytes_per_pixel, ShmBufferUserData},
@@ -16,7 +17,7 @@ use wayland_server::{
         wl_shm::{self, WlShm},
         wl_shm_pool::{self, WlShmPool},
     },
-    DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+    DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, WEnum,
 };
 
 impl<D> GlobalDispatch<WlShm, (), D> for ShmState
@@ -66,16 +67,23 @@ where
         };
 
         if size <= 0 {
-            shm.post_error(Error::InvalidStride, "invalid wl_shm_pool size");
+            post_error(
+                shm,
+                Error::InvalidStride,
+                "invalid wl_shm_pool size",
+                ErrorContext::new().request("wl_shm.create_pool"),
+            );
             return;
         }
 
         let mmap_pool = match Pool::new(fd, NonZeroUsize::try_from(size as usize).unwrap()) {
             Ok(p) => p,
             Err(fd) => {
-                shm.post_error(
+                post_error(
+                    shm,
                     wl_shm::Error::InvalidFd,
                     format!("Failed to mmap fd {}", fd.as_raw_fd()),
+                    ErrorContext::new().request("wl_shm.create_pool"),
                 );
                 return;
             }
@@ -148,16 +156,23 @@ where
                 };
 
                 if let Some(message) = message {
-                    pool.post_error(wl_shm::Error::InvalidStride, message);
+                    post_error(
+                        pool,
+                        wl_shm::Error::InvalidStride,
+                        message,
+                        ErrorContext::new().request("wl_shm_pool.create_buffer"),
+                    );
                     return;
                 }
 
                 match format {
                     WEnum::Value(format) => {
                         if !state.shm_state().formats.contains(&format) {
-                            pool.post_error(
+                            post_error(
+                                pool,
                                 wl_shm::Error::InvalidFormat,
                                 format!("format {:?} not supported", format),
+                                ErrorContext::new().request("wl_shm_pool.create_buffer"),
                             );
 
                             return;
@@ -179,9 +194,11 @@ where
                     }
 
                     WEnum::Unknown(unknown) => {
-                        pool.post_error(
+                        post_error(
+                            pool,
                             wl_shm::Error::InvalidFormat,
                             format!("unknown format 0x{:x}", unknown),
+                            ErrorContext::new().request("wl_shm_pool.create_buffer"),
                         );
                     }
                 }
@@ -189,17 +206,32 @@ where
 
             Request::Resize { size } => {
                 if size <= 0 {
-                    pool.post_error(wl_shm::Error::InvalidFd, "invalid wl_shm_pool size");
+                    post_error(
+                        pool,
+                        wl_shm::Error::InvalidFd,
+                        "invalid wl_shm_pool size",
+                        ErrorContext::new().request("wl_shm_pool.resize"),
+                    );
                 }
 
                 if let Err(err) = arc_pool.resize(NonZeroUsize::try_from(size as usize).unwrap()) {
                     match err {
                         ResizeError::InvalidSize => {
-                            pool.post_error(wl_shm::Error::InvalidFd, "cannot shrink wl_shm_pool");
+                            post_error(
+                                pool,
+                                wl_shm::Error::InvalidFd,
+                                "cannot shrink wl_shm_pool",
+                                ErrorContext::new().request("wl_shm_pool.resize"),
+                            );
                         }
 
                         ResizeError::MremapFailed => {
-                            pool.post_error(wl_shm::Error::InvalidFd, "mremap failed");
+                            post_error(
+                                pool,
+                                wl_shm::Error::InvalidFd,
+                                "mremap failed",
+                                ErrorContext::new().request("wl_shm_pool.resize"),
+                            );
                         }
                     }
                 }
@@ -237,8 +269,8 @@ where
 
     fn destroyed(data: &mut D, _client: ClientId, buffer: &wl_buffer::WlBuffer, udata: &ShmBufferUserData) {
         // Clone to drop the mutex guard
-        let destruction_hooks = udata.destruction_hooks.lock().unwrap().clone();
-        for hook in destruction_hooks.iter() {
+        let destruction_hooks = udata.destruction_hooks.lock().unwrap().snapshot();
+        for hook in destruction_hooks.iter().filter(|hook| !hook.is_removed()) {
             (hook.cb)(data, buffer);
         }
 
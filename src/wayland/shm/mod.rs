@@ -114,9 +114,13 @@ use wayland_server::{
 mod handlers;
 mod pool;
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     backend::allocator::format::get_bpp,
-    utils::{hook::Hook, HookId, UnmanagedResource},
+    utils::{
+        hook::{Hook, HookList},
+        HookId, UnmanagedResource,
+    },
 };
 
 use self::pool::Pool;
@@ -250,7 +254,12 @@ where
         Ok(t) => Ok(t),
         Err(()) => {
             // SIGBUS error occurred
-            buffer.post_error(wl_shm::Error::InvalidFd, "Bad pool size.");
+            post_error(
+                buffer,
+                wl_shm::Error::InvalidFd,
+                "Bad pool size.",
+                ErrorContext::new(),
+            );
             Err(BufferAccessError::BadMap)
         }
     }
@@ -288,7 +297,12 @@ where
         Ok(t) => Ok(t),
         Err(()) => {
             // SIGBUS error occurred
-            buffer.post_error(wl_shm::Error::InvalidFd, "Bad pool size.");
+            post_error(
+                buffer,
+                wl_shm::Error::InvalidFd,
+                "Bad pool size.",
+                ErrorContext::new(),
+            );
             Err(BufferAccessError::BadMap)
         }
     }
@@ -477,7 +491,7 @@ type DestructionHook = dyn Fn(&mut dyn Any, &wl_buffer::WlBuffer) + Send + Sync;
 pub struct ShmBufferUserData {
     pub(crate) pool: Arc<Pool>,
     pub(crate) data: BufferData,
-    destruction_hooks: Mutex<Vec<Hook<DestructionHook>>>,
+    destruction_hooks: Mutex<HookList<DestructionHook>>,
 }
 
 impl ShmBufferUserData {
@@ -486,16 +500,11 @@ impl ShmBufferUserData {
         hook: impl Fn(&mut dyn Any, &wl_buffer::WlBuffer) + Send + Sync + 'static,
     ) -> HookId {
         let hook: Hook<DestructionHook> = Hook::new(Arc::new(hook));
-        let id = hook.id;
-        self.destruction_hooks.lock().unwrap().push(hook);
-        id
+        self.destruction_hooks.lock().unwrap().insert(hook)
     }
 
     pub(crate) fn remove_destruction_hook(&self, hook_id: HookId) {
-        let mut guard = self.destruction_hooks.lock().unwrap();
-        if let Some(id) = guard.iter().position(|hook| hook.id != hook_id) {
-            guard.remove(id);
-        }
+        self.destruction_hooks.lock().unwrap().remove(hook_id);
     }
 }
 
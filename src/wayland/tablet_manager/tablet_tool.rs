@@ -6,6 +6,7 @@ use crate::backend::input::{ButtonState, TabletToolCapabilities, TabletToolDescr
 use crate::input::pointer::{CursorImageAttributes, CursorImageStatus};
 use crate::utils::{Client as ClientCoords, Logical, Point};
 use crate::wayland::compositor::CompositorHandler;
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::seat::CURSOR_IMAGE_ROLE;
 use wayland_protocols::wp::tablet::zv2::server::{
     zwp_tablet_seat_v2::ZwpTabletSeatV2,
@@ -495,9 +496,11 @@ where
                             if compositor::give_role(&surface, CURSOR_IMAGE_ROLE).is_err()
                                 && compositor::get_role(&surface) != Some(CURSOR_IMAGE_ROLE)
                             {
-                                tool.post_error(
+                                post_error(
+                                    tool,
                                     zwp_tablet_tool_v2::Error::Role,
                                     "Given wl_surface has another role.",
+                                    ErrorContext::new().request("zwp_tablet_tool_v2.set_cursor"),
                                 );
                                 return;
                             }
@@ -0,0 +1,850 @@
+//! Connected wayland client and server for unit tests
+//!
+//! The client is driven from the same thread as the server, [`TestFixture::roundtrip`] exchanges
+//! all pending requests and events between them.
+
+#![allow(dead_code)]
+
+use std::{
+    io::Write,
+    os::unix::{io::AsFd, net::UnixStream},
+    sync::Arc,
+};
+
+use wayland_client::{
+    backend::protocol::ProtocolError,
+    delegate_noop, event_created_child,
+    protocol::{
+        wl_buffer, wl_callback, wl_compositor, wl_data_device, wl_data_device_manager, wl_data_offer,
+        wl_data_source, wl_keyboard, wl_output, wl_region, wl_registry, wl_seat, wl_shm, wl_shm_pool,
+        wl_subcompositor, wl_subsurface, wl_surface,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::{
+    wp::{
+        fullscreen_shell::zv1::client::{zwp_fullscreen_shell_mode_feedback_v1, zwp_fullscreen_shell_v1},
+        linux_dmabuf::zv1::client::{zwp_linux_buffer_params_v1, zwp_linux_dmabuf_v1},
+    },
+    xdg::{
+        foreign::{
+            zv1::client::{zxdg_exported_v1, zxdg_exporter_v1, zxdg_imported_v1, zxdg_importer_v1},
+            zv2::client::{zxdg_exported_v2, zxdg_exporter_v2, zxdg_imported_v2, zxdg_importer_v2},
+        },
+        shell::client::{xdg_popup, xdg_positioner, xdg_surface, xdg_toplevel, xdg_wm_base},
+    },
+};
+use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+use wayland_server::{
+    backend::{ClientData, ClientId, DisconnectReason},
+    protocol::{wl_output::WlOutput, wl_seat::WlSeat, wl_surface::WlSurface},
+    Client, Display, Resource,
+};
+
+use crate::{
+    backend::{
+        allocator::dmabuf::Dmabuf,
+        renderer::utils::{on_commit_buffer_handler_with_policy, InconsistentBufferPolicy},
+    },
+    delegate_compositor, delegate_data_device, delegate_dmabuf, delegate_fullscreen_shell,
+    delegate_layer_shell, delegate_output, delegate_seat, delegate_shm, delegate_xdg_foreign,
+    delegate_xdg_shell,
+    input::{keyboard::LedState, Seat, SeatHandler, SeatState},
+    output::{Output, PhysicalProperties, Subpixel},
+    utils::Serial,
+    wayland::{
+        buffer::BufferHandler,
+        compositor::{CompositorClientState, CompositorHandler, CompositorState},
+        dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier},
+        output::OutputHandler,
+        selection::{
+            data_device::{ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler},
+            SelectionHandler,
+        },
+        shell::{
+            fullscreen::{FullscreenShellHandler, FullscreenShellState, FullscreenSurface, ModeFeedback},
+            wlr_layer::{Layer, LayerSurface, WlrLayerShellHandler, WlrLayerShellState},
+            xdg::{PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler, XdgShellState},
+        },
+        shm::{ShmHandler, ShmState},
+        xdg_foreign::{XdgForeignHandler, XdgForeignState},
+    },
+};
+
+/// Server state of the fixture
+#[derive(Debug)]
+pub(crate) struct TestState {
+    pub compositor: CompositorState,
+    pub shm: ShmState,
+    pub xdg_shell: XdgShellState,
+    pub layer_shell: WlrLayerShellState,
+    pub data_device: DataDeviceState,
+    pub xdg_foreign: XdgForeignState,
+    pub dmabuf: DmabufState,
+    pub fullscreen_shell: FullscreenShellState,
+    pub seat_state: SeatState<TestState>,
+    pub seat: Seat<TestState>,
+    /// Output advertised to the client
+    pub output: Output,
+    pub buffer_policy: InconsistentBufferPolicy,
+    /// Toplevels created by the client
+    pub toplevels: Vec<ToplevelSurface>,
+    /// Popups created by the client
+    pub popups: Vec<PopupSurface>,
+    /// Layer surfaces created by the client
+    pub layers: Vec<LayerSurface>,
+    /// Whether the drops of client initiated drags were validated
+    pub dnd_dropped: Vec<bool>,
+    /// Number of cancelled compositor initiated drags
+    pub server_dnd_cancelled: usize,
+    /// Led states reported for the keyboard of the seat
+    pub led_states: Vec<LedState>,
+    /// Dmabufs imported by the client, waiting to be accepted or rejected
+    pub dmabuf_imports: Vec<(Dmabuf, ImportNotifier)>,
+    /// Pending mode switches of fullscreen shell surfaces
+    pub fullscreen_mode_switches: Vec<(FullscreenSurface, ModeFeedback)>,
+    /// Commits of presented fullscreen shell surfaces
+    pub fullscreen_commits: Vec<(FullscreenSurface, Output)>,
+}
+
+#[derive(Debug, Default)]
+struct TestClientData {
+    compositor: CompositorClientState,
+}
+
+impl ClientData for TestClientData {
+    fn initialized(&self, _client_id: ClientId) {}
+    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
+}
+
+impl CompositorHandler for TestState {
+    fn compositor_state(&mut self) -> &mut CompositorState {
+        &mut self.compositor
+    }
+
+    fn client_compositor_state<'a>(&self, client: &'a Client) -> &'a CompositorClientState {
+        &client.get_data::<TestClientData>().unwrap().compositor
+    }
+
+    fn commit(&mut self, surface: &WlSurface) {
+        on_commit_buffer_handler_with_policy::<Self>(surface, self.buffer_policy);
+
+        // answer the initial commits of shell surfaces
+        if let Some(toplevel) = self.toplevels.iter().find(|t| t.wl_surface() == surface) {
+            toplevel.send_pending_configure();
+        }
+        if let Some(popup) = self.popups.iter().find(|p| p.wl_surface() == surface) {
+            if !popup.is_initial_configure_sent() {
+                popup.send_configure().unwrap();
+            }
+        }
+        if let Some(layer) = self.layers.iter().find(|l| l.wl_surface() == surface) {
+            layer.send_pending_configure();
+        }
+    }
+}
+
+impl SeatHandler for TestState {
+    type KeyboardFocus = WlSurface;
+    type PointerFocus = WlSurface;
+    type TouchFocus = WlSurface;
+
+    fn seat_state(&mut self) -> &mut SeatState<Self> {
+        &mut self.seat_state
+    }
+
+    fn led_state_changed(&mut self, _seat: &Seat<Self>, led_state: LedState) {
+        self.led_states.push(led_state);
+    }
+}
+
+impl XdgShellHandler for TestState {
+    fn xdg_shell_state(&mut self) -> &mut XdgShellState {
+        &mut self.xdg_shell
+    }
+
+    fn new_toplevel(&mut self, surface: ToplevelSurface) {
+        self.toplevels.push(surface);
+    }
+
+    fn new_popup(&mut self, surface: PopupSurface, _positioner: PositionerState) {
+        self.popups.push(surface);
+    }
+
+    fn grab(&mut self, _surface: PopupSurface, _seat: WlSeat, _serial: Serial) {}
+
+    fn reposition_request(&mut self, _surface: PopupSurface, _positioner: PositionerState, _token: u32) {}
+}
+
+impl WlrLayerShellHandler for TestState {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.layer_shell
+    }
+
+    fn new_layer_surface(
+        &mut self,
+        surface: LayerSurface,
+        _output: Option<WlOutput>,
+        _layer: Layer,
+        _namespace: String,
+    ) {
+        self.layers.push(surface);
+    }
+}
+
+impl SelectionHandler for TestState {
+    type SelectionUserData = ();
+}
+
+impl DataDeviceHandler for TestState {
+    fn data_device_state(&self) -> &DataDeviceState {
+        &self.data_device
+    }
+}
+
+impl ClientDndGrabHandler for TestState {
+    fn dropped(&mut self, _target: Option<WlSurface>, validated: bool, _seat: Seat<Self>) {
+        self.dnd_dropped.push(validated);
+    }
+}
+
+impl ServerDndGrabHandler for TestState {
+    fn cancelled(&mut self, _seat: Seat<Self>) {
+        self.server_dnd_cancelled += 1;
+    }
+}
+
+impl XdgForeignHandler for TestState {
+    fn xdg_foreign_state(&mut self) -> &mut XdgForeignState {
+        &mut self.xdg_foreign
+    }
+}
+
+impl DmabufHandler for TestState {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.dmabuf
+    }
+
+    fn dmabuf_imported(&mut self, _global: &DmabufGlobal, dmabuf: Dmabuf, notifier: ImportNotifier) {
+        self.dmabuf_imports.push((dmabuf, notifier));
+    }
+}
+
+impl FullscreenShellHandler for TestState {
+    fn fullscreen_shell_state(&mut self) -> &mut FullscreenShellState {
+        &mut self.fullscreen_shell
+    }
+
+    fn present_surface(&mut self, surface: Option<FullscreenSurface>, output: Option<Output>) {
+        let output = output.unwrap_or_else(|| self.output.clone());
+        self.fullscreen_shell.present(&output, surface);
+    }
+
+    fn present_surface_for_mode(
+        &mut self,
+        surface: FullscreenSurface,
+        _output: Output,
+        feedback: ModeFeedback,
+    ) {
+        self.fullscreen_mode_switches.push((surface, feedback));
+    }
+
+    fn presented_surface_committed(&mut self, surface: &FullscreenSurface, output: &Output) {
+        self.fullscreen_commits.push((surface.clone(), output.clone()));
+    }
+}
+
+impl OutputHandler for TestState {}
+
+impl BufferHandler for TestState {
+    fn buffer_destroyed(&mut self, _buffer: &wayland_server::protocol::wl_buffer::WlBuffer) {}
+}
+
+impl ShmHandler for TestState {
+    fn shm_state(&self) -> &ShmState {
+        &self.shm
+    }
+}
+
+delegate_compositor!(TestState);
+delegate_shm!(TestState);
+delegate_xdg_shell!(TestState);
+delegate_seat!(TestState);
+delegate_layer_shell!(TestState);
+delegate_data_device!(TestState);
+delegate_xdg_foreign!(TestState);
+delegate_dmabuf!(TestState);
+delegate_fullscreen_shell!(TestState);
+delegate_output!(TestState);
+
+/// Client state of the fixture
+#[derive(Debug, Default)]
+pub(crate) struct TestClient {
+    globals: Vec<(u32, String, u32)>,
+    /// Buffers released by the server
+    pub released: Vec<wl_buffer::WlBuffer>,
+    /// Offers of the drags that entered a surface of the client
+    pub dnd_entered: Vec<Option<wl_data_offer::WlDataOffer>>,
+    /// Number of drops on a surface of the client
+    pub dnd_dropped: usize,
+    /// Events received by data sources of the client
+    pub source_events: Vec<wl_data_source::Event>,
+    /// Events received by keyboards of the client, except for the keymap and repeat info
+    pub keyboard_events: Vec<wl_keyboard::Event>,
+    /// Handles of the toplevels exported by the client
+    pub exported_handles: Vec<String>,
+    /// Number of imported toplevels, which were destroyed by the server
+    pub imported_destroyed: usize,
+    /// Buffers created from dmabuf params
+    pub dmabuf_created: Vec<wl_buffer::WlBuffer>,
+    /// Number of failed dmabuf imports
+    pub dmabuf_failed: usize,
+    /// Events received by fullscreen shell mode feedbacks
+    pub mode_feedback_events: Vec<zwp_fullscreen_shell_mode_feedback_v1::Event>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            state.globals.push((name, interface, version));
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            state.released.push(proxy.clone());
+        }
+    }
+}
+
+impl Dispatch<xdg_wm_base::XdgWmBase, ()> for TestClient {
+    fn event(
+        _state: &mut Self,
+        proxy: &xdg_wm_base::XdgWmBase,
+        event: xdg_wm_base::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            proxy.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<xdg_surface::XdgSurface, ()> for TestClient {
+    fn event(
+        _state: &mut Self,
+        proxy: &xdg_surface::XdgSurface,
+        event: xdg_surface::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            proxy.ack_configure(serial);
+        }
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for TestClient {
+    fn event(
+        _state: &mut Self,
+        proxy: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let zwlr_layer_surface_v1::Event::Configure { serial, .. } = event {
+            proxy.ack_configure(serial);
+        }
+    }
+}
+
+impl Dispatch<wl_data_device::WlDataDevice, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_data_device::WlDataDevice,
+        event: wl_data_device::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_device::Event::Enter { id, .. } => state.dnd_entered.push(id),
+            wl_data_device::Event::Drop => state.dnd_dropped += 1,
+            _ => {}
+        }
+    }
+
+    event_created_child!(TestClient, wl_data_device::WlDataDevice, [
+        wl_data_device::EVT_DATA_OFFER_OPCODE => (wl_data_offer::WlDataOffer, ()),
+    ]);
+}
+
+impl Dispatch<wl_data_source::WlDataSource, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_data_source::WlDataSource,
+        event: wl_data_source::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        state.source_events.push(event);
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &wl_keyboard::WlKeyboard,
+        event: wl_keyboard::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if !matches!(
+            event,
+            wl_keyboard::Event::Keymap { .. } | wl_keyboard::Event::RepeatInfo { .. }
+        ) {
+            state.keyboard_events.push(event);
+        }
+    }
+}
+
+impl Dispatch<zxdg_exported_v1::ZxdgExportedV1, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &zxdg_exported_v1::ZxdgExportedV1,
+        event: zxdg_exported_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let zxdg_exported_v1::Event::Handle { handle } = event {
+            state.exported_handles.push(handle);
+        }
+    }
+}
+
+impl Dispatch<zxdg_exported_v2::ZxdgExportedV2, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &zxdg_exported_v2::ZxdgExportedV2,
+        event: zxdg_exported_v2::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let zxdg_exported_v2::Event::Handle { handle } = event {
+            state.exported_handles.push(handle);
+        }
+    }
+}
+
+impl Dispatch<zxdg_imported_v1::ZxdgImportedV1, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &zxdg_imported_v1::ZxdgImportedV1,
+        event: zxdg_imported_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let zxdg_imported_v1::Event::Destroyed = event {
+            state.imported_destroyed += 1;
+        }
+    }
+}
+
+impl Dispatch<zxdg_imported_v2::ZxdgImportedV2, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &zxdg_imported_v2::ZxdgImportedV2,
+        event: zxdg_imported_v2::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let zxdg_imported_v2::Event::Destroyed = event {
+            state.imported_destroyed += 1;
+        }
+    }
+}
+
+impl Dispatch<zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1,
+        event: zwp_linux_buffer_params_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_linux_buffer_params_v1::Event::Created { buffer } => state.dmabuf_created.push(buffer),
+            zwp_linux_buffer_params_v1::Event::Failed => state.dmabuf_failed += 1,
+            _ => {}
+        }
+    }
+
+    event_created_child!(TestClient, zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1, [
+        zwp_linux_buffer_params_v1::EVT_CREATED_OPCODE => (wl_buffer::WlBuffer, ()),
+    ]);
+}
+
+impl Dispatch<zwp_fullscreen_shell_mode_feedback_v1::ZwpFullscreenShellModeFeedbackV1, ()> for TestClient {
+    fn event(
+        state: &mut Self,
+        _proxy: &zwp_fullscreen_shell_mode_feedback_v1::ZwpFullscreenShellModeFeedbackV1,
+        event: zwp_fullscreen_shell_mode_feedback_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        state.mode_feedback_events.push(event);
+    }
+}
+
+delegate_noop!(TestClient: ignore wl_compositor::WlCompositor);
+delegate_noop!(TestClient: ignore wl_surface::WlSurface);
+delegate_noop!(TestClient: ignore wl_region::WlRegion);
+delegate_noop!(TestClient: ignore wl_callback::WlCallback);
+delegate_noop!(TestClient: ignore wl_shm::WlShm);
+delegate_noop!(TestClient: ignore wl_shm_pool::WlShmPool);
+delegate_noop!(TestClient: ignore wl_subcompositor::WlSubcompositor);
+delegate_noop!(TestClient: ignore wl_subsurface::WlSubsurface);
+delegate_noop!(TestClient: ignore xdg_toplevel::XdgToplevel);
+delegate_noop!(TestClient: ignore xdg_popup::XdgPopup);
+delegate_noop!(TestClient: ignore xdg_positioner::XdgPositioner);
+delegate_noop!(TestClient: ignore zwlr_layer_shell_v1::ZwlrLayerShellV1);
+delegate_noop!(TestClient: ignore wl_seat::WlSeat);
+delegate_noop!(TestClient: ignore wl_data_device_manager::WlDataDeviceManager);
+delegate_noop!(TestClient: ignore zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1);
+delegate_noop!(TestClient: ignore zxdg_exporter_v1::ZxdgExporterV1);
+delegate_noop!(TestClient: ignore zxdg_importer_v1::ZxdgImporterV1);
+delegate_noop!(TestClient: ignore zxdg_exporter_v2::ZxdgExporterV2);
+delegate_noop!(TestClient: ignore zxdg_importer_v2::ZxdgImporterV2);
+delegate_noop!(TestClient: ignore wl_data_offer::WlDataOffer);
+delegate_noop!(TestClient: ignore wl_output::WlOutput);
+delegate_noop!(TestClient: ignore zwp_fullscreen_shell_v1::ZwpFullscreenShellV1);
+
+/// A server with a single connected client
+pub(crate) struct TestFixture {
+    pub display: Display<TestState>,
+    pub state: TestState,
+    pub client: TestClient,
+    server_client: Client,
+    connection: Connection,
+    queue: EventQueue<TestClient>,
+    registry: wl_registry::WlRegistry,
+    compositor: wl_compositor::WlCompositor,
+    subcompositor: wl_subcompositor::WlSubcompositor,
+    shm: wl_shm::WlShm,
+    xdg_wm_base: xdg_wm_base::XdgWmBase,
+    layer_shell: zwlr_layer_shell_v1::ZwlrLayerShellV1,
+}
+
+impl std::fmt::Debug for TestFixture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TestFixture").finish_non_exhaustive()
+    }
+}
+
+impl TestFixture {
+    pub fn new() -> Self {
+        let mut display = Display::<TestState>::new().unwrap();
+        let dh = display.handle();
+        let mut seat_state = SeatState::new();
+        let seat = seat_state.new_wl_seat(&dh, "seat-0");
+        let output = Output::new(
+            "output-0".into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Test".into(),
+            },
+        );
+        output.create_global::<TestState>(&dh);
+        let mut state = TestState {
+            compositor: CompositorState::new::<TestState>(&dh),
+            shm: ShmState::new::<TestState>(&dh, Vec::new()),
+            xdg_shell: XdgShellState::new::<TestState>(&dh),
+            layer_shell: WlrLayerShellState::new::<TestState>(&dh),
+            data_device: DataDeviceState::new::<TestState>(&dh),
+            xdg_foreign: XdgForeignState::new::<TestState>(&dh),
+            dmabuf: DmabufState::new(),
+            fullscreen_shell: FullscreenShellState::new::<TestState>(&dh, []),
+            seat_state,
+            seat,
+            output,
+            buffer_policy: InconsistentBufferPolicy::default(),
+            toplevels: Vec::new(),
+            popups: Vec::new(),
+            layers: Vec::new(),
+            dnd_dropped: Vec::new(),
+            server_dnd_cancelled: 0,
+            led_states: Vec::new(),
+            dmabuf_imports: Vec::new(),
+            fullscreen_mode_switches: Vec::new(),
+            fullscreen_commits: Vec::new(),
+        };
+
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        client_stream.set_nonblocking(true).unwrap();
+        let server_client = dh
+            .clone()
+            .insert_client(server_stream, Arc::new(TestClientData::default()))
+            .unwrap();
+        let connection = Connection::from_socket(client_stream).unwrap();
+        let mut queue = connection.new_event_queue();
+        let registry = connection.display().get_registry(&queue.handle(), ());
+
+        let mut client = TestClient::default();
+        exchange(&connection, &mut display, &mut state, &mut queue, &mut client);
+        let bind = |interface: &str, version: u32| {
+            let (name, _, advertised) = client
+                .globals
+                .iter()
+                .find(|(_, name, _)| name == interface)
+                .unwrap_or_else(|| panic!("missing global {}", interface));
+            (*name, version.min(*advertised))
+        };
+        let (name, version) = bind("wl_compositor", 6);
+        let compositor = registry.bind(name, version, &queue.handle(), ());
+        let (name, version) = bind("wl_subcompositor", 1);
+        let subcompositor = registry.bind(name, version, &queue.handle(), ());
+        let (name, version) = bind("wl_shm", 1);
+        let shm = registry.bind(name, version, &queue.handle(), ());
+        let (name, version) = bind("xdg_wm_base", 6);
+        let xdg_wm_base = registry.bind(name, version, &queue.handle(), ());
+        let (name, version) = bind("zwlr_layer_shell_v1", 4);
+        let layer_shell = registry.bind(name, version, &queue.handle(), ());
+
+        let mut fixture = TestFixture {
+            display,
+            state,
+            client,
+            server_client,
+            connection,
+            queue,
+            registry,
+            compositor,
+            subcompositor,
+            shm,
+            xdg_wm_base,
+            layer_shell,
+        };
+        fixture.roundtrip();
+        fixture
+    }
+
+    /// Exchange all pending requests and events
+    pub fn roundtrip(&mut self) {
+        exchange(
+            &self.connection,
+            &mut self.display,
+            &mut self.state,
+            &mut self.queue,
+            &mut self.client,
+        );
+    }
+
+    /// Exchange all pending requests and events, returning the protocol error the client was
+    /// disconnected with, if any
+    pub fn protocol_error(&mut self) -> Option<ProtocolError> {
+        let _ = self.connection.flush();
+        let _ = self.display.dispatch_clients(&mut self.state);
+        let _ = self.display.flush_clients();
+        if let Some(guard) = self.queue.prepare_read() {
+            let _ = guard.read();
+        }
+        let _ = self.queue.dispatch_pending(&mut self.client);
+        self.connection.protocol_error()
+    }
+
+    /// The queue handle of the client
+    pub fn handle(&self) -> QueueHandle<TestClient> {
+        self.queue.handle()
+    }
+
+    /// Bind a global of the server
+    pub fn bind<I>(&mut self, version: u32) -> I
+    where
+        I: Proxy + 'static,
+        TestClient: Dispatch<I, ()>,
+    {
+        let (name, _, advertised) = self
+            .client
+            .globals
+            .iter()
+            .find(|(_, interface, _)| interface == I::interface().name)
+            .unwrap_or_else(|| panic!("missing global {}", I::interface().name));
+        self.registry
+            .bind(*name, version.min(*advertised), &self.queue.handle(), ())
+    }
+
+    /// Returns the server side object of a client object
+    pub fn server_object<I: Resource + 'static>(&self, proxy: &impl Proxy) -> I {
+        self.server_client
+            .object_from_protocol_id(&self.display.handle(), proxy.id().protocol_id())
+            .unwrap()
+    }
+
+    /// Create a new surface, returning the client and server side objects
+    pub fn create_surface(&mut self) -> (wl_surface::WlSurface, WlSurface) {
+        let surface = self.compositor.create_surface(&self.queue.handle(), ());
+        self.roundtrip();
+        let server_surface = self.server_object(&surface);
+        (surface, server_surface)
+    }
+
+    /// Create a new subsurface of `parent`
+    pub fn create_subsurface(
+        &mut self,
+        parent: &wl_surface::WlSurface,
+    ) -> (wl_surface::WlSurface, wl_subsurface::WlSubsurface, WlSurface) {
+        let surface = self.compositor.create_surface(&self.queue.handle(), ());
+        let subsurface = self
+            .subcompositor
+            .get_subsurface(&surface, parent, &self.queue.handle(), ());
+        self.roundtrip();
+        let server_surface = self.server_object(&surface);
+        (surface, subsurface, server_surface)
+    }
+
+    /// Create a region containing `rects`
+    pub fn create_region(&mut self, rects: &[(i32, i32, i32, i32)]) -> wl_region::WlRegion {
+        let region = self.compositor.create_region(&self.queue.handle(), ());
+        for (x, y, w, h) in rects {
+            region.add(*x, *y, *w, *h);
+        }
+        region
+    }
+
+    /// Create a new xdg toplevel, returning the client and server side objects
+    ///
+    /// The initial configure is already acknowledged, so the toplevel can be mapped by attaching a buffer.
+    pub fn create_toplevel(&mut self) -> (wl_surface::WlSurface, xdg_toplevel::XdgToplevel, ToplevelSurface) {
+        let qh = self.queue.handle();
+        let surface = self.compositor.create_surface(&qh, ());
+        let xdg_surface = self.xdg_wm_base.get_xdg_surface(&surface, &qh, ());
+        let toplevel = xdg_surface.get_toplevel(&qh, ());
+        surface.commit();
+        self.roundtrip();
+        let server_surface = self.server_object::<WlSurface>(&surface);
+        let server_toplevel = self
+            .state
+            .toplevels
+            .iter()
+            .find(|t| *t.wl_surface() == server_surface)
+            .unwrap()
+            .clone();
+        (surface, toplevel, server_toplevel)
+    }
+
+    /// Create a new layer surface, returning the client and server side objects
+    ///
+    /// The initial configure is already acknowledged, so the layer surface can be mapped by attaching a buffer.
+    pub fn create_layer_surface(
+        &mut self,
+        layer: zwlr_layer_shell_v1::Layer,
+        size: (u32, u32),
+    ) -> (
+        wl_surface::WlSurface,
+        zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        LayerSurface,
+    ) {
+        let qh = self.queue.handle();
+        let surface = self.compositor.create_surface(&qh, ());
+        let layer_surface = self
+            .layer_shell
+            .get_layer_surface(&surface, None, layer, "test".into(), &qh, ());
+        layer_surface.set_size(size.0, size.1);
+        surface.commit();
+        self.roundtrip();
+        let server_surface = self.server_object::<WlSurface>(&surface);
+        let server_layer = self
+            .state
+            .layers
+            .iter()
+            .find(|l| *l.wl_surface() == server_surface)
+            .unwrap()
+            .clone();
+        (surface, layer_surface, server_layer)
+    }
+
+    /// Attach a new buffer of the given size and commit
+    pub fn map(&mut self, surface: &wl_surface::WlSurface, width: i32, height: i32) -> wl_buffer::WlBuffer {
+        let buffer = self.create_buffer(width, height);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.damage_buffer(0, 0, width, height);
+        surface.commit();
+        self.roundtrip();
+        buffer
+    }
+
+    /// Create a new argb8888 shm buffer
+    pub fn create_buffer(&mut self, width: i32, height: i32) -> wl_buffer::WlBuffer {
+        let size = (width * height * 4) as usize;
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&vec![0u8; size]).unwrap();
+        let pool = self
+            .shm
+            .create_pool(file.as_fd(), size as i32, &self.queue.handle(), ());
+        let buffer = pool.create_buffer(
+            0,
+            width,
+            height,
+            width * 4,
+            wl_shm::Format::Argb8888,
+            &self.queue.handle(),
+            (),
+        );
+        pool.destroy();
+        buffer
+    }
+}
+
+fn exchange(
+    connection: &Connection,
+    display: &mut Display<TestState>,
+    state: &mut TestState,
+    queue: &mut EventQueue<TestClient>,
+    client: &mut TestClient,
+) {
+    // requests may trigger events which trigger requests again, a few passes settle everything
+    for _ in 0..4 {
+        connection.flush().unwrap();
+        display.dispatch_clients(state).unwrap();
+        display.flush_clients().unwrap();
+        if let Some(guard) = queue.prepare_read() {
+            let _ = guard.read();
+        }
+        queue.dispatch_pending(client).unwrap();
+    }
+}
@@ -62,6 +62,7 @@ use wayland_server::{
 };
 
 use crate::utils::{Client, Logical, Rectangle, Size};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 use super::compositor::{self, with_states, Cacheable, CompositorHandler, SurfaceData};
 
@@ -71,7 +72,7 @@ pub struct ViewporterState {
     global: GlobalId,
 }
 
-pub(crate) type ViewporterSurfaceState = Mutex<Option<ViewportMarker>>;
+crate::user_data_key!(pub(crate) ViewporterSurfaceState: Mutex<Option<ViewportMarker>> = Mutex::new(None));
 
 impl ViewporterState {
     /// Create new [`wp_viewporter`] global.
@@ -134,15 +135,17 @@ where
                 let already_has_viewport = with_states(&surface, |states| {
                     states
                         .data_map
-                        .get::<ViewporterSurfaceState>()
+                        .try_get_key::<ViewporterSurfaceState>()
                         .map(|v| v.lock().unwrap().is_some())
                         .unwrap_or(false)
                 });
 
                 if already_has_viewport {
-                    surface.post_error(
+                    post_error(
+                        &surface,
                         wp_viewporter::Error::ViewportExists as u32,
                         "the surface already has a viewport object associated".to_string(),
+                        ErrorContext::new().request("wp_viewporter.get_viewport"),
                     );
                     return;
                 }
@@ -154,30 +157,25 @@ where
                     },
                 );
                 let initial = with_states(&surface, |states| {
-                    let inserted = states
+                    // if the marker already exists it will be None as
+                    // checked in already_has_viewport
+                    let inserted = states.data_map.try_get_key::<ViewporterSurfaceState>().is_none();
+                    *states
                         .data_map
-                        .insert_if_missing_threadsafe::<ViewporterSurfaceState, _>(|| {
-                            Mutex::new(Some(ViewportMarker(viewport.downgrade())))
-                        });
-
-                    // if we did not insert the marker it will be None as
-                    // checked in already_has_viewport and we have to update
-                    // it now
-                    if !inserted {
-                        *states
-                            .data_map
-                            .get::<ViewporterSurfaceState>()
-                            .unwrap()
-                            .lock()
-                            .unwrap() = Some(ViewportMarker(viewport.downgrade()));
-                    }
+                        .get_key::<ViewporterSurfaceState>()
+                        .lock()
+                        .unwrap() = Some(ViewportMarker(viewport.downgrade()));
 
                     inserted
                 });
 
                 // only add the pre-commit hook once for the surface
                 if initial {
-                    compositor::add_pre_commit_hook::<D, _>(&surface, viewport_pre_commit_hook);
+                    compositor::add_pre_commit_hook_with_priority::<D, _>(
+                        &surface,
+                        compositor::HookPriority::Protocol,
+                        viewport_pre_commit_hook,
+                    );
                 }
             }
             wp_viewporter::Request::Destroy => {
@@ -210,8 +208,7 @@ where
                     with_states(&surface, |states| {
                         states
                             .data_map
-                            .get::<ViewporterSurfaceState>()
-                            .unwrap()
+                            .get_key::<ViewporterSurfaceState>()
                             .lock()
                             .unwrap()
                             .take();
@@ -228,9 +225,11 @@ where
                 let is_valid_src = x >= 0.0 && y >= 0.0 && width > 0.0 && height > 0.0;
 
                 if !is_unset && !is_valid_src {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_viewport::Error::BadValue as u32,
                         "negative or zero values in width or height or negative values in x or y".to_string(),
+                        ErrorContext::new().request("wp_viewport.set_source"),
                     );
                     return;
                 }
@@ -238,9 +237,11 @@ where
                 // If the wl_surface associated with the wp_viewport is destroyed,
                 // all wp_viewport requests except 'destroy' raise the protocol error no_surface.
                 let Ok(surface) = data.surface.upgrade() else {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_viewport::Error::NoSurface as u32,
                         "the wl_surface was destroyed".to_string(),
+                        ErrorContext::new().request("wp_viewport.set_source"),
                     );
                     return;
                 };
@@ -266,9 +267,11 @@ where
                 let is_valid_size = width > 0 && height > 0;
 
                 if !is_unset && !is_valid_size {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_viewport::Error::BadValue as u32,
                         "negative or zero values in width or height".to_string(),
+                        ErrorContext::new().request("wp_viewport.set_destination"),
                     );
                     return;
                 }
@@ -276,9 +279,11 @@ where
                 // If the wl_surface associated with the wp_viewport is destroyed,
                 // all wp_viewport requests except 'destroy' raise the protocol error no_surface.
                 let Ok(surface) = data.surface.upgrade() else {
-                    resource.post_error(
+                    post_error(
+                        resource,
                         wp_viewport::Error::NoSurface as u32,
                         "the wl_surface was destroyed".to_string(),
+                        ErrorContext::new().request("wp_viewport.set_destination"),
                     );
                     return;
                 };
@@ -316,13 +321,9 @@ fn viewport_pre_commit_hook<D: 'static>(
     surface: &wl_surface::WlSurface,
 ) {
     with_states(surface, |states| {
-        states
-            .data_map
-            .insert_if_missing_threadsafe::<ViewporterSurfaceState, _>(|| Mutex::new(None));
         let viewport = states
             .data_map
-            .get::<ViewporterSurfaceState>()
-            .unwrap()
+            .get_key::<ViewporterSurfaceState>()
             .lock()
             .unwrap();
         if let Some(viewport) = &*viewport {
@@ -336,9 +337,11 @@ fn viewport_pre_commit_hook<D: 'static>(
                 && src_size != src_size.map(|s| Size::from((s.w as i32, s.h as i32)).to_f64())
             {
                 if let Ok(viewport) = viewport.0.upgrade() {
-                    viewport.post_error(
+                    post_error(
+                        &viewport,
                         wp_viewport::Error::BadSize as u32,
                         "destination size is not integer".to_string(),
+                        ErrorContext::new().request("wl_surface.commit"),
                     );
                 }
             }
@@ -351,13 +354,9 @@ fn viewport_pre_commit_hook<D: 'static>(
 /// If the viewport violates any protocol checks a protocol error will be raised and `false`
 /// is returned.
 pub fn ensure_viewport_valid(states: &SurfaceData, buffer_size: Size<i32, Logical>) -> bool {
-    states
-        .data_map
-        .insert_if_missing_threadsafe::<ViewporterSurfaceState, _>(|| Mutex::new(None));
     let viewport = states
         .data_map
-        .get::<ViewporterSurfaceState>()
-        .unwrap()
+        .get_key::<ViewporterSurfaceState>()
         .lock()
         .unwrap();
 
@@ -370,13 +369,11 @@ pub fn ensure_viewport_valid(states: &SurfaceData, buffer_size: Size<i32, Logica
         let valid = buffer_rect.contains_rect(src);
         if !valid {
             if let Ok(viewport) = viewport.0.upgrade() {
-                viewport.post_error(
-                    wp_viewport::Error::OutOfBuffer as u32,
+                post_error(&viewport, wp_viewport::Error::OutOfBuffer as u32,
                     format!(
                         "source rectangle x={},y={},w={},h={} extends outside of the content area x={},y={},w={},h={}", 
                         src.loc.x, src.loc.y, src.size.w, src.size.h,
-                        buffer_rect.loc.x, buffer_rect.loc.y, buffer_rect.size.w, buffer_rect.size.h),
-                );
+                        buffer_rect.loc.x, buffer_rect.loc.y, buffer_rect.size.w, buffer_rect.size.h), ErrorContext::new().request("wl_surface.commit"));
             }
         }
         valid
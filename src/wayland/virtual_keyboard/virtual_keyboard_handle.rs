@@ -12,11 +12,12 @@ use wayland_protocols_misc::zwp_virtual_keyboard_v1::server::zwp_virtual_keyboar
 use wayland_server::{
     backend::ClientId,
     protocol::wl_keyboard::{KeyState, KeymapFormat},
-    Client, DataInit, Dispatch, DisplayHandle, Resource,
+    Client, DataInit, Dispatch, DisplayHandle,
 };
 use xkbcommon::xkb;
 
 use crate::input::keyboard::{KeyboardTarget, KeymapFile, ModifiersState};
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     input::{Seat, SeatHandler},
     utils::SERIAL_COUNTER,
@@ -96,7 +97,12 @@ where
                 let vk_state = match virtual_data.state.as_mut() {
                     Some(vk_state) => vk_state,
                     None => {
-                        virtual_keyboard.post_error(NoKeymap, "`key` sent before keymap.");
+                        post_error(
+                            virtual_keyboard,
+                            NoKeymap,
+                            "`key` sent before keymap.",
+                            ErrorContext::new().request("zwp_virtual_keyboard_v1.key"),
+                        );
                         return;
                     }
                 };
@@ -132,7 +138,12 @@ where
                 let state = match virtual_data.state.as_mut() {
                     Some(state) => state,
                     None => {
-                        virtual_keyboard.post_error(NoKeymap, "`modifiers` sent before keymap.");
+                        post_error(
+                            virtual_keyboard,
+                            NoKeymap,
+                            "`modifiers` sent before keymap.",
+                            ErrorContext::new().request("zwp_virtual_keyboard_v1.modifiers"),
+                        );
                         return;
                     }
                 };
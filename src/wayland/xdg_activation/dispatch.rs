@@ -4,13 +4,12 @@ use std::sync::{
 };
 
 use wayland_protocols::xdg::activation::v1::server::{xdg_activation_token_v1, xdg_activation_v1};
-use wayland_server::{
-    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
-};
+use wayland_server::{backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New};
 
 use super::{
     ActivationTokenData, TokenBuilder, XdgActivationHandler, XdgActivationState, XdgActivationTokenData,
 };
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 
 impl<D> Dispatch<xdg_activation_v1::XdgActivationV1, (), D> for XdgActivationState
 where
@@ -97,9 +96,11 @@ where
         match request {
             xdg_activation_token_v1::Request::SetSerial { serial, seat } => {
                 if data.constructed.load(Ordering::Relaxed) {
-                    token.post_error(
+                    post_error(
+                        token,
                         xdg_activation_token_v1::Error::AlreadyUsed,
                         "The activation token has already been constructed",
+                        ErrorContext::new().request("xdg_activation_token_v1.set_serial"),
                     );
                     return;
                 }
@@ -109,9 +110,11 @@ where
 
             xdg_activation_token_v1::Request::SetAppId { app_id } => {
                 if data.constructed.load(Ordering::Relaxed) {
-                    token.post_error(
+                    post_error(
+                        token,
                         xdg_activation_token_v1::Error::AlreadyUsed,
                         "The activation token has already been constructed",
+                        ErrorContext::new().request("xdg_activation_token_v1.set_app_id"),
                     );
                     return;
                 }
@@ -121,9 +124,11 @@ where
 
             xdg_activation_token_v1::Request::SetSurface { surface } => {
                 if data.constructed.load(Ordering::Relaxed) {
-                    token.post_error(
+                    post_error(
+                        token,
                         xdg_activation_token_v1::Error::AlreadyUsed,
                         "The activation token has already been constructed",
+                        ErrorContext::new().request("xdg_activation_token_v1.set_surface"),
                     );
                     return;
                 }
@@ -133,9 +138,11 @@ where
 
             xdg_activation_token_v1::Request::Commit => {
                 if data.constructed.load(Ordering::Relaxed) {
-                    token.post_error(
+                    post_error(
+                        token,
                         xdg_activation_token_v1::Error::AlreadyUsed,
                         "The activation token has already been constructed",
+                        ErrorContext::new().request("xdg_activation_token_v1.commit"),
                     );
                     return;
                 }
@@ -1,15 +1,25 @@
 use std::collections::HashSet;
 
-use wayland_protocols::xdg::foreign::zv2::server::{
-    zxdg_exported_v2::{self, ZxdgExportedV2},
-    zxdg_exporter_v2::{self, ZxdgExporterV2},
-    zxdg_imported_v2::{self, ZxdgImportedV2},
-    zxdg_importer_v2::{self, ZxdgImporterV2},
+use wayland_protocols::xdg::foreign::{
+    zv1::server::{
+        zxdg_exported_v1::{self, ZxdgExportedV1},
+        zxdg_exporter_v1::{self, ZxdgExporterV1},
+        zxdg_imported_v1::{self, ZxdgImportedV1},
+        zxdg_importer_v1::{self, ZxdgImporterV1},
+    },
+    zv2::server::{
+        zxdg_exported_v2::{self, ZxdgExportedV2},
+        zxdg_exporter_v2::{self, ZxdgExporterV2},
+        zxdg_imported_v2::{self, ZxdgImportedV2},
+        zxdg_importer_v2::{self, ZxdgImporterV2},
+    },
 };
 use wayland_server::{
-    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    backend::ClientId, protocol::wl_surface::WlSurface, Client, DataInit, Dispatch, DisplayHandle,
+    GlobalDispatch, New,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::wayland::{
     compositor,
     shell::{
@@ -19,7 +29,7 @@ use crate::wayland::{
 };
 
 use super::{
-    ExportedState, XdgExportedUserData, XdgForeignHandle, XdgForeignHandler, XdgForeignState,
+    ExportedState, XdgExportedUserData, XdgForeignHandle, XdgForeignHandler, XdgForeignState, XdgImported,
     XdgImportedUserData,
 };
 
@@ -67,15 +77,7 @@ where
                     },
                 );
                 exported.handle(handle.as_str().to_owned());
-
-                state.xdg_foreign_state().exported.insert(
-                    handle,
-                    ExportedState {
-                        exported_surface: surface,
-                        requested_child: None,
-                        imported_by: HashSet::new(),
-                    },
-                );
+                export(state, handle, surface);
             }
             zxdg_exporter_v2::Request::Destroy => {}
             _ => {}
@@ -99,13 +101,99 @@ where
     }
 
     fn destroyed(state: &mut D, _client: ClientId, _resource: &ZxdgExportedV2, data: &XdgExportedUserData) {
-        // Revoke the previously exported surface.
-        // This invalidates any relationship the importer may have set up using the xdg_imported created given the handle sent via xdg_exported.handle.
-        invalidate_all_relationships(state, &data.handle);
-        state.xdg_foreign_state().exported.remove(&data.handle);
+        unexport(state, &data.handle);
+    }
+}
+
+impl<D> GlobalDispatch<ZxdgExporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExporterV1, ()>,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgExporterV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZxdgExporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgExportedV1, XdgExportedUserData>,
+    D: XdgForeignHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZxdgExporterV1,
+        request: zxdg_exporter_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_exporter_v1::Request::Export { id, surface } => {
+                let handle = XdgForeignHandle::new();
+                let exported = data_init.init(
+                    id,
+                    XdgExportedUserData {
+                        handle: handle.clone(),
+                    },
+                );
+                exported.handle(handle.as_str().to_owned());
+                export(state, handle, surface);
+            }
+            zxdg_exporter_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgExportedV1, XdgExportedUserData, D> for XdgForeignState
+where
+    D: XdgForeignHandler + XdgShellHandler,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZxdgExportedV1,
+        _request: zxdg_exported_v1::Request,
+        _data: &XdgExportedUserData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, _resource: &ZxdgExportedV1, data: &XdgExportedUserData) {
+        unexport(state, &data.handle);
     }
 }
 
+fn export<D: XdgForeignHandler>(state: &mut D, handle: XdgForeignHandle, surface: WlSurface) {
+    state.xdg_foreign_state().exported.insert(
+        handle,
+        ExportedState {
+            exported_surface: surface,
+            requested_child: None,
+            imported_by: HashSet::new(),
+        },
+    );
+}
+
+fn unexport<D>(state: &mut D, handle: &XdgForeignHandle)
+where
+    D: XdgForeignHandler + XdgShellHandler,
+{
+    // Revoke the previously exported surface.
+    // This invalidates any relationship the importer may have set up using the xdg_imported created given the handle sent via xdg_exported.handle.
+    invalidate_all_relationships(state, handle);
+    state.xdg_foreign_state().exported.remove(handle);
+}
+
 //
 // Import
 //
@@ -141,27 +229,14 @@ where
     ) {
         match request {
             zxdg_importer_v2::Request::ImportToplevel { id, handle } => {
-                let exported = state
-                    .xdg_foreign_state()
-                    .exported
-                    .iter_mut()
-                    .find(|(key, _)| key.as_str() == handle.as_str());
-
+                let handle = XdgForeignHandle(handle);
                 let imported = data_init.init(
                     id,
                     XdgImportedUserData {
-                        handle: XdgForeignHandle(handle),
+                        handle: handle.clone(),
                     },
                 );
-
-                match exported {
-                    Some((_, state)) => {
-                        state.imported_by.insert(imported);
-                    }
-                    None => {
-                        imported.destroyed();
-                    }
-                }
+                import(state, &handle, XdgImported::V2(imported));
             }
             zxdg_importer_v2::Request::Destroy => {}
             _ => {}
@@ -184,49 +259,14 @@ where
     ) {
         match request {
             zxdg_imported_v2::Request::SetParentOf { surface: child } => {
-                if let Some((_, exported_state)) = state
-                    .xdg_foreign_state()
-                    .exported
-                    .iter_mut()
-                    .find(|(key, _)| key.as_str() == data.handle.as_str())
-                {
-                    let parent = &exported_state.exported_surface;
-
-                    let mut invalid = false;
-                    let mut changed = false;
-                    compositor::with_states(&child, |states| {
-                        if let Some(data) = states.data_map.get::<XdgToplevelSurfaceData>() {
-                            if is_valid_parent(&child, parent) {
-                                let mut role = data.lock().unwrap();
-                                changed = role.parent.as_ref() != Some(parent);
-                                role.parent = Some(parent.clone());
-                            } else {
-                                invalid = true;
-                            }
-                        }
-                    });
-
-                    if invalid {
-                        resource.post_error(
-                            zxdg_imported_v2::Error::InvalidSurface,
-                            "invalid parent relationship",
-                        );
-                        return;
-                    }
-
-                    exported_state.requested_child = Some((child.clone(), resource.clone()));
-
-                    if changed {
-                        if let Some(toplevel) = state
-                            .xdg_shell_state()
-                            .toplevel_surfaces()
-                            .iter()
-                            .find(|toplevel| *toplevel.wl_surface() == child)
-                            .cloned()
-                        {
-                            XdgShellHandler::parent_changed(state, toplevel);
-                        }
-                    }
+                let imported = XdgImported::V2(resource.clone());
+                if !set_parent_of(state, &data.handle, imported, child) {
+                    post_error(
+                        resource,
+                        zxdg_imported_v2::Error::InvalidSurface,
+                        "invalid parent relationship",
+                        ErrorContext::new().request("zxdg_imported_v2.set_parent_of"),
+                    );
                 }
             }
             zxdg_imported_v2::Request::Destroy => {}
@@ -235,17 +275,149 @@ where
     }
 
     fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgImportedV2, data: &XdgImportedUserData) {
-        if let Some((_, exported_state)) = state
-            .xdg_foreign_state()
-            .exported
-            .iter_mut()
-            .find(|(key, _)| key.as_str() == data.handle.as_str())
-        {
-            exported_state.imported_by.remove(resource);
+        unimport(state, &data.handle, &XdgImported::V2(resource.clone()));
+    }
+}
+
+impl<D> GlobalDispatch<ZxdgImporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImporterV1, ()>,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZxdgImporterV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D: XdgForeignHandler> Dispatch<ZxdgImporterV1, (), D> for XdgForeignState
+where
+    D: Dispatch<ZxdgImportedV1, XdgImportedUserData>,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZxdgImporterV1,
+        request: zxdg_importer_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_importer_v1::Request::Import { id, handle } => {
+                let handle = XdgForeignHandle(handle);
+                let imported = data_init.init(
+                    id,
+                    XdgImportedUserData {
+                        handle: handle.clone(),
+                    },
+                );
+                import(state, &handle, XdgImported::V1(imported));
+            }
+            zxdg_importer_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgImportedV1, XdgImportedUserData, D> for XdgForeignState
+where
+    D: XdgForeignHandler + XdgShellHandler,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZxdgImportedV1,
+        request: zxdg_imported_v1::Request,
+        data: &XdgImportedUserData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zxdg_imported_v1::Request::SetParentOf { surface: child } => {
+                // v1 has no error for invalid relationships, so these are ignored
+                set_parent_of(state, &data.handle, XdgImported::V1(resource.clone()), child);
+            }
+            zxdg_imported_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZxdgImportedV1, data: &XdgImportedUserData) {
+        unimport(state, &data.handle, &XdgImported::V1(resource.clone()));
+    }
+}
+
+fn import<D: XdgForeignHandler>(state: &mut D, handle: &XdgForeignHandle, imported: XdgImported) {
+    match state.xdg_foreign_state().exported.get_mut(handle) {
+        Some(exported_state) => {
+            exported_state.imported_by.insert(imported);
+        }
+        None => {
+            imported.destroyed();
+        }
+    }
+}
+
+fn unimport<D>(state: &mut D, handle: &XdgForeignHandle, imported: &XdgImported)
+where
+    D: XdgForeignHandler + XdgShellHandler,
+{
+    if let Some(exported_state) = state.xdg_foreign_state().exported.get_mut(handle) {
+        exported_state.imported_by.remove(imported);
+    }
+
+    invalidate_relationship_for(state, handle, Some(imported));
+}
+
+// Returns `false` if the relationship would be invalid
+fn set_parent_of<D>(state: &mut D, handle: &XdgForeignHandle, imported: XdgImported, child: WlSurface) -> bool
+where
+    D: XdgForeignHandler + XdgShellHandler,
+{
+    let Some(exported_state) = state.xdg_foreign_state().exported.get_mut(handle) else {
+        return true;
+    };
+    let parent = &exported_state.exported_surface;
+
+    let mut invalid = false;
+    let mut changed = false;
+    compositor::with_states(&child, |states| {
+        if let Some(data) = states.data_map.get::<XdgToplevelSurfaceData>() {
+            if is_valid_parent(&child, parent) {
+                let mut role = data.lock().unwrap();
+                changed = role.parent.as_ref() != Some(parent);
+                role.parent = Some(parent.clone());
+            } else {
+                invalid = true;
+            }
         }
+    });
+
+    if invalid {
+        return false;
+    }
+
+    exported_state.requested_child = Some((child.clone(), imported));
 
-        invalidate_relationship_for(state, &data.handle, Some(resource));
+    if changed {
+        if let Some(toplevel) = state
+            .xdg_shell_state()
+            .toplevel_surfaces()
+            .iter()
+            .find(|toplevel| *toplevel.wl_surface() == child)
+            .cloned()
+        {
+            XdgShellHandler::parent_changed(state, toplevel);
+        }
     }
+
+    true
 }
 
 fn invalidate_all_relationships<D>(state: &mut D, handle: &XdgForeignHandle)
@@ -258,16 +430,11 @@ where
 fn invalidate_relationship_for<D>(
     state: &mut D,
     handle: &XdgForeignHandle,
-    invalidate_for: Option<&ZxdgImportedV2>,
+    invalidate_for: Option<&XdgImported>,
 ) where
     D: XdgForeignHandler + XdgShellHandler,
 {
-    let Some((_, exported_state)) = state
-        .xdg_foreign_state()
-        .exported
-        .iter_mut()
-        .find(|(key, _)| key.as_str() == handle.as_str())
-    else {
+    let Some(exported_state) = state.xdg_foreign_state().exported.get_mut(handle) else {
         return;
     };
 
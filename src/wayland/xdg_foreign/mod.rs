@@ -1,5 +1,12 @@
 //! Implementation `xdg_foreign` protocol
 //!
+//! Both the unstable v1 and v2 versions of the protocol are supported. Clients can export their toplevels
+//! and hand the resulting handle to other clients, which can import it and make their own toplevels
+//! children of the exported one, e.g. for file dialogs shown by a portal for a sandboxed application.
+//! The parent relationship is reflected in [`ToplevelSurface::parent`](crate::wayland::shell::xdg::ToplevelSurface::parent)
+//! and reported through [`XdgShellHandler::parent_changed`](crate::wayland::shell::xdg::XdgShellHandler::parent_changed),
+//! like a parent set through xdg-shell itself.
+//!
 //! ```rs
 //! # extern crate wayland_server;
 //! #
@@ -35,8 +42,13 @@ use std::{
 };
 
 use rand::distributions::{Alphanumeric, DistString};
-use wayland_protocols::xdg::foreign::zv2::server::{
-    zxdg_exporter_v2::ZxdgExporterV2, zxdg_imported_v2::ZxdgImportedV2, zxdg_importer_v2::ZxdgImporterV2,
+use wayland_protocols::xdg::foreign::{
+    zv1::server::{
+        zxdg_exporter_v1::ZxdgExporterV1, zxdg_imported_v1::ZxdgImportedV1, zxdg_importer_v1::ZxdgImporterV1,
+    },
+    zv2::server::{
+        zxdg_exporter_v2::ZxdgExporterV2, zxdg_imported_v2::ZxdgImportedV2, zxdg_importer_v2::ZxdgImporterV2,
+    },
 };
 use wayland_server::{backend::GlobalId, protocol::wl_surface::WlSurface, DisplayHandle, GlobalDispatch};
 
@@ -83,11 +95,27 @@ pub struct XdgImportedUserData {
     handle: XdgForeignHandle,
 }
 
+/// xdg_imported object of either protocol version
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+enum XdgImported {
+    V1(ZxdgImportedV1),
+    V2(ZxdgImportedV2),
+}
+
+impl XdgImported {
+    fn destroyed(&self) {
+        match self {
+            XdgImported::V1(imported) => imported.destroyed(),
+            XdgImported::V2(imported) => imported.destroyed(),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct ExportedState {
     exported_surface: WlSurface,
-    requested_child: Option<(WlSurface, ZxdgImportedV2)>,
-    imported_by: HashSet<ZxdgImportedV2>,
+    requested_child: Option<(WlSurface, XdgImported)>,
+    imported_by: HashSet<XdgImported>,
 }
 
 /// Tracks the list of exported surfaces
@@ -96,6 +124,8 @@ pub struct XdgForeignState {
     exported: HashMap<XdgForeignHandle, ExportedState>,
     exporter: GlobalId,
     importer: GlobalId,
+    exporter_v1: GlobalId,
+    importer_v1: GlobalId,
 }
 
 impl XdgForeignState {
@@ -107,14 +137,20 @@ impl XdgForeignState {
         D: XdgForeignHandler,
         D: GlobalDispatch<ZxdgExporterV2, ()>,
         D: GlobalDispatch<ZxdgImporterV2, ()>,
+        D: GlobalDispatch<ZxdgExporterV1, ()>,
+        D: GlobalDispatch<ZxdgImporterV1, ()>,
     {
         let exporter = display.create_global::<D, ZxdgExporterV2, _>(1, ());
         let importer = display.create_global::<D, ZxdgImporterV2, _>(1, ());
+        let exporter_v1 = display.create_global::<D, ZxdgExporterV1, _>(1, ());
+        let importer_v1 = display.create_global::<D, ZxdgImporterV1, _>(1, ());
 
         Self {
             exported: HashMap::new(),
             exporter,
             importer,
+            exporter_v1,
+            importer_v1,
         }
     }
 
@@ -127,6 +163,24 @@ impl XdgForeignState {
     pub fn importer_global(&self) -> GlobalId {
         self.importer.clone()
     }
+
+    /// Returns the xdg_exporter global of the unstable v1 protocol.
+    pub fn exporter_v1_global(&self) -> GlobalId {
+        self.exporter_v1.clone()
+    }
+
+    /// Returns the xdg_importer global of the unstable v1 protocol.
+    pub fn importer_v1_global(&self) -> GlobalId {
+        self.importer_v1.clone()
+    }
+
+    /// Returns the surface exported with the given handle, if any
+    pub fn exported_surface(&self, handle: &str) -> Option<WlSurface> {
+        self.exported
+            .iter()
+            .find(|(key, _)| key.as_str() == handle)
+            .map(|(_, state)| state.exported_surface.clone())
+    }
 }
 
 /// Macro to delegate implementation of the xdg foreign to [`XdgForeignState`].
@@ -146,6 +200,16 @@ macro_rules! delegate_xdg_foreign {
         type __ZxdgImportedV2 =
             $crate::reexports::wayland_protocols::xdg::foreign::zv2::server::zxdg_imported_v2::ZxdgImportedV2;
 
+        type __ZxdgExporterV1 =
+            $crate::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_exporter_v1::ZxdgExporterV1;
+        type __ZxdgImporterV1 =
+            $crate::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_importer_v1::ZxdgImporterV1;
+
+        type __ZxdgExportedV1 =
+            $crate::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_exported_v1::ZxdgExportedV1;
+        type __ZxdgImportedV1 =
+            $crate::reexports::wayland_protocols::xdg::foreign::zv1::server::zxdg_imported_v1::ZxdgImportedV1;
+
         $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
             [
                 __ZxdgExporterV2: ()
@@ -178,5 +242,121 @@ macro_rules! delegate_xdg_foreign {
                 __ZxdgImportedV2: $crate::wayland::xdg_foreign::XdgImportedUserData
             ] => $crate::wayland::xdg_foreign::XdgForeignState
         );
+
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __ZxdgExporterV1: ()
+            ] => $crate::wayland::xdg_foreign::XdgForeignState
+        );
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __ZxdgImporterV1: ()
+            ] => $crate::wayland::xdg_foreign::XdgForeignState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __ZxdgExporterV1: ()
+            ] => $crate::wayland::xdg_foreign::XdgForeignState
+        );
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __ZxdgImporterV1: ()
+            ] => $crate::wayland::xdg_foreign::XdgForeignState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __ZxdgExportedV1: $crate::wayland::xdg_foreign::XdgExportedUserData
+            ] => $crate::wayland::xdg_foreign::XdgForeignState
+        );
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __ZxdgImportedV1: $crate::wayland::xdg_foreign::XdgImportedUserData
+            ] => $crate::wayland::xdg_foreign::XdgForeignState
+        );
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use wayland_protocols::xdg::foreign::{
+        zv1::client::{zxdg_exporter_v1::ZxdgExporterV1, zxdg_importer_v1::ZxdgImporterV1},
+        zv2::client::{zxdg_exporter_v2::ZxdgExporterV2, zxdg_importer_v2::ZxdgImporterV2},
     };
+
+    use crate::wayland::test_utils::TestFixture;
+
+    #[test]
+    fn v1_export_and_import() {
+        let mut fixture = TestFixture::new();
+        let (parent, _, server_parent) = fixture.create_toplevel();
+        let (child, _, server_child) = fixture.create_toplevel();
+        let exporter = fixture.bind::<ZxdgExporterV1>(1);
+        let importer = fixture.bind::<ZxdgImporterV1>(1);
+
+        let exported = exporter.export(&parent, &fixture.handle(), ());
+        fixture.roundtrip();
+        let handle = fixture.client.exported_handles[0].clone();
+        assert_eq!(
+            fixture.state.xdg_foreign.exported_surface(&handle).as_ref(),
+            Some(server_parent.wl_surface())
+        );
+
+        let imported = importer.import(handle.clone(), &fixture.handle(), ());
+        imported.set_parent_of(&child);
+        fixture.roundtrip();
+        assert_eq!(server_child.parent().as_ref(), Some(server_parent.wl_surface()));
+
+        // revoking the export removes the relationship
+        exported.destroy();
+        fixture.roundtrip();
+        assert_eq!(server_child.parent(), None);
+        assert_eq!(fixture.state.xdg_foreign.exported_surface(&handle), None);
+
+        // the handle is invalid now
+        importer.import(handle, &fixture.handle(), ());
+        fixture.roundtrip();
+        assert_eq!(fixture.client.imported_destroyed, 1);
+    }
+
+    #[test]
+    fn v1_export_v2_import() {
+        let mut fixture = TestFixture::new();
+        let (parent, _, server_parent) = fixture.create_toplevel();
+        let (child, _, server_child) = fixture.create_toplevel();
+        let exporter = fixture.bind::<ZxdgExporterV1>(1);
+        let importer = fixture.bind::<ZxdgImporterV2>(1);
+        let _exported = exporter.export(&parent, &fixture.handle(), ());
+        fixture.roundtrip();
+        let handle = fixture.client.exported_handles[0].clone();
+
+        let imported = importer.import_toplevel(handle, &fixture.handle(), ());
+        imported.set_parent_of(&child);
+        fixture.roundtrip();
+        assert_eq!(server_child.parent().as_ref(), Some(server_parent.wl_surface()));
+
+        // destroying the imported object removes the relationship
+        imported.destroy();
+        fixture.roundtrip();
+        assert_eq!(server_child.parent(), None);
+        assert_eq!(fixture.client.imported_destroyed, 0);
+    }
+
+    #[test]
+    fn v2_export_v1_import() {
+        let mut fixture = TestFixture::new();
+        let (parent, _, server_parent) = fixture.create_toplevel();
+        let (child, _, server_child) = fixture.create_toplevel();
+        let exporter = fixture.bind::<ZxdgExporterV2>(1);
+        let importer = fixture.bind::<ZxdgImporterV1>(1);
+        let _exported = exporter.export_toplevel(&parent, &fixture.handle(), ());
+        fixture.roundtrip();
+        let handle = fixture.client.exported_handles[0].clone();
+
+        let imported = importer.import(handle, &fixture.handle(), ());
+        imported.set_parent_of(&child);
+        fixture.roundtrip();
+        assert_eq!(server_child.parent().as_ref(), Some(server_parent.wl_surface()));
+    }
 }
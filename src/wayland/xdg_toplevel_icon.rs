@@ -24,6 +24,7 @@ use wayland_server::{
     Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     utils::HookId,
     wayland::{
@@ -359,9 +360,11 @@ impl<D: XdgToplevelIconHandler> Dispatch<XdgToplevelIconV1, XdgToplevelIconUserD
                 data.register_buffer_destruction_hook(buffer.clone(), shm, {
                     let icon = icon.clone();
                     move || {
-                        icon.post_error(
+                        post_error(
+                            &icon,
                             xdg_toplevel_icon_v1::Error::NoBuffer,
                             "The provided buffer has been destroyed before the toplevel icon",
+                            ErrorContext::new().request("xdg_toplevel_icon_v1.add_buffer"),
                         )
                     }
                 });
@@ -72,9 +72,13 @@ use wayland_server::{
     Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
 };
 
+use crate::wayland::protocol_error::{post_error, ErrorContext};
 use crate::{
     wayland::compositor,
-    xwayland::{xwm::XwmId, X11Surface, XWaylandClientData, XwmHandler},
+    xwayland::{
+        xwm::{associate_surface, XwmId},
+        X11Surface, XWaylandClientData, XwmHandler,
+    },
 };
 
 /// The role for an xwayland-associated surface.
@@ -86,6 +90,7 @@ const VERSION: u32 = 1;
 #[derive(Debug, Clone)]
 pub struct XWaylandShellState {
     global: GlobalId,
+    dh: DisplayHandle,
     by_serial: HashMap<u64, WlSurface>,
 }
 
@@ -102,6 +107,7 @@ impl XWaylandShellState {
         let global = display.create_global::<D, XwaylandShellV1, _>(VERSION, ());
         Self {
             global,
+            dh: display.clone(),
             by_serial: HashMap::new(),
         }
     }
@@ -112,9 +118,22 @@ impl XWaylandShellState {
     }
 
     /// Retrieves the surface for a given serial.
+    ///
+    /// Only surfaces, which have committed a serial, but are not yet associated with an X11 window
+    /// are tracked.
     pub fn surface_for_serial(&self, serial: u64) -> Option<WlSurface> {
         self.by_serial.get(&serial).cloned()
     }
+
+    pub(crate) fn take_surface_for_serial(&mut self, serial: u64) -> Option<WlSurface> {
+        self.by_serial
+            .remove(&serial)
+            .filter(|surface| surface.is_alive())
+    }
+
+    pub(crate) fn display_handle(&self) -> &DisplayHandle {
+        &self.dh
+    }
 }
 
 /// Userdata for an xwayland shell surface.
@@ -128,8 +147,12 @@ pub trait XWaylandShellHandler {
     /// Retrieves the global state.
     fn xwayland_shell_state(&mut self) -> &mut XWaylandShellState;
 
-    /// An X11 window has been associated with a wayland surface. This doesn't
-    /// take effect until the wl_surface is committed.
+    /// An X11 window has been associated with a wayland surface.
+    ///
+    /// This is called once per mapping of the X11 window, after
+    /// [`X11Surface::wl_surface`] started returning `wl_surface`. The surface
+    /// might already have committed a buffer at this point, so compositors
+    /// should treat it like a commit of the surface.
     fn surface_associated(&mut self, xwm: XwmId, wl_surface: wl_surface::WlSurface, surface: X11Surface) {
         let _ = (xwm, wl_surface, surface);
     }
@@ -194,11 +217,20 @@ where
         match request {
             xwayland_shell_v1::Request::GetXwaylandSurface { id, surface } => {
                 if compositor::give_role(&surface, XWAYLAND_SHELL_ROLE).is_err() {
-                    resource.post_error(xwayland_shell_v1::Error::Role, "Surface already has a role.");
+                    post_error(
+                        resource,
+                        xwayland_shell_v1::Error::Role,
+                        "Surface already has a role.",
+                        ErrorContext::new().request("xwayland_shell_v1.get_xwayland_surface"),
+                    );
                     return;
                 }
 
-                compositor::add_pre_commit_hook::<D, _>(&surface, serial_commit_hook);
+                compositor::add_post_commit_hook_with_priority::<D, _>(
+                    &surface,
+                    compositor::HookPriority::Protocol,
+                    serial_commit_hook,
+                );
 
                 data_init.init(id, XWaylandSurfaceUserData { wl_surface: surface });
                 // We call the handler callback once the serial is set.
@@ -255,55 +287,61 @@ fn serial_commit_hook<D: XWaylandShellHandler + XwmHandler + 'static>(
     _dh: &DisplayHandle,
     surface: &WlSurface,
 ) {
-    if let Some(serial) = compositor::with_states(surface, |states| {
+    // Running after the state is applied makes the association visible to the commit handler
+    // of the compositor.
+    let Some(serial) = compositor::with_states(surface, |states| {
         states
             .cached_state
             .get::<XWaylandShellCachedState>()
-            .pending()
+            .current()
             .serial
-    }) {
-        if let Some(client) = surface.client() {
-            // We only care about surfaces created by XWayland.
-            if let Some(xwm_id) = client
-                .get_data::<XWaylandClientData>()
-                .and_then(|data| data.user_data().get::<XwmId>())
-            {
-                let xwm = XwmHandler::xwm_state(state, *xwm_id);
-
-                // This handles the case that the serial was set on the X11
-                // window before surface. To handle the other case, we look for
-                // a matching surface when the WL_SURFACE_SERIAL atom is sent.
-                if let Some(window) = xwm.unpaired_surfaces.remove(&serial) {
-                    if let Some(xsurface) = xwm
-                        .windows
-                        .iter()
-                        .find(|x| x.window_id() == window || x.mapped_window_id() == Some(window))
-                        .cloned()
-                    {
-                        debug!(
-                            window = xsurface.window_id(),
-                            wl_surface = ?surface.id().protocol_id(),
-                            "associated X11 window to wl_surface in commit hook",
-                        );
+    }) else {
+        return;
+    };
 
-                        xsurface.state.lock().unwrap().wl_surface = Some(surface.clone());
+    // We only care about surfaces created by XWayland.
+    let Some(xwm_id) = surface.client().and_then(|client| {
+        client
+            .get_data::<XWaylandClientData>()
+            .and_then(|data| data.user_data().get::<XwmId>().copied())
+    }) else {
+        return;
+    };
+    let xwm = XwmHandler::xwm_state(state, xwm_id);
+    if xwm.paired_surfaces.contains_key(&serial) {
+        // already associated by an earlier commit
+        return;
+    }
 
-                        XWaylandShellHandler::surface_associated(state, *xwm_id, surface.clone(), xsurface);
-                    } else {
-                        warn!(
-                            window,
-                            wl_surface = ?surface.id().protocol_id(),
-                            "Unknown X11 window associated to wl_surface in commit hook"
-                        )
-                    }
-                } else {
-                    // this is necessary for the atom-handler to look up the matching surface
-                    XWaylandShellHandler::xwayland_shell_state(state)
-                        .by_serial
-                        .insert(serial, surface.clone());
-                }
-            }
+    // This handles the case that the serial was set on the X11
+    // window before surface. To handle the other case, we look for
+    // a matching surface when the WL_SURFACE_SERIAL atom is sent.
+    if let Some(window) = xwm.unpaired_surfaces.remove(&serial) {
+        if let Some(xsurface) = xwm
+            .windows
+            .iter()
+            .find(|x| x.window_id() == window || x.mapped_window_id() == Some(window))
+            .cloned()
+        {
+            debug!(
+                window = xsurface.window_id(),
+                wl_surface = ?surface.id().protocol_id(),
+                "associated X11 window to wl_surface in commit hook",
+            );
+            xwm.paired_surfaces.insert(serial, window);
+            associate_surface(state, xwm_id, xsurface, surface.clone());
+        } else {
+            warn!(
+                window,
+                wl_surface = ?surface.id().protocol_id(),
+                "Unknown X11 window associated to wl_surface in commit hook"
+            )
         }
+    } else {
+        // this is necessary for the atom-handler to look up the matching surface
+        let shell_state = XWaylandShellHandler::xwayland_shell_state(state);
+        shell_state.by_serial.retain(|_, surface| surface.is_alive());
+        shell_state.by_serial.insert(serial, surface.clone());
     }
 }
 
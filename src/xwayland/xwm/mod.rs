@@ -22,7 +22,18 @@
 //!    X11 window, which can be queried with
 //!    [`X11Surface::wl_surface_serial()`].
 //!
-//! Note that these two steps can happen in any order.
+//! Note that these two steps can happen in any order. The association is
+//! only made once the surface committed its serial, and
+//! [`XWaylandShellHandler::surface_associated`] is called afterwards.
+//!
+//! Older versions of XWayland, which don't support the xwayland shell
+//! protocol, set a `WL_SURFACE_ID` atom instead. The surface is then looked up
+//! by its protocol id, once the pending wayland requests of XWayland were
+//! dispatched.
+//!
+//! Either way the associated surface is available via
+//! [`X11Surface::wl_surface()`] and doesn't change until the window is
+//! unmapped.
 //!
 //! # Example
 //!
@@ -90,6 +101,26 @@
 //! }); if let Err(e) = ret { tracing::error!( "Failed to insert the
 //! XWaylandSource into the event loop: {}", e ); }
 //! ```
+//!
+//! # Override-redirect windows
+//!
+//! Override-redirect windows, like menus and tooltips, are not managed by the window manager.
+//! The X11 client grabs the keyboard and pointer inside the X server to handle input for them,
+//! which only works as long as the wayland keyboard focus stays on a surface of Xwayland.
+//! Giving the keyboard focus to the override-redirect window itself or to no window at all
+//! dismisses the menu in many clients, so the keyboard focus should stay on the window the menu
+//! belongs to, similar to the toplevel of a wayland popup grab.
+//!
+//! [`X11Wm::keyboard_focus_for`] resolves the window that should receive the keyboard focus,
+//! when an X11 window is clicked. [`X11Wm::override_redirect_parent`] returns the managed window
+//! an override-redirect window belongs to and [`X11Wm::override_redirect_children`] the mapped
+//! override-redirect windows belonging to a managed window, e.g. to raise them together.
+//!
+//! # Transient windows
+//!
+//! X11 clients may split the contents of a window into child windows, that declare the window as
+//! their parent using `WM_TRANSIENT_FOR`, e.g. for video areas. [`X11Wm::surface_tree`] groups a window
+//! with its transient children, so they can be rendered along with it.
 
 use crate::{
     utils::{x11rb::X11Source, Client, Coordinate, Logical, Point, Rectangle, Size},
@@ -114,7 +145,7 @@ use std::{
     },
 };
 use tracing::{debug, debug_span, error, info, trace, warn};
-use wayland_server::Resource;
+use wayland_server::{protocol::wl_surface::WlSurface, Resource};
 
 pub use x11rb::protocol::xproto::Window as X11Window;
 use x11rb::{
@@ -140,12 +171,16 @@ use x11rb::{
 
 pub mod settings;
 use settings::{NameError, Value, XSettings};
+pub mod startup;
+use startup::{StartupMessages, StartupNotification};
 mod surface;
 pub use self::surface::*;
 
 // copied from wlroots - docs say "maximum size can vary widely depending on the implementation"
 // and there is no way to query the maximum size, you just get a non-descriptive `Length` error...
 const INCR_CHUNK_SIZE: usize = 64 * 1024;
+// stop reading from wayland clients, while this much data is waiting for slow X11 clients
+const INCR_MAX_BUFFERED: usize = 4 * INCR_CHUNK_SIZE;
 
 #[allow(missing_docs)]
 mod atoms {
@@ -187,6 +222,9 @@ mod atoms {
             _NET_WM_STATE_MODAL,
             _MOTIF_WM_HINTS,
             _NET_STARTUP_ID,
+            _NET_STARTUP_INFO_BEGIN,
+            _NET_STARTUP_INFO,
+            _XWAYLAND_RANDR_EMU_MONITOR_RECTS,
 
             // server -> client
             WM_S0,
@@ -346,6 +384,30 @@ pub trait XwmHandler {
     fn unfullscreen_request(&mut self, xwm: XwmId, window: X11Surface) {
         let _ = (xwm, window);
     }
+    /// Xwayland started or stopped emulating a video mode for a window
+    ///
+    /// Legacy X11 clients, like fullscreen games, change the resolution using RandR or XF86VidMode
+    /// and resize their window to the new mode, often without requesting to be fullscreen.
+    /// Xwayland emulates these mode changes and scales windows matching an emulated mode up
+    /// to the real mode of the output, once they are fullscreen.
+    ///
+    /// `emulated` is set, once the window matches the size of one of its
+    /// [emulated monitors](X11Surface::emulated_monitors), and unset once it stops doing so.
+    ///
+    /// The default implementation forwards to [`XwmHandler::fullscreen_request`] and
+    /// [`XwmHandler::unfullscreen_request`] to let Xwayland scale the window. Only windows made
+    /// fullscreen this way are unfullscreened again, windows the client made fullscreen itself stay
+    /// fullscreen. Compositors can instead change the mode of the output to the emulated one.
+    fn emulated_mode_request(&mut self, xwm: XwmId, window: X11Surface, emulated: bool) {
+        if emulated {
+            if !window.is_fullscreen() {
+                window.set_fullscreen_from_emulation(true);
+                self.fullscreen_request(xwm, window);
+            }
+        } else if window.set_fullscreen_from_emulation(false) {
+            self.unfullscreen_request(xwm, window);
+        }
+    }
     /// Window requests to be minimized.
     fn minimize_request(&mut self, xwm: XwmId, window: X11Surface) {
         let _ = (xwm, window);
@@ -382,11 +444,30 @@ pub trait XwmHandler {
         let _ = (xwm, selection, mime_types);
     }
 
+    /// A selection transfer between X11 and wayland clients made progress
+    ///
+    /// Large selections are transferred incrementally, in which case this is called for every chunk.
+    fn selection_transfer_progress(
+        &mut self,
+        xwm: XwmId,
+        selection: SelectionTarget,
+        progress: SelectionTransferProgress,
+    ) {
+        let _ = (xwm, selection, progress);
+    }
+
     /// A proviously set selection of an X client got cleared
     fn cleared_selection(&mut self, xwm: XwmId, selection: SelectionTarget) {
         let _ = (xwm, selection);
     }
 
+    /// An X11 client sent a startup notification message
+    ///
+    /// See the [`startup`] module for details.
+    fn startup_notification(&mut self, xwm: XwmId, notification: StartupNotification) {
+        let _ = (xwm, notification);
+    }
+
     /// WM has lost connection to X server
     fn disconnected(&mut self, _xwm: XwmId) {}
 }
@@ -401,16 +482,23 @@ pub struct X11Wm {
     wm_window: X11Window,
     atoms: Atoms,
     xsettings: XSettings,
+    startup_messages: StartupMessages,
 
+    client: wayland_server::Client,
     pub(crate) unpaired_surfaces: HashMap<u64, X11Window>,
+    // serials of windows already associated to their wl_surface
+    pub(crate) paired_surfaces: HashMap<u64, X11Window>,
     sequences_to_ignore: BinaryHeap<Reverse<u16>>,
 
     // selections
     _xfixes_data: QueryExtensionReply,
     clipboard: XWmSelection,
     primary: XWmSelection,
+    selection_size_limit: Option<usize>,
 
     pub(crate) windows: Vec<X11Surface>,
+    // last managed window, that got the X11 input focus
+    focused_window: Option<X11Window>,
     // oldest mapped -> newest
     client_list: Vec<X11Window>,
     // bottom -> top
@@ -450,6 +538,8 @@ struct IncomingTransfer {
     incr: bool,
     source_data: Vec<u8>,
     incr_done: bool,
+    transferred: usize,
+    expected: Option<usize>,
 }
 
 impl fmt::Debug for IncomingTransfer {
@@ -461,15 +551,27 @@ impl fmt::Debug for IncomingTransfer {
             .field("incr", &self.incr)
             .field("source_data", &self.source_data)
             .field("incr_done", &self.incr_done)
+            .field("transferred", &self.transferred)
+            .field("expected", &self.expected)
             .finish()
     }
 }
 
 impl IncomingTransfer {
     fn read_selection_prop(&mut self, reply: GetPropertyReply) {
+        self.transferred += reply.value.len();
         self.source_data.extend(&reply.value)
     }
 
+    fn progress(&self, state: SelectionTransferState) -> SelectionTransferProgress {
+        SelectionTransferProgress {
+            direction: SelectionTransferDirection::FromX11,
+            transferred: self.transferred,
+            expected: self.expected,
+            state,
+        }
+    }
+
     fn write_selection(&mut self, fd: BorrowedFd<'_>) -> std::io::Result<bool> {
         if self.source_data.is_empty() {
             return Ok(true);
@@ -512,6 +614,7 @@ struct OutgoingTransfer {
     flush_property_on_delete: bool,
     /// The final 0-byte data chunk has been sent, denoting the completion of this transfer
     sent_finished: bool,
+    transferred: usize,
 }
 
 impl fmt::Debug for OutgoingTransfer {
@@ -524,6 +627,8 @@ impl fmt::Debug for OutgoingTransfer {
             .field("request", &self.request)
             .field("property_set", &self.property_set)
             .field("flush_property_on_delete", &self.flush_property_on_delete)
+            .field("sent_finished", &self.sent_finished)
+            .field("transferred", &self.transferred)
             .finish()
     }
 }
@@ -554,6 +659,15 @@ impl OutgoingTransfer {
         Ok(remaining)
     }
 
+    fn progress(&self, state: SelectionTransferState) -> SelectionTransferProgress {
+        SelectionTransferProgress {
+            direction: SelectionTransferDirection::ToX11,
+            transferred: self.transferred,
+            expected: None,
+            state,
+        }
+    }
+
     fn destroy<D>(mut self, handle: &LoopHandle<'_, D>) {
         if let Some(token) = self.token.take() {
             handle.remove(token);
@@ -655,6 +769,41 @@ pub enum ResizeEdge {
     BottomRight,
 }
 
+/// Direction of a selection transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SelectionTransferDirection {
+    /// Data of an X11 selection is sent to a wayland client
+    FromX11,
+    /// Data of a wayland selection is sent to an X11 client
+    ToX11,
+}
+
+/// State of a selection transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SelectionTransferState {
+    /// More data is expected
+    Transferring,
+    /// All data was received from the source of the selection
+    Finished,
+    /// The transfer failed or exceeded the [selection size limit](X11Wm::set_selection_size_limit)
+    Aborted,
+}
+
+/// Progress of a selection transfer, see [`XwmHandler::selection_transfer_progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SelectionTransferProgress {
+    /// Direction of the transfer
+    pub direction: SelectionTransferDirection,
+    /// Bytes received from the source of the selection so far
+    pub transferred: usize,
+    /// Size announced by the source of the selection, if any
+    ///
+    /// X11 clients only announce a lower bound for incremental transfers.
+    pub expected: Option<usize>,
+    /// State of the transfer
+    pub state: SelectionTransferState,
+}
+
 /// Errors generated working with Xwm Selections
 #[derive(thiserror::Error, Debug)]
 pub enum SelectionError {
@@ -868,13 +1017,18 @@ impl X11Wm {
             screen,
             atoms,
             xsettings,
+            startup_messages: StartupMessages::default(),
             wm_window: win,
             _xfixes_data,
             clipboard,
             primary,
+            selection_size_limit: None,
+            client,
             unpaired_surfaces: Default::default(),
+            paired_surfaces: Default::default(),
             sequences_to_ignore: Default::default(),
             windows: Vec::new(),
+            focused_window: None,
             client_list: Vec::new(),
             client_list_stacking: Vec::new(),
             span,
@@ -899,6 +1053,117 @@ impl X11Wm {
         self.id
     }
 
+    /// Returns the managed window an override-redirect window belongs to
+    ///
+    /// This is the window set by `WM_TRANSIENT_FOR` (following nested menus), if any.
+    /// Otherwise, as most override-redirect windows don't set it, the managed window of the same
+    /// X11 client, that was focused last, or the topmost one is returned.
+    ///
+    /// Returns `None` for managed windows or if no matching window exists.
+    pub fn override_redirect_parent(&self, window: &X11Surface) -> Option<X11Surface> {
+        if !window.is_override_redirect() {
+            return None;
+        }
+
+        let mut current = window.clone();
+        // Limit the depth to guard against cycles of transient windows
+        for _ in 0..self.windows.len() {
+            let Some(parent) = current
+                .is_transient_for()
+                .and_then(|id| self.windows.iter().find(|w| w.window_id() == id))
+            else {
+                break;
+            };
+            if !parent.is_override_redirect() {
+                return Some(parent.clone());
+            }
+            current = parent.clone();
+        }
+
+        let focused = self
+            .focused_window
+            .and_then(|id| self.windows.iter().find(|w| w.window_id() == id))
+            .filter(|w| self.same_client(w, window));
+        focused
+            .or_else(|| {
+                self.client_list_stacking
+                    .iter()
+                    .rev()
+                    .filter_map(|id| self.windows.iter().find(|w| w.window_id() == *id))
+                    .find(|w| !w.is_override_redirect() && self.same_client(w, window))
+            })
+            .cloned()
+    }
+
+    /// Returns the mapped override-redirect windows belonging to a managed window
+    ///
+    /// See [`X11Wm::override_redirect_parent`].
+    pub fn override_redirect_children<'a>(
+        &'a self,
+        window: &'a X11Surface,
+    ) -> impl Iterator<Item = &'a X11Surface> + 'a {
+        self.windows.iter().filter(move |w| {
+            w.is_override_redirect()
+                && w.is_mapped()
+                && self.override_redirect_parent(w).as_ref() == Some(window)
+        })
+    }
+
+    /// Returns `window` together with its mapped transient child windows
+    ///
+    /// Child windows are the windows declaring `window` as their parent using `WM_TRANSIENT_FOR`,
+    /// including their own transient children. They are ordered as they were created, with every
+    /// window following its parent.
+    pub fn surface_tree(&self, window: &X11Surface) -> X11SurfaceTree {
+        let mut children = Vec::new();
+        self.collect_transient_children(window, window, &mut children);
+        X11SurfaceTree {
+            root: window.clone(),
+            children,
+        }
+    }
+
+    fn collect_transient_children(
+        &self,
+        root: &X11Surface,
+        parent: &X11Surface,
+        children: &mut Vec<X11Surface>,
+    ) {
+        let parent_id = parent.window_id();
+        for child in self
+            .windows
+            .iter()
+            .filter(|w| w.is_mapped() && w.is_transient_for() == Some(parent_id))
+        {
+            // guard against cycles of transient windows
+            if child == root || children.contains(child) {
+                continue;
+            }
+            children.push(child.clone());
+            self.collect_transient_children(root, child, children);
+        }
+    }
+
+    /// Returns the window that should get the keyboard focus, when `window` is clicked
+    ///
+    /// Override-redirect windows, that do not [want the keyboard focus](X11Surface::wants_keyboard_focus),
+    /// like menus, resolve to their [parent](X11Wm::override_redirect_parent). `None` is returned, if the
+    /// keyboard focus should not be changed.
+    pub fn keyboard_focus_for(&self, window: &X11Surface) -> Option<X11Surface> {
+        if window.wants_keyboard_focus() {
+            Some(window.clone())
+        } else if window.is_override_redirect() {
+            self.override_redirect_parent(window)
+        } else {
+            None
+        }
+    }
+
+    fn same_client(&self, a: &X11Surface, b: &X11Surface) -> bool {
+        let mask = !self.conn.setup().resource_id_mask;
+        a.window_id() & mask == b.window_id() & mask
+    }
+
     /// Raises a window in the internal X11 state
     ///
     /// Needs to be called to match raising of windows inside the compositor to keep the stacking order
@@ -1109,6 +1374,18 @@ impl X11Wm {
         Ok(())
     }
 
+    /// Limit the size of selections transferred between X11 and wayland clients
+    ///
+    /// Transfers exceeding the limit are aborted, `None` (the default) allows selections of any size.
+    pub fn set_selection_size_limit(&mut self, limit: Option<usize>) {
+        self.selection_size_limit = limit;
+    }
+
+    /// Returns the size limit of selections transferred between X11 and wayland clients
+    pub fn selection_size_limit(&self) -> Option<usize> {
+        self.selection_size_limit
+    }
+
     /// Notify Xwayland of a new selection.
     ///
     /// `mime_types` being `None` indicate there is no active selection anymore.
@@ -1167,7 +1444,7 @@ impl X11Wm {
                     .get_property(true, selection.window, self.atoms.TARGETS, AtomEnum::ANY, 0, 4096)?
                     .reply_unchecked()?
                     .ok_or(SelectionError::UnableToDetermineAtom)?;
-                if prop.type_ != AtomEnum::ATOM.into() {
+                if prop.type_ != u32::from(AtomEnum::ATOM) {
                     return Err(SelectionError::UnableToDetermineAtom);
                 }
                 let values = prop.value32().ok_or(SelectionError::UnableToDetermineAtom)?;
@@ -1264,6 +1541,8 @@ impl X11Wm {
             incr: false,
             source_data: Vec::new(),
             incr_done: false,
+            transferred: 0,
+            expected: None,
         };
         selection.incoming.push(transfer);
 
@@ -1288,6 +1567,68 @@ impl X11Wm {
     }
 }
 
+/// Associate `wl_surface` with the X11 window of `xsurface`
+///
+/// Once associated the surface is kept until the window is unmapped,
+/// so [`X11Surface::wl_surface`] is stable for the lifetime of a mapping.
+pub(crate) fn associate_surface<D>(state: &mut D, xwm_id: XwmId, xsurface: X11Surface, wl_surface: WlSurface)
+where
+    D: XwmHandler + XWaylandShellHandler,
+{
+    {
+        let mut guard = xsurface.state.lock().unwrap();
+        match guard.wl_surface.as_ref() {
+            Some(current) if current == &wl_surface => return,
+            Some(current) if current.is_alive() => {
+                warn!(
+                    window = xsurface.window_id(),
+                    wl_surface = ?wl_surface.id().protocol_id(),
+                    current = ?current.id().protocol_id(),
+                    "X11 window is already associated to a wl_surface",
+                );
+                return;
+            }
+            _ => {}
+        }
+        guard.wl_surface = Some(wl_surface.clone());
+    }
+
+    XWaylandShellHandler::surface_associated(state, xwm_id, wl_surface, xsurface);
+}
+
+// Fallback for xwayland versions not supporting the xwayland shell
+fn associate_surface_id<D>(state: &mut D, xwm_id: XwmId, window: X11Window, id: u32)
+where
+    D: XwmHandler + XWaylandShellHandler,
+{
+    let xwm = state.xwm_state(xwm_id);
+    let Some(xsurface) = xwm
+        .windows
+        .iter()
+        .find(|x| x.window_id() == window && x.state.lock().unwrap().wl_surface_id == Some(id))
+        .cloned()
+    else {
+        // unmapped or destroyed in the meantime
+        return;
+    };
+    let client = xwm.client.clone();
+
+    let dh = XWaylandShellHandler::xwayland_shell_state(state).display_handle();
+    match client.object_from_protocol_id::<WlSurface>(dh, id) {
+        Ok(wl_surface) => {
+            debug!(
+                window,
+                wl_surface = id,
+                "associated X11 window to wl_surface by id",
+            );
+            associate_surface(state, xwm_id, xsurface, wl_surface);
+        }
+        Err(_) => {
+            warn!(window, wl_surface = id, "no matching wl_surface for X11 window");
+        }
+    }
+}
+
 fn handle_event<D>(
     loop_handle: &LoopHandle<'_, D>,
     state: &mut D,
@@ -1520,6 +1861,16 @@ where
                     X11SurfaceError::Connection(err) => err,
                     X11SurfaceError::UnsupportedForOverrideRedirect => unreachable!(),
                 })?;
+
+                // Games often resize their window to the emulated mode
+                let mask = u16::from(ConfigWindow::WIDTH) | u16::from(ConfigWindow::HEIGHT);
+                if u16::from(r.value_mask) & mask == mask {
+                    let size = Size::<i32, Client>::from((r.width as i32, r.height as i32))
+                        .to_logical(client_scale as i32);
+                    if let Some(emulated) = surface.update_emulated_fullscreen(Some(size)) {
+                        state.emulated_mode_request(xwm_id, surface, emulated);
+                    }
+                }
             }
         }
         Event::ConfigureNotify(n) => {
@@ -1602,11 +1953,16 @@ where
                         }
                     }
                 }
+                xwm.unpaired_surfaces.retain(|_, w| *w != n.window);
+                xwm.paired_surfaces.retain(|_, w| *w != n.window);
                 drop(_guard);
                 state.unmapped_window(xwm_id, surface.clone());
                 {
+                    // xwayland creates a new wl_surface, once the window gets mapped again
                     let mut state = surface.state.lock().unwrap();
                     state.wl_surface = None;
+                    state.wl_surface_serial = None;
+                    state.wl_surface_id = None;
                 }
             }
         }
@@ -1652,6 +2008,8 @@ where
             if let Some(pos) = xwm.windows.iter().position(|x| x.window_id() == n.window) {
                 let surface = xwm.windows.remove(pos);
                 surface.state.lock().unwrap().alive = false;
+                xwm.unpaired_surfaces.retain(|_, w| *w != n.window);
+                xwm.paired_surfaces.retain(|_, w| *w != n.window);
                 drop(_guard);
                 state.destroyed_window(xwm_id, surface);
             }
@@ -1706,7 +2064,7 @@ where
                         )?
                         .reply_unchecked()?
                     {
-                        if prop.type_ == AtomEnum::ATOM.into() {
+                        if prop.type_ == u32::from(AtomEnum::ATOM) {
                             if let Some(values) = prop.value32() {
                                 let mime_types = values
                                     .filter_map(|val| {
@@ -1755,13 +2113,20 @@ where
                         }
                     }
                 }
-                x if x == AtomEnum::NONE.into() => {
+                x if x == u32::from(AtomEnum::NONE) => {
                     // transfer failed
                     if let Some(pos) = selection.incoming.iter().position(|t| t.window == n.requestor) {
-                        selection.incoming.remove(pos).destroy(loop_handle);
+                        let transfer = selection.incoming.remove(pos);
+                        let progress = transfer.progress(SelectionTransferState::Aborted);
+                        transfer.destroy(loop_handle);
+
+                        let selection = selection.type_;
+                        drop(_guard);
+                        state.selection_transfer_progress(xwm_id, selection, progress);
                     }
                 }
                 _ => {
+                    let size_limit = xwm.selection_size_limit;
                     let Some(transfer) = selection.incoming.iter_mut().find(|t| t.window == n.requestor)
                     else {
                         return Ok(());
@@ -1778,18 +2143,50 @@ where
                         )?
                         .reply_unchecked()?
                     {
-                        let type_ = prop.type_;
-                        transfer.read_selection_prop(prop);
-                        if type_ == xwm.atoms.INCR {
+                        let transfer_state = if prop.type_ == xwm.atoms.INCR {
+                            // the value of an INCR property is a lower bound of the size, not data
                             transfer.incr = true;
-                            return Ok(());
-                        } else if let Some(token) = transfer.token.as_ref() {
-                            let _ = loop_handle.enable(token);
-                        } else if let Some(pos) =
-                            selection.incoming.iter().position(|t| t.window == n.requestor)
-                        {
-                            selection.incoming.remove(pos);
-                        }
+                            transfer.expected = prop
+                                .value32()
+                                .and_then(|mut values| values.next())
+                                .map(|size| size as usize);
+                            SelectionTransferState::Transferring
+                        } else {
+                            transfer.read_selection_prop(prop);
+                            SelectionTransferState::Finished
+                        };
+
+                        let progress = if let Some(limit) = size_limit.filter(|limit| {
+                            transfer.transferred > *limit
+                                || transfer.expected.is_some_and(|size| size > *limit)
+                        }) {
+                            warn!(
+                                window = transfer.window,
+                                limit, "Selection exceeds size limit, aborting transfer"
+                            );
+                            let progress = transfer.progress(SelectionTransferState::Aborted);
+                            if let Some(pos) = selection.incoming.iter().position(|t| t.window == n.requestor)
+                            {
+                                selection.incoming.remove(pos).destroy(loop_handle);
+                            }
+                            progress
+                        } else if transfer_state == SelectionTransferState::Finished {
+                            let progress = transfer.progress(transfer_state);
+                            if let Some(token) = transfer.token.as_ref() {
+                                let _ = loop_handle.enable(token);
+                            } else if let Some(pos) =
+                                selection.incoming.iter().position(|t| t.window == n.requestor)
+                            {
+                                selection.incoming.remove(pos);
+                            }
+                            progress
+                        } else {
+                            transfer.progress(transfer_state)
+                        };
+
+                        let selection = selection.type_;
+                        drop(_guard);
+                        state.selection_transfer_progress(xwm_id, selection, progress);
                     }
                 }
             }
@@ -1948,30 +2345,58 @@ where
                             Generic::new(recv_fd, Interest::READ, Mode::Level),
                             move |_, fd, data| {
                                 let xwm = data.xwm_state(xwm_id);
+                                let size_limit = xwm.selection_size_limit;
                                 let selection = match selection_type {
                                     SelectionTarget::Clipboard => &mut xwm.clipboard,
                                     SelectionTarget::Primary => &mut xwm.primary,
                                 };
 
-                                if let Some(transfer) = selection
+                                let Some(transfer) = selection
                                     .outgoing
                                     .iter_mut()
                                     .find(|t| t.request.requestor == requestor)
-                                {
-                                    match read_selection_callback(&xwm.conn, &xwm.atoms, fd.as_fd(), transfer)
+                                else {
+                                    return Ok(PostAction::Remove);
+                                };
+
+                                let (action, transfer_state) = match read_selection_callback(
+                                    &xwm.conn,
+                                    &xwm.atoms,
+                                    size_limit,
+                                    fd.as_fd(),
+                                    transfer,
+                                ) {
+                                    Ok(OutgoingAction::WaitForReadable)
+                                        if transfer.incr
+                                            && transfer.source_data.len() >= INCR_MAX_BUFFERED =>
                                     {
-                                        Ok(OutgoingAction::WaitForReadable) => {
-                                            return Ok(PostAction::Continue);
-                                        } // transfer ongoing
-                                        Ok(_) => {}
-                                        Err(err) => {
-                                            warn!(?err, "Transfer aborted");
-                                        }
-                                    };
+                                        // resumed once the requestor caught up
+                                        (PostAction::Disable, SelectionTransferState::Transferring)
+                                    }
+                                    Ok(OutgoingAction::WaitForReadable) => {
+                                        (PostAction::Continue, SelectionTransferState::Transferring)
+                                    } // transfer ongoing
+                                    Ok(OutgoingAction::Done | OutgoingAction::DoneReading) => {
+                                        (PostAction::Remove, SelectionTransferState::Finished)
+                                    }
+                                    Ok(OutgoingAction::Aborted) => {
+                                        (PostAction::Remove, SelectionTransferState::Aborted)
+                                    }
+                                    Err(err) => {
+                                        warn!(?err, "Transfer aborted");
+                                        (PostAction::Remove, SelectionTransferState::Aborted)
+                                    }
+                                };
+                                let progress = transfer.progress(transfer_state);
+                                if action == PostAction::Remove {
                                     let _ = transfer.token.take();
                                 }
+                                if transfer_state == SelectionTransferState::Aborted {
+                                    selection.outgoing.retain(|t| t.request.requestor != requestor);
+                                }
 
-                                Ok(PostAction::Remove)
+                                data.selection_transfer_progress(xwm_id, selection_type, progress);
+                                Ok(action)
                             },
                         );
 
@@ -2003,6 +2428,7 @@ where
                             property_set: false,
                             flush_property_on_delete: false,
                             sent_finished: false,
+                            transferred: 0,
                         };
                         selection.outgoing.push(transfer);
 
@@ -2018,6 +2444,8 @@ where
         }
         Event::PropertyNotify(n) => {
             if n.state == Property::NEW_VALUE && n.atom == xwm.atoms._WL_SELECTION {
+                let size_limit = xwm.selection_size_limit;
+                let mut progress = None;
                 if let Some(selection) = if xwm.clipboard.incoming.iter().any(|t| t.window == n.window) {
                     Some(&mut xwm.clipboard)
                 } else if xwm.primary.incoming.iter().any(|t| t.window == n.window) {
@@ -2044,6 +2472,10 @@ where
                         {
                             if prop.value_len == 0 {
                                 debug!(?transfer, "Incr Transfer complete!");
+                                progress = Some((
+                                    selection.type_,
+                                    transfer.progress(SelectionTransferState::Finished),
+                                ));
                                 if transfer.source_data.is_empty() {
                                     if let Some(pos) =
                                         selection.incoming.iter().position(|t| t.window == n.window)
@@ -2055,17 +2487,46 @@ where
                                 }
                             } else {
                                 transfer.read_selection_prop(prop);
-                                if let Some(token) = transfer.token.as_ref() {
-                                    let _ = loop_handle.enable(token);
-                                } else if let Some(pos) =
-                                    selection.incoming.iter().position(|t| t.window == n.window)
+                                if let Some(limit) = size_limit.filter(|limit| transfer.transferred > *limit)
                                 {
-                                    selection.incoming.remove(pos);
+                                    warn!(
+                                        window = transfer.window,
+                                        limit, "Selection exceeds size limit, aborting transfer"
+                                    );
+                                    progress = Some((
+                                        selection.type_,
+                                        transfer.progress(SelectionTransferState::Aborted),
+                                    ));
+                                    if let Some(pos) =
+                                        selection.incoming.iter().position(|t| t.window == n.window)
+                                    {
+                                        selection.incoming.remove(pos).destroy(loop_handle);
+                                    }
+                                } else {
+                                    progress = Some((
+                                        selection.type_,
+                                        transfer.progress(SelectionTransferState::Transferring),
+                                    ));
+                                    if let Some(token) = transfer.token.as_ref() {
+                                        let _ = loop_handle.enable(token);
+                                    } else if let Some(pos) =
+                                        selection.incoming.iter().position(|t| t.window == n.window)
+                                    {
+                                        selection.incoming.remove(pos);
+                                    }
                                 }
                             }
                         }
                     }
                 }
+
+                if let Some((selection, progress)) = progress {
+                    // transfer windows are not managed, so there is nothing else to update
+                    drop(_guard);
+                    state.selection_transfer_progress(xwm_id, selection, progress);
+                    conn.flush()?;
+                    return Ok(());
+                }
             }
 
             if n.state == Property::DELETE {
@@ -2095,11 +2556,17 @@ where
                     transfer.property_set = false;
                     if transfer.flush_property_on_delete {
                         transfer.flush_property_on_delete = false;
+                        let paused = transfer.source_data.len() >= INCR_MAX_BUFFERED;
                         let len = transfer.flush_data()?;
                         let requestor = transfer.request.requestor;
                         trace!(requestor, len, "Send data chunk");
 
-                        if transfer.token.is_none() {
+                        if let Some(token) = transfer.token.as_ref() {
+                            if paused && len < INCR_MAX_BUFFERED {
+                                // the requestor caught up, continue reading
+                                let _ = loop_handle.enable(token);
+                            }
+                        } else {
                             if len > 0 || !transfer.sent_finished {
                                 // Either the transfer is done, but we still have bytes left, or
                                 // all bytes have been transferred but the final 0-byte data chunk
@@ -2121,11 +2588,24 @@ where
             if let Some(surface) = xwm.windows.iter().find(|x| x.window_id() == n.window).cloned() {
                 if let Some(property) = surface.update_property(n.atom)? {
                     drop(_guard);
-                    state.property_notify(xwm_id, surface, property);
+                    state.property_notify(xwm_id, surface.clone(), property);
+                    if property == WmWindowProperty::EmulatedMonitors {
+                        if let Some(emulated) = surface.update_emulated_fullscreen(None) {
+                            state.emulated_mode_request(xwm_id, surface, emulated);
+                        }
+                    }
                 }
             }
         }
         Event::FocusIn(n) => {
+            if let Some(surface) = xwm
+                .windows
+                .iter()
+                .find(|w| w.window_id() == n.event || w.mapped_window_id() == Some(n.event))
+                .filter(|w| !w.is_override_redirect())
+            {
+                xwm.focused_window = Some(surface.window_id());
+            }
             conn.change_property32(
                 PropMode::REPLACE,
                 xwm.screen.root,
@@ -2165,12 +2645,15 @@ where
                         .find(|x| x.window_id() == msg.window || x.mapped_window_id() == Some(msg.window))
                     {
                         // This is the old, deprecated method for associating a
-                        // wl_surface with an X11 window. It can race with the
-                        // lifecycle of the wl_surface. Still, we can make it
-                        // available to compositors who want to support old
-                        // versions of xwayland by manually matching on ID in an
-                        // idle callback.
+                        // wl_surface with an X11 window, used if xwayland
+                        // didn't bind the xwayland shell. The wl_surface might
+                        // not be created yet, as xwayland can send the message
+                        // before the wayland requests were dispatched, so we
+                        // look it up in an idle callback.
                         surface.state.lock().unwrap().wl_surface_id = Some(wid);
+                        let window = surface.window_id();
+                        loop_handle
+                            .insert_idle(move |state| associate_surface_id(state, xwm_id, window, wid));
                     }
                 }
                 x if x == xwm.atoms.WL_SURFACE_SERIAL => {
@@ -2189,24 +2672,21 @@ where
                         let serial_hi = msg.data.as_data32()[1];
                         let serial = u64::from(serial_lo) | (u64::from(serial_hi) << 32);
 
-                        let surface_state = xsurface.state.clone();
-                        let mut guard = surface_state.lock().unwrap();
-                        guard.wl_surface_serial = Some(serial);
+                        xsurface.state.lock().unwrap().wl_surface_serial = Some(serial);
+                        let window = xsurface.window_id();
+                        xwm.unpaired_surfaces.retain(|_, w| *w != window);
+                        xwm.paired_surfaces.retain(|_, w| *w != window);
 
                         if let Some(wl_surface) =
-                            xwayland_shell::XWaylandShellHandler::xwayland_shell_state(state)
-                                .surface_for_serial(serial)
-                                .clone()
+                            XWaylandShellHandler::xwayland_shell_state(state).take_surface_for_serial(serial)
                         {
                             debug!(
-                                window = ?xsurface.window_id(),
+                                window = ?window,
                                 wl_surface = ?wl_surface.id().protocol_id(),
                                 "associated X11 window to wl_surface",
                             );
-
-                            guard.wl_surface = Some(wl_surface.clone());
-                            std::mem::drop(guard);
-                            XWaylandShellHandler::surface_associated(state, xwm_id, wl_surface, xsurface);
+                            state.xwm_state(xwm_id).paired_surfaces.insert(serial, window);
+                            associate_surface(state, xwm_id, xsurface, wl_surface);
                         } else {
                             debug!(
                                 window = ?msg.window,
@@ -2214,8 +2694,7 @@ where
                                 "no matching wl_surface for X11 window",
                             );
                             let xwm = state.xwm_state(xwm_id);
-                            xwm.unpaired_surfaces.insert(serial, xsurface.window_id());
-                            guard.wl_surface = None;
+                            xwm.unpaired_surfaces.insert(serial, window);
                         }
                     }
                 }
@@ -2278,6 +2757,8 @@ where
                                 }
                             }
                             actions if actions.contains(&xwm.atoms._NET_WM_STATE_FULLSCREEN) => {
+                                // the client takes over the fullscreen state from the mode emulation
+                                surface.set_fullscreen_from_emulation(false);
                                 match data[0] {
                                     0 => state.unfullscreen_request(xwm_id, surface),
                                     1 => state.fullscreen_request(xwm_id, surface),
@@ -2295,6 +2776,16 @@ where
                         }
                     }
                 }
+                x if x == xwm.atoms._NET_STARTUP_INFO_BEGIN || x == xwm.atoms._NET_STARTUP_INFO => {
+                    let begin = x == xwm.atoms._NET_STARTUP_INFO_BEGIN;
+                    if let Some(notification) =
+                        xwm.startup_messages
+                            .chunk(msg.window, begin, &msg.data.as_data8())
+                    {
+                        drop(_guard);
+                        state.startup_notification(xwm_id, notification);
+                    }
+                }
                 x if x == xwm.atoms._NET_WM_MOVERESIZE => {
                     if let Some(surface) = xwm.windows.iter().find(|x| x.window_id() == msg.window).cloned() {
                         drop(_guard);
@@ -2340,11 +2831,13 @@ enum OutgoingAction {
     Done,
     DoneReading,
     WaitForReadable,
+    Aborted,
 }
 
 fn read_selection_callback(
     conn: &RustConnection,
     atoms: &Atoms,
+    size_limit: Option<usize>,
     fd: BorrowedFd<'_>,
     transfer: &mut OutgoingTransfer,
 ) -> Result<OutgoingAction, ReplyOrIdError> {
@@ -2355,7 +2848,7 @@ fn read_selection_callback(
             "File descriptor closed, aborting transfer."
         );
         send_selection_notify_resp(conn, &transfer.request, false)?;
-        return Ok(OutgoingAction::Done);
+        return Ok(OutgoingAction::Aborted);
     };
     trace!(
         requestor = transfer.request.requestor,
@@ -2363,6 +2856,19 @@ fn read_selection_callback(
         len
     );
 
+    transfer.transferred += len;
+    if let Some(limit) = size_limit.filter(|limit| transfer.transferred > *limit) {
+        warn!(
+            requestor = transfer.request.requestor,
+            limit, "Selection exceeds size limit, aborting transfer"
+        );
+        // an incremental transfer can't be aborted, the requestor has to time out
+        if !transfer.incr {
+            send_selection_notify_resp(conn, &transfer.request, false)?;
+        }
+        return Ok(OutgoingAction::Aborted);
+    }
+
     transfer.source_data.extend_from_slice(&buf[..len]);
     if transfer.source_data.len() >= INCR_CHUNK_SIZE {
         if !transfer.incr {
@@ -0,0 +1,176 @@
+//! X11 startup notification
+//!
+//! Launchers announce applications started on X11 by broadcasting `_NET_STARTUP_INFO` messages
+//! to the root window, as described by the
+//! [startup notification specification](https://specifications.freedesktop.org/startup-notification-spec/latest/).
+//! The [`X11Wm`](super::X11Wm) reassembles these messages and reports them through
+//! [`XwmHandler::startup_notification`](super::XwmHandler::startup_notification).
+//!
+//! The startup id is passed to the launched application through the `DESKTOP_STARTUP_ID` environment
+//! variable and is available as [`X11Surface::startup_id`](super::X11Surface::startup_id) once it maps a window.
+
+use std::collections::HashMap;
+
+/// A startup notification message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupNotification {
+    /// A new launch was started
+    New {
+        /// Startup id of the launch
+        id: String,
+        /// Additional properties of the launch, like `NAME`, `BIN` or `WMCLASS`
+        properties: HashMap<String, String>,
+    },
+    /// Properties of a launch changed
+    Change {
+        /// Startup id of the launch
+        id: String,
+        /// Changed properties of the launch
+        properties: HashMap<String, String>,
+    },
+    /// A launch was completed or canceled
+    Remove {
+        /// Startup id of the launch
+        id: String,
+    },
+}
+
+impl StartupNotification {
+    /// Returns the startup id of the launch
+    pub fn id(&self) -> &str {
+        match self {
+            StartupNotification::New { id, .. }
+            | StartupNotification::Change { id, .. }
+            | StartupNotification::Remove { id } => id,
+        }
+    }
+
+    /// Parse a complete message
+    ///
+    /// Returns `None` for malformed messages, messages without an `ID` or of unknown type.
+    pub fn parse(message: &str) -> Option<Self> {
+        let (kind, rest) = message.split_once(':')?;
+        let mut properties = parse_properties(rest)?;
+        let id = properties.remove("ID")?;
+
+        match kind {
+            "new" => Some(StartupNotification::New { id, properties }),
+            "change" => Some(StartupNotification::Change { id, properties }),
+            "remove" => Some(StartupNotification::Remove { id }),
+            _ => None,
+        }
+    }
+}
+
+// Parses `KEY=VALUE` pairs, values may be quoted and use backslash escapes
+fn parse_properties(input: &str) -> Option<HashMap<String, String>> {
+    let mut properties = HashMap::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| *c == ' ').is_some() {}
+        if chars.peek().is_none() {
+            return Some(properties);
+        }
+
+        let mut key = String::new();
+        loop {
+            match chars.next()? {
+                '=' => break,
+                ' ' => return None,
+                c => key.push(c),
+            }
+        }
+
+        let mut value = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' => value.push(chars.next()?),
+                ' ' if !quoted => break,
+                c => value.push(c),
+            }
+        }
+        if quoted {
+            return None;
+        }
+
+        properties.insert(key, value);
+    }
+}
+
+// Reassembles messages split into the 20 byte chunks of client messages
+#[derive(Debug, Default)]
+pub(super) struct StartupMessages {
+    // partial messages by sending window
+    partial: HashMap<u32, Vec<u8>>,
+}
+
+impl StartupMessages {
+    pub(super) fn chunk(&mut self, window: u32, begin: bool, data: &[u8]) -> Option<StartupNotification> {
+        if begin {
+            self.partial.remove(&window);
+        }
+        let buffer = if begin {
+            self.partial.entry(window).or_default()
+        } else {
+            // continuation of an unknown message
+            self.partial.get_mut(&window)?
+        };
+
+        match data.iter().position(|b| *b == 0) {
+            Some(end) => {
+                buffer.extend_from_slice(&data[..end]);
+                let message = self.partial.remove(&window)?;
+                StartupNotification::parse(std::str::from_utf8(&message).ok()?)
+            }
+            None => {
+                buffer.extend_from_slice(data);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StartupMessages, StartupNotification};
+
+    #[test]
+    fn parse_message() {
+        let notification =
+            StartupNotification::parse(r#"new: ID=app-1_TIME42 NAME="Text \"Editor\"" SCREEN=0"#).unwrap();
+        let StartupNotification::New { id, properties } = notification else {
+            panic!("wrong message type");
+        };
+        assert_eq!(id, "app-1_TIME42");
+        assert_eq!(properties["NAME"], r#"Text "Editor""#);
+        assert_eq!(properties["SCREEN"], "0");
+
+        assert_eq!(
+            StartupNotification::parse("remove: ID=app-1_TIME42"),
+            Some(StartupNotification::Remove {
+                id: "app-1_TIME42".into()
+            })
+        );
+        assert_eq!(StartupNotification::parse("remove: NAME=foo"), None);
+        assert_eq!(StartupNotification::parse(r#"new: ID="unterminated"#), None);
+    }
+
+    #[test]
+    fn reassemble_chunks() {
+        let message = b"remove: ID=a-rather-long-startup-id\0";
+        let mut messages = StartupMessages::default();
+        let mut chunks = message.chunks(20);
+        assert_eq!(messages.chunk(1, true, chunks.next().unwrap()), None);
+        // unrelated continuation
+        assert_eq!(messages.chunk(2, false, b"foo\0"), None);
+        assert_eq!(
+            messages.chunk(1, false, chunks.next().unwrap()),
+            Some(StartupNotification::Remove {
+                id: "a-rather-long-startup-id".into()
+            })
+        );
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
         touch::TouchTarget,
         Seat, SeatHandler,
     },
-    utils::{user_data::UserDataMap, Client, IsAlive, Logical, Rectangle, Serial, Size},
+    utils::{user_data::UserDataMap, Client, IsAlive, Logical, Point, Rectangle, Serial, Size},
     wayland::compositor,
 };
 use encoding_rs::WINDOWS_1252;
@@ -77,6 +77,9 @@ pub(crate) struct SharedSurfaceState {
     net_state: HashSet<Atom>,
     motif_hints: Vec<u32>,
     window_type: Vec<Atom>,
+    emulated_monitors: Vec<Rectangle<i32, Client>>,
+    emulated_fullscreen: bool,
+    fullscreen_from_emulation: bool,
 }
 
 pub(super) type Protocols = Vec<WMProtocol>;
@@ -146,6 +149,7 @@ pub enum WmWindowProperty {
     MotifHints,
     StartupId,
     Pid,
+    EmulatedMonitors,
 }
 
 impl X11Surface {
@@ -192,6 +196,9 @@ impl X11Surface {
                 net_state: HashSet::new(),
                 motif_hints: vec![0; 5],
                 window_type: Vec::new(),
+                emulated_monitors: Vec::new(),
+                emulated_fullscreen: false,
+                fullscreen_from_emulation: false,
             })),
             user_data: Arc::new(UserDataMap::new()),
         }
@@ -316,6 +323,11 @@ impl X11Surface {
     ///   - The wl_surface has been assigned the same serial using the [xwayland
     ///     shell](crate::wayland::xwayland_shell) protocol on the wayland side,
     ///     and then committed.
+    ///
+    /// If xwayland doesn't support the xwayland shell, the surface is instead
+    /// looked up using the deprecated [`wl_surface_id`][Self::wl_surface_id].
+    ///
+    /// Once set, the surface doesn't change until the window is unmapped.
     #[inline]
     pub fn wl_surface(&self) -> Option<WlSurface> {
         self.state.lock().unwrap().wl_surface.clone()
@@ -326,7 +338,8 @@ impl X11Surface {
     ///
     /// Note that XWayland will only set this if it was unable to bind the
     /// [xwayland shell](crate::wayland::xwayland_shell) protocol on the wayland
-    /// side.
+    /// side. The matching surface is associated automatically and returned by
+    /// [`wl_surface`][Self::wl_surface].
     #[deprecated = "Since XWayland 23.1, the recommended approach is to use [wl_surface_serial] and the [xwayland shell](crate::wayland::xwayland_shell) protocol on the wayland side to match X11 windows."]
     pub fn wl_surface_id(&self) -> Option<u32> {
         self.state.lock().unwrap().wl_surface_id
@@ -458,6 +471,60 @@ impl X11Surface {
             .contains(&self.atoms._NET_WM_STATE_FULLSCREEN)
     }
 
+    /// Returns if the window expects to receive the keyboard focus
+    ///
+    /// This is not the case for windows not accepting input and for override-redirect menus, tooltips
+    /// and notifications, whose input is handled by the window they belong to. Other override-redirect
+    /// windows, like the main windows of some games, expect to be focused.
+    /// See [`X11Wm::keyboard_focus_for`](super::X11Wm::keyboard_focus_for).
+    pub fn wants_keyboard_focus(&self) -> bool {
+        if self.override_redirect {
+            !matches!(
+                self.window_type(),
+                Some(
+                    WmWindowType::DropdownMenu
+                        | WmWindowType::Menu
+                        | WmWindowType::PopupMenu
+                        | WmWindowType::Tooltip
+                        | WmWindowType::Notification
+                )
+            )
+        } else {
+            self.input_mode() != InputMode::None
+        }
+    }
+
+    /// Returns the monitor areas for which Xwayland emulates a video mode change of this window
+    ///
+    /// Legacy X11 clients, like fullscreen games, often change the resolution using RandR or XF86VidMode.
+    /// Xwayland does not change the mode of the output, but pretends towards the client that the monitor
+    /// uses the requested mode. Windows of the size of an emulated monitor are scaled up by Xwayland to the
+    /// real size of the output, once they are made fullscreen.
+    ///
+    /// The areas are reported in the coordinate space of the X11 client, scaled to logical coordinates.
+    /// An empty list means no mode is emulated for this window.
+    pub fn emulated_monitors(&self) -> Vec<Rectangle<i32, Logical>> {
+        let client_scale = self
+            .client_scale
+            .as_ref()
+            .map(|s| s.load(Ordering::Acquire))
+            .unwrap_or(1);
+        self.state
+            .lock()
+            .unwrap()
+            .emulated_monitors
+            .iter()
+            .map(|rect| rect.to_logical(client_scale as i32))
+            .collect()
+    }
+
+    /// Returns if the window matches the size of an [emulated monitor](X11Surface::emulated_monitors)
+    ///
+    /// Such windows should be made fullscreen, see [`XwmHandler::emulated_mode_request`](super::XwmHandler::emulated_mode_request).
+    pub fn is_emulated_fullscreen(&self) -> bool {
+        self.state.lock().unwrap().emulated_fullscreen
+    }
+
     /// Returns if the window is in the minimized state
     pub fn is_minimized(&self) -> bool {
         self.state
@@ -628,16 +695,17 @@ impl X11Surface {
         self.update_motif_hints()?;
         self.update_startup_id()?;
         self.update_pid()?;
+        self.update_emulated_monitors()?;
         Ok(())
     }
 
     pub(super) fn update_property(&self, atom: Atom) -> Result<Option<WmWindowProperty>, ConnectionError> {
         match atom {
-            atom if atom == self.atoms._NET_WM_NAME || atom == AtomEnum::WM_NAME.into() => {
+            atom if atom == self.atoms._NET_WM_NAME || atom == u32::from(AtomEnum::WM_NAME) => {
                 self.update_title()?;
                 Ok(Some(WmWindowProperty::Title))
             }
-            atom if atom == AtomEnum::WM_CLASS.into() => {
+            atom if atom == u32::from(AtomEnum::WM_CLASS) => {
                 self.update_class()?;
                 Ok(Some(WmWindowProperty::Class))
             }
@@ -649,11 +717,11 @@ impl X11Surface {
                 self.update_hints()?;
                 Ok(Some(WmWindowProperty::Hints))
             }
-            atom if atom == AtomEnum::WM_NORMAL_HINTS.into() => {
+            atom if atom == u32::from(AtomEnum::WM_NORMAL_HINTS) => {
                 self.update_normal_hints()?;
                 Ok(Some(WmWindowProperty::NormalHints))
             }
-            atom if atom == AtomEnum::WM_TRANSIENT_FOR.into() => {
+            atom if atom == u32::from(AtomEnum::WM_TRANSIENT_FOR) => {
                 self.update_transient_for()?;
                 Ok(Some(WmWindowProperty::TransientFor))
             }
@@ -673,6 +741,10 @@ impl X11Surface {
                 self.update_pid()?;
                 Ok(Some(WmWindowProperty::Pid))
             }
+            atom if atom == self.atoms._XWAYLAND_RANDR_EMU_MONITOR_RECTS => {
+                self.update_emulated_monitors()?;
+                Ok(Some(WmWindowProperty::EmulatedMonitors))
+            }
 
             _ => Ok(None), // unknown
         }
@@ -858,6 +930,63 @@ impl X11Surface {
         Ok(())
     }
 
+    fn update_emulated_monitors(&self) -> Result<(), ConnectionError> {
+        let conn = self.conn.upgrade().ok_or(ConnectionError::UnknownError)?;
+        let values = match conn
+            .get_property(
+                false,
+                self.window,
+                self.atoms._XWAYLAND_RANDR_EMU_MONITOR_RECTS,
+                AtomEnum::CARDINAL,
+                0,
+                2048,
+            )?
+            .reply_unchecked()
+        {
+            Ok(Some(reply)) => reply.value32().map(|vals| vals.collect::<Vec<_>>()),
+            Ok(None) | Err(ConnectionError::ParseError(_)) => None,
+            Err(err) => return Err(err),
+        };
+
+        // The property is a list of x, y, width, height quadruples
+        let mut state = self.state.lock().unwrap();
+        state.emulated_monitors = values
+            .unwrap_or_default()
+            .chunks_exact(4)
+            .map(|rect| {
+                Rectangle::new(
+                    (rect[0] as i32, rect[1] as i32).into(),
+                    (rect[2] as i32, rect[3] as i32).into(),
+                )
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Update if the window (with the given size) matches an emulated monitor
+    ///
+    /// Returns the new state, if it changed.
+    pub(super) fn update_emulated_fullscreen(&self, size: Option<Size<i32, Logical>>) -> Option<bool> {
+        let monitors = self.emulated_monitors();
+        let mut state = self.state.lock().unwrap();
+        let size = size.unwrap_or(state.geometry.size);
+        let emulated = monitors.iter().any(|monitor| monitor.size == size);
+        if emulated == state.emulated_fullscreen {
+            return None;
+        }
+        state.emulated_fullscreen = emulated;
+        Some(emulated)
+    }
+
+    /// Remember if the window was made fullscreen because of an emulated mode,
+    /// see the default implementation of [`XwmHandler::emulated_mode_request`](super::XwmHandler::emulated_mode_request)
+    pub(super) fn set_fullscreen_from_emulation(&self, from_emulation: bool) -> bool {
+        std::mem::replace(
+            &mut self.state.lock().unwrap().fullscreen_from_emulation,
+            from_emulation,
+        )
+    }
+
     fn read_window_property_string(&self, atom: impl Into<Atom>) -> Result<Option<String>, ConnectionError> {
         let conn = self.conn.upgrade().ok_or(ConnectionError::UnknownError)?;
         let reply = match conn
@@ -874,7 +1003,7 @@ impl X11Surface {
         let bytes = bytes.collect::<Vec<u8>>();
 
         match reply.type_ {
-            x if x == AtomEnum::STRING.into() => Ok(Some(WINDOWS_1252.decode(&bytes).0.to_string())),
+            x if x == u32::from(AtomEnum::STRING) => Ok(Some(WINDOWS_1252.decode(&bytes).0.to_string())),
             x if x == self.atoms.UTF8_STRING => Ok(String::from_utf8(bytes).ok()),
             _ => Ok(None),
         }
@@ -926,6 +1055,46 @@ impl X11Surface {
     }
 }
 
+/// An X11 window together with its transient child windows
+///
+/// Some X11 clients place parts of their content, like video areas, into child windows declaring
+/// the window as their parent using `WM_TRANSIENT_FOR`. Rendering them as part of their parent keeps
+/// them in sync with the parent, e.g. when it is moved or stacked below other elements, instead of
+/// leaving black rectangles where their content should be.
+///
+/// Can be created using [`X11Wm::surface_tree`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct X11SurfaceTree {
+    pub(super) root: X11Surface,
+    pub(super) children: Vec<X11Surface>,
+}
+
+impl X11SurfaceTree {
+    /// Returns the window at the root of this tree
+    pub fn root(&self) -> &X11Surface {
+        &self.root
+    }
+
+    /// Returns the transient child windows of this tree ordered from bottom to top
+    pub fn children(&self) -> &[X11Surface] {
+        &self.children
+    }
+
+    /// Returns the offset of a child window relative to the root window
+    pub fn child_offset(&self, child: &X11Surface) -> Point<i32, Logical> {
+        child.geometry().loc - self.root.geometry().loc
+    }
+
+    /// Returns the bounding box of all windows of this tree relative to the root window
+    pub fn bbox(&self) -> Rectangle<i32, Logical> {
+        self.children
+            .iter()
+            .fold(Rectangle::from_size(self.root.geometry().size), |bbox, child| {
+                bbox.merge(Rectangle::new(self.child_offset(child), child.geometry().size))
+            })
+    }
+}
+
 /// Trait for objects, that represent an x11 window in some shape or form
 /// and can be tested for equality.
 pub trait X11Relatable {
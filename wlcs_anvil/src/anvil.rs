@@ -0,0 +1,318 @@
+use std::{
+    os::unix::net::UnixStream,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
+
+use smithay::{
+    backend::{
+        input::ButtonState,
+        renderer::{damage::OutputDamageTracker, element::AsRenderElements, test::DummyRenderer},
+    },
+    desktop::space::OutputRenderElementsBuilder,
+    input::pointer::{
+        ButtonEvent, CursorImageAttributes, CursorImageStatus, MotionEvent, RelativeMotionEvent,
+    },
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{
+        calloop::LoopHandle,
+        wayland_server::{protocol::wl_surface, Client, Display, DisplayHandle},
+    },
+    utils::{IsAlive, Logical, Point, Scale, SERIAL_COUNTER as SCOUNTER},
+    wayland::compositor,
+};
+use wlcs::{
+    extension_list,
+    ffi_display_server_api::{WlcsExtensionDescriptor, WlcsIntegrationDescriptor},
+};
+
+use anvil::{drawing::PointerElement, render::*, state::Backend, AnvilState, ClientState};
+
+use smithay_wlcs::WlcsCompositor;
+
+const OUTPUT_NAME: &str = "anvil";
+
+static SUPPORTED_EXTENSIONS: &[WlcsExtensionDescriptor] = extension_list!(
+    ("wl_compositor", 4),
+    ("wl_subcompositor", 1),
+    ("wl_data_device_manager", 3),
+    ("wl_seat", 7),
+    ("wl_output", 4),
+    ("xdg_wm_base", 3),
+);
+
+static DESCRIPTOR: WlcsIntegrationDescriptor = WlcsIntegrationDescriptor {
+    version: 1,
+    num_extensions: SUPPORTED_EXTENSIONS.len(),
+    supported_extensions: SUPPORTED_EXTENSIONS.as_ptr(),
+};
+
+/// [`WlcsServerHandle`](smithay_wlcs::WlcsServerHandle) running anvil
+pub type AnvilDisplayServerHandle = smithay_wlcs::WlcsServerHandle<AnvilWlcs>;
+
+/// [`WlcsCompositor`] running anvil with the [`TestState`] backend
+pub struct AnvilWlcs;
+
+/// Backend of anvil when running under WLCS
+pub struct TestState {
+    renderer: DummyRenderer,
+    damage_tracker: OutputDamageTracker,
+    pointer_element: PointerElement,
+    output: Output,
+}
+
+impl Backend for TestState {
+    fn seat_name(&self) -> String {
+        "anvil_wlcs".into()
+    }
+
+    fn reset_buffers(&mut self, _output: &Output) {}
+    fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
+    fn update_led_state(&mut self, _led_state: smithay::input::keyboard::LedState) {}
+}
+
+impl WlcsCompositor for AnvilWlcs {
+    type State = AnvilState<TestState>;
+
+    fn descriptor() -> &'static WlcsIntegrationDescriptor {
+        &DESCRIPTOR
+    }
+
+    fn spawn(handle: LoopHandle<'static, Self::State>) -> Self::State {
+        let display = Display::new().expect("Failed to init display");
+
+        let mode = Mode {
+            size: (800, 600).into(),
+            refresh: 60_000,
+        };
+
+        let output = Output::new(
+            OUTPUT_NAME.to_string(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "WLCS".into(),
+            },
+        );
+        output.change_current_state(Some(mode), None, None, Some((0, 0).into()));
+        output.set_preferred(mode);
+
+        let test_state = TestState {
+            renderer: DummyRenderer::new(),
+            damage_tracker: OutputDamageTracker::from_output(&output),
+            pointer_element: PointerElement::default(),
+            output: output.clone(),
+        };
+        let mut state = AnvilState::init(display, handle, test_state, false);
+
+        let _global = output.create_global::<AnvilState<TestState>>(&state.display_handle);
+        state.space.map_output(&output, (0, 0));
+
+        state
+    }
+
+    fn display_handle(state: &Self::State) -> DisplayHandle {
+        state.display_handle.clone()
+    }
+
+    fn running(state: &Self::State) -> bool {
+        state.running.load(Ordering::SeqCst)
+    }
+
+    fn stop(state: &mut Self::State) {
+        state.running.store(false, Ordering::SeqCst);
+    }
+
+    fn new_client(state: &mut Self::State, stream: UnixStream) -> Client {
+        state
+            .display_handle
+            .insert_client(stream, Arc::new(ClientState::default()))
+            .expect("Failed to insert client")
+    }
+
+    fn position_window(
+        state: &mut Self::State,
+        surface: &wl_surface::WlSurface,
+        location: Point<i32, Logical>,
+    ) {
+        let toplevel = state
+            .space
+            .elements()
+            .find(|w| w.wl_surface().as_deref() == Some(surface));
+        if let Some(toplevel) = toplevel.cloned() {
+            // set its location
+            state.space.map_element(toplevel, location, false);
+        }
+    }
+
+    fn pointer_move_absolute(state: &mut Self::State, _device_id: u32, location: Point<f64, Logical>) {
+        let serial = SCOUNTER.next_serial();
+        let under = state.surface_under(location);
+        let time = Duration::from(state.clock.now()).as_millis() as u32;
+        let ptr = state.pointer.clone();
+        ptr.motion(
+            state,
+            under,
+            &MotionEvent {
+                location,
+                serial,
+                time,
+            },
+        );
+        ptr.frame(state);
+    }
+
+    fn pointer_move_relative(state: &mut Self::State, _device_id: u32, delta: Point<f64, Logical>) {
+        let pointer_location = state.pointer.current_location() + delta;
+        let serial = SCOUNTER.next_serial();
+        let under = state.surface_under(pointer_location);
+        let time = Duration::from(state.clock.now()).as_millis() as u32;
+        let utime = Duration::from(state.clock.now()).as_micros() as u64;
+        let ptr = state.pointer.clone();
+        ptr.motion(
+            state,
+            under.clone(),
+            &MotionEvent {
+                location: pointer_location,
+                serial,
+                time,
+            },
+        );
+        ptr.relative_motion(
+            state,
+            under,
+            &RelativeMotionEvent {
+                delta,
+                delta_unaccel: delta,
+                utime,
+            },
+        );
+        ptr.frame(state);
+    }
+
+    fn pointer_button(state: &mut Self::State, _device_id: u32, button: u32, button_state: ButtonState) {
+        let serial = SCOUNTER.next_serial();
+        let ptr = state.seat.get_pointer().unwrap();
+        if button_state == ButtonState::Pressed && !ptr.is_grabbed() {
+            let under = state
+                .space
+                .element_under(ptr.current_location())
+                .map(|(w, _)| w.clone());
+            if let Some(window) = under.as_ref() {
+                state.space.raise_element(window, true);
+            }
+            state
+                .seat
+                .get_keyboard()
+                .unwrap()
+                .set_focus(state, under.map(Into::into), serial);
+        }
+        let time = Duration::from(state.clock.now()).as_millis() as u32;
+        ptr.button(
+            state,
+            &ButtonEvent {
+                button,
+                state: button_state,
+                serial,
+                time,
+            },
+        );
+        ptr.frame(state);
+    }
+
+    fn frame(state: &mut Self::State) {
+        let output = state.backend_data.output.clone();
+
+        // pretend to draw something
+        {
+            let scale = Scale::from(output.current_scale().fractional_scale());
+            let mut elements = OutputRenderElementsBuilder::default();
+
+            // draw the cursor as relevant
+            // reset the cursor if the surface is no longer alive
+            let mut reset = false;
+            if let CursorImageStatus::Surface(ref surface) = state.cursor_status {
+                reset = !surface.alive();
+            }
+            if reset {
+                state.cursor_status = CursorImageStatus::default_named();
+            }
+
+            let cursor_hotspot = if let CursorImageStatus::Surface(ref surface) = state.cursor_status {
+                compositor::with_states(surface, |states| {
+                    states
+                        .data_map
+                        .get::<Mutex<CursorImageAttributes>>()
+                        .unwrap()
+                        .lock()
+                        .unwrap()
+                        .hotspot
+                })
+            } else {
+                (0, 0).into()
+            };
+            let cursor_pos = state.pointer.current_location();
+
+            let TestState {
+                renderer,
+                damage_tracker,
+                pointer_element,
+                ..
+            } = &mut state.backend_data;
+
+            pointer_element.set_status(state.cursor_status.clone());
+            elements.cursor(
+                pointer_element.render_elements::<CustomRenderElements<DummyRenderer>>(
+                    renderer,
+                    (cursor_pos - cursor_hotspot.to_f64())
+                        .to_physical(scale)
+                        .to_i32_round(),
+                    scale,
+                    1.0,
+                ),
+            );
+
+            // draw the dnd icon if any
+            if let Some(icon) = state.dnd_icon.as_ref() {
+                if icon.surface.alive() {
+                    let dnd_icon_pos = (cursor_pos + icon.offset.to_f64())
+                        .to_physical(scale)
+                        .to_i32_round();
+                    elements.dnd_icon(AsRenderElements::<DummyRenderer>::render_elements::<
+                        CustomRenderElements<DummyRenderer>,
+                    >(
+                        &smithay::desktop::space::SurfaceTree::from_surface(&icon.surface),
+                        renderer,
+                        dnd_icon_pos,
+                        scale,
+                        1.0,
+                    ));
+                }
+            }
+
+            let _ = render_output(
+                &output,
+                &state.space,
+                elements,
+                renderer,
+                damage_tracker,
+                0,
+                false,
+            );
+        }
+
+        // Send frame events so that client start drawing their next frame
+        state.space.elements().for_each(|window| {
+            window.send_frame(&output, state.clock.now(), Some(Duration::ZERO), |_, _| {
+                Some(output.clone())
+            })
+        });
+    }
+
+    fn refresh(state: &mut Self::State) {
+        state.space.refresh();
+        state.popups.cleanup();
+        state.display_handle.flush_clients().unwrap();
+    }
+}